@@ -0,0 +1,1945 @@
+//! Channel agent — the bridge between [`ChannelManager`] and the LLM.
+//!
+//! Each `(channel, thread_id)` gets its own conversation, so two Telegram
+//! chats never see each other's history. An incoming message is turned into
+//! a prompt (persona system prompt + thread history + the new turn), sent to
+//! the configured provider — the local `BrainEngine` or a remote API — and
+//! the answer comes back as an [`OutgoingMessage`] for the same thread.
+//!
+//! With a [`HistoryStore`] attached, turns are persisted and a thread's
+//! context is rebuilt from the store the first time it is seen after a
+//! restart. Sending `/reset`, `/new` or "new chat" clears the thread; with a
+//! session TTL, a thread left idle for longer starts over by itself, so
+//! days-old context doesn't leak into new questions.
+//!
+//! Threads that outgrow the model's context window are compressed: older
+//! turns are summarized by the model into a rolling summary block (see
+//! [`crate::compression`]) rather than silently dropped.
+//!
+//! With a [`RagStore`] attached, the chunks most relevant to each message
+//! are retrieved and placed before it in the prompt, so answers come from
+//! the business's own documents.
+//!
+//! With a [`ToolRegistry`] attached, the model may call tools before it
+//! answers (see [`crate::tool_loop`]). Only the question and the final
+//! answer are kept in history, not the intermediate calls.
+//!
+//! Replies follow the language of each message (see [`crate::language`]),
+//! unless the sender or the persona pinned one; the bot's own texts, such
+//! as command replies and errors, are localized the same way.
+//!
+//! With a [`ProfileStore`] attached, users can tell the bot how to address
+//! them, which language to reply in and what to remember (see
+//! [`crate::profile`]); the sender's profile is placed before each of their
+//! messages in the prompt.
+//!
+//! With a [`FactMemory`] attached, durable facts about each user are
+//! extracted from their conversations in the background and the relevant
+//! ones recalled before their messages (see [`crate::facts`]).
+//!
+//! Threads assigned a persona (see [`crate::persona`]) use its prompt,
+//! sampling preset and tool list instead of the defaults. `/persona <name>`
+//! switches the sender's thread until the agent restarts. With
+//! `[[routing.intents]]`, threads follow the intent of each message instead
+//! (see [`crate::intent`]).
+//!
+//! The system prompt and scheduled-post instruction are rendered from
+//! prompt templates (see [`crate::template`]), so operators can reword them,
+//! per persona too, without code changes.
+//!
+//! Replies are checked against the channel's output policy from
+//! `[guardrails]` — length, banned phrases, language — and generated again
+//! when they break it (see [`crate::guardrails`]).
+//!
+//! Personas, templates, guardrails, the sampling defaults and the language
+//! policy can be replaced while running with [`ChannelAgent::reload`], e.g.
+//! on a config file edit.
+//!
+//! With an [`EventBus`] attached, every generation and tool call is
+//! published as an [`Event`].
+//!
+//! With a [`UsageTracker`] attached, every generation's tokens and estimated
+//! cost are recorded per provider, model and sender; senders over a monthly
+//! budget are answered by the local fallback route (see [`crate::usage`]).
+//!
+//! With an [`AnalyticsStore`] attached, every answered message is recorded
+//! with its latency, tokens, persona and tools, and a message made only of
+//! reaction emoji (👍, 👎) is taken as feedback on the reply before it
+//! rather than answered.
+//!
+//! [`ChannelManager`]: bizclaw_channels::manager::ChannelManager
+
+use crate::compression::{self, CompressionPolicy};
+use crate::context::ConversationContext;
+use crate::engine;
+use crate::facts::FactMemory;
+use crate::guardrails::{self, Guardrails, OutputPolicy};
+use crate::intent::IntentRouter;
+use crate::language::{Lang, LanguagePolicy};
+use crate::persona::{PERSONA_COMMAND, Persona, PersonaSet};
+use crate::profile::{self, ProfileUpdate};
+use crate::template::{self, PromptTemplates};
+use crate::tool_loop;
+use crate::usage::{UsageTracker, is_usage_command};
+use async_trait::async_trait;
+use bizclaw_channels::manager::{MessageHandler, ProgressReporter};
+use bizclaw_core::config::BizClawConfig;
+use bizclaw_core::error::Result;
+use bizclaw_core::events::{Event, EventBus};
+use bizclaw_core::traits::Provider;
+use bizclaw_core::traits::identity::Identity;
+use bizclaw_core::traits::provider::GenerateParams;
+use bizclaw_core::types::{
+    IncomingMessage, Message, OutgoingMessage, ProgressEvent, Role, ThreadType, Usage,
+};
+use bizclaw_knowledge::rag::{self, RagStore};
+use bizclaw_memory::analytics::{self, AnalyticsStore, MessageRecord};
+use bizclaw_memory::facts::FactStore;
+use bizclaw_memory::history::{HistoryStore, RetentionPolicy};
+use bizclaw_memory::profile::{ProfileStore, UserProfile};
+use bizclaw_memory::usage::UsageStore;
+use bizclaw_tools::ToolRegistry;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Messages kept per thread, system prompt included.
+pub const DEFAULT_MAX_HISTORY: usize = 40;
+
+/// Context window assumed for remote providers.
+const REMOTE_CONTEXT_WINDOW: usize = 128_000;
+
+/// Command that clears the sender's thread.
+pub const RESET_COMMAND: &str = "/reset";
+
+/// Other messages that clear the thread, matched case-insensitively.
+const NEW_CHAT_PHRASES: &[&str] = &[
+    "/new",
+    "new chat",
+    "new conversation",
+    "start over",
+    "cuộc trò chuyện mới",
+    "bắt đầu lại",
+];
+
+type Session = Arc<tokio::sync::Mutex<ConversationContext>>;
+
+struct SessionEntry {
+    context: Session,
+    last_active: Instant,
+}
+
+/// Answers channel messages with per-thread conversation history.
+pub struct ChannelAgent {
+    provider: Arc<dyn Provider>,
+    /// The bot's name, for templates.
+    name: String,
+    system_prompt: String,
+    settings: RwLock<Arc<Settings>>,
+    max_history: usize,
+    compression: CompressionPolicy,
+    sessions: Mutex<HashMap<String, SessionEntry>>,
+    /// Idle time after which a thread starts over.
+    session_ttl: Option<Duration>,
+    history: Option<Arc<HistoryStore>>,
+    profiles: Option<Arc<ProfileStore>>,
+    facts: Option<Arc<FactMemory>>,
+    rag: Option<Retrieval>,
+    tools: Option<Tools>,
+    /// Session key → persona chosen with `/persona`.
+    persona_overrides: Mutex<HashMap<String, String>>,
+    /// Session key → persona of the last intent routed to.
+    routed: Mutex<HashMap<String, String>>,
+    events: Option<EventBus>,
+    usage: Option<Arc<UsageTracker>>,
+    analytics: Option<Arc<AnalyticsStore>>,
+}
+
+/// Settings [`ChannelAgent::reload`] replaces. Each message reads one
+/// snapshot, so a reload never mixes old and new values in a reply.
+#[derive(Clone, Default)]
+struct Settings {
+    params: GenerateParams,
+    personas: PersonaSet,
+    languages: LanguagePolicy,
+    templates: PromptTemplates,
+    router: IntentRouter,
+    guardrails: Guardrails,
+}
+
+impl Settings {
+    fn from_config(config: &BizClawConfig, model: String) -> Self {
+        let params = GenerateParams {
+            model,
+            temperature: config.default_temperature,
+            max_tokens: config.brain.max_tokens,
+            ..Default::default()
+        };
+        Self {
+            personas: PersonaSet::from_config(&config.personas, &config.identity, &params),
+            languages: LanguagePolicy::from_config(&config.language),
+            templates: PromptTemplates::from_config(&config.templates),
+            router: IntentRouter::from_config(config),
+            guardrails: Guardrails::from_config(&config.guardrails),
+            params,
+        }
+    }
+}
+
+/// A generated answer and what producing it took.
+struct Generation {
+    answer: String,
+    model: String,
+    usage: Usage,
+    /// Tools run, in order.
+    tools: Vec<String>,
+}
+
+struct Retrieval {
+    store: Arc<RagStore>,
+    top_k: usize,
+    min_score: f32,
+}
+
+struct Tools {
+    registry: Arc<ToolRegistry>,
+    max_rounds: usize,
+}
+
+impl ChannelAgent {
+    pub fn new(provider: Arc<dyn Provider>, identity: &Identity) -> Self {
+        Self {
+            provider,
+            name: identity.name.clone(),
+            system_prompt: persona_prompt(identity),
+            settings: RwLock::new(Arc::new(Settings::default())),
+            max_history: DEFAULT_MAX_HISTORY,
+            compression: CompressionPolicy::default(),
+            sessions: Mutex::new(HashMap::new()),
+            session_ttl: None,
+            history: None,
+            profiles: None,
+            facts: None,
+            rag: None,
+            tools: None,
+            persona_overrides: Mutex::new(HashMap::new()),
+            routed: Mutex::new(HashMap::new()),
+            events: None,
+            usage: None,
+            analytics: None,
+        }
+    }
+
+    /// Build from config: provider from `[LLM]`, persona from `[identity]`,
+    /// history in `~/.bizclaw/history.db` with `[memory]` retention,
+    /// compression sized to `brain.context_length` for the local brain,
+    /// retrieval from `~/.bizclaw/rag.db` when `[rag]` is enabled, the
+    /// built-in tools listed in `[tools] enabled` plus the `[plugins]` tools,
+    /// `[[personas]]`, `[language]`, the `[memory] session_ttl_minutes` idle
+    /// timeout, user profiles in `~/.bizclaw/profiles.db`, with
+    /// `facts_enabled` long-term facts in `~/.bizclaw/facts.db`, with
+    /// `[usage]` enabled, usage totals in `~/.bizclaw/usage.db` and, with
+    /// `[analytics]` enabled, message analytics in `~/.bizclaw/analytics.db`.
+    pub fn from_config(config: &BizClawConfig) -> Result<Self> {
+        let provider: Arc<dyn Provider> = bizclaw_providers::create_provider(config)?.into();
+        let context_window = if provider.name() == "brain" {
+            config.brain.context_length as usize
+        } else {
+            REMOTE_CONTEXT_WINDOW
+        };
+        let mut agent = Self::new(provider.clone(), &config.identity).with_compression(
+            CompressionPolicy::for_window(context_window, config.brain.max_tokens as usize),
+        );
+        *agent.settings_mut() = Settings::from_config(config, config.default_model.clone());
+        if config.memory.session_ttl_minutes > 0 {
+            agent =
+                agent.with_session_ttl(Duration::from_secs(config.memory.session_ttl_minutes * 60));
+        }
+
+        if config.rag.enabled {
+            match RagStore::open(&RagStore::default_path(), provider) {
+                Ok(store) => {
+                    let store = store.with_chunk_size(config.rag.chunk_chars);
+                    agent = agent.with_rag(Arc::new(store), config.rag.top_k, config.rag.min_score);
+                }
+                Err(e) => tracing::warn!("Document retrieval disabled: {e}"),
+            }
+        }
+
+        let plugin_tools = if config.plugins.enabled {
+            bizclaw_plugins::load_tools(&config.plugins)
+        } else {
+            Vec::new()
+        };
+        if !config.tools.enabled.is_empty() || !plugin_tools.is_empty() {
+            let enabled = &config.tools.enabled;
+            let mut registry = ToolRegistry::with_config(&config.tools);
+            registry.retain(|name| enabled.iter().any(|e| e == name));
+            for name in enabled {
+                if registry.get(name).is_none() {
+                    tracing::warn!("Unknown tool in [tools] enabled: {name}");
+                }
+            }
+            for tool in plugin_tools {
+                registry.register(tool);
+            }
+            agent = agent.with_tools(Arc::new(registry), config.tools.max_rounds);
+        }
+
+        match ProfileStore::open(ProfileStore::default_path()) {
+            Ok(store) => agent = agent.with_profiles(Arc::new(store)),
+            Err(e) => tracing::warn!("User profiles disabled: {e}"),
+        }
+
+        if config.memory.facts_enabled {
+            match FactStore::open(FactStore::default_path()) {
+                Ok(store) => {
+                    let store = store.with_max_per_user(config.memory.facts_max_per_user);
+                    let memory =
+                        FactMemory::from_config(&config.memory, Arc::new(store), agent.provider());
+                    agent = agent.with_facts(Arc::new(memory));
+                }
+                Err(e) => tracing::warn!("Long-term memory disabled: {e}"),
+            }
+        }
+
+        if config.usage.enabled {
+            match UsageStore::open(UsageStore::default_path()) {
+                Ok(store) => {
+                    let tracker = UsageTracker::from_config(config, Arc::new(store));
+                    agent = agent.with_usage(Arc::new(tracker));
+                }
+                Err(e) => tracing::warn!("Usage tracking disabled: {e}"),
+            }
+        }
+
+        if config.analytics.enabled {
+            match AnalyticsStore::open(AnalyticsStore::default_path()) {
+                Ok(store) => {
+                    if config.analytics.retention_days > 0
+                        && let Err(e) = store.prune(config.analytics.retention_days)
+                    {
+                        tracing::warn!("Failed to prune analytics: {e}");
+                    }
+                    agent = agent.with_analytics(Arc::new(store));
+                }
+                Err(e) => tracing::warn!("Analytics disabled: {e}"),
+            }
+        }
+
+        let retention = RetentionPolicy::from_config(&config.memory);
+        match HistoryStore::open(HistoryStore::default_path()) {
+            Ok(store) => {
+                let store = store.with_retention(retention);
+                if let Err(e) = store.prune() {
+                    tracing::warn!("Failed to prune conversation history: {e}");
+                }
+                Ok(agent.with_history(Arc::new(store)))
+            }
+            Err(e) => {
+                tracing::warn!("Conversation history disabled: {e}");
+                Ok(agent)
+            }
+        }
+    }
+
+    pub fn with_params(mut self, params: GenerateParams) -> Self {
+        self.settings_mut().params = params;
+        self
+    }
+
+    pub fn with_max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history.max(2);
+        self
+    }
+
+    pub fn with_compression(mut self, policy: CompressionPolicy) -> Self {
+        self.compression = policy;
+        self
+    }
+
+    /// Start a thread over once it has been idle for `ttl`.
+    pub fn with_session_ttl(mut self, ttl: Duration) -> Self {
+        self.session_ttl = Some(ttl);
+        self
+    }
+
+    /// Persist turns to `store` and restore threads from it.
+    pub fn with_history(mut self, store: Arc<HistoryStore>) -> Self {
+        self.history = Some(store);
+        self
+    }
+
+    /// Keep user preferences in `store` and tell the model about them.
+    pub fn with_profiles(mut self, store: Arc<ProfileStore>) -> Self {
+        self.profiles = Some(store);
+        self
+    }
+
+    /// Prepend the `top_k` chunks scoring at least `min_score` to each prompt.
+    pub fn with_rag(mut self, store: Arc<RagStore>, top_k: usize, min_score: f32) -> Self {
+        self.rag = Some(Retrieval {
+            store,
+            top_k,
+            min_score,
+        });
+        self
+    }
+
+    /// Let the model call `registry`'s tools, at most `max_rounds` rounds
+    /// per message.
+    pub fn with_tools(mut self, registry: Arc<ToolRegistry>, max_rounds: usize) -> Self {
+        self.tools = Some(Tools {
+            registry,
+            max_rounds,
+        });
+        self
+    }
+
+    /// Publish generations and tool calls on `bus`.
+    pub fn with_events(mut self, bus: EventBus) -> Self {
+        self.events = Some(bus);
+        self
+    }
+
+    /// Extract and recall facts about users with `memory`.
+    pub fn with_facts(mut self, memory: Arc<FactMemory>) -> Self {
+        self.facts = Some(memory);
+        self
+    }
+
+    /// Record usage and enforce budgets with `tracker`.
+    pub fn with_usage(mut self, tracker: Arc<UsageTracker>) -> Self {
+        self.usage = Some(tracker);
+        self
+    }
+
+    /// Record every answered message, and reactions as feedback, in `store`.
+    pub fn with_analytics(mut self, store: Arc<AnalyticsStore>) -> Self {
+        self.analytics = Some(store);
+        self
+    }
+
+    /// The provider answering messages, e.g. to share it with moderation.
+    pub fn provider(&self) -> Arc<dyn Provider> {
+        self.provider.clone()
+    }
+
+    /// Use `personas` for the threads they are assigned to.
+    pub fn with_personas(mut self, personas: PersonaSet) -> Self {
+        self.settings_mut().personas = personas;
+        self
+    }
+
+    /// Decide reply languages with `languages` instead of the defaults.
+    pub fn with_languages(mut self, languages: LanguagePolicy) -> Self {
+        self.settings_mut().languages = languages;
+        self
+    }
+
+    pub fn with_guardrails(mut self, guardrails: Guardrails) -> Self {
+        self.settings_mut().guardrails = guardrails;
+        self
+    }
+
+    fn settings_mut(&mut self) -> &mut Settings {
+        Arc::make_mut(self.settings.get_mut().unwrap())
+    }
+
+    fn settings(&self) -> Arc<Settings> {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// Apply the settings of `config` that are safe to change while
+    /// running: `[[personas]]`, `default_temperature`, `brain.max_tokens`,
+    /// `[language]`, `[routing]`, `[guardrails]` and `[templates]`, whose
+    /// files are read again. The model stays the one the agent started with;
+    /// the provider is handed the config too, as the local brain keeps its
+    /// own `max_tokens` cap. Live threads get their persona's new prompt;
+    /// messages already being answered finish with the old settings.
+    pub async fn reload(&self, config: &BizClawConfig) {
+        let model = self.settings().params.model.clone();
+        *self.settings.write().unwrap() = Arc::new(Settings::from_config(config, model));
+        self.provider.reload(config).await;
+        let keys: Vec<String> = self.sessions.lock().unwrap().keys().cloned().collect();
+        for key in keys {
+            if let Some((channel, thread_id)) = key.split_once(':') {
+                self.refresh_system_prompt(channel, thread_id).await;
+            }
+        }
+    }
+
+    /// Key identifying a conversation.
+    pub fn session_key(channel: &str, thread_id: &str) -> String {
+        format!("{channel}:{thread_id}")
+    }
+
+    /// Number of threads with history.
+    pub fn session_count(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Forget a thread's history, stored turns included. Returns whether
+    /// there was any.
+    pub fn reset(&self, channel: &str, thread_id: &str) -> bool {
+        let key = Self::session_key(channel, thread_id);
+        self.routed.lock().unwrap().remove(&key);
+        let mut had_history = self.sessions.lock().unwrap().remove(&key).is_some();
+        if let Some(store) = &self.history {
+            match store.clear(channel, thread_id) {
+                Ok(n) => had_history |= n > 0,
+                Err(e) => tracing::warn!("Failed to clear history of {channel}:{thread_id}: {e}"),
+            }
+        }
+        had_history
+    }
+
+    /// The persona a thread uses: the one chosen with `/persona`, else the
+    /// one its last intent was routed to, else the one assigned in config,
+    /// `None` for the defaults.
+    pub fn persona(&self, channel: &str, thread_id: &str) -> Option<Persona> {
+        self.persona_in(&self.settings(), channel, thread_id)
+            .cloned()
+    }
+
+    fn persona_in<'a>(
+        &self,
+        settings: &'a Settings,
+        channel: &str,
+        thread_id: &str,
+    ) -> Option<&'a Persona> {
+        let key = Self::session_key(channel, thread_id);
+        let chosen = self
+            .persona_overrides
+            .lock()
+            .unwrap()
+            .get(&key)
+            .cloned()
+            .or_else(|| self.routed.lock().unwrap().get(&key).cloned());
+        match chosen {
+            Some(name) => settings.personas.get(&name),
+            None => settings.personas.for_thread(channel, thread_id),
+        }
+    }
+
+    /// Switch a thread to the persona `name`, or back to its configured one
+    /// with `None`. Returns false for an unknown name.
+    pub async fn set_persona(&self, channel: &str, thread_id: &str, name: Option<&str>) -> bool {
+        let key = Self::session_key(channel, thread_id);
+        match name {
+            Some(name) => {
+                let Some(name) = self.settings().personas.get(name).map(|p| p.name.clone()) else {
+                    return false;
+                };
+                self.persona_overrides.lock().unwrap().insert(key, name);
+            }
+            None => {
+                self.persona_overrides.lock().unwrap().remove(&key);
+            }
+        }
+
+        self.refresh_system_prompt(channel, thread_id).await;
+        true
+    }
+
+    /// Switch the thread to the persona of `text`'s intent, unless it was
+    /// pinned with `/persona` or no intent fits.
+    async fn route(&self, channel: &str, thread_id: &str, text: &str) {
+        let settings = self.settings();
+        let key = Self::session_key(channel, thread_id);
+        if settings.router.is_empty() || self.persona_overrides.lock().unwrap().contains_key(&key) {
+            return;
+        }
+        let Some(intent) = settings
+            .router
+            .classify(text, self.provider.as_ref(), &settings.params)
+            .await
+        else {
+            return;
+        };
+        let Some(persona) = settings.personas.get(&intent.persona) else {
+            tracing::warn!(
+                "Intent '{}': unknown persona '{}'",
+                intent.name,
+                intent.persona
+            );
+            return;
+        };
+        let previous = self
+            .routed
+            .lock()
+            .unwrap()
+            .insert(key, persona.name.clone());
+        if previous.as_deref() != Some(persona.name.as_str()) {
+            tracing::debug!(
+                "[{channel}] {thread_id} routed to {} ({})",
+                persona.name,
+                intent.name
+            );
+            self.refresh_system_prompt(channel, thread_id).await;
+        }
+    }
+
+    /// Put the thread's current system prompt at the start of its live
+    /// session, keeping the history.
+    async fn refresh_system_prompt(&self, channel: &str, thread_id: &str) {
+        let session = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get(&Self::session_key(channel, thread_id))
+            .map(|entry| entry.context.clone());
+        if let Some(session) = session {
+            let mut history = session.lock().await;
+            let mut messages = history.messages().to_vec();
+            if let Some(first) = messages.first_mut()
+                && first.role == Role::System
+            {
+                *first = Message::system(self.system_prompt_for(channel, thread_id));
+            }
+            history.replace(messages);
+        }
+    }
+
+    /// Reply to `/persona [name]`.
+    async fn persona_command(
+        &self,
+        channel: &str,
+        thread_id: &str,
+        arg: &str,
+        lang: Lang,
+    ) -> String {
+        let settings = self.settings();
+        let personas = &settings.personas;
+        if personas.is_empty() {
+            return lang
+                .pick("Chưa cấu hình persona nào.", "No personas are configured.")
+                .into();
+        }
+        let available = format!("default, {}", personas.names().join(", "));
+        let current = || {
+            self.persona(channel, thread_id)
+                .map_or_else(|| "default".to_string(), |p| p.name)
+        };
+        if arg.is_empty() {
+            let current = current();
+            return lang.pick(
+                format!(
+                    "Persona hiện tại: {current}. Có sẵn: {available}. Đổi bằng {PERSONA_COMMAND} <tên>."
+                ),
+                format!(
+                    "Persona: {current}. Available: {available}. Switch with {PERSONA_COMMAND} <name>."
+                ),
+            );
+        }
+        if arg.eq_ignore_ascii_case("default") {
+            self.set_persona(channel, thread_id, None).await;
+            let current = current();
+            return lang.pick(
+                format!("Đã quay về persona {current}."),
+                format!("Persona reset to {current}."),
+            );
+        }
+        if self.set_persona(channel, thread_id, Some(arg)).await {
+            let name = current();
+            lang.pick(
+                format!("Đã chuyển sang persona {name}."),
+                format!("Switched to persona {name}."),
+            )
+        } else {
+            lang.pick(
+                format!("Không có persona '{arg}'. Có sẵn: {available}."),
+                format!("Unknown persona '{arg}'. Available: {available}."),
+            )
+        }
+    }
+
+    /// The language the sender or the thread's persona asked for.
+    fn pinned_language<'a>(
+        persona: Option<&'a Persona>,
+        about: Option<&'a UserProfile>,
+    ) -> Option<&'a str> {
+        about
+            .and_then(|p| p.language.as_deref())
+            .or_else(|| persona.and_then(|p| p.language.as_deref()))
+    }
+
+    /// The language of the bot's own texts for `msg`: the pinned one, else
+    /// the message's, else the channel's default.
+    fn message_language(&self, msg: &IncomingMessage, about: Option<&UserProfile>) -> Lang {
+        let settings = self.settings();
+        let persona = self.persona_in(&settings, &msg.channel, &msg.thread_id);
+        Self::pinned_language(persona, about)
+            .and_then(Lang::parse)
+            .unwrap_or_else(|| settings.languages.for_message(&msg.channel, &msg.content))
+    }
+
+    /// The thread's persona or `[identity]` prompt, through the
+    /// [`template::SYSTEM`] template.
+    fn system_prompt_for(&self, channel: &str, thread_id: &str) -> String {
+        let settings = self.settings();
+        let persona = self.persona_in(&settings, channel, thread_id);
+        let persona_name = persona.map(|p| p.name.as_str());
+        let prompt = persona.map_or(self.system_prompt.as_str(), |p| p.system_prompt.as_str());
+        let date = today();
+        settings.templates.render(
+            template::SYSTEM,
+            persona_name,
+            &[
+                ("prompt", prompt),
+                ("name", self.name.as_str()),
+                ("persona", persona_name.unwrap_or_default()),
+                ("channel", channel),
+                ("date", date.as_str()),
+            ],
+        )
+    }
+
+    /// Generate the reply to one incoming message.
+    ///
+    /// The turn is only recorded in history once the provider answers, so a
+    /// failed request can simply be retried.
+    pub async fn respond(
+        &self,
+        msg: &IncomingMessage,
+        progress: Option<&ProgressReporter>,
+    ) -> Result<Option<OutgoingMessage>> {
+        let about = self.load_profile(&msg.channel, &msg.sender_id);
+        let lang = self.message_language(msg, about.as_ref());
+        if is_reset_command(&msg.content) {
+            self.reset(&msg.channel, &msg.thread_id);
+            if let Some(facts) = &self.facts
+                && let Some(transcript) = facts.take_pending(&msg.channel, &msg.sender_id)
+            {
+                self.spawn_extraction(facts, &msg.channel, &msg.sender_id, transcript);
+            }
+            return Ok(Some(OutgoingMessage::text(
+                &msg.thread_id,
+                lang.pick(
+                    "Đã xoá cuộc trò chuyện, mình bắt đầu lại nhé.",
+                    "Conversation cleared.",
+                ),
+                msg.thread_type.clone(),
+            )));
+        }
+        if let Some(usage) = &self.usage
+            && is_usage_command(&msg.content)
+        {
+            let reply = if usage.is_admin(&msg.channel, &msg.sender_id) {
+                usage.report(lang)
+            } else {
+                lang.pick(
+                    "Chỉ quản trị viên mới xem được số liệu sử dụng.",
+                    "Only admins can see usage.",
+                )
+                .into()
+            };
+            return Ok(Some(OutgoingMessage::text(
+                &msg.thread_id,
+                reply,
+                msg.thread_type.clone(),
+            )));
+        }
+        if let Some(analytics) = &self.analytics
+            && let Some(score) = analytics::reaction_score(&msg.content)
+        {
+            if let Err(e) = analytics.record_feedback(&msg.channel, &msg.thread_id, score) {
+                tracing::warn!("Failed to record feedback: {e}");
+            }
+            return Ok(None);
+        }
+        if let Some(arg) = persona_argument(&msg.content) {
+            let reply = self
+                .persona_command(&msg.channel, &msg.thread_id, arg, lang)
+                .await;
+            return Ok(Some(OutgoingMessage::text(
+                &msg.thread_id,
+                reply,
+                msg.thread_type.clone(),
+            )));
+        }
+        if let Some(profiles) = &self.profiles
+            && let Some(update) = profile::parse(&msg.content)
+        {
+            if update == ProfileUpdate::Forget
+                && let Some(facts) = &self.facts
+                && let Err(e) = facts.forget(&msg.channel, &msg.sender_id)
+            {
+                tracing::warn!("Failed to forget facts about {}: {e}", msg.sender_id);
+            }
+            let reply = update_profile(profiles, &msg.channel, &msg.sender_id, update, lang);
+            return Ok(Some(OutgoingMessage::text(
+                &msg.thread_id,
+                reply,
+                msg.thread_type.clone(),
+            )));
+        }
+
+        let user_turn = user_turn(msg);
+        if user_turn.trim().is_empty() {
+            return Ok(None);
+        }
+        let started = Instant::now();
+        self.route(&msg.channel, &msg.thread_id, &msg.content).await;
+
+        let settings = self.settings();
+        let persona = self.persona_in(&settings, &msg.channel, &msg.thread_id);
+        let params = persona.map_or(&settings.params, |p| &p.params);
+        let session = self.session(&msg.channel, &msg.thread_id);
+        let mut history = session.lock().await;
+
+        let mut prompt = history.messages().to_vec();
+        prompt.push(Message::user(&user_turn));
+
+        if let Some(progress) = progress {
+            progress.report(ProgressEvent::Thinking).await;
+        }
+        if self.compression.needs_compression(&prompt) {
+            match compression::compress(
+                self.provider.as_ref(),
+                params,
+                history.messages(),
+                &self.compression,
+            )
+            .await
+            {
+                Ok(Some(compressed)) => {
+                    tracing::info!(
+                        "Compressed {}:{} from {} to {} messages",
+                        msg.channel,
+                        msg.thread_id,
+                        history.len(),
+                        compressed.len()
+                    );
+                    history.replace(compressed);
+                    prompt = history.messages().to_vec();
+                    prompt.push(Message::user(&user_turn));
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Context compression failed: {e}"),
+            }
+        }
+        // The sender's profile and retrieved context go right before the new
+        // turn and are not kept in history; the next message gets its own.
+        if let Some(context) = about.as_ref().and_then(profile::prompt_context) {
+            prompt.insert(prompt.len() - 1, Message::system(context));
+        }
+        if let Some(facts) = &self.facts
+            && let Some(context) = facts
+                .recall(&msg.channel, &msg.sender_id, &msg.content)
+                .await
+        {
+            prompt.insert(prompt.len() - 1, Message::system(context));
+        }
+        if let Some(context) = self.retrieve(&msg.content).await {
+            prompt.insert(prompt.len() - 1, Message::system(context));
+        }
+        // So is the language instruction, which follows each message.
+        if Self::pinned_language(persona, about.as_ref()).is_none()
+            && let Some(system) = prompt.first_mut()
+            && system.role == Role::System
+        {
+            let instruction = settings.languages.instruction(&msg.channel, &msg.content);
+            system.content = format!("{}\n\n{instruction}", system.content);
+        }
+        let policy = settings.guardrails.for_channel(&msg.channel);
+        let expected = Self::pinned_language(persona, about.as_ref())
+            .and_then(Lang::parse)
+            .or_else(|| settings.languages.detect(&msg.content));
+        let generated = self
+            .generate(
+                &msg.channel,
+                &msg.thread_id,
+                &msg.sender_id,
+                &prompt,
+                params,
+                persona.and_then(|p| p.tools.as_deref()),
+                progress,
+                policy,
+                expected,
+            )
+            .await;
+        self.record_message(
+            &msg.channel,
+            &msg.thread_id,
+            &msg.sender_id,
+            persona,
+            started,
+            &generated,
+        );
+        let answer = generated?.answer;
+
+        self.persist(&msg.channel, &msg.thread_id, Role::User, &user_turn);
+        self.persist(&msg.channel, &msg.thread_id, Role::Assistant, &answer);
+        if let Some(facts) = &self.facts
+            && !answer.is_empty()
+            && let Some(transcript) =
+                facts.note_exchange(&msg.channel, &msg.sender_id, &user_turn, &answer)
+        {
+            self.spawn_extraction(facts, &msg.channel, &msg.sender_id, transcript);
+        }
+        history.push(Message::user(user_turn));
+        history.push(Message::assistant(&answer));
+        if answer.is_empty() {
+            return Ok(None);
+        }
+
+        let mut reply = OutgoingMessage::text(
+            &msg.thread_id,
+            policy.append_disclaimer(&answer),
+            msg.thread_type.clone(),
+        );
+        reply.reply_to = msg
+            .reply_to
+            .clone()
+            .filter(|_| msg.thread_type == ThreadType::Group);
+        Ok(Some(reply))
+    }
+
+    /// Write a message for a thread nobody asked in — a scheduled standup
+    /// summary or reminder. The model sees the thread's history and
+    /// `instruction`; only its answer is recorded, so later replies in the
+    /// thread can refer to it.
+    pub async fn proactive(
+        &self,
+        channel: &str,
+        thread_id: &str,
+        instruction: &str,
+    ) -> Result<String> {
+        let started = Instant::now();
+        let settings = self.settings();
+        let persona = self.persona_in(&settings, channel, thread_id);
+        let params = persona.map_or(&settings.params, |p| &p.params);
+        let session = self.session(channel, thread_id);
+        let mut history = session.lock().await;
+
+        let mut prompt = history.messages().to_vec();
+        let date = today();
+        prompt.push(Message::user(settings.templates.render(
+            template::SCHEDULED,
+            persona.map(|p| p.name.as_str()),
+            &[
+                ("instruction", instruction),
+                ("channel", channel),
+                ("date", date.as_str()),
+            ],
+        )));
+        if let Some(context) = self.retrieve(instruction).await {
+            prompt.insert(prompt.len() - 1, Message::system(context));
+        }
+        let pinned = persona
+            .and_then(|p| p.language.as_deref())
+            .and_then(Lang::parse);
+        if pinned.is_none()
+            && let Some(system) = prompt.first_mut()
+            && system.role == Role::System
+        {
+            let language = settings.languages.default_for(channel).name();
+            system.content = format!("{}\n\nReply in {language}.", system.content);
+        }
+        let policy = settings.guardrails.for_channel(channel);
+        let expected = pinned.unwrap_or_else(|| settings.languages.default_for(channel));
+        let generated = self
+            .generate(
+                channel,
+                thread_id,
+                "",
+                &prompt,
+                params,
+                persona.and_then(|p| p.tools.as_deref()),
+                None,
+                policy,
+                Some(expected),
+            )
+            .await;
+        self.record_message(channel, thread_id, "", persona, started, &generated);
+        let answer = generated?.answer;
+
+        if !answer.is_empty() {
+            self.persist(channel, thread_id, Role::Assistant, &answer);
+            history.push(Message::assistant(&answer));
+        }
+        Ok(policy.append_disclaimer(&answer))
+    }
+
+    /// [`generate_once`](Self::generate_once), again while the answer
+    /// breaks `policy` and retries are left, then enforced. The answer is
+    /// expected to be in `lang`, if given. The disclaimer is not added.
+    #[allow(clippy::too_many_arguments)]
+    async fn generate(
+        &self,
+        channel: &str,
+        thread_id: &str,
+        user_id: &str,
+        prompt: &[Message],
+        params: &GenerateParams,
+        allowed: Option<&[String]>,
+        progress: Option<&ProgressReporter>,
+        policy: &OutputPolicy,
+        lang: Option<Lang>,
+    ) -> Result<Generation> {
+        let mut generation = self
+            .generate_once(
+                channel, thread_id, user_id, prompt, params, allowed, progress,
+            )
+            .await?;
+        let mut retry = prompt.to_vec();
+        for _ in 0..policy.retries() {
+            let violations = policy.check(&generation.answer, lang);
+            if violations.is_empty() {
+                return Ok(generation);
+            }
+            tracing::info!(
+                "[{channel}] Reply broke the output policy ({}), generating it again",
+                violations
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            retry.push(Message::assistant(&generation.answer));
+            retry.push(Message::user(guardrails::correction(&violations)));
+            let again = self
+                .generate_once(
+                    channel, thread_id, user_id, &retry, params, allowed, progress,
+                )
+                .await?;
+            generation.answer = again.answer;
+            generation.model = again.model;
+            generation.usage += &again.usage;
+            generation.tools.extend(again.tools);
+        }
+        generation.answer = policy.enforce(&generation.answer);
+        Ok(generation)
+    }
+
+    /// Ask the model for the answer to `prompt`, through the tool loop
+    /// when tools are attached, offering only `allowed` if set. Senders over
+    /// budget get the usage fallback route instead; `user_id` is empty when
+    /// nobody asked.
+    #[allow(clippy::too_many_arguments)]
+    async fn generate_once(
+        &self,
+        channel: &str,
+        thread_id: &str,
+        user_id: &str,
+        prompt: &[Message],
+        params: &GenerateParams,
+        allowed: Option<&[String]>,
+        progress: Option<&ProgressReporter>,
+    ) -> Result<Generation> {
+        let fallback = self
+            .usage
+            .as_ref()
+            .and_then(|usage| usage.fallback_for(channel, user_id));
+        let fallback_params;
+        let (provider, params) = match fallback {
+            Some(fallback) => {
+                tracing::info!(
+                    "[{channel}] {user_id} is over budget, answering with {}",
+                    fallback.provider.name()
+                );
+                fallback_params = GenerateParams {
+                    model: fallback
+                        .model
+                        .clone()
+                        .unwrap_or_else(|| params.model.clone()),
+                    ..params.clone()
+                };
+                (fallback.provider.as_ref(), &fallback_params)
+            }
+            None => (self.provider.as_ref(), params),
+        };
+        self.publish(|| Event::GenerationStarted {
+            channel: channel.to_string(),
+            thread_id: thread_id.to_string(),
+            model: params.model.clone(),
+        });
+        let started = Instant::now();
+        let answer = match &self.tools {
+            Some(tools) => tool_loop::run_allowing(
+                provider,
+                &tools.registry,
+                allowed,
+                prompt,
+                params,
+                tools.max_rounds,
+                progress,
+            )
+            .await
+            .map(|outcome| {
+                for run in &outcome.runs {
+                    self.publish(|| Event::ToolExecuted {
+                        tool: run.name.clone(),
+                        duration_ms: run.duration.as_millis() as u64,
+                        success: run.success,
+                    });
+                }
+                let tools = outcome.runs.into_iter().map(|run| run.name).collect();
+                (outcome.answer, outcome.usage, tools)
+            }),
+            None => provider.chat(prompt, &[], params).await.map(|response| {
+                let usage = engine::response_usage(&response, prompt);
+                (
+                    response.content.unwrap_or_default().trim().to_string(),
+                    usage,
+                    Vec::new(),
+                )
+            }),
+        };
+        self.publish(|| Event::GenerationFinished {
+            channel: channel.to_string(),
+            thread_id: thread_id.to_string(),
+            model: params.model.clone(),
+            duration_ms: started.elapsed().as_millis() as u64,
+            success: answer.is_ok(),
+        });
+        if let Err(e) = &answer {
+            bizclaw_core::metrics::count_error(e);
+        }
+        let (answer, usage, tools) = answer?;
+        if let Some(tracker) = &self.usage {
+            tracker.record(provider.name(), &params.model, channel, user_id, &usage);
+        }
+        Ok(Generation {
+            answer,
+            model: params.model.clone(),
+            usage,
+            tools,
+        })
+    }
+
+    /// Record a message answered, or not, since `started` in analytics.
+    fn record_message(
+        &self,
+        channel: &str,
+        thread_id: &str,
+        user_id: &str,
+        persona: Option<&Persona>,
+        started: Instant,
+        generated: &Result<Generation>,
+    ) {
+        let Some(analytics) = &self.analytics else {
+            return;
+        };
+        let mut record = MessageRecord {
+            channel: channel.to_string(),
+            thread_id: thread_id.to_string(),
+            user_id: user_id.to_string(),
+            persona: persona.map(|p| p.name.clone()).unwrap_or_default(),
+            latency_ms: started.elapsed().as_millis() as u64,
+            success: generated.is_ok(),
+            ..Default::default()
+        };
+        if let Ok(generation) = generated {
+            record.model = generation.model.clone();
+            record.prompt_tokens = generation.usage.prompt_tokens;
+            record.completion_tokens = generation.usage.completion_tokens;
+            record.tools = generation.tools.clone();
+        }
+        if let Err(e) = analytics.record(&record) {
+            tracing::warn!("Failed to record analytics: {e}");
+        }
+    }
+
+    /// Extract facts from `transcript` without holding up the reply.
+    fn spawn_extraction(
+        &self,
+        facts: &Arc<FactMemory>,
+        channel: &str,
+        user_id: &str,
+        transcript: Vec<Message>,
+    ) {
+        let facts = facts.clone();
+        let params = self.settings().params.clone();
+        let (channel, user_id) = (channel.to_string(), user_id.to_string());
+        tokio::spawn(async move {
+            if let Err(e) = facts
+                .extract(&channel, &user_id, &transcript, &params)
+                .await
+            {
+                tracing::warn!("Failed to extract facts about {channel}:{user_id}: {e}");
+            }
+        });
+    }
+
+    /// Publish the event `make` builds, if a bus is attached.
+    fn publish(&self, make: impl FnOnce() -> Event) {
+        if let Some(bus) = &self.events {
+            bus.publish(make());
+        }
+    }
+
+    /// [`respond`](Self::respond), with an apology in the user's language
+    /// instead of an error they would never see.
+    async fn respond_or_apologize(
+        &self,
+        msg: &IncomingMessage,
+        progress: Option<&ProgressReporter>,
+    ) -> Result<Option<OutgoingMessage>> {
+        match self.respond(msg, progress).await {
+            Err(e) => {
+                tracing::error!(
+                    "[{}] Failed to answer (thread={}): {e}",
+                    msg.channel,
+                    msg.thread_id
+                );
+                let about = self.load_profile(&msg.channel, &msg.sender_id);
+                let apology = self.message_language(msg, about.as_ref()).pick(
+                    "Xin lỗi, mình đang gặp sự cố. Bạn thử lại sau ít phút nhé.",
+                    "Sorry, something went wrong. Please try again in a moment.",
+                );
+                Ok(Some(OutgoingMessage::text(
+                    &msg.thread_id,
+                    apology,
+                    msg.thread_type.clone(),
+                )))
+            }
+            answered => answered,
+        }
+    }
+
+    fn session(&self, channel: &str, thread_id: &str) -> Session {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        if let Some(ttl) = self.session_ttl {
+            let before = sessions.len();
+            sessions.retain(|_, entry| now.duration_since(entry.last_active) < ttl);
+            if sessions.len() < before {
+                tracing::debug!("{} idle session(s) expired", before - sessions.len());
+            }
+        }
+        let entry = sessions
+            .entry(Self::session_key(channel, thread_id))
+            .or_insert_with(|| {
+                let mut ctx = ConversationContext::new(self.max_history);
+                ctx.push(Message::system(self.system_prompt_for(channel, thread_id)));
+                for turn in self.restore(channel, thread_id) {
+                    ctx.push(turn);
+                }
+                SessionEntry {
+                    context: Arc::new(tokio::sync::Mutex::new(ctx)),
+                    last_active: now,
+                }
+            });
+        entry.last_active = now;
+        entry.context.clone()
+    }
+
+    async fn retrieve(&self, query: &str) -> Option<String> {
+        let rag = self.rag.as_ref()?;
+        match rag.store.retrieve(query, rag.top_k, rag.min_score).await {
+            Ok(hits) if !hits.is_empty() => Some(rag::format_context(&hits)),
+            Ok(_) => None,
+            Err(e) => {
+                tracing::warn!("Document retrieval failed: {e}");
+                None
+            }
+        }
+    }
+
+    fn load_profile(&self, channel: &str, user_id: &str) -> Option<UserProfile> {
+        match self.profiles.as_ref()?.get(channel, user_id) {
+            Ok(found) => found,
+            Err(e) => {
+                tracing::warn!("Failed to load profile of {channel}:{user_id}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Stored turns of a thread, newest `max_history - 1` of them. With a
+    /// session TTL, only the current conversation: turns before an idle gap
+    /// longer than the TTL, or older than it altogether, are left out.
+    fn restore(&self, channel: &str, thread_id: &str) -> Vec<Message> {
+        let Some(store) = &self.history else {
+            return Vec::new();
+        };
+        match store.recent(channel, thread_id, self.max_history - 1) {
+            Ok(mut turns) => {
+                if let Some(ttl) = self
+                    .session_ttl
+                    .and_then(|t| chrono::Duration::from_std(t).ok())
+                {
+                    let mut start = turns.len();
+                    let mut newer = chrono::Utc::now();
+                    while start > 0 && newer - turns[start - 1].created_at < ttl {
+                        start -= 1;
+                        newer = turns[start].created_at;
+                    }
+                    turns.drain(..start);
+                }
+                turns.iter().map(|t| t.to_message()).collect()
+            }
+            Err(e) => {
+                tracing::warn!("Failed to load history of {channel}:{thread_id}: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// A store failure costs the turn's persistence, not the reply.
+    fn persist(&self, channel: &str, thread_id: &str, role: Role, content: &str) {
+        if let Some(store) = &self.history
+            && let Err(e) = store.append(channel, thread_id, role, content)
+        {
+            tracing::warn!("Failed to save history of {channel}:{thread_id}: {e}");
+        }
+    }
+}
+
+#[async_trait]
+impl MessageHandler for ChannelAgent {
+    async fn handle(&self, message: IncomingMessage) -> Result<Option<OutgoingMessage>> {
+        self.respond_or_apologize(&message, None).await
+    }
+
+    async fn handle_with_progress(
+        &self,
+        message: IncomingMessage,
+        progress: ProgressReporter,
+    ) -> Result<Option<OutgoingMessage>> {
+        self.respond_or_apologize(&message, Some(&progress)).await
+    }
+}
+
+/// `/reset` (also in Telegram's `/reset@botname` form), `/new` or a
+/// "new chat" phrase.
+fn is_reset_command(content: &str) -> bool {
+    let content = content.trim().trim_end_matches(['.', '!']).to_lowercase();
+    let command = if content.starts_with('/') {
+        content.split('@').next().unwrap_or_default()
+    } else {
+        content.as_str()
+    };
+    command == RESET_COMMAND || NEW_CHAT_PHRASES.contains(&command)
+}
+
+/// Apply a profile update from chat and return the confirmation.
+fn update_profile(
+    profiles: &ProfileStore,
+    channel: &str,
+    user_id: &str,
+    update: ProfileUpdate,
+    lang: Lang,
+) -> String {
+    // A new reply language is confirmed in that language.
+    let lang = match &update {
+        ProfileUpdate::Language(language) => Lang::parse(language).unwrap_or(lang),
+        _ => lang,
+    };
+    let result = profiles.get_or_default(channel, user_id).and_then(|mut p| {
+        let forget = update == ProfileUpdate::Forget;
+        let changed = !matches!(update, ProfileUpdate::Show | ProfileUpdate::Forget);
+        let reply = profile::apply(&mut p, update, lang);
+        if forget {
+            profiles.delete(channel, user_id)?;
+        } else if changed {
+            profiles.save(&p)?;
+        }
+        Ok(reply)
+    });
+    result.unwrap_or_else(|e| {
+        tracing::warn!("Failed to update profile of {channel}:{user_id}: {e}");
+        lang.pick(
+            "Xin lỗi, mình chưa lưu được. Bạn thử lại sau nhé.",
+            "Sorry, I couldn't save that. Please try again later.",
+        )
+        .into()
+    })
+}
+
+/// The argument of `/persona [name]` (also `/persona@botname`), `None` for
+/// other messages.
+fn persona_argument(content: &str) -> Option<&str> {
+    let content = content.trim();
+    let (command, arg) = content
+        .split_once(char::is_whitespace)
+        .unwrap_or((content, ""));
+    let command = command.split('@').next()?;
+    command
+        .eq_ignore_ascii_case(PERSONA_COMMAND)
+        .then(|| arg.trim())
+}
+
+/// System prompt with the persona appended when it is not already part of it.
+pub(crate) fn persona_prompt(identity: &Identity) -> String {
+    let mut prompt = identity.system_prompt.trim().to_string();
+    if !identity.name.is_empty() && !prompt.contains(&identity.name) {
+        prompt.push_str(&format!("\n\nYour name is {}.", identity.name));
+    }
+    if !identity.persona.is_empty() && !prompt.contains(&identity.persona) {
+        prompt.push_str(&format!(
+            "\nPersona: {}.",
+            identity.persona.trim_end_matches('.')
+        ));
+    }
+    prompt
+}
+
+/// Today's date in local time, for templates.
+fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+/// The user turn: the text, prefixed with the sender in groups, plus a note
+/// per attachment so the model knows what was sent.
+fn user_turn(msg: &IncomingMessage) -> String {
+    let mut text = match (&msg.thread_type, &msg.sender_name) {
+        (ThreadType::Group, Some(name)) if !msg.content.is_empty() => {
+            format!("{name}: {}", msg.content)
+        }
+        _ => msg.content.clone(),
+    };
+    for media in &msg.media {
+        let name = media.filename.as_deref().unwrap_or("file");
+        let location = media
+            .local_path
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| media.source.clone());
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        text.push_str(&format!("[Attached {:?}: {name} — {location}]", media.kind));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::config::PersonaConfig;
+    use bizclaw_core::traits::Tool;
+    use bizclaw_core::types::{
+        FunctionCall, MediaKind, MediaRef, ModelInfo, ProviderResponse, ToolCall, ToolDefinition,
+        ToolResult,
+    };
+
+    /// Replies with the number of prompt messages and the last turn. When
+    /// tools are offered, first calls the first one with the user turn.
+    struct EchoProvider;
+
+    #[async_trait]
+    impl Provider for EchoProvider {
+        fn name(&self) -> &str {
+            "echo"
+        }
+        async fn chat(
+            &self,
+            messages: &[Message],
+            tools: &[ToolDefinition],
+            _params: &GenerateParams,
+        ) -> Result<ProviderResponse> {
+            let last = messages.last().unwrap();
+            if let Some(tool) = tools.first()
+                && last.role == Role::User
+            {
+                return Ok(ProviderResponse {
+                    content: None,
+                    tool_calls: vec![ToolCall {
+                        id: "call_1".into(),
+                        r#type: "function".into(),
+                        function: FunctionCall {
+                            name: tool.name.clone(),
+                            arguments: serde_json::json!({ "text": last.content }).to_string(),
+                        },
+                    }],
+                    finish_reason: Some("tool_calls".into()),
+                    usage: None,
+                });
+            }
+            Ok(ProviderResponse::text(format!(
+                "{}|{}",
+                messages.len(),
+                last.content
+            )))
+        }
+        async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+            Ok(vec![])
+        }
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+        /// One dimension per keyword of the FAQ used in the tests.
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            Ok(texts
+                .iter()
+                .map(|t| {
+                    let t = t.to_lowercase();
+                    ["hours", "refund"]
+                        .iter()
+                        .map(|w| t.matches(w).count() as f32)
+                        .collect()
+                })
+                .collect())
+        }
+    }
+
+    struct UpperTool;
+
+    #[async_trait]
+    impl Tool for UpperTool {
+        fn name(&self) -> &str {
+            "upper"
+        }
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition {
+                name: "upper".into(),
+                description: "Uppercase text".into(),
+                parameters: serde_json::json!({"required": ["text"]}),
+            }
+        }
+        async fn execute(&self, arguments: &str) -> Result<ToolResult> {
+            let args: serde_json::Value = serde_json::from_str(arguments)?;
+            Ok(ToolResult {
+                tool_call_id: String::new(),
+                output: args["text"].as_str().unwrap_or_default().to_uppercase(),
+                success: true,
+            })
+        }
+    }
+
+    fn agent() -> ChannelAgent {
+        ChannelAgent::new(Arc::new(EchoProvider), &Identity::default())
+    }
+
+    fn incoming(channel: &str, thread: &str, content: &str) -> IncomingMessage {
+        IncomingMessage {
+            channel: channel.into(),
+            thread_id: thread.into(),
+            sender_id: "u1".into(),
+            sender_name: Some("An".into()),
+            content: content.into(),
+            thread_type: ThreadType::Direct,
+            timestamp: chrono::Utc::now(),
+            reply_to: None,
+            message_id: None,
+            media: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history_is_per_thread() {
+        let agent = agent();
+        let a1 = agent.respond(&incoming("telegram", "1", "hi"), None).await;
+        assert_eq!(a1.unwrap().unwrap().content, "2|hi");
+        let a2 = agent
+            .respond(&incoming("telegram", "1", "again"), None)
+            .await;
+        // system + hi + answer + again
+        assert_eq!(a2.unwrap().unwrap().content, "4|again");
+
+        let b1 = agent.respond(&incoming("discord", "1", "hey"), None).await;
+        assert_eq!(b1.unwrap().unwrap().content, "2|hey");
+        assert_eq!(agent.session_count(), 2);
+
+        assert!(agent.reset("telegram", "1"));
+        let a3 = agent
+            .respond(&incoming("telegram", "1", "fresh"), None)
+            .await;
+        assert_eq!(a3.unwrap().unwrap().content, "2|fresh");
+    }
+
+    #[tokio::test]
+    async fn test_group_turns_name_the_sender() {
+        let agent = agent();
+        let mut msg = incoming("telegram", "-100", "who am I?");
+        msg.thread_type = ThreadType::Group;
+        msg.reply_to = Some("42".into());
+        let reply = agent.respond(&msg, None).await.unwrap().unwrap();
+        assert_eq!(reply.content, "2|An: who am I?");
+        assert_eq!(reply.reply_to.as_deref(), Some("42"));
+        assert_eq!(reply.thread_type, ThreadType::Group);
+    }
+
+    #[tokio::test]
+    async fn test_attachments_and_empty_messages() {
+        let agent = agent();
+        assert!(
+            agent
+                .respond(&incoming("x", "1", "  "), None)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let mut msg = incoming("x", "1", "");
+        msg.media
+            .push(MediaRef::remote(MediaKind::Image, "https://cdn/x.png").with_filename("x.png"));
+        let reply = agent.respond(&msg, None).await.unwrap().unwrap();
+        assert!(
+            reply
+                .content
+                .contains("[Attached Image: x.png — https://cdn/x.png]")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_history_restored_and_reset() {
+        let store = Arc::new(HistoryStore::in_memory().unwrap());
+        let first = agent().with_history(store.clone());
+        first
+            .respond(&incoming("telegram", "1", "hi"), None)
+            .await
+            .unwrap();
+
+        // A fresh agent (as after a restart) picks the thread up from the store.
+        let restarted = agent().with_history(store.clone());
+        let reply = restarted
+            .respond(&incoming("telegram", "1", "again"), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.content, "4|again");
+
+        let reply = restarted
+            .respond(&incoming("telegram", "1", "/reset@mybot"), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.content, "Conversation cleared.");
+        assert!(store.recent("telegram", "1", 10).unwrap().is_empty());
+        let reply = restarted
+            .respond(&incoming("telegram", "1", "fresh"), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.content, "2|fresh");
+    }
+
+    #[tokio::test]
+    async fn test_idle_threads_start_over() {
+        let store = Arc::new(HistoryStore::in_memory().unwrap());
+        let ttl = Duration::from_millis(200);
+        let agent = agent().with_history(store.clone()).with_session_ttl(ttl);
+        let say = async |agent: &ChannelAgent, text: &str| {
+            agent
+                .respond(&incoming("zalo", "1", text), None)
+                .await
+                .unwrap()
+                .unwrap()
+                .content
+        };
+
+        assert_eq!(say(&agent, "hi").await, "2|hi");
+        assert_eq!(say(&agent, "again").await, "4|again");
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(say(&agent, "later").await, "2|later");
+
+        // After a restart only the turns since the idle gap come back.
+        let restarted = ChannelAgent::new(Arc::new(EchoProvider), &Identity::default())
+            .with_history(store.clone())
+            .with_session_ttl(ttl);
+        assert_eq!(say(&restarted, "more").await, "4|more");
+
+        assert_eq!(say(&restarted, "New chat!").await, "Conversation cleared.");
+        assert_eq!(
+            say(&restarted, "bắt đầu lại").await,
+            "Đã xoá cuộc trò chuyện, mình bắt đầu lại nhé."
+        );
+        assert_eq!(
+            say(&restarted, "new chat please").await,
+            "2|new chat please"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_profile_is_set_from_chat() {
+        let store = Arc::new(ProfileStore::in_memory().unwrap());
+        let agent = agent().with_profiles(store.clone());
+        let say = async |text: &str| {
+            agent
+                .respond(&incoming("zalo", "1", text), None)
+                .await
+                .unwrap()
+                .unwrap()
+                .content
+        };
+
+        assert_eq!(say("Call me Anh").await, "Okay, I'll call you Anh.");
+        assert_eq!(
+            say("reply in English").await,
+            "Okay, I'll reply in English."
+        );
+        // system + profile note + hi
+        assert_eq!(say("hi").await, "3|hi");
+        let profile = store.get("zalo", "u1").unwrap().unwrap();
+        assert_eq!(profile.name.as_deref(), Some("Anh"));
+        assert_eq!(profile.language.as_deref(), Some("English"));
+
+        assert_eq!(
+            say("/profile forget").await,
+            "I've forgotten your preferences."
+        );
+        assert!(store.get("zalo", "u1").unwrap().is_none());
+        // system + hi + answer + again
+        assert_eq!(say("again").await, "4|again");
+    }
+
+    #[tokio::test]
+    async fn test_long_threads_are_summarized() {
+        let agent = agent().with_compression(CompressionPolicy {
+            budget_tokens: 40,
+            trigger_ratio: 1.0,
+            keep_recent: 2,
+            summary_tokens: 32,
+        });
+        for i in 0..4 {
+            let text = format!("question number {i} with some padding text");
+            agent
+                .respond(&incoming("zalo", "1", &text), None)
+                .await
+                .unwrap();
+        }
+        let session = agent.session("zalo", "1");
+        let history = session.lock().await;
+        let summaries = history
+            .messages()
+            .iter()
+            .filter(|m| compression::is_summary(m))
+            .count();
+        assert_eq!(summaries, 1);
+        assert!(history.len() < 9);
+    }
+
+    #[tokio::test]
+    async fn test_relevant_documents_are_retrieved() {
+        let store = Arc::new(RagStore::in_memory(Arc::new(EchoProvider)).unwrap());
+        store
+            .ingest("faq.txt", "Opening hours: 8am to 9pm.", "test")
+            .await
+            .unwrap();
+        let agent = agent().with_rag(store, 3, 0.5);
+
+        // system + knowledge + question
+        let reply = agent
+            .respond(&incoming("x", "1", "What are your hours?"), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.content, "3|What are your hours?");
+
+        // Nothing relevant: no knowledge block, and the last one wasn't kept.
+        let reply = agent
+            .respond(&incoming("x", "1", "Can I get a refund?"), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.content, "4|Can I get a refund?");
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_stay_out_of_history() {
+        let mut registry = ToolRegistry::new();
+        registry.register(Box::new(UpperTool));
+        let agent = agent().with_tools(Arc::new(registry), 3);
+
+        // system + hi + call + result
+        let reply = agent
+            .respond(&incoming("x", "1", "hi"), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.content, "4|HI");
+
+        // system + hi + answer + again + call + result
+        let reply = agent
+            .respond(&incoming("x", "1", "again"), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.content, "6|AGAIN");
+    }
+
+    #[tokio::test]
+    async fn test_generation_and_tool_events() {
+        let mut registry = ToolRegistry::new();
+        registry.register(Box::new(UpperTool));
+        let bus = EventBus::new();
+        let mut events = bus.subscribe();
+        let agent = agent().with_tools(Arc::new(registry), 3).with_events(bus);
+        agent
+            .respond(&incoming("x", "1", "hi"), None)
+            .await
+            .unwrap();
+
+        let mut kinds = Vec::new();
+        while let Ok(record) = events.try_recv() {
+            if let Event::GenerationFinished { success, .. } = record.event {
+                assert!(success);
+            }
+            kinds.push(record.event.kind());
+        }
+        assert_eq!(
+            kinds,
+            ["generation.started", "tool.executed", "generation.finished"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_proactive_message_joins_history() {
+        let agent = agent();
+        // system + instruction
+        let posted = agent
+            .proactive("telegram", "1", "Post the standup")
+            .await
+            .unwrap();
+        assert!(posted.starts_with("2|[Scheduled task"));
+        assert!(posted.ends_with("Post the standup"));
+
+        // system + posted message + reply
+        let reply = agent
+            .respond(&incoming("telegram", "1", "thanks"), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.content, "3|thanks");
+    }
+
+    #[tokio::test]
+    async fn test_persona_switching() {
+        let persona = |name: &str, prompt: &str| PersonaConfig {
+            name: name.into(),
+            system_prompt: prompt.into(),
+            language: String::new(),
+            preset: String::new(),
+            chain: String::new(),
+            tools: None,
+            channels: vec!["telegram".into()],
+            chats: Vec::new(),
+            api_keys: Vec::new(),
+        };
+        let personas = PersonaSet::from_config(
+            &[
+                persona("sales", "You sell."),
+                persona("support", "You help."),
+            ],
+            &Identity::default(),
+            &GenerateParams::default(),
+        );
+        let agent = agent().with_personas(personas);
+        let command = async |text: &str| {
+            agent
+                .respond(&incoming("telegram", "1", text), None)
+                .await
+                .unwrap()
+                .unwrap()
+                .content
+        };
+        let system_prompt = async || {
+            let session = agent.session("telegram", "1");
+            let history = session.lock().await;
+            history.messages()[0].content.clone()
+        };
+
+        assert_eq!(
+            command("/persona").await,
+            "Persona: sales. Available: default, sales, support. Switch with /persona <name>."
+        );
+        assert_eq!(system_prompt().await, "You sell.");
+        assert_eq!(
+            command("/persona Support").await,
+            "Switched to persona support."
+        );
+        assert_eq!(system_prompt().await, "You help.");
+        assert!(
+            command("/persona pirate")
+                .await
+                .starts_with("Unknown persona")
+        );
+        assert_eq!(command("/persona default").await, "Persona reset to sales.");
+        assert_eq!(system_prompt().await, "You sell.");
+
+        // Other channels keep the identity prompt.
+        assert!(agent.persona("discord", "1").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_personas() {
+        let agent = agent();
+        agent
+            .respond(&incoming("telegram", "1", "hi"), None)
+            .await
+            .unwrap();
+
+        let mut config = BizClawConfig::default();
+        config.personas.push(PersonaConfig {
+            name: "sales".into(),
+            system_prompt: "You sell.".into(),
+            language: String::new(),
+            preset: String::new(),
+            chain: String::new(),
+            tools: None,
+            channels: vec!["telegram".into()],
+            chats: Vec::new(),
+            api_keys: Vec::new(),
+        });
+        config.default_temperature = 0.2;
+        agent.reload(&config).await;
+
+        assert_eq!(agent.persona("telegram", "1").unwrap().name, "sales");
+        assert_eq!(agent.settings().params.temperature, 0.2);
+        let session = agent.session("telegram", "1");
+        assert_eq!(session.lock().await.messages()[0].content, "You sell.");
+    }
+
+    #[tokio::test]
+    async fn test_replies_follow_the_output_policy() {
+        let policy = OutputPolicy::default()
+            .with_banned_phrases(&["secret"])
+            .with_disclaimer("Not financial advice.")
+            .with_retries(1);
+        let agent = agent().with_guardrails(Guardrails::default().with_channel("zalo", policy));
+
+        // Echoed twice — the second time from the correction — then masked.
+        let reply = agent
+            .respond(&incoming("zalo", "1", "tell me the secret"), None)
+            .await;
+        let reply = reply.unwrap().unwrap().content;
+        assert!(reply.starts_with("4|[Your reply breaks the reply policy."));
+        assert!(reply.ends_with("Do not say \"***\".]\n\nNot financial advice."));
+
+        let other = agent
+            .respond(&incoming("telegram", "1", "the secret"), None)
+            .await;
+        assert_eq!(other.unwrap().unwrap().content, "2|the secret");
+    }
+
+    #[tokio::test]
+    async fn test_messages_and_reactions_are_recorded() {
+        use bizclaw_memory::analytics::ReportPeriod;
+
+        let store = Arc::new(AnalyticsStore::in_memory().unwrap());
+        let agent = agent().with_analytics(store.clone());
+        let since = chrono::Utc::now() - chrono::Duration::minutes(1);
+
+        agent
+            .respond(&incoming("zalo", "1", "hi"), None)
+            .await
+            .unwrap();
+        let reaction = agent.respond(&incoming("zalo", "1", "👍"), None).await;
+        assert!(reaction.unwrap().is_none());
+
+        let report = store.report(ReportPeriod::Day, since, None).unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!((report[0].messages, report[0].users), (1, 1));
+        assert_eq!(report[0].positive_feedback, 1);
+        assert!(report[0].prompt_tokens > 0);
+    }
+
+    #[test]
+    fn test_persona_prompt() {
+        let identity = Identity {
+            name: "Mai".into(),
+            persona: "A cheerful shop assistant.".into(),
+            system_prompt: "Answer in Vietnamese.".into(),
+        };
+        assert_eq!(
+            persona_prompt(&identity),
+            "Answer in Vietnamese.\n\nYour name is Mai.\nPersona: A cheerful shop assistant."
+        );
+        // The default prompt already names the bot.
+        assert!(!persona_prompt(&Identity::default()).contains("Your name is"));
+    }
+}
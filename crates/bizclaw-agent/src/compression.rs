@@ -0,0 +1,212 @@
+//! Context compression — fold old turns into a rolling summary.
+//!
+//! When a thread no longer fits the model's context window, everything but
+//! the system prompt and the last few turns is summarized by the model
+//! itself and replaced with a single summary block. The next compression
+//! folds the previous summary in, so the block rolls forward instead of
+//! piling up.
+
+use crate::engine::estimate_tokens;
+use bizclaw_core::error::Result;
+use bizclaw_core::traits::Provider;
+use bizclaw_core::traits::provider::GenerateParams;
+use bizclaw_core::types::{Message, Role};
+
+/// Header of the summary block.
+pub const SUMMARY_MARKER: &str = "[Summary of earlier conversation]";
+
+const SUMMARIZE_PROMPT: &str = "You condense chat transcripts. Write a short summary of the \
+conversation below, keeping names, numbers, orders, promises and open questions. \
+Reply with the summary only.";
+
+/// When and how much to compress.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionPolicy {
+    /// Tokens the prompt may use (context window minus room for the answer).
+    pub budget_tokens: usize,
+    /// Compress once the prompt exceeds this fraction of the budget.
+    pub trigger_ratio: f32,
+    /// Newest messages always kept verbatim.
+    pub keep_recent: usize,
+    /// Length limit of the summary itself.
+    pub summary_tokens: u32,
+}
+
+impl CompressionPolicy {
+    /// Policy for a model with `context_window` tokens that answers with up
+    /// to `max_answer_tokens`.
+    pub fn for_window(context_window: usize, max_answer_tokens: usize) -> Self {
+        let budget_tokens = context_window.saturating_sub(max_answer_tokens).max(256);
+        Self {
+            budget_tokens,
+            trigger_ratio: 0.75,
+            keep_recent: 6,
+            summary_tokens: (budget_tokens / 4).clamp(64, 512) as u32,
+        }
+    }
+
+    pub fn needs_compression(&self, messages: &[Message]) -> bool {
+        estimate_tokens(messages) as f32 > self.budget_tokens as f32 * self.trigger_ratio
+    }
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        Self::for_window(2048, 256)
+    }
+}
+
+/// Whether `message` is a summary block produced by [`compress`].
+pub fn is_summary(message: &Message) -> bool {
+    message.role == Role::System && message.content.starts_with(SUMMARY_MARKER)
+}
+
+/// Summarize the older part of `messages`.
+///
+/// `messages` starts with the system prompt, optionally followed by a
+/// previous summary block. Returns the compressed list — system prompt,
+/// new summary, recent turns — or `None` when there is nothing old enough
+/// to fold.
+pub async fn compress(
+    provider: &dyn Provider,
+    params: &GenerateParams,
+    messages: &[Message],
+    policy: &CompressionPolicy,
+) -> Result<Option<Vec<Message>>> {
+    let Some((system, rest)) = messages.split_first() else {
+        return Ok(None);
+    };
+    let (previous, rest) = match rest.split_first() {
+        Some((first, tail)) if is_summary(first) => (Some(first), tail),
+        _ => (None, rest),
+    };
+    if rest.len() <= policy.keep_recent {
+        return Ok(None);
+    }
+    let (old, recent) = rest.split_at(rest.len() - policy.keep_recent);
+
+    let mut transcript = String::new();
+    if let Some(previous) = previous {
+        transcript.push_str(previous.content.trim_start_matches(SUMMARY_MARKER).trim());
+        transcript.push_str("\n\n");
+    }
+    for msg in old {
+        let speaker = match msg.role {
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+            Role::Tool => "Tool",
+            Role::System => "Note",
+        };
+        transcript.push_str(&format!("{speaker}: {}\n", msg.content.trim()));
+    }
+
+    let request = [Message::system(SUMMARIZE_PROMPT), Message::user(transcript)];
+    let params = GenerateParams {
+        temperature: 0.2,
+        max_tokens: policy.summary_tokens,
+        ..params.clone()
+    };
+    let response = provider.chat(&request, &[], &params).await?;
+    let summary = response.content.unwrap_or_default();
+    let summary = summary.trim();
+    if summary.is_empty() {
+        return Ok(None);
+    }
+
+    let mut compressed = Vec::with_capacity(recent.len() + 2);
+    compressed.push(system.clone());
+    compressed.push(Message::system(format!("{SUMMARY_MARKER}\n{summary}")));
+    compressed.extend_from_slice(recent);
+    Ok(Some(compressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bizclaw_core::types::{ModelInfo, ProviderResponse, ToolDefinition};
+
+    /// Replies with the number of transcript lines it was asked to summarize.
+    struct CountingSummarizer;
+
+    #[async_trait]
+    impl Provider for CountingSummarizer {
+        fn name(&self) -> &str {
+            "summarizer"
+        }
+        async fn chat(
+            &self,
+            messages: &[Message],
+            _tools: &[ToolDefinition],
+            _params: &GenerateParams,
+        ) -> Result<ProviderResponse> {
+            let lines = messages[1]
+                .content
+                .lines()
+                .filter(|l| !l.is_empty())
+                .count();
+            Ok(ProviderResponse::text(format!("{lines} lines")))
+        }
+        async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+            Ok(vec![])
+        }
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    fn policy() -> CompressionPolicy {
+        CompressionPolicy {
+            keep_recent: 2,
+            ..CompressionPolicy::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compress_keeps_prompt_and_recent_turns() {
+        let mut messages = vec![Message::system("Be brief.")];
+        for i in 0..5 {
+            messages.push(Message::user(format!("q{i}")));
+            messages.push(Message::assistant(format!("a{i}")));
+        }
+        let params = GenerateParams::default();
+        let out = compress(&CountingSummarizer, &params, &messages, &policy())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[0].content, "Be brief.");
+        assert!(is_summary(&out[1]));
+        assert!(out[1].content.ends_with("8 lines"));
+        assert_eq!(out[3].content, "a4");
+
+        // The previous summary is folded into the next one.
+        let mut next = out.clone();
+        next.push(Message::user("q5"));
+        let out = compress(&CountingSummarizer, &params, &next, &policy())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(out.len(), 4);
+        assert!(out[1].content.ends_with("2 lines"));
+        assert_eq!(out.iter().filter(|m| is_summary(m)).count(), 1);
+
+        // Nothing old enough to fold.
+        assert!(
+            compress(&CountingSummarizer, &params, &out[..3], &policy())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_needs_compression() {
+        let policy = CompressionPolicy::for_window(600, 200);
+        assert_eq!(policy.budget_tokens, 400);
+        let short = vec![Message::system("x"), Message::user("hi")];
+        assert!(!policy.needs_compression(&short));
+        let long = vec![Message::user("word ".repeat(400))];
+        assert!(policy.needs_compression(&long));
+    }
+}
@@ -35,6 +35,13 @@ impl ConversationContext {
         &self.messages
     }
 
+    /// Replace the whole context, e.g. with a compressed version of itself.
+    pub fn replace(&mut self, messages: Vec<Message>) {
+        self.messages = messages;
+        self.max_tokens_estimate = self.messages.iter().map(|m| m.content.len() / 4).sum();
+        self.trim_if_needed();
+    }
+
     /// Clear all messages except the system prompt (first message).
     pub fn clear(&mut self) {
         if !self.messages.is_empty() {
@@ -46,16 +53,21 @@ impl ConversationContext {
     }
 
     /// Trim old messages if we exceed the maximum.
-    /// Keeps the system prompt and the most recent messages.
+    /// Keeps the system prompt, a rolling summary right after it, and the
+    /// most recent messages.
     fn trim_if_needed(&mut self) {
         if self.messages.len() > self.max_messages {
-            // Keep system prompt (index 0) and last N-1 messages
-            let keep = self.max_messages - 1;
+            let pinned =
+                if self.messages.len() > 1 && crate::compression::is_summary(&self.messages[1]) {
+                    2
+                } else {
+                    1
+                };
+            let keep = self.max_messages.saturating_sub(pinned).max(1);
             let start = self.messages.len() - keep;
-            let system = self.messages[0].clone();
+            let head: Vec<Message> = self.messages[..pinned].to_vec();
             let recent: Vec<Message> = self.messages[start..].to_vec();
-            self.messages.clear();
-            self.messages.push(system);
+            self.messages = head;
             self.messages.extend(recent);
 
             // Recalculate token estimate
@@ -123,6 +135,21 @@ mod tests {
         assert_eq!(ctx.messages()[0].content, "System");
     }
 
+    #[test]
+    fn test_trim_keeps_summary() {
+        let mut ctx = ConversationContext::new(4);
+        ctx.replace(vec![
+            Message::system("System"),
+            Message::system(format!("{}\nearlier", crate::compression::SUMMARY_MARKER)),
+            Message::user("msg1"),
+        ]);
+        ctx.push(Message::assistant("msg2"));
+        ctx.push(Message::user("msg3"));
+        assert_eq!(ctx.len(), 4);
+        assert!(crate::compression::is_summary(&ctx.messages()[1]));
+        assert_eq!(ctx.messages()[2].content, "msg2");
+    }
+
     #[test]
     fn test_estimated_tokens() {
         let mut ctx = ConversationContext::new(10);
@@ -1,6 +1,6 @@
 //! Agent engine internals — core processing pipeline.
 
-use bizclaw_core::types::{Message, ProviderResponse};
+use bizclaw_core::types::{Message, ProviderResponse, Usage};
 
 /// Format a provider response for display.
 pub fn format_response(response: &ProviderResponse) -> String {
@@ -26,6 +26,19 @@ pub fn estimate_tokens(messages: &[Message]) -> usize {
         .sum()
 }
 
+/// The usage `response` reports, or an estimate for providers that don't.
+pub fn response_usage(response: &ProviderResponse, prompt: &[Message]) -> Usage {
+    response.usage.clone().unwrap_or_else(|| {
+        let prompt_tokens = estimate_tokens(prompt) as u32;
+        let completion_tokens = (response.content.as_deref().map_or(0, str::len) / 4) as u32;
+        Usage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    })
+}
+
 /// Check if conversation needs compaction.
 pub fn needs_compaction(messages: &[Message], max_tokens: usize) -> bool {
     estimate_tokens(messages) > max_tokens
@@ -0,0 +1,290 @@
+//! Long-term memory — durable facts about each user, extracted from
+//! conversations and recalled in later ones.
+//!
+//! Every few exchanges with a user (`[memory] facts_extract_every`), and
+//! when they reset their conversation, the model is asked for what is worth
+//! remembering about them ("prefers invoices in English"). The facts are
+//! embedded and kept in a [`FactStore`]; the ones most similar to each new
+//! message are placed before it in the prompt. Exchanges not yet extracted
+//! from are held in memory only and are lost on restart.
+
+use bizclaw_core::config::MemoryConfig;
+use bizclaw_core::error::Result;
+use bizclaw_core::traits::Provider;
+use bizclaw_core::traits::provider::GenerateParams;
+use bizclaw_core::types::{Message, Role};
+use bizclaw_memory::facts::FactStore;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+const EXTRACT_PROMPT: &str = "You keep long-term notes about a customer for a business assistant. \
+From the conversation below, list durable facts about the user worth knowing in future \
+conversations: preferences, circumstances, decisions and details they chose to share. Skip small \
+talk, one-off questions and anything about the assistant. Write one short fact per line, starting \
+with \"- \", in the third person and in the language of the conversation. If nothing is worth \
+keeping, answer NONE.";
+
+/// Facts kept from one extraction, at most.
+const MAX_FACTS_PER_EXTRACTION: usize = 10;
+
+/// Longer lines are not facts but rambling.
+const MAX_FACT_CHARS: usize = 200;
+
+pub struct FactMemory {
+    store: Arc<FactStore>,
+    /// Extracts and embeds facts.
+    provider: Arc<dyn Provider>,
+    extract_every: usize,
+    top_k: usize,
+    min_score: f32,
+    /// `channel:user_id` → exchanges not extracted from yet.
+    pending: Mutex<HashMap<String, Vec<Message>>>,
+}
+
+impl FactMemory {
+    pub fn new(store: Arc<FactStore>, provider: Arc<dyn Provider>) -> Self {
+        Self {
+            store,
+            provider,
+            extract_every: 5,
+            top_k: 5,
+            min_score: 0.4,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// With the `facts_*` settings of `[memory]`.
+    pub fn from_config(
+        config: &MemoryConfig,
+        store: Arc<FactStore>,
+        provider: Arc<dyn Provider>,
+    ) -> Self {
+        Self::new(store, provider)
+            .with_extract_every(config.facts_extract_every)
+            .with_recall(config.facts_top_k, config.facts_min_score)
+    }
+
+    pub fn with_extract_every(mut self, exchanges: usize) -> Self {
+        self.extract_every = exchanges.max(1);
+        self
+    }
+
+    pub fn with_recall(mut self, top_k: usize, min_score: f32) -> Self {
+        self.top_k = top_k;
+        self.min_score = min_score;
+        self
+    }
+
+    pub fn store(&self) -> &FactStore {
+        &self.store
+    }
+
+    /// The facts about the user most relevant to `query`, as a prompt block.
+    pub async fn recall(&self, channel: &str, user_id: &str, query: &str) -> Option<String> {
+        if self.top_k == 0 || user_id.is_empty() || query.trim().is_empty() {
+            return None;
+        }
+        let recalled = async {
+            let vector = self
+                .provider
+                .embed(&[query.to_string()])
+                .await?
+                .pop()
+                .unwrap_or_default();
+            self.store
+                .recall(channel, user_id, &vector, self.top_k, self.min_score)
+        };
+        match recalled.await {
+            Ok(facts) if !facts.is_empty() => {
+                let mut context = String::from("What you remember about this user:");
+                for (fact, _) in facts {
+                    context.push_str(&format!("\n- {}", fact.text));
+                }
+                Some(context)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                tracing::warn!("Failed to recall facts about {channel}:{user_id}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Note an exchange with the user. Once enough have piled up, they are
+    /// returned for [`extract`](Self::extract).
+    pub fn note_exchange(
+        &self,
+        channel: &str,
+        user_id: &str,
+        question: &str,
+        answer: &str,
+    ) -> Option<Vec<Message>> {
+        if user_id.is_empty() {
+            return None;
+        }
+        let mut pending = self.pending.lock().unwrap();
+        let key = format!("{channel}:{user_id}");
+        let transcript = pending.entry(key.clone()).or_default();
+        transcript.push(Message::user(question));
+        transcript.push(Message::assistant(answer));
+        if transcript.len() < self.extract_every * 2 {
+            return None;
+        }
+        pending.remove(&key)
+    }
+
+    /// The exchanges not extracted from yet, e.g. when the conversation ends.
+    pub fn take_pending(&self, channel: &str, user_id: &str) -> Option<Vec<Message>> {
+        self.pending
+            .lock()
+            .unwrap()
+            .remove(&format!("{channel}:{user_id}"))
+    }
+
+    /// Ask the model for the facts in `transcript` and store them. Returns
+    /// how many were stored, near-duplicates of known facts included.
+    pub async fn extract(
+        &self,
+        channel: &str,
+        user_id: &str,
+        transcript: &[Message],
+        params: &GenerateParams,
+    ) -> Result<usize> {
+        let mut conversation = String::new();
+        for msg in transcript {
+            let speaker = if msg.role == Role::User {
+                "User"
+            } else {
+                "Assistant"
+            };
+            conversation.push_str(&format!("{speaker}: {}\n", msg.content.trim()));
+        }
+        let params = GenerateParams {
+            temperature: 0.2,
+            ..params.clone()
+        };
+        let prompt = [Message::system(EXTRACT_PROMPT), Message::user(conversation)];
+        let response = self.provider.chat(&prompt, &[], &params).await?;
+        let facts = parse_facts(response.content.as_deref().unwrap_or_default());
+        if facts.is_empty() {
+            return Ok(0);
+        }
+
+        let vectors = self.provider.embed(&facts).await?;
+        for (fact, vector) in facts.iter().zip(&vectors) {
+            self.store.remember(channel, user_id, fact, vector)?;
+        }
+        tracing::debug!(
+            "Remembered {} fact(s) about {channel}:{user_id}",
+            vectors.len()
+        );
+        Ok(vectors.len())
+    }
+
+    /// Forget everything about the user, pending exchanges included.
+    pub fn forget(&self, channel: &str, user_id: &str) -> Result<usize> {
+        self.take_pending(channel, user_id);
+        self.store.clear(channel, user_id)
+    }
+}
+
+/// The facts in an extraction answer: its `- ` lines, or none for `NONE`.
+pub fn parse_facts(answer: &str) -> Vec<String> {
+    answer
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            line.strip_prefix("- ")
+                .or_else(|| line.strip_prefix("* "))
+                .or_else(|| line.strip_prefix("• "))
+        })
+        .map(str::trim)
+        .filter(|fact| !fact.is_empty() && fact.chars().count() <= MAX_FACT_CHARS)
+        .take(MAX_FACTS_PER_EXTRACTION)
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bizclaw_core::types::{ModelInfo, ProviderResponse, ToolDefinition};
+
+    /// Extracts a fixed answer, and embeds by keyword.
+    struct Notes;
+
+    #[async_trait]
+    impl Provider for Notes {
+        fn name(&self) -> &str {
+            "notes"
+        }
+
+        async fn chat(
+            &self,
+            _messages: &[Message],
+            _tools: &[ToolDefinition],
+            _params: &GenerateParams,
+        ) -> Result<ProviderResponse> {
+            Ok(ProviderResponse::text(
+                "Facts:\n- Prefers invoices in English\n- Owns a bakery",
+            ))
+        }
+
+        async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+            Ok(Vec::new())
+        }
+
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            Ok(texts
+                .iter()
+                .map(|t| {
+                    let t = t.to_lowercase();
+                    vec![
+                        t.contains("invoice") as u8 as f32,
+                        t.contains("bakery") as u8 as f32,
+                        0.1,
+                    ]
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_parse_facts() {
+        assert_eq!(
+            parse_facts("- Vegetarian\n* Lives in Huế\nnot a fact\n-  "),
+            ["Vegetarian", "Lives in Huế"]
+        );
+        assert!(parse_facts("NONE").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_extract_and_recall() {
+        let store = Arc::new(FactStore::in_memory().unwrap());
+        let memory = FactMemory::new(store, Arc::new(Notes)).with_extract_every(2);
+
+        assert!(memory.note_exchange("zalo", "7", "hi", "hello").is_none());
+        let transcript = memory
+            .note_exchange("zalo", "7", "send the invoice", "done")
+            .unwrap();
+        assert_eq!(transcript.len(), 4);
+        assert!(memory.take_pending("zalo", "7").is_none());
+
+        let params = GenerateParams::default();
+        let stored = memory.extract("zalo", "7", &transcript, &params).await;
+        assert_eq!(stored.unwrap(), 2);
+
+        let context = memory
+            .recall("zalo", "7", "where is my invoice?")
+            .await
+            .unwrap();
+        assert!(context.contains("- Prefers invoices in English"));
+        assert!(!context.contains("bakery"));
+        assert!(memory.recall("zalo", "8", "invoice").await.is_none());
+    }
+}
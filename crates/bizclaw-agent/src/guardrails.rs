@@ -0,0 +1,305 @@
+//! Output guardrails — policies replies must meet before they are sent.
+//!
+//! A channel's [`OutputPolicy`] limits a reply's length, bans phrases and
+//! may require it to be in the language the user is answered in. A reply
+//! breaking the policy is generated again with the model told what was
+//! wrong ([`correction`]); once the retries are used up, it is cut to
+//! length and banned phrases are masked ([`OutputPolicy::enforce`]). A
+//! wrong language can't be fixed that way and is let through.
+//!
+//! The disclaimer footer is added to the reply sent, not to the thread's
+//! history, so the model doesn't learn to write it itself.
+
+use crate::language::{self, Lang};
+use bizclaw_core::config::GuardrailsConfig;
+use std::collections::HashMap;
+
+const MASK: &str = "***";
+
+/// How a reply breaks its policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    TooLong { chars: usize, max: usize },
+    BannedPhrase(String),
+    WrongLanguage { expected: Lang },
+}
+
+impl Violation {
+    /// What the model is told to do about it.
+    fn instruction(&self) -> String {
+        match self {
+            Self::TooLong { chars, max } => {
+                format!("Keep it under {max} characters; it had {chars}.")
+            }
+            Self::BannedPhrase(phrase) => format!("Do not say \"{phrase}\"."),
+            Self::WrongLanguage { expected } => format!("Write it in {}.", expected.name()),
+        }
+    }
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLong { chars, max } => write!(f, "too long ({chars}/{max} chars)"),
+            Self::BannedPhrase(phrase) => write!(f, "banned phrase \"{phrase}\""),
+            Self::WrongLanguage { expected } => write!(f, "not in {}", expected.name()),
+        }
+    }
+}
+
+/// The turn asking the model to write its last reply again.
+pub fn correction(violations: &[Violation]) -> String {
+    let mut text = String::from("[Your reply breaks the reply policy. Write it again.");
+    for violation in violations {
+        text.push_str(&format!("\n- {}", violation.instruction()));
+    }
+    text.push(']');
+    text
+}
+
+/// The policy of one channel. The default one lets everything through.
+#[derive(Debug, Clone, Default)]
+pub struct OutputPolicy {
+    /// 0 is unlimited.
+    max_chars: usize,
+    banned_phrases: Vec<String>,
+    disclaimer: String,
+    enforce_language: bool,
+    /// Times a reply breaking the policy is generated again.
+    retries: u32,
+}
+
+impl OutputPolicy {
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars;
+        self
+    }
+
+    pub fn with_banned_phrases<S: AsRef<str>>(mut self, phrases: &[S]) -> Self {
+        self.banned_phrases = phrases
+            .iter()
+            .map(|p| p.as_ref().trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+        self
+    }
+
+    pub fn with_disclaimer(mut self, disclaimer: &str) -> Self {
+        self.disclaimer = disclaimer.trim().to_string();
+        self
+    }
+
+    pub fn with_language_enforced(mut self, enforce: bool) -> Self {
+        self.enforce_language = enforce;
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// How `answer` breaks the policy; `lang` is the language it should be
+    /// in, if known.
+    pub fn check(&self, answer: &str, lang: Option<Lang>) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        if answer.is_empty() {
+            return violations;
+        }
+        let chars = answer.chars().count();
+        if self.max_chars > 0 && chars > self.max_chars {
+            violations.push(Violation::TooLong {
+                chars,
+                max: self.max_chars,
+            });
+        }
+        for phrase in &self.banned_phrases {
+            if find_ignore_case(answer, phrase).is_some() {
+                violations.push(Violation::BannedPhrase(phrase.clone()));
+            }
+        }
+        if self.enforce_language
+            && let Some(expected) = lang
+            && language_of(answer).is_some_and(|found| found != expected)
+        {
+            violations.push(Violation::WrongLanguage { expected });
+        }
+        violations
+    }
+
+    /// `answer` with banned phrases masked, cut to `max_chars`.
+    pub fn enforce(&self, answer: &str) -> String {
+        let mut text = answer.to_string();
+        for phrase in &self.banned_phrases {
+            while let Some((start, end)) = find_ignore_case(&text, phrase) {
+                text.replace_range(start..end, MASK);
+            }
+        }
+        if self.max_chars > 0 {
+            text = cut(&text, self.max_chars);
+        }
+        text
+    }
+
+    /// `answer` with the disclaimer footer, as sent.
+    pub fn append_disclaimer(&self, answer: &str) -> String {
+        if self.disclaimer.is_empty() || answer.is_empty() || answer.ends_with(&self.disclaimer) {
+            answer.to_string()
+        } else {
+            format!("{answer}\n\n{}", self.disclaimer)
+        }
+    }
+}
+
+/// `[guardrails]`: the default policy and the channels' own.
+#[derive(Debug, Clone, Default)]
+pub struct Guardrails {
+    default: OutputPolicy,
+    channels: HashMap<String, OutputPolicy>,
+}
+
+impl Guardrails {
+    pub fn from_config(config: &GuardrailsConfig) -> Self {
+        let default = OutputPolicy::default()
+            .with_max_chars(config.max_chars)
+            .with_banned_phrases(&config.banned_phrases)
+            .with_disclaimer(&config.disclaimer)
+            .with_language_enforced(config.enforce_language)
+            .with_retries(config.retries);
+        let channels = config
+            .channels
+            .iter()
+            .map(|(channel, c)| {
+                let policy = OutputPolicy::default()
+                    .with_max_chars(c.max_chars.unwrap_or(config.max_chars))
+                    .with_banned_phrases(
+                        c.banned_phrases.as_ref().unwrap_or(&config.banned_phrases),
+                    )
+                    .with_disclaimer(c.disclaimer.as_ref().unwrap_or(&config.disclaimer))
+                    .with_language_enforced(c.enforce_language.unwrap_or(config.enforce_language))
+                    .with_retries(c.retries.unwrap_or(config.retries));
+                (channel.clone(), policy)
+            })
+            .collect();
+        Self { default, channels }
+    }
+
+    pub fn with_channel(mut self, channel: &str, policy: OutputPolicy) -> Self {
+        self.channels.insert(channel.to_string(), policy);
+        self
+    }
+
+    pub fn for_channel(&self, channel: &str) -> &OutputPolicy {
+        self.channels.get(channel).unwrap_or(&self.default)
+    }
+}
+
+/// The language most of `text`'s words are in. Word by word, so a
+/// Vietnamese name in an English reply doesn't make it Vietnamese.
+fn language_of(text: &str) -> Option<Lang> {
+    let (mut vi, mut en) = (0, 0);
+    for word in text.split_whitespace() {
+        match language::detect(word) {
+            Some(Lang::Vietnamese) => vi += 1,
+            Some(Lang::English) => en += 1,
+            None => {}
+        }
+    }
+    match vi.cmp(&en) {
+        std::cmp::Ordering::Greater => Some(Lang::Vietnamese),
+        std::cmp::Ordering::Less => Some(Lang::English),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+/// Byte range of the first case-insensitive match of `phrase` in `text`.
+fn find_ignore_case(text: &str, phrase: &str) -> Option<(usize, usize)> {
+    let phrase: Vec<char> = phrase.chars().flat_map(char::to_lowercase).collect();
+    if phrase.is_empty() {
+        return None;
+    }
+    'start: for (start, _) in text.char_indices() {
+        let mut matched = 0;
+        for (offset, c) in text[start..].char_indices() {
+            for lower in c.to_lowercase() {
+                if phrase.get(matched) != Some(&lower) {
+                    continue 'start;
+                }
+                matched += 1;
+            }
+            if matched == phrase.len() {
+                return Some((start, start + offset + c.len_utf8()));
+            }
+        }
+    }
+    None
+}
+
+/// `text` cut to at most `max` characters: after the last sentence in the
+/// second half if there is one, else at a word with an ellipsis.
+fn cut(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let end = text
+        .char_indices()
+        .nth(max.saturating_sub(1))
+        .map_or(text.len(), |(i, _)| i);
+    let head = &text[..end];
+    let half = head.len() / 2;
+    if let Some(i) = head.rfind(['.', '!', '?', '\n'])
+        && i >= half
+    {
+        return head[..=i].trim_end().to_string();
+    }
+    let head = match head.rfind(char::is_whitespace) {
+        Some(i) if i >= half => &head[..i],
+        _ => head,
+    };
+    format!("{}…", head.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_and_enforce() {
+        let policy = OutputPolicy::default()
+            .with_max_chars(40)
+            .with_banned_phrases(&["Cam kết lợi nhuận"])
+            .with_disclaimer("Chỉ mang tính tham khảo.")
+            .with_language_enforced(true);
+
+        let answer = "Quỹ này CAM KẾT LỢI NHUẬN 20% mỗi năm. Bạn muốn đăng ký không?";
+        let violations = policy.check(answer, Some(Lang::Vietnamese));
+        assert_eq!(
+            violations,
+            [
+                Violation::TooLong { chars: 62, max: 40 },
+                Violation::BannedPhrase("Cam kết lợi nhuận".into()),
+            ]
+        );
+        assert!(correction(&violations).contains("\n- Keep it under 40 characters; it had 62."));
+        assert_eq!(policy.enforce(answer), "Quỹ này *** 20% mỗi năm.");
+
+        let english = "The shop in Đà Nẵng opens at nine, and you can order it online.";
+        assert!(policy.check(english, Some(Lang::English)).len() == 1);
+        assert_eq!(
+            policy.check(english, Some(Lang::Vietnamese))[1],
+            Violation::WrongLanguage {
+                expected: Lang::Vietnamese
+            }
+        );
+        assert_eq!(cut("one two three four", 12), "one two…");
+
+        let sent = policy.append_disclaimer("Dạ vâng.");
+        assert_eq!(sent, "Dạ vâng.\n\nChỉ mang tính tham khảo.");
+        assert_eq!(policy.append_disclaimer(&sent), sent);
+        assert_eq!(policy.append_disclaimer(""), "");
+    }
+}
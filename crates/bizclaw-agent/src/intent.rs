@@ -0,0 +1,265 @@
+//! Intent routing — answering each message with the persona made for it.
+//!
+//! With `[[routing.intents]]` configured, the channel agent classifies every
+//! message before answering: by keyword rules, or by asking a model (ideally
+//! a small local one) to pick an intent from their descriptions. The thread
+//! then switches to the intent's persona and stays with it until a message
+//! of another intent arrives; messages matching none keep the current one.
+//! A persona chosen with `/persona` pins the thread and turns routing off
+//! for it.
+
+use bizclaw_core::config::{BizClawConfig, RoutingConfig};
+use bizclaw_core::traits::Provider;
+use bizclaw_core::traits::provider::GenerateParams;
+use bizclaw_core::types::Message;
+use std::sync::Arc;
+
+const CLASSIFY_PROMPT: &str = "You route customer messages for a business assistant. \
+Classify the message into one of the intents below. Answer with the intent name only, \
+or \"none\" if no intent fits.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Classifier {
+    #[default]
+    Keywords,
+    /// Keywords first, then the model.
+    Model,
+}
+
+#[derive(Debug, Clone)]
+pub struct Intent {
+    pub name: String,
+    pub persona: String,
+    pub description: String,
+    /// Lowercase.
+    keywords: Vec<String>,
+}
+
+impl Intent {
+    pub fn new(name: &str, persona: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            persona: persona.to_string(),
+            description: String::new(),
+            keywords: Vec::new(),
+        }
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn with_keywords<S: AsRef<str>>(mut self, keywords: &[S]) -> Self {
+        self.keywords = keywords
+            .iter()
+            .map(|k| k.as_ref().trim().to_lowercase())
+            .filter(|k| !k.is_empty())
+            .collect();
+        self
+    }
+}
+
+/// The route the model classifier asks, and the model to ask it for.
+#[derive(Clone)]
+struct ClassifierModel {
+    provider: Arc<dyn Provider>,
+    model: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct IntentRouter {
+    intents: Vec<Intent>,
+    classifier: Classifier,
+    /// `None` asks the provider passed to [`classify`](Self::classify).
+    model: Option<ClassifierModel>,
+}
+
+impl IntentRouter {
+    pub fn new(intents: Vec<Intent>) -> Self {
+        Self {
+            intents,
+            ..Default::default()
+        }
+    }
+
+    /// From `[routing]`; the classifier route is only created when the
+    /// model classifier is used.
+    pub fn from_config(config: &BizClawConfig) -> Self {
+        let routing: &RoutingConfig = &config.routing;
+        let intents = routing
+            .intents
+            .iter()
+            .map(|i| {
+                Intent::new(&i.name, &i.persona)
+                    .with_description(&i.description)
+                    .with_keywords(&i.keywords)
+            })
+            .collect();
+        let mut router = Self::new(intents);
+        if routing.classifier == "model" && !router.is_empty() {
+            router.classifier = Classifier::Model;
+            if !routing.model.is_empty() {
+                match bizclaw_providers::router::create_route(config, &routing.model) {
+                    Ok((provider, model)) => {
+                        router = router.with_model(provider.into(), model);
+                    }
+                    Err(e) => tracing::warn!(
+                        "Intent classifier '{}' unavailable, using the default provider: {e}",
+                        routing.model
+                    ),
+                }
+            }
+        }
+        router
+    }
+
+    /// Classify with the model too, asking `provider` for `model`.
+    pub fn with_model(mut self, provider: Arc<dyn Provider>, model: Option<String>) -> Self {
+        self.classifier = Classifier::Model;
+        self.model = Some(ClassifierModel { provider, model });
+        self
+    }
+
+    pub fn with_classifier(mut self, classifier: Classifier) -> Self {
+        self.classifier = classifier;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intents.is_empty()
+    }
+
+    /// The first intent with a keyword in `text`.
+    pub fn match_keywords(&self, text: &str) -> Option<&Intent> {
+        let text = text.to_lowercase();
+        self.intents
+            .iter()
+            .find(|i| i.keywords.iter().any(|k| text.contains(k.as_str())))
+    }
+
+    /// The intent of `text`, if any fits. The model classifier asks the
+    /// configured route, else `provider` with `params`.
+    pub async fn classify(
+        &self,
+        text: &str,
+        provider: &dyn Provider,
+        params: &GenerateParams,
+    ) -> Option<&Intent> {
+        if self.is_empty() || text.trim().is_empty() {
+            return None;
+        }
+        if let Some(intent) = self.match_keywords(text) {
+            return Some(intent);
+        }
+        if self.classifier != Classifier::Model {
+            return None;
+        }
+
+        let mut params = GenerateParams {
+            temperature: 0.0,
+            max_tokens: 16,
+            ..params.clone()
+        };
+        let provider = match &self.model {
+            Some(route) => {
+                if let Some(model) = &route.model {
+                    params.model = model.clone();
+                }
+                route.provider.as_ref()
+            }
+            None => provider,
+        };
+        let mut system = CLASSIFY_PROMPT.to_string();
+        for intent in &self.intents {
+            system.push_str(&format!("\n- {}: {}", intent.name, intent.description));
+        }
+        let prompt = [Message::system(system), Message::user(text)];
+        match provider.chat(&prompt, &[], &params).await {
+            Ok(response) => self.parse(response.content.as_deref().unwrap_or_default()),
+            Err(e) => {
+                tracing::warn!("Intent classification failed: {e}");
+                None
+            }
+        }
+    }
+
+    /// The intent a classifier answer names.
+    fn parse(&self, answer: &str) -> Option<&Intent> {
+        let answer = answer
+            .trim()
+            .trim_matches(|c: char| !c.is_alphanumeric())
+            .to_lowercase();
+        self.intents
+            .iter()
+            .find(|i| i.name.to_lowercase() == answer)
+            .or_else(|| {
+                self.intents
+                    .iter()
+                    .find(|i| answer.contains(&i.name.to_lowercase()))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bizclaw_core::error::Result;
+    use bizclaw_core::types::{ModelInfo, ProviderResponse, ToolDefinition};
+
+    struct Answers(&'static str);
+
+    #[async_trait]
+    impl Provider for Answers {
+        fn name(&self) -> &str {
+            "answers"
+        }
+
+        async fn chat(
+            &self,
+            _messages: &[Message],
+            _tools: &[ToolDefinition],
+            _params: &GenerateParams,
+        ) -> Result<ProviderResponse> {
+            Ok(ProviderResponse::text(self.0))
+        }
+
+        async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+            Ok(Vec::new())
+        }
+
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    fn router() -> IntentRouter {
+        IntentRouter::new(vec![
+            Intent::new("sales", "sales").with_keywords(&["giá", "Price"]),
+            Intent::new("support", "helpdesk").with_description("Broken orders, refunds"),
+        ])
+    }
+
+    #[tokio::test]
+    async fn test_classify() {
+        let params = GenerateParams::default();
+        let llm = Answers("Support.");
+
+        let keywords = router();
+        let intent = keywords.classify("Giá bao nhiêu?", &llm, &params).await;
+        assert_eq!(intent.unwrap().persona, "sales");
+        assert!(
+            keywords
+                .classify("my order broke", &llm, &params)
+                .await
+                .is_none()
+        );
+
+        let model = router().with_classifier(Classifier::Model);
+        let intent = model.classify("my order broke", &llm, &params).await;
+        assert_eq!(intent.unwrap().persona, "helpdesk");
+        let none = Answers("none");
+        assert!(model.classify("hello", &none, &params).await.is_none());
+    }
+}
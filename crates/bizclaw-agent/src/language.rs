@@ -0,0 +1,181 @@
+//! Reply language — detection and localized bot texts.
+//!
+//! [`detect`] tells Vietnamese from English: Vietnamese letters settle it,
+//! otherwise common words decide, with and without diacritics ("ko biết",
+//! "bao nhieu"). Messages too short or mixed to tell fall back to the
+//! channel's default from `[language]`.
+//!
+//! The bot's own texts (command replies, errors) come in both languages;
+//! [`Lang::pick`] selects one at the call site.
+
+use bizclaw_core::config::LanguageConfig;
+use std::collections::HashMap;
+
+/// Letters that only Vietnamese uses among the languages we expect.
+const VIETNAMESE_LETTERS: &str = "ăằắặẳẵâầấậẩẫđêềếệểễôồốộổỗơờớợởỡưừứựửữạảẹẻẽịỉĩọỏụủũỳỵỷỹ";
+
+const VIETNAMESE_WORDS: &[&str] = &[
+    "anh", "ban", "bao", "cho", "chua", "chi", "co", "cua", "duoc", "em", "gi", "gia", "hang",
+    "hoi", "khong", "ko", "la", "lam", "minh", "mua", "muon", "nay", "nha", "nhe", "nhieu", "oi",
+    "roi", "sao", "shop", "toi", "va", "vay", "voi", "xin",
+];
+
+const ENGLISH_WORDS: &[&str] = &[
+    "a", "about", "and", "are", "can", "do", "does", "for", "have", "hello", "hi", "how", "i",
+    "is", "it", "me", "my", "of", "please", "price", "thanks", "the", "this", "to", "want", "what",
+    "when", "where", "why", "you", "your",
+];
+
+/// A language the bot's own texts come in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lang {
+    Vietnamese,
+    English,
+}
+
+impl Lang {
+    /// `vi`, `en` or the language's name in either language.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "vi" | "vietnamese" | "tiếng việt" => Some(Self::Vietnamese),
+            "en" | "english" | "tiếng anh" => Some(Self::English),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Vietnamese => "Vietnamese",
+            Self::English => "English",
+        }
+    }
+
+    /// `vi` in Vietnamese, `en` in English.
+    pub fn pick<T>(self, vi: T, en: T) -> T {
+        match self {
+            Self::Vietnamese => vi,
+            Self::English => en,
+        }
+    }
+}
+
+/// The language of `text`, `None` when it can't be told.
+pub fn detect(text: &str) -> Option<Lang> {
+    let lower = text.to_lowercase();
+    if lower.chars().any(|c| VIETNAMESE_LETTERS.contains(c)) {
+        return Some(Lang::Vietnamese);
+    }
+    let (mut vi, mut en) = (0, 0);
+    for word in lower.split(|c: char| !c.is_alphanumeric()) {
+        vi += VIETNAMESE_WORDS.contains(&word) as usize;
+        en += ENGLISH_WORDS.contains(&word) as usize;
+    }
+    match vi.cmp(&en) {
+        std::cmp::Ordering::Greater => Some(Lang::Vietnamese),
+        std::cmp::Ordering::Less => Some(Lang::English),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+/// Which language to answer in, per `[language]`.
+#[derive(Debug, Clone)]
+pub struct LanguagePolicy {
+    detect: bool,
+    default: Lang,
+    channels: HashMap<String, Lang>,
+}
+
+impl Default for LanguagePolicy {
+    fn default() -> Self {
+        Self {
+            detect: true,
+            default: Lang::English,
+            channels: HashMap::new(),
+        }
+    }
+}
+
+impl LanguagePolicy {
+    pub fn from_config(config: &LanguageConfig) -> Self {
+        let parse = |s: &str| {
+            Lang::parse(s).unwrap_or_else(|| {
+                tracing::warn!("Unknown language '{s}' in [language], using English");
+                Lang::English
+            })
+        };
+        Self {
+            detect: config.detect,
+            default: parse(&config.default),
+            channels: config
+                .channels
+                .iter()
+                .map(|(channel, lang)| (channel.clone(), parse(lang)))
+                .collect(),
+        }
+    }
+
+    pub fn default_for(&self, channel: &str) -> Lang {
+        self.channels.get(channel).copied().unwrap_or(self.default)
+    }
+
+    /// The detected language of `text`, `None` when detection is off or
+    /// unsure.
+    pub fn detect(&self, text: &str) -> Option<Lang> {
+        if self.detect { detect(text) } else { None }
+    }
+
+    /// The language to answer `text` on `channel` in.
+    pub fn for_message(&self, channel: &str, text: &str) -> Lang {
+        self.detect(text)
+            .unwrap_or_else(|| self.default_for(channel))
+    }
+
+    /// The instruction added to the system prompt for `text`.
+    pub fn instruction(&self, channel: &str, text: &str) -> String {
+        let default = self.default_for(channel).name();
+        match self.detect(text) {
+            Some(lang) => format!("The user is writing in {0}. Reply in {0}.", lang.name()),
+            None if self.detect => {
+                format!("Reply in the language of the user's message; if unsure, in {default}.")
+            }
+            None => format!("Reply in {default}."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("Áo này còn size M không?"), Some(Lang::Vietnamese));
+        assert_eq!(
+            detect("ao nay gia bao nhieu vay shop"),
+            Some(Lang::Vietnamese)
+        );
+        assert_eq!(detect("What is the price of this?"), Some(Lang::English));
+        assert_eq!(detect("ok"), None);
+        assert_eq!(detect("/reset"), None);
+        assert_eq!(detect("Je voudrais un café"), None);
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = LanguagePolicy::from_config(&LanguageConfig {
+            detect: true,
+            default: "en".into(),
+            channels: [("zalo".to_string(), "vi".to_string())]
+                .into_iter()
+                .collect(),
+        });
+        assert_eq!(policy.for_message("zalo", "ok"), Lang::Vietnamese);
+        assert_eq!(policy.for_message("telegram", "ok"), Lang::English);
+        assert_eq!(policy.for_message("zalo", "How much?"), Lang::English);
+        assert_eq!(
+            policy.instruction("zalo", "cảm ơn"),
+            "The user is writing in Vietnamese. Reply in Vietnamese."
+        );
+        assert!(policy.instruction("zalo", "ok").ends_with("in Vietnamese."));
+    }
+}
@@ -9,10 +9,21 @@
 //! - **Session management**: Thread isolation via session_id
 //! - **Context tracking**: Monitor conversation length and estimate token usage
 
+pub mod channel_agent;
+pub mod compression;
 pub mod context;
 pub mod engine;
+pub mod facts;
+pub mod guardrails;
+pub mod intent;
+pub mod language;
 pub mod orchestrator;
+pub mod persona;
 pub mod proactive;
+pub mod profile;
+pub mod template;
+pub mod tool_loop;
+pub mod usage;
 
 use bizclaw_core::config::BizClawConfig;
 use bizclaw_core::error::Result;
@@ -21,6 +32,7 @@ use bizclaw_core::traits::SecurityPolicy;
 use bizclaw_core::traits::memory::MemoryBackend;
 use bizclaw_core::traits::provider::GenerateParams;
 use bizclaw_core::types::{Message, OutgoingMessage};
+use tracing::Instrument;
 
 /// Prompt cache — caches serialized system prompt + tool definitions to avoid
 /// re-serializing on every request.
@@ -105,7 +117,7 @@ impl Agent {
     pub fn new(config: BizClawConfig) -> Result<Self> {
         let provider = bizclaw_providers::create_provider(&config)?;
         let memory = bizclaw_memory::create_memory(&config.memory)?;
-        let tools = bizclaw_tools::ToolRegistry::with_defaults();
+        let tools = bizclaw_tools::ToolRegistry::with_config(&config.tools);
         let security = bizclaw_security::DefaultSecurityPolicy::new(config.autonomy.clone());
 
         // 3-Tier Memory: assemble brain context from workspace files
@@ -157,7 +169,7 @@ impl Agent {
             bizclaw_providers::create_provider(&config_clone)
         }).await.map_err(|e| bizclaw_core::error::BizClawError::Other(format!("spawn: {e}")))??;
         let memory = bizclaw_memory::create_memory(&config.memory)?;
-        let mut tools = bizclaw_tools::ToolRegistry::with_defaults();
+        let mut tools = bizclaw_tools::ToolRegistry::with_config(&config.tools);
         let security = bizclaw_security::DefaultSecurityPolicy::new(config.autonomy.clone());
 
         // Connect MCP servers and register their tools
@@ -261,6 +273,7 @@ impl Agent {
     ///
     /// Uses Think-Act-Observe loop (inspired by [GoClaw](https://github.com/nextlevelbuilder/goclaw))
     /// with Quality Gate evaluation (inspired by [OpenFang](https://github.com/RightNow-AI/openfang)).
+    #[tracing::instrument(name = "agent", skip_all, fields(session = %self.session_id))]
     pub async fn process(&mut self, user_message: &str) -> Result<String> {
         let mut compacted = false;
         let estimated_tokens = self.estimate_tokens();
@@ -340,19 +353,8 @@ impl Agent {
                     results.push(Message::tool(format!("Permission denied: '{cmd}'"), &tc.id));
                     continue;
                 }
-                if let Some(tool) = self.tools.get(&tc.function.name) {
-                    match tool.execute(&tc.function.arguments).await {
-                        Ok(r) => {
-                            let out = if r.output.len() > 4000 {
-                                format!("{}...[truncated]", &r.output[..4000])
-                            } else { r.output };
-                            results.push(Message::tool(&out, &tc.id));
-                        }
-                        Err(e) => results.push(Message::tool(format!("Error: {e}"), &tc.id)),
-                    }
-                } else {
-                    results.push(Message::tool(format!("Not found: {}", tc.function.name), &tc.id));
-                }
+                let r = self.tools.execute(tc).await;
+                results.push(Message::tool(r.output, &tc.id));
             }
 
             // OBSERVE
@@ -543,11 +545,42 @@ impl Agent {
 
     /// Auto-compact conversation when context is too large.
     /// Keeps system prompt + summary of old messages + recent messages.
+    /// The model writes the summary; if it can't, old messages are clipped.
     async fn compact_conversation(&mut self) {
         if self.conversation.len() <= 10 {
             return;
         }
 
+        let policy = compression::CompressionPolicy {
+            keep_recent: 10,
+            ..compression::CompressionPolicy::for_window(
+                self.config.brain.context_length as usize,
+                self.config.brain.max_tokens as usize,
+            )
+        };
+        let params = GenerateParams {
+            model: self.config.default_model.clone(),
+            ..Default::default()
+        };
+        match compression::compress(self.provider.as_ref(), &params, &self.conversation, &policy)
+            .await
+        {
+            Ok(Some(compressed)) => {
+                tracing::info!(
+                    "📦 Summarized {} → {} messages",
+                    self.conversation.len(),
+                    compressed.len()
+                );
+                if let Err(e) = self.daily_log.save_compaction(&compressed[1].content) {
+                    tracing::warn!("Failed to save compaction to daily log: {e}");
+                }
+                self.conversation = compressed;
+                return;
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Summarization failed, clipping old messages: {e}"),
+        }
+
         let system = self.conversation[0].clone();
 
         // Summarize old messages (keep last 10)
@@ -614,13 +647,20 @@ impl Agent {
         &mut self,
         msg: &bizclaw_core::types::IncomingMessage,
     ) -> Result<OutgoingMessage> {
-        let response = self.process(&msg.content).await?;
-        Ok(OutgoingMessage {
-            thread_id: msg.thread_id.clone(),
-            content: response,
-            thread_type: msg.thread_type.clone(),
-            reply_to: None,
-        })
+        // Channel messages have no HTTP request to correlate with; give
+        // each its own id.
+        let span = tracing::info_span!(
+            "message",
+            channel = %msg.channel,
+            thread = %msg.thread_id,
+            request_id = %uuid::Uuid::new_v4(),
+        );
+        let response = self.process(&msg.content).instrument(span).await?;
+        Ok(OutgoingMessage::text(
+            msg.thread_id.clone(),
+            response,
+            msg.thread_type.clone(),
+        ))
     }
 
     /// Get provider name.
@@ -0,0 +1,252 @@
+//! Personas — per-channel, per-chat and per-API-key variations of the bot.
+//!
+//! A persona replaces the `[identity]` system prompt, can pin the reply
+//! language, pick a sampling preset and provider chain, and narrow the tools
+//! the model may call.
+//! [`PersonaSet::for_thread`] picks the persona assigned to a thread; the
+//! channel agent lets users override that with `/persona <name>`.
+
+use bizclaw_core::config::PersonaConfig;
+use bizclaw_core::traits::identity::Identity;
+use bizclaw_core::traits::provider::GenerateParams;
+use bizclaw_providers::router::CHAIN_PREFIX;
+
+/// Command that lists personas or switches the thread's persona.
+pub const PERSONA_COMMAND: &str = "/persona";
+
+/// Named sampling settings, so personas don't need raw temperatures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplingPreset {
+    /// Factual answers, support, data lookups.
+    Precise,
+    Balanced,
+    /// Marketing copy, brainstorming.
+    Creative,
+}
+
+impl SamplingPreset {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "precise" => Some(Self::Precise),
+            "balanced" => Some(Self::Balanced),
+            "creative" => Some(Self::Creative),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, params: &mut GenerateParams) {
+        let (temperature, top_p) = match self {
+            Self::Precise => (0.2, 0.8),
+            Self::Balanced => (0.7, 0.9),
+            Self::Creative => (1.0, 0.95),
+        };
+        params.temperature = temperature;
+        params.top_p = top_p;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Persona {
+    pub name: String,
+    /// Full system prompt, language instruction included.
+    pub system_prompt: String,
+    pub params: GenerateParams,
+    /// Language the persona always replies in, whatever the user writes.
+    pub language: Option<String>,
+    /// Tools the persona may call; `None` allows every tool.
+    pub tools: Option<Vec<String>>,
+    channels: Vec<String>,
+    chats: Vec<String>,
+    api_keys: Vec<String>,
+}
+
+impl Persona {
+    /// `identity` supplies the prompt when the persona has none, `base` the
+    /// sampling settings the preset adjusts.
+    pub fn from_config(config: &PersonaConfig, identity: &Identity, base: &GenerateParams) -> Self {
+        let mut system_prompt = if config.system_prompt.trim().is_empty() {
+            crate::channel_agent::persona_prompt(identity)
+        } else {
+            config.system_prompt.trim().to_string()
+        };
+        if !config.language.trim().is_empty() {
+            system_prompt.push_str(&format!("\n\nAlways reply in {}.", config.language.trim()));
+        }
+
+        let mut params = base.clone();
+        if !config.preset.is_empty() {
+            match SamplingPreset::parse(&config.preset) {
+                Some(preset) => preset.apply(&mut params),
+                None => tracing::warn!(
+                    "Persona '{}': unknown preset '{}' (precise, balanced, creative)",
+                    config.name,
+                    config.preset
+                ),
+            }
+        }
+        if !config.chain.is_empty() {
+            params.model = format!("{CHAIN_PREFIX}{}", config.chain);
+        }
+
+        Self {
+            name: config.name.clone(),
+            system_prompt,
+            params,
+            language: Some(config.language.trim().to_string()).filter(|l| !l.is_empty()),
+            tools: config.tools.clone(),
+            channels: config.channels.clone(),
+            chats: config.chats.clone(),
+            api_keys: config.api_keys.clone(),
+        }
+    }
+
+    fn has_chat(&self, channel: &str, thread_id: &str) -> bool {
+        self.chats.iter().any(|chat| match chat.split_once(':') {
+            Some((ch, thread)) => ch == channel && thread == thread_id,
+            None => chat == thread_id,
+        })
+    }
+}
+
+/// All configured personas.
+#[derive(Debug, Clone, Default)]
+pub struct PersonaSet {
+    personas: Vec<Persona>,
+}
+
+impl PersonaSet {
+    pub fn from_config(
+        configs: &[PersonaConfig],
+        identity: &Identity,
+        base: &GenerateParams,
+    ) -> Self {
+        let mut personas: Vec<Persona> = Vec::new();
+        for config in configs {
+            if config.name.trim().is_empty() {
+                tracing::warn!("Persona without a name ignored");
+            } else if personas.iter().any(|p| p.name == config.name) {
+                tracing::warn!("Duplicate persona '{}' ignored", config.name);
+            } else {
+                personas.push(Persona::from_config(config, identity, base));
+            }
+        }
+        Self { personas }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.personas.is_empty()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.personas.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    /// Case-insensitive lookup by name.
+    pub fn get(&self, name: &str) -> Option<&Persona> {
+        self.personas
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// The persona assigned to a thread: by chat first, then by channel.
+    pub fn for_thread(&self, channel: &str, thread_id: &str) -> Option<&Persona> {
+        self.personas
+            .iter()
+            .find(|p| p.has_chat(channel, thread_id))
+            .or_else(|| {
+                self.personas
+                    .iter()
+                    .find(|p| p.channels.iter().any(|c| c == channel))
+            })
+    }
+
+    /// The persona an API key answers as.
+    pub fn for_api_key(&self, key: &str) -> Option<&Persona> {
+        self.personas
+            .iter()
+            .find(|p| p.api_keys.iter().any(|k| constant_time_eq(k, key)))
+    }
+}
+
+/// Compare secrets without leaking how much of them matched.
+pub fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(name: &str) -> PersonaConfig {
+        PersonaConfig {
+            name: name.into(),
+            system_prompt: String::new(),
+            language: String::new(),
+            preset: String::new(),
+            chain: String::new(),
+            tools: None,
+            channels: Vec::new(),
+            chats: Vec::new(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    fn personas() -> PersonaSet {
+        let mut sales = config("sales");
+        sales.system_prompt = "You sell.".into();
+        sales.language = "Vietnamese".into();
+        sales.preset = "creative".into();
+        sales.chain = "vip".into();
+        sales.channels = vec!["zalo".into()];
+        sales.api_keys = vec!["sk-widget".into()];
+        let mut support = config("support");
+        support.preset = "precise".into();
+        support.tools = Some(vec!["calculator".into()]);
+        support.chats = vec!["zalo:42".into(), "777".into()];
+        PersonaSet::from_config(
+            &[sales, support, config("sales"), config("")],
+            &Identity::default(),
+            &GenerateParams::default(),
+        )
+    }
+
+    #[test]
+    fn test_persona_from_config() {
+        let set = personas();
+        assert_eq!(set.names(), ["sales", "support"]);
+
+        let sales = set.get("Sales").unwrap();
+        assert_eq!(
+            sales.system_prompt,
+            "You sell.\n\nAlways reply in Vietnamese."
+        );
+        assert_eq!(sales.params.temperature, 1.0);
+        assert_eq!(sales.params.model, "chain:vip");
+        assert!(sales.tools.is_none());
+
+        let support = set.get("support").unwrap();
+        assert!(support.system_prompt.starts_with("You are BizClaw"));
+        assert_eq!(support.params.temperature, 0.2);
+    }
+
+    #[test]
+    fn test_selection() {
+        let set = personas();
+        let name = |p: Option<&Persona>| p.map(|p| p.name.clone());
+        // A chat beats its channel.
+        assert_eq!(name(set.for_thread("zalo", "42")), Some("support".into()));
+        assert_eq!(name(set.for_thread("zalo", "1")), Some("sales".into()));
+        assert_eq!(
+            name(set.for_thread("telegram", "777")),
+            Some("support".into())
+        );
+        assert_eq!(name(set.for_thread("telegram", "1")), None);
+
+        assert_eq!(name(set.for_api_key("sk-widget")), Some("sales".into()));
+        assert_eq!(name(set.for_api_key("sk-other")), None);
+    }
+}
@@ -0,0 +1,340 @@
+//! User preferences set from chat.
+//!
+//! Users tell the bot how to treat them in plain words — "call me Anh",
+//! "reply in English", "gọi tôi là Anh", "trả lời bằng tiếng Anh" — or with
+//! commands:
+//!
+//! ```text
+//! /profile                 show what is stored
+//! /profile forget          delete it
+//! /note <text>             add a note ("allergic to peanuts")
+//! /subscribe <topic>       opt in to a topic
+//! /unsubscribe <topic>     opt out
+//! ```
+//!
+//! [`parse`] recognizes these, [`apply`] changes a [`UserProfile`] and
+//! returns the confirmation, and [`prompt_context`] turns a profile into the
+//! system note placed before each of the user's messages.
+
+use crate::language::Lang;
+use bizclaw_memory::profile::UserProfile;
+
+/// Command that shows or forgets the sender's profile.
+pub const PROFILE_COMMAND: &str = "/profile";
+
+/// Longest accepted name or language, in words.
+const MAX_WORDS: usize = 4;
+
+/// First words of "call me …" messages that are not names.
+const NOT_NAMES: &[&str] = &[
+    "at", "back", "later", "tomorrow", "now", "when", "if", "on", "after", "before", "again",
+];
+
+const NAME_PREFIXES: &[&str] = &[
+    "call me ",
+    "please call me ",
+    "my name is ",
+    "gọi tôi là ",
+    "gọi mình là ",
+    "gọi em là ",
+    "gọi anh là ",
+    "gọi chị là ",
+    "tên tôi là ",
+    "tên mình là ",
+];
+
+const LANGUAGE_PREFIXES: &[&str] = &[
+    "reply in ",
+    "please reply in ",
+    "answer in ",
+    "respond in ",
+    "talk to me in ",
+    "trả lời bằng ",
+    "hãy trả lời bằng ",
+    "nói tiếng ",
+];
+
+/// A change to (or question about) the sender's profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProfileUpdate {
+    Name(String),
+    Language(String),
+    Note(String),
+    OptIn(String),
+    OptOut(String),
+    Show,
+    Forget,
+}
+
+/// The profile update a message asks for, `None` for ordinary messages.
+///
+/// Plain-word requests must be the whole message, so "call me at 5pm" or
+/// "can you reply in English and …" go to the model as usual.
+pub fn parse(text: &str) -> Option<ProfileUpdate> {
+    let text = text.trim().trim_end_matches(['.', '!']);
+    if let Some(rest) = text.strip_prefix('/') {
+        let (command, arg) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let command = command.split('@').next()?.to_ascii_lowercase();
+        let arg = arg.trim();
+        return match (command.as_str(), arg) {
+            ("profile", "") => Some(ProfileUpdate::Show),
+            ("profile", "forget" | "reset" | "clear") => Some(ProfileUpdate::Forget),
+            ("note", note) if !note.is_empty() => Some(ProfileUpdate::Note(note.to_string())),
+            ("subscribe", topic) if !topic.is_empty() => {
+                Some(ProfileUpdate::OptIn(topic.to_lowercase()))
+            }
+            ("unsubscribe", topic) if !topic.is_empty() => {
+                Some(ProfileUpdate::OptOut(topic.to_lowercase()))
+            }
+            _ => None,
+        };
+    }
+
+    let lower = text.to_lowercase();
+    if let Some(name) = strip_any(text, &lower, NAME_PREFIXES)
+        && is_short_phrase(name)
+        && !NOT_NAMES.contains(&name.split_whitespace().next()?.to_lowercase().as_str())
+    {
+        return Some(ProfileUpdate::Name(name.to_string()));
+    }
+    if let Some(language) = strip_any(text, &lower, LANGUAGE_PREFIXES)
+        && is_short_phrase(language)
+    {
+        return language_name(language).map(ProfileUpdate::Language);
+    }
+    None
+}
+
+/// `text` after the first of `prefixes` it starts with, ignoring case.
+fn strip_any<'a>(text: &'a str, lower: &str, prefixes: &[&str]) -> Option<&'a str> {
+    let prefix = prefixes.iter().find(|p| lower.starts_with(*p))?;
+    // Lowercasing can change byte lengths, so count characters.
+    let skip = prefix.chars().count();
+    let start = text.char_indices().nth(skip).map_or(text.len(), |(i, _)| i);
+    Some(text[start..].trim())
+}
+
+fn is_short_phrase(s: &str) -> bool {
+    let words = s.split_whitespace().count();
+    (1..=MAX_WORDS).contains(&words)
+        && s.chars()
+            .all(|c| c.is_alphabetic() || c == ' ' || c == '-' || c == '\'')
+}
+
+/// `English` for "english", "tiếng Anh" or "en". Only known languages are
+/// accepted, so "answer in detail" isn't taken for one.
+fn language_name(s: &str) -> Option<String> {
+    let lower = s.to_lowercase();
+    let lower = lower.strip_prefix("tiếng ").unwrap_or(&lower);
+    let name = match lower {
+        "en" | "english" | "anh" => "English",
+        "vi" | "vietnamese" | "việt" | "viet" => "Vietnamese",
+        "zh" | "chinese" | "trung" | "hoa" => "Chinese",
+        "ja" | "japanese" | "nhật" => "Japanese",
+        "ko" | "korean" | "hàn" => "Korean",
+        "th" | "thai" | "thái" => "Thai",
+        "fr" | "french" | "pháp" => "French",
+        "de" | "german" | "đức" => "German",
+        "es" | "spanish" | "tây ban nha" => "Spanish",
+        "ru" | "russian" | "nga" => "Russian",
+        "id" | "indonesian" => "Indonesian",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Apply `update` to `profile` and return the reply for the user, in
+/// `lang`. [`ProfileUpdate::Forget`] is the caller's to carry out.
+pub fn apply(profile: &mut UserProfile, update: ProfileUpdate, lang: Lang) -> String {
+    match update {
+        ProfileUpdate::Name(name) => {
+            let reply = lang.pick(
+                format!("Dạ, mình sẽ gọi bạn là {name}."),
+                format!("Okay, I'll call you {name}."),
+            );
+            profile.name = Some(name);
+            reply
+        }
+        ProfileUpdate::Language(language) => {
+            let reply = lang.pick(
+                format!("Dạ, từ giờ mình sẽ trả lời bằng {language}."),
+                format!("Okay, I'll reply in {language}."),
+            );
+            profile.language = Some(language);
+            reply
+        }
+        ProfileUpdate::Note(note) => {
+            profile.notes.push(note);
+            lang.pick("Mình đã ghi nhớ.", "Noted.").into()
+        }
+        ProfileUpdate::OptIn(topic) => {
+            let reply = lang.pick(
+                format!("Đã đăng ký nhận {topic}. Gửi /unsubscribe {topic} để huỷ."),
+                format!("Subscribed to {topic}. Send /unsubscribe {topic} to stop."),
+            );
+            if !profile.opt_ins.contains(&topic) {
+                profile.opt_ins.push(topic);
+            }
+            reply
+        }
+        ProfileUpdate::OptOut(topic) => {
+            let before = profile.opt_ins.len();
+            profile.opt_ins.retain(|t| *t != topic);
+            if profile.opt_ins.len() < before {
+                lang.pick(
+                    format!("Đã huỷ đăng ký {topic}."),
+                    format!("Unsubscribed from {topic}."),
+                )
+            } else {
+                lang.pick(
+                    format!("Bạn chưa đăng ký {topic}."),
+                    format!("You weren't subscribed to {topic}."),
+                )
+            }
+        }
+        ProfileUpdate::Show => describe(profile, lang),
+        ProfileUpdate::Forget => lang
+            .pick(
+                "Mình đã xoá thông tin của bạn.",
+                "I've forgotten your preferences.",
+            )
+            .into(),
+    }
+}
+
+/// The profile as shown by `/profile`.
+pub fn describe(profile: &UserProfile, lang: Lang) -> String {
+    if profile.is_empty() {
+        return lang
+            .pick(
+                "Mình chưa biết gì về bạn. Thử nhắn \"gọi tôi là <tên>\" hoặc \
+                 \"trả lời bằng tiếng Anh\".",
+                "I don't know anything about you yet. Try \"call me <name>\" or \
+                 \"reply in <language>\".",
+            )
+            .into();
+    }
+    let label = |vi, en| lang.pick(vi, en);
+    let mut lines = Vec::new();
+    if let Some(name) = &profile.name {
+        lines.push(format!("{}: {name}", label("Tên", "Name")));
+    }
+    if let Some(language) = &profile.language {
+        lines.push(format!("{}: {language}", label("Ngôn ngữ", "Language")));
+    }
+    for note in &profile.notes {
+        lines.push(format!("{}: {note}", label("Ghi chú", "Note")));
+    }
+    if !profile.opt_ins.is_empty() {
+        lines.push(format!(
+            "{}: {}",
+            label("Đã đăng ký", "Subscribed to"),
+            profile.opt_ins.join(", ")
+        ));
+    }
+    lines.push(lang.pick(
+        format!("Gửi {PROFILE_COMMAND} forget để xoá."),
+        format!("Send {PROFILE_COMMAND} forget to delete this."),
+    ));
+    lines.join("\n")
+}
+
+/// The system note telling the model about the user, `None` for an empty
+/// profile.
+pub fn prompt_context(profile: &UserProfile) -> Option<String> {
+    if profile.is_empty() {
+        return None;
+    }
+    let mut context = String::from("About the user you are talking to:");
+    if let Some(name) = &profile.name {
+        context.push_str(&format!("\n- Address them as {name}."));
+    }
+    if let Some(language) = &profile.language {
+        context.push_str(&format!("\n- Always reply in {language}."));
+    }
+    for note in &profile.notes {
+        context.push_str(&format!("\n- {note}"));
+    }
+    if !profile.opt_ins.is_empty() {
+        context.push_str(&format!(
+            "\n- Subscribed to: {}.",
+            profile.opt_ins.join(", ")
+        ));
+    }
+    Some(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let name = |s: &str| Some(ProfileUpdate::Name(s.into()));
+        let language = |s: &str| Some(ProfileUpdate::Language(s.into()));
+        assert_eq!(parse("Call me Anh"), name("Anh"));
+        assert_eq!(parse("gọi tôi là Minh Anh."), name("Minh Anh"));
+        assert_eq!(parse("call me at 5pm"), None);
+        assert_eq!(parse("call me back tomorrow"), None);
+        assert_eq!(parse("Reply in English please and thanks a lot"), None);
+        assert_eq!(parse("reply in english"), language("English"));
+        assert_eq!(parse("Trả lời bằng tiếng Việt"), language("Vietnamese"));
+        assert_eq!(parse("reply in german"), language("German"));
+        assert_eq!(parse("nói tiếng Anh"), language("English"));
+        assert_eq!(parse("answer in detail"), None);
+
+        assert_eq!(parse("/profile@shop_bot"), Some(ProfileUpdate::Show));
+        assert_eq!(parse("/profile forget"), Some(ProfileUpdate::Forget));
+        assert_eq!(
+            parse("/subscribe Promotions"),
+            Some(ProfileUpdate::OptIn("promotions".into()))
+        );
+        assert_eq!(parse("/note"), None);
+        assert_eq!(parse("/reset"), None);
+    }
+
+    #[test]
+    fn test_apply_and_prompt() {
+        let mut profile = UserProfile::new("zalo", "u1");
+        assert_eq!(prompt_context(&profile), None);
+
+        assert_eq!(
+            apply(
+                &mut profile,
+                ProfileUpdate::Name("Anh".into()),
+                Lang::English
+            ),
+            "Okay, I'll call you Anh."
+        );
+        apply(
+            &mut profile,
+            ProfileUpdate::Language("English".into()),
+            Lang::English,
+        );
+        let reply = apply(
+            &mut profile,
+            ProfileUpdate::OptIn("news".into()),
+            Lang::Vietnamese,
+        );
+        assert_eq!(reply, "Đã đăng ký nhận news. Gửi /unsubscribe news để huỷ.");
+        apply(
+            &mut profile,
+            ProfileUpdate::OptIn("news".into()),
+            Lang::English,
+        );
+        assert_eq!(profile.opt_ins, ["news"]);
+        assert_eq!(
+            apply(
+                &mut profile,
+                ProfileUpdate::OptOut("sales".into()),
+                Lang::English
+            ),
+            "You weren't subscribed to sales."
+        );
+
+        let context = prompt_context(&profile).unwrap();
+        assert!(context.contains("Address them as Anh."));
+        assert!(context.contains("Always reply in English."));
+        assert!(describe(&profile, Lang::English).starts_with("Name: Anh\nLanguage: English"));
+    }
+}
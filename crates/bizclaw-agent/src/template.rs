@@ -0,0 +1,304 @@
+//! Prompt templates — the agent's prompts as named text operators can edit.
+//!
+//! Each template is a piece of text with `{{variable}}` placeholders and
+//! `{{> partial}}` includes of other templates:
+//!
+//! ```text
+//! {{prompt}}
+//!
+//! Today is {{date}}. {{> policies}}
+//! ```
+//!
+//! The built-in ones are [`SYSTEM`] and [`SCHEDULED`]; `<name>.md` files in
+//! the `[templates]` directory replace them or add partials, and files in a
+//! `<persona>/` subdirectory do so for that persona only. Unknown variables
+//! render empty, and `[templates.vars]` adds variables every template sees.
+//! Files are read at start and again when the config is reloaded.
+
+use bizclaw_core::config::TemplatesConfig;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// The system prompt. Variables: `prompt` (the `[identity]` or persona
+/// prompt), `name`, `persona`, `channel` and `date`.
+pub const SYSTEM: &str = "system";
+
+/// The turn asking for a scheduled post. Variables: `instruction`,
+/// `channel` and `date`.
+pub const SCHEDULED: &str = "scheduled";
+
+/// Template file extensions, the first one for new files.
+pub const EXTENSIONS: &[&str] = &["md", "txt"];
+
+/// Includes nested deeper are dropped, so a partial including itself can't
+/// loop forever.
+const MAX_DEPTH: usize = 8;
+
+const BUILTIN: &[(&str, &str)] = &[
+    (SYSTEM, "{{prompt}}"),
+    (
+        SCHEDULED,
+        "[Scheduled task — write the message to post in this chat]\n{{instruction}}",
+    ),
+];
+
+/// Where a template comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateSource {
+    Builtin,
+    File,
+}
+
+#[derive(Debug, Clone)]
+struct Template {
+    text: String,
+    source: TemplateSource,
+}
+
+/// The built-in templates, overridden by the files loaded over them.
+#[derive(Debug, Clone)]
+pub struct PromptTemplates {
+    templates: HashMap<String, Template>,
+    /// Lowercase persona name → its own templates.
+    personas: HashMap<String, HashMap<String, Template>>,
+    vars: BTreeMap<String, String>,
+}
+
+impl Default for PromptTemplates {
+    fn default() -> Self {
+        let templates = BUILTIN
+            .iter()
+            .map(|(name, text)| {
+                let template = Template {
+                    text: text.to_string(),
+                    source: TemplateSource::Builtin,
+                };
+                (name.to_string(), template)
+            })
+            .collect();
+        Self {
+            templates,
+            personas: HashMap::new(),
+            vars: BTreeMap::new(),
+        }
+    }
+}
+
+impl PromptTemplates {
+    /// The built-ins, the files in `[templates] dir` and `[templates.vars]`.
+    pub fn from_config(config: &TemplatesConfig) -> Self {
+        let mut templates = Self::default().with_vars(config.vars.clone());
+        templates.load_dir(&config.templates_dir());
+        templates
+    }
+
+    /// Load `<name>.md` files from `dir`, and persona templates from its
+    /// subdirectories. A missing directory loads nothing.
+    pub fn load_dir(&mut self, dir: &Path) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                let Some(persona) = path.file_name().and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                for (name, text) in read_templates(&path) {
+                    self.insert(Some(persona), &name, text, TemplateSource::File);
+                }
+            } else if let Some((name, text)) = read_template(&path) {
+                self.insert(None, &name, text, TemplateSource::File);
+            }
+        }
+    }
+
+    pub fn with_template(mut self, name: &str, text: impl Into<String>) -> Self {
+        self.insert(None, name, text.into(), TemplateSource::File);
+        self
+    }
+
+    pub fn with_persona_template(
+        mut self,
+        persona: &str,
+        name: &str,
+        text: impl Into<String>,
+    ) -> Self {
+        self.insert(Some(persona), name, text.into(), TemplateSource::File);
+        self
+    }
+
+    pub fn with_vars(mut self, vars: BTreeMap<String, String>) -> Self {
+        self.vars = vars;
+        self
+    }
+
+    fn insert(&mut self, persona: Option<&str>, name: &str, text: String, source: TemplateSource) {
+        let templates = match persona {
+            Some(persona) => self.personas.entry(persona.to_lowercase()).or_default(),
+            None => &mut self.templates,
+        };
+        templates.insert(name.to_string(), Template { text, source });
+    }
+
+    /// Names of the shared templates, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.templates.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Personas with templates of their own, lowercase and sorted.
+    pub fn personas(&self) -> Vec<&str> {
+        let mut personas: Vec<&str> = self.personas.keys().map(String::as_str).collect();
+        personas.sort_unstable();
+        personas
+    }
+
+    /// Names of `persona`'s own templates, sorted.
+    pub fn persona_names(&self, persona: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .personas
+            .get(&persona.to_lowercase())
+            .map(|t| t.keys().map(String::as_str).collect())
+            .unwrap_or_default();
+        names.sort_unstable();
+        names
+    }
+
+    /// The text of `name` for `persona`, falling back to the shared one.
+    pub fn get(&self, name: &str, persona: Option<&str>) -> Option<&str> {
+        self.lookup(name, persona).map(|t| t.text.as_str())
+    }
+
+    pub fn source(&self, name: &str, persona: Option<&str>) -> Option<TemplateSource> {
+        self.lookup(name, persona).map(|t| t.source)
+    }
+
+    fn lookup(&self, name: &str, persona: Option<&str>) -> Option<&Template> {
+        persona
+            .and_then(|p| self.personas.get(&p.to_lowercase()))
+            .and_then(|templates| templates.get(name))
+            .or_else(|| self.templates.get(name))
+    }
+
+    /// Render `name` for `persona` with `vars`, which take precedence over
+    /// `[templates.vars]`. A missing template renders empty.
+    pub fn render(&self, name: &str, persona: Option<&str>, vars: &[(&str, &str)]) -> String {
+        let Some(text) = self.get(name, persona) else {
+            tracing::warn!("Unknown prompt template '{name}'");
+            return String::new();
+        };
+        let mut out = String::with_capacity(text.len());
+        self.expand(text, persona, vars, 0, &mut out);
+        out.trim().to_string()
+    }
+
+    fn expand(
+        &self,
+        text: &str,
+        persona: Option<&str>,
+        vars: &[(&str, &str)],
+        depth: usize,
+        out: &mut String,
+    ) {
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start + 2..].find("}}") else {
+                break;
+            };
+            out.push_str(&rest[..start]);
+            let tag = rest[start + 2..start + 2 + len].trim();
+            rest = &rest[start + 2 + len + 2..];
+
+            if let Some(partial) = tag.strip_prefix('>') {
+                let partial = partial.trim();
+                match self.get(partial, persona) {
+                    Some(_) if depth >= MAX_DEPTH => {
+                        tracing::warn!("Prompt template '{partial}' nested too deep");
+                    }
+                    Some(text) => self.expand(text, persona, vars, depth + 1, out),
+                    None => tracing::warn!("Unknown prompt partial '{partial}'"),
+                }
+            } else if let Some((_, value)) = vars.iter().find(|(k, _)| *k == tag) {
+                out.push_str(value);
+            } else if let Some(value) = self.vars.get(tag) {
+                out.push_str(value);
+            } else {
+                tracing::debug!("Prompt variable '{tag}' is not set");
+            }
+        }
+        out.push_str(rest);
+    }
+}
+
+/// Whether `name` can be used as a template or persona file name.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn read_templates(dir: &Path) -> Vec<(String, String)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| read_template(&entry.path()))
+        .collect()
+}
+
+fn read_template(path: &Path) -> Option<(String, String)> {
+    let extension = path.extension()?.to_str()?;
+    if !EXTENSIONS.contains(&extension) {
+        return None;
+    }
+    let name = path.file_stem()?.to_str()?;
+    if !is_valid_name(name) {
+        return None;
+    }
+    match std::fs::read_to_string(path) {
+        Ok(text) => Some((name.to_string(), text)),
+        Err(e) => {
+            tracing::warn!("Failed to read prompt template {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let templates = PromptTemplates::default()
+            .with_vars(BTreeMap::from([("hotline".into(), "1900 1234".into())]))
+            .with_template(SYSTEM, "{{prompt}}\n\n{{> policies}}{{missing}}")
+            .with_template("policies", "Hotline: {{hotline}}. {{> loop}}")
+            .with_template("loop", "x{{> loop}}")
+            .with_persona_template("Sales", "policies", "Offer the {{deal}} deal.");
+
+        let system = templates.render(SYSTEM, None, &[("prompt", "You are Bé Ba.")]);
+        assert_eq!(
+            system,
+            format!(
+                "You are Bé Ba.\n\nHotline: 1900 1234. {}",
+                "x".repeat(MAX_DEPTH - 1)
+            )
+        );
+        let sales = templates.render(SYSTEM, Some("sales"), &[("prompt", "Hi"), ("deal", "Tết")]);
+        assert_eq!(sales, "Hi\n\nOffer the Tết deal.");
+
+        let scheduled = templates.render(SCHEDULED, None, &[("instruction", "Post the menu")]);
+        assert!(scheduled.ends_with("\nPost the menu"));
+        assert_eq!(
+            templates.source(SCHEDULED, None),
+            Some(TemplateSource::Builtin)
+        );
+        assert_eq!(templates.render("nope", None, &[]), "");
+        assert!(!is_valid_name("../system"));
+    }
+}
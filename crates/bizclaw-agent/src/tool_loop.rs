@@ -0,0 +1,273 @@
+//! Tool loop — let the model call tools until it has an answer.
+//!
+//! Each round the model sees the conversation plus the available tools.
+//! A reply with tool calls is executed, the calls and their results are
+//! appended, and the model is asked again; a reply without tool calls is
+//! the final answer. The last round offers no tools, so the model has to
+//! answer with what it has gathered.
+
+use bizclaw_channels::manager::ProgressReporter;
+use bizclaw_core::error::Result;
+use bizclaw_core::traits::Provider;
+use bizclaw_core::traits::provider::GenerateParams;
+use bizclaw_core::types::{Message, ProgressEvent, Role, Usage};
+use bizclaw_tools::ToolRegistry;
+use std::time::{Duration, Instant};
+
+/// Result of a tool loop.
+#[derive(Debug, Clone)]
+pub struct ToolLoopOutcome {
+    /// The model's final answer.
+    pub answer: String,
+    /// Rounds in which tools were called.
+    pub tool_rounds: usize,
+    /// Tool calls and results appended during the loop, in order.
+    pub steps: Vec<Message>,
+    /// The tools that were executed, in order.
+    pub runs: Vec<ToolRun>,
+    /// Tokens of every round together.
+    pub usage: Usage,
+}
+
+/// One executed tool call.
+#[derive(Debug, Clone)]
+pub struct ToolRun {
+    pub name: String,
+    pub success: bool,
+    pub duration: Duration,
+}
+
+/// Run the loop on `messages`, allowing at most `max_rounds` rounds of
+/// tool calls.
+pub async fn run(
+    provider: &dyn Provider,
+    tools: &ToolRegistry,
+    messages: &[Message],
+    params: &GenerateParams,
+    max_rounds: usize,
+    progress: Option<&ProgressReporter>,
+) -> Result<ToolLoopOutcome> {
+    run_allowing(
+        provider, tools, None, messages, params, max_rounds, progress,
+    )
+    .await
+}
+
+/// [`run`] offering only the `allowed` tools, all of them if `None`. Calls
+/// to other tools are answered with an error instead of executed.
+pub async fn run_allowing(
+    provider: &dyn Provider,
+    tools: &ToolRegistry,
+    allowed: Option<&[String]>,
+    messages: &[Message],
+    params: &GenerateParams,
+    max_rounds: usize,
+    progress: Option<&ProgressReporter>,
+) -> Result<ToolLoopOutcome> {
+    let is_allowed = |name: &str| allowed.is_none_or(|names| names.iter().any(|n| n == name));
+    let mut definitions = tools.list();
+    definitions.retain(|d| is_allowed(&d.name));
+    let mut conversation = messages.to_vec();
+    let mut steps = Vec::new();
+    let mut runs = Vec::new();
+    let mut usage = Usage::default();
+
+    for round in 0..=max_rounds {
+        let offered = if round < max_rounds {
+            definitions.as_slice()
+        } else {
+            &[]
+        };
+        let response = provider.chat(&conversation, offered, params).await?;
+        usage += &crate::engine::response_usage(&response, &conversation);
+        if response.tool_calls.is_empty() {
+            return Ok(ToolLoopOutcome {
+                answer: response.content.unwrap_or_default().trim().to_string(),
+                tool_rounds: round,
+                steps,
+                runs,
+                usage,
+            });
+        }
+
+        let mut turn = vec![Message {
+            role: Role::Assistant,
+            content: response.content.unwrap_or_default(),
+            name: None,
+            tool_call_id: None,
+            tool_calls: Some(response.tool_calls.clone()),
+        }];
+        for call in &response.tool_calls {
+            tracing::info!("Tool call: {}", call.function.name);
+            if let Some(progress) = progress {
+                progress
+                    .report(ProgressEvent::ToolCall {
+                        name: call.function.name.clone(),
+                    })
+                    .await;
+            }
+            if !is_allowed(&call.function.name) {
+                turn.push(Message::tool(
+                    format!("Tool '{}' is not available", call.function.name),
+                    &call.id,
+                ));
+                continue;
+            }
+            let started = Instant::now();
+            let result = tools.execute(call).await;
+            runs.push(ToolRun {
+                name: call.function.name.clone(),
+                success: result.success,
+                duration: started.elapsed(),
+            });
+            if !result.success {
+                tracing::debug!("Tool {} failed: {}", call.function.name, result.output);
+            }
+            turn.push(Message::tool(result.output, &call.id));
+        }
+        conversation.extend_from_slice(&turn);
+        steps.extend(turn);
+    }
+
+    // The last round offers no tools, so only a provider that ignores the
+    // tool list ends up here.
+    Ok(ToolLoopOutcome {
+        answer: String::new(),
+        tool_rounds: max_rounds,
+        steps,
+        runs,
+        usage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bizclaw_core::traits::Tool;
+    use bizclaw_core::types::{
+        FunctionCall, ModelInfo, ProviderResponse, ToolCall, ToolDefinition, ToolResult,
+    };
+
+    /// Adds two numbers.
+    struct AddTool;
+
+    #[async_trait]
+    impl Tool for AddTool {
+        fn name(&self) -> &str {
+            "add"
+        }
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition {
+                name: "add".into(),
+                description: "Add a and b".into(),
+                parameters: serde_json::json!({"required": ["a", "b"]}),
+            }
+        }
+        async fn execute(&self, arguments: &str) -> Result<ToolResult> {
+            let args: serde_json::Value = serde_json::from_str(arguments)?;
+            let sum = args["a"].as_f64().unwrap_or(0.0) + args["b"].as_f64().unwrap_or(0.0);
+            Ok(ToolResult {
+                tool_call_id: String::new(),
+                output: sum.to_string(),
+                success: true,
+            })
+        }
+    }
+
+    /// Calls `add` while tools are offered and no result is in yet, then
+    /// answers with the last tool result.
+    struct Calculating;
+
+    #[async_trait]
+    impl Provider for Calculating {
+        fn name(&self) -> &str {
+            "calculating"
+        }
+        async fn chat(
+            &self,
+            messages: &[Message],
+            tools: &[ToolDefinition],
+            _params: &GenerateParams,
+        ) -> Result<ProviderResponse> {
+            let last = messages.last().unwrap();
+            if last.role == Role::Tool || tools.is_empty() {
+                return Ok(ProviderResponse::text(format!("= {}", last.content)));
+            }
+            Ok(ProviderResponse {
+                content: None,
+                tool_calls: vec![ToolCall {
+                    id: "call_1".into(),
+                    r#type: "function".into(),
+                    function: FunctionCall {
+                        name: "add".into(),
+                        arguments: r#"{"a": 2, "b": 40}"#.into(),
+                    },
+                }],
+                finish_reason: Some("tool_calls".into()),
+                usage: None,
+            })
+        }
+        async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+            Ok(vec![])
+        }
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    fn registry() -> ToolRegistry {
+        let mut tools = ToolRegistry::new();
+        tools.register(Box::new(AddTool));
+        tools
+    }
+
+    #[tokio::test]
+    async fn test_tool_result_is_fed_back() {
+        let messages = [Message::system("Calculator."), Message::user("2 + 40?")];
+        let params = GenerateParams::default();
+        let outcome = run(&Calculating, &registry(), &messages, &params, 3, None)
+            .await
+            .unwrap();
+        assert_eq!(outcome.answer, "= 42");
+        assert_eq!(outcome.tool_rounds, 1);
+        assert_eq!(outcome.steps.len(), 2);
+        assert!(outcome.steps[0].tool_calls.is_some());
+        assert_eq!(outcome.steps[1].tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(outcome.runs.len(), 1);
+        assert_eq!(outcome.runs[0].name, "add");
+        assert!(outcome.runs[0].success);
+    }
+
+    #[tokio::test]
+    async fn test_no_rounds_means_no_tools() {
+        let messages = [Message::user("2 + 40?")];
+        let params = GenerateParams::default();
+        let outcome = run(&Calculating, &registry(), &messages, &params, 0, None)
+            .await
+            .unwrap();
+        assert_eq!(outcome.answer, "= 2 + 40?");
+        assert_eq!(outcome.tool_rounds, 0);
+        assert!(outcome.steps.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_only_allowed_tools_are_offered() {
+        let messages = [Message::user("2 + 40?")];
+        let params = GenerateParams::default();
+        let allowed = ["search".to_string()];
+        let outcome = run_allowing(
+            &Calculating,
+            &registry(),
+            Some(&allowed),
+            &messages,
+            &params,
+            3,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome.answer, "= 2 + 40?");
+        assert!(outcome.steps.is_empty());
+    }
+}
@@ -0,0 +1,275 @@
+//! Usage accounting and monthly budget caps.
+//!
+//! Every generation is added to the [`UsageStore`] under the provider,
+//! model, channel and sender it was for, with its cost estimated from list
+//! prices (`bizclaw_providers::pricing`). Once this month's spend reaches
+//! `[usage] monthly_budget_usd`, or the sender's reaches
+//! `user_monthly_budget_usd`, the channel agent answers with the free local
+//! `fallback` route instead, until the month ends.
+//!
+//! Admins listed in `[usage] admins` can send `/usage` for the month's
+//! totals.
+
+use crate::language::Lang;
+use bizclaw_core::config::BizClawConfig;
+use bizclaw_core::traits::Provider;
+use bizclaw_core::types::Usage;
+use bizclaw_memory::usage::{UsageEntry, UsageGroup, UsageStore, UsageTotal, current_month};
+use std::sync::Arc;
+
+/// Command that shows this month's usage to admins.
+pub const USAGE_COMMAND: &str = "/usage";
+
+/// Groups listed in a `/usage` report.
+const REPORT_ROWS: usize = 5;
+
+/// The route answering over budget, and the model to ask it for (`None`
+/// keeps the requested one).
+#[derive(Clone)]
+pub struct Fallback {
+    pub provider: Arc<dyn Provider>,
+    pub model: Option<String>,
+}
+
+pub struct UsageTracker {
+    store: Arc<UsageStore>,
+    /// USD per month; 0 is unlimited.
+    budget_usd: f64,
+    user_budget_usd: f64,
+    fallback: Option<Fallback>,
+    /// `channel:user_id`
+    admins: Vec<String>,
+}
+
+impl UsageTracker {
+    pub fn new(store: Arc<UsageStore>) -> Self {
+        Self {
+            store,
+            budget_usd: 0.0,
+            user_budget_usd: 0.0,
+            fallback: None,
+            admins: Vec::new(),
+        }
+    }
+
+    /// From `[usage]`. The fallback route is only created when a budget is
+    /// set, so an unused local model is never loaded.
+    pub fn from_config(config: &BizClawConfig, store: Arc<UsageStore>) -> Self {
+        let usage = &config.usage;
+        let mut tracker = Self::new(store)
+            .with_budgets(usage.monthly_budget_usd, usage.user_monthly_budget_usd)
+            .with_admins(usage.admins.clone());
+        if tracker.has_budget() {
+            match bizclaw_providers::router::create_route(config, &usage.fallback) {
+                Ok((provider, model)) => {
+                    tracker = tracker.with_fallback(Fallback {
+                        provider: provider.into(),
+                        model,
+                    });
+                }
+                Err(e) => tracing::warn!(
+                    "Budget fallback '{}' unavailable, budgets won't be enforced: {e}",
+                    usage.fallback
+                ),
+            }
+        }
+        tracker
+    }
+
+    pub fn with_budgets(mut self, budget_usd: f64, user_budget_usd: f64) -> Self {
+        self.budget_usd = budget_usd;
+        self.user_budget_usd = user_budget_usd;
+        self
+    }
+
+    pub fn with_fallback(mut self, fallback: Fallback) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    pub fn with_admins(mut self, admins: Vec<String>) -> Self {
+        self.admins = admins;
+        self
+    }
+
+    fn has_budget(&self) -> bool {
+        self.budget_usd > 0.0 || self.user_budget_usd > 0.0
+    }
+
+    pub fn is_admin(&self, channel: &str, user_id: &str) -> bool {
+        let sender = format!("{channel}:{user_id}");
+        self.admins.contains(&sender)
+    }
+
+    /// The route to answer `channel:user_id` with instead of the
+    /// configured provider, if a budget is used up.
+    pub fn fallback_for(&self, channel: &str, user_id: &str) -> Option<&Fallback> {
+        let fallback = self.fallback.as_ref()?;
+        let month = current_month();
+        let spent = |total: bizclaw_core::error::Result<UsageTotal>| match total {
+            Ok(total) => total.cost_usd,
+            Err(e) => {
+                tracing::warn!("Failed to read usage: {e}");
+                0.0
+            }
+        };
+        let over = (self.budget_usd > 0.0 && spent(self.store.total(&month)) >= self.budget_usd)
+            || (self.user_budget_usd > 0.0
+                && !user_id.is_empty()
+                && spent(self.store.user_total(&month, channel, user_id)) >= self.user_budget_usd);
+        over.then_some(fallback)
+    }
+
+    /// Add a generation to this month's totals.
+    pub fn record(&self, provider: &str, model: &str, channel: &str, user_id: &str, usage: &Usage) {
+        let entry = UsageEntry {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            channel: channel.to_string(),
+            user_id: user_id.to_string(),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            cost_usd: bizclaw_providers::pricing::estimate_cost(
+                provider,
+                model,
+                usage.prompt_tokens,
+                usage.completion_tokens,
+            ),
+        };
+        if let Err(e) = self.store.record(&entry) {
+            tracing::warn!("Failed to record usage: {e}");
+        }
+    }
+
+    /// This month's totals, by model and by user, for `/usage`.
+    pub fn report(&self, lang: Lang) -> String {
+        let month = current_month();
+        let (total, models, users) = match (
+            self.store.total(&month),
+            self.store.summary(&month, UsageGroup::Model),
+            self.store.summary(&month, UsageGroup::User),
+        ) {
+            (Ok(total), Ok(models), Ok(users)) => (total, models, users),
+            (Err(e), ..) | (_, Err(e), _) | (.., Err(e)) => {
+                tracing::warn!("Failed to read usage: {e}");
+                return lang
+                    .pick("Không đọc được số liệu sử dụng.", "Usage is unavailable.")
+                    .into();
+            }
+        };
+
+        let mut out = format!(
+            "{} {month}: {} {}, {} tokens, ${:.2}",
+            lang.pick("Sử dụng", "Usage"),
+            total.requests,
+            lang.pick("yêu cầu", "requests"),
+            total.prompt_tokens + total.completion_tokens,
+            total.cost_usd
+        );
+        if self.budget_usd > 0.0 {
+            out.push_str(&format!(
+                " / ${:.2} {}",
+                self.budget_usd,
+                lang.pick("ngân sách", "budget")
+            ));
+        }
+        for (title, rows) in [
+            (lang.pick("Theo model", "By model"), &models),
+            (lang.pick("Theo người dùng", "By user"), &users),
+        ] {
+            if rows.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("\n\n{title}:"));
+            for row in rows.iter().take(REPORT_ROWS) {
+                out.push_str(&format!(
+                    "\n- {}: ${:.2}, {} tokens ({})",
+                    row.key,
+                    row.cost_usd,
+                    row.prompt_tokens + row.completion_tokens,
+                    row.requests
+                ));
+            }
+        }
+        out
+    }
+}
+
+/// `/usage`, also in Telegram's `/usage@botname` form.
+pub fn is_usage_command(content: &str) -> bool {
+    let command = content.trim().split('@').next().unwrap_or_default();
+    command.eq_ignore_ascii_case(USAGE_COMMAND)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bizclaw_core::error::Result;
+    use bizclaw_core::traits::provider::GenerateParams;
+    use bizclaw_core::types::{Message, ModelInfo, ProviderResponse, ToolDefinition};
+
+    struct Local;
+
+    #[async_trait]
+    impl Provider for Local {
+        fn name(&self) -> &str {
+            "ollama"
+        }
+
+        async fn chat(
+            &self,
+            _messages: &[Message],
+            _tools: &[ToolDefinition],
+            _params: &GenerateParams,
+        ) -> Result<ProviderResponse> {
+            Ok(ProviderResponse::text("local"))
+        }
+
+        async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+            Ok(Vec::new())
+        }
+
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+    }
+
+    fn usage(prompt_tokens: u32, completion_tokens: u32) -> Usage {
+        Usage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+
+    #[test]
+    fn test_budgets_and_report() {
+        let store = Arc::new(UsageStore::in_memory().unwrap());
+        let fallback = Fallback {
+            provider: Arc::new(Local),
+            model: None,
+        };
+        let tracker = UsageTracker::new(store)
+            .with_budgets(0.0, 1.0)
+            .with_fallback(fallback)
+            .with_admins(vec!["telegram:42".into()]);
+
+        tracker.record("openai", "gpt-4o", "zalo", "7", &usage(100_000, 80_000));
+        tracker.record("ollama", "qwen2.5", "zalo", "8", &usage(500_000, 0));
+        assert!(tracker.fallback_for("zalo", "7").is_some());
+        assert!(tracker.fallback_for("zalo", "8").is_none());
+        assert!(tracker.fallback_for("zalo", "").is_none());
+
+        assert!(tracker.is_admin("telegram", "42"));
+        assert!(!tracker.is_admin("zalo", "42"));
+        assert!(is_usage_command(" /usage@bizclaw_bot"));
+        let report = tracker.report(Lang::English);
+        assert!(
+            report.contains("2 requests, 680000 tokens, $1.05"),
+            "{report}"
+        );
+        assert!(report.contains("- openai/gpt-4o: $1.05"), "{report}");
+        assert!(report.contains("- zalo:8: $0.00"), "{report}");
+    }
+}
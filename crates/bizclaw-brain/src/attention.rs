@@ -2,6 +2,16 @@
 //!
 //! Computes attention scores incrementally without materializing
 //! the full QK^T matrix, saving O(seq_len) memory.
+//!
+//! [`tiled_attention`] is the kernel the forward pass runs: it walks the
+//! KV cache a tile of [`ATTENTION_TILE`] positions at a time, scoring the
+//! tile for every query head that shares its KV head before moving on, so
+//! each key and value row is read from memory once per group rather than
+//! once per head. The softmax running max and sum are updated once per
+//! tile instead of once per position. [`paged_attention`] does the same
+//! over a KV cache held in pages, reading each in place. Scores can be soft-capped first, as
+//! Gemma-2 requires, and biased by distance with [`Alibi`] for models that
+//! use it in place of RoPE.
 
 /// Compute single-head attention output for a single query position.
 /// Uses online softmax (flash attention) — no intermediate score buffer.
@@ -78,7 +88,23 @@ pub fn attention(
     }
 }
 
-/// Multi-head attention: apply attention for all heads in parallel.
+/// The KV head query head `head` reads under grouped-query attention:
+/// consecutive groups of `n_heads / n_kv_heads` query heads share one.
+/// With as many KV heads as query heads this is plain multi-head attention.
+#[inline]
+pub fn kv_head(head: usize, n_heads: usize, n_kv_heads: usize) -> usize {
+    head / (n_heads / n_kv_heads)
+}
+
+/// Multi-head attention with grouped-query attention (GQA).
+///
+/// q: all query heads [n_heads x head_dim]
+/// key_cache / value_cache: [seq_len x n_kv_heads x head_dim], as stored
+/// in the KV cache
+/// output: [n_heads x head_dim]
+///
+/// Each query head attends over its [`kv_head`] in place, without copying
+/// the shared keys and values out per head.
 pub fn multi_head_attention(
     output: &mut [f32],
     q: &[f32],
@@ -89,22 +115,19 @@ pub fn multi_head_attention(
     seq_len: usize,
     head_dim: usize,
 ) {
-    let gqa_ratio = n_heads / n_kv_heads;
-
-    for h in 0..n_heads {
-        let q_offset = h * head_dim;
-        let kv_head = h / gqa_ratio;
-        let kv_stride = n_kv_heads * head_dim;
-
-        // For GQA: multiple Q heads share the same KV head
-        let k_base = kv_head * head_dim;
-        let v_base = kv_head * head_dim;
-
-        // Build per-head key/value views (strided)
-        let q_slice = &q[q_offset..q_offset + head_dim];
-        let out_slice = &mut output[q_offset..q_offset + head_dim];
-
-        // Single-head attention with flash attention
+    debug_assert!(n_kv_heads > 0 && n_heads.is_multiple_of(n_kv_heads));
+    debug_assert_eq!(q.len(), n_heads * head_dim);
+    debug_assert_eq!(output.len(), n_heads * head_dim);
+    let kv_stride = n_kv_heads * head_dim;
+    debug_assert!(key_cache.len() >= seq_len * kv_stride);
+    debug_assert!(value_cache.len() >= seq_len * kv_stride);
+
+    for (h, (out_slice, q_slice)) in output
+        .chunks_exact_mut(head_dim)
+        .zip(q.chunks_exact(head_dim))
+        .enumerate()
+    {
+        let kv_base = kv_head(h, n_heads, n_kv_heads) * head_dim;
         attention_strided(
             out_slice,
             q_slice,
@@ -113,12 +136,200 @@ pub fn multi_head_attention(
             seq_len,
             head_dim,
             kv_stride,
-            k_base,
-            v_base,
+            kv_base,
+            kv_base,
         );
     }
 }
 
+/// KV positions per tile of [`tiled_attention`]: the tile's keys and
+/// values (64 x 128 f32 each for a typical head) stay in L1/L2 while every
+/// query head of the group uses them.
+pub const ATTENTION_TILE: usize = 64;
+
+/// Multi-head attention with GQA, tiled over the sequence. Same result as
+/// [`multi_head_attention`], with less memory traffic on long contexts.
+///
+/// softcap: if above 0, scores are soft-capped to (-softcap, softcap)
+/// with [`tensor::softcap`](crate::tensor::softcap) before the softmax
+pub fn tiled_attention(
+    output: &mut [f32],
+    q: &[f32],
+    key_cache: &[f32],
+    value_cache: &[f32],
+    n_heads: usize,
+    n_kv_heads: usize,
+    seq_len: usize,
+    head_dim: usize,
+    softcap: f32,
+) {
+    let kv_len = seq_len * n_kv_heads * head_dim;
+    debug_assert!(key_cache.len() >= kv_len && value_cache.len() >= kv_len);
+    paged_attention(
+        output,
+        q,
+        &[&key_cache[..kv_len]],
+        &[&value_cache[..kv_len]],
+        n_heads,
+        n_kv_heads,
+        head_dim,
+        softcap,
+        None,
+    );
+}
+
+/// ALiBi slopes of `n_heads` heads, as MPT and BLOOM set them: a geometric
+/// sequence from `2^(-max_bias / n)` down to `2^-max_bias` over the largest
+/// power of two `n` of heads, and the odd steps of the sequence for twice
+/// as many heads interleaved in after it.
+pub fn alibi_slopes(n_heads: usize, max_bias: f32) -> Vec<f32> {
+    let n = n_heads.checked_ilog2().map_or(1, |log| 1 << log);
+    let base = 2f32.powf(-max_bias / n as f32);
+    let odd_base = 2f32.powf(-max_bias / 2.0 / n as f32);
+    (0..n_heads)
+        .map(|h| {
+            if h < n {
+                base.powi(h as i32 + 1)
+            } else {
+                odd_base.powi(2 * (h - n) as i32 + 1)
+            }
+        })
+        .collect()
+}
+
+/// Attention with linear biases (ALiBi), used by MPT and BLOOM in place of
+/// RoPE: each head's score of a key is lowered by the head's slope times
+/// the key's distance from the query.
+#[derive(Debug, Clone, Copy)]
+pub struct Alibi<'a> {
+    /// Slope of each query head, from [`alibi_slopes`]
+    pub slopes: &'a [f32],
+    /// Position of the query
+    pub pos: usize,
+    /// Rows the KV cache holds: row `t` holds the last position at or
+    /// before `pos` that is `t` modulo this, as a ring buffer stores them
+    pub capacity: usize,
+}
+
+impl Alibi<'_> {
+    /// Bias of query head `head` for the key in row `row`.
+    #[inline]
+    fn bias(&self, head: usize, row: usize) -> f32 {
+        let distance = (self.pos + self.capacity - row) % self.capacity;
+        -self.slopes[head] * distance as f32
+    }
+}
+
+/// [`tiled_attention`] over a KV cache held in pages, as
+/// [`KvCache::key_pages`](crate::kv_cache::KvCache::key_pages) returns it:
+/// the keys and values are the concatenation of the pages, whole rows of
+/// `n_kv_heads * head_dim` each, and tiles never span two pages.
+///
+/// alibi: if given, scores are biased by distance after any soft-cap
+pub fn paged_attention(
+    output: &mut [f32],
+    q: &[f32],
+    key_pages: &[&[f32]],
+    value_pages: &[&[f32]],
+    n_heads: usize,
+    n_kv_heads: usize,
+    head_dim: usize,
+    softcap: f32,
+    alibi: Option<&Alibi>,
+) {
+    debug_assert!(n_kv_heads > 0 && n_heads.is_multiple_of(n_kv_heads));
+    debug_assert_eq!(q.len(), n_heads * head_dim);
+    debug_assert_eq!(output.len(), n_heads * head_dim);
+    debug_assert_eq!(key_pages.len(), value_pages.len());
+    let kv_stride = n_kv_heads * head_dim;
+
+    output.fill(0.0);
+    if key_pages.iter().all(|page| page.is_empty()) {
+        return;
+    }
+
+    let group = n_heads / n_kv_heads;
+    let scale = 1.0 / (head_dim as f32).sqrt();
+    // Scores, then softmax weights, of one tile: [group x ATTENTION_TILE]
+    let mut weights = vec![0.0f32; group * ATTENTION_TILE];
+    let mut running_max = vec![f32::NEG_INFINITY; group];
+    let mut running_sum = vec![0.0f32; group];
+
+    for kv_h in 0..n_kv_heads {
+        let kv_base = kv_h * head_dim;
+        // The query heads sharing this KV head, and their outputs
+        let q_group = &q[kv_h * group * head_dim..(kv_h + 1) * group * head_dim];
+        let out_group = &mut output[kv_h * group * head_dim..(kv_h + 1) * group * head_dim];
+        running_max.fill(f32::NEG_INFINITY);
+        running_sum.fill(0.0);
+
+        // Row of the cache each page starts at
+        let mut page_start = 0;
+        for (key_page, value_page) in key_pages.iter().zip(value_pages) {
+            debug_assert_eq!(key_page.len() % kv_stride, 0);
+            debug_assert_eq!(key_page.len(), value_page.len());
+            let page_len = key_page.len() / kv_stride;
+            for tile_start in (0..page_len).step_by(ATTENTION_TILE) {
+                let tile_len = ATTENTION_TILE.min(page_len - tile_start);
+
+                // 1. Scores: each key is loaded once for the whole group
+                for t in 0..tile_len {
+                    let k_offset = (tile_start + t) * kv_stride + kv_base;
+                    let k = &key_page[k_offset..k_offset + head_dim];
+                    for (g, q_head) in q_group.chunks_exact(head_dim).enumerate() {
+                        weights[g * ATTENTION_TILE + t] =
+                            crate::simd::dot_product_simd(q_head, k) * scale;
+                    }
+                }
+
+                // 2. Online softmax, one rescale of the accumulator per tile
+                for (g, out_head) in out_group.chunks_exact_mut(head_dim).enumerate() {
+                    let tile = &mut weights[g * ATTENTION_TILE..g * ATTENTION_TILE + tile_len];
+                    crate::tensor::softcap(tile, softcap);
+                    if let Some(alibi) = alibi {
+                        let row = page_start + tile_start;
+                        for (t, w) in tile.iter_mut().enumerate() {
+                            *w += alibi.bias(kv_h * group + g, row + t);
+                        }
+                    }
+                    let new_max = running_max[g].max(crate::simd::max_simd(tile));
+                    let scale_old = (running_max[g] - new_max).exp();
+                    let tile_sum = crate::simd::exp_sub_sum_simd(tile, new_max);
+                    running_sum[g] = running_sum[g] * scale_old + tile_sum;
+                    running_max[g] = new_max;
+                    if scale_old != 1.0 {
+                        for o in out_head.iter_mut() {
+                            *o *= scale_old;
+                        }
+                    }
+                }
+
+                // 3. Weighted values: each value is loaded once for the group
+                for t in 0..tile_len {
+                    let v_offset = (tile_start + t) * kv_stride + kv_base;
+                    let v = &value_page[v_offset..v_offset + head_dim];
+                    for (g, out_head) in out_group.chunks_exact_mut(head_dim).enumerate() {
+                        let w = weights[g * ATTENTION_TILE + t];
+                        for (o, &x) in out_head.iter_mut().zip(v) {
+                            *o += w * x;
+                        }
+                    }
+                }
+            }
+            page_start += page_len;
+        }
+
+        for (g, out_head) in out_group.chunks_exact_mut(head_dim).enumerate() {
+            if running_sum[g] > 0.0 {
+                let inv_sum = 1.0 / running_sum[g];
+                for o in out_head.iter_mut() {
+                    *o *= inv_sum;
+                }
+            }
+        }
+    }
+}
+
 /// Strided attention — works with interleaved multi-head KV cache layout.
 fn attention_strided(
     output: &mut [f32],
@@ -215,6 +426,279 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_kv_head_mapping() {
+        // Llama 3 8B: 32 query heads over 8 KV heads.
+        let heads: Vec<usize> = (0..32).map(|h| kv_head(h, 32, 8)).collect();
+        assert_eq!(&heads[..8], &[0, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(heads[31], 7);
+        // Multi-head and multi-query attention are the two extremes.
+        assert!((0..8).all(|h| kv_head(h, 8, 8) == h));
+        assert!((0..8).all(|h| kv_head(h, 8, 1) == 0));
+    }
+
+    #[test]
+    fn test_gqa_matches_repeated_kv() {
+        // 4 query heads sharing 2 KV heads must give the same output as
+        // 4 KV heads with each shared head repeated.
+        let (n_heads, n_kv_heads, head_dim, seq_len) = (4, 2, 4, 3);
+        let value = |i: usize| ((i * 7919) % 13) as f32 / 13.0 - 0.5;
+        let q: Vec<f32> = (0..n_heads * head_dim).map(value).collect();
+        let keys: Vec<f32> = (0..seq_len * n_kv_heads * head_dim)
+            .map(|i| value(i + 100))
+            .collect();
+        let values: Vec<f32> = (0..seq_len * n_kv_heads * head_dim)
+            .map(|i| value(i + 200))
+            .collect();
+        let repeat = |cache: &[f32]| -> Vec<f32> {
+            let mut out = Vec::new();
+            for t in 0..seq_len {
+                for h in 0..n_heads {
+                    let start = (t * n_kv_heads + kv_head(h, n_heads, n_kv_heads)) * head_dim;
+                    out.extend_from_slice(&cache[start..start + head_dim]);
+                }
+            }
+            out
+        };
+
+        let mut gqa = vec![0.0; n_heads * head_dim];
+        multi_head_attention(
+            &mut gqa, &q, &keys, &values, n_heads, n_kv_heads, seq_len, head_dim,
+        );
+        let mut mha = vec![0.0; n_heads * head_dim];
+        multi_head_attention(
+            &mut mha,
+            &q,
+            &repeat(&keys),
+            &repeat(&values),
+            n_heads,
+            n_heads,
+            seq_len,
+            head_dim,
+        );
+        for (a, b) in gqa.iter().zip(&mha) {
+            assert!((a - b).abs() < 1e-6, "GQA {a} != MHA {b}");
+        }
+
+        // And each head matches single-head attention over its KV head.
+        let h = 3;
+        let kv_h = kv_head(h, n_heads, n_kv_heads);
+        let gather = |cache: &[f32]| -> Vec<f32> {
+            (0..seq_len)
+                .flat_map(|t| {
+                    let start = (t * n_kv_heads + kv_h) * head_dim;
+                    cache[start..start + head_dim].to_vec()
+                })
+                .collect()
+        };
+        let mut single = vec![0.0; head_dim];
+        attention(
+            &mut single,
+            &q[h * head_dim..(h + 1) * head_dim],
+            &gather(&keys),
+            &gather(&values),
+            seq_len,
+            head_dim,
+        );
+        for (a, b) in single.iter().zip(&gqa[h * head_dim..]) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_tiled_matches_untiled() {
+        let head_dim = 8;
+        let value = |i: usize| (i.wrapping_mul(2_654_435_761) % 1000) as f32 / 250.0 - 2.0;
+        // Shorter than a tile, exactly one, and several with a remainder;
+        // multi-head, grouped and multi-query.
+        for seq_len in [1, ATTENTION_TILE, 2 * ATTENTION_TILE + 7] {
+            for (n_heads, n_kv_heads) in [(4, 4), (4, 2), (4, 1)] {
+                let kv_len = seq_len * n_kv_heads * head_dim;
+                let q: Vec<f32> = (0..n_heads * head_dim).map(value).collect();
+                let keys: Vec<f32> = (0..kv_len).map(|i| value(i + 1)).collect();
+                let values: Vec<f32> = (0..kv_len).map(|i| value(i + 2)).collect();
+
+                let mut expected = vec![0.0; n_heads * head_dim];
+                multi_head_attention(
+                    &mut expected,
+                    &q,
+                    &keys,
+                    &values,
+                    n_heads,
+                    n_kv_heads,
+                    seq_len,
+                    head_dim,
+                );
+                let mut tiled = vec![1.0; n_heads * head_dim];
+                tiled_attention(
+                    &mut tiled, &q, &keys, &values, n_heads, n_kv_heads, seq_len, head_dim, 0.0,
+                );
+                for (a, b) in tiled.iter().zip(&expected) {
+                    assert!(
+                        (a - b).abs() < 1e-4,
+                        "seq_len {seq_len}, {n_heads}/{n_kv_heads} heads: {a} != {b}"
+                    );
+                }
+            }
+        }
+
+        let mut output = vec![1.0; 8];
+        tiled_attention(&mut output, &[0.5; 8], &[], &[], 2, 1, 0, 4, 0.0);
+        assert_eq!(output, [0.0; 8]);
+    }
+
+    #[test]
+    fn test_paged_matches_tiled() {
+        let (n_heads, n_kv_heads, head_dim, seq_len) = (4, 2, 8, 150);
+        let kv_stride = n_kv_heads * head_dim;
+        let value = |i: usize| (i.wrapping_mul(2_654_435_761) % 1000) as f32 / 250.0 - 2.0;
+        let q: Vec<f32> = (0..n_heads * head_dim).map(value).collect();
+        let keys: Vec<f32> = (0..seq_len * kv_stride).map(|i| value(i + 1)).collect();
+        let values: Vec<f32> = (0..seq_len * kv_stride).map(|i| value(i + 2)).collect();
+
+        let mut expected = vec![0.0; n_heads * head_dim];
+        tiled_attention(
+            &mut expected,
+            &q,
+            &keys,
+            &values,
+            n_heads,
+            n_kv_heads,
+            seq_len,
+            head_dim,
+            0.0,
+        );
+        // Pages of uneven lengths, one longer than a tile.
+        let split = |rows: &'static [usize], data: &[f32]| -> Vec<Vec<f32>> {
+            let mut start = 0;
+            rows.iter()
+                .map(|&n| {
+                    let page = data[start * kv_stride..(start + n) * kv_stride].to_vec();
+                    start += n;
+                    page
+                })
+                .collect()
+        };
+        let (key_pages, value_pages) =
+            (split(&[10, 100, 40], &keys), split(&[10, 100, 40], &values));
+        let key_refs: Vec<&[f32]> = key_pages.iter().map(Vec::as_slice).collect();
+        let value_refs: Vec<&[f32]> = value_pages.iter().map(Vec::as_slice).collect();
+        let mut paged = vec![1.0; n_heads * head_dim];
+        paged_attention(
+            &mut paged,
+            &q,
+            &key_refs,
+            &value_refs,
+            n_heads,
+            n_kv_heads,
+            head_dim,
+            0.0,
+            None,
+        );
+        for (a, b) in paged.iter().zip(&expected) {
+            assert!((a - b).abs() < 1e-4, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn test_tiled_softcap() {
+        // Scores 40 and 80 (after the 1/sqrt(4) scale): uncapped the second
+        // key takes all the weight; capped at 50 they are 50·tanh(0.8) and
+        // 50·tanh(1.6), still far apart but no longer saturating exp.
+        let q = [40.0, 0.0, 0.0, 0.0];
+        let keys = [2.0, 0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 0.0];
+        let values = [1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+        let scores = [50.0 * (0.8f32).tanh(), 50.0 * (1.6f32).tanh()];
+        let max = scores[1];
+        let (w0, w1) = ((scores[0] - max).exp(), 1.0);
+        let expected = [w0 / (w0 + w1), w1 / (w0 + w1), 0.0, 0.0];
+
+        let mut output = [0.0; 4];
+        tiled_attention(&mut output, &q, &keys, &values, 1, 1, 2, 4, 50.0);
+        for (a, b) in output.iter().zip(&expected) {
+            assert!((a - b).abs() < 1e-5, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn test_alibi() {
+        // MPT's 8 heads: slopes 1/2, 1/4, ... 1/256. 12 heads add the odd
+        // steps of the 16-head sequence.
+        let slopes = alibi_slopes(8, 8.0);
+        assert_eq!(slopes[0], 0.5);
+        assert_eq!(slopes[7], 1.0 / 256.0);
+        let slopes = alibi_slopes(12, 8.0);
+        assert_eq!(slopes[..8], alibi_slopes(8, 8.0)[..]);
+        let sixteen = alibi_slopes(16, 8.0);
+        assert_eq!(
+            slopes[8..],
+            [sixteen[0], sixteen[2], sixteen[4], sixteen[6]]
+        );
+
+        // Each query head against its own softmax over biased scores, for
+        // a cache filled in order and for a ring buffer that has wrapped.
+        let (n_heads, head_dim, capacity) = (4, 4, 100);
+        let value = |i: usize| (i.wrapping_mul(2_654_435_761) % 1000) as f32 / 250.0 - 2.0;
+        let slopes = alibi_slopes(n_heads, 8.0);
+        for pos in [capacity / 2, 2 * capacity + 17] {
+            let q: Vec<f32> = (0..n_heads * head_dim).map(value).collect();
+            let rows = (pos + 1).min(capacity);
+            // Key and value of each position still cached, by position...
+            let first = pos + 1 - rows;
+            let key = |p: usize| (0..head_dim).map(move |i| value(p * 7 + i + 1));
+            let val = |p: usize| (0..head_dim).map(move |i| value(p * 7 + i + 2));
+            // ...and stored in ring order.
+            let mut keys = vec![0.0; rows * head_dim];
+            let mut values = vec![0.0; rows * head_dim];
+            for p in first..=pos {
+                let row = p % capacity;
+                for (i, (k, v)) in key(p).zip(val(p)).enumerate() {
+                    keys[row * head_dim + i] = k;
+                    values[row * head_dim + i] = v;
+                }
+            }
+
+            let mut expected = vec![0.0; n_heads * head_dim];
+            for (h, q_head) in q.chunks(head_dim).enumerate() {
+                let scores: Vec<f32> = (first..=pos)
+                    .map(|p| {
+                        let dot: f32 = q_head.iter().zip(key(p)).map(|(q, k)| q * k).sum();
+                        dot / (head_dim as f32).sqrt() - slopes[h] * (pos - p) as f32
+                    })
+                    .collect();
+                let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                let sum: f32 = scores.iter().map(|s| (s - max).exp()).sum();
+                for (p, s) in (first..=pos).zip(&scores) {
+                    let w = (s - max).exp() / sum;
+                    for (o, v) in expected[h * head_dim..].iter_mut().zip(val(p)) {
+                        *o += w * v;
+                    }
+                }
+            }
+
+            let mut output = vec![0.0; n_heads * head_dim];
+            let alibi = Alibi {
+                slopes: &slopes,
+                pos,
+                capacity,
+            };
+            paged_attention(
+                &mut output,
+                &q,
+                &[&keys[..30 * head_dim], &keys[30 * head_dim..]],
+                &[&values[..30 * head_dim], &values[30 * head_dim..]],
+                n_heads,
+                1,
+                head_dim,
+                0.0,
+                Some(&alibi),
+            );
+            for (a, b) in output.iter().zip(&expected) {
+                assert!((a - b).abs() < 1e-4, "pos {pos}: {a} != {b}");
+            }
+        }
+    }
+
     #[test]
     fn test_attention_empty() {
         let head_dim = 4;
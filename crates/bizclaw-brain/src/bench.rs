@@ -0,0 +1,144 @@
+//! Throughput benchmark: prompt processing (prefill) and generation
+//! (decode) tokens/sec, as `bizclaw bench` reports them.
+//!
+//! The engine prefills one token at a time, so prompt lengths stand in
+//! for batch sizes. Generation ignores EOS so every run decodes the same
+//! number of tokens.
+
+use crate::{BrainEngine, forward};
+use bizclaw_core::error::{BizClawError, Result};
+use std::time::{Duration, Instant};
+
+/// Text the prompt tokens are taken from.
+const PROMPT_TEXT: &str = "The quick brown fox jumps over the lazy dog while the \
+    shopkeeper counts the morning orders and answers customers one by one. ";
+
+/// Timings of one run.
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub threads: usize,
+    pub prompt_tokens: usize,
+    pub gen_tokens: usize,
+    pub prefill: Duration,
+    pub decode: Duration,
+}
+
+impl BenchResult {
+    pub fn prefill_tps(&self) -> f64 {
+        tokens_per_sec(self.prompt_tokens, self.prefill)
+    }
+
+    pub fn decode_tps(&self) -> f64 {
+        tokens_per_sec(self.gen_tokens, self.decode)
+    }
+}
+
+fn tokens_per_sec(tokens: usize, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        0.0
+    } else {
+        tokens as f64 / elapsed.as_secs_f64()
+    }
+}
+
+impl BrainEngine {
+    /// Prefill `prompt_tokens` tokens, then generate `gen_tokens`, on a
+    /// pool of `threads` threads (one without the `threads` feature).
+    pub fn bench(
+        &mut self,
+        threads: usize,
+        prompt_tokens: usize,
+        gen_tokens: usize,
+    ) -> Result<BenchResult> {
+        let model = self
+            .model
+            .as_mut()
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?;
+        if prompt_tokens == 0 || prompt_tokens + gen_tokens > model.params.max_seq_len as usize {
+            return Err(BizClawError::ContextOverflow(format!(
+                "{prompt_tokens} prompt + {gen_tokens} generated tokens don't fit in {} positions",
+                model.params.max_seq_len
+            )));
+        }
+        #[cfg(feature = "threads")]
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|e| BizClawError::Brain(e.to_string()))?;
+
+        let mut text = model.tokenizer.encode(PROMPT_TEXT);
+        if text.is_empty() {
+            text.push(model.tokenizer.bos_id);
+        }
+        let mut tokens = vec![model.tokenizer.bos_id];
+        tokens.extend(text.iter().cycle().take(prompt_tokens - 1));
+        model.cached.clear();
+        let mut logits = vec![0.0f32; model.params.vocab_size as usize];
+
+        let run = || {
+            let started = Instant::now();
+            for (pos, &token) in tokens.iter().enumerate() {
+                forward::forward(
+                    &model.mmap_model,
+                    &model.weights,
+                    &model.params,
+                    &mut model.kv_cache,
+                    token,
+                    pos,
+                    &mut logits,
+                )?;
+            }
+            let prefill = started.elapsed();
+
+            let started = Instant::now();
+            for pos in prompt_tokens..prompt_tokens + gen_tokens {
+                let token = model.sampler.sample(&mut logits, &tokens);
+                tokens.push(token);
+                forward::forward(
+                    &model.mmap_model,
+                    &model.weights,
+                    &model.params,
+                    &mut model.kv_cache,
+                    token,
+                    pos,
+                    &mut logits,
+                )?;
+            }
+            Ok(BenchResult {
+                threads,
+                prompt_tokens,
+                gen_tokens,
+                prefill,
+                decode: started.elapsed(),
+            })
+        };
+
+        #[cfg(feature = "threads")]
+        {
+            pool.install(run)
+        }
+        #[cfg(not(feature = "threads"))]
+        {
+            let mut run = run;
+            run()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_per_sec() {
+        let result = BenchResult {
+            threads: 4,
+            prompt_tokens: 128,
+            gen_tokens: 32,
+            prefill: Duration::from_millis(500),
+            decode: Duration::ZERO,
+        };
+        assert!((result.prefill_tps() - 256.0).abs() < 1e-9);
+        assert_eq!(result.decode_tps(), 0.0);
+    }
+}
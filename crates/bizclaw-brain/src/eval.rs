@@ -0,0 +1,217 @@
+//! Corpus evaluation: chunked perplexity and, against a reference model,
+//! KL-divergence. Backs `bizclaw eval`.
+//!
+//! As in llama.cpp's `perplexity`, the corpus is split into chunks of the
+//! context length, each starting at BOS, and only the second half of a
+//! chunk is scored, so every scored token sees at least half a context.
+
+use crate::{BrainEngine, forward};
+use bizclaw_core::error::{BizClawError, Result};
+
+/// Running totals over the chunks evaluated so far.
+#[derive(Debug, Clone, Default)]
+pub struct EvalSummary {
+    pub chunks: usize,
+    /// Tokens scored.
+    pub tokens: usize,
+    /// Sum of negative log-likelihoods.
+    pub nll: f64,
+    /// Sum of KL(reference ‖ model), when evaluated against a reference.
+    pub kl: Option<f64>,
+    /// Positions where both models rank the same token first.
+    pub top1_matches: usize,
+}
+
+impl EvalSummary {
+    pub fn perplexity(&self) -> f64 {
+        (self.nll / self.tokens.max(1) as f64).exp()
+    }
+
+    pub fn mean_kl(&self) -> Option<f64> {
+        self.kl.map(|kl| kl / self.tokens.max(1) as f64)
+    }
+
+    /// Share of positions where the top token matches the reference.
+    pub fn top1_agreement(&self) -> Option<f64> {
+        self.kl
+            .map(|_| self.top1_matches as f64 / self.tokens.max(1) as f64)
+    }
+}
+
+/// Log-probabilities of `logits`, written to `out`.
+pub fn log_softmax(logits: &[f32], out: &mut Vec<f32>) {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let sum: f64 = logits.iter().map(|&l| ((l - max) as f64).exp()).sum();
+    let log_sum = sum.ln() as f32 + max;
+    out.clear();
+    out.extend(logits.iter().map(|&l| l - log_sum));
+}
+
+/// KL(p ‖ q) of two distributions given as log-probabilities.
+pub fn kl_divergence(log_p: &[f32], log_q: &[f32]) -> f64 {
+    log_p
+        .iter()
+        .zip(log_q)
+        .map(|(&p, &q)| (p as f64).exp() * (p - q) as f64)
+        .sum()
+}
+
+fn argmax(values: &[f32]) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map_or(0, |(i, _)| i)
+}
+
+impl BrainEngine {
+    /// Run `sequence` from position 0, calling `on_position` with the
+    /// log-probabilities predicting each token from index `from` on.
+    fn score_sequence(
+        &mut self,
+        sequence: &[u32],
+        from: usize,
+        mut on_position: impl FnMut(usize, &[f32]),
+    ) -> Result<()> {
+        let model = self
+            .model
+            .as_mut()
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?;
+        model.cached.clear();
+        let mut logits = vec![0.0f32; model.params.vocab_size as usize];
+        let mut log_probs = Vec::with_capacity(logits.len());
+        for pos in 0..sequence.len() - 1 {
+            forward::forward(
+                &model.mmap_model,
+                &model.weights,
+                &model.params,
+                &mut model.kv_cache,
+                sequence[pos],
+                pos,
+                &mut logits,
+            )?;
+            if pos + 1 >= from {
+                log_softmax(&logits, &mut log_probs);
+                on_position(pos + 1, &log_probs);
+            }
+        }
+        Ok(())
+    }
+
+    /// Perplexity of `text` in chunks of `context` tokens, and its
+    /// KL-divergence from `reference` if given. `on_chunk` gets the running
+    /// totals and the number of chunks after each one.
+    pub fn evaluate(
+        &mut self,
+        mut reference: Option<&mut BrainEngine>,
+        text: &str,
+        context: usize,
+        mut on_chunk: impl FnMut(&EvalSummary, usize),
+    ) -> Result<EvalSummary> {
+        let mut context = context.min(
+            self.context_size()
+                .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?,
+        );
+        let tokens = self.tokenize(text)?;
+        if let Some(reference) = reference.as_deref() {
+            context = context.min(reference.context_size().unwrap_or(0));
+            if reference.tokenize(text)? != tokens {
+                return Err(BizClawError::Brain(
+                    "The reference model tokenizes the corpus differently; KL-divergence \
+                     needs models sharing a vocabulary"
+                        .into(),
+                ));
+            }
+        }
+        if context < 4 {
+            return Err(BizClawError::ContextOverflow(format!(
+                "A context of {context} tokens is too small to evaluate"
+            )));
+        }
+        let first = context / 2;
+        let chunks: Vec<&[u32]> = tokens
+            .chunks(context - 1)
+            .filter(|chunk| chunk.len() >= first)
+            .collect();
+        if chunks.is_empty() {
+            return Err(BizClawError::Brain(format!(
+                "The corpus has {} tokens; at least {first} are needed",
+                tokens.len()
+            )));
+        }
+        let bos = self.model.as_ref().map_or(1, |m| m.tokenizer.bos_id);
+
+        let mut summary = EvalSummary {
+            kl: reference.as_ref().map(|_| 0.0),
+            ..Default::default()
+        };
+        let mut reference_log_probs: Vec<Vec<f32>> = Vec::new();
+        for chunk in &chunks {
+            let mut sequence = Vec::with_capacity(chunk.len() + 1);
+            sequence.push(bos);
+            sequence.extend_from_slice(chunk);
+
+            reference_log_probs.clear();
+            if let Some(reference) = reference.as_deref_mut() {
+                reference.score_sequence(&sequence, first, |_, log_probs| {
+                    reference_log_probs.push(log_probs.to_vec())
+                })?;
+            }
+
+            let mut nll = 0.0f64;
+            let mut kl = 0.0f64;
+            let mut top1_matches = 0;
+            let mut scored = 0;
+            self.score_sequence(&sequence, first, |index, log_probs| {
+                nll -= log_probs[sequence[index] as usize] as f64;
+                if let Some(reference) = reference_log_probs.get(scored) {
+                    kl += kl_divergence(reference, log_probs);
+                    if argmax(reference) == argmax(log_probs) {
+                        top1_matches += 1;
+                    }
+                }
+                scored += 1;
+            })?;
+
+            summary.chunks += 1;
+            summary.tokens += scored;
+            summary.nll += nll;
+            if let Some(total) = summary.kl.as_mut() {
+                *total += kl;
+            }
+            summary.top1_matches += top1_matches;
+            on_chunk(&summary, chunks.len());
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_softmax_and_kl() {
+        let mut p = Vec::new();
+        log_softmax(&[1.0, 2.0, 3.0], &mut p);
+        let total: f32 = p.iter().map(|l| l.exp()).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+        assert_eq!(argmax(&p), 2);
+
+        let mut q = Vec::new();
+        log_softmax(&[3.0, 2.0, 1.0], &mut q);
+        assert!(kl_divergence(&p, &p).abs() < 1e-9);
+        assert!(kl_divergence(&p, &q) > 0.0);
+
+        let summary = EvalSummary {
+            chunks: 1,
+            tokens: 2,
+            nll: 2.0 * 4.0f64.ln(),
+            kl: Some(0.5),
+            top1_matches: 1,
+        };
+        assert!((summary.perplexity() - 4.0).abs() < 1e-9);
+        assert_eq!(summary.mean_kl(), Some(0.25));
+        assert_eq!(summary.top1_agreement(), Some(0.5));
+    }
+}
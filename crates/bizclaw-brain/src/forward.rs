@@ -6,7 +6,10 @@
 //! Reads weights from mmap, dequantizes on-the-fly, computes the forward
 //! pass, and produces logits for the next token.
 
-use crate::{kv_cache::KvCache, mmap::MmapModel, model::ModelParams, quant, rope, tensor};
+use crate::{
+    attention, gpu, kv_cache::KvCache, mmap::MmapModel, model::ModelParams, quant, rope::Rope,
+    simd, tensor, thread_pool, tuning,
+};
 use bizclaw_core::error::{BizClawError, Result};
 
 /// Transformer weights — indices into the GGUF tensor list.
@@ -17,6 +20,8 @@ pub struct TransformerWeights {
     pub output_norm: Option<usize>,
     // LM head (output projection)
     pub output: Option<usize>,
+    // Per-pair RoPE frequency factors (Llama 3.1)
+    pub rope_freqs: Option<usize>,
     // Per-layer weight indices
     pub layers: Vec<LayerWeights>,
 }
@@ -59,12 +64,28 @@ impl TransformerWeights {
         Self {
             token_embd: find("token_embd.weight"),
             output_norm: find("output_norm.weight"),
-            output: find("output.weight"),
+            // Models with tied embeddings reuse the embedding table as
+            // the LM head
+            output: find("output.weight").or(find("token_embd.weight")),
+            rope_freqs: find("rope_freqs.weight"),
             layers,
         }
     }
 }
 
+/// RoPE for this model, with the frequency factors of its
+/// `rope_freqs.weight` tensor if it has one.
+pub fn rope(model: &MmapModel, weights: &TransformerWeights, params: &ModelParams) -> Result<Rope> {
+    let rope = params.rope();
+    match weights.rope_freqs {
+        Some(idx) => {
+            let factors = dequant_weight(model, idx, params.rope_dims as usize / 2)?;
+            Ok(rope.with_freq_factors(&factors))
+        }
+        None => Ok(rope),
+    }
+}
+
 /// Run a single-token forward pass through the LLaMA transformer.
 ///
 /// Returns logits of shape [vocab_size].
@@ -76,62 +97,65 @@ pub fn forward(
     token: u32,
     pos: usize,
     logits: &mut [f32],
+) -> Result<()> {
+    let dim = params.dim as usize;
+    let vocab_size = params.vocab_size as usize;
+    let mut hidden = vec![0.0f32; dim];
+    forward_hidden(model, weights, params, kv_cache, token, pos, &mut hidden)?;
+
+    // ---- Step 4: LM Head → logits, soft-capped for Gemma-2 ----
+    matmul_weight(model, weights.output, &hidden, logits, vocab_size, dim)?;
+    tensor::softcap(logits, params.final_logit_softcap);
+
+    Ok(())
+}
+
+/// Run the transformer up to the final RMSNorm, without the LM head.
+///
+/// Writes the normalized hidden state of shape [dim] — the token's
+/// representation used for embeddings.
+pub fn forward_hidden(
+    model: &MmapModel,
+    weights: &TransformerWeights,
+    params: &ModelParams,
+    kv_cache: &mut KvCache,
+    token: u32,
+    pos: usize,
+    hidden: &mut [f32],
 ) -> Result<()> {
     let dim = params.dim as usize;
     let hidden_dim = params.hidden_dim as usize;
     let n_heads = params.n_heads as usize;
     let n_kv_heads = params.n_kv_heads as usize;
     let head_dim = params.head_dim as usize;
-    let kv_dim = n_kv_heads * head_dim;
-    let vocab_size = params.vocab_size as usize;
+    let q_dim = params.q_dim() as usize;
+    let kv_dim = params.kv_dim() as usize;
+    let rope = rope(model, weights, params)?;
+    let alibi_slopes = attention::alibi_slopes(n_heads, params.alibi_max_bias);
+    let alibi = params.uses_alibi().then(|| attention::Alibi {
+        slopes: &alibi_slopes,
+        pos,
+        capacity: kv_cache.capacity(),
+    });
 
     // ---- Step 1: Token embedding lookup ----
     let mut x = vec![0.0f32; dim];
-    if let Some(embd_idx) = weights.token_embd {
-        let embd_tensor = &model.gguf.tensors[embd_idx];
-        let embd_data = model.tensor_data(embd_idx)?;
-        let offset = token as usize * dim;
-        let row_bytes =
-            dim * embd_tensor.ggml_type.type_size() / embd_tensor.ggml_type.block_size();
-
-        // If embedding is F32, direct copy. Otherwise dequantize.
-        if embd_tensor.ggml_type == crate::gguf::GgmlType::F32 {
-            let byte_offset = offset * 4;
-            for i in 0..dim {
-                let o = byte_offset + i * 4;
-                if o + 4 <= embd_data.len() {
-                    x[i] = f32::from_le_bytes([
-                        embd_data[o],
-                        embd_data[o + 1],
-                        embd_data[o + 2],
-                        embd_data[o + 3],
-                    ]);
-                }
-            }
-        } else {
-            let row_offset = token as usize * row_bytes;
-            if row_offset + row_bytes <= embd_data.len() {
-                quant::dequantize_row(
-                    &embd_data[row_offset..],
-                    &mut x,
-                    dim,
-                    embd_tensor.ggml_type,
-                )?;
-            }
-        }
-    } else {
-        return Err(BizClawError::Brain("Missing token_embd.weight".into()));
-    }
+    let embd_idx = weights
+        .token_embd
+        .ok_or_else(|| BizClawError::Brain("Missing token_embd.weight".into()))?;
+    dequant_row(model, embd_idx, token as usize, &mut x)?;
 
     // Scratch buffers
     let mut xb = vec![0.0f32; dim]; // after RMSNorm
     let mut xb2 = vec![0.0f32; dim]; // second residual
-    let mut q = vec![0.0f32; dim]; // query
-    let mut k = vec![0.0f32; kv_dim]; // key
-    let mut v = vec![0.0f32; kv_dim]; // value
-    let mut att_out = vec![0.0f32; dim]; // attention output
+    let mut q = vec![0.0f32; q_dim]; // query, all heads
+    let mut k = vec![0.0f32; kv_dim]; // key, KV heads only
+    let mut v = vec![0.0f32; kv_dim]; // value, KV heads only
+    let mut att_out = vec![0.0f32; q_dim]; // attention output
     let mut hb = vec![0.0f32; hidden_dim]; // FFN hidden
     let mut hb2 = vec![0.0f32; hidden_dim]; // FFN gate
+    let mut key_rows = Vec::new(); // keys dequantized from a Q8 cache
+    let mut value_rows = Vec::new(); // values dequantized from a Q8 cache
 
     // ---- Step 2: Transformer layers ----
     for l in 0..params.n_layers as usize {
@@ -140,63 +164,69 @@ pub fn forward(
         // 2a. Attention RMSNorm
         if let Some(norm_idx) = layer.attn_norm {
             let norm_w = dequant_weight(model, norm_idx, dim)?;
-            tensor::rmsnorm(&mut xb, &x, &norm_w, params.rms_norm_eps);
+            simd::rmsnorm_simd(&mut xb, &x, &norm_w, params.rms_norm_eps);
         } else {
             xb.copy_from_slice(&x);
         }
 
-        // 2b. Q/K/V projections
-        matmul_weight(model, layer.attn_q, &xb, &mut q, dim, dim)?;
-        matmul_weight(model, layer.attn_k, &xb, &mut k, kv_dim, dim)?;
-        matmul_weight(model, layer.attn_v, &xb, &mut v, kv_dim, dim)?;
-
-        // 2c. RoPE on Q and K
-        rope::apply_rope_multi_head(&mut q, pos, n_heads, head_dim, params.rope_theta);
-        rope::apply_rope_multi_head(&mut k, pos, n_kv_heads, head_dim, params.rope_theta);
+        // 2b. Q/K/V projections, as one batch of stealable row chunks
+        matmul_weights(
+            model,
+            &xb,
+            [
+                (layer.attn_q, &mut q),
+                (layer.attn_k, &mut k),
+                (layer.attn_v, &mut v),
+            ],
+        )?;
+
+        // 2c. RoPE on Q and K, unless attention biases by distance instead
+        if alibi.is_none() {
+            rope.apply_multi_head(&mut q, pos, n_heads);
+            rope.apply_multi_head(&mut k, pos, n_kv_heads);
+        }
 
         // 2d. Store K/V in cache
-        kv_cache.key_at_mut(l, pos).copy_from_slice(&k);
-        kv_cache.value_at_mut(l, pos).copy_from_slice(&v);
-
-        let seq_len = pos + 1;
-
-        // 2e. Multi-head attention (with GQA)
-        let kv_keys = kv_cache.keys(l, seq_len);
-        let kv_values = kv_cache.values(l, seq_len);
-
-        for h in 0..n_heads {
-            let kv_h = h * n_kv_heads / n_heads; // GQA: map query head to kv head
-            let q_slice = &q[h * head_dim..(h + 1) * head_dim];
-
-            // Build key/value slices for this kv head
-            let mut head_keys = vec![0.0f32; seq_len * head_dim];
-            let mut head_values = vec![0.0f32; seq_len * head_dim];
-            for t in 0..seq_len {
-                let k_start = t * kv_dim + kv_h * head_dim;
-                let v_start = t * kv_dim + kv_h * head_dim;
-                head_keys[t * head_dim..(t + 1) * head_dim]
-                    .copy_from_slice(&kv_keys[k_start..k_start + head_dim]);
-                head_values[t * head_dim..(t + 1) * head_dim]
-                    .copy_from_slice(&kv_values[v_start..v_start + head_dim]);
-            }
-
-            // Attention for this head
-            let mut head_out = vec![0.0f32; head_dim];
-            crate::attention::attention(
-                &mut head_out,
-                q_slice,
-                &head_keys,
-                &head_values,
-                seq_len,
+        kv_cache.store_key(l, pos, &k);
+        kv_cache.store_value(l, pos, &v);
+
+        // Positions attended to: all so far, or the last `sliding_window`
+        // of them, which is all a windowed cache holds.
+        let seq_len = (pos + 1).min(kv_cache.capacity());
+
+        // 2e. Multi-head attention, on the GPU if there is one and the
+        // model needs no ALiBi bias. Otherwise under GQA each group of
+        // query heads reads one shared KV head straight from the cache's
+        // pages, a tile at a time
+        let offloaded = alibi.is_none()
+            && gpu::attend(
+                kv_cache,
+                l,
+                pos,
+                &k,
+                &v,
+                &q,
+                &mut att_out,
+                n_heads,
+                n_kv_heads,
+                params.attn_logit_softcap,
+            )?;
+        if !offloaded {
+            attention::paged_attention(
+                &mut att_out,
+                &q,
+                &kv_cache.key_pages(l, seq_len, &mut key_rows),
+                &kv_cache.value_pages(l, seq_len, &mut value_rows),
+                n_heads,
+                n_kv_heads,
                 head_dim,
+                params.attn_logit_softcap,
+                alibi.as_ref(),
             );
-
-            // Copy to full output
-            att_out[h * head_dim..(h + 1) * head_dim].copy_from_slice(&head_out);
         }
 
         // 2f. Output projection
-        matmul_weight(model, layer.attn_output, &att_out, &mut xb2, dim, dim)?;
+        matmul_weight(model, layer.attn_output, &att_out, &mut xb2, dim, q_dim)?;
 
         // 2g. Residual connection
         tensor::elementwise_add(&mut x, &xb2);
@@ -204,7 +234,7 @@ pub fn forward(
         // 2h. FFN RMSNorm
         if let Some(norm_idx) = layer.ffn_norm {
             let norm_w = dequant_weight(model, norm_idx, dim)?;
-            tensor::rmsnorm(&mut xb, &x, &norm_w, params.rms_norm_eps);
+            simd::rmsnorm_simd(&mut xb, &x, &norm_w, params.rms_norm_eps);
         } else {
             xb.copy_from_slice(&x);
         }
@@ -213,11 +243,13 @@ pub fn forward(
         // gate = silu(xb @ gate_proj)
         // up   = xb @ up_proj
         // down = (gate * up) @ down_proj
-        matmul_weight(model, layer.ffn_gate, &xb, &mut hb, hidden_dim, dim)?;
-        matmul_weight(model, layer.ffn_up, &xb, &mut hb2, hidden_dim, dim)?;
+        matmul_weights(
+            model,
+            &xb,
+            [(layer.ffn_gate, &mut hb), (layer.ffn_up, &mut hb2)],
+        )?;
 
-        tensor::silu(&mut hb);
-        tensor::elementwise_mul(&mut hb, &hb2);
+        simd::swiglu_simd(&mut hb, &hb2);
 
         matmul_weight(model, layer.ffn_down, &hb, &mut xb2, dim, hidden_dim)?;
 
@@ -228,14 +260,11 @@ pub fn forward(
     // ---- Step 3: Final RMSNorm ----
     if let Some(norm_idx) = weights.output_norm {
         let norm_w = dequant_weight(model, norm_idx, dim)?;
-        tensor::rmsnorm(&mut xb, &x, &norm_w, params.rms_norm_eps);
+        simd::rmsnorm_simd(hidden, &x, &norm_w, params.rms_norm_eps);
     } else {
-        xb.copy_from_slice(&x);
+        hidden.copy_from_slice(&x);
     }
 
-    // ---- Step 4: LM Head → logits ----
-    matmul_weight(model, weights.output, &xb, logits, vocab_size, dim)?;
-
     Ok(())
 }
 
@@ -248,6 +277,49 @@ fn dequant_weight(model: &MmapModel, tensor_idx: usize, n_elements: usize) -> Re
     Ok(output)
 }
 
+/// Dequantize row `row` of a weight matrix whose rows hold
+/// `output.len()` values, whatever type the tensor is stored in.
+fn dequant_row(model: &MmapModel, tensor_idx: usize, row: usize, output: &mut [f32]) -> Result<()> {
+    let data = model.tensor_data(tensor_idx)?;
+    let tensor = &model.gguf.tensors[tensor_idx];
+    let (cols, ggml_type) = (output.len(), tensor.ggml_type);
+    if !cols.is_multiple_of(ggml_type.block_size()) {
+        return Err(BizClawError::Brain(format!(
+            "{}: {ggml_type:?} rows of {cols} values don't split into whole blocks",
+            tensor.name
+        )));
+    }
+    let row_bytes = cols / ggml_type.block_size() * ggml_type.type_size();
+    let bytes = data
+        .get(row * row_bytes..(row + 1) * row_bytes)
+        .ok_or_else(|| BizClawError::Brain(format!("{}: no row {row}", tensor.name)))?;
+    quant::dequantize_row(bytes, output, cols, ggml_type)
+}
+
+/// Multiply `input` by several weight tensors sharing it, on the GPU if
+/// there is one, else as one [`thread_pool::matmul_batch`]: each output
+/// holds that tensor's rows.
+fn matmul_weights<const N: usize>(
+    model: &MmapModel,
+    input: &[f32],
+    targets: [(Option<usize>, &mut [f32]); N],
+) -> Result<()> {
+    let mut jobs = Vec::with_capacity(N);
+    for (tensor_idx, output) in targets {
+        let idx = tensor_idx.ok_or_else(|| BizClawError::Brain("Missing weight tensor".into()))?;
+        jobs.push(thread_pool::MatmulJob {
+            output,
+            data: model.tensor_data(idx)?,
+            ggml_type: model.gguf.tensors[idx].ggml_type,
+            input,
+        });
+    }
+    if gpu::matmul(&mut jobs)? {
+        return Ok(());
+    }
+    thread_pool::matmul_batch(jobs, &tuning::current())
+}
+
 /// Matrix-vector multiply using a weight tensor from mmap.
 /// output[rows] = weight[rows x cols] @ input[cols]
 fn matmul_weight(
@@ -258,16 +330,6 @@ fn matmul_weight(
     rows: usize,
     cols: usize,
 ) -> Result<()> {
-    let idx = tensor_idx.ok_or_else(|| BizClawError::Brain("Missing weight tensor".into()))?;
-    let data = model.tensor_data(idx)?;
-    let tensor = &model.gguf.tensors[idx];
-
-    // Dequantize entire weight matrix
-    let n_elements = rows * cols;
-    let mut weight = vec![0.0f32; n_elements];
-    quant::dequantize_row(data, &mut weight, n_elements, tensor.ggml_type)?;
-
-    // MatMul
-    tensor::matmul(output, &weight, input, rows, cols);
-    Ok(())
+    debug_assert_eq!((output.len(), input.len()), (rows, cols));
+    matmul_weights(model, input, [(tensor_idx, output)])
 }
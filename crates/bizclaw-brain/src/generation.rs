@@ -0,0 +1,373 @@
+//! Per-call generation settings and results.
+//!
+//! [`GenerationParams`] overrides the engine's sampler settings and token
+//! limit for one call, leaving them as they were afterwards.
+//! [`GenerationResult`] reports why generation ended, how many tokens went
+//! in and out, and where the time went.
+//!
+//! Generation ends at the first of `stop` in the output, which is cut
+//! before it. While streaming, text that may be the start of a stop
+//! sequence is held back until the next tokens show it isn't, so callers
+//! never see part of one.
+//!
+//! When the prompt and output fill the model's context, the oldest half of
+//! it after BOS and the first `keep_tokens` is dropped and generation goes
+//! on, unless [`BrainConfig::context_shift`](crate::BrainConfig) is off.
+//!
+//! `logit_bias` is added to the logits of the given token IDs before
+//! sampling: negative values make a token rarer, `f32::NEG_INFINITY` rules
+//! it out (see [`ban`](GenerationParams::ban); in JSON, where there is no
+//! infinity, `-100` does the same in practice), and a large positive value
+//! all but forces it.
+
+use crate::sampler::SamplerConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Settings for one generation. `None` keeps the engine's own setting.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationParams {
+    /// Tokens to generate at most; the engine's `max_tokens` if unset.
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub repeat_penalty: Option<f32>,
+    /// Restart the random sequence from this seed, for reproducible output.
+    pub seed: Option<u64>,
+    /// End generation at the first of these in the output, e.g.
+    /// `"\nUser:"`, leaving it out of the text.
+    pub stop: Vec<String>,
+    /// Added to these tokens' logits before sampling.
+    pub logit_bias: HashMap<u32, f32>,
+    /// Prompt tokens after BOS, e.g. the system prompt's, that are never
+    /// dropped when a full context shifts.
+    pub keep_tokens: u32,
+    /// KV cache slot holding this conversation, below
+    /// [`BrainConfig::slots`](crate::BrainConfig::slots). Each slot keeps
+    /// its own prompt cache, so conversations taking turns each only run
+    /// their new tokens.
+    pub slot: u32,
+    /// Constrain output to one JSON value, see
+    /// [`generate_json`](crate::BrainEngine::generate_json).
+    pub json: bool,
+}
+
+impl GenerationParams {
+    pub fn max_tokens(max_tokens: u32) -> Self {
+        Self {
+            max_tokens: Some(max_tokens),
+            ..Default::default()
+        }
+    }
+
+    /// Never sample `tokens`.
+    pub fn ban(mut self, tokens: impl IntoIterator<Item = u32>) -> Self {
+        self.logit_bias
+            .extend(tokens.into_iter().map(|id| (id, f32::NEG_INFINITY)));
+        self
+    }
+
+    /// `base` with these overrides applied.
+    pub fn sampler_config(&self, base: &SamplerConfig) -> SamplerConfig {
+        SamplerConfig {
+            temperature: self.temperature.unwrap_or(base.temperature),
+            top_p: self.top_p.unwrap_or(base.top_p),
+            top_k: self.top_k.unwrap_or(base.top_k),
+            repeat_penalty: self.repeat_penalty.unwrap_or(base.repeat_penalty),
+            ..base.clone()
+        }
+    }
+}
+
+/// Why generation ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// The model emitted its end-of-sequence token.
+    Eos,
+    /// `max_tokens` were generated, or the context is full.
+    Length,
+    /// A stop sequence matched, or a JSON value closed.
+    Stop,
+    /// The token callback asked to stop.
+    Cancelled,
+}
+
+impl FinishReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Eos => "eos",
+            Self::Length => "length",
+            Self::Stop => "stop",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// Where a generation's time went. Zero in the browser build, which has
+/// no clock.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timings {
+    pub tokenize: Duration,
+    /// Running the prompt tokens the prompt cache didn't hold.
+    pub prefill: Duration,
+    /// Sampling and running the generated tokens.
+    pub decode: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.tokenize + self.prefill + self.decode
+    }
+}
+
+/// The outcome of one generation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenerationResult {
+    pub text: String,
+    pub finish_reason: FinishReason,
+    /// Prompt tokens, BOS included.
+    pub prompt_tokens: usize,
+    /// Of those, the ones the prompt cache already held.
+    pub cached_tokens: usize,
+    pub generated_tokens: usize,
+    pub timings: Timings,
+}
+
+impl GenerationResult {
+    /// Prompt tokens run per second of prefill.
+    pub fn prefill_tps(&self) -> f64 {
+        per_sec(
+            self.prompt_tokens - self.cached_tokens,
+            self.timings.prefill,
+        )
+    }
+
+    /// Tokens generated per second of decoding.
+    pub fn decode_tps(&self) -> f64 {
+        per_sec(self.generated_tokens, self.timings.decode)
+    }
+}
+
+fn per_sec(tokens: usize, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        0.0
+    } else {
+        tokens as f64 / elapsed.as_secs_f64()
+    }
+}
+
+/// Add `bias` to `logits`; token IDs past the end are ignored.
+pub(crate) fn apply_logit_bias(logits: &mut [f32], bias: &HashMap<u32, f32>) {
+    for (&id, &bias) in bias {
+        if let Some(logit) = logits.get_mut(id as usize) {
+            *logit += bias;
+        }
+    }
+}
+
+/// Length of the longest end of `text` that `stop` starts with.
+fn stop_prefix_len(text: &str, stop: &str) -> usize {
+    (1..stop.len())
+        .rev()
+        .find(|&n| stop.is_char_boundary(n) && text.ends_with(&stop[..n]))
+        .unwrap_or(0)
+}
+
+/// Output text as it is generated, cut at the first stop sequence.
+pub(crate) struct StopScanner<'a> {
+    stops: Vec<&'a str>,
+    text: String,
+    /// End of the text passed on so far; after it is the held-back tail.
+    emitted: usize,
+}
+
+impl<'a> StopScanner<'a> {
+    pub(crate) fn new(stops: &'a [String]) -> Self {
+        Self {
+            stops: stops
+                .iter()
+                .map(String::as_str)
+                .filter(|s| !s.is_empty())
+                .collect(),
+            text: String::new(),
+            emitted: 0,
+        }
+    }
+
+    /// Add a generated `piece`. Returns the text that can be passed on,
+    /// and whether a stop sequence matched, cutting the text before it.
+    pub(crate) fn push(&mut self, piece: &str) -> (&str, bool) {
+        self.text.push_str(piece);
+        // Nothing before `emitted` can start a stop sequence: it would
+        // have been held back.
+        let tail = &self.text[self.emitted..];
+        let matched = self.stops.iter().filter_map(|s| tail.find(s)).min();
+        let end = match matched {
+            Some(at) => {
+                self.text.truncate(self.emitted + at);
+                self.text.len()
+            }
+            None => {
+                let held = self
+                    .stops
+                    .iter()
+                    .map(|s| stop_prefix_len(&self.text, s))
+                    .max()
+                    .unwrap_or(0);
+                (self.text.len() - held).max(self.emitted)
+            }
+        };
+        let start = std::mem::replace(&mut self.emitted, end);
+        (&self.text[start..end], matched.is_some())
+    }
+
+    /// The held-back tail, once generation has ended without a match.
+    pub(crate) fn flush(&mut self) -> &str {
+        let start = std::mem::replace(&mut self.emitted, self.text.len());
+        &self.text[start..]
+    }
+
+    pub(crate) fn into_text(self) -> String {
+        self.text
+    }
+}
+
+/// Times consecutive phases. `std::time::Instant` panics on wasm32, so
+/// there every lap is zero.
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    last: std::time::Instant,
+}
+
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            last: std::time::Instant::now(),
+        }
+    }
+
+    /// Time since the start or the previous lap.
+    pub(crate) fn lap(&mut self) -> Duration {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let now = std::time::Instant::now();
+            let elapsed = now - self.last;
+            self.last = now;
+            elapsed
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            Duration::ZERO
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params_override_sampler() {
+        let base = SamplerConfig::default();
+        assert_eq!(GenerationParams::default().sampler_config(&base), base);
+
+        let params = GenerationParams {
+            temperature: Some(0.0),
+            top_k: Some(1),
+            ..GenerationParams::max_tokens(16)
+        };
+        let config = params.sampler_config(&base);
+        assert_eq!(config.temperature, 0.0);
+        assert_eq!(config.top_k, 1);
+        assert_eq!(config.top_p, base.top_p);
+        assert_eq!(config.repeat_last_n, base.repeat_last_n);
+
+        let params: GenerationParams =
+            serde_json::from_str(r#"{"max_tokens": 32, "seed": 7}"#).unwrap();
+        assert_eq!(params.max_tokens, Some(32));
+        assert_eq!(params.seed, Some(7));
+        assert!(!params.json);
+    }
+
+    #[test]
+    fn test_logit_bias() {
+        let params = GenerationParams {
+            logit_bias: HashMap::from([(1, 2.0), (3, -1.5)]),
+            ..Default::default()
+        }
+        .ban([0]);
+        let mut logits = vec![1.0; 4];
+        apply_logit_bias(&mut logits, &params.logit_bias);
+        assert_eq!(logits, [f32::NEG_INFINITY, 3.0, 1.0, -0.5]);
+
+        // Banned tokens stay out however the sampler scales logits.
+        let mut sampler = crate::sampler::Sampler::new(SamplerConfig {
+            temperature: 0.0,
+            ..Default::default()
+        });
+        let mut logits = vec![5.0, 1.0, 0.5];
+        apply_logit_bias(
+            &mut logits,
+            &GenerationParams::default().ban([0]).logit_bias,
+        );
+        assert_eq!(sampler.sample(&mut logits, &[]), 1);
+
+        let params: GenerationParams =
+            serde_json::from_str(r#"{"logit_bias": {"42": -100.0}}"#).unwrap();
+        assert_eq!(params.logit_bias[&42], -100.0);
+    }
+
+    #[test]
+    fn test_stop_scanner() {
+        let stops = vec!["\nUser:".to_string(), "</answer>".to_string()];
+        let mut scanner = StopScanner::new(&stops);
+        assert_eq!(scanner.push("Hello"), ("Hello", false));
+        // May be the start of "\nUser:": held back.
+        assert_eq!(scanner.push(" there\nUs"), (" there", false));
+        // It wasn't.
+        assert_eq!(scanner.push("ually"), ("\nUsually", false));
+        assert_eq!(scanner.push(" yes</ans"), (" yes", false));
+        assert_eq!(scanner.push("wer> trailing"), ("", true));
+        assert_eq!(scanner.into_text(), "Hello there\nUsually yes");
+
+        // A stop sequence inside one piece, and an unfinished one at the end.
+        let mut scanner = StopScanner::new(&stops);
+        assert_eq!(scanner.push("a</answer>b"), ("a", true));
+        let mut scanner = StopScanner::new(&stops);
+        assert_eq!(scanner.push("done\nUse"), ("done", false));
+        assert_eq!(scanner.flush(), "\nUse");
+        assert_eq!(scanner.into_text(), "done\nUse");
+
+        // Without stop sequences everything passes straight through.
+        let none = vec![String::new()];
+        let mut scanner = StopScanner::new(&none);
+        assert_eq!(scanner.push("x\n"), ("x\n", false));
+        assert_eq!(stop_prefix_len("abc</an", "</answer>"), 4);
+        assert_eq!(stop_prefix_len("abc", "</answer>"), 0);
+    }
+
+    #[test]
+    fn test_result_rates() {
+        let result = GenerationResult {
+            text: "hi".into(),
+            finish_reason: FinishReason::Eos,
+            prompt_tokens: 12,
+            cached_tokens: 2,
+            generated_tokens: 20,
+            timings: Timings {
+                tokenize: Duration::from_millis(1),
+                prefill: Duration::from_millis(500),
+                decode: Duration::from_secs(2),
+            },
+        };
+        assert_eq!(result.prefill_tps(), 20.0);
+        assert_eq!(result.decode_tps(), 10.0);
+        assert_eq!(result.timings.total(), Duration::from_millis(2501));
+        assert_eq!(serde_json::to_value(result.finish_reason).unwrap(), "eos");
+    }
+}
@@ -9,10 +9,14 @@
 //! - Tensor infos
 //! - Padding to alignment boundary
 //! - Tensor data
+//!
+//! Model files come from users, so parsing is bounded by [`ParseOptions`]
+//! and every malformed input is a [`GgufError`], never a panic or an
+//! allocation the file's size doesn't justify.
 
 use bizclaw_core::error::{BizClawError, Result};
 use std::collections::HashMap;
-use std::io::{Read, Seek};
+use std::io::{self, Read, Seek, Write};
 
 /// GGUF magic number.
 const GGUF_MAGIC: u32 = 0x46554747; // "GGUF" in little-endian
@@ -20,6 +24,85 @@ const GGUF_MAGIC: u32 = 0x46554747; // "GGUF" in little-endian
 /// Supported GGUF version.
 const GGUF_VERSION: u32 = 3;
 
+/// Most dimensions a tensor may have, as in ggml.
+pub const MAX_DIMS: u32 = 4;
+
+/// What a GGUF header may declare before parsing gives up, so a corrupt or
+/// hostile file fails with a [`GgufError`] rather than exhausting memory.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Bytes from the start of the file to the end of the metadata.
+    pub max_metadata_bytes: u64,
+    pub max_string_len: u64,
+    pub max_array_len: u64,
+    pub max_tensors: u64,
+    /// Recovery mode: leave out tensors of types this build can't read,
+    /// instead of failing. Models that need them for inference still won't
+    /// run, but the rest of the file can be inspected.
+    pub skip_unknown_tensors: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_metadata_bytes: 256 * 1024 * 1024,
+            max_string_len: 1024 * 1024,
+            max_array_len: 10_000_000,
+            max_tensors: 65_536,
+            skip_unknown_tensors: false,
+        }
+    }
+}
+
+/// Why a GGUF file was rejected.
+#[derive(Debug, thiserror::Error)]
+pub enum GgufError {
+    #[error("Invalid GGUF magic: 0x{0:08X} (expected 0x{magic:08X})", magic = GGUF_MAGIC)]
+    BadMagic(u32),
+    #[error("Unsupported GGUF version: {0} (expected {version})", version = GGUF_VERSION)]
+    UnsupportedVersion(u32),
+    #[error("File ends inside {0}")]
+    Truncated(&'static str),
+    #[error("{what} is {value}, over the limit of {limit}")]
+    LimitExceeded {
+        what: &'static str,
+        value: u64,
+        limit: u64,
+    },
+    #[error("{0} is not valid UTF-8")]
+    InvalidUtf8(&'static str),
+    #[error("Unknown metadata value type: {0}")]
+    UnknownValueType(u32),
+    #[error("Nested metadata arrays are not supported")]
+    NestedArray,
+    #[error("Invalid alignment {0}: must be a power of two")]
+    BadAlignment(u64),
+    #[error("Tensor '{name}' has unknown type {type_id}")]
+    UnknownTensorType { name: String, type_id: u32 },
+    #[error("Tensor '{name}' is invalid: {reason}")]
+    InvalidTensor { name: String, reason: String },
+    #[error("Invalid model metadata: {0}")]
+    InvalidMetadata(String),
+    #[error("Invalid tokenizer: {0}")]
+    InvalidTokenizer(String),
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+impl From<GgufError> for BizClawError {
+    fn from(e: GgufError) -> Self {
+        BizClawError::GgufParse(e.to_string())
+    }
+}
+
+fn check_limit(what: &'static str, value: u64, limit: u64) -> std::result::Result<(), GgufError> {
+    if value > limit {
+        Err(GgufError::LimitExceeded { what, value, limit })
+    } else {
+        Ok(())
+    }
+}
+
 /// GGUF metadata value types.
 #[derive(Debug, Clone)]
 pub enum GgufValue {
@@ -79,6 +162,44 @@ impl GgufValue {
     }
 }
 
+/// Strings are cut to 60 characters and arrays to their first 5 items,
+/// so a vocabulary prints on one line.
+impl std::fmt::Display for GgufValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GgufValue::U8(v) => write!(f, "{v}"),
+            GgufValue::I8(v) => write!(f, "{v}"),
+            GgufValue::U16(v) => write!(f, "{v}"),
+            GgufValue::I16(v) => write!(f, "{v}"),
+            GgufValue::U32(v) => write!(f, "{v}"),
+            GgufValue::I32(v) => write!(f, "{v}"),
+            GgufValue::U64(v) => write!(f, "{v}"),
+            GgufValue::I64(v) => write!(f, "{v}"),
+            GgufValue::F32(v) => write!(f, "{v}"),
+            GgufValue::F64(v) => write!(f, "{v}"),
+            GgufValue::Bool(v) => write!(f, "{v}"),
+            GgufValue::String(s) if s.chars().count() > 60 => {
+                let head: String = s.chars().take(60).collect();
+                write!(f, "{:?}… ({} chars)", head, s.chars().count())
+            }
+            GgufValue::String(s) => write!(f, "{s:?}"),
+            GgufValue::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().take(5).enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                if items.len() > 5 {
+                    write!(f, ", … {} items", items.len())?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
 /// GGML tensor types (quantization formats).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
@@ -125,6 +246,14 @@ impl GgmlType {
             13 => Ok(GgmlType::Q5K),
             14 => Ok(GgmlType::Q6K),
             15 => Ok(GgmlType::Q8K),
+            16 => Ok(GgmlType::IQ2XXS),
+            17 => Ok(GgmlType::IQ2XS),
+            18 => Ok(GgmlType::IQ3XXS),
+            19 => Ok(GgmlType::IQ1S),
+            20 => Ok(GgmlType::IQ4NL),
+            21 => Ok(GgmlType::IQ3S),
+            22 => Ok(GgmlType::IQ2S),
+            23 => Ok(GgmlType::IQ4XS),
             _ => Err(BizClawError::GgufParse(format!("Unknown GGML type: {v}"))),
         }
     }
@@ -135,14 +264,20 @@ impl GgmlType {
             GgmlType::F32 | GgmlType::F16 => 1,
             GgmlType::Q4_0 | GgmlType::Q4_1 => 32,
             GgmlType::Q5_0 | GgmlType::Q5_1 => 32,
-            GgmlType::Q8_0 | GgmlType::Q8_1 => 32,
+            GgmlType::Q8_0 | GgmlType::Q8_1 | GgmlType::IQ4NL => 32,
             GgmlType::Q2K
             | GgmlType::Q3K
             | GgmlType::Q4K
             | GgmlType::Q5K
             | GgmlType::Q6K
-            | GgmlType::Q8K => 256,
-            _ => 32,
+            | GgmlType::Q8K
+            | GgmlType::IQ2XXS
+            | GgmlType::IQ2XS
+            | GgmlType::IQ2S
+            | GgmlType::IQ3XXS
+            | GgmlType::IQ3S
+            | GgmlType::IQ1S
+            | GgmlType::IQ4XS => 256,
         }
     }
 
@@ -163,7 +298,14 @@ impl GgmlType {
             GgmlType::Q5K => 176,
             GgmlType::Q6K => 210,
             GgmlType::Q8K => 292,
-            _ => 0,
+            GgmlType::IQ2XXS => 66, // 2 + 256/4
+            GgmlType::IQ2XS => 74,  // 2 + 256/4 + 256/32
+            GgmlType::IQ2S => 82,   // 2 + 256/4 + 256/32 + 256/32
+            GgmlType::IQ3XXS => 98, // 2 + 3*256/8
+            GgmlType::IQ3S => 110,  // 2 + 256/4 + 256/32 + 256/8 + 256/64
+            GgmlType::IQ1S => 50,   // 2 + 256/8 + 256/16
+            GgmlType::IQ4NL => 18,  // 2 + 32/2
+            GgmlType::IQ4XS => 136, // 2 + 2 + 256/64 + 256/2
         }
     }
 }
@@ -191,6 +333,17 @@ impl TensorInfo {
         let ts = self.ggml_type.type_size();
         (n.div_ceil(bs) * ts) as u64
     }
+
+    /// Size of this tensor in bytes, or `None` if it overflows a `u64`.
+    pub fn checked_size_bytes(&self) -> Option<u64> {
+        let n = self
+            .dims
+            .iter()
+            .try_fold(1u64, |n, &dim| n.checked_mul(dim))?;
+        let bs = self.ggml_type.block_size() as u64;
+        let ts = self.ggml_type.type_size() as u64;
+        n.div_ceil(bs).checked_mul(ts)
+    }
 }
 
 /// Parsed GGUF file header — metadata + tensor index.
@@ -201,38 +354,53 @@ pub struct GgufFile {
     pub tensors: Vec<TensorInfo>,
     pub data_offset: u64,
     pub alignment: u64,
+    /// Tensors left out of `tensors` for having a type this build can't
+    /// read, see [`ParseOptions::skip_unknown_tensors`].
+    pub skipped_tensors: Vec<String>,
 }
 
 impl GgufFile {
-    /// Parse a GGUF file from a reader.
+    /// Parse a GGUF file from a reader, with the default limits.
     pub fn parse<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        Ok(Self::parse_with(reader, &ParseOptions::default())?)
+    }
+
+    /// Parse a GGUF file from a reader. Anything past `options`' limits or
+    /// inconsistent with itself fails with a [`GgufError`].
+    pub fn parse_with<R: Read + Seek>(
+        reader: &mut R,
+        options: &ParseOptions,
+    ) -> std::result::Result<Self, GgufError> {
+        let mut r = Counted {
+            inner: &mut *reader,
+            read: 0,
+        };
+
         // Read magic
-        let magic = read_u32(reader)?;
+        let magic = read_u32(&mut r, "the magic number")?;
         if magic != GGUF_MAGIC {
-            return Err(BizClawError::GgufParse(format!(
-                "Invalid GGUF magic: 0x{:08X} (expected 0x{:08X})",
-                magic, GGUF_MAGIC
-            )));
+            return Err(GgufError::BadMagic(magic));
         }
 
         // Read version
-        let version = read_u32(reader)?;
+        let version = read_u32(&mut r, "the version")?;
         if version != GGUF_VERSION {
-            return Err(BizClawError::GgufParse(format!(
-                "Unsupported GGUF version: {version} (expected {GGUF_VERSION})"
-            )));
+            return Err(GgufError::UnsupportedVersion(version));
         }
 
         // Read counts
-        let tensor_count = read_u64(reader)?;
-        let metadata_kv_count = read_u64(reader)?;
+        let tensor_count = read_u64(&mut r, "the tensor count")?;
+        check_limit("Tensor count", tensor_count, options.max_tensors)?;
+        let metadata_kv_count = read_u64(&mut r, "the metadata count")?;
 
         // Read metadata
         let mut metadata = HashMap::new();
         for _ in 0..metadata_kv_count {
-            let key = read_string(reader)?;
-            let value = read_value(reader)?;
+            let key = read_string(&mut r, "a metadata key", options.max_string_len)?;
+            let type_id = read_u32(&mut r, "a metadata value")?;
+            let value = read_value(&mut r, type_id, options)?;
             metadata.insert(key, value);
+            check_limit("Metadata size", r.read, options.max_metadata_bytes)?;
         }
 
         // Get alignment (default 32)
@@ -240,33 +408,62 @@ impl GgufFile {
             .get("general.alignment")
             .and_then(|v| v.as_u64())
             .unwrap_or(32);
+        if !alignment.is_power_of_two() {
+            return Err(GgufError::BadAlignment(alignment));
+        }
 
         // Read tensor infos
         let mut tensors = Vec::with_capacity(tensor_count as usize);
+        let mut skipped_tensors = Vec::new();
         for _ in 0..tensor_count {
-            let name = read_string(reader)?;
-            let n_dims = read_u32(reader)?;
+            let name = read_string(&mut r, "a tensor name", options.max_string_len)?;
+            let n_dims = read_u32(&mut r, "a tensor info")?;
+            if n_dims > MAX_DIMS {
+                return Err(GgufError::InvalidTensor {
+                    name,
+                    reason: format!("{n_dims} dimensions; at most {MAX_DIMS} are allowed"),
+                });
+            }
             let mut dims = Vec::with_capacity(n_dims as usize);
             for _ in 0..n_dims {
-                dims.push(read_u64(reader)?);
+                dims.push(read_u64(&mut r, "a tensor info")?);
             }
-            let type_id = read_u32(reader)?;
-            let ggml_type = GgmlType::from_u32(type_id)?;
-            let offset = read_u64(reader)?;
-
-            tensors.push(TensorInfo {
+            let type_id = read_u32(&mut r, "a tensor info")?;
+            let offset = read_u64(&mut r, "a tensor info")?;
+
+            let ggml_type = match GgmlType::from_u32(type_id) {
+                Ok(ggml_type) => ggml_type,
+                Err(_) if options.skip_unknown_tensors => {
+                    tracing::warn!("Skipping tensor '{name}' of unknown type {type_id}");
+                    skipped_tensors.push(name);
+                    continue;
+                }
+                Err(_) => return Err(GgufError::UnknownTensorType { name, type_id }),
+            };
+            let tensor = TensorInfo {
                 name,
                 n_dims,
                 dims,
                 ggml_type,
                 offset,
-            });
+            };
+            if tensor.checked_size_bytes().is_none() {
+                return Err(GgufError::InvalidTensor {
+                    name: tensor.name,
+                    reason: "its size overflows".into(),
+                });
+            }
+            if offset % alignment != 0 {
+                return Err(GgufError::InvalidTensor {
+                    name: tensor.name,
+                    reason: format!("offset {offset} is not a multiple of {alignment}"),
+                });
+            }
+            tensors.push(tensor);
         }
 
         // Calculate data offset (aligned to alignment)
-        let current_pos = reader
-            .stream_position()
-            .map_err(|e| BizClawError::GgufParse(e.to_string()))?;
+        let current_pos = reader.stream_position()?;
         let data_offset = current_pos.div_ceil(alignment) * alignment;
 
         Ok(GgufFile {
@@ -275,14 +472,48 @@ impl GgufFile {
             tensors,
             data_offset,
             alignment,
+            skipped_tensors,
         })
     }
 
+    /// Check that every tensor's data lies within a file of `file_len` bytes.
+    pub fn check_bounds(&self, file_len: u64) -> std::result::Result<(), GgufError> {
+        for tensor in &self.tensors {
+            let end = tensor
+                .checked_size_bytes()
+                .and_then(|size| size.checked_add(tensor.offset))
+                .and_then(|end| end.checked_add(self.data_offset));
+            if end.is_none_or(|end| end > file_len) {
+                return Err(GgufError::InvalidTensor {
+                    name: tensor.name.clone(),
+                    reason: format!("its data runs past the end of the file ({file_len} bytes)"),
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// Get model architecture name.
     pub fn architecture(&self) -> Option<&str> {
         self.metadata.get("general.architecture")?.as_str()
     }
 
+    /// The tensor type holding most of the weights, e.g. `Q4_0` for a
+    /// "Q4_0" model whose norms are still `F32`.
+    pub fn main_type(&self) -> Option<GgmlType> {
+        let mut bytes: Vec<(GgmlType, u64)> = Vec::new();
+        for tensor in &self.tensors {
+            match bytes.iter_mut().find(|(t, _)| *t == tensor.ggml_type) {
+                Some((_, total)) => *total += tensor.size_bytes(),
+                None => bytes.push((tensor.ggml_type, tensor.size_bytes())),
+            }
+        }
+        bytes
+            .into_iter()
+            .max_by_key(|(_, total)| *total)
+            .map(|(t, _)| t)
+    }
+
     /// Get model name.
     pub fn model_name(&self) -> Option<&str> {
         self.metadata.get("general.name")?.as_str()
@@ -299,124 +530,320 @@ impl GgufFile {
     }
 }
 
-// ===== Low-level reading helpers =====
-
-fn read_u8<R: Read>(r: &mut R) -> Result<u8> {
-    let mut buf = [0u8; 1];
-    r.read_exact(&mut buf)
+/// Write a GGUF v3 header — metadata, then tensor infos — padded to
+/// `alignment`. Tensor offsets are relative to the data that follows.
+/// Returns the bytes written.
+pub fn write_header<W: Write>(
+    w: &mut W,
+    metadata: &[(String, GgufValue)],
+    tensors: &[TensorInfo],
+    alignment: u64,
+) -> Result<u64> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(&GGUF_MAGIC.to_le_bytes());
+    buf.extend_from_slice(&GGUF_VERSION.to_le_bytes());
+    buf.extend_from_slice(&(tensors.len() as u64).to_le_bytes());
+    buf.extend_from_slice(&(metadata.len() as u64).to_le_bytes());
+    for (key, value) in metadata {
+        write_string(&mut buf, key);
+        buf.extend_from_slice(&value_type(value).to_le_bytes());
+        write_value(&mut buf, value);
+    }
+    for tensor in tensors {
+        write_string(&mut buf, &tensor.name);
+        buf.extend_from_slice(&(tensor.dims.len() as u32).to_le_bytes());
+        for dim in &tensor.dims {
+            buf.extend_from_slice(&dim.to_le_bytes());
+        }
+        buf.extend_from_slice(&(tensor.ggml_type as u32).to_le_bytes());
+        buf.extend_from_slice(&tensor.offset.to_le_bytes());
+    }
+    let padded = (buf.len() as u64).div_ceil(alignment) * alignment;
+    buf.resize(padded as usize, 0);
+    w.write_all(&buf)
         .map_err(|e| BizClawError::GgufParse(e.to_string()))?;
-    Ok(buf[0])
+    Ok(padded)
 }
 
-fn read_u32<R: Read>(r: &mut R) -> Result<u32> {
-    let mut buf = [0u8; 4];
-    r.read_exact(&mut buf)
-        .map_err(|e| BizClawError::GgufParse(e.to_string()))?;
-    Ok(u32::from_le_bytes(buf))
+// ===== Low-level writing helpers =====
+
+fn value_type(value: &GgufValue) -> u32 {
+    match value {
+        GgufValue::U8(_) => 0,
+        GgufValue::I8(_) => 1,
+        GgufValue::U16(_) => 2,
+        GgufValue::I16(_) => 3,
+        GgufValue::U32(_) => 4,
+        GgufValue::I32(_) => 5,
+        GgufValue::F32(_) => 6,
+        GgufValue::Bool(_) => 7,
+        GgufValue::String(_) => 8,
+        GgufValue::Array(_) => 9,
+        GgufValue::U64(_) => 10,
+        GgufValue::I64(_) => 11,
+        GgufValue::F64(_) => 12,
+    }
 }
 
-fn read_i32<R: Read>(r: &mut R) -> Result<i32> {
-    let mut buf = [0u8; 4];
-    r.read_exact(&mut buf)
-        .map_err(|e| BizClawError::GgufParse(e.to_string()))?;
-    Ok(i32::from_le_bytes(buf))
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u64).to_le_bytes());
+    buf.extend_from_slice(s.as_bytes());
 }
 
-fn read_u64<R: Read>(r: &mut R) -> Result<u64> {
-    let mut buf = [0u8; 8];
-    r.read_exact(&mut buf)
-        .map_err(|e| BizClawError::GgufParse(e.to_string()))?;
-    Ok(u64::from_le_bytes(buf))
+fn write_value(buf: &mut Vec<u8>, value: &GgufValue) {
+    match value {
+        GgufValue::U8(v) => buf.push(*v),
+        GgufValue::I8(v) => buf.push(*v as u8),
+        GgufValue::U16(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        GgufValue::I16(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        GgufValue::U32(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        GgufValue::I32(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        GgufValue::F32(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        GgufValue::Bool(v) => buf.push(*v as u8),
+        GgufValue::String(v) => write_string(buf, v),
+        GgufValue::Array(items) => {
+            // The element type of an empty array isn't kept; any will do.
+            let elem_type = items.first().map_or(4, value_type);
+            buf.extend_from_slice(&elem_type.to_le_bytes());
+            buf.extend_from_slice(&(items.len() as u64).to_le_bytes());
+            for item in items {
+                write_value(buf, item);
+            }
+        }
+        GgufValue::U64(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        GgufValue::I64(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        GgufValue::F64(v) => buf.extend_from_slice(&v.to_le_bytes()),
+    }
 }
 
-fn read_i64<R: Read>(r: &mut R) -> Result<i64> {
-    let mut buf = [0u8; 8];
-    r.read_exact(&mut buf)
-        .map_err(|e| BizClawError::GgufParse(e.to_string()))?;
-    Ok(i64::from_le_bytes(buf))
+// ===== Low-level reading helpers =====
+
+/// A reader that counts the bytes read, for the metadata size limit.
+struct Counted<R> {
+    inner: R,
+    read: u64,
 }
 
-fn read_f32<R: Read>(r: &mut R) -> Result<f32> {
-    let mut buf = [0u8; 4];
-    r.read_exact(&mut buf)
-        .map_err(|e| BizClawError::GgufParse(e.to_string()))?;
-    Ok(f32::from_le_bytes(buf))
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        Ok(n)
+    }
 }
 
-fn read_f64<R: Read>(r: &mut R) -> Result<f64> {
-    let mut buf = [0u8; 8];
-    r.read_exact(&mut buf)
-        .map_err(|e| BizClawError::GgufParse(e.to_string()))?;
-    Ok(f64::from_le_bytes(buf))
+fn read_bytes<R: Read, const N: usize>(
+    r: &mut R,
+    what: &'static str,
+) -> std::result::Result<[u8; N], GgufError> {
+    let mut buf = [0u8; N];
+    r.read_exact(&mut buf).map_err(|e| eof(e, what))?;
+    Ok(buf)
 }
 
-fn read_string<R: Read>(r: &mut R) -> Result<String> {
-    let len = read_u64(r)? as usize;
-    if len > 1024 * 1024 {
-        // 1MB max string
-        return Err(BizClawError::GgufParse(format!("String too long: {len}")));
+fn eof(e: io::Error, what: &'static str) -> GgufError {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        GgufError::Truncated(what)
+    } else {
+        GgufError::Io(e)
     }
-    let mut buf = vec![0u8; len];
-    r.read_exact(&mut buf)
-        .map_err(|e| BizClawError::GgufParse(e.to_string()))?;
-    String::from_utf8(buf).map_err(|e| BizClawError::GgufParse(e.to_string()))
 }
 
-fn read_value<R: Read>(r: &mut R) -> Result<GgufValue> {
-    let type_id = read_u32(r)?;
-    match type_id {
-        0 => Ok(GgufValue::U8(read_u8(r)?)),
-        1 => Ok(GgufValue::I8(read_u8(r)? as i8)),
-        2 => {
-            let mut buf = [0u8; 2];
-            r.read_exact(&mut buf)
-                .map_err(|e| BizClawError::GgufParse(e.to_string()))?;
-            Ok(GgufValue::U16(u16::from_le_bytes(buf)))
-        }
-        3 => {
-            let mut buf = [0u8; 2];
-            r.read_exact(&mut buf)
-                .map_err(|e| BizClawError::GgufParse(e.to_string()))?;
-            Ok(GgufValue::I16(i16::from_le_bytes(buf)))
-        }
-        4 => Ok(GgufValue::U32(read_u32(r)?)),
-        5 => Ok(GgufValue::I32(read_i32(r)?)),
-        6 => Ok(GgufValue::F32(read_f32(r)?)),
-        7 => Ok(GgufValue::Bool(read_u8(r)? != 0)),
-        8 => Ok(GgufValue::String(read_string(r)?)),
+fn read_u32<R: Read>(r: &mut R, what: &'static str) -> std::result::Result<u32, GgufError> {
+    Ok(u32::from_le_bytes(read_bytes(r, what)?))
+}
+
+fn read_u64<R: Read>(r: &mut R, what: &'static str) -> std::result::Result<u64, GgufError> {
+    Ok(u64::from_le_bytes(read_bytes(r, what)?))
+}
+
+fn read_string<R: Read>(
+    r: &mut R,
+    what: &'static str,
+    max_len: u64,
+) -> std::result::Result<String, GgufError> {
+    let len = read_u64(r, what)?;
+    check_limit("String length", len, max_len)?;
+    let mut buf = vec![0u8; len as usize];
+    r.read_exact(&mut buf).map_err(|e| eof(e, what))?;
+    String::from_utf8(buf).map_err(|_| GgufError::InvalidUtf8(what))
+}
+
+fn read_value<R: Read>(
+    r: &mut Counted<R>,
+    type_id: u32,
+    options: &ParseOptions,
+) -> std::result::Result<GgufValue, GgufError> {
+    const WHAT: &str = "a metadata value";
+    Ok(match type_id {
+        0 => GgufValue::U8(u8::from_le_bytes(read_bytes(r, WHAT)?)),
+        1 => GgufValue::I8(i8::from_le_bytes(read_bytes(r, WHAT)?)),
+        2 => GgufValue::U16(u16::from_le_bytes(read_bytes(r, WHAT)?)),
+        3 => GgufValue::I16(i16::from_le_bytes(read_bytes(r, WHAT)?)),
+        4 => GgufValue::U32(u32::from_le_bytes(read_bytes(r, WHAT)?)),
+        5 => GgufValue::I32(i32::from_le_bytes(read_bytes(r, WHAT)?)),
+        6 => GgufValue::F32(f32::from_le_bytes(read_bytes(r, WHAT)?)),
+        7 => GgufValue::Bool(read_bytes::<_, 1>(r, WHAT)?[0] != 0),
+        8 => GgufValue::String(read_string(r, WHAT, options.max_string_len)?),
         9 => {
             // Array: element_type (u32) + count (u64) + elements
-            let elem_type = read_u32(r)?;
-            let count = read_u64(r)? as usize;
-            if count > 10_000_000 {
-                return Err(BizClawError::GgufParse(format!("Array too large: {count}")));
+            let elem_type = read_u32(r, "an array")?;
+            if elem_type == 9 {
+                return Err(GgufError::NestedArray);
             }
-            let mut arr = Vec::with_capacity(count);
+            let count = read_u64(r, "an array")?;
+            check_limit("Array length", count, options.max_array_len)?;
+            // Grown as elements arrive: the count alone proves nothing.
+            let mut arr = Vec::with_capacity(count.min(65_536) as usize);
             for _ in 0..count {
-                let val = match elem_type {
-                    0 => GgufValue::U8(read_u8(r)?),
-                    4 => GgufValue::U32(read_u32(r)?),
-                    5 => GgufValue::I32(read_i32(r)?),
-                    6 => GgufValue::F32(read_f32(r)?),
-                    8 => GgufValue::String(read_string(r)?),
-                    10 => GgufValue::U64(read_u64(r)?),
-                    11 => GgufValue::I64(read_i64(r)?),
-                    12 => GgufValue::F64(read_f64(r)?),
-                    _ => {
-                        return Err(BizClawError::GgufParse(format!(
-                            "Unknown array element type: {elem_type}"
-                        )));
-                    }
-                };
-                arr.push(val);
+                arr.push(read_value(r, elem_type, options)?);
+                check_limit("Metadata size", r.read, options.max_metadata_bytes)?;
             }
-            Ok(GgufValue::Array(arr))
+            GgufValue::Array(arr)
         }
-        10 => Ok(GgufValue::U64(read_u64(r)?)),
-        11 => Ok(GgufValue::I64(read_i64(r)?)),
-        12 => Ok(GgufValue::F64(read_f64(r)?)),
-        _ => Err(BizClawError::GgufParse(format!(
-            "Unknown value type: {type_id}"
-        ))),
+        10 => GgufValue::U64(u64::from_le_bytes(read_bytes(r, WHAT)?)),
+        11 => GgufValue::I64(i64::from_le_bytes(read_bytes(r, WHAT)?)),
+        12 => GgufValue::F64(f64::from_le_bytes(read_bytes(r, WHAT)?)),
+        _ => return Err(GgufError::UnknownValueType(type_id)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn header(type_id: u32, dims: &[u64]) -> Vec<u8> {
+        let metadata = [(
+            "general.architecture".to_string(),
+            GgufValue::String("llama".into()),
+        )];
+        let tensors = [TensorInfo {
+            name: "output_norm.weight".into(),
+            n_dims: dims.len() as u32,
+            dims: dims.to_vec(),
+            ggml_type: GgmlType::F32,
+            offset: 0,
+        }];
+        // Unpadded, so the tensor index ends the file.
+        let mut file = Vec::new();
+        write_header(&mut file, &metadata, &tensors, 1).unwrap();
+        // The type ID is followed only by the tensor's offset.
+        let type_at = file.len() - 12;
+        file[type_at..type_at + 4].copy_from_slice(&type_id.to_le_bytes());
+        file
+    }
+
+    fn parse(bytes: &[u8], options: &ParseOptions) -> std::result::Result<GgufFile, GgufError> {
+        GgufFile::parse_with(&mut Cursor::new(bytes), options)
+    }
+
+    #[test]
+    fn test_truncated_files_fail_cleanly() {
+        let file = header(GgmlType::F32 as u32, &[4]);
+        for len in 0..file.len() {
+            let err = parse(&file[..len], &ParseOptions::default()).unwrap_err();
+            assert!(matches!(err, GgufError::Truncated(_)), "{len}: {err}");
+        }
+        let gguf = parse(&file, &ParseOptions::default()).unwrap();
+        // 16 bytes of data are declared but none follow.
+        assert!(gguf.check_bounds(file.len() as u64).is_err());
+        assert!(gguf.check_bounds(gguf.data_offset + 16).is_ok());
+    }
+
+    #[test]
+    fn test_limits() {
+        let file = header(GgmlType::F32 as u32, &[4]);
+        let mut bad_magic = file.clone();
+        bad_magic[0] = b'X';
+        assert!(matches!(
+            parse(&bad_magic, &ParseOptions::default()),
+            Err(GgufError::BadMagic(_))
+        ));
+
+        let tight = ParseOptions {
+            max_string_len: 4,
+            ..Default::default()
+        };
+        assert!(matches!(
+            parse(&file, &tight),
+            Err(GgufError::LimitExceeded {
+                what: "String length",
+                ..
+            })
+        ));
+
+        let tight = ParseOptions {
+            max_metadata_bytes: 32,
+            ..Default::default()
+        };
+        assert!(matches!(
+            parse(&file, &tight),
+            Err(GgufError::LimitExceeded {
+                what: "Metadata size",
+                ..
+            })
+        ));
+
+        // A tensor count no file could hold.
+        let mut huge = file.clone();
+        huge[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            parse(&huge, &ParseOptions::default()),
+            Err(GgufError::LimitExceeded {
+                what: "Tensor count",
+                ..
+            })
+        ));
+
+        let overflowing = header(GgmlType::F32 as u32, &[u64::MAX, 2]);
+        assert!(matches!(
+            parse(&overflowing, &ParseOptions::default()),
+            Err(GgufError::InvalidTensor { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_tensor_types() {
+        let file = header(99, &[4]);
+        assert!(matches!(
+            parse(&file, &ParseOptions::default()),
+            Err(GgufError::UnknownTensorType { type_id: 99, .. })
+        ));
+
+        let recover = ParseOptions {
+            skip_unknown_tensors: true,
+            ..Default::default()
+        };
+        let gguf = parse(&file, &recover).unwrap();
+        assert!(gguf.tensors.is_empty());
+        assert_eq!(gguf.skipped_tensors, ["output_norm.weight"]);
+        assert_eq!(gguf.architecture(), Some("llama"));
+    }
+
+    #[test]
+    fn test_main_type() {
+        let tensor = |name: &str, ggml_type, n: u64| TensorInfo {
+            name: name.into(),
+            n_dims: 1,
+            dims: vec![n],
+            ggml_type,
+            offset: 0,
+        };
+        let mut gguf = GgufFile {
+            version: 3,
+            metadata: HashMap::new(),
+            tensors: Vec::new(),
+            data_offset: 0,
+            alignment: 32,
+            skipped_tensors: Vec::new(),
+        };
+        assert_eq!(gguf.main_type(), None);
+        gguf.tensors = vec![
+            tensor("output_norm.weight", GgmlType::F32, 256),
+            tensor("blk.0.attn_q.weight", GgmlType::Q4_0, 4096),
+            tensor("blk.0.attn_k.weight", GgmlType::Q4_0, 4096),
+        ];
+        assert_eq!(gguf.main_type(), Some(GgmlType::Q4_0));
     }
 }
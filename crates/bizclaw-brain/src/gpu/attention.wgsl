@@ -0,0 +1,93 @@
+// Single-query attention over a layer's cached keys and values. A
+// workgroup per query head: its scores into `scores`, softmax, then the
+// weighted sum of values, a dimension per invocation. Under GQA each group
+// of query heads reads one shared KV head.
+
+struct Params {
+    n_heads: u32,
+    n_kv_heads: u32,
+    head_dim: u32,
+    seq_len: u32,
+    // Rows per layer of `keys` and `values`, and of `scores` per head
+    capacity: u32,
+    scale: f32,
+    // Gemma-2 soft cap on the scores, 0 for none
+    softcap: f32,
+    _pad: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> q: array<f32>;
+@group(0) @binding(2) var<storage, read> keys: array<f32>;
+@group(0) @binding(3) var<storage, read> values: array<f32>;
+@group(0) @binding(4) var<storage, read_write> scores: array<f32>;
+@group(0) @binding(5) var<storage, read_write> output: array<f32>;
+
+const WG: u32 = 64u;
+var<workgroup> partial: array<f32, 64>;
+
+@compute @workgroup_size(64)
+fn main(
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(local_invocation_index) lid: u32,
+) {
+    let head = wid.x;
+    let group = params.n_heads / params.n_kv_heads;
+    let kv_dim = params.n_kv_heads * params.head_dim;
+    let kv_base = head / group * params.head_dim;
+    let q_base = head * params.head_dim;
+    let s_base = head * params.capacity;
+
+    // 1. Scores, and the largest this invocation saw
+    var local_max = -3.4e38;
+    for (var t = lid; t < params.seq_len; t += WG) {
+        var s = 0.0;
+        for (var d = 0u; d < params.head_dim; d++) {
+            s += q[q_base + d] * keys[t * kv_dim + kv_base + d];
+        }
+        s *= params.scale;
+        if (params.softcap > 0.0) {
+            s = params.softcap * tanh(s / params.softcap);
+        }
+        scores[s_base + t] = s;
+        local_max = max(local_max, s);
+    }
+    partial[lid] = local_max;
+    workgroupBarrier();
+    for (var stride = WG / 2u; stride > 0u; stride >>= 1u) {
+        if (lid < stride) {
+            partial[lid] = max(partial[lid], partial[lid + stride]);
+        }
+        workgroupBarrier();
+    }
+    let largest = partial[0];
+    workgroupBarrier();
+
+    // 2. Softmax numerators and their sum
+    var local_sum = 0.0;
+    for (var t = lid; t < params.seq_len; t += WG) {
+        let e = exp(scores[s_base + t] - largest);
+        scores[s_base + t] = e;
+        local_sum += e;
+    }
+    partial[lid] = local_sum;
+    workgroupBarrier();
+    for (var stride = WG / 2u; stride > 0u; stride >>= 1u) {
+        if (lid < stride) {
+            partial[lid] += partial[lid + stride];
+        }
+        workgroupBarrier();
+    }
+    let total = partial[0];
+    // Every invocation reads every score next
+    storageBarrier();
+
+    // 3. Weighted values
+    for (var d = lid; d < params.head_dim; d += WG) {
+        var acc = 0.0;
+        for (var t = 0u; t < params.seq_len; t++) {
+            acc += scores[s_base + t] * values[t * kv_dim + kv_base + d];
+        }
+        output[q_base + d] = acc / total;
+    }
+}
@@ -0,0 +1,91 @@
+// Matrix-vector multiply straight from the mapped GGUF weight bytes:
+// out[row] = dot(weights[row], x). A threadgroup per row, its threads
+// striding over the row's values (or blocks) and summing their partial
+// dot products at the end. Mirrors matmul.wgsl.
+
+#include <metal_stdlib>
+using namespace metal;
+
+struct Params {
+    uint rows;
+    uint cols;
+    // 0 F32, 1 F16, 2 Q4_0, 3 Q8_0
+    uint kind;
+    uint pad;
+};
+
+constant uint WG = 64;
+
+kernel void matmul(
+    constant Params& p [[buffer(0)]],
+    device const uchar* weights [[buffer(1)]],
+    device const float* x [[buffer(2)]],
+    device float* out [[buffer(3)]],
+    uint row [[threadgroup_position_in_grid]],
+    uint lid [[thread_index_in_threadgroup]])
+{
+    threadgroup float partial[WG];
+    if (row >= p.rows) {
+        return;
+    }
+    const uint cols = p.cols;
+
+    float sum = 0.0f;
+    switch (p.kind) {
+        case 0: {
+            device const float* w = (device const float*)weights + (ulong)row * cols;
+            for (uint i = lid; i < cols; i += WG) {
+                sum += w[i] * x[i];
+            }
+            break;
+        }
+        case 1: {
+            device const half* w = (device const half*)weights + (ulong)row * cols;
+            for (uint i = lid; i < cols; i += WG) {
+                sum += float(w[i]) * x[i];
+            }
+            break;
+        }
+        case 2: {
+            // 18-byte blocks: f16 scale, 16 bytes of nibble pairs
+            const uint blocks = cols / 32;
+            for (uint b = lid; b < blocks; b += WG) {
+                device const uchar* block = weights + ((ulong)row * blocks + b) * 18;
+                float acc = 0.0f;
+                for (uint j = 0; j < 16; j++) {
+                    const uchar q = block[2 + j];
+                    acc += (float(q & 0xF) - 8.0f) * x[b * 32 + j];
+                    acc += (float(q >> 4) - 8.0f) * x[b * 32 + j + 16];
+                }
+                sum += float(*(device const half*)block) * acc;
+            }
+            break;
+        }
+        case 3: {
+            // 34-byte blocks: f16 scale, 32 signed bytes
+            const uint blocks = cols / 32;
+            for (uint b = lid; b < blocks; b += WG) {
+                device const uchar* block = weights + ((ulong)row * blocks + b) * 34;
+                device const char* q = (device const char*)(block + 2);
+                float acc = 0.0f;
+                for (uint j = 0; j < 32; j++) {
+                    acc += float(q[j]) * x[b * 32 + j];
+                }
+                sum += float(*(device const half*)block) * acc;
+            }
+            break;
+        }
+    }
+
+    partial[lid] = sum;
+    threadgroup_barrier(mem_flags::mem_threadgroup);
+    for (uint stride = WG / 2; stride > 0; stride >>= 1) {
+        if (lid < stride) {
+            partial[lid] += partial[lid + stride];
+        }
+        threadgroup_barrier(mem_flags::mem_threadgroup);
+    }
+    if (lid == 0) {
+        out[row] = partial[0];
+    }
+}
@@ -0,0 +1,103 @@
+// Matrix-vector multiply straight from GGUF weight bytes:
+// output[row] = dot(weights[row], input). A workgroup per row, its
+// invocations striding over the row's values (or blocks) and summing their
+// partial dot products at the end.
+
+struct Params {
+    rows: u32,
+    cols: u32,
+    // 0 F32, 1 F16, 2 Q4_0, 3 Q8_0
+    kind: u32,
+    _pad: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> weights: array<u32>;
+@group(0) @binding(2) var<storage, read> input: array<f32>;
+@group(0) @binding(3) var<storage, read_write> output: array<f32>;
+
+const WG: u32 = 64u;
+var<workgroup> partial: array<f32, 64>;
+
+fn byte_at(i: u32) -> u32 {
+    return (weights[i >> 2u] >> ((i & 3u) * 8u)) & 0xFFu;
+}
+
+// The f16 block scale at byte `i`, which needn't be word aligned.
+fn half_at(i: u32) -> f32 {
+    return unpack2x16float(byte_at(i) | (byte_at(i + 1u) << 8u)).x;
+}
+
+fn i8_at(i: u32) -> f32 {
+    return f32(bitcast<i32>(byte_at(i) << 24u) >> 24u);
+}
+
+@compute @workgroup_size(64)
+fn main(
+    @builtin(workgroup_id) wid: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+    @builtin(local_invocation_index) lid: u32,
+) {
+    let row = wid.y * groups.x + wid.x;
+    if (row >= params.rows) {
+        return;
+    }
+    let cols = params.cols;
+
+    var sum = 0.0;
+    switch params.kind {
+        case 0u: {
+            let base = row * cols;
+            for (var i = lid; i < cols; i += WG) {
+                sum += bitcast<f32>(weights[base + i]) * input[i];
+            }
+        }
+        case 1u: {
+            let base = row * cols;
+            for (var i = lid; i < cols; i += WG) {
+                let e = base + i;
+                let pair = unpack2x16float(weights[e >> 1u]);
+                sum += select(pair.x, pair.y, (e & 1u) == 1u) * input[i];
+            }
+        }
+        case 2u: {
+            // 18-byte blocks: f16 scale, 16 bytes of nibble pairs
+            let blocks = cols / 32u;
+            for (var b = lid; b < blocks; b += WG) {
+                let at = (row * blocks + b) * 18u;
+                var acc = 0.0;
+                for (var j = 0u; j < 16u; j++) {
+                    let q = byte_at(at + 2u + j);
+                    acc += (f32(q & 0xFu) - 8.0) * input[b * 32u + j];
+                    acc += (f32(q >> 4u) - 8.0) * input[b * 32u + j + 16u];
+                }
+                sum += half_at(at) * acc;
+            }
+        }
+        case 3u: {
+            // 34-byte blocks: f16 scale, 32 signed bytes
+            let blocks = cols / 32u;
+            for (var b = lid; b < blocks; b += WG) {
+                let at = (row * blocks + b) * 34u;
+                var acc = 0.0;
+                for (var j = 0u; j < 32u; j++) {
+                    acc += i8_at(at + 2u + j) * input[b * 32u + j];
+                }
+                sum += half_at(at) * acc;
+            }
+        }
+        default: {}
+    }
+
+    partial[lid] = sum;
+    workgroupBarrier();
+    for (var stride = WG / 2u; stride > 0u; stride >>= 1u) {
+        if (lid < stride) {
+            partial[lid] += partial[lid + stride];
+        }
+        workgroupBarrier();
+    }
+    if (lid == 0u) {
+        output[row] = partial[0];
+    }
+}
@@ -0,0 +1,199 @@
+//! The Metal backend: matmul kernels reading the mapped model in place.
+//!
+//! On Apple Silicon CPU and GPU share memory, so a buffer made with
+//! `newBufferWithBytesNoCopy` over the file mapping is the weights
+//! themselves: nothing is uploaded, and the pages are read from disk once
+//! for both. Inputs and outputs are small shared buffers the CPU reads and
+//! writes directly.
+
+use crate::gguf::GgmlType;
+use crate::thread_pool::MatmulJob;
+use bizclaw_core::error::{BizClawError, Result};
+use metal::{CompileOptions, MTLResourceOptions, MTLSize};
+use std::sync::{Mutex, MutexGuard};
+
+/// Threads per threadgroup, `WG` in `matmul.metal`.
+const THREADS: u64 = 64;
+
+/// A model's bytes as a Metal buffer.
+struct Model {
+    start: usize,
+    len: usize,
+    buffer: metal::Buffer,
+}
+
+pub struct Device {
+    name: String,
+    device: metal::Device,
+    queue: metal::CommandQueue,
+    matmul: metal::ComputePipelineState,
+    models: Mutex<Vec<Model>>,
+}
+
+/// How `matmul.metal` decodes a weight type, if it can.
+fn weight_kind(ggml_type: GgmlType) -> Option<u32> {
+    match ggml_type {
+        GgmlType::F32 => Some(0),
+        GgmlType::F16 => Some(1),
+        GgmlType::Q4_0 => Some(2),
+        GgmlType::Q8_0 => Some(3),
+        _ => None,
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions.
+    (unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).max(4096) as usize
+}
+
+impl Device {
+    /// Open the system's default Metal device and compile the kernels.
+    pub fn new() -> Result<Self> {
+        let device = metal::Device::system_default()
+            .ok_or_else(|| BizClawError::Brain("no Metal device found".into()))?;
+        let compile_error =
+            |e: String| BizClawError::Brain(format!("can't build the Metal kernels: {e}"));
+        let library = device
+            .new_library_with_source(include_str!("matmul.metal"), &CompileOptions::new())
+            .map_err(compile_error)?;
+        let function = library
+            .get_function("matmul", None)
+            .map_err(compile_error)?;
+        let matmul = device
+            .new_compute_pipeline_state_with_function(&function)
+            .map_err(compile_error)?;
+        Ok(Self {
+            name: format!("{} (Metal)", device.name()),
+            queue: device.new_command_queue(),
+            device,
+            matmul,
+            models: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+impl super::Device for Device {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn map_model(&self, data: &[u8], mapped: bool) {
+        if data.len() as u64 > self.device.max_buffer_length() {
+            tracing::warn!(
+                "Model too large for one Metal buffer ({} bytes); matmuls stay on the CPU",
+                data.len()
+            );
+            return;
+        }
+        let options = MTLResourceOptions::StorageModeShared;
+        let page = page_size();
+        let buffer = if mapped && (data.as_ptr() as usize).is_multiple_of(page) {
+            // The mapping's pages themselves. Metal wants whole pages; the
+            // last one is mapped past the end of the file. The buffer is
+            // dropped by `unmap_model` before the mapping goes.
+            self.device.new_buffer_with_bytes_no_copy(
+                data.as_ptr().cast(),
+                data.len().next_multiple_of(page) as u64,
+                options,
+                None,
+            )
+        } else {
+            tracing::debug!("Model bytes aren't page-mapped; copying them for Metal");
+            self.device
+                .new_buffer_with_data(data.as_ptr().cast(), data.len() as u64, options)
+        };
+        lock(&self.models).push(Model {
+            start: data.as_ptr() as usize,
+            len: data.len(),
+            buffer,
+        });
+    }
+
+    fn unmap_model(&self, data: &[u8]) {
+        let start = data.as_ptr() as usize;
+        lock(&self.models).retain(|model| model.start != start);
+    }
+
+    fn matmul(&self, jobs: &mut [MatmulJob]) -> Result<bool> {
+        let models = lock(&self.models);
+        let mut bound = Vec::with_capacity(jobs.len());
+        for job in jobs.iter() {
+            let (rows, cols, ggml_type) = (job.output.len(), job.input.len(), job.ggml_type);
+            let Some(kind) = weight_kind(ggml_type) else {
+                return Ok(false);
+            };
+            let block = ggml_type.block_size();
+            // Odd shapes and short matrices are the CPU path's to report
+            if !cols.is_multiple_of(block)
+                || job.data.len() < rows * (cols / block * ggml_type.type_size())
+            {
+                return Ok(false);
+            }
+            let start = job.data.as_ptr() as usize;
+            let Some(model) = models
+                .iter()
+                .find(|model| (model.start..model.start + model.len).contains(&start))
+            else {
+                return Ok(false);
+            };
+            let offset = start - model.start;
+            // Rows are read as floats and halves from there
+            if !offset.is_multiple_of(4) {
+                return Ok(false);
+            }
+            bound.push((&model.buffer, offset as u64, kind));
+        }
+
+        metal::objc::rc::autoreleasepool(|| {
+            let options = MTLResourceOptions::StorageModeShared;
+            let command_buffer = self.queue.new_command_buffer();
+            let encoder = command_buffer.new_compute_command_encoder();
+            encoder.set_compute_pipeline_state(&self.matmul);
+            let mut outputs = Vec::with_capacity(jobs.len());
+            for (job, (weights, offset, kind)) in jobs.iter().zip(&bound) {
+                let rows = job.output.len();
+                let params = [rows as u32, job.input.len() as u32, *kind, 0];
+                encoder.set_bytes(
+                    0,
+                    std::mem::size_of_val(&params) as u64,
+                    params.as_ptr().cast(),
+                );
+                encoder.set_buffer(1, Some(weights), *offset);
+                let input = self.device.new_buffer_with_data(
+                    job.input.as_ptr().cast(),
+                    std::mem::size_of_val(job.input) as u64,
+                    options,
+                );
+                encoder.set_buffer(2, Some(&input), 0);
+                let output = self.device.new_buffer((rows * 4) as u64, options);
+                encoder.set_buffer(3, Some(&output), 0);
+                // A threadgroup per row
+                encoder.dispatch_thread_groups(
+                    MTLSize::new(rows as u64, 1, 1),
+                    MTLSize::new(THREADS, 1, 1),
+                );
+                outputs.push((input, output));
+            }
+            encoder.end_encoding();
+            command_buffer.commit();
+            command_buffer.wait_until_completed();
+            if command_buffer.status() == metal::MTLCommandBufferStatus::Error {
+                return Err(BizClawError::Inference("Metal matmul failed".into()));
+            }
+
+            for (job, (_, output)) in jobs.iter_mut().zip(&outputs) {
+                // SAFETY: the GPU is done with `output`, a shared buffer
+                // of one float per row.
+                let values = unsafe {
+                    std::slice::from_raw_parts(output.contents() as *const f32, job.output.len())
+                };
+                job.output.copy_from_slice(values);
+            }
+            Ok(true)
+        })
+    }
+}
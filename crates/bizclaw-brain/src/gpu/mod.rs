@@ -0,0 +1,200 @@
+//! GPU offload: wgpu compute shaders anywhere, Metal on Apple Silicon.
+//!
+//! With `backend = wgpu` the forward pass runs its matmuls, and attention
+//! unless the model biases it by distance (ALiBi), on whatever GPU wgpu
+//! finds: Vulkan, Metal or DX12, no vendor toolkit needed. Each matrix is
+//! uploaded the first time it is used and stays on the device. Each KV
+//! cache gets a device copy that the forward pass extends a row at a time,
+//! copied afresh whenever the cache's [`KvCache::epoch`] changes.
+//!
+//! With `backend = metal` the matmuls run as Metal kernels on the mapped
+//! model itself: Apple Silicon's unified memory lets the GPU read the
+//! pages the file is mapped to, so nothing is uploaded and the weights
+//! aren't held twice. Attention stays on the CPU, where the KV cache is.
+//!
+//! Work a backend doesn't cover (other quant types, matrices past the
+//! device's buffer limits) runs on the CPU as before. wgpu needs the `gpu`
+//! feature, Metal the `metal` feature and macOS; without them [`enable`]
+//! fails and the offload functions report that nothing ran.
+
+#[cfg(all(feature = "metal", target_os = "macos"))]
+mod metal_backend;
+#[cfg(feature = "gpu")]
+mod wgpu_backend;
+
+use crate::kv_cache::KvCache;
+use crate::thread_pool::MatmulJob;
+use bizclaw_core::error::{BizClawError, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Where the forward pass runs its heavy kernels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// The SIMD kernels on the rayon pool.
+    #[default]
+    Cpu,
+    /// wgpu compute shaders on the GPU, see the [module docs](self).
+    Wgpu,
+    /// Metal kernels reading the mapped weights in place (macOS).
+    Metal,
+}
+
+impl Backend {
+    /// `"cpu"`, `"wgpu"` or `"metal"`, as in the `[brain] backend`
+    /// setting.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "cpu" => Some(Self::Cpu),
+            "wgpu" | "gpu" => Some(Self::Wgpu),
+            "metal" => Some(Self::Metal),
+            _ => None,
+        }
+    }
+}
+
+/// A backend's device, as the forward pass uses it. Each offload returns
+/// false, leaving its outputs alone, for work the device can't take.
+trait Device: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn matmul(&self, jobs: &mut [MatmulJob]) -> Result<bool>;
+
+    fn attend(
+        &self,
+        _kv_cache: &KvCache,
+        _layer: usize,
+        _pos: usize,
+        _key: &[f32],
+        _value: &[f32],
+        _q: &[f32],
+        _output: &mut [f32],
+        _n_heads: usize,
+        _n_kv_heads: usize,
+        _softcap: f32,
+    ) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn map_model(&self, _data: &[u8], _mapped: bool) {}
+
+    fn unmap_model(&self, data: &[u8]);
+
+    fn forget_cache(&self, _id: u64) {}
+}
+
+static DEVICE: OnceLock<Box<dyn Device>> = OnceLock::new();
+
+/// Open the GPU for `backend` and offload to it from now on. Returns the
+/// adapter's name. The first backend enabled stays for the life of the
+/// process.
+pub fn enable(backend: Backend) -> Result<String> {
+    if let Some(device) = DEVICE.get() {
+        return Ok(device.name().to_string());
+    }
+    let device = open(backend)?;
+    // Another thread may have won the race; its device serves too
+    Ok(DEVICE.get_or_init(|| device).name().to_string())
+}
+
+fn open(backend: Backend) -> Result<Box<dyn Device>> {
+    match backend {
+        Backend::Cpu => Err(BizClawError::Brain("the CPU backend needs no GPU".into())),
+        #[cfg(feature = "gpu")]
+        Backend::Wgpu => Ok(Box::new(wgpu_backend::Device::new()?)),
+        #[cfg(not(feature = "gpu"))]
+        Backend::Wgpu => Err(BizClawError::Brain(
+            "this build has no wgpu support; rebuild with the `gpu` feature".into(),
+        )),
+        #[cfg(all(feature = "metal", target_os = "macos"))]
+        Backend::Metal => Ok(Box::new(metal_backend::Device::new()?)),
+        #[cfg(not(all(feature = "metal", target_os = "macos")))]
+        Backend::Metal => Err(BizClawError::Brain(
+            "this build has no Metal support; it needs macOS and the `metal` feature".into(),
+        )),
+    }
+}
+
+/// Whether [`enable`] has opened a GPU.
+pub fn enabled() -> bool {
+    DEVICE.get().is_some()
+}
+
+/// Run a batch of matmuls on the GPU. Returns false, leaving the outputs
+/// alone, if there is none or it can't take one of the matrices; the
+/// caller then runs the batch on the CPU.
+pub fn matmul(jobs: &mut [MatmulJob]) -> Result<bool> {
+    match DEVICE.get() {
+        Some(device) => device.matmul(jobs),
+        None => Ok(false),
+    }
+}
+
+/// Attention of `q` over the first `pos + 1` positions of `layer`, whose
+/// key and value at `pos` were just stored in `kv_cache`, into `output`.
+/// Returns false if there is no GPU, its backend leaves attention to the
+/// CPU or the layer doesn't fit on it; the caller then attends on the CPU.
+///
+/// A device copy of the cache only stays in step if every row stored is
+/// passed through here, so a model either attends on the GPU throughout
+/// or not at all.
+pub fn attend(
+    kv_cache: &KvCache,
+    layer: usize,
+    pos: usize,
+    key: &[f32],
+    value: &[f32],
+    q: &[f32],
+    output: &mut [f32],
+    n_heads: usize,
+    n_kv_heads: usize,
+    softcap: f32,
+) -> Result<bool> {
+    match DEVICE.get() {
+        Some(device) => device.attend(
+            kv_cache, layer, pos, key, value, q, output, n_heads, n_kv_heads, softcap,
+        ),
+        None => Ok(false),
+    }
+}
+
+/// Show the GPU a newly loaded model's bytes whole, before its first
+/// forward pass; `mapped` if they are a file mapping. Metal wraps a
+/// mapping in place and copies anything else; wgpu uploads matrices as
+/// they are used instead.
+pub fn map_model(data: &[u8], mapped: bool) {
+    if let Some(device) = DEVICE.get() {
+        device.map_model(data, mapped);
+    }
+}
+
+/// Forget everything the GPU holds of a model whose bytes are about to
+/// go away.
+pub fn unmap_model(data: &[u8]) {
+    if let Some(device) = DEVICE.get() {
+        device.unmap_model(data);
+    }
+}
+
+/// Drop the device copy of the KV cache with [`KvCache::id`] `id`, which
+/// is going away.
+pub fn forget_cache(id: u64) {
+    if let Some(device) = DEVICE.get() {
+        device.forget_cache(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_from_name() {
+        assert_eq!(Backend::from_name("CPU"), Some(Backend::Cpu));
+        assert_eq!(Backend::from_name("wgpu"), Some(Backend::Wgpu));
+        assert_eq!(Backend::from_name("metal"), Some(Backend::Metal));
+        assert_eq!(Backend::from_name("cuda"), None);
+        assert!(enable(Backend::Cpu).is_err());
+    }
+}
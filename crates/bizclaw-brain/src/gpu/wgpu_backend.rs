@@ -0,0 +1,399 @@
+//! The wgpu backend: pipelines, uploaded matrices and KV cache copies.
+
+use crate::gguf::GgmlType;
+use crate::kv_cache::KvCache;
+use crate::thread_pool::MatmulJob;
+use bizclaw_core::error::{BizClawError, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use wgpu::util::DeviceExt;
+
+/// Workgroups per dispatch dimension every device supports.
+const MAX_GROUPS: u32 = 65535;
+
+/// A matrix on the device, or `None` for one it can't take.
+type Weights = Option<Arc<wgpu::Buffer>>;
+
+pub struct Device {
+    name: String,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    matmul: wgpu::ComputePipeline,
+    attention: wgpu::ComputePipeline,
+    /// Matrices by the address and length of their bytes in the model.
+    weights: Mutex<HashMap<(usize, usize), Weights>>,
+    /// KV cache copies by [`KvCache::id`].
+    caches: Mutex<HashMap<u64, Vec<Option<LayerKv>>>>,
+}
+
+/// One layer of a KV cache on the device, a row per slot.
+struct LayerKv {
+    keys: wgpu::Buffer,
+    values: wgpu::Buffer,
+    /// The [`KvCache::epoch`] the rows were last copied at.
+    epoch: u64,
+}
+
+/// How `matmul.wgsl` decodes a weight type, if it can.
+fn weight_kind(ggml_type: GgmlType) -> Option<u32> {
+    match ggml_type {
+        GgmlType::F32 => Some(0),
+        GgmlType::F16 => Some(1),
+        GgmlType::Q4_0 => Some(2),
+        GgmlType::Q8_0 => Some(3),
+        _ => None,
+    }
+}
+
+fn to_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Device {
+    /// Open the highest-performance adapter wgpu finds.
+    pub fn new() -> Result<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .ok_or_else(|| BizClawError::Brain("no GPU adapter found".into()))?;
+        let info = adapter.get_info();
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("bizclaw-brain"),
+                required_features: wgpu::Features::empty(),
+                // As large buffers as the adapter allows, for the LM head
+                required_limits: adapter.limits(),
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        ))
+        .map_err(|e| BizClawError::Brain(format!("can't open GPU {}: {e}", info.name)))?;
+
+        let pipeline = |label: &str, source: &str| {
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: None,
+                module: &module,
+                entry_point: Some("main"),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let matmul = pipeline("matmul", include_str!("matmul.wgsl"));
+        let attention = pipeline("attention", include_str!("attention.wgsl"));
+
+        Ok(Self {
+            name: format!("{} ({:?})", info.name, info.backend),
+            device,
+            queue,
+            matmul,
+            attention,
+            weights: Mutex::new(HashMap::new()),
+            caches: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Largest buffer a shader can bind.
+    fn max_binding(&self) -> u64 {
+        self.device.limits().max_storage_buffer_binding_size as u64
+    }
+
+    fn storage(&self, label: &str, bytes: u64) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: bytes,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn storage_init(&self, label: &str, values: &[f32]) -> wgpu::Buffer {
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents: &to_bytes(values),
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+    }
+
+    /// A uniform of 32-bit words, floats passed as their bits.
+    fn uniform(&self, words: &[u32]) -> wgpu::Buffer {
+        let contents: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("params"),
+                contents: &contents,
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+    }
+
+    fn bind_group(
+        &self,
+        pipeline: &wgpu::ComputePipeline,
+        buffers: &[&wgpu::Buffer],
+    ) -> wgpu::BindGroup {
+        let entries: Vec<_> = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        })
+    }
+
+    /// The matrix whose bytes are `data` on the device, uploaded on first
+    /// use.
+    fn weights(&self, data: &[u8]) -> Weights {
+        let key = (data.as_ptr() as usize, data.len());
+        lock(&self.weights)
+            .entry(key)
+            .or_insert_with(|| {
+                // Storage buffers hold whole words
+                let size = data.len().next_multiple_of(4) as u64;
+                if size > self.max_binding() {
+                    return None;
+                }
+                let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("weights"),
+                    size,
+                    usage: wgpu::BufferUsages::STORAGE,
+                    mapped_at_creation: true,
+                });
+                buffer.slice(..).get_mapped_range_mut()[..data.len()].copy_from_slice(data);
+                buffer.unmap();
+                Some(Arc::new(buffer))
+            })
+            .clone()
+    }
+
+    /// Submit `encoder`, wait for it, and read `buffers` back as f32.
+    fn finish(
+        &self,
+        mut encoder: wgpu::CommandEncoder,
+        buffers: &[wgpu::Buffer],
+    ) -> Result<Vec<Vec<f32>>> {
+        let staging: Vec<wgpu::Buffer> = buffers
+            .iter()
+            .map(|buffer| {
+                let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("readback"),
+                    size: buffer.size(),
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+                staging
+            })
+            .collect();
+        self.queue.submit(Some(encoder.finish()));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        for buffer in &staging {
+            let tx = tx.clone();
+            buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |mapped| {
+                    let _ = tx.send(mapped);
+                });
+        }
+        let _ = self.device.poll(wgpu::Maintain::Wait);
+        for _ in &staging {
+            rx.recv()
+                .map_err(|_| BizClawError::Inference("GPU lost".into()))?
+                .map_err(|e| BizClawError::Inference(format!("GPU readback failed: {e}")))?;
+        }
+
+        Ok(staging
+            .iter()
+            .map(|buffer| {
+                let values = buffer
+                    .slice(..)
+                    .get_mapped_range()
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                buffer.unmap();
+                values
+            })
+            .collect())
+    }
+}
+
+impl super::Device for Device {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn matmul(&self, jobs: &mut [MatmulJob]) -> Result<bool> {
+        let mut uploaded = Vec::with_capacity(jobs.len());
+        for job in jobs.iter() {
+            let (rows, cols, ggml_type) = (job.output.len(), job.input.len(), job.ggml_type);
+            let Some(kind) = weight_kind(ggml_type) else {
+                return Ok(false);
+            };
+            let block = ggml_type.block_size();
+            // Odd shapes and short matrices are the CPU path's to report
+            if !cols.is_multiple_of(block)
+                || job.data.len() < rows * (cols / block * ggml_type.type_size())
+            {
+                return Ok(false);
+            }
+            let Some(weights) = self.weights(job.data) else {
+                return Ok(false);
+            };
+            uploaded.push((kind, weights));
+        }
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("matmul"),
+            });
+        let mut outputs = Vec::with_capacity(jobs.len());
+        for (job, (kind, weights)) in jobs.iter().zip(&uploaded) {
+            let rows = job.output.len() as u32;
+            let params = self.uniform(&[rows, job.input.len() as u32, *kind, 0]);
+            let input = self.storage_init("input", job.input);
+            let output = self.storage("output", rows as u64 * 4);
+            let bind_group = self.bind_group(&self.matmul, &[&params, weights, &input, &output]);
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("matmul"),
+                    timestamp_writes: None,
+                });
+                pass.set_pipeline(&self.matmul);
+                pass.set_bind_group(0, &bind_group, &[]);
+                // A workgroup per row, rows past the first dimension's
+                // limit wrapping into the second
+                pass.dispatch_workgroups(rows.min(MAX_GROUPS), rows.div_ceil(MAX_GROUPS), 1);
+            }
+            outputs.push(output);
+        }
+
+        for (job, values) in jobs.iter_mut().zip(self.finish(encoder, &outputs)?) {
+            job.output.copy_from_slice(&values);
+        }
+        Ok(true)
+    }
+
+    fn attend(
+        &self,
+        kv_cache: &KvCache,
+        layer: usize,
+        pos: usize,
+        key: &[f32],
+        value: &[f32],
+        q: &[f32],
+        output: &mut [f32],
+        n_heads: usize,
+        n_kv_heads: usize,
+        softcap: f32,
+    ) -> Result<bool> {
+        let capacity = kv_cache.capacity();
+        let kv_dim = key.len();
+        let head_dim = q.len() / n_heads;
+        let layer_bytes = (capacity * kv_dim * 4) as u64;
+        let score_bytes = (n_heads * capacity * 4) as u64;
+        if layer_bytes.max(score_bytes) > self.max_binding() {
+            return Ok(false);
+        }
+        let seq_len = (pos + 1).min(capacity);
+
+        let mut caches = lock(&self.caches);
+        let layers = caches.entry(kv_cache.id()).or_default();
+        if layers.len() <= layer {
+            layers.resize_with(layer + 1, || None);
+        }
+        let copy = layers[layer].get_or_insert_with(|| LayerKv {
+            keys: self.storage("keys", layer_bytes),
+            values: self.storage("values", layer_bytes),
+            // No cache has epoch 0: copy everything on first use
+            epoch: 0,
+        });
+        if copy.epoch == kv_cache.epoch() {
+            let offset = (pos % capacity * kv_dim * 4) as u64;
+            self.queue.write_buffer(&copy.keys, offset, &to_bytes(key));
+            self.queue
+                .write_buffer(&copy.values, offset, &to_bytes(value));
+        } else {
+            let mut rows = Vec::new();
+            self.queue.write_buffer(
+                &copy.keys,
+                0,
+                &to_bytes(kv_cache.keys(layer, seq_len, &mut rows)),
+            );
+            self.queue.write_buffer(
+                &copy.values,
+                0,
+                &to_bytes(kv_cache.values(layer, seq_len, &mut rows)),
+            );
+            copy.epoch = kv_cache.epoch();
+        }
+
+        let params = self.uniform(&[
+            n_heads as u32,
+            n_kv_heads as u32,
+            head_dim as u32,
+            seq_len as u32,
+            capacity as u32,
+            (1.0 / (head_dim as f32).sqrt()).to_bits(),
+            softcap.to_bits(),
+            0,
+        ]);
+        let q = self.storage_init("q", q);
+        let scores = self.storage("scores", score_bytes);
+        let out = self.storage("attention", (output.len() * 4) as u64);
+        let bind_group = self.bind_group(
+            &self.attention,
+            &[&params, &q, &copy.keys, &copy.values, &scores, &out],
+        );
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("attention"),
+            });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("attention"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.attention);
+            pass.set_bind_group(0, &bind_group, &[]);
+            // A workgroup per query head
+            pass.dispatch_workgroups(n_heads as u32, 1, 1);
+        }
+        drop(caches);
+
+        let values = self.finish(encoder, &[out])?;
+        output.copy_from_slice(&values[0]);
+        Ok(true)
+    }
+
+    fn unmap_model(&self, data: &[u8]) {
+        let range = data.as_ptr_range();
+        let range = range.start as usize..range.end as usize;
+        lock(&self.weights).retain(|&(start, _), _| !range.contains(&start));
+    }
+
+    fn forget_cache(&self, id: u64) {
+        lock(&self.caches).remove(&id);
+    }
+}
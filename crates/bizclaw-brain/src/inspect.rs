@@ -0,0 +1,180 @@
+//! GGUF inspection — what a model file holds and what it takes to run,
+//! without loading its weights. Backs `bizclaw inspect`.
+
+use crate::gguf::{GgufFile, GgufValue};
+use crate::model::ModelParams;
+use bizclaw_core::error::{BizClawError, Result};
+use std::path::Path;
+
+/// Parse only the header (metadata and tensor index) of a GGUF file.
+pub fn read_header(path: &Path) -> Result<GgufFile> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| BizClawError::ModelLoad(format!("{}: {e}", path.display())))?;
+    GgufFile::parse(&mut std::io::BufReader::new(file))
+}
+
+/// The tokenizer a model ships with.
+#[derive(Debug, Clone)]
+pub struct TokenizerInfo {
+    /// `llama` (SentencePiece) or `gpt2` (byte-level BPE).
+    pub model: String,
+    pub vocab_size: usize,
+    pub merges: usize,
+    /// Role (`bos`, `eos`, …), token ID and its text.
+    pub special: Vec<(&'static str, u32, String)>,
+    pub has_chat_template: bool,
+}
+
+impl TokenizerInfo {
+    pub fn from_gguf(gguf: &GgufFile) -> Self {
+        let array_len = |key: &str| match gguf.metadata.get(key) {
+            Some(GgufValue::Array(items)) => items.len(),
+            _ => 0,
+        };
+        let token = |id: u32| match gguf.metadata.get("tokenizer.ggml.tokens") {
+            Some(GgufValue::Array(tokens)) => tokens
+                .get(id as usize)
+                .and_then(|t| t.as_str())
+                .unwrap_or("?")
+                .to_string(),
+            _ => "?".to_string(),
+        };
+        let special = [
+            ("bos", "tokenizer.ggml.bos_token_id"),
+            ("eos", "tokenizer.ggml.eos_token_id"),
+            ("pad", "tokenizer.ggml.padding_token_id"),
+            ("unk", "tokenizer.ggml.unknown_token_id"),
+        ]
+        .into_iter()
+        .filter_map(|(role, key)| {
+            let id = gguf.get_u32(key)?;
+            Some((role, id, token(id)))
+        })
+        .collect();
+
+        Self {
+            model: gguf
+                .metadata
+                .get("tokenizer.ggml.model")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown")
+                .to_string(),
+            vocab_size: array_len("tokenizer.ggml.tokens"),
+            merges: array_len("tokenizer.ggml.merges"),
+            special,
+            has_chat_template: gguf.metadata.contains_key("tokenizer.chat_template"),
+        }
+    }
+}
+
+/// Memory the engine needs to run a model at one context length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    pub context: u64,
+    /// Mapped from the file; the OS pages it in as layers run.
+    pub weights: u64,
+    /// f32 keys and values for every layer and position, or only the
+    /// window's positions for sliding-window models.
+    pub kv_cache: u64,
+    /// The largest weight matrix dequantized to f32 during a matmul.
+    pub scratch: u64,
+}
+
+impl MemoryEstimate {
+    pub fn total(&self) -> u64 {
+        self.weights + self.kv_cache + self.scratch
+    }
+}
+
+pub fn estimate_memory(gguf: &GgufFile, context: u64) -> MemoryEstimate {
+    let params = ModelParams::from_gguf(gguf);
+    let kv_dim = params.kv_dim() as u64;
+    MemoryEstimate {
+        context,
+        weights: gguf.tensors.iter().map(|t| t.size_bytes()).sum(),
+        kv_cache: 2 * params.n_layers as u64 * context.min(params.kv_len() as u64) * kv_dim * 4,
+        scratch: gguf
+            .tensors
+            .iter()
+            .filter(|t| t.dims.len() >= 2)
+            .map(|t| t.n_elements() * 4)
+            .max()
+            .unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gguf::{GgmlType, TensorInfo};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_estimate_memory_and_tokenizer() {
+        let metadata: HashMap<String, GgufValue> = HashMap::from([
+            (
+                "general.architecture".into(),
+                GgufValue::String("llama".into()),
+            ),
+            ("llama.embedding_length".into(), GgufValue::U32(64)),
+            ("llama.attention.head_count".into(), GgufValue::U32(8)),
+            ("llama.attention.head_count_kv".into(), GgufValue::U32(2)),
+            ("llama.block_count".into(), GgufValue::U32(4)),
+            (
+                "tokenizer.ggml.model".into(),
+                GgufValue::String("llama".into()),
+            ),
+            (
+                "tokenizer.ggml.tokens".into(),
+                GgufValue::Array(
+                    ["<unk>", "<s>", "</s>", "a"]
+                        .map(|t| GgufValue::String(t.into()))
+                        .to_vec(),
+                ),
+            ),
+            ("tokenizer.ggml.bos_token_id".into(), GgufValue::U32(1)),
+            ("tokenizer.ggml.eos_token_id".into(), GgufValue::U32(2)),
+        ]);
+        let mut gguf = GgufFile {
+            version: 3,
+            metadata,
+            tensors: vec![TensorInfo {
+                name: "output.weight".into(),
+                n_dims: 2,
+                dims: vec![64, 4],
+                ggml_type: GgmlType::Q8_0,
+                offset: 0,
+            }],
+            data_offset: 0,
+            alignment: 32,
+            skipped_tensors: Vec::new(),
+        };
+
+        let estimate = estimate_memory(&gguf, 1024);
+        assert_eq!(estimate.weights, 8 * 34);
+        // 2 (K and V) × 4 layers × 1024 positions × 2 heads × 8 dims × 4 bytes
+        assert_eq!(estimate.kv_cache, 2 * 4 * 1024 * 16 * 4);
+        assert_eq!(estimate.scratch, 256 * 4);
+
+        let tokenizer = TokenizerInfo::from_gguf(&gguf);
+        assert_eq!(tokenizer.vocab_size, 4);
+        assert_eq!(
+            tokenizer.special,
+            [
+                ("bos", 1, "<s>".to_string()),
+                ("eos", 2, "</s>".to_string())
+            ]
+        );
+        assert!(!tokenizer.has_chat_template);
+        assert_eq!(
+            gguf.metadata["tokenizer.ggml.tokens"].to_string(),
+            r#"["<unk>", "<s>", "</s>", "a"]"#
+        );
+
+        // A sliding window caps the cache however long the context.
+        gguf.metadata
+            .insert("llama.attention.sliding_window".into(), GgufValue::U32(256));
+        assert_eq!(estimate_memory(&gguf, 1024).kv_cache, 2 * 4 * 256 * 16 * 4);
+        assert_eq!(estimate_memory(&gguf, 128).kv_cache, 2 * 4 * 128 * 16 * 4);
+    }
+}
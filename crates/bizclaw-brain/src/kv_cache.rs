@@ -1,56 +1,427 @@
-//! KV Cache — both f32 (compatible) and FP16 (memory-optimised) variants.
+//! KV Cache — f32 or Q8 (see [`KvCacheType`]), and an FP16 variant.
 //!
-//! FP16 variant halves memory (88MB → 44MB for typical models).
+//! FP16 variant halves memory (88MB → 44MB for typical models); Q8 cuts
+//! it ~3.8x, quantizing rows as they are stored and dequantizing them as
+//! attention reads them.
+//! The cache doubles as a ring buffer for sliding-window attention:
+//! sized to the window, position `pos` lives in slot `pos % capacity`.
+//! Slots are held in pages of [`KV_PAGE`] positions allocated as they are
+//! first written, so memory follows the tokens in the cache rather than
+//! its capacity, and idle slots of a multi-slot engine stay small.
 //! Includes KV Cache Persistence (save/load .bckv files)
 //! and Pre-computed RoPE tables for fast positional encoding.
 
+use serde::{Deserialize, Serialize};
 use std::io::{Read, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-// ── f32 KV Cache (backward compatible) ──────────────────────
+// ── KV Cache (f32 or Q8) ──────────────────────────────────
 
-/// Standard f32 KV Cache for transformer inference.
+/// How [`KvCache`] stores keys and values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KvCacheType {
+    /// Exact, 4 bytes a value.
+    #[default]
+    F32,
+    /// `Q8_0` blocks of 32 values sharing an f16 scale: 34 bytes per 32
+    /// values, ~3.8x smaller, at a small loss of precision.
+    Q8,
+}
+
+impl KvCacheType {
+    /// `"f32"` or `"q8"`, as in the `[brain] kv_cache` setting.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "f32" => Some(Self::F32),
+            "q8" | "q8_0" => Some(Self::Q8),
+            _ => None,
+        }
+    }
+}
+
+const Q8_BLOCK: usize = 32;
+const Q8_BLOCK_BYTES: usize = 34;
+
+/// Positions per page of a [`KvCache`], one attention tile. Pages are
+/// allocated when the first of their positions is stored, so a cache takes
+/// memory for the tokens it holds rather than for its whole capacity.
+pub const KV_PAGE: usize = crate::attention::ATTENTION_TILE;
+
+/// [`KV_PAGE`] rows of `kv_dim` values, or fewer in a layer's last page.
+enum Page {
+    F32(Box<[f32]>),
+    /// `kv_dim / Q8_BLOCK` blocks a row.
+    Q8(Box<[u8]>),
+}
+
+/// Rows of `kv_dim` values, one per layer and slot, in pages.
+struct Rows {
+    kv_type: KvCacheType,
+    kv_dim: usize,
+    /// Slots per layer.
+    capacity: usize,
+    /// [n_layers x capacity / KV_PAGE], `None` until first stored to.
+    pages: Vec<Option<Page>>,
+}
+
+impl Rows {
+    fn new(kv_type: KvCacheType, n_layers: usize, capacity: usize, kv_dim: usize) -> Self {
+        let pages = n_layers * capacity.div_ceil(KV_PAGE);
+        Self {
+            kv_type,
+            kv_dim,
+            capacity,
+            pages: (0..pages).map(|_| None).collect(),
+        }
+    }
+
+    /// Bytes a row takes as stored.
+    fn row_bytes(&self) -> usize {
+        match self.kv_type {
+            KvCacheType::F32 => self.kv_dim * std::mem::size_of::<f32>(),
+            KvCacheType::Q8 => self.kv_dim / Q8_BLOCK * Q8_BLOCK_BYTES,
+        }
+    }
+
+    /// Index of the first page of `layer`.
+    fn first_page(&self, layer: usize) -> usize {
+        layer * self.capacity.div_ceil(KV_PAGE)
+    }
+
+    /// Rows of the `n`th page of a layer holding its first `rows` slots.
+    fn page_rows(n: usize, rows: usize) -> usize {
+        KV_PAGE.min(rows - n * KV_PAGE)
+    }
+
+    /// Page `index`, allocated if it isn't yet.
+    fn page_mut(&mut self, index: usize) -> &mut Page {
+        let per_layer = self.capacity.div_ceil(KV_PAGE);
+        let rows = Self::page_rows(index % per_layer, self.capacity);
+        let (kv_type, kv_dim) = (self.kv_type, self.kv_dim);
+        self.pages[index].get_or_insert_with(|| match kv_type {
+            // Interleaved across NUMA nodes when that is on
+            KvCacheType::F32 => Page::F32(crate::numa::zeroed(rows * kv_dim)),
+            KvCacheType::Q8 => Page::Q8(crate::numa::zeroed(
+                rows * kv_dim / Q8_BLOCK * Q8_BLOCK_BYTES,
+            )),
+        })
+    }
+
+    fn store(&mut self, layer: usize, slot: usize, data: &[f32]) {
+        let (index, row) = (self.first_page(layer) + slot / KV_PAGE, slot % KV_PAGE);
+        match self.page_mut(index) {
+            Page::F32(values) => {
+                values[row * data.len()..(row + 1) * data.len()].copy_from_slice(data)
+            }
+            Page::Q8(blocks) => {
+                let row_bytes = data.len() / Q8_BLOCK * Q8_BLOCK_BYTES;
+                let out = &mut blocks[row * row_bytes..(row + 1) * row_bytes];
+                for (values, block) in data
+                    .chunks_exact(Q8_BLOCK)
+                    .zip(out.chunks_exact_mut(Q8_BLOCK_BYTES))
+                {
+                    crate::quant::quantize_q8_0(values, block);
+                }
+            }
+        }
+    }
+
+    /// Append `rows` rows of page `index` from `start` to `out` as f32.
+    /// A page never stored to reads as zeros.
+    fn extend(&self, index: usize, start: usize, rows: usize, out: &mut Vec<f32>) {
+        let kv_dim = self.kv_dim;
+        match &self.pages[index] {
+            Some(Page::F32(values)) => {
+                out.extend_from_slice(&values[start * kv_dim..(start + rows) * kv_dim])
+            }
+            Some(Page::Q8(blocks)) => {
+                let row_bytes = self.row_bytes();
+                let from = out.len();
+                out.resize(from + rows * kv_dim, 0.0);
+                for (block, values) in blocks[start * row_bytes..(start + rows) * row_bytes]
+                    .chunks_exact(Q8_BLOCK_BYTES)
+                    .zip(out[from..].chunks_exact_mut(Q8_BLOCK))
+                {
+                    crate::quant::dequantize_q8_0(block, values);
+                }
+            }
+            None => out.resize(out.len() + rows * kv_dim, 0.0),
+        }
+    }
+
+    /// The first `rows` slots of `layer`, page by page: f32 pages in
+    /// place, the others dequantized into `scratch`.
+    fn pages<'a>(&'a self, layer: usize, rows: usize, scratch: &'a mut Vec<f32>) -> Vec<&'a [f32]> {
+        let first = self.first_page(layer);
+        let n_pages = rows.div_ceil(KV_PAGE);
+        scratch.clear();
+        for n in 0..n_pages {
+            if !matches!(self.pages[first + n], Some(Page::F32(_))) {
+                self.extend(first + n, 0, Self::page_rows(n, rows), scratch);
+            }
+        }
+        let mut rest: &'a [f32] = scratch;
+        (0..n_pages)
+            .map(|n| {
+                let len = Self::page_rows(n, rows) * self.kv_dim;
+                match &self.pages[first + n] {
+                    Some(Page::F32(values)) => &values[..len],
+                    _ => {
+                        let (page, tail) = rest.split_at(len);
+                        rest = tail;
+                        page
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// The first `rows` slots of `layer` copied into `out` as f32.
+    fn read(&self, layer: usize, rows: usize, out: &mut Vec<f32>) {
+        out.clear();
+        let first = self.first_page(layer);
+        for n in 0..rows.div_ceil(KV_PAGE) {
+            self.extend(first + n, 0, Self::page_rows(n, rows), out);
+        }
+    }
+
+    /// Row `slot` of `layer` copied into `out` as f32.
+    fn read_row(&self, layer: usize, slot: usize, out: &mut Vec<f32>) {
+        out.clear();
+        self.extend(
+            self.first_page(layer) + slot / KV_PAGE,
+            slot % KV_PAGE,
+            1,
+            out,
+        );
+    }
+
+    /// Free every page.
+    fn reset(&mut self) {
+        self.pages.iter_mut().for_each(|page| *page = None);
+    }
+
+    /// Bytes of the pages allocated so far.
+    fn bytes(&self) -> usize {
+        self.pages
+            .iter()
+            .map(|page| match page {
+                Some(Page::F32(values)) => values.len() * std::mem::size_of::<f32>(),
+                Some(Page::Q8(blocks)) => blocks.len(),
+                None => 0,
+            })
+            .sum()
+    }
+
+    /// Write the first `rows` slots of `layer` as stored: f32 or Q8 blocks.
+    fn write(&self, w: &mut impl Write, layer: usize, rows: usize) -> std::io::Result<()> {
+        let first = self.first_page(layer);
+        for n in 0..rows.div_ceil(KV_PAGE) {
+            let page_rows = Self::page_rows(n, rows);
+            match &self.pages[first + n] {
+                Some(Page::F32(values)) => {
+                    for v in &values[..page_rows * self.kv_dim] {
+                        w.write_all(&v.to_le_bytes())?;
+                    }
+                }
+                Some(Page::Q8(blocks)) => w.write_all(&blocks[..page_rows * self.row_bytes()])?,
+                // Zero bytes are zeros in either format.
+                None => w.write_all(&vec![0; page_rows * self.row_bytes()])?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Read rows written by [`write`](Self::write) into the first `rows`
+    /// slots of `layer`.
+    fn read_from(&mut self, r: &mut impl Read, layer: usize, rows: usize) -> std::io::Result<()> {
+        let first = self.first_page(layer);
+        let (kv_dim, row_bytes) = (self.kv_dim, self.row_bytes());
+        for n in 0..rows.div_ceil(KV_PAGE) {
+            let page_rows = Self::page_rows(n, rows);
+            match self.page_mut(first + n) {
+                Page::F32(values) => {
+                    let mut buf = [0u8; 4];
+                    for v in &mut values[..page_rows * kv_dim] {
+                        r.read_exact(&mut buf)?;
+                        *v = f32::from_le_bytes(buf);
+                    }
+                }
+                Page::Q8(blocks) => r.read_exact(&mut blocks[..page_rows * row_bytes])?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Source of [`KvCache::id`]s and epochs, unique across the process.
+static NEXT_EPOCH: AtomicU64 = AtomicU64::new(1);
+
+fn next_epoch() -> u64 {
+    NEXT_EPOCH.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Magic of [`KvCache::save`] files.
+const KV_MAGIC: &[u8; 4] = b"BCKC";
+const KV_VERSION: u32 = 1;
+
+/// KV Cache for transformer inference, f32 or 8-bit ([`KvCacheType`]).
+/// Q8 rows are quantized on store and dequantized on read.
 pub struct KvCache {
-    key_cache: Vec<f32>,
-    value_cache: Vec<f32>,
+    keys: Rows,
+    values: Rows,
+    kv_type: KvCacheType,
     n_layers: usize,
-    max_seq_len: usize,
+    /// Positions held per layer: the context length, or the attention
+    /// window for sliding-window models.
+    capacity: usize,
     kv_dim: usize,
     pos: usize,
+    id: u64,
+    epoch: u64,
 }
 
 impl KvCache {
-    pub fn new(n_layers: usize, max_seq_len: usize, n_kv_heads: usize, head_dim: usize) -> Self {
+    /// An f32 cache.
+    pub fn new(n_layers: usize, capacity: usize, n_kv_heads: usize, head_dim: usize) -> Self {
+        Self::with_type(n_layers, capacity, n_kv_heads, head_dim, KvCacheType::F32)
+    }
+
+    /// A cache storing `kv_type`. Q8 needs rows of whole 32-value blocks;
+    /// other widths fall back to f32.
+    pub fn with_type(
+        n_layers: usize,
+        capacity: usize,
+        n_kv_heads: usize,
+        head_dim: usize,
+        kv_type: KvCacheType,
+    ) -> Self {
         let kv_dim = n_kv_heads * head_dim;
-        let total = n_layers * max_seq_len * kv_dim;
+        let kv_type = if kv_type == KvCacheType::Q8 && !kv_dim.is_multiple_of(Q8_BLOCK) {
+            tracing::warn!(
+                "KV width {kv_dim} isn't a multiple of {Q8_BLOCK}; using an f32 KV cache"
+            );
+            KvCacheType::F32
+        } else {
+            kv_type
+        };
         Self {
-            key_cache: vec![0.0; total],
-            value_cache: vec![0.0; total],
+            keys: Rows::new(kv_type, n_layers, capacity, kv_dim),
+            values: Rows::new(kv_type, n_layers, capacity, kv_dim),
+            kv_type,
             n_layers,
-            max_seq_len,
+            capacity,
             kv_dim,
             pos: 0,
+            id: next_epoch(),
+            epoch: next_epoch(),
         }
     }
 
-    pub fn key_at_mut(&mut self, layer: usize, pos: usize) -> &mut [f32] {
-        let offset = (layer * self.max_seq_len + pos) * self.kv_dim;
-        &mut self.key_cache[offset..offset + self.kv_dim]
+    /// Tells this cache apart from every other in the process.
+    pub fn id(&self) -> u64 {
+        self.id
     }
 
-    pub fn value_at_mut(&mut self, layer: usize, pos: usize) -> &mut [f32] {
-        let offset = (layer * self.max_seq_len + pos) * self.kv_dim;
-        &mut self.value_cache[offset..offset + self.kv_dim]
+    /// Changes whenever rows are moved, dropped or loaded wholesale rather
+    /// than stored one at a time. A copy of the cache kept elsewhere, such
+    /// as on a GPU, by storing the same rows has to be copied afresh once
+    /// it does.
+    pub fn epoch(&self) -> u64 {
+        self.epoch
     }
 
-    pub fn keys(&self, layer: usize, seq_len: usize) -> &[f32] {
-        let offset = layer * self.max_seq_len * self.kv_dim;
-        &self.key_cache[offset..offset + seq_len * self.kv_dim]
+    pub fn kv_type(&self) -> KvCacheType {
+        self.kv_type
     }
 
-    pub fn values(&self, layer: usize, seq_len: usize) -> &[f32] {
-        let offset = layer * self.max_seq_len * self.kv_dim;
-        &self.value_cache[offset..offset + seq_len * self.kv_dim]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Slot of `pos`. Past the capacity positions wrap around, overwriting
+    /// the oldest, which a sliding window no longer attends to.
+    fn slot(&self, pos: usize) -> usize {
+        pos % self.capacity
+    }
+
+    /// Store the key vector [kv_dim] of `pos`.
+    pub fn store_key(&mut self, layer: usize, pos: usize, key: &[f32]) {
+        debug_assert_eq!(key.len(), self.kv_dim);
+        let slot = self.slot(pos);
+        self.keys.store(layer, slot, key);
+    }
+
+    /// Store the value vector [kv_dim] of `pos`.
+    pub fn store_value(&mut self, layer: usize, pos: usize, value: &[f32]) {
+        debug_assert_eq!(value.len(), self.kv_dim);
+        let slot = self.slot(pos);
+        self.values.store(layer, slot, value);
+    }
+
+    /// Keys of the last `seq_len` positions, or of the last `capacity`
+    /// once the cache has wrapped, in slot order rather than position
+    /// order, a page of up to [`KV_PAGE`] rows at a time. Attention doesn't
+    /// depend on the order: RoPE has already encoded each key's position.
+    /// f32 pages are read in place; Q8 ones are dequantized into `scratch`.
+    pub fn key_pages<'a>(
+        &'a self,
+        layer: usize,
+        seq_len: usize,
+        scratch: &'a mut Vec<f32>,
+    ) -> Vec<&'a [f32]> {
+        self.keys.pages(layer, seq_len.min(self.capacity), scratch)
+    }
+
+    /// Values matching [`key_pages`](Self::key_pages).
+    pub fn value_pages<'a>(
+        &'a self,
+        layer: usize,
+        seq_len: usize,
+        scratch: &'a mut Vec<f32>,
+    ) -> Vec<&'a [f32]> {
+        self.values
+            .pages(layer, seq_len.min(self.capacity), scratch)
+    }
+
+    /// The rows of [`key_pages`](Self::key_pages) copied into `out`, one
+    /// after another.
+    pub fn keys<'a>(&self, layer: usize, seq_len: usize, out: &'a mut Vec<f32>) -> &'a [f32] {
+        self.keys.read(layer, seq_len.min(self.capacity), out);
+        out
+    }
+
+    /// The rows of [`value_pages`](Self::value_pages) copied into `out`.
+    pub fn values<'a>(&self, layer: usize, seq_len: usize, out: &'a mut Vec<f32>) -> &'a [f32] {
+        self.values.read(layer, seq_len.min(self.capacity), out);
+        out
+    }
+
+    /// Drop positions `keep..keep + discard` of the first `len`, moving the
+    /// ones after them down to close the gap. `rekey` re-encodes each moved
+    /// key for its new position.
+    pub fn shift(
+        &mut self,
+        len: usize,
+        keep: usize,
+        discard: usize,
+        mut rekey: impl FnMut(&mut [f32]),
+    ) {
+        debug_assert!(len <= self.capacity && keep + discard <= len);
+        self.epoch = next_epoch();
+        let mut row = Vec::new();
+        for layer in 0..self.n_layers {
+            for pos in keep + discard..len {
+                let (from, to) = (self.slot(pos), self.slot(pos - discard));
+                self.keys.read_row(layer, from, &mut row);
+                rekey(&mut row);
+                self.keys.store(layer, to, &row);
+                self.values.read_row(layer, from, &mut row);
+                self.values.store(layer, to, &row);
+            }
+        }
     }
 
     pub fn advance(&mut self) {
@@ -60,14 +431,111 @@ impl KvCache {
         self.pos
     }
 
+    /// Forget every position and free the pages holding them.
     pub fn reset(&mut self) {
-        self.key_cache.fill(0.0);
-        self.value_cache.fill(0.0);
+        self.keys.reset();
+        self.values.reset();
         self.pos = 0;
+        self.epoch = next_epoch();
     }
 
+    /// Bytes of the pages allocated so far.
     pub fn memory_usage(&self) -> usize {
-        (self.key_cache.len() + self.value_cache.len()) * std::mem::size_of::<f32>()
+        self.keys.bytes() + self.values.bytes()
+    }
+
+    /// Bytes the cache takes once every position has been stored.
+    pub fn max_memory_usage(&self) -> usize {
+        2 * self.n_layers * self.capacity * self.keys.row_bytes()
+    }
+
+    /// Save the first `len` positions (at most the capacity) to `path`, as
+    /// stored, so a long prompt needn't be evaluated again.
+    ///
+    /// Layout (little-endian): `BCKC`, version `u32`, type `u8` (0 f32,
+    /// 1 Q8), layers, capacity, KV width and positions as `u32`, then per
+    /// layer the key rows and the value rows.
+    pub fn save(&self, path: &Path, len: usize) -> std::io::Result<()> {
+        let rows = len.min(self.capacity);
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        out.write_all(KV_MAGIC)?;
+        out.write_all(&KV_VERSION.to_le_bytes())?;
+        out.write_all(&[self.kv_type as u8])?;
+        for n in [self.n_layers, self.capacity, self.kv_dim, rows] {
+            out.write_all(&(n as u32).to_le_bytes())?;
+        }
+        for layer in 0..self.n_layers {
+            self.keys.write(&mut out, layer, rows)?;
+            self.values.write(&mut out, layer, rows)?;
+        }
+        out.into_inner()?.sync_all()
+    }
+
+    /// Load a cache [`save`](Self::save)d from one of the same shape and
+    /// type. Returns the positions it holds; past them the cache is
+    /// unchanged.
+    pub fn load(&mut self, path: &Path) -> std::io::Result<usize> {
+        let invalid = |what: String| std::io::Error::new(std::io::ErrorKind::InvalidData, what);
+        let mut file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != KV_MAGIC {
+            return Err(invalid(format!(
+                "{} is not a BizClaw KV cache file",
+                path.display()
+            )));
+        }
+        let mut buf4 = [0u8; 4];
+        let mut read_u32 = |file: &mut std::io::BufReader<std::fs::File>| {
+            file.read_exact(&mut buf4)
+                .map(|_| u32::from_le_bytes(buf4) as usize)
+        };
+        let version = read_u32(&mut file)?;
+        if version != KV_VERSION as usize {
+            return Err(invalid(format!(
+                "KV cache version {version}; this build reads {KV_VERSION}"
+            )));
+        }
+        let mut kv_type = [0u8; 1];
+        file.read_exact(&mut kv_type)?;
+        let shape = [
+            kv_type[0] as usize,
+            read_u32(&mut file)?,
+            read_u32(&mut file)?,
+            read_u32(&mut file)?,
+        ];
+        if shape
+            != [
+                self.kv_type as usize,
+                self.n_layers,
+                self.capacity,
+                self.kv_dim,
+            ]
+        {
+            return Err(invalid(format!(
+                "{} holds a different model's or type's KV cache",
+                path.display()
+            )));
+        }
+        let rows = read_u32(&mut file)?;
+        if rows > self.capacity {
+            return Err(invalid(format!(
+                "{rows} positions in a cache of {}",
+                self.capacity
+            )));
+        }
+        self.epoch = next_epoch();
+        for layer in 0..self.n_layers {
+            self.keys.read_from(&mut file, layer, rows)?;
+            self.values.read_from(&mut file, layer, rows)?;
+        }
+        Ok(rows)
+    }
+}
+
+impl Drop for KvCache {
+    fn drop(&mut self) {
+        crate::gpu::forget_cache(self.id);
     }
 }
 
@@ -351,9 +819,141 @@ impl RopeTable {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_kv_cache_sliding_window() {
+        // Two layers, a window of 3 positions, one KV head of 2.
+        let mut cache = KvCache::new(2, 3, 1, 2);
+        for pos in 0..5 {
+            cache.store_key(1, pos, &[pos as f32; 2]);
+            cache.store_value(1, pos, &[-(pos as f32); 2]);
+        }
+        let mut scratch = Vec::new();
+        // Positions 3 and 4 replaced 0 and 1; 2 is still there.
+        assert_eq!(
+            cache.keys(1, 5, &mut scratch),
+            &[3.0, 3.0, 4.0, 4.0, 2.0, 2.0]
+        );
+        assert_eq!(
+            cache.values(1, 5, &mut scratch),
+            &[-3.0, -3.0, -4.0, -4.0, -2.0, -2.0]
+        );
+        assert_eq!(cache.keys(1, 2, &mut scratch).len(), 4);
+        // Layer 0 untouched.
+        assert!(cache.keys(0, 5, &mut scratch).iter().all(|&k| k == 0.0));
+    }
+
+    #[test]
+    fn test_kv_cache_shift() {
+        let mut cache = KvCache::new(2, 6, 1, 2);
+        for layer in 0..2 {
+            for pos in 0..6 {
+                cache.store_key(layer, pos, &[pos as f32; 2]);
+                cache.store_value(layer, pos, &[10.0 + pos as f32; 2]);
+            }
+        }
+        // Stores leave the epoch be; moving rows changes it.
+        let (id, epoch) = (cache.id(), cache.epoch());
+        assert_ne!(id, KvCache::new(2, 6, 1, 2).id());
+        // Keep position 0, drop 1 and 2; 3..6 move to 1..4, keys marked.
+        cache.shift(6, 1, 2, |key| key[1] = -1.0);
+        assert_ne!(cache.epoch(), epoch);
+        assert_eq!(cache.id(), id);
+        let mut scratch = Vec::new();
+        for layer in 0..2 {
+            assert_eq!(
+                &cache.keys(layer, 4, &mut scratch)[..8],
+                &[0.0, 0.0, 3.0, -1.0, 4.0, -1.0, 5.0, -1.0]
+            );
+            assert_eq!(
+                &cache.values(layer, 4, &mut scratch)[..8],
+                &[10.0, 10.0, 13.0, 13.0, 14.0, 14.0, 15.0, 15.0]
+            );
+        }
+    }
+
+    #[test]
+    fn test_kv_cache_pages() {
+        let (n_layers, capacity, kv_dim) = (2, 3 * KV_PAGE - 5, 4);
+        let mut cache = KvCache::new(n_layers, capacity, 1, kv_dim);
+        assert_eq!(cache.memory_usage(), 0);
+        let page_bytes = KV_PAGE * kv_dim * 4;
+
+        // Memory grows a page at a time, per layer, keys and values.
+        for pos in 0..KV_PAGE + 1 {
+            cache.store_key(0, pos, &[pos as f32; 4]);
+            cache.store_value(0, pos, &[-(pos as f32); 4]);
+        }
+        assert_eq!(cache.memory_usage(), 2 * 2 * page_bytes);
+        cache.store_key(1, 0, &[1.0; 4]);
+        assert_eq!(cache.memory_usage(), 5 * page_bytes);
+
+        // Read in place, page by page, or copied into one run.
+        let mut scratch = Vec::new();
+        let pages = cache.key_pages(0, KV_PAGE + 1, &mut scratch);
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].len(), KV_PAGE * kv_dim);
+        assert_eq!(pages[1], &[KV_PAGE as f32; 4]);
+        let mut rows = Vec::new();
+        let keys = cache.keys(0, KV_PAGE + 1, &mut rows);
+        assert_eq!(keys.len(), (KV_PAGE + 1) * kv_dim);
+        assert_eq!(keys[(KV_PAGE - 1) * kv_dim], (KV_PAGE - 1) as f32);
+        // The last page is cut to the capacity.
+        for pos in 0..capacity {
+            cache.store_key(1, pos, &[0.0; 4]);
+        }
+        assert_eq!(cache.keys.bytes(), (2 * page_bytes) + capacity * kv_dim * 4);
+        // Unallocated: layer 0's last page of each, layer 1's values.
+        let last_page = capacity - 2 * KV_PAGE;
+        assert_eq!(
+            cache.memory_usage() + (2 * last_page + capacity) * kv_dim * 4,
+            cache.max_memory_usage()
+        );
+
+        cache.reset();
+        assert_eq!(cache.memory_usage(), 0);
+        assert!(cache.keys(0, KV_PAGE, &mut rows).iter().all(|&k| k == 0.0));
+    }
+
+    #[test]
+    fn test_q8_kv_cache() {
+        let (n_layers, capacity, n_kv_heads, head_dim) = (2, 8, 2, 64);
+        let mut cache =
+            KvCache::with_type(n_layers, capacity, n_kv_heads, head_dim, KvCacheType::Q8);
+        assert_eq!(cache.kv_type(), KvCacheType::Q8);
+        let f32_size = KvCache::new(n_layers, capacity, n_kv_heads, head_dim).max_memory_usage();
+        assert_eq!(cache.max_memory_usage() * 64, f32_size * 17);
+
+        let row = |pos: usize| -> Vec<f32> {
+            (0..128)
+                .map(|i| ((i * 37 + pos * 11) % 101) as f32 / 25.0 - 2.0)
+                .collect()
+        };
+        for pos in 0..3 {
+            cache.store_key(1, pos, &row(pos));
+            cache.store_value(1, pos, &row(pos + 5));
+        }
+        let mut scratch = Vec::new();
+        let keys = cache.keys(1, 3, &mut scratch).to_vec();
+        let values = cache.values(1, 3, &mut scratch);
+        for pos in 0..3 {
+            let (key, value) = (row(pos), row(pos + 5));
+            for i in 0..128 {
+                // Within half a quantization step of amax / 127.
+                assert!((keys[pos * 128 + i] - key[i]).abs() <= 2.0 / 127.0);
+                assert!((values[pos * 128 + i] - value[i]).abs() <= 2.0 / 127.0);
+            }
+        }
+
+        assert_eq!(KvCacheType::from_name("Q8"), Some(KvCacheType::Q8));
+        assert_eq!(KvCacheType::from_name("f16"), None);
+        // Rows that aren't whole blocks stay f32.
+        let odd = KvCache::with_type(1, 4, 1, 20, KvCacheType::Q8);
+        assert_eq!(odd.kv_type(), KvCacheType::F32);
+    }
+
     #[test]
     fn test_fp16_roundtrip() {
-        let values = [0.0f32, 1.0, -1.0, 0.5, 3.14, -0.001, 65504.0];
+        let values = [0.0f32, 1.0, -1.0, 0.5, 3.25, -0.001, 65504.0];
         for &v in &values {
             let fp16 = fp32_to_fp16(v);
             let back = fp16_to_fp32(fp16);
@@ -393,6 +993,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_kv_cache_file_round_trip() {
+        for kv_type in [KvCacheType::F32, KvCacheType::Q8] {
+            let mut cache = KvCache::with_type(2, 8, 1, 32, kv_type);
+            for pos in 0..3 {
+                let row: Vec<f32> = (0..32).map(|i| (i + pos) as f32 / 10.0).collect();
+                cache.store_key(1, pos, &row);
+                cache.store_value(0, pos, &row);
+            }
+            let path = std::env::temp_dir().join(format!(
+                "bizclaw-kv-{}-{kv_type:?}.bckc",
+                std::process::id()
+            ));
+            cache.save(&path, 3).unwrap();
+
+            let mut loaded = KvCache::with_type(2, 8, 1, 32, kv_type);
+            assert_eq!(loaded.load(&path).unwrap(), 3);
+            let (mut a, mut b) = (Vec::new(), Vec::new());
+            for layer in 0..2 {
+                assert_eq!(cache.keys(layer, 3, &mut a), loaded.keys(layer, 3, &mut b));
+                assert_eq!(
+                    cache.values(layer, 3, &mut a),
+                    loaded.values(layer, 3, &mut b)
+                );
+            }
+
+            // A cache of another shape or type refuses it.
+            let mut other = KvCache::with_type(2, 16, 1, 32, kv_type);
+            assert!(other.load(&path).is_err());
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+
     #[test]
     fn test_kv_cache_save_load() {
         let mut cache = Fp16KvCache::new(2, 8, 2, 4);
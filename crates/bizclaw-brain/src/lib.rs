@@ -2,6 +2,9 @@
 //!
 //! Local LLM inference engine — PicoLM rewrite in pure Rust.
 //! Runs LLaMA-architecture models in GGUF format with mmap, SIMD, and quantization.
+//!
+//! Builds for `wasm32-unknown-unknown` with `--no-default-features`, which
+//! drops mmap and the rayon pool; `bizclaw-wasm` wraps it for the browser.
 
 // SIMD/math code: intentional loop indexing, unused struct fields for future use
 #![allow(
@@ -11,25 +14,39 @@
 )]
 
 pub mod attention;
+pub mod bench;
+pub mod eval;
 pub mod forward;
+pub mod generation;
 pub mod gguf;
+pub mod gpu;
 pub mod grammar;
+pub mod inspect;
 pub mod kv_cache;
 pub mod llamacpp;
 pub mod mmap;
 pub mod model;
+pub mod numa;
 pub mod quant;
+pub mod quantize;
 pub mod rope;
 pub mod sampler;
 pub mod simd;
+pub mod snapshot;
+pub mod system;
 pub mod tensor;
 pub mod thread_pool;
 pub mod tokenizer;
+pub mod tuning;
 
 use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::metrics;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+pub use generation::{FinishReason, GenerationParams, GenerationResult, Timings};
+
 /// Brain engine configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BrainConfig {
@@ -39,6 +56,56 @@ pub struct BrainConfig {
     pub temperature: f32,
     pub top_p: f32,
     pub json_mode: bool,
+    /// Load models with tensors of unknown types, leaving those tensors
+    /// out, see [`gguf::ParseOptions::skip_unknown_tensors`].
+    #[serde(default)]
+    pub skip_unknown_tensors: bool,
+    /// Where this CPU's matmul tuning is cached; loading a model tunes the
+    /// kernels on first use (see [`tuning`]). None keeps the defaults.
+    #[serde(default)]
+    pub tuning_file: Option<PathBuf>,
+    /// How the KV cache stores keys and values; Q8 takes ~3.8x less
+    /// memory at long contexts.
+    #[serde(default)]
+    pub kv_cache: kv_cache::KvCacheType,
+    /// When prompt and output fill the context, drop the oldest tokens
+    /// (after those [`GenerationParams::keep_tokens`] protects) and go on,
+    /// instead of ending with [`FinishReason::Length`].
+    #[serde(default = "default_context_shift")]
+    pub context_shift: bool,
+    /// Independent conversations the loaded model holds at once, each with
+    /// its own KV cache and prompt cache; see [`GenerationParams::slot`].
+    #[serde(default = "default_slots")]
+    pub slots: u32,
+    /// RoPE scaling to run the model with in place of its own, stretching
+    /// its context by the factor; None keeps what the model file declares.
+    #[serde(default)]
+    pub rope_scaling: Option<rope::RopeScaling>,
+    /// Run inference on one worker per physical core, pinned to it, up to
+    /// `threads`; see [`thread_pool::pin_workers`]. Linux only.
+    #[serde(default)]
+    pub pin_threads: bool,
+    /// With `pin_threads`, leave the last core without a worker for the
+    /// async runtime serving requests.
+    #[serde(default)]
+    pub reserve_runtime_core: bool,
+    /// Interleave weights and KV cache across NUMA nodes, and spread
+    /// pinned workers over them; see [`numa`]. Linux only.
+    #[serde(default)]
+    pub numa: bool,
+    /// Run matmuls and attention on the CPU or offload them to a GPU;
+    /// see [`gpu`]. wgpu needs the `gpu` feature, Metal the `metal`
+    /// feature.
+    #[serde(default)]
+    pub backend: gpu::Backend,
+}
+
+fn default_context_shift() -> bool {
+    true
+}
+
+fn default_slots() -> u32 {
+    1
 }
 
 impl Default for BrainConfig {
@@ -50,6 +117,16 @@ impl Default for BrainConfig {
             temperature: 0.7,
             top_p: 0.9,
             json_mode: false,
+            skip_unknown_tensors: false,
+            tuning_file: None,
+            kv_cache: kv_cache::KvCacheType::F32,
+            context_shift: true,
+            slots: 1,
+            rope_scaling: None,
+            pin_threads: false,
+            reserve_runtime_core: false,
+            numa: false,
+            backend: gpu::Backend::Cpu,
         }
     }
 }
@@ -59,6 +136,14 @@ pub struct BrainEngine {
     config: BrainConfig,
     /// Loaded model (mmap)
     model: Option<LoadedModel>,
+    /// Caller-defined session metadata, carried by [`snapshot`](Self::snapshot).
+    session: BTreeMap<String, String>,
+}
+
+/// One conversation's state, parked while another slot is active.
+struct Slot {
+    kv_cache: kv_cache::KvCache,
+    cached: Vec<u32>,
 }
 
 /// A loaded model ready for inference.
@@ -71,20 +156,265 @@ struct LoadedModel {
     weights: forward::TransformerWeights,
     /// BPE tokenizer
     tokenizer: tokenizer::BpeTokenizer,
-    /// KV cache for generation
+    /// KV cache for generation, of the active slot
     kv_cache: kv_cache::KvCache,
+    /// Prompt cache: the token at each position the KV cache holds, so a
+    /// prompt extending the last one only runs its new tokens.
+    cached: Vec<u32>,
+    /// Every slot's state but the active one's; `slots[active]` is an empty
+    /// stand-in while its state is in `kv_cache` and `cached`.
+    slots: Vec<Slot>,
+    active: usize,
     /// Sampler
     sampler: sampler::Sampler,
+    /// JSON structure of every vocabulary token, for constrained decoding
+    grammar: grammar::JsonGrammar,
     /// Model file path
     path: PathBuf,
 }
 
+impl LoadedModel {
+    /// Generate up to `max_gen` tokens after `prompt` with the current
+    /// sampler settings and `params`' stop sequences, logit bias and JSON
+    /// mode.
+    fn decode(
+        &mut self,
+        prompt: &str,
+        params: &GenerationParams,
+        max_gen: usize,
+        context_shift: bool,
+        on_token: &mut dyn FnMut(&str) -> bool,
+    ) -> Result<GenerationResult> {
+        let vocab_size = self.params.vocab_size;
+        if let Some(id) = params.logit_bias.keys().find(|&&id| id >= vocab_size) {
+            return Err(BizClawError::Brain(format!(
+                "logit_bias: token {id} is outside the vocabulary ({vocab_size} tokens)"
+            )));
+        }
+        let json = params.json;
+        let mut clock = generation::Stopwatch::start();
+        let mut timings = Timings::default();
+
+        // Tokenize prompt
+        let mut input_tokens = vec![self.tokenizer.bos_id];
+        input_tokens.extend(self.tokenizer.encode(prompt));
+        timings.tokenize = clock.lap();
+
+        let total_len = input_tokens.len();
+        // Nests under the caller's agent and request spans.
+        let _span = tracing::info_span!("brain", prompt_tokens = total_len).entered();
+
+        // Positions whose tokens match the prompt cache are already in the
+        // KV cache. The last prompt token always runs, for its logits.
+        let mut reused = self
+            .cached
+            .iter()
+            .zip(&input_tokens)
+            .take_while(|(cached, token)| cached == token)
+            .count()
+            .min(total_len - 1);
+        // A sliding-window cache that has wrapped around has overwritten
+        // the positions just before any earlier one, so it can only go on
+        // from its end (or rerun the last position).
+        if self.cached.len() > self.kv_cache.capacity() && reused + 1 < self.cached.len() {
+            reused = 0;
+        }
+        self.cached.truncate(reused);
+        tracing::debug!(
+            "Generate: prompt_len={}, input_tokens={}, cached={}",
+            prompt.len(),
+            total_len,
+            reused
+        );
+
+        let mut output_tokens = Vec::new();
+        let mut text = generation::StopScanner::new(&params.stop);
+        let mut finish_reason = FinishReason::Length;
+        let mut logits = vec![0.0f32; self.params.vocab_size as usize];
+        self.grammar.reset();
+
+        // RoPE covers `max_seq_len` positions. Once they are full the
+        // context shifts to make room, or generation ends there.
+        let context = self.params.max_seq_len as usize;
+        let keep = 1 + params.keep_tokens as usize;
+        for step in reused..total_len + max_gen {
+            // Get the token to process
+            let token = if step < total_len {
+                input_tokens[step]
+            } else if let Some(&last) = output_tokens.last() {
+                last
+            } else {
+                break;
+            };
+            if self.cached.len() >= context && !(context_shift && self.shift_context(keep)) {
+                break;
+            }
+
+            // Run forward pass at the next free position
+            forward::forward(
+                &self.mmap_model,
+                &self.weights,
+                &self.params,
+                &mut self.kv_cache,
+                token,
+                self.cached.len(),
+                &mut logits,
+            )?;
+            self.cached.push(token);
+
+            // Only sample after processing all input tokens
+            if step >= total_len - 1 {
+                if step == total_len - 1 {
+                    timings.prefill = clock.lap();
+                }
+                if max_gen == 0 {
+                    break;
+                }
+                let all_tokens: Vec<u32> = input_tokens
+                    .iter()
+                    .chain(output_tokens.iter())
+                    .copied()
+                    .collect();
+                generation::apply_logit_bias(&mut logits, &params.logit_bias);
+                if json {
+                    self.grammar.apply_mask(&mut logits);
+                }
+                let next_token = self.sampler.sample(&mut logits, &all_tokens);
+
+                // Check for EOS
+                if next_token == self.tokenizer.eos_id {
+                    finish_reason = FinishReason::Eos;
+                    break;
+                }
+
+                output_tokens.push(next_token);
+                let (piece, stopped) = text.push(self.tokenizer.decode_token(next_token));
+                if !piece.is_empty() && !on_token(piece) {
+                    finish_reason = FinishReason::Cancelled;
+                    break;
+                }
+                if stopped {
+                    finish_reason = FinishReason::Stop;
+                    break;
+                }
+                if json {
+                    self.grammar.accept_token(next_token as usize);
+                    if self.grammar.is_complete() {
+                        finish_reason = FinishReason::Stop;
+                        break;
+                    }
+                }
+                // No forward pass for a token nothing will be sampled after.
+                if output_tokens.len() == max_gen {
+                    break;
+                }
+            }
+        }
+        // Held back for a stop sequence that never came.
+        if finish_reason != FinishReason::Cancelled {
+            let rest = text.flush();
+            if !rest.is_empty() {
+                on_token(rest);
+            }
+        }
+        timings.decode = clock.lap();
+
+        Ok(GenerationResult {
+            text: text.into_text(),
+            finish_reason,
+            prompt_tokens: total_len,
+            cached_tokens: reused,
+            generated_tokens: output_tokens.len(),
+            timings,
+        })
+    }
+
+    /// Make `slot`'s state the one `kv_cache` and `cached` hold. Switching
+    /// swaps buffers; nothing is copied.
+    fn select_slot(&mut self, slot: usize) -> Result<()> {
+        if slot >= self.slots.len() {
+            return Err(BizClawError::Brain(format!(
+                "No KV cache slot {slot}; the engine has {}",
+                self.slots.len()
+            )));
+        }
+        if slot != self.active {
+            // Park the active state, taking the stand-in...
+            let parked = &mut self.slots[self.active];
+            std::mem::swap(&mut self.kv_cache, &mut parked.kv_cache);
+            std::mem::swap(&mut self.cached, &mut parked.cached);
+            // ...and leave it in place of the state brought in.
+            let next = &mut self.slots[slot];
+            std::mem::swap(&mut self.kv_cache, &mut next.kv_cache);
+            std::mem::swap(&mut self.cached, &mut next.cached);
+            self.active = slot;
+        }
+        Ok(())
+    }
+
+    /// Make room in a full context: keep the first `keep` positions, drop
+    /// half of the rest and move the others down, re-rotating their keys
+    /// for their new positions. False if nothing can be dropped, or the
+    /// cache is a sliding window too small to hold the whole context.
+    fn shift_context(&mut self, keep: usize) -> bool {
+        let len = self.cached.len();
+        if len > self.kv_cache.capacity() {
+            return false;
+        }
+        let keep = keep.min(len);
+        let discard = (len - keep) / 2;
+        if discard == 0 {
+            return false;
+        }
+        let n_kv_heads = self.params.n_kv_heads as usize;
+        // ALiBi keys carry no position; the bias follows the new rows.
+        let rope = match (!self.params.uses_alibi())
+            .then(|| forward::rope(&self.mmap_model, &self.weights, &self.params))
+            .transpose()
+        {
+            Ok(rope) => rope,
+            Err(e) => {
+                tracing::warn!("Can't shift the context: {e}");
+                return false;
+            }
+        };
+        self.kv_cache.shift(len, keep, discard, |key| {
+            if let Some(rope) = &rope {
+                rope.shift_multi_head(key, -(discard as f32), n_kv_heads)
+            }
+        });
+        self.cached.drain(keep..keep + discard);
+        metrics::counter("bizclaw_brain_context_shifts_total", &[]).inc();
+        tracing::debug!("Context full: dropped {discard} tokens after the first {keep}");
+        true
+    }
+}
+
 impl BrainEngine {
     /// Create a new brain engine (model not yet loaded).
     pub fn new(config: BrainConfig) -> Self {
+        if config.numa {
+            match numa::enable() {
+                Ok(nodes) => tracing::info!("Interleaving memory across {nodes} NUMA nodes"),
+                Err(e) => tracing::warn!("NUMA placement off: {e}"),
+            }
+        }
+        if config.backend != gpu::Backend::Cpu {
+            match gpu::enable(config.backend) {
+                Ok(name) => tracing::info!("Offloading to GPU: {name}"),
+                Err(e) => tracing::warn!("Running on the CPU, GPU unavailable: {e}"),
+            }
+        }
+        if config.pin_threads {
+            match thread_pool::pin_workers(config.threads as usize, config.reserve_runtime_core) {
+                Ok(workers) => tracing::info!("Pinned {workers} inference threads to cores"),
+                Err(e) => tracing::warn!("Threads not pinned: {e}"),
+            }
+        }
         Self {
             config,
             model: None,
+            session: BTreeMap::new(),
         }
     }
 
@@ -94,6 +424,7 @@ impl BrainEngine {
         let mut engine = Self {
             config,
             model: None,
+            session: BTreeMap::new(),
         };
         engine.load_model(model_path)?;
         Ok(engine)
@@ -103,17 +434,78 @@ impl BrainEngine {
     pub fn load_model(&mut self, model_path: &Path) -> Result<()> {
         tracing::info!("Loading model from: {}", model_path.display());
 
-        let mmap_model = mmap::MmapModel::load(model_path)?;
-        let params = model::ModelParams::from_gguf(&mmap_model.gguf);
+        let mmap_model = mmap::MmapModel::load_with(model_path, &self.parse_options())?;
+        self.init_model(mmap_model, model_path)
+    }
+
+    /// Load a GGUF model from memory, e.g. one a browser fetched. `name`
+    /// stands in for the file path in [`model_info`](Self::model_info).
+    pub fn load_model_bytes(&mut self, bytes: Vec<u8>, name: &str) -> Result<()> {
+        tracing::info!("Loading model from memory: {name}");
+
+        let mmap_model = mmap::MmapModel::from_bytes_with(bytes, &self.parse_options())?;
+        self.init_model(mmap_model, Path::new(name))
+    }
+
+    fn parse_options(&self) -> gguf::ParseOptions {
+        gguf::ParseOptions {
+            skip_unknown_tensors: self.config.skip_unknown_tensors,
+            ..Default::default()
+        }
+    }
+
+    fn init_model(&mut self, mmap_model: mmap::MmapModel, model_path: &Path) -> Result<()> {
+        let mut params = model::ModelParams::from_gguf(&mmap_model.gguf);
+        if let Some(scaling) = self.config.rope_scaling {
+            params.set_rope_scaling(scaling);
+        }
+        params.validate()?;
+        quant::check_readable(&mmap_model.gguf.tensors)?;
+        mmap_model.interleave_pages();
+        mmap_model.map_to_gpu();
+        if !mmap_model.gguf.skipped_tensors.is_empty() {
+            tracing::warn!(
+                "Skipped {} tensors of unknown types: {}",
+                mmap_model.gguf.skipped_tensors.len(),
+                mmap_model.gguf.skipped_tensors.join(", ")
+            );
+        }
 
         tracing::info!(
-            "Model params: dim={}, layers={}, heads={}, kv_heads={}, vocab={}",
+            "Model params: dim={}, layers={}, heads={}, kv_heads={}, vocab={}, window={}",
             params.dim,
             params.n_layers,
             params.n_heads,
             params.n_kv_heads,
-            params.vocab_size
+            params.vocab_size,
+            params.sliding_window
         );
+        if !params.rope_scaling.is_none() {
+            tracing::info!(
+                "RoPE scaling: {:?} x{} ({} trained positions to {})",
+                params.rope_scaling.kind,
+                params.rope_scaling.factor,
+                params.trained_context(),
+                params.max_seq_len
+            );
+        }
+        let quant = mmap_model
+            .gguf
+            .main_type()
+            .map(|t| format!("{t:?}").to_lowercase())
+            .unwrap_or_else(|| "unknown".into());
+        metrics::counter(
+            "bizclaw_brain_models_loaded_total",
+            &[("arch", mmap_model.architecture()), ("quant", &quant)],
+        )
+        .inc();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(path) = &self.config.tuning_file
+            && let Err(e) = tuning::load_or_tune(path)
+        {
+            tracing::warn!("Matmul autotuning failed, using the defaults: {e}");
+        }
 
         // Build weight index
         let weights = forward::TransformerWeights::from_gguf(&mmap_model, &params);
@@ -132,18 +524,46 @@ impl BrainEngine {
             });
 
         tracing::info!("Tokenizer loaded: vocab_size={}", tokenizer.vocab_size());
+        // Token IDs index the logits.
+        if tokenizer.vocab_size() > params.vocab_size as usize {
+            return Err(gguf::GgufError::InvalidMetadata(format!(
+                "the tokenizer has {} tokens but the model only {}",
+                tokenizer.vocab_size(),
+                params.vocab_size
+            ))
+            .into());
+        }
 
-        // Create KV cache
-        let kv_cache = kv_cache::KvCache::new(
-            params.n_layers as usize,
-            params.max_seq_len as usize,
-            params.n_kv_heads as usize,
-            params.head_dim as usize,
-        );
+        // Create KV caches, one per slot: the whole context, or a ring
+        // buffer over the attention window for sliding-window models
+        let new_cache = || {
+            kv_cache::KvCache::with_type(
+                params.n_layers as usize,
+                params.kv_len() as usize,
+                params.n_kv_heads as usize,
+                params.head_dim as usize,
+                self.config.kv_cache,
+            )
+        };
+        let kv_cache = new_cache();
+        let n_slots = self.config.slots.max(1) as usize;
         tracing::info!(
-            "KV cache: {:.1} MB",
-            kv_cache.memory_usage() as f64 / 1024.0 / 1024.0
+            "KV cache: up to {:.1} MB ({:?}) x {n_slots} slots, allocated {} positions at a time",
+            kv_cache.max_memory_usage() as f64 / 1024.0 / 1024.0,
+            kv_cache.kv_type(),
+            kv_cache::KV_PAGE
         );
+        // Slot 0 starts active; its place holds an empty stand-in.
+        let slots = (0..n_slots)
+            .map(|i| Slot {
+                kv_cache: if i == 0 {
+                    kv_cache::KvCache::new(0, 0, 0, 0)
+                } else {
+                    new_cache()
+                },
+                cached: Vec::new(),
+            })
+            .collect();
 
         // Create sampler
         let sampler = sampler::Sampler::new(sampler::SamplerConfig {
@@ -154,13 +574,19 @@ impl BrainEngine {
             repeat_last_n: 64,
         });
 
+        let grammar = grammar::JsonGrammar::new(tokenizer.vocab());
+
         self.model = Some(LoadedModel {
             mmap_model,
             params,
             weights,
             tokenizer,
             kv_cache,
+            cached: Vec::new(),
+            slots,
+            active: 0,
             sampler,
+            grammar,
             path: model_path.to_path_buf(),
         });
 
@@ -175,75 +601,258 @@ impl BrainEngine {
 
     /// Generate text completion using the loaded model.
     pub fn generate(&mut self, prompt: &str, max_tokens: u32) -> Result<String> {
+        let params = GenerationParams::max_tokens(max_tokens.min(self.config.max_tokens));
+        Ok(self.decode(prompt, &params, &mut |_| true)?.text)
+    }
+
+    /// Generate text, passing each token's text to `on_token` as soon as
+    /// it is sampled. Generation stops early when `on_token` returns false.
+    pub fn generate_stream(
+        &mut self,
+        prompt: &str,
+        max_tokens: u32,
+        mut on_token: impl FnMut(&str) -> bool,
+    ) -> Result<String> {
+        let params = GenerationParams::max_tokens(max_tokens.min(self.config.max_tokens));
+        Ok(self.decode(prompt, &params, &mut on_token)?.text)
+    }
+
+    /// Generate with per-call `params`, reporting why it ended, token
+    /// counts and timings.
+    pub fn generate_with(
+        &mut self,
+        prompt: &str,
+        params: &GenerationParams,
+    ) -> Result<GenerationResult> {
+        self.decode(prompt, params, &mut |_| true)
+    }
+
+    /// [`generate_with`](Self::generate_with), passing each token's text to
+    /// `on_token`; returning false cancels generation.
+    pub fn generate_stream_with(
+        &mut self,
+        prompt: &str,
+        params: &GenerationParams,
+        mut on_token: impl FnMut(&str) -> bool,
+    ) -> Result<GenerationResult> {
+        self.decode(prompt, params, &mut on_token)
+    }
+
+    /// Generate one JSON value. Logits are masked by the JSON grammar, so
+    /// the output is balanced and generation stops as soon as it closes.
+    pub fn generate_json(&mut self, prompt: &str, max_tokens: u32) -> Result<serde_json::Value> {
+        let params = GenerationParams {
+            json: true,
+            ..GenerationParams::max_tokens(max_tokens.min(self.config.max_tokens))
+        };
+        let text = self.decode(prompt, &params, &mut |_| true)?.text;
+        serde_json::from_str(text.trim())
+            .map_err(|e| BizClawError::Brain(format!("Model produced invalid JSON: {e}")))
+    }
+
+    /// Run `params`' sampler settings for one generation, then put the
+    /// engine's back.
+    fn decode(
+        &mut self,
+        prompt: &str,
+        params: &GenerationParams,
+        on_token: &mut dyn FnMut(&str) -> bool,
+    ) -> Result<GenerationResult> {
+        let max_tokens = params.max_tokens.unwrap_or(self.config.max_tokens);
         let model = self
             .model
             .as_mut()
-            .ok_or_else(|| BizClawError::Brain("Model not loaded".into()))?;
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?;
 
-        // Tokenize prompt
-        let mut input_tokens = vec![model.tokenizer.bos_id];
-        input_tokens.extend(model.tokenizer.encode(prompt));
+        model.select_slot(params.slot as usize)?;
+        let saved = model.sampler.config().clone();
+        model.sampler.set_config(params.sampler_config(&saved));
+        if let Some(seed) = params.seed {
+            model.sampler.set_rng_state(seed);
+        }
+        let context_shift = self.config.context_shift;
+        let result = model.decode(prompt, params, max_tokens as usize, context_shift, on_token);
+        model.sampler.set_config(saved);
+        let result = result?;
 
-        let total_len = input_tokens.len();
         tracing::debug!(
-            "Generate: prompt_len={}, input_tokens={}",
-            prompt.len(),
-            total_len
+            "Generated {} tokens ({})",
+            result.generated_tokens,
+            result.finish_reason.as_str()
         );
+        metrics::counter("bizclaw_brain_prompt_tokens_total", &[]).add(result.prompt_tokens as u64);
+        metrics::counter("bizclaw_brain_generated_tokens_total", &[])
+            .add(result.generated_tokens as u64);
+        #[cfg(not(target_arch = "wasm32"))]
+        metrics::histogram("bizclaw_brain_decode_seconds", &[])
+            .observe_duration(result.timings.total());
+        Ok(result)
+    }
 
-        let mut output_tokens = Vec::new();
-        let max_gen = max_tokens.min(self.config.max_tokens) as usize;
-        let mut logits = vec![0.0f32; model.params.vocab_size as usize];
+    /// Embed text as the mean of the model's final hidden states, L2-normalized.
+    pub fn embed(&mut self, text: &str) -> Result<Vec<f32>> {
+        let model = self
+            .model
+            .as_mut()
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?;
+
+        let mut tokens = vec![model.tokenizer.bos_id];
+        tokens.extend(model.tokenizer.encode(text));
+        tokens.truncate(model.params.max_seq_len as usize);
+
+        model.cached.clear();
+        let dim = model.params.dim as usize;
+        let mut hidden = vec![0.0f32; dim];
+        let mut sum = vec![0.0f32; dim];
+        for (pos, &token) in tokens.iter().enumerate() {
+            forward::forward_hidden(
+                &model.mmap_model,
+                &model.weights,
+                &model.params,
+                &mut model.kv_cache,
+                token,
+                pos,
+                &mut hidden,
+            )?;
+            tensor::elementwise_add(&mut sum, &hidden);
+        }
 
-        for step in 0..total_len + max_gen {
-            // Get the token to process
-            let token = if step < total_len {
-                input_tokens[step]
-            } else if let Some(&last) = output_tokens.last() {
-                last
-            } else {
-                break;
-            };
+        let norm = sum.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            sum.iter_mut().for_each(|v| *v /= norm);
+        }
+        Ok(sum)
+    }
+
+    /// Perplexity on `text`: exp of the mean negative log-likelihood of each
+    /// token given the ones before it. Lower is better.
+    pub fn perplexity(&mut self, text: &str) -> Result<f64> {
+        let model = self
+            .model
+            .as_mut()
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?;
+
+        let mut tokens = vec![model.tokenizer.bos_id];
+        tokens.extend(model.tokenizer.encode(text));
+        tokens.truncate(model.params.max_seq_len as usize);
+        if tokens.len() < 2 {
+            return Err(BizClawError::Brain("Text too short for perplexity".into()));
+        }
 
-            // Run forward pass
+        model.cached.clear();
+        let mut logits = vec![0.0f32; model.params.vocab_size as usize];
+        let mut nll = 0.0f64;
+        for pos in 0..tokens.len() - 1 {
             forward::forward(
                 &model.mmap_model,
                 &model.weights,
                 &model.params,
                 &mut model.kv_cache,
-                token,
-                step,
+                tokens[pos],
+                pos,
                 &mut logits,
             )?;
+            let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            let sum: f64 = logits.iter().map(|&l| ((l - max) as f64).exp()).sum();
+            let target = logits[tokens[pos + 1] as usize];
+            nll += sum.ln() - (target - max) as f64;
+        }
+        Ok((nll / (tokens.len() - 1) as f64).exp())
+    }
 
-            // Only sample after processing all input tokens
-            if step >= total_len - 1 {
-                let all_tokens: Vec<u32> = input_tokens
-                    .iter()
-                    .chain(output_tokens.iter())
-                    .copied()
-                    .collect();
-                let next_token = model.sampler.sample(&mut logits, &all_tokens);
+    /// Tokens of `text`, without BOS.
+    pub fn tokenize(&self, text: &str) -> Result<Vec<u32>> {
+        let model = self
+            .model
+            .as_ref()
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?;
+        Ok(model.tokenizer.encode(text))
+    }
 
-                // Check for EOS
-                if next_token == model.tokenizer.eos_id {
-                    break;
-                }
+    /// Text of `tokens`.
+    pub fn detokenize(&self, tokens: &[u32]) -> Result<String> {
+        let model = self
+            .model
+            .as_ref()
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?;
+        Ok(model.tokenizer.decode(tokens))
+    }
 
-                output_tokens.push(next_token);
+    /// Number of tokens `text` encodes to, without BOS.
+    pub fn count_tokens(&self, text: &str) -> Result<usize> {
+        let model = self
+            .model
+            .as_ref()
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?;
+        Ok(model.tokenizer.encode(text).len())
+    }
+
+    /// KV cache slots of the loaded model, see [`GenerationParams::slot`].
+    pub fn slots(&self) -> usize {
+        self.model.as_ref().map_or(0, |m| m.slots.len())
+    }
+
+    /// Make `slot` the conversation that [`embed`](Self::embed),
+    /// [`perplexity`](Self::perplexity) and [`snapshot`](Self::snapshot) use
+    /// and replace. Generation selects its own, see
+    /// [`GenerationParams::slot`].
+    pub fn select_slot(&mut self, slot: usize) -> Result<()> {
+        self.model
+            .as_mut()
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?
+            .select_slot(slot)
+    }
+
+    /// Forget the conversation in `slot`, freeing its KV cache pages; its
+    /// next prompt runs in full.
+    pub fn clear_slot(&mut self, slot: usize) -> Result<()> {
+        let model = self
+            .model
+            .as_mut()
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?;
+        match model.slots.get_mut(slot) {
+            Some(_) if slot == model.active => {
+                model.cached.clear();
+                model.kv_cache.reset();
+            }
+            Some(parked) => {
+                parked.cached.clear();
+                parked.kv_cache.reset();
+            }
+            None => {
+                return Err(BizClawError::Brain(format!(
+                    "No KV cache slot {slot}; the engine has {}",
+                    model.slots.len()
+                )));
             }
         }
+        Ok(())
+    }
+
+    /// Positions the loaded model's KV cache holds: prompt and output together.
+    pub fn context_size(&self) -> Option<usize> {
+        self.model.as_ref().map(|m| m.params.max_seq_len as usize)
+    }
 
-        // Decode output tokens
-        let output = model.tokenizer.decode(&output_tokens);
-        tracing::debug!("Generated {} tokens", output_tokens.len());
-        Ok(output)
+    /// Sampler settings of the loaded model.
+    pub fn sampler_config(&self) -> Option<&sampler::SamplerConfig> {
+        self.model.as_ref().map(|m| m.sampler.config())
     }
 
-    /// Generate with JSON grammar constraint.
-    pub fn generate_json(&mut self, prompt: &str) -> Result<serde_json::Value> {
-        let text = self.generate(prompt, self.config.max_tokens)?;
-        Ok(serde_json::json!({"response": text}))
+    /// Change the sampler settings of the loaded model. Temperature and
+    /// top-p are kept in the brain config, so they outlive a reload.
+    pub fn set_sampler_config(&mut self, config: sampler::SamplerConfig) {
+        self.config.temperature = config.temperature;
+        self.config.top_p = config.top_p;
+        if let Some(model) = self.model.as_mut() {
+            model.sampler.set_config(config);
+        }
+    }
+
+    /// Change the cap on tokens generated per call, e.g. after a config
+    /// reload.
+    pub fn set_max_tokens(&mut self, max_tokens: u32) {
+        self.config.max_tokens = max_tokens;
     }
 
     /// Get the brain config.
@@ -264,3 +873,111 @@ impl BrainEngine {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gguf::{GgmlType, GgufValue, TensorInfo};
+
+    /// A one-layer LLaMA of F32 ramps, small enough to run in a test. It
+    /// has no vocabulary, so the fallback tokenizer reads prompts.
+    fn tiny_model() -> Vec<u8> {
+        let (dim, hidden, vocab) = (8u64, 16u64, 16u64);
+        let metadata: Vec<(String, GgufValue)> = [
+            ("general.architecture", GgufValue::String("llama".into())),
+            ("llama.embedding_length", GgufValue::U32(dim as u32)),
+            ("llama.feed_forward_length", GgufValue::U32(hidden as u32)),
+            ("llama.block_count", GgufValue::U32(1)),
+            ("llama.attention.head_count", GgufValue::U32(2)),
+            ("llama.context_length", GgufValue::U32(32)),
+            ("llama.vocab_size", GgufValue::U32(vocab as u32)),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+        let shapes = [
+            ("token_embd.weight", vec![dim, vocab]),
+            ("output_norm.weight", vec![dim]),
+            ("output.weight", vec![dim, vocab]),
+            ("blk.0.attn_norm.weight", vec![dim]),
+            ("blk.0.attn_q.weight", vec![dim, dim]),
+            ("blk.0.attn_k.weight", vec![dim, dim]),
+            ("blk.0.attn_v.weight", vec![dim, dim]),
+            ("blk.0.attn_output.weight", vec![dim, dim]),
+            ("blk.0.ffn_norm.weight", vec![dim]),
+            ("blk.0.ffn_gate.weight", vec![dim, hidden]),
+            ("blk.0.ffn_up.weight", vec![dim, hidden]),
+            ("blk.0.ffn_down.weight", vec![hidden, dim]),
+        ];
+        let mut offset = 0;
+        let tensors: Vec<TensorInfo> = shapes
+            .iter()
+            .map(|(name, dims)| {
+                let tensor = TensorInfo {
+                    name: name.to_string(),
+                    n_dims: dims.len() as u32,
+                    dims: dims.clone(),
+                    ggml_type: GgmlType::F32,
+                    offset,
+                };
+                offset += tensor.size_bytes().div_ceil(32) * 32;
+                tensor
+            })
+            .collect();
+        let mut file = Vec::new();
+        gguf::write_header(&mut file, &metadata, &tensors, 32).unwrap();
+        let data_start = file.len();
+        for tensor in &tensors {
+            file.resize(data_start + tensor.offset as usize, 0);
+            for i in 0..tensor.n_elements() {
+                let v = (i * 7919 % 211) as f32 / 211.0 - 0.5;
+                file.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        file
+    }
+
+    #[test]
+    fn test_slots() {
+        let mut engine = BrainEngine::new(BrainConfig {
+            slots: 2,
+            ..Default::default()
+        });
+        engine.load_model_bytes(tiny_model(), "tiny.gguf").unwrap();
+        // Prompts that differ after the BOS token: 4 tokens each.
+        let run = |engine: &mut BrainEngine, slot: u32, prompt: &str| {
+            let params = GenerationParams {
+                slot,
+                ..GenerationParams::max_tokens(2)
+            };
+            engine.generate_with(prompt, &params).unwrap().cached_tokens
+        };
+        let (a, b) = ("  x", "x  ");
+
+        assert_eq!(run(&mut engine, 0, a), 0);
+        assert_eq!(run(&mut engine, 0, a), 3);
+        // Slot 1 starts empty; sharing slot 0's cache would reuse the BOS.
+        assert_eq!(run(&mut engine, 1, b), 0);
+        assert_eq!(run(&mut engine, 0, a), 3);
+        assert_eq!(run(&mut engine, 1, b), 3);
+
+        // Clearing the parked slot 0 leaves the active slot 1 alone...
+        engine.clear_slot(0).unwrap();
+        assert_eq!(run(&mut engine, 1, b), 3);
+        assert_eq!(run(&mut engine, 0, a), 0);
+        // ...and clearing the active one leaves the parked one.
+        engine.clear_slot(0).unwrap();
+        assert_eq!(run(&mut engine, 1, b), 3);
+        assert_eq!(run(&mut engine, 0, a), 0);
+
+        assert!(engine.select_slot(2).is_err());
+        assert!(engine.clear_slot(2).is_err());
+        let params = GenerationParams {
+            slot: 2,
+            ..GenerationParams::max_tokens(2)
+        };
+        assert!(engine.generate_with(a, &params).is_err());
+        // The failed switch left slot 0 active and intact.
+        assert_eq!(run(&mut engine, 0, a), 3);
+    }
+}
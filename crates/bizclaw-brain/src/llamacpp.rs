@@ -123,7 +123,7 @@ impl LlamaCppBackend {
     /// This calls the llama.cpp C API via FFI for fast inference.
     pub fn generate(&self, prompt: &str, max_tokens: u32) -> Result<String> {
         if !self.loaded {
-            return Err(BizClawError::Brain("Model not loaded".into()));
+            return Err(BizClawError::ModelNotLoaded("call load_model first".into()));
         }
 
         // FFI call placeholder — actual implementation requires linking to libllama
@@ -3,25 +3,62 @@
 //! Uses mmap to load model weights directly from disk without copying
 //! them into process memory. This is critical for running on devices
 //! with limited RAM (e.g., Raspberry Pi with 512MB).
+//!
+//! Without the `mmap` feature (the browser build) the file is read into
+//! memory instead, and [`MmapModel::from_bytes`] takes a model that was
+//! fetched rather than opened.
 
 use bizclaw_core::error::{BizClawError, Result};
-use memmap2::Mmap;
 use std::fs::File;
 use std::path::Path;
 
-use crate::gguf::GgufFile;
+use crate::gguf::{GgufFile, ParseOptions};
+
+/// The bytes of a model file.
+enum ModelBytes {
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+    Owned(Vec<u8>),
+}
+
+impl ModelBytes {
+    fn is_mapped(&self) -> bool {
+        match self {
+            #[cfg(feature = "mmap")]
+            Self::Mapped(_) => true,
+            Self::Owned(_) => false,
+        }
+    }
+}
+
+impl std::ops::Deref for ModelBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(feature = "mmap")]
+            Self::Mapped(mmap) => mmap,
+            Self::Owned(bytes) => bytes,
+        }
+    }
+}
 
 /// A memory-mapped GGUF model file.
 pub struct MmapModel {
     /// The parsed GGUF header with metadata and tensor index.
     pub gguf: GgufFile,
     /// Memory-mapped file data.
-    mmap: Mmap,
+    mmap: ModelBytes,
 }
 
 impl MmapModel {
     /// Load a GGUF model file using mmap.
     pub fn load(path: &Path) -> Result<Self> {
+        Self::load_with(path, &ParseOptions::default())
+    }
+
+    /// Load a GGUF model file, parsing its header with `options`.
+    pub fn load_with(path: &Path, options: &ParseOptions) -> Result<Self> {
         if !path.exists() {
             return Err(BizClawError::ModelLoad(format!(
                 "Model file not found: {}",
@@ -34,7 +71,7 @@ impl MmapModel {
 
         // Parse GGUF header
         let mut reader = std::io::BufReader::new(&file);
-        let gguf = GgufFile::parse(&mut reader)?;
+        let gguf = GgufFile::parse_with(&mut reader, options)?;
 
         tracing::info!(
             "GGUF model: arch={}, tensors={}, data_offset={}",
@@ -44,9 +81,18 @@ impl MmapModel {
         );
 
         // Memory-map the entire file
-        let mmap = unsafe {
-            Mmap::map(&file).map_err(|e| BizClawError::ModelLoad(format!("mmap failed: {e}")))?
-        };
+        #[cfg(feature = "mmap")]
+        let mmap = ModelBytes::Mapped(unsafe {
+            memmap2::Mmap::map(&file)
+                .map_err(|e| BizClawError::ModelLoad(format!("mmap failed: {e}")))?
+        });
+        #[cfg(not(feature = "mmap"))]
+        let mmap = ModelBytes::Owned(
+            std::fs::read(path)
+                .map_err(|e| BizClawError::ModelLoad(format!("Failed to read model: {e}")))?,
+        );
+
+        gguf.check_bounds(mmap.len() as u64)?;
 
         tracing::info!(
             "Model loaded via mmap: {} ({:.1} MB)",
@@ -57,6 +103,39 @@ impl MmapModel {
         Ok(Self { gguf, mmap })
     }
 
+    /// Load a GGUF model already in memory, e.g. fetched by a browser.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        Self::from_bytes_with(bytes, &ParseOptions::default())
+    }
+
+    /// Like [`from_bytes`](Self::from_bytes), parsing with `options`.
+    pub fn from_bytes_with(bytes: Vec<u8>, options: &ParseOptions) -> Result<Self> {
+        let gguf = GgufFile::parse_with(&mut std::io::Cursor::new(&bytes), options)?;
+        gguf.check_bounds(bytes.len() as u64)?;
+        tracing::info!(
+            "GGUF model: arch={}, tensors={}, data_offset={}",
+            gguf.architecture().unwrap_or("unknown"),
+            gguf.tensors.len(),
+            gguf.data_offset
+        );
+        Ok(Self {
+            gguf,
+            mmap: ModelBytes::Owned(bytes),
+        })
+    }
+
+    /// Page the whole file in now, interleaved across NUMA nodes; does
+    /// nothing unless [`crate::numa::enable`] was called.
+    pub fn interleave_pages(&self) {
+        crate::numa::prefault_interleaved(&self.mmap);
+    }
+
+    /// Let the GPU backend, if one is on, see the whole file; see
+    /// [`crate::gpu::map_model`].
+    pub fn map_to_gpu(&self) {
+        crate::gpu::map_model(&self.mmap, self.mmap.is_mapped());
+    }
+
     /// Get a raw byte slice for a tensor's data.
     pub fn tensor_data(&self, tensor_index: usize) -> Result<&[u8]> {
         let tensor = self.gguf.tensors.get(tensor_index).ok_or_else(|| {
@@ -110,3 +189,42 @@ impl MmapModel {
         self.gguf.tensors.len()
     }
 }
+
+impl Drop for MmapModel {
+    fn drop(&mut self) {
+        crate::gpu::unmap_model(&self.mmap);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gguf::{self, GgmlType, GgufValue, TensorInfo};
+
+    #[test]
+    fn test_from_bytes() {
+        let tensors = [TensorInfo {
+            name: "output_norm.weight".into(),
+            n_dims: 1,
+            dims: vec![4],
+            ggml_type: GgmlType::F32,
+            offset: 0,
+        }];
+        let metadata = [(
+            "general.architecture".to_string(),
+            GgufValue::String("llama".into()),
+        )];
+        let mut file = Vec::new();
+        gguf::write_header(&mut file, &metadata, &tensors, 32).unwrap();
+        for v in [1.0f32, 2.0, 3.0, 4.0] {
+            file.extend_from_slice(&v.to_le_bytes());
+        }
+
+        let size = file.len();
+        let model = MmapModel::from_bytes(file).unwrap();
+        assert_eq!(model.architecture(), "llama");
+        assert_eq!(model.file_size(), size);
+        let data = model.tensor_data_by_name("output_norm.weight").unwrap();
+        assert_eq!(data[12..], 4.0f32.to_le_bytes());
+    }
+}
@@ -4,6 +4,17 @@
 //! Reads weights from mmap, dequantizes on-the-fly, and computes
 //! the forward pass producing logits for the next token.
 
+use crate::gguf::GgufError;
+use crate::rope::{Rope, RopeScaling, RopeScalingType};
+use crate::tokenizer::MAX_VOCAB;
+
+/// Most layers a model may declare.
+pub const MAX_LAYERS: u32 = 1024;
+/// Longest context a model may declare.
+pub const MAX_SEQ_LEN: u32 = 1 << 20;
+/// Largest embedding or FFN width a model may declare.
+pub const MAX_WIDTH: u32 = 1 << 20;
+
 /// Model hyperparameters extracted from GGUF metadata.
 #[derive(Debug, Clone)]
 pub struct ModelParams {
@@ -13,10 +24,29 @@ pub struct ModelParams {
     pub n_layers: u32,
     pub n_heads: u32,
     pub n_kv_heads: u32, // for GQA (Grouped Query Attention)
-    pub head_dim: u32,   // dim / n_heads
+    pub head_dim: u32,   // attention.key_length, else dim / n_heads
     pub max_seq_len: u32,
+    /// Positions each token attends to, itself included, for
+    /// sliding-window models like Mistral; 0 attends to the whole context.
+    pub sliding_window: u32,
+    /// RoPE base frequency (`rope.freq_base`): 10000 for LLaMA 2, 1e6 for
+    /// Qwen2 and others trained on long contexts.
     pub rope_theta: f32,
+    /// Leading dimensions of each head RoPE rotates
+    /// (`rope.dimension_count`), the rest passing through as they are:
+    /// `head_dim` but for partial rotary models like Phi-2.
+    pub rope_dims: u32,
+    /// Stretches RoPE positions for models run past their trained context.
+    pub rope_scaling: RopeScaling,
+    /// Largest ALiBi bias for models like MPT and BLOOM that encode
+    /// positions with [`attention::Alibi`](crate::attention::Alibi)
+    /// instead of RoPE; 0 for RoPE.
+    pub alibi_max_bias: f32,
     pub rms_norm_eps: f32,
+    /// Gemma-2 soft-caps of the attention scores and the final logits,
+    /// see [`tensor::softcap`](crate::tensor::softcap); 0 for none.
+    pub attn_logit_softcap: f32,
+    pub final_logit_softcap: f32,
 }
 
 impl Default for ModelParams {
@@ -31,8 +61,14 @@ impl Default for ModelParams {
             n_kv_heads: 4,
             head_dim: 64,
             max_seq_len: 2048,
+            sliding_window: 0,
             rope_theta: 10000.0,
+            rope_dims: 64,
+            rope_scaling: RopeScaling::default(),
+            alibi_max_bias: 0.0,
             rms_norm_eps: 1e-5,
+            attn_logit_softcap: 0.0,
+            final_logit_softcap: 0.0,
         }
     }
 }
@@ -52,6 +88,45 @@ impl ModelParams {
         let n_kv_heads = gguf
             .get_u32(&format!("{prefix}attention.head_count_kv"))
             .unwrap_or(n_heads);
+        // Usually dim / n_heads, but some models set it separately. Zero
+        // (rejected by `validate`) if neither gives a whole number.
+        let head_dim = gguf
+            .get_u32(&format!("{prefix}attention.key_length"))
+            .or_else(|| (dim.checked_rem(n_heads) == Some(0)).then(|| dim / n_heads))
+            .unwrap_or(0);
+        // `rope.scaling.*`, or older files' `rope.scale_linear`. Unknown
+        // types run unscaled.
+        let rope_key = |key: &str| format!("{prefix}rope.{key}");
+        let rope_scaling = match gguf
+            .metadata
+            .get(&rope_key("scaling.type"))
+            .and_then(|v| v.as_str())
+        {
+            Some(name) => {
+                let defaults = RopeScaling::default();
+                RopeScaling {
+                    kind: RopeScalingType::from_name(name).unwrap_or_default(),
+                    factor: gguf.get_f32(&rope_key("scaling.factor")).unwrap_or(1.0),
+                    original_context: gguf
+                        .get_u32(&rope_key("scaling.original_context_length"))
+                        .unwrap_or(0),
+                    low_freq_factor: gguf
+                        .get_f32(&rope_key("scaling.low_freq_factor"))
+                        .unwrap_or(defaults.low_freq_factor),
+                    high_freq_factor: gguf
+                        .get_f32(&rope_key("scaling.high_freq_factor"))
+                        .unwrap_or(defaults.high_freq_factor),
+                }
+            }
+            None => match gguf.get_f32(&rope_key("scale_linear")) {
+                Some(factor) => RopeScaling {
+                    kind: RopeScalingType::Linear,
+                    factor,
+                    ..Default::default()
+                },
+                None => RopeScaling::default(),
+            },
+        };
 
         Self {
             vocab_size: gguf
@@ -73,16 +148,273 @@ impl ModelParams {
             n_layers: gguf.get_u32(&format!("{prefix}block_count")).unwrap_or(22),
             n_heads,
             n_kv_heads,
-            head_dim: dim / n_heads,
+            head_dim,
             max_seq_len: gguf
                 .get_u32(&format!("{prefix}context_length"))
                 .unwrap_or(2048),
-            rope_theta: gguf
-                .get_f32(&format!("{prefix}rope.freq_base"))
-                .unwrap_or(10000.0),
+            sliding_window: gguf
+                .get_u32(&format!("{prefix}attention.sliding_window"))
+                .unwrap_or(0),
+            rope_theta: gguf.get_f32(&rope_key("freq_base")).unwrap_or(10000.0),
+            rope_dims: gguf
+                .get_u32(&rope_key("dimension_count"))
+                .unwrap_or(head_dim),
+            rope_scaling,
+            // MPT stores its bias; BLOOM always uses 8.
+            alibi_max_bias: gguf
+                .get_f32(&format!("{prefix}attention.max_alibi_bias"))
+                .or_else(|| matches!(arch, "mpt" | "bloom").then_some(8.0))
+                .unwrap_or(0.0),
             rms_norm_eps: gguf
                 .get_f32(&format!("{prefix}attention.layer_norm_rms_epsilon"))
                 .unwrap_or(1e-5),
+            attn_logit_softcap: gguf
+                .get_f32(&format!("{prefix}attn_logit_softcapping"))
+                .unwrap_or(0.0),
+            final_logit_softcap: gguf
+                .get_f32(&format!("{prefix}final_logit_softcapping"))
+                .unwrap_or(0.0),
+        }
+    }
+
+    /// Width of the query projection: every query head.
+    pub fn q_dim(&self) -> u32 {
+        self.n_heads * self.head_dim
+    }
+
+    /// Width of the key and value projections: only the KV heads, which
+    /// under GQA groups of query heads share.
+    pub fn kv_dim(&self) -> u32 {
+        self.n_kv_heads * self.head_dim
+    }
+
+    /// Positions the KV cache holds: the context, or just the window when
+    /// that is shorter.
+    pub fn kv_len(&self) -> u32 {
+        match self.sliding_window {
+            0 => self.max_seq_len,
+            window => window.min(self.max_seq_len),
+        }
+    }
+
+    /// Whether positions are encoded with ALiBi rather than RoPE.
+    pub fn uses_alibi(&self) -> bool {
+        self.alibi_max_bias > 0.0
+    }
+
+    /// Context the model was trained on: shorter than `max_seq_len` under
+    /// RoPE scaling.
+    pub fn trained_context(&self) -> u32 {
+        match self.rope_scaling.original_context {
+            0 if self.rope_scaling.is_none() => self.max_seq_len,
+            0 => (self.max_seq_len as f32 / self.rope_scaling.factor) as u32,
+            original => original,
+        }
+    }
+
+    /// Run with `scaling` in place of the model's own RoPE scaling: the
+    /// context becomes `factor` times the trained one.
+    pub fn set_rope_scaling(&mut self, scaling: RopeScaling) {
+        let trained = match scaling.original_context {
+            0 => self.trained_context(),
+            original => original,
+        };
+        self.max_seq_len = if scaling.is_none() {
+            trained
+        } else {
+            (trained as f32 * scaling.factor) as u32
+        };
+        self.rope_scaling = RopeScaling {
+            original_context: trained,
+            ..scaling
+        };
+    }
+
+    /// RoPE for these heads, scaled.
+    pub fn rope(&self) -> Rope {
+        let scaling = RopeScaling {
+            original_context: self.trained_context(),
+            ..self.rope_scaling
+        };
+        Rope::new(
+            self.head_dim as usize,
+            self.rope_dims as usize,
+            self.rope_theta,
+            &scaling,
+        )
+    }
+
+    /// Reject parameters the forward pass can't run, or would run out of
+    /// memory allocating for, before anything is allocated.
+    pub fn validate(&self) -> Result<(), GgufError> {
+        let invalid = |reason: String| Err(GgufError::InvalidMetadata(reason));
+        if self.n_heads == 0 || self.head_dim == 0 {
+            return invalid(format!(
+                "embedding length {} is not a multiple of {} heads",
+                self.dim, self.n_heads
+            ));
+        }
+        if !self.head_dim.is_multiple_of(2) || self.head_dim > MAX_WIDTH {
+            return invalid(format!(
+                "head dimension {} must be even and at most {MAX_WIDTH}",
+                self.head_dim
+            ));
         }
+        if self.n_kv_heads == 0 || !self.n_heads.is_multiple_of(self.n_kv_heads) {
+            return invalid(format!(
+                "{} heads can't be grouped into {} KV heads",
+                self.n_heads, self.n_kv_heads
+            ));
+        }
+        if self.rope_dims == 0
+            || !self.rope_dims.is_multiple_of(2)
+            || self.rope_dims > self.head_dim
+        {
+            return invalid(format!(
+                "RoPE dimension count {} must be even and at most the head dimension {}",
+                self.rope_dims, self.head_dim
+            ));
+        }
+        let factor = self.rope_scaling.factor;
+        if !factor.is_finite() || factor < 1.0 {
+            return invalid(format!("RoPE scaling factor {factor} must be at least 1"));
+        }
+        let (low, high) = (
+            self.rope_scaling.low_freq_factor,
+            self.rope_scaling.high_freq_factor,
+        );
+        if !(low > 0.0 && high > low && high.is_finite()) {
+            return invalid(format!(
+                "RoPE low and high frequency factors {low} and {high} must be increasing and positive"
+            ));
+        }
+        if !self.rope_theta.is_finite() || self.rope_theta <= 1.0 {
+            return invalid(format!(
+                "RoPE base {} must be greater than 1",
+                self.rope_theta
+            ));
+        }
+        for (what, cap) in [
+            ("ALiBi bias", self.alibi_max_bias),
+            ("attention logit soft-cap", self.attn_logit_softcap),
+            ("final logit soft-cap", self.final_logit_softcap),
+        ] {
+            if !cap.is_finite() || cap < 0.0 {
+                return invalid(format!("{what} {cap} must be 0 or positive"));
+            }
+        }
+        for (what, value, max) in [
+            ("block count", self.n_layers, MAX_LAYERS),
+            ("context length", self.max_seq_len, MAX_SEQ_LEN),
+            ("embedding length", self.dim, MAX_WIDTH),
+            ("feed-forward length", self.hidden_dim, MAX_WIDTH),
+            ("vocabulary size", self.vocab_size, MAX_VOCAB as u32),
+        ] {
+            if value == 0 || value > max {
+                return invalid(format!("{what} {value} is outside 1..={max}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(ModelParams::default().validate().is_ok());
+
+        let no_heads = ModelParams {
+            n_heads: 0,
+            ..Default::default()
+        };
+        assert!(no_heads.validate().is_err());
+
+        let no_head_dim = ModelParams {
+            head_dim: 0,
+            ..Default::default()
+        };
+        assert!(no_head_dim.validate().is_err());
+
+        // Llama 3 / Mistral style: 8 KV heads shared by 32 query heads.
+        let gqa = ModelParams {
+            dim: 4096,
+            n_heads: 32,
+            n_kv_heads: 8,
+            head_dim: 128,
+            ..Default::default()
+        };
+        assert!(gqa.validate().is_ok());
+        assert_eq!(gqa.q_dim(), 4096);
+        assert_eq!(gqa.kv_dim(), 1024);
+
+        // Mistral: a 4096-token window over a 32k context.
+        let mistral = ModelParams {
+            max_seq_len: 32768,
+            sliding_window: 4096,
+            ..Default::default()
+        };
+        assert!(mistral.validate().is_ok());
+        assert_eq!(mistral.kv_len(), 4096);
+        assert_eq!(ModelParams::default().kv_len(), 2048);
+
+        // Gemma-2 caps; a negative one makes no sense.
+        let gemma2 = ModelParams {
+            attn_logit_softcap: 50.0,
+            final_logit_softcap: 30.0,
+            ..Default::default()
+        };
+        assert!(gemma2.validate().is_ok());
+        let negative_cap = ModelParams {
+            final_logit_softcap: -30.0,
+            ..Default::default()
+        };
+        assert!(negative_cap.validate().is_err());
+
+        // Phi-2: RoPE on 32 of each head's 80 dimensions.
+        let phi2 = ModelParams {
+            dim: 2560,
+            n_heads: 32,
+            n_kv_heads: 32,
+            head_dim: 80,
+            rope_dims: 32,
+            ..Default::default()
+        };
+        assert!(phi2.validate().is_ok());
+        let odd_rope = ModelParams {
+            rope_dims: 33,
+            ..Default::default()
+        };
+        assert!(odd_rope.validate().is_err());
+        let wide_rope = ModelParams {
+            rope_dims: 128,
+            ..Default::default()
+        };
+        assert!(wide_rope.validate().is_err());
+
+        // MPT-7B: ALiBi, no RoPE.
+        let mpt = ModelParams {
+            alibi_max_bias: 8.0,
+            ..Default::default()
+        };
+        assert!(mpt.validate().is_ok() && mpt.uses_alibi());
+        assert!(!ModelParams::default().uses_alibi());
+
+        let ungroupable = ModelParams {
+            n_kv_heads: 5,
+            ..Default::default()
+        };
+        assert!(ungroupable.validate().is_err());
+
+        let huge = ModelParams {
+            n_layers: u32::MAX,
+            ..Default::default()
+        };
+        assert!(matches!(
+            huge.validate(),
+            Err(GgufError::InvalidMetadata(reason)) if reason.contains("block count")
+        ));
     }
 }
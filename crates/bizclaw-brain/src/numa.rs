@@ -0,0 +1,163 @@
+//! NUMA placement for multi-socket servers.
+//!
+//! Left to first touch, the mapped weights land on whichever node's
+//! worker happened to read a page first and the KV cache on the node of
+//! the thread running the forward pass, so the workers on the other
+//! socket spend most of their time on remote memory. With [`enable`] the
+//! weights are paged in and KV pages allocated interleaved across all
+//! nodes, and [`crate::thread_pool::pin_workers`] spreads its workers
+//! evenly over the nodes, so every worker sees the same mix of local and
+//! remote pages.
+//!
+//! Memory policy is set with `set_mempolicy`/`mbind`, so this is Linux
+//! only; elsewhere [`enable`] fails and everything else does nothing.
+
+use bizclaw_core::error::{BizClawError, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Interleave model and KV cache memory across this machine's NUMA
+/// nodes from now on. Returns the number of nodes; fails on a machine
+/// with one, where there is nothing to spread.
+pub fn enable() -> Result<usize> {
+    let nodes = crate::system::numa_nodes().len();
+    if nodes < 2 {
+        return Err(BizClawError::Brain(format!(
+            "NUMA placement needs several nodes, this machine has {nodes}"
+        )));
+    }
+    if !cfg!(target_os = "linux") {
+        return Err(BizClawError::Brain(
+            "NUMA placement is only supported on Linux".into(),
+        ));
+    }
+    ENABLED.store(true, Ordering::Relaxed);
+    Ok(nodes)
+}
+
+/// Whether [`enable`] has been called.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Page in all of `data`, a mapped model, with its pages interleaved
+/// across nodes. Reads one byte per page, so the whole file, once.
+pub fn prefault_interleaved(data: &[u8]) {
+    if !enabled() {
+        return;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if let Err(e) = linux::set_interleave(true) {
+            tracing::warn!("Couldn't interleave model pages: {e}");
+            return;
+        }
+        let mut sum = 0u8;
+        for page in data.chunks(linux::page_size()) {
+            sum = sum.wrapping_add(page[0]);
+        }
+        std::hint::black_box(sum);
+        if let Err(e) = linux::set_interleave(false) {
+            tracing::warn!("Couldn't restore the default memory policy: {e}");
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = data;
+}
+
+/// `len` zeroed values whose pages, if large enough to have whole ones,
+/// are placed interleaved across nodes when they are first written.
+pub fn zeroed<T: Clone + Default>(len: usize) -> Box<[T]> {
+    let mut values = Vec::with_capacity(len);
+    if enabled() {
+        #[cfg(target_os = "linux")]
+        linux::interleave_range(values.as_ptr() as *const u8, len * std::mem::size_of::<T>());
+    }
+    // The first touch, after the policy is set
+    values.resize(len, T::default());
+    values.into_boxed_slice()
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    const MPOL_DEFAULT: libc::c_long = 0;
+    const MPOL_INTERLEAVE: libc::c_long = 3;
+
+    pub fn page_size() -> usize {
+        // SAFETY: sysconf has no preconditions.
+        (unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).max(4096) as usize
+    }
+
+    /// A bit per node, for up to 64 nodes; read once, KV pages need it
+    /// as they are allocated.
+    fn all_nodes() -> u64 {
+        static MASK: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
+        *MASK.get_or_init(|| {
+            crate::system::numa_nodes()
+                .iter()
+                .filter(|(node, _)| *node < 64)
+                .fold(0, |mask, (node, _)| mask | 1 << node)
+        })
+    }
+
+    /// Interleave the calling thread's new allocations across all nodes,
+    /// or go back to the default local placement.
+    pub fn set_interleave(on: bool) -> std::io::Result<()> {
+        let mask = all_nodes();
+        // SAFETY: the mask outlives the call and holds `maxnode` bits.
+        let status = unsafe {
+            if on {
+                libc::syscall(
+                    libc::SYS_set_mempolicy,
+                    MPOL_INTERLEAVE,
+                    &mask as *const u64,
+                    65,
+                )
+            } else {
+                libc::syscall(
+                    libc::SYS_set_mempolicy,
+                    MPOL_DEFAULT,
+                    std::ptr::null::<u64>(),
+                    0,
+                )
+            }
+        };
+        if status == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+
+    /// Interleave the whole pages within `len` bytes from `ptr`, which
+    /// must not have been touched yet.
+    pub fn interleave_range(ptr: *const u8, len: usize) {
+        let page = page_size();
+        let start = (ptr as usize).next_multiple_of(page);
+        let end = (ptr as usize + len) / page * page;
+        if end <= start {
+            return;
+        }
+        let mask = all_nodes();
+        // SAFETY: the range lies within an allocation this process owns;
+        // mbind only changes where its pages will be placed.
+        let status = unsafe {
+            libc::syscall(
+                libc::SYS_mbind,
+                start,
+                end - start,
+                MPOL_INTERLEAVE,
+                &mask as *const u64,
+                65,
+                0,
+            )
+        };
+        if status != 0 {
+            tracing::debug!(
+                "mbind failed, KV pages stay local: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
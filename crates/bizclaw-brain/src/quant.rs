@@ -1,11 +1,23 @@
-//! Quantization kernels — dequantize quantized weight blocks to f32.
+//! Quantization kernels — dequantize quantized weight blocks to f32, and
+//! quantize f32 back for `bizclaw quantize`.
 //!
-//! Supports Q4_0, Q4_K_M, Q6_K, Q8_0 formats used by GGUF models.
+//! Supports Q4_0 and Q8_0, and the k-quants Q2_K to Q6_K that most GGUF
+//! models (Q4_K_M, Q5_K_M, Q6_K) are made of: super-blocks of
+//! [`QK_K`] values split into sub-blocks with their own 4 to 8-bit scales.
+//! Of the importance-matrix "IQ" types, IQ4_NL and IQ4_XS are read; they
+//! map 4-bit indices through a fixed non-linear table. The 1 to 3-bit IQ
+//! types index ggml's lattice grids, which this module doesn't carry;
+//! [`check_readable`] refuses models using them at load.
+//!
+//! Each super-block is decoded once, by a function handing each value to a
+//! sink, for both dequantization and [`dot_row`].
 
-use bizclaw_core::error::Result;
+use crate::gguf::GgmlType;
+use bizclaw_core::error::{BizClawError, Result};
 
 /// Dequantize Q4_0 block (18 bytes → 32 f32 values).
-/// Format: scale (f16, 2 bytes) + 16 bytes of 4-bit quantized values.
+/// Format: scale (f16, 2 bytes) + 16 bytes of 4-bit quantized values; byte
+/// `i` holds value `i` in its low nibble and value `i + 16` in its high one.
 pub fn dequantize_q4_0(block: &[u8], output: &mut [f32]) {
     debug_assert!(block.len() >= 18);
     debug_assert!(output.len() >= 32);
@@ -16,8 +28,8 @@ pub fn dequantize_q4_0(block: &[u8], output: &mut [f32]) {
         let byte = block[2 + i];
         let lo = (byte & 0x0F) as f32 - 8.0;
         let hi = ((byte >> 4) & 0x0F) as f32 - 8.0;
-        output[i * 2] = lo * scale;
-        output[i * 2 + 1] = hi * scale;
+        output[i] = lo * scale;
+        output[i + 16] = hi * scale;
     }
 }
 
@@ -34,6 +46,316 @@ pub fn dequantize_q8_0(block: &[u8], output: &mut [f32]) {
     }
 }
 
+/// Values per k-quant super-block.
+pub const QK_K: usize = 256;
+
+fn f16_at(block: &[u8], offset: usize) -> f32 {
+    half::f16::from_le_bytes([block[offset], block[offset + 1]]).to_f32()
+}
+
+/// Scale and min of sub-block `j` of a Q4_K or Q5_K block: eight 6-bit
+/// pairs packed into 12 bytes.
+fn scale_min_k4(j: usize, scales: &[u8]) -> (f32, f32) {
+    let (sc, m) = if j < 4 {
+        (scales[j] & 63, scales[j + 4] & 63)
+    } else {
+        (
+            (scales[j + 4] & 0x0F) | ((scales[j - 4] >> 6) << 4),
+            (scales[j + 4] >> 4) | ((scales[j] >> 6) << 4),
+        )
+    };
+    (sc as f32, m as f32)
+}
+
+/// Decode a Q2_K block (84 bytes → 256 values).
+/// Format: 16 scale/min nibble pairs, 64 bytes of 2-bit values, then the
+/// f16 super-block scale and min.
+fn decode_q2_k(block: &[u8], mut emit: impl FnMut(usize, f32)) {
+    debug_assert!(block.len() >= 84);
+    let (scales, qs) = (&block[..16], &block[16..80]);
+    let (d, dmin) = (f16_at(block, 80), f16_at(block, 82));
+
+    let mut i = 0;
+    for (half, q) in qs.chunks_exact(32).enumerate() {
+        for shift in (0..8).step_by(2) {
+            for (part, q) in q.chunks_exact(16).enumerate() {
+                let sc = scales[half * 8 + shift + part];
+                let (dl, ml) = (d * (sc & 0x0F) as f32, dmin * (sc >> 4) as f32);
+                for &byte in q {
+                    emit(i, dl * ((byte >> shift) & 3) as f32 - ml);
+                    i += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Decode a Q3_K block (110 bytes → 256 values).
+/// Format: 32 bytes of high bits, 64 bytes of low 2-bit values, sixteen
+/// 6-bit scales packed into 12 bytes, then the f16 super-block scale.
+fn decode_q3_k(block: &[u8], mut emit: impl FnMut(usize, f32)) {
+    debug_assert!(block.len() >= 110);
+    let (hmask, qs, packed) = (&block[..32], &block[32..96], &block[96..108]);
+    let d = f16_at(block, 108);
+
+    // Low 4 bits of each scale in the first 8 bytes, high 2 in the last 4
+    let scales: [i8; 16] = std::array::from_fn(|j| {
+        let low = if j < 8 {
+            packed[j] & 0x0F
+        } else {
+            packed[j - 8] >> 4
+        };
+        let high = (packed[8 + j % 4] >> (2 * (j / 4))) & 3;
+        (low | (high << 4)) as i8 - 32
+    });
+
+    let mut i = 0;
+    let mut m = 1u8;
+    for (half, q) in qs.chunks_exact(32).enumerate() {
+        for shift in (0..8).step_by(2) {
+            for part in 0..2 {
+                let dl = d * scales[half * 8 + shift + part] as f32;
+                for l in part * 16..part * 16 + 16 {
+                    let low = ((q[l] >> shift) & 3) as i8;
+                    let high = if hmask[l] & m != 0 { 0 } else { 4 };
+                    emit(i, dl * (low - high) as f32);
+                    i += 1;
+                }
+            }
+            m <<= 1;
+        }
+    }
+}
+
+/// Decode a Q4_K block (144 bytes → 256 values).
+/// Format: f16 super-block scale and min, eight 6-bit scale/min pairs
+/// packed into 12 bytes, then 128 bytes of 4-bit values, 64 at a time:
+/// low nibbles first, then high.
+fn decode_q4_k(block: &[u8], mut emit: impl FnMut(usize, f32)) {
+    debug_assert!(block.len() >= 144);
+    let (d, dmin) = (f16_at(block, 0), f16_at(block, 2));
+    let (scales, qs) = (&block[4..16], &block[16..144]);
+
+    let mut i = 0;
+    for (j, q) in qs.chunks_exact(32).enumerate() {
+        for (nibble, shift) in [(0, 0), (1, 4)] {
+            let (sc, m) = scale_min_k4(2 * j + nibble, scales);
+            let (dl, ml) = (d * sc, dmin * m);
+            for &byte in q {
+                emit(i, dl * ((byte >> shift) & 0x0F) as f32 - ml);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Decode a Q5_K block (176 bytes → 256 values).
+/// Format: as Q4_K, with 32 bytes of fifth bits before the 4-bit values.
+fn decode_q5_k(block: &[u8], mut emit: impl FnMut(usize, f32)) {
+    debug_assert!(block.len() >= 176);
+    let (d, dmin) = (f16_at(block, 0), f16_at(block, 2));
+    let (scales, qh, qs) = (&block[4..16], &block[16..48], &block[48..176]);
+
+    let mut i = 0;
+    for (j, q) in qs.chunks_exact(32).enumerate() {
+        for (nibble, shift) in [(0, 0), (1, 4)] {
+            let (sc, m) = scale_min_k4(2 * j + nibble, scales);
+            let (dl, ml) = (d * sc, dmin * m);
+            let bit = 1u8 << (2 * j + nibble);
+            for (&byte, &high) in q.iter().zip(qh) {
+                let high = if high & bit != 0 { 16 } else { 0 };
+                emit(i, dl * (((byte >> shift) & 0x0F) + high) as f32 - ml);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Decode a Q6_K block (210 bytes → 256 values).
+/// Format: 128 bytes of low 4 bits, 64 bytes of high 2 bits, sixteen
+/// 8-bit scales, then the f16 super-block scale.
+fn decode_q6_k(block: &[u8], mut emit: impl FnMut(usize, f32)) {
+    debug_assert!(block.len() >= 210);
+    let (ql, qh, scales) = (&block[..128], &block[128..192], &block[192..208]);
+    let d = f16_at(block, 208);
+
+    for half in 0..2 {
+        let (ql, qh) = (&ql[half * 64..], &qh[half * 32..]);
+        let sc = |k: usize| d * scales[half * 8 + k] as i8 as f32;
+        for l in 0..32 {
+            let is = l / 16;
+            let q = [
+                (ql[l] & 0x0F) | (qh[l] & 3) << 4,
+                (ql[l + 32] & 0x0F) | ((qh[l] >> 2) & 3) << 4,
+                (ql[l] >> 4) | ((qh[l] >> 4) & 3) << 4,
+                (ql[l + 32] >> 4) | ((qh[l] >> 6) & 3) << 4,
+            ];
+            for (k, q) in q.into_iter().enumerate() {
+                emit(
+                    half * 128 + k * 32 + l,
+                    sc(is + 2 * k) * (q as i8 - 32) as f32,
+                );
+            }
+        }
+    }
+}
+
+/// Decodes a block, handing each value and its index to the sink.
+type Decoder = fn(&[u8], &mut dyn FnMut(usize, f32));
+
+/// Decoder of a type stored in [`QK_K`] super-blocks.
+fn super_block_decoder(ggml_type: GgmlType) -> Option<Decoder> {
+    Some(match ggml_type {
+        GgmlType::Q2K => |b, f| decode_q2_k(b, f),
+        GgmlType::Q3K => |b, f| decode_q3_k(b, f),
+        GgmlType::Q4K => |b, f| decode_q4_k(b, f),
+        GgmlType::Q5K => |b, f| decode_q5_k(b, f),
+        GgmlType::Q6K => |b, f| decode_q6_k(b, f),
+        GgmlType::IQ4XS => |b, f| decode_iq4_xs(b, f),
+        _ => return None,
+    })
+}
+
+/// Dequantize a k-quant or IQ4_XS block of `ggml_type` to [`QK_K`]
+/// values.
+pub fn dequantize_super_block(block: &[u8], output: &mut [f32], ggml_type: GgmlType) {
+    debug_assert!(output.len() >= QK_K);
+    if let Some(decode) = super_block_decoder(ggml_type) {
+        decode(block, &mut |i, v| output[i] = v);
+    }
+}
+
+/// Dot product of a row of `x.len()` values in `ggml_type` with `x`,
+/// taken a block at a time: types with a fused SIMD kernel go through it,
+/// super-blocks are accumulated as they are decoded, other types
+/// dequantized into a block-sized buffer first.
+pub fn dot_row(data: &[u8], x: &[f32], ggml_type: GgmlType) -> Result<f32> {
+    let (block, type_size) = (ggml_type.block_size(), ggml_type.type_size());
+    let n_blocks = x.len().div_ceil(block);
+    if !x.len().is_multiple_of(block) || data.len() < n_blocks * type_size {
+        return Err(BizClawError::Inference(format!(
+            "{ggml_type:?} row of {} bytes can't be dotted with {} values",
+            data.len(),
+            x.len()
+        )));
+    }
+    if let Some(dot) = crate::simd::dot_quantized(&data[..n_blocks * type_size], x, ggml_type) {
+        return Ok(dot);
+    }
+    let mut sum = 0.0f32;
+    if let Some(decode) = super_block_decoder(ggml_type) {
+        for (block_data, x) in data.chunks_exact(type_size).zip(x.chunks_exact(QK_K)) {
+            decode(block_data, &mut |i, v| sum += v * x[i]);
+        }
+    } else {
+        let mut values = vec![0.0f32; block];
+        for (block_data, x) in data.chunks_exact(type_size).zip(x.chunks_exact(block)) {
+            dequantize_row(block_data, &mut values, block, ggml_type)?;
+            sum += crate::simd::dot_product_simd(&values, x);
+        }
+    }
+    Ok(sum)
+}
+
+/// The 16 levels IQ4_NL and IQ4_XS indices select, denser near zero
+/// where most weights are.
+const IQ4_NL_VALUES: [i8; 16] = [
+    -127, -104, -83, -65, -49, -35, -22, -10, 1, 13, 25, 38, 53, 69, 89, 113,
+];
+
+/// Dequantize an IQ4_NL block (18 bytes → 32 f32 values).
+/// Format: scale (f16, 2 bytes) + 16 bytes of 4-bit indices into
+/// [`IQ4_NL_VALUES`], laid out as in Q4_0.
+pub fn dequantize_iq4_nl(block: &[u8], output: &mut [f32]) {
+    debug_assert!(block.len() >= 18);
+    debug_assert!(output.len() >= 32);
+
+    let scale = f16_at(block, 0);
+    for i in 0..16 {
+        let byte = block[2 + i];
+        output[i] = IQ4_NL_VALUES[(byte & 0x0F) as usize] as f32 * scale;
+        output[i + 16] = IQ4_NL_VALUES[(byte >> 4) as usize] as f32 * scale;
+    }
+}
+
+/// Decode an IQ4_XS block (136 bytes → 256 values).
+/// Format: f16 super-block scale, eight 6-bit sub-block scales (high 2
+/// bits in a u16, low 4 in 4 bytes), then eight IQ4_NL-style groups of 16
+/// bytes.
+fn decode_iq4_xs(block: &[u8], mut emit: impl FnMut(usize, f32)) {
+    debug_assert!(block.len() >= 136);
+    let d = f16_at(block, 0);
+    let scales_h = u16::from_le_bytes([block[2], block[3]]);
+    let (scales_l, qs) = (&block[4..8], &block[8..136]);
+
+    for (ib, q) in qs.chunks_exact(16).enumerate() {
+        let low = (scales_l[ib / 2] >> (4 * (ib % 2))) & 0x0F;
+        let high = ((scales_h >> (2 * ib)) & 3) as u8;
+        let dl = d * ((low | (high << 4)) as i32 - 32) as f32;
+        for (j, &byte) in q.iter().enumerate() {
+            emit(
+                ib * 32 + j,
+                dl * IQ4_NL_VALUES[(byte & 0x0F) as usize] as f32,
+            );
+            emit(
+                ib * 32 + j + 16,
+                dl * IQ4_NL_VALUES[(byte >> 4) as usize] as f32,
+            );
+        }
+    }
+}
+
+/// Whether [`dequantize_row`] reads `ggml_type`.
+pub fn can_dequantize(ggml_type: GgmlType) -> bool {
+    matches!(
+        ggml_type,
+        GgmlType::F32
+            | GgmlType::F16
+            | GgmlType::Q4_0
+            | GgmlType::Q8_0
+            | GgmlType::Q2K
+            | GgmlType::Q3K
+            | GgmlType::Q4K
+            | GgmlType::Q5K
+            | GgmlType::Q6K
+            | GgmlType::IQ4NL
+            | GgmlType::IQ4XS
+    )
+}
+
+/// Refuse a model with weights in one of the grid-based IQ types, naming
+/// them, so it fails when loaded rather than at its first forward pass.
+pub fn check_readable(tensors: &[crate::gguf::TensorInfo]) -> Result<()> {
+    let mut grid_types: Vec<GgmlType> = Vec::new();
+    for tensor in tensors {
+        if is_grid_type(tensor.ggml_type) && !grid_types.contains(&tensor.ggml_type) {
+            grid_types.push(tensor.ggml_type);
+        }
+    }
+    if grid_types.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<String> = grid_types.iter().map(|t| format!("{t:?}")).collect();
+    Err(BizClawError::ModelLoad(format!(
+        "Model has {} weights, which index ggml's lattice grids and can't be read; \
+         use an IQ4_XS or k-quant build of the model",
+        names.join(", ")
+    )))
+}
+
+/// The 1 to 3-bit IQ types, whose values index ggml's lattice grids.
+fn is_grid_type(ggml_type: GgmlType) -> bool {
+    matches!(
+        ggml_type,
+        GgmlType::IQ1S
+            | GgmlType::IQ2XXS
+            | GgmlType::IQ2XS
+            | GgmlType::IQ2S
+            | GgmlType::IQ3XXS
+            | GgmlType::IQ3S
+    )
+}
+
 /// Dequantize a full row of quantized data to f32.
 /// Dispatches to the correct dequantization kernel based on type.
 pub fn dequantize_row(
@@ -42,6 +364,14 @@ pub fn dequantize_row(
     n_elements: usize,
     ggml_type: crate::gguf::GgmlType,
 ) -> Result<()> {
+    // A tensor smaller than its shape says must not index past its data.
+    let needed = n_elements.div_ceil(ggml_type.block_size()) * ggml_type.type_size();
+    if output.len() < n_elements || data.len() < needed {
+        return Err(BizClawError::Inference(format!(
+            "{ggml_type:?} tensor too small: {} bytes for {n_elements} values",
+            data.len()
+        )));
+    }
     match ggml_type {
         crate::gguf::GgmlType::F32 => {
             // Direct copy from bytes to f32
@@ -83,6 +413,27 @@ pub fn dequantize_row(
                 dequantize_q8_0(block_data, &mut output[b * block_size..]);
             }
         }
+        GgmlType::IQ4NL => {
+            for b in 0..n_elements / 32 {
+                dequantize_iq4_nl(&data[b * 18..], &mut output[b * 32..]);
+            }
+        }
+        GgmlType::Q2K
+        | GgmlType::Q3K
+        | GgmlType::Q4K
+        | GgmlType::Q5K
+        | GgmlType::Q6K
+        | GgmlType::IQ4XS => {
+            let type_size = ggml_type.type_size();
+            for b in 0..n_elements / QK_K {
+                dequantize_super_block(&data[b * type_size..], &mut output[b * QK_K..], ggml_type);
+            }
+        }
+        _ if is_grid_type(ggml_type) => {
+            return Err(BizClawError::Inference(format!(
+                "{ggml_type:?} weights aren't supported; use an IQ4_XS or k-quant build of the model"
+            )));
+        }
         _ => {
             // For unsupported types, fill with zeros
             tracing::warn!(
@@ -97,6 +448,346 @@ pub fn dequantize_row(
     Ok(())
 }
 
+/// Quantize 32 values to a Q4_0 block (18 bytes), as ggml does: the value
+/// furthest from zero maps to -8.
+pub fn quantize_q4_0(input: &[f32], block: &mut [u8]) {
+    debug_assert!(input.len() >= 32);
+    debug_assert!(block.len() >= 18);
+
+    let max = input[..32]
+        .iter()
+        .copied()
+        .fold(0.0f32, |max, v| if v.abs() > max.abs() { v } else { max });
+    let scale = max / -8.0;
+    let inv = if scale != 0.0 { 1.0 / scale } else { 0.0 };
+    block[..2].copy_from_slice(&half::f16::from_f32(scale).to_le_bytes());
+
+    for i in 0..16 {
+        let lo = ((input[i] * inv + 8.5) as i8).clamp(0, 15) as u8;
+        let hi = ((input[i + 16] * inv + 8.5) as i8).clamp(0, 15) as u8;
+        block[2 + i] = lo | (hi << 4);
+    }
+}
+
+/// Quantize 32 values to a Q8_0 block (34 bytes).
+pub fn quantize_q8_0(input: &[f32], block: &mut [u8]) {
+    debug_assert!(input.len() >= 32);
+    debug_assert!(block.len() >= 34);
+
+    let amax = input[..32].iter().fold(0.0f32, |max, v| max.max(v.abs()));
+    let scale = amax / 127.0;
+    let inv = if scale != 0.0 { 1.0 / scale } else { 0.0 };
+    block[..2].copy_from_slice(&half::f16::from_f32(scale).to_le_bytes());
+
+    for i in 0..32 {
+        block[2 + i] = (input[i] * inv).round() as i8 as u8;
+    }
+}
+
+/// How much each value's error counts when fitting a k-quant scale: its
+/// importance (1 without an importance matrix) scaled by its magnitude
+/// against the block's spread, so large weights are kept closest.
+fn error_weights(x: &[f32], importance: Option<&[f32]>) -> Vec<f32> {
+    let sigma2 = x.iter().map(|v| v * v).sum::<f32>() / x.len() as f32;
+    x.iter()
+        .enumerate()
+        .map(|(i, v)| importance.map_or(1.0, |imp| imp[i]) * (sigma2 + v * v).sqrt())
+        .collect()
+}
+
+/// Scale and min putting `x` on levels `0..=nmax` as `scale * q - min`
+/// with the least weighted squared error: a handful of candidate scales
+/// around the plain range fit, each refined by least squares on the
+/// levels it picks. The min is never negative, as k-quants store it.
+fn fit_scale_min(x: &[f32], w: &[f32], nmax: u8) -> (f32, f32) {
+    let lo = x.iter().copied().fold(0.0f32, f32::min);
+    let hi = x.iter().copied().fold(f32::MIN, f32::max);
+    if hi <= lo {
+        return (0.0, -lo);
+    }
+    let nmax = nmax as f32;
+    let error = |scale: f32, offset: f32, levels: &[f32]| -> f32 {
+        x.iter()
+            .zip(w)
+            .zip(levels)
+            .map(|((x, w), l)| w * (scale * l + offset - x).powi(2))
+            .sum()
+    };
+
+    let mut levels = vec![0.0f32; x.len()];
+    let mut best = (f32::MAX, (hi - lo) / nmax, lo);
+    for step in -9..=9 {
+        let iscale = (nmax + 0.1 * step as f32) / (hi - lo);
+        for (l, x) in levels.iter_mut().zip(x) {
+            *l = (iscale * (x - lo)).round().clamp(0.0, nmax);
+        }
+        let (mut sw, mut swl, mut swll, mut swx, mut swlx) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for ((x, w), l) in x.iter().zip(w).zip(&levels) {
+            sw += w;
+            swl += w * l;
+            swll += w * l * l;
+            swx += w * x;
+            swlx += w * l * x;
+        }
+        let det = sw * swll - swl * swl;
+        if det <= 0.0 {
+            continue;
+        }
+        let mut scale = (sw * swlx - swl * swx) / det;
+        let mut offset = (swll * swx - swl * swlx) / det;
+        if offset > 0.0 {
+            offset = 0.0;
+            scale = swlx / swll;
+        }
+        let err = error(scale, offset, &levels);
+        if err < best.0 {
+            best = (err, scale, offset);
+        }
+    }
+    (best.1.max(0.0), -best.2)
+}
+
+/// Scale putting `x` on levels `-nmax..nmax` with the least weighted
+/// squared error, searched as in [`fit_scale_min`]. Negative when the
+/// largest value is positive, so it lands on the extra negative level.
+fn fit_scale(x: &[f32], w: &[f32], nmax: i32) -> f32 {
+    let max = x
+        .iter()
+        .copied()
+        .fold(0.0f32, |max, v| if v.abs() > max.abs() { v } else { max });
+    if max == 0.0 {
+        return 0.0;
+    }
+    let mut best = (0.0f32, -max / nmax as f32);
+    for step in -9..=9 {
+        let iscale = -(nmax as f32 + 0.1 * step as f32) / max;
+        let (mut swxl, mut swll) = (0.0, 0.0);
+        for (x, w) in x.iter().zip(w) {
+            let l = (iscale * x).round().clamp(-nmax as f32, (nmax - 1) as f32);
+            swxl += w * x * l;
+            swll += w * l * l;
+        }
+        // Least squares leaves an error of Σwx² - (Σwxl)² / Σwll
+        if swll > 0.0 && swxl * swxl / swll > best.0 {
+            best = (swxl * swxl / swll, swxl / swll);
+        }
+    }
+    best.1
+}
+
+/// Fit a scale and min to each 32-value sub-block and pack them as
+/// Q4_K and Q5_K store them: 6-bit multiples of the f16 super-block scale
+/// and min written at the start of `block`. Returns each sub-block's
+/// scale and min as packed.
+fn pack_scale_mins(
+    input: &[f32],
+    importance: Option<&[f32]>,
+    nmax: u8,
+    block: &mut [u8],
+) -> [(f32, f32); 8] {
+    let mut fits = [(0.0f32, 0.0f32); 8];
+    for (j, fit) in fits.iter_mut().enumerate() {
+        let x = &input[j * 32..j * 32 + 32];
+        let w = error_weights(x, importance.map(|imp| &imp[j * 32..j * 32 + 32]));
+        *fit = fit_scale_min(x, &w, nmax);
+    }
+    let max_scale = fits.iter().fold(0.0f32, |max, f| max.max(f.0));
+    let max_min = fits.iter().fold(0.0f32, |max, f| max.max(f.1));
+    let d = half::f16::from_f32(max_scale / 63.0);
+    let dmin = half::f16::from_f32(max_min / 63.0);
+    block[..2].copy_from_slice(&d.to_le_bytes());
+    block[2..4].copy_from_slice(&dmin.to_le_bytes());
+    let (d, dmin) = (d.to_f32(), dmin.to_f32());
+
+    let scales = &mut block[4..16];
+    scales.fill(0);
+    let six_bits = |v: f32, unit: f32| {
+        if unit > 0.0 {
+            (v / unit).round().clamp(0.0, 63.0) as u8
+        } else {
+            0
+        }
+    };
+    let mut packed = [(0.0f32, 0.0f32); 8];
+    for (j, &(scale, min)) in fits.iter().enumerate() {
+        let (ls, lm) = (six_bits(scale, d), six_bits(min, dmin));
+        if j < 4 {
+            scales[j] = ls;
+            scales[j + 4] = lm;
+        } else {
+            scales[j + 4] = (ls & 0x0F) | ((lm & 0x0F) << 4);
+            scales[j - 4] |= (ls >> 4) << 6;
+            scales[j] |= (lm >> 4) << 6;
+        }
+        packed[j] = (d * ls as f32, dmin * lm as f32);
+    }
+    packed
+}
+
+/// The level of `x` under a packed scale and min, in `0..=nmax`.
+fn level(x: f32, (scale, min): (f32, f32), nmax: u8) -> u8 {
+    if scale == 0.0 {
+        return 0;
+    }
+    ((x + min) / scale).round().clamp(0.0, nmax as f32) as u8
+}
+
+/// Quantize 256 values to a Q4_K block (144 bytes), the inverse of
+/// [`decode_q4_k`]; `importance` weighs each value's error when fitting
+/// the scales.
+pub fn quantize_q4_k(input: &[f32], importance: Option<&[f32]>, block: &mut [u8]) {
+    debug_assert!(input.len() >= QK_K);
+    debug_assert!(block.len() >= 144);
+
+    let packed = pack_scale_mins(input, importance, 15, block);
+    for (j, q) in block[16..144].chunks_exact_mut(32).enumerate() {
+        for (l, byte) in q.iter_mut().enumerate() {
+            let low = level(input[64 * j + l], packed[2 * j], 15);
+            let high = level(input[64 * j + 32 + l], packed[2 * j + 1], 15);
+            *byte = low | (high << 4);
+        }
+    }
+}
+
+/// Quantize 256 values to a Q5_K block (176 bytes), the inverse of
+/// [`decode_q5_k`].
+pub fn quantize_q5_k(input: &[f32], importance: Option<&[f32]>, block: &mut [u8]) {
+    debug_assert!(input.len() >= QK_K);
+    debug_assert!(block.len() >= 176);
+
+    let packed = pack_scale_mins(input, importance, 31, block);
+    block[16..48].fill(0);
+    for j in 0..4 {
+        for l in 0..32 {
+            let low = level(input[64 * j + l], packed[2 * j], 31);
+            let high = level(input[64 * j + 32 + l], packed[2 * j + 1], 31);
+            block[48 + 32 * j + l] = (low & 0x0F) | ((high & 0x0F) << 4);
+            block[16 + l] |= ((low >> 4) << (2 * j)) | ((high >> 4) << (2 * j + 1));
+        }
+    }
+}
+
+/// Quantize 256 values to a Q6_K block (210 bytes), the inverse of
+/// [`decode_q6_k`]: a signed scale per 16 values, as a multiple of the f16
+/// super-block scale.
+pub fn quantize_q6_k(input: &[f32], importance: Option<&[f32]>, block: &mut [u8]) {
+    debug_assert!(input.len() >= QK_K);
+    debug_assert!(block.len() >= 210);
+
+    let fits: [f32; 16] = std::array::from_fn(|j| {
+        let x = &input[j * 16..j * 16 + 16];
+        let w = error_weights(x, importance.map(|imp| &imp[j * 16..j * 16 + 16]));
+        fit_scale(x, &w, 32)
+    });
+    let max = fits
+        .iter()
+        .copied()
+        .fold(0.0f32, |max, v| if v.abs() > max.abs() { v } else { max });
+    let d = half::f16::from_f32(if max != 0.0 { max / -128.0 } else { 0.0 });
+    block[208..210].copy_from_slice(&d.to_le_bytes());
+    let d = d.to_f32();
+
+    let mut levels = [0u8; QK_K];
+    for (j, &scale) in fits.iter().enumerate() {
+        let sc = if d != 0.0 {
+            (scale / d).round().clamp(-128.0, 127.0) as i8
+        } else {
+            0
+        };
+        block[192 + j] = sc as u8;
+        let dl = d * sc as f32;
+        for (i, l) in levels[j * 16..j * 16 + 16].iter_mut().enumerate() {
+            let q = if dl != 0.0 {
+                (input[j * 16 + i] / dl).round().clamp(-32.0, 31.0) as i8
+            } else {
+                0
+            };
+            *l = (q + 32) as u8;
+        }
+    }
+
+    for half in 0..2 {
+        let l6 = &levels[half * 128..];
+        for l in 0..32 {
+            block[half * 64 + l] = (l6[l] & 0x0F) | ((l6[l + 64] & 0x0F) << 4);
+            block[half * 64 + l + 32] = (l6[l + 32] & 0x0F) | ((l6[l + 96] & 0x0F) << 4);
+            block[128 + half * 32 + l] = (l6[l] >> 4)
+                | ((l6[l + 32] >> 4) << 2)
+                | ((l6[l + 64] >> 4) << 4)
+                | ((l6[l + 96] >> 4) << 6);
+        }
+    }
+}
+
+/// Quantize a row of f32 values to `ggml_type`, appending to `output`.
+pub fn quantize_row(
+    input: &[f32],
+    output: &mut Vec<u8>,
+    ggml_type: crate::gguf::GgmlType,
+) -> Result<()> {
+    quantize_row_weighted(input, None, output, ggml_type)
+}
+
+/// [`quantize_row`], with the k-quants fitting their scales to the
+/// per-column `importance` of an importance matrix if there is one.
+pub fn quantize_row_weighted(
+    input: &[f32],
+    importance: Option<&[f32]>,
+    output: &mut Vec<u8>,
+    ggml_type: crate::gguf::GgmlType,
+) -> Result<()> {
+    use crate::gguf::GgmlType;
+
+    if importance.is_some_and(|imp| imp.len() != input.len()) {
+        return Err(BizClawError::Brain(format!(
+            "Importance of {} columns doesn't fit a row of {}",
+            importance.map_or(0, <[f32]>::len),
+            input.len()
+        )));
+    }
+    match ggml_type {
+        GgmlType::F32 => input
+            .iter()
+            .for_each(|v| output.extend_from_slice(&v.to_le_bytes())),
+        GgmlType::F16 => input
+            .iter()
+            .for_each(|v| output.extend_from_slice(&half::f16::from_f32(*v).to_le_bytes())),
+        GgmlType::Q4_0 | GgmlType::Q8_0 if input.len().is_multiple_of(32) => {
+            let type_size = ggml_type.type_size();
+            for values in input.chunks_exact(32) {
+                let start = output.len();
+                output.resize(start + type_size, 0);
+                if ggml_type == GgmlType::Q4_0 {
+                    quantize_q4_0(values, &mut output[start..]);
+                } else {
+                    quantize_q8_0(values, &mut output[start..]);
+                }
+            }
+        }
+        GgmlType::Q4K | GgmlType::Q5K | GgmlType::Q6K if input.len().is_multiple_of(QK_K) => {
+            let type_size = ggml_type.type_size();
+            for (b, values) in input.chunks_exact(QK_K).enumerate() {
+                let importance = importance.map(|imp| &imp[b * QK_K..(b + 1) * QK_K]);
+                let start = output.len();
+                output.resize(start + type_size, 0);
+                let block = &mut output[start..];
+                match ggml_type {
+                    GgmlType::Q4K => quantize_q4_k(values, importance, block),
+                    GgmlType::Q5K => quantize_q5_k(values, importance, block),
+                    _ => quantize_q6_k(values, importance, block),
+                }
+            }
+        }
+        _ => {
+            return Err(BizClawError::Brain(format!(
+                "Can't quantize a row of {} values to {ggml_type:?}",
+                input.len()
+            )));
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -116,4 +807,241 @@ mod tests {
         assert!((output[0] - 1.0).abs() < 0.01);
         assert!((output[1] - 2.0).abs() < 0.01);
     }
+
+    fn dequantize(block: &[u8], ggml_type: GgmlType) -> Vec<f32> {
+        let mut output = vec![0.0f32; QK_K];
+        dequantize_row(block, &mut output, QK_K, ggml_type).unwrap();
+        output
+    }
+
+    fn put_f16(block: &mut [u8], offset: usize, value: f32) {
+        block[offset..offset + 2].copy_from_slice(&half::f16::from_f32(value).to_le_bytes());
+    }
+
+    #[test]
+    fn test_dequantize_k_quants() {
+        // Q2_K: sub-block s scales by s with min 1; the 2-bit values in
+        // each byte are 0, 1, 2, 3 from the lowest bits up.
+        let mut block = vec![0u8; 84];
+        for s in 0..16 {
+            block[s] = s as u8 | (1 << 4);
+        }
+        block[16..80].fill(0b11_10_01_00);
+        put_f16(&mut block, 80, 1.0);
+        put_f16(&mut block, 82, 1.0);
+        for (i, v) in dequantize(&block, GgmlType::Q2K).into_iter().enumerate() {
+            let s = i / 16;
+            assert_eq!(v, (s * (s % 8 / 2)) as f32 - 1.0, "Q2_K {i}");
+        }
+
+        // Q3_K: every scale 33 - 32 = 1; high bits set for the first half
+        // only, so the second half is 4 lower.
+        let mut block = vec![0u8; 110];
+        block[..32].fill(0x0F);
+        block[32..96].fill(0b11_10_01_00);
+        block[96..104].fill(0x11);
+        block[104..108].fill(0b10_10_10_10);
+        put_f16(&mut block, 108, 2.0);
+        for (i, v) in dequantize(&block, GgmlType::Q3K).into_iter().enumerate() {
+            let high = if i < 128 { 0 } else { 4 };
+            assert_eq!(v, 2.0 * ((i / 16 % 8 / 2) as f32 - high as f32), "Q3_K {i}");
+        }
+
+        // Q4_K: sub-blocks 0-3 scale by 1-4 with min 1, 4-7 by 5 with min
+        // 2; low nibbles 1, high 2.
+        let mut block = vec![0u8; 144];
+        put_f16(&mut block, 0, 1.0);
+        put_f16(&mut block, 2, 0.5);
+        for j in 0..4 {
+            block[4 + j] = j as u8 + 1;
+            block[8 + j] = 1;
+            block[12 + j] = 5 | (2 << 4);
+        }
+        block[16..].fill(0x21);
+        for (i, v) in dequantize(&block, GgmlType::Q4K).into_iter().enumerate() {
+            let s = i / 32;
+            let (sc, m) = if s < 4 { (s + 1, 1) } else { (5, 2) };
+            let q = if s % 2 == 0 { 1 } else { 2 };
+            assert_eq!(v, (sc * q) as f32 - 0.5 * m as f32, "Q4_K {i}");
+        }
+
+        // Q5_K: every scale 1, no mins; the fifth bit set for the low
+        // nibbles only.
+        let mut block = vec![0u8; 176];
+        put_f16(&mut block, 0, 1.0);
+        block[4..8].fill(1);
+        block[12..16].fill(1);
+        block[16..48].fill(0b01_01_01_01);
+        block[48..].fill(0x21);
+        for (i, v) in dequantize(&block, GgmlType::Q5K).into_iter().enumerate() {
+            let expected = if i / 32 % 2 == 0 { 17.0 } else { 2.0 };
+            assert_eq!(v, expected, "Q5_K {i}");
+        }
+
+        // Q6_K: sub-block k scales by k - 8; the four 6-bit values of each
+        // position are 3, 19, 37 and 53, less 32.
+        let mut block = vec![0u8; 210];
+        block[..128].fill(0x53);
+        block[128..192].fill(0b11_10_01_00);
+        for k in 0..16 {
+            block[192 + k] = (k as i8 - 8) as u8;
+        }
+        put_f16(&mut block, 208, 0.5);
+        for (i, v) in dequantize(&block, GgmlType::Q6K).into_iter().enumerate() {
+            let q = [-29.0, -13.0, 5.0, 21.0][i % 128 / 32];
+            assert_eq!(v, 0.5 * ((i / 16) as f32 - 8.0) * q, "Q6_K {i}");
+        }
+    }
+
+    #[test]
+    fn test_dequantize_iq4() {
+        // IQ4_NL: index j low and 15 - j high.
+        let mut block = vec![0u8; 18];
+        put_f16(&mut block, 0, 0.5);
+        for j in 0..16 {
+            block[2 + j] = j as u8 | ((15 - j as u8) << 4);
+        }
+        let mut output = vec![0.0f32; 32];
+        dequantize_row(&block, &mut output, 32, GgmlType::IQ4NL).unwrap();
+        for j in 0..16 {
+            assert_eq!(output[j], 0.5 * IQ4_NL_VALUES[j] as f32);
+            assert_eq!(output[j + 16], 0.5 * IQ4_NL_VALUES[15 - j] as f32);
+        }
+
+        // IQ4_XS: sub-block ib's 6-bit scale is 33 + ib, so it scales by
+        // ib + 1; index j in both nibbles of byte j.
+        let mut block = vec![0u8; 136];
+        put_f16(&mut block, 0, 1.0);
+        block[2..4].copy_from_slice(&0xAAAAu16.to_le_bytes());
+        for k in 0..4 {
+            block[4 + k] = (1 + 2 * k as u8) | ((2 + 2 * k as u8) << 4);
+        }
+        for (j, byte) in block[8..].iter_mut().enumerate() {
+            *byte = (j % 16) as u8 * 0x11;
+        }
+        for (i, v) in dequantize(&block, GgmlType::IQ4XS).into_iter().enumerate() {
+            let (ib, j) = (i / 32, i % 16);
+            assert_eq!(v, (ib + 1) as f32 * IQ4_NL_VALUES[j] as f32, "IQ4_XS {i}");
+        }
+
+        // The lattice-grid types are refused rather than read as zeros.
+        let block = vec![0u8; 66];
+        let mut output = vec![0.0f32; QK_K];
+        assert!(dequantize_row(&block, &mut output, QK_K, GgmlType::IQ2XXS).is_err());
+        let tensor = |ggml_type| crate::gguf::TensorInfo {
+            name: "blk.0.ffn_down.weight".into(),
+            n_dims: 2,
+            dims: vec![256, 4],
+            ggml_type,
+            offset: 0,
+        };
+        assert!(check_readable(&[tensor(GgmlType::IQ4XS), tensor(GgmlType::Q6K)]).is_ok());
+        let err = check_readable(&[tensor(GgmlType::Q4K), tensor(GgmlType::IQ3S)]).unwrap_err();
+        assert!(err.to_string().contains("IQ3S"), "{err}");
+    }
+
+    #[test]
+    fn test_dot_row() {
+        // Two blocks of arbitrary bits with sane super-block scales: the
+        // dot product matches dequantizing first.
+        let bytes = |n: usize| -> Vec<u8> {
+            (0..n)
+                .map(|i| (i.wrapping_mul(2_654_435_761) >> 7) as u8)
+                .collect()
+        };
+        let x: Vec<f32> = (0..2 * QK_K).map(|i| (i % 7) as f32 - 3.0).collect();
+        for (ggml_type, scales) in [
+            (GgmlType::Q2K, &[80, 82][..]),
+            (GgmlType::Q3K, &[108]),
+            (GgmlType::Q4K, &[0, 2]),
+            (GgmlType::Q5K, &[0, 2]),
+            (GgmlType::Q6K, &[208]),
+            (GgmlType::IQ4XS, &[0]),
+            (GgmlType::IQ4NL, &[0]),
+            (GgmlType::Q8_0, &[0]),
+        ] {
+            let type_size = ggml_type.type_size();
+            let mut data = bytes(2 * QK_K / ggml_type.block_size() * type_size);
+            for block in data.chunks_exact_mut(type_size) {
+                for &offset in scales {
+                    put_f16(block, offset, 0.01);
+                }
+            }
+            let mut values = vec![0.0f32; 2 * QK_K];
+            dequantize_row(&data, &mut values, 2 * QK_K, ggml_type).unwrap();
+            let expected: f32 = values.iter().zip(&x).map(|(v, x)| v * x).sum();
+            let dot = dot_row(&data, &x, ggml_type).unwrap();
+            assert!(
+                (dot - expected).abs() <= 1e-3 * expected.abs().max(1.0),
+                "{ggml_type:?}: {dot} != {expected}"
+            );
+            assert!(dot_row(&data[..type_size], &x, ggml_type).is_err());
+        }
+    }
+
+    #[test]
+    fn test_quantize_round_trip() {
+        let input: Vec<f32> = (0..64).map(|i| (i as f32 - 20.0) / 10.0).collect();
+        for (ggml_type, tolerance) in [
+            (crate::gguf::GgmlType::Q8_0, 0.025),
+            (crate::gguf::GgmlType::Q4_0, 0.3),
+        ] {
+            let mut data = Vec::new();
+            quantize_row(&input, &mut data, ggml_type).unwrap();
+            assert_eq!(data.len(), 2 * ggml_type.type_size());
+            let mut output = vec![0.0f32; 64];
+            dequantize_row(&data, &mut output, 64, ggml_type).unwrap();
+            for (a, b) in input.iter().zip(&output) {
+                assert!((a - b).abs() <= tolerance, "{ggml_type:?}: {a} vs {b}");
+            }
+        }
+        assert!(quantize_row(&input[..40], &mut Vec::new(), crate::gguf::GgmlType::Q4_0).is_err());
+    }
+
+    #[test]
+    fn test_quantize_k_quants() {
+        // Two super-blocks of uneven values, one sub-block of them large:
+        // each decodes close, and more bits mean less error.
+        let input: Vec<f32> = (0..2 * QK_K)
+            .map(|i| {
+                let v = ((i * 7919) % 211) as f32 / 211.0 - 0.5;
+                if i / 32 == 3 { v * 4.0 } else { v }
+            })
+            .collect();
+        let rms =
+            |ggml_type: GgmlType, importance: Option<&[f32]>, range: std::ops::Range<usize>| {
+                let mut data = Vec::new();
+                quantize_row_weighted(&input, importance, &mut data, ggml_type).unwrap();
+                assert_eq!(data.len(), 2 * ggml_type.type_size());
+                let mut output = vec![0.0f32; 2 * QK_K];
+                dequantize_row(&data, &mut output, 2 * QK_K, ggml_type).unwrap();
+                let n = range.len() as f32;
+                (range.map(|i| (input[i] - output[i]).powi(2)).sum::<f32>() / n).sqrt()
+            };
+        let all = 0..2 * QK_K;
+        let errors = [GgmlType::Q4K, GgmlType::Q5K, GgmlType::Q6K]
+            .map(|ggml_type| rms(ggml_type, None, all.clone()));
+        assert!(errors[0] < 0.04, "Q4_K rms {}", errors[0]);
+        assert!(errors[0] > errors[1] && errors[1] > errors[2], "{errors:?}");
+
+        // Columns marked important come out closer than unweighted.
+        let mut importance = vec![1.0f32; 2 * QK_K];
+        importance[320..328].fill(1000.0);
+        for ggml_type in [GgmlType::Q4K, GgmlType::Q5K, GgmlType::Q6K] {
+            let plain = rms(ggml_type, None, 320..328);
+            let weighted = rms(ggml_type, Some(&importance), 320..328);
+            assert!(weighted < plain, "{ggml_type:?}: {weighted} >= {plain}");
+        }
+
+        assert!(quantize_row(&input[..QK_K + 32], &mut Vec::new(), GgmlType::Q4K).is_err());
+        assert!(
+            quantize_row_weighted(
+                &input,
+                Some(&importance[..32]),
+                &mut Vec::new(),
+                GgmlType::Q6K
+            )
+            .is_err()
+        );
+    }
 }
@@ -0,0 +1,537 @@
+//! Requantization — rewrite a GGUF model's weights in another format.
+//!
+//! Matrices (2-D tensors whose rows split into 32-value blocks) are
+//! converted row by row; norms, biases and tensors in a format the engine
+//! can't read are copied as they are. The targets are F16, Q8_0, Q4_0 and
+//! the k-quants Q4_K, Q5_K and Q6_K, with llama.cpp's `_m` mixes that give
+//! the most sensitive matrices Q6_K. K-quant rows must split into
+//! 256-value super-blocks; matrices whose rows don't are written as Q8_0.
+//!
+//! An importance matrix (llama.cpp's `imatrix` output, legacy `.dat` or
+//! GGUF) weighs each column's error when the k-quants fit their scales,
+//! keeping the inputs the model leans on most closest.
+
+use crate::gguf::{self, GgmlType, GgufValue, TensorInfo};
+use crate::mmap::MmapModel;
+use crate::quant;
+use bizclaw_core::error::{BizClawError, Result};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+/// What `bizclaw quantize --type` names: the format matrices are written
+/// in, and whether some are given more bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    pub ggml_type: GgmlType,
+    /// llama.cpp's `_m` mix: Q6_K for the output matrix and for `attn_v`
+    /// and `ffn_down` in the first and last eighth of the layers and every
+    /// third between.
+    pub mixed: bool,
+}
+
+impl Target {
+    /// The name `bizclaw quantize --type` takes, e.g. `Q4_K_M`.
+    pub fn name(&self) -> &'static str {
+        match (self.ggml_type, self.mixed) {
+            (GgmlType::F16, _) => "F16",
+            (GgmlType::Q8_0, _) => "Q8_0",
+            (GgmlType::Q4_0, _) => "Q4_0",
+            (GgmlType::Q4K, false) => "Q4_K_S",
+            (GgmlType::Q4K, true) => "Q4_K_M",
+            (GgmlType::Q5K, false) => "Q5_K_S",
+            (GgmlType::Q5K, true) => "Q5_K_M",
+            (GgmlType::Q6K, _) => "Q6_K",
+            _ => "F32",
+        }
+    }
+
+    /// Whether an importance matrix changes the output: only the
+    /// k-quants search for their scales.
+    pub fn uses_importance(&self) -> bool {
+        matches!(
+            self.ggml_type,
+            GgmlType::Q4K | GgmlType::Q5K | GgmlType::Q6K
+        )
+    }
+
+    /// llama.cpp's `general.file_type` for the model written.
+    fn file_type(&self) -> u32 {
+        match (self.ggml_type, self.mixed) {
+            (GgmlType::F16, _) => 1,
+            (GgmlType::Q4_0, _) => 2,
+            (GgmlType::Q8_0, _) => 7,
+            (GgmlType::Q4K, false) => 14,
+            (GgmlType::Q4K, true) => 15,
+            (GgmlType::Q5K, false) => 16,
+            (GgmlType::Q5K, true) => 17,
+            (GgmlType::Q6K, _) => 18,
+            _ => 0,
+        }
+    }
+
+    /// The format `tensor` is written in, out of a model of `n_layers`.
+    fn tensor_type(&self, tensor: &TensorInfo, n_layers: usize) -> GgmlType {
+        if !convertible(tensor) {
+            return tensor.ggml_type;
+        }
+        let mut ggml_type = self.ggml_type;
+        if self.mixed && more_bits(&tensor.name, n_layers) {
+            ggml_type = GgmlType::Q6K;
+        }
+        if tensor.dims[0].is_multiple_of(ggml_type.block_size() as u64) {
+            ggml_type
+        } else {
+            GgmlType::Q8_0
+        }
+    }
+}
+
+/// The target named `name`, as `bizclaw quantize --type` takes it.
+pub fn parse_type(name: &str) -> Result<Target> {
+    let (ggml_type, mixed) = match name.to_ascii_lowercase().as_str() {
+        "f16" => (GgmlType::F16, false),
+        "q8_0" => (GgmlType::Q8_0, false),
+        "q4_0" => (GgmlType::Q4_0, false),
+        "q4_k" | "q4_k_s" => (GgmlType::Q4K, false),
+        "q4_k_m" => (GgmlType::Q4K, true),
+        "q5_k" | "q5_k_s" => (GgmlType::Q5K, false),
+        "q5_k_m" => (GgmlType::Q5K, true),
+        "q6_k" => (GgmlType::Q6K, false),
+        other => {
+            return Err(BizClawError::Brain(format!(
+                "Can't quantize to '{other}': choose f16, q8_0, q4_0, q4_k_s, q4_k_m, \
+                 q5_k_s, q5_k_m or q6_k"
+            )));
+        }
+    };
+    Ok(Target { ggml_type, mixed })
+}
+
+/// Whether an `_m` mix gives the tensor named `name` Q6_K.
+fn more_bits(name: &str, n_layers: usize) -> bool {
+    if name == "output.weight" {
+        return true;
+    }
+    let Some(layer) = layer_of(name) else {
+        return false;
+    };
+    let sensitive = name.ends_with(".attn_v.weight") || name.ends_with(".ffn_down.weight");
+    let eighth = n_layers / 8;
+    sensitive
+        && match layer.checked_sub(eighth) {
+            None => true,
+            Some(_) if layer >= 7 * n_layers / 8 => true,
+            Some(past) => past % 3 == 2,
+        }
+}
+
+/// The layer of a `blk.N.` tensor.
+fn layer_of(name: &str) -> Option<usize> {
+    name.strip_prefix("blk.")?.split('.').next()?.parse().ok()
+}
+
+/// Per-column importance of each matrix, from llama.cpp's `imatrix`: the
+/// mean squared activation each column of the weights was multiplied by.
+#[derive(Debug, Clone, Default)]
+pub struct ImportanceMatrix {
+    columns: HashMap<String, Vec<f32>>,
+}
+
+impl ImportanceMatrix {
+    /// Read an importance matrix in llama.cpp's GGUF or legacy `.dat`
+    /// format.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        if bytes.starts_with(b"GGUF") {
+            Self::from_gguf(MmapModel::from_bytes(bytes)?)
+        } else {
+            Self::from_dat(&bytes)
+        }
+    }
+
+    /// GGUF: a `<tensor>.in_sum2` of summed squares and a `<tensor>.counts`
+    /// of how many went in, for each matrix. Experts' stacked entries are
+    /// skipped.
+    fn from_gguf(model: MmapModel) -> Result<Self> {
+        let read = |name: &str| -> Result<Option<Vec<f32>>> {
+            let Some(i) = model.gguf.tensors.iter().position(|t| t.name == name) else {
+                return Ok(None);
+            };
+            let tensor = &model.gguf.tensors[i];
+            let n = tensor.n_elements() as usize;
+            let mut values = vec![0.0f32; n];
+            quant::dequantize_row(model.tensor_data(i)?, &mut values, n, tensor.ggml_type)?;
+            Ok(Some(values))
+        };
+        let mut columns = HashMap::new();
+        for tensor in &model.gguf.tensors {
+            let Some(name) = tensor.name.strip_suffix(".in_sum2") else {
+                continue;
+            };
+            let (Some(sums), Some(counts)) =
+                (read(&tensor.name)?, read(&format!("{name}.counts"))?)
+            else {
+                continue;
+            };
+            if let [count] = counts[..]
+                && count > 0.0
+            {
+                columns.insert(name.to_string(), sums.iter().map(|v| v / count).collect());
+            }
+        }
+        Ok(Self { columns })
+    }
+
+    /// Legacy `.dat`: an entry count, then per entry a length-prefixed
+    /// name, the number of calls summed and the values, all little-endian.
+    fn from_dat(mut bytes: &[u8]) -> Result<Self> {
+        let entries = read_len(&mut bytes)?;
+        let mut columns = HashMap::with_capacity(entries.min(4096));
+        for _ in 0..entries {
+            let len = read_len(&mut bytes)?;
+            let name = String::from_utf8_lossy(read_bytes(&mut bytes, len)?).into_owned();
+            let calls = read_len(&mut bytes)?.max(1) as f32;
+            let n = read_len(&mut bytes)?;
+            let values = read_bytes(&mut bytes, n.saturating_mul(4))?
+                .chunks_exact(4)
+                .map(|v| f32::from_le_bytes([v[0], v[1], v[2], v[3]]) / calls)
+                .collect();
+            columns.insert(name, values);
+        }
+        Ok(Self { columns })
+    }
+
+    /// Matrices with an importance vector.
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// The importance of each of a matrix's `cols` columns, if it was
+    /// measured for that shape.
+    pub fn get(&self, name: &str, cols: usize) -> Option<&[f32]> {
+        self.columns
+            .get(name)
+            .map(Vec::as_slice)
+            .filter(|v| v.len() == cols)
+    }
+}
+
+fn read_bytes<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    let (chunk, rest) = bytes
+        .split_at_checked(n)
+        .ok_or_else(|| BizClawError::Brain("Importance matrix file is truncated".into()))?;
+    *bytes = rest;
+    Ok(chunk)
+}
+
+fn read_len(bytes: &mut &[u8]) -> Result<usize> {
+    let v = read_bytes(bytes, 4)?;
+    let v = i32::from_le_bytes([v[0], v[1], v[2], v[3]]);
+    usize::try_from(v)
+        .map_err(|_| BizClawError::Brain(format!("Importance matrix has a negative count: {v}")))
+}
+
+/// One tensor written, for progress output.
+#[derive(Debug, Clone)]
+pub struct TensorProgress {
+    /// 1-based.
+    pub index: usize,
+    pub count: usize,
+    pub name: String,
+    pub dims: Vec<u64>,
+    pub from: GgmlType,
+    pub to: GgmlType,
+    pub bytes: u64,
+}
+
+/// What a requantization did.
+#[derive(Debug, Clone)]
+pub struct QuantizeSummary {
+    pub tensors: usize,
+    /// Tensors converted; the rest were copied.
+    pub converted: usize,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+}
+
+/// Whether the tensor is a matrix in a format the engine reads.
+fn convertible(tensor: &TensorInfo) -> bool {
+    tensor.dims.len() >= 2
+        && tensor.dims[0].is_multiple_of(tensor.ggml_type.block_size().max(32) as u64)
+        && quant::can_dequantize(tensor.ggml_type)
+}
+
+/// Write `input`'s weights to `output` in `target`, fitting k-quant
+/// scales to `imatrix` where it covers a matrix, calling `on_tensor` after
+/// each tensor.
+pub fn quantize_file(
+    input: &Path,
+    output: &Path,
+    target: Target,
+    imatrix: Option<&ImportanceMatrix>,
+    mut on_tensor: impl FnMut(&TensorProgress),
+) -> Result<QuantizeSummary> {
+    let model = MmapModel::load(input)?;
+    let source = &model.gguf;
+    let alignment = source.alignment;
+    let n_layers = source
+        .tensors
+        .iter()
+        .filter_map(|t| layer_of(&t.name))
+        .max()
+        .map_or(0, |last| last + 1);
+
+    let mut tensors = Vec::with_capacity(source.tensors.len());
+    let mut offset = 0u64;
+    for tensor in &source.tensors {
+        let mut tensor = tensor.clone();
+        tensor.ggml_type = target.tensor_type(&tensor, n_layers);
+        tensor.offset = offset;
+        offset = (offset + tensor.size_bytes()).div_ceil(alignment) * alignment;
+        tensors.push(tensor);
+    }
+
+    let mut metadata: Vec<(String, GgufValue)> = source
+        .metadata
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    metadata.retain(|(key, _)| key != "general.file_type");
+    metadata.push((
+        "general.file_type".into(),
+        GgufValue::U32(target.file_type()),
+    ));
+    metadata.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let file = std::fs::File::create(output)?;
+    let mut writer = std::io::BufWriter::new(file);
+    let mut written = gguf::write_header(&mut writer, &metadata, &tensors, alignment)?;
+    let data_start = written;
+
+    let mut converted = 0;
+    let mut row = Vec::new();
+    let mut out = Vec::new();
+    for (i, (from, to)) in source.tensors.iter().zip(&tensors).enumerate() {
+        let data = model.tensor_data(i)?;
+        let padding = data_start + to.offset - written;
+        writer.write_all(&vec![0u8; padding as usize])?;
+        written += padding;
+
+        if from.ggml_type == to.ggml_type {
+            writer.write_all(data)?;
+        } else {
+            let cols = from.dims[0] as usize;
+            let rows = (from.n_elements() / from.dims[0]) as usize;
+            let row_bytes = cols / from.ggml_type.block_size() * from.ggml_type.type_size();
+            let importance = imatrix.and_then(|m| m.get(&from.name, cols));
+            row.resize(cols, 0.0);
+            for r in 0..rows {
+                quant::dequantize_row(&data[r * row_bytes..], &mut row, cols, from.ggml_type)?;
+                out.clear();
+                quant::quantize_row_weighted(&row, importance, &mut out, to.ggml_type)?;
+                writer.write_all(&out)?;
+            }
+            converted += 1;
+        }
+        written += to.size_bytes();
+
+        on_tensor(&TensorProgress {
+            index: i + 1,
+            count: tensors.len(),
+            name: to.name.clone(),
+            dims: to.dims.clone(),
+            from: from.ggml_type,
+            to: to.ggml_type,
+            bytes: to.size_bytes(),
+        });
+    }
+    writer.flush()?;
+
+    Ok(QuantizeSummary {
+        tensors: tensors.len(),
+        converted,
+        input_bytes: model.file_size() as u64,
+        output_bytes: written,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantize_file() {
+        let dir = std::env::temp_dir().join(format!("bizclaw-quantize-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.gguf");
+        let output = dir.join("out.gguf");
+
+        let weights: Vec<f32> = (0..64 * 2).map(|i| (i % 17) as f32 / 8.0 - 1.0).collect();
+        let norm = [1.0f32; 64];
+        let tensors = [
+            TensorInfo {
+                name: "blk.0.attn_q.weight".into(),
+                n_dims: 2,
+                dims: vec![64, 2],
+                ggml_type: GgmlType::F32,
+                offset: 0,
+            },
+            TensorInfo {
+                name: "blk.0.attn_norm.weight".into(),
+                n_dims: 1,
+                dims: vec![64],
+                ggml_type: GgmlType::F32,
+                offset: 512,
+            },
+        ];
+        let metadata = [(
+            "general.architecture".to_string(),
+            GgufValue::String("llama".into()),
+        )];
+        let mut file = Vec::new();
+        gguf::write_header(&mut file, &metadata, &tensors, 32).unwrap();
+        for v in weights.iter().chain(&norm) {
+            file.extend_from_slice(&v.to_le_bytes());
+        }
+        std::fs::write(&input, file).unwrap();
+
+        let mut names = Vec::new();
+        let summary = quantize_file(&input, &output, parse_type("q8_0").unwrap(), None, |t| {
+            names.push((t.name.clone(), t.to))
+        })
+        .unwrap();
+        assert_eq!(summary.converted, 1);
+        assert_eq!(
+            names,
+            [
+                ("blk.0.attn_q.weight".to_string(), GgmlType::Q8_0),
+                ("blk.0.attn_norm.weight".to_string(), GgmlType::F32),
+            ]
+        );
+
+        let model = MmapModel::load(&output).unwrap();
+        assert_eq!(model.gguf.get_u32("general.file_type"), Some(7));
+        assert_eq!(model.gguf.architecture(), Some("llama"));
+        let mut row = vec![0.0f32; 128];
+        quant::dequantize_row(model.tensor_data(0).unwrap(), &mut row, 128, GgmlType::Q8_0)
+            .unwrap();
+        assert!(weights.iter().zip(&row).all(|(a, b)| (a - b).abs() < 0.01));
+        assert_eq!(model.tensor_data(1).unwrap()[..4], 1.0f32.to_le_bytes());
+        assert_eq!(summary.output_bytes, model.file_size() as u64);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    /// A GGUF of F32 tensors, each `(name, dims)` holding a ramp of values.
+    fn write_model(path: &Path, shapes: &[(&str, Vec<u64>)]) {
+        let mut offset = 0;
+        let tensors: Vec<TensorInfo> = shapes
+            .iter()
+            .map(|(name, dims)| {
+                let tensor = TensorInfo {
+                    name: name.to_string(),
+                    n_dims: dims.len() as u32,
+                    dims: dims.clone(),
+                    ggml_type: GgmlType::F32,
+                    offset,
+                };
+                offset += tensor.size_bytes().div_ceil(32) * 32;
+                tensor
+            })
+            .collect();
+        let mut file = Vec::new();
+        gguf::write_header(&mut file, &[], &tensors, 32).unwrap();
+        let data_start = file.len();
+        for tensor in &tensors {
+            file.resize(data_start + tensor.offset as usize, 0);
+            for i in 0..tensor.n_elements() {
+                let v = (i * 7919 % 211) as f32 / 211.0 - 0.5;
+                file.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        std::fs::write(path, file).unwrap();
+    }
+
+    #[test]
+    fn test_quantize_k_mix() {
+        let dir = std::env::temp_dir().join(format!("bizclaw-quantize-k-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.gguf");
+        let output = dir.join("out.gguf");
+        write_model(
+            &input,
+            &[
+                ("blk.0.attn_q.weight", vec![256, 2]),
+                ("blk.0.attn_v.weight", vec![256, 2]),
+                ("blk.0.ffn_up.weight", vec![64, 2]),
+                ("output.weight", vec![256, 2]),
+            ],
+        );
+
+        // A legacy imatrix for attn_q: sums over 2 calls.
+        let mut dat = Vec::new();
+        dat.extend_from_slice(&1i32.to_le_bytes());
+        let name = b"blk.0.attn_q.weight";
+        dat.extend_from_slice(&(name.len() as i32).to_le_bytes());
+        dat.extend_from_slice(name);
+        dat.extend_from_slice(&2i32.to_le_bytes());
+        dat.extend_from_slice(&256i32.to_le_bytes());
+        for i in 0..256 {
+            dat.extend_from_slice(&(2.0 * (1 + i % 4) as f32).to_le_bytes());
+        }
+        let imatrix_path = dir.join("imatrix.dat");
+        std::fs::write(&imatrix_path, &dat).unwrap();
+        let imatrix = ImportanceMatrix::load(&imatrix_path).unwrap();
+        assert_eq!(imatrix.len(), 1);
+        assert_eq!(
+            imatrix.get("blk.0.attn_q.weight", 256).unwrap()[..2],
+            [1.0, 2.0]
+        );
+        assert!(imatrix.get("blk.0.attn_q.weight", 512).is_none());
+        std::fs::write(&imatrix_path, &dat[..100]).unwrap();
+        assert!(ImportanceMatrix::load(&imatrix_path).is_err());
+
+        let target = parse_type("Q4_K_M").unwrap();
+        assert_eq!(target.name(), "Q4_K_M");
+        let mut types = Vec::new();
+        quantize_file(&input, &output, target, Some(&imatrix), |t| {
+            types.push(t.to)
+        })
+        .unwrap();
+        // The single layer is in the last eighth, so attn_v gets Q6_K;
+        // 64-value rows can't be k-quants.
+        assert_eq!(
+            types,
+            [GgmlType::Q4K, GgmlType::Q6K, GgmlType::Q8_0, GgmlType::Q6K]
+        );
+
+        let model = MmapModel::load(&output).unwrap();
+        assert_eq!(model.gguf.get_u32("general.file_type"), Some(15));
+        let mut row = vec![0.0f32; 512];
+        quant::dequantize_row(model.tensor_data(0).unwrap(), &mut row, 512, GgmlType::Q4K).unwrap();
+        let expected = (0..512).map(|i| (i * 7919 % 211) as f32 / 211.0 - 0.5);
+        assert!(expected.zip(&row).all(|(a, b)| (a - b).abs() < 0.05));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_more_bits() {
+        // 32 layers: the first and last 4, and every third between.
+        let layers: Vec<usize> = (0..32)
+            .filter(|i| more_bits(&format!("blk.{i}.ffn_down.weight"), 32))
+            .collect();
+        assert_eq!(
+            layers,
+            [0, 1, 2, 3, 6, 9, 12, 15, 18, 21, 24, 27, 28, 29, 30, 31]
+        );
+        assert!(!more_bits("blk.0.attn_q.weight", 32));
+        assert!(more_bits("output.weight", 32));
+        assert!(parse_type("q3_k_m").is_err());
+    }
+}
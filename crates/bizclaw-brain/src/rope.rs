@@ -1,23 +1,262 @@
 //! Rotary Position Embeddings (RoPE).
 //!
 //! Applied to query and key vectors to encode position information.
+//! [`RopeScaling`] stretches positions for a context longer than the one
+//! the model was trained on: linear interpolation, NTK-aware (a larger
+//! base), YaRN (interpolating only the low frequencies) or Llama 3.1's
+//! variant of the same idea. Each is still a
+//! rotation by position times a per-dimension frequency, so [`Rope::shift`]
+//! works the same under all of them.
+
+use serde::{Deserialize, Serialize};
+
+/// How [`RopeScaling`] stretches positions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RopeScalingType {
+    #[default]
+    None,
+    /// Divide every position by the factor (position interpolation).
+    Linear,
+    /// Raise the base so the lowest frequency is interpolated by the factor
+    /// and the highest barely changes.
+    Ntk,
+    /// Interpolate the frequencies that turn less than once over the
+    /// trained context, keep those turning many times, ramp in between,
+    /// and sharpen attention slightly to make up for the interpolation.
+    Yarn,
+    /// Llama 3.1's: keep the frequencies whose wavelength is under the
+    /// trained context divided by the high-frequency factor, interpolate
+    /// those over it divided by the low-frequency factor, and blend
+    /// linearly in between. No change to the rotation's length.
+    Llama3,
+}
+
+impl RopeScalingType {
+    /// GGUF `rope.scaling.type` names, `"none"`, `"linear"`, `"yarn"` and
+    /// `"llama3"`, and `"ntk"`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" => Some(Self::None),
+            "linear" => Some(Self::Linear),
+            "ntk" => Some(Self::Ntk),
+            "yarn" => Some(Self::Yarn),
+            "llama3" => Some(Self::Llama3),
+            _ => None,
+        }
+    }
+}
+
+/// RoPE scaling, from the model's GGUF `rope.scaling.*` metadata or
+/// [`BrainConfig::rope_scaling`](crate::BrainConfig::rope_scaling).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RopeScaling {
+    #[serde(rename = "type")]
+    pub kind: RopeScalingType,
+    /// How many times the trained context the model runs at: 2 for 8k
+    /// positions on a model trained on 4k.
+    pub factor: f32,
+    /// Context the model was trained on, which the YaRN and Llama 3 ramps
+    /// depend on; 0 for the context length divided by the factor.
+    #[serde(default)]
+    pub original_context: u32,
+    /// Llama 3's ramp: frequencies turning fewer than this many times over
+    /// the trained context are interpolated...
+    #[serde(default = "default_low_freq_factor")]
+    pub low_freq_factor: f32,
+    /// ...and those turning more than this many times are kept.
+    #[serde(default = "default_high_freq_factor")]
+    pub high_freq_factor: f32,
+}
+
+fn default_low_freq_factor() -> f32 {
+    1.0
+}
+
+fn default_high_freq_factor() -> f32 {
+    4.0
+}
+
+impl Default for RopeScaling {
+    fn default() -> Self {
+        Self {
+            kind: RopeScalingType::None,
+            factor: 1.0,
+            original_context: 0,
+            low_freq_factor: default_low_freq_factor(),
+            high_freq_factor: default_high_freq_factor(),
+        }
+    }
+}
+
+impl RopeScaling {
+    /// Scaling named as in the `[brain] rope_scaling` setting; None for
+    /// `""`, which leaves the model's own, or an unknown name.
+    pub fn from_name(name: &str, factor: f32) -> Option<Self> {
+        Some(Self {
+            kind: RopeScalingType::from_name(name)?,
+            factor,
+            ..Default::default()
+        })
+    }
+
+    /// Whether positions are used as they are.
+    pub fn is_none(&self) -> bool {
+        self.kind == RopeScalingType::None || self.factor == 1.0
+    }
+}
+
+/// YaRN keeps the frequencies turning more than this many times over the
+/// trained context...
+const YARN_BETA_FAST: f32 = 32.0;
+/// ...and interpolates those turning fewer times than this.
+const YARN_BETA_SLOW: f32 = 1.0;
+
+/// RoPE for heads of one width: the frequency of each pair of rotated
+/// dimensions, scaled, computed once.
+#[derive(Debug, Clone)]
+pub struct Rope {
+    /// Radians per position of each pair: `i` and `i + rope_dims / 2`
+    freqs: Vec<f32>,
+    /// Length of each rotated pair: above 1 under YaRN
+    mscale: f32,
+    /// Stride between heads; dimensions past `rope_dims` pass through.
+    head_dim: usize,
+}
+
+impl Rope {
+    /// RoPE rotating the first `rope_dims` dimensions of each `head_dim`
+    /// wide head; `rope_dims` is `head_dim` but for partial rotary models
+    /// like Phi-2.
+    pub fn new(head_dim: usize, rope_dims: usize, rope_theta: f32, scaling: &RopeScaling) -> Self {
+        let dims = rope_dims.min(head_dim);
+        let freqs =
+            |theta: f32| (0..dims / 2).map(move |i| 1.0 / theta.powf(2.0 * i as f32 / dims as f32));
+        let factor = scaling.factor;
+        let kind = if scaling.is_none() {
+            RopeScalingType::None
+        } else {
+            scaling.kind
+        };
+        let (freqs, mscale) = match kind {
+            RopeScalingType::None => (freqs(rope_theta).collect(), 1.0),
+            RopeScalingType::Linear => (freqs(rope_theta).map(|f| f / factor).collect(), 1.0),
+            RopeScalingType::Ntk if dims > 2 => {
+                let exponent = dims as f32 / (dims as f32 - 2.0);
+                (freqs(rope_theta * factor.powf(exponent)).collect(), 1.0)
+            }
+            RopeScalingType::Ntk => (freqs(rope_theta).map(|f| f / factor).collect(), 1.0),
+            RopeScalingType::Yarn => {
+                // The pair turning `turns` times over the trained context
+                let original = scaling.original_context as f32;
+                let pair = |turns: f32| {
+                    dims as f32 * (original / (turns * 2.0 * std::f32::consts::PI)).ln()
+                        / (2.0 * rope_theta.ln())
+                };
+                let low = pair(YARN_BETA_FAST).floor().max(0.0);
+                let high = pair(YARN_BETA_SLOW).ceil().min(dims as f32 - 1.0);
+                let freqs = freqs(rope_theta)
+                    .enumerate()
+                    .map(|(i, f)| {
+                        // 1 keeps the frequency, 0 interpolates it fully
+                        let keep =
+                            1.0 - ((i as f32 - low) / (high - low).max(0.001)).clamp(0.0, 1.0);
+                        f * (keep + (1.0 - keep) / factor)
+                    })
+                    .collect();
+                (freqs, 1.0 + 0.1 * factor.ln())
+            }
+            RopeScalingType::Llama3 => {
+                let original = scaling.original_context as f32;
+                let (low, high) = (scaling.low_freq_factor, scaling.high_freq_factor);
+                let freqs = freqs(rope_theta)
+                    .map(|f| {
+                        // Turns over the trained context
+                        let turns = original * f / (2.0 * std::f32::consts::PI);
+                        if turns > high {
+                            f
+                        } else if turns < low {
+                            f / factor
+                        } else {
+                            let keep = (turns - low) / (high - low).max(0.001);
+                            f * (keep + (1.0 - keep) / factor)
+                        }
+                    })
+                    .collect();
+                (freqs, 1.0)
+            }
+        };
+        Self {
+            freqs,
+            mscale,
+            head_dim,
+        }
+    }
+
+    /// Divide each pair's frequency by its factor: the `rope_freqs.weight`
+    /// tensor some GGUF files carry in place of scaling metadata, one
+    /// factor per pair.
+    pub fn with_freq_factors(mut self, factors: &[f32]) -> Self {
+        for (freq, factor) in self.freqs.iter_mut().zip(factors) {
+            *freq /= factor;
+        }
+        self
+    }
+
+    /// Encode `pos` in one head.
+    pub fn apply(&self, vec: &mut [f32], pos: usize) {
+        self.rotate(vec, pos as f32, self.mscale, 1);
+    }
+
+    /// Re-encode a head encoded at some position for that position plus
+    /// `delta`, which may be negative: rotations by position add up. Moves
+    /// cached keys when the context shifts.
+    pub fn shift(&self, vec: &mut [f32], delta: f32) {
+        self.rotate(vec, delta, 1.0, 1);
+    }
+
+    /// [`apply`](Self::apply) to the first `n_heads` heads of a layer.
+    pub fn apply_multi_head(&self, vec: &mut [f32], pos: usize, n_heads: usize) {
+        self.rotate(vec, pos as f32, self.mscale, n_heads);
+    }
+
+    /// [`shift`](Self::shift) the first `n_heads` heads of a layer.
+    pub fn shift_multi_head(&self, vec: &mut [f32], delta: f32, n_heads: usize) {
+        self.rotate(vec, delta, 1.0, n_heads);
+    }
+
+    /// Rotate each pair of rotated dimensions of the first `n_heads` heads
+    /// by `pos` times its frequency and scale it by `mscale`. The angles
+    /// are the same for every head, so their sines and cosines are taken
+    /// once and the heads rotated with the SIMD kernel.
+    fn rotate(&self, vec: &mut [f32], pos: f32, mscale: f32, n_heads: usize) {
+        let half_dim = self.freqs.len();
+        let (sin, cos): (Vec<f32>, Vec<f32>) = self
+            .freqs
+            .iter()
+            .map(|&freq| {
+                let (sin, cos) = (pos * freq).sin_cos();
+                (sin * mscale, cos * mscale)
+            })
+            .unzip();
+        for head in vec.chunks_exact_mut(self.head_dim).take(n_heads) {
+            let (lo, hi) = head[..2 * half_dim].split_at_mut(half_dim);
+            crate::simd::rope_rotate_simd(lo, hi, &cos, &sin);
+        }
+    }
+}
 
 /// Apply RoPE to a vector in-place.
 /// `pos` is the token position, `dim` is the embedding dimension,
 /// `head_dim` is the dimension per attention head.
 pub fn apply_rope(vec: &mut [f32], pos: usize, head_dim: usize, rope_theta: f32) {
-    let half_dim = head_dim / 2;
-    for i in 0..half_dim {
-        let freq = 1.0 / rope_theta.powf(2.0 * i as f32 / head_dim as f32);
-        let angle = pos as f32 * freq;
-        let cos = angle.cos();
-        let sin = angle.sin();
+    Rope::new(head_dim, head_dim, rope_theta, &RopeScaling::default()).apply(vec, pos);
+}
 
-        let x0 = vec[i];
-        let x1 = vec[i + half_dim];
-        vec[i] = x0 * cos - x1 * sin;
-        vec[i + half_dim] = x0 * sin + x1 * cos;
-    }
+/// Re-encode a vector RoPE-encoded at some position for that position plus
+/// `delta`, see [`Rope::shift`].
+pub fn shift_rope(vec: &mut [f32], delta: f32, head_dim: usize, rope_theta: f32) {
+    Rope::new(head_dim, head_dim, rope_theta, &RopeScaling::default()).shift(vec, delta);
 }
 
 /// Apply RoPE to all heads in a layer.
@@ -28,11 +267,20 @@ pub fn apply_rope_multi_head(
     head_dim: usize,
     rope_theta: f32,
 ) {
-    for h in 0..n_heads {
-        let start = h * head_dim;
-        let end = start + head_dim;
-        apply_rope(&mut vec[start..end], pos, head_dim, rope_theta);
-    }
+    Rope::new(head_dim, head_dim, rope_theta, &RopeScaling::default())
+        .apply_multi_head(vec, pos, n_heads);
+}
+
+/// [`shift_rope`] for all heads in a layer.
+pub fn shift_rope_multi_head(
+    vec: &mut [f32],
+    delta: f32,
+    n_heads: usize,
+    head_dim: usize,
+    rope_theta: f32,
+) {
+    Rope::new(head_dim, head_dim, rope_theta, &RopeScaling::default())
+        .shift_multi_head(vec, delta, n_heads);
 }
 
 #[cfg(test)]
@@ -49,4 +297,147 @@ mod tests {
             assert!((a - b).abs() < 1e-5);
         }
     }
+
+    #[test]
+    fn test_shift_rope() {
+        // Encoded at 10 then shifted back 4 is the same as encoded at 6.
+        let mut shifted = vec![1.0, 2.0, 3.0, 4.0, -1.0, 0.5, 0.0, 2.0];
+        let mut direct = shifted.clone();
+        apply_rope_multi_head(&mut shifted, 10, 2, 4, 10000.0);
+        shift_rope_multi_head(&mut shifted, -4.0, 2, 4, 10000.0);
+        apply_rope_multi_head(&mut direct, 6, 2, 4, 10000.0);
+        for (a, b) in shifted.iter().zip(&direct) {
+            assert!((a - b).abs() < 1e-5, "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn test_rope_scaling() {
+        let (head_dim, theta) = (64, 10000.0);
+        let x: Vec<f32> = (0..head_dim).map(|i| (i as f32 * 0.37).sin()).collect();
+        let encode = |scaling: &RopeScaling, pos: usize| {
+            let mut v = x.clone();
+            Rope::new(head_dim, head_dim, theta, scaling).apply(&mut v, pos);
+            v
+        };
+        let close = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-4);
+        let scaled = |kind, factor| RopeScaling {
+            kind,
+            factor,
+            original_context: 4096,
+            ..Default::default()
+        };
+
+        // Factor 1 or no type is plain RoPE.
+        assert!(close(
+            &encode(&scaled(RopeScalingType::Yarn, 1.0), 100),
+            &encode(&RopeScaling::default(), 100)
+        ));
+        // Linear: position 200 at factor 2 is plain position 100.
+        assert!(close(
+            &encode(&scaled(RopeScalingType::Linear, 2.0), 200),
+            &encode(&RopeScaling::default(), 100)
+        ));
+        // NTK: the base grows, so the highest frequency is untouched and
+        // the lowest is interpolated by the factor.
+        let ntk = Rope::new(
+            head_dim,
+            head_dim,
+            theta,
+            &scaled(RopeScalingType::Ntk, 4.0),
+        );
+        let plain = Rope::new(head_dim, head_dim, theta, &RopeScaling::default());
+        assert_eq!(ntk.freqs[0], 1.0);
+        let last = head_dim / 2 - 1;
+        assert!((ntk.freqs[last] * 4.0 / plain.freqs[last] - 1.0).abs() < 0.05);
+        // YaRN: high frequencies kept, low ones interpolated, and a larger
+        // rotation.
+        let yarn = Rope::new(
+            head_dim,
+            head_dim,
+            theta,
+            &scaled(RopeScalingType::Yarn, 4.0),
+        );
+        assert_eq!(yarn.freqs[0], plain.freqs[0]);
+        assert!((yarn.freqs[last] * 4.0 - plain.freqs[last]).abs() < 1e-9);
+        assert!((yarn.mscale - (1.0 + 0.1 * 4.0f32.ln())).abs() < 1e-6);
+
+        // Shifting a scaled encoding still matches encoding directly.
+        for kind in [
+            RopeScalingType::Linear,
+            RopeScalingType::Ntk,
+            RopeScalingType::Yarn,
+            RopeScalingType::Llama3,
+        ] {
+            let scaling = scaled(kind, 4.0);
+            let mut shifted = encode(&scaling, 500);
+            Rope::new(head_dim, head_dim, theta, &scaling).shift(&mut shifted, -100.0);
+            assert!(close(&shifted, &encode(&scaling, 400)), "{kind:?}");
+        }
+
+        assert_eq!(RopeScaling::from_name("", 2.0), None);
+        assert_eq!(
+            RopeScaling::from_name("YaRN", 2.0).unwrap().kind,
+            RopeScalingType::Yarn
+        );
+    }
+
+    #[test]
+    fn test_llama3_scaling() {
+        // Llama 3.1: 128-wide heads, base 500000, 8k trained context run
+        // at 128k.
+        let scaling = RopeScaling {
+            kind: RopeScalingType::Llama3,
+            factor: 8.0,
+            original_context: 8192,
+            low_freq_factor: 1.0,
+            high_freq_factor: 4.0,
+        };
+        let llama3 = Rope::new(128, 128, 500000.0, &scaling);
+        let plain = Rope::new(128, 128, 500000.0, &RopeScaling::default());
+        let turns = |f: f32| 8192.0 * f / (2.0 * std::f32::consts::PI);
+        let (mut kept, mut interpolated, mut blended) = (0, 0, 0);
+        for (&f, &p) in llama3.freqs.iter().zip(&plain.freqs) {
+            if turns(p) > 4.0 {
+                assert_eq!(f, p);
+                kept += 1;
+            } else if turns(p) < 1.0 {
+                assert!((f * 8.0 - p).abs() <= p * 1e-6);
+                interpolated += 1;
+            } else {
+                assert!(f < p && f > p / 8.0);
+                blended += 1;
+            }
+        }
+        assert!(kept > 0 && interpolated > 0 && blended > 0);
+        assert_eq!(llama3.mscale, 1.0);
+
+        // The same frequencies from per-pair factors, as `rope_freqs.weight`
+        // holds them.
+        let factors: Vec<f32> = plain
+            .freqs
+            .iter()
+            .zip(&llama3.freqs)
+            .map(|(p, f)| p / f)
+            .collect();
+        let from_tensor = plain.clone().with_freq_factors(&factors);
+        for (a, b) in from_tensor.freqs.iter().zip(&llama3.freqs) {
+            assert!((a - b).abs() <= b * 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_partial_rope() {
+        // Two heads of 8, the first 4 of each rotated like a 4-wide head
+        // and the last 4 passed through.
+        let x: Vec<f32> = (0..16).map(|i| i as f32 + 1.0).collect();
+        let mut partial = x.clone();
+        Rope::new(8, 4, 10000.0, &RopeScaling::default()).apply_multi_head(&mut partial, 7, 2);
+        for (head, original) in partial.chunks(8).zip(x.chunks(8)) {
+            let mut full = original[..4].to_vec();
+            apply_rope(&mut full, 7, 4, 10000.0);
+            assert_eq!(&head[..4], &full[..]);
+            assert_eq!(&head[4..], &original[4..]);
+        }
+    }
 }
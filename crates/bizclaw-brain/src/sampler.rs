@@ -1,9 +1,9 @@
 //! Temperature + Top-p/Top-k sampling for token generation.
 
-use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 /// Sampler configuration.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SamplerConfig {
     pub temperature: f32,
     pub top_p: f32,
@@ -24,18 +24,70 @@ impl Default for SamplerConfig {
     }
 }
 
+/// SplitMix64 — a generator whose whole state is one `u64`, so an engine
+/// snapshot can carry it and resume the same sequence.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn seeded(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
 /// Token sampler — selects next token from logits.
 pub struct Sampler {
     config: SamplerConfig,
+    rng: Rng,
 }
 
 impl Sampler {
     pub fn new(config: SamplerConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            rng: Rng::seeded(rand::random()),
+        }
+    }
+
+    pub fn config(&self) -> &SamplerConfig {
+        &self.config
+    }
+
+    /// Change the settings, keeping the random sequence.
+    pub fn set_config(&mut self, config: SamplerConfig) {
+        self.config = config;
+    }
+
+    pub fn rng(&self) -> &Rng {
+        &self.rng
+    }
+
+    /// Continue the random sequence from `state`, see [`Rng::state`].
+    pub fn set_rng_state(&mut self, state: u64) {
+        self.rng = Rng::seeded(state);
     }
 
     /// Sample a token from logits.
-    pub fn sample(&self, logits: &mut [f32], last_tokens: &[u32]) -> u32 {
+    pub fn sample(&mut self, logits: &mut [f32], last_tokens: &[u32]) -> u32 {
         // Apply repeat penalty
         if self.config.repeat_penalty != 1.0 {
             let n = last_tokens.len().min(self.config.repeat_last_n);
@@ -109,8 +161,7 @@ impl Sampler {
         }
 
         // Random sampling
-        let mut rng = rand::thread_rng();
-        let r: f32 = rng.r#gen();
+        let r = self.rng.next_f32();
         let mut cumulative = 0.0;
         for &(idx, prob) in &probs {
             cumulative += prob;
@@ -133,3 +184,48 @@ fn argmax(values: &[f32]) -> u32 {
         .map(|(i, _)| i as u32)
         .unwrap_or(0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_resumes_from_state() {
+        let mut rng = Rng::seeded(42);
+        rng.next_u64();
+        let mut resumed = Rng::seeded(rng.state());
+        for _ in 0..8 {
+            assert_eq!(rng.next_u64(), resumed.next_u64());
+        }
+        for _ in 0..1000 {
+            let r = rng.next_f32();
+            assert!((0.0..1.0).contains(&r));
+        }
+    }
+
+    #[test]
+    fn test_sampling_is_reproducible() {
+        let config = SamplerConfig {
+            top_k: 0,
+            top_p: 1.0,
+            repeat_penalty: 1.0,
+            ..Default::default()
+        };
+        let mut a = Sampler::new(config.clone());
+        let mut b = Sampler::new(config);
+        b.set_rng_state(a.rng().state());
+        let logits = [0.5f32, 1.0, 0.2, 0.9, 0.1];
+        for _ in 0..16 {
+            assert_eq!(
+                a.sample(&mut logits.clone(), &[]),
+                b.sample(&mut logits.clone(), &[])
+            );
+        }
+
+        a.set_config(SamplerConfig {
+            temperature: 0.0,
+            ..Default::default()
+        });
+        assert_eq!(a.sample(&mut logits.clone(), &[]), 1);
+    }
+}
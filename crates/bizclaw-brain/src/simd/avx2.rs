@@ -1,45 +1,340 @@
-//! x86 AVX2 SIMD intrinsics for dot product.
+//! x86 AVX2 SIMD intrinsics for dot product, plain and fused with
+//! dequantization.
 //!
 //! Available on Intel Haswell+ (2013), AMD Zen+ (2018).
 //! Processes 8 floats per iteration (256-bit vectors).
+//!
+//! The kernels are compiled for AVX2 + FMA + F16C whatever the build's
+//! target features, so callers must check the CPU has them first — see
+//! [`super::simd_level`].
 
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
 
 /// AVX2-accelerated dot product (8 floats per iteration).
+///
+/// # Safety
+/// The CPU must support AVX2 and FMA.
 #[cfg(target_arch = "x86_64")]
-pub fn dot_product_avx2(a: &[f32], b: &[f32]) -> f32 {
+#[target_feature(enable = "avx2,fma")]
+pub unsafe fn dot_product_avx2(a: &[f32], b: &[f32]) -> f32 {
     debug_assert_eq!(a.len(), b.len());
     let n = a.len();
+    let mut sum_vec = _mm256_setzero_ps();
+    let chunks = n / 8;
+
+    for i in 0..chunks {
+        let offset = i * 8;
+        let (va, vb) = unsafe {
+            (
+                _mm256_loadu_ps(a.as_ptr().add(offset)),
+                _mm256_loadu_ps(b.as_ptr().add(offset)),
+            )
+        };
+        sum_vec = _mm256_fmadd_ps(va, vb, sum_vec); // fused multiply-add
+    }
 
-    unsafe {
-        let mut sum_vec = _mm256_setzero_ps();
-        let chunks = n / 8;
+    let mut sum = hsum(sum_vec);
+
+    // Tail
+    for i in (chunks * 8)..n {
+        sum += a[i] * b[i];
+    }
+
+    sum
+}
 
-        for i in 0..chunks {
-            let offset = i * 8;
-            let va = _mm256_loadu_ps(a.as_ptr().add(offset));
-            let vb = _mm256_loadu_ps(b.as_ptr().add(offset));
-            sum_vec = _mm256_fmadd_ps(va, vb, sum_vec); // fused multiply-add
+/// Sum of the 8 lanes of `v`.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn hsum(v: __m256) -> f32 {
+    // [a, b, c, d | e, f, g, h] → [a+e, b+f, c+g, d+h] → ...
+    let sum128 = _mm_add_ps(_mm256_castps256_ps128(v), _mm256_extractf128_ps(v, 1));
+    let sum64 = _mm_add_ps(sum128, _mm_movehl_ps(sum128, sum128));
+    _mm_cvtss_f32(_mm_add_ss(sum64, _mm_shuffle_ps(sum64, sum64, 1)))
+}
+
+/// `acc + x[..16] * q` for 16 signed bytes `q`, widened 8 at a time.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+fn fma_i8x16(acc: __m256, q: __m128i, x: &[f32]) -> __m256 {
+    debug_assert!(x.len() >= 16);
+    let lo = _mm256_cvtepi32_ps(_mm256_cvtepi8_epi32(q));
+    let hi = _mm256_cvtepi32_ps(_mm256_cvtepi8_epi32(_mm_srli_si128(q, 8)));
+    let (x_lo, x_hi) = unsafe {
+        (
+            _mm256_loadu_ps(x.as_ptr()),
+            _mm256_loadu_ps(x.as_ptr().add(8)),
+        )
+    };
+    _mm256_fmadd_ps(hi, x_hi, _mm256_fmadd_ps(lo, x_lo, acc))
+}
+
+/// AVX2 fused Q4_0 dot product: nibbles are unpacked and widened in
+/// registers, one scale multiply per block.
+///
+/// # Safety
+/// The CPU must support AVX2 and FMA.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+pub unsafe fn dot_q4_0_avx2(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 18 * 32, x.len());
+    let mut sum = _mm256_setzero_ps();
+    let mask = _mm_set1_epi8(0x0F);
+    let eight = _mm_set1_epi8(8);
+    for (block, x) in row.chunks_exact(18).zip(x.chunks_exact(32)) {
+        let scale = half::f16::from_le_bytes([block[0], block[1]]).to_f32();
+        let bytes = unsafe { _mm_loadu_si128(block.as_ptr().add(2) as *const __m128i) };
+        let lo = _mm_sub_epi8(_mm_and_si128(bytes, mask), eight);
+        let hi = _mm_sub_epi8(_mm_and_si128(_mm_srli_epi16(bytes, 4), mask), eight);
+        let acc = fma_i8x16(_mm256_setzero_ps(), lo, x);
+        let acc = fma_i8x16(acc, hi, &x[16..]);
+        sum = _mm256_fmadd_ps(_mm256_set1_ps(scale), acc, sum);
+    }
+    hsum(sum)
+}
+
+/// AVX2 fused Q8_0 dot product.
+///
+/// # Safety
+/// The CPU must support AVX2 and FMA.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+pub unsafe fn dot_q8_0_avx2(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 34 * 32, x.len());
+    let mut sum = _mm256_setzero_ps();
+    for (block, x) in row.chunks_exact(34).zip(x.chunks_exact(32)) {
+        let scale = half::f16::from_le_bytes([block[0], block[1]]).to_f32();
+        let (q_lo, q_hi) = unsafe {
+            let q = block.as_ptr().add(2) as *const __m128i;
+            (_mm_loadu_si128(q), _mm_loadu_si128(q.add(1)))
+        };
+        let acc = fma_i8x16(_mm256_setzero_ps(), q_lo, x);
+        let acc = fma_i8x16(acc, q_hi, &x[16..]);
+        sum = _mm256_fmadd_ps(_mm256_set1_ps(scale), acc, sum);
+    }
+    hsum(sum)
+}
+
+/// AVX2 dot product of an F16 row, converted 8 halves at a time.
+///
+/// # Safety
+/// The CPU must support AVX2, FMA and F16C.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma,f16c")]
+pub unsafe fn dot_f16_avx2(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 2, x.len());
+    let chunks = x.len() / 8;
+    let mut sum_vec = _mm256_setzero_ps();
+    for i in 0..chunks {
+        let (h, vb) = unsafe {
+            (
+                _mm_loadu_si128(row.as_ptr().add(i * 16) as *const __m128i),
+                _mm256_loadu_ps(x.as_ptr().add(i * 8)),
+            )
+        };
+        sum_vec = _mm256_fmadd_ps(_mm256_cvtph_ps(h), vb, sum_vec);
+    }
+    hsum(sum_vec) + super::fused::dot_f16(&row[chunks * 16..], &x[chunks * 8..])
+}
+
+/// AVX2 dot product of an F32 row read straight from its (possibly
+/// unaligned) bytes.
+///
+/// # Safety
+/// The CPU must support AVX2 and FMA.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+pub unsafe fn dot_f32_avx2(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 4, x.len());
+    let chunks = x.len() / 8;
+    let ptr = row.as_ptr() as *const f32;
+    let mut sum_vec = _mm256_setzero_ps();
+    for i in 0..chunks {
+        let (va, vb) = unsafe {
+            (
+                _mm256_loadu_ps(ptr.add(i * 8)),
+                _mm256_loadu_ps(x.as_ptr().add(i * 8)),
+            )
+        };
+        sum_vec = _mm256_fmadd_ps(va, vb, sum_vec);
+    }
+    hsum(sum_vec) + super::fused::dot_f32(&row[chunks * 32..], &x[chunks * 8..])
+}
+
+/// e^x for each lane: x = n·ln2 + r, e^r from a degree-5 polynomial
+/// (Cephes' `expf`), 2^n built in the exponent bits. Relative error is
+/// about 1e-7; inputs are clamped to ±88.37, so very negative ones give
+/// a tiny value rather than exactly 0.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+fn exp_ps(x: __m256) -> __m256 {
+    let x = _mm256_min_ps(
+        _mm256_max_ps(x, _mm256_set1_ps(-88.376)),
+        _mm256_set1_ps(88.376),
+    );
+    let n = _mm256_floor_ps(_mm256_fmadd_ps(
+        x,
+        _mm256_set1_ps(std::f32::consts::LOG2_E),
+        _mm256_set1_ps(0.5),
+    ));
+    // ln2 split in two so r keeps its low bits
+    let r = _mm256_fnmadd_ps(n, _mm256_set1_ps(0.693_359_4), x);
+    let r = _mm256_fnmadd_ps(n, _mm256_set1_ps(-2.121_944_4e-4), r);
+
+    let mut y = _mm256_set1_ps(1.987_569_1e-4);
+    for c in [
+        1.398_199_9e-3,
+        8.333_452e-3,
+        4.166_579_6e-2,
+        0.166_666_65,
+        0.5,
+    ] {
+        y = _mm256_fmadd_ps(y, r, _mm256_set1_ps(c));
+    }
+    let y = _mm256_fmadd_ps(
+        y,
+        _mm256_mul_ps(r, r),
+        _mm256_add_ps(r, _mm256_set1_ps(1.0)),
+    );
+
+    let pow2n = _mm256_slli_epi32::<23>(_mm256_add_epi32(
+        _mm256_cvtps_epi32(n),
+        _mm256_set1_epi32(127),
+    ));
+    _mm256_mul_ps(y, _mm256_castsi256_ps(pow2n))
+}
+
+/// AVX2 maximum of `values` (−∞ when empty).
+///
+/// # Safety
+/// The CPU must support AVX2 and FMA.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+pub unsafe fn max_avx2(values: &[f32]) -> f32 {
+    let chunks = values.len() / 8;
+    let mut max_vec = _mm256_set1_ps(f32::NEG_INFINITY);
+    for i in 0..chunks {
+        let v = unsafe { _mm256_loadu_ps(values.as_ptr().add(i * 8)) };
+        max_vec = _mm256_max_ps(max_vec, v);
+    }
+    let mut lanes = [0.0f32; 8];
+    unsafe { _mm256_storeu_ps(lanes.as_mut_ptr(), max_vec) };
+    lanes
+        .into_iter()
+        .chain(values[chunks * 8..].iter().copied())
+        .fold(f32::NEG_INFINITY, f32::max)
+}
+
+/// AVX2 `values[i] = e^(values[i] − max)`, returning their sum.
+///
+/// # Safety
+/// The CPU must support AVX2 and FMA.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+pub unsafe fn exp_sub_sum_avx2(values: &mut [f32], max: f32) -> f32 {
+    let chunks = values.len() / 8;
+    let max_vec = _mm256_set1_ps(max);
+    let mut sum_vec = _mm256_setzero_ps();
+    for i in 0..chunks {
+        let ptr = unsafe { values.as_mut_ptr().add(i * 8) };
+        let e = exp_ps(_mm256_sub_ps(unsafe { _mm256_loadu_ps(ptr) }, max_vec));
+        unsafe { _mm256_storeu_ps(ptr, e) };
+        sum_vec = _mm256_add_ps(sum_vec, e);
+    }
+    let mut sum = hsum(sum_vec);
+    for v in &mut values[chunks * 8..] {
+        *v = (*v - max).exp();
+        sum += *v;
+    }
+    sum
+}
+
+/// AVX2 SwiGLU: `gate[i] = silu(gate[i]) * up[i]`, or plain SiLU of
+/// `gate` when `up` is `None`.
+///
+/// # Safety
+/// The CPU must support AVX2 and FMA.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+pub unsafe fn swiglu_avx2(gate: &mut [f32], up: Option<&[f32]>) {
+    debug_assert!(up.is_none_or(|up| up.len() == gate.len()));
+    let chunks = gate.len() / 8;
+    let one = _mm256_set1_ps(1.0);
+    for i in 0..chunks {
+        let ptr = unsafe { gate.as_mut_ptr().add(i * 8) };
+        let x = unsafe { _mm256_loadu_ps(ptr) };
+        // x / (1 + e^−x)
+        let e = exp_ps(_mm256_sub_ps(_mm256_setzero_ps(), x));
+        let mut y = _mm256_div_ps(x, _mm256_add_ps(one, e));
+        if let Some(up) = up {
+            y = _mm256_mul_ps(y, unsafe { _mm256_loadu_ps(up.as_ptr().add(i * 8)) });
         }
+        unsafe { _mm256_storeu_ps(ptr, y) };
+    }
+    for j in chunks * 8..gate.len() {
+        let x = gate[j];
+        gate[j] = x / (1.0 + (-x).exp()) * up.map_or(1.0, |up| up[j]);
+    }
+}
+
+/// AVX2 RMSNorm: the sum of squares and the normalize-and-scale pass
+/// both 8 floats per iteration.
+///
+/// # Safety
+/// The CPU must support AVX2 and FMA.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+pub unsafe fn rmsnorm_avx2(output: &mut [f32], input: &[f32], weight: &[f32], eps: f32) {
+    let n = input.len();
+    debug_assert_eq!(output.len(), n);
+    debug_assert_eq!(weight.len(), n);
+    let chunks = n / 8;
 
-        // Horizontal sum of 8 lanes
-        // [a, b, c, d | e, f, g, h]
-        let hi128 = _mm256_extractf128_ps(sum_vec, 1); // [e, f, g, h]
-        let lo128 = _mm256_castps256_ps128(sum_vec); // [a, b, c, d]
-        let sum128 = _mm_add_ps(lo128, hi128); // [a+e, b+f, c+g, d+h]
-        let hi64 = _mm_movehl_ps(sum128, sum128);
-        let sum64 = _mm_add_ps(sum128, hi64);
-        let hi32 = _mm_shuffle_ps(sum64, sum64, 1);
-        let total = _mm_add_ss(sum64, hi32);
-        let mut sum = _mm_cvtss_f32(total);
-
-        // Tail
-        for i in (chunks * 8)..n {
-            sum += a[i] * b[i];
+    let ss = unsafe { dot_product_avx2(input, input) } / n as f32;
+    let inv_rms = 1.0 / (ss + eps).sqrt();
+    let scale = _mm256_set1_ps(inv_rms);
+    for i in 0..chunks {
+        let offset = i * 8;
+        unsafe {
+            let v = _mm256_mul_ps(_mm256_loadu_ps(input.as_ptr().add(offset)), scale);
+            let w = _mm256_loadu_ps(weight.as_ptr().add(offset));
+            _mm256_storeu_ps(output.as_mut_ptr().add(offset), _mm256_mul_ps(v, w));
         }
+    }
+    for i in (chunks * 8)..n {
+        output[i] = input[i] * inv_rms * weight[i];
+    }
+}
 
-        sum
+/// AVX2 RoPE rotation of split halves: each pair `(lo[i], hi[i])` is
+/// rotated by the angle whose cosine and sine are `cos[i]` and `sin[i]`.
+///
+/// # Safety
+/// The CPU must support AVX2 and FMA.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+pub unsafe fn rope_rotate_avx2(lo: &mut [f32], hi: &mut [f32], cos: &[f32], sin: &[f32]) {
+    let n = lo.len();
+    debug_assert!(hi.len() == n && cos.len() == n && sin.len() == n);
+    let chunks = n / 8;
+    for i in 0..chunks {
+        let offset = i * 8;
+        unsafe {
+            let x0 = _mm256_loadu_ps(lo.as_ptr().add(offset));
+            let x1 = _mm256_loadu_ps(hi.as_ptr().add(offset));
+            let c = _mm256_loadu_ps(cos.as_ptr().add(offset));
+            let s = _mm256_loadu_ps(sin.as_ptr().add(offset));
+            let r0 = _mm256_fmsub_ps(x0, c, _mm256_mul_ps(x1, s));
+            let r1 = _mm256_fmadd_ps(x0, s, _mm256_mul_ps(x1, c));
+            _mm256_storeu_ps(lo.as_mut_ptr().add(offset), r0);
+            _mm256_storeu_ps(hi.as_mut_ptr().add(offset), r1);
+        }
+    }
+    for i in (chunks * 8)..n {
+        let (x0, x1) = (lo[i], hi[i]);
+        lo[i] = x0 * cos[i] - x1 * sin[i];
+        hi[i] = x0 * sin[i] + x1 * cos[i];
     }
 }
 
@@ -0,0 +1,147 @@
+//! x86 AVX-512 SIMD intrinsics for dot product, plain and fused with
+//! dequantization.
+//!
+//! Available on Intel Skylake-SP+ (2017), AMD Zen 4+ (2022).
+//! Processes 16 floats per iteration (512-bit vectors), so a 32-value
+//! quant block is two registers.
+//!
+//! Like the AVX2 kernels these are compiled for AVX-512F whatever the
+//! build's target features; callers check the CPU first.
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+/// AVX-512 dot product (16 floats per iteration).
+///
+/// # Safety
+/// The CPU must support AVX-512F.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+pub unsafe fn dot_product_avx512(a: &[f32], b: &[f32]) -> f32 {
+    debug_assert_eq!(a.len(), b.len());
+    let n = a.len();
+    let chunks = n / 16;
+    let mut sum_vec = _mm512_setzero_ps();
+    for i in 0..chunks {
+        let (va, vb) = unsafe {
+            (
+                _mm512_loadu_ps(a.as_ptr().add(i * 16)),
+                _mm512_loadu_ps(b.as_ptr().add(i * 16)),
+            )
+        };
+        sum_vec = _mm512_fmadd_ps(va, vb, sum_vec);
+    }
+
+    let mut sum = _mm512_reduce_add_ps(sum_vec);
+    for i in (chunks * 16)..n {
+        sum += a[i] * b[i];
+    }
+    sum
+}
+
+/// `acc + x[..16] * q` for 16 signed bytes `q`.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+fn fma_i8x16(acc: __m512, q: __m128i, x: &[f32]) -> __m512 {
+    debug_assert!(x.len() >= 16);
+    let vx = unsafe { _mm512_loadu_ps(x.as_ptr()) };
+    _mm512_fmadd_ps(_mm512_cvtepi32_ps(_mm512_cvtepi8_epi32(q)), vx, acc)
+}
+
+/// AVX-512 fused Q4_0 dot product: the low and high nibbles of a block
+/// each fill one register.
+///
+/// # Safety
+/// The CPU must support AVX-512F.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+pub unsafe fn dot_q4_0_avx512(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 18 * 32, x.len());
+    let mut sum = _mm512_setzero_ps();
+    let mask = _mm_set1_epi8(0x0F);
+    let eight = _mm_set1_epi8(8);
+    for (block, x) in row.chunks_exact(18).zip(x.chunks_exact(32)) {
+        let scale = half::f16::from_le_bytes([block[0], block[1]]).to_f32();
+        let bytes = unsafe { _mm_loadu_si128(block.as_ptr().add(2) as *const __m128i) };
+        let lo = _mm_sub_epi8(_mm_and_si128(bytes, mask), eight);
+        let hi = _mm_sub_epi8(_mm_and_si128(_mm_srli_epi16(bytes, 4), mask), eight);
+        let acc = fma_i8x16(_mm512_setzero_ps(), lo, x);
+        let acc = fma_i8x16(acc, hi, &x[16..]);
+        sum = _mm512_fmadd_ps(_mm512_set1_ps(scale), acc, sum);
+    }
+    _mm512_reduce_add_ps(sum)
+}
+
+/// AVX-512 fused Q8_0 dot product.
+///
+/// # Safety
+/// The CPU must support AVX-512F.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+pub unsafe fn dot_q8_0_avx512(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 34 * 32, x.len());
+    let mut sum = _mm512_setzero_ps();
+    for (block, x) in row.chunks_exact(34).zip(x.chunks_exact(32)) {
+        let scale = half::f16::from_le_bytes([block[0], block[1]]).to_f32();
+        let (q_lo, q_hi) = unsafe {
+            let q = block.as_ptr().add(2) as *const __m128i;
+            (_mm_loadu_si128(q), _mm_loadu_si128(q.add(1)))
+        };
+        let acc = fma_i8x16(_mm512_setzero_ps(), q_lo, x);
+        let acc = fma_i8x16(acc, q_hi, &x[16..]);
+        sum = _mm512_fmadd_ps(_mm512_set1_ps(scale), acc, sum);
+    }
+    _mm512_reduce_add_ps(sum)
+}
+
+/// AVX-512 dot product of an F16 row, converted 16 halves at a time.
+///
+/// # Safety
+/// The CPU must support AVX-512F.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+pub unsafe fn dot_f16_avx512(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 2, x.len());
+    let chunks = x.len() / 16;
+    let mut sum_vec = _mm512_setzero_ps();
+    for i in 0..chunks {
+        let (h, vb) = unsafe {
+            (
+                _mm256_loadu_si256(row.as_ptr().add(i * 32) as *const __m256i),
+                _mm512_loadu_ps(x.as_ptr().add(i * 16)),
+            )
+        };
+        sum_vec = _mm512_fmadd_ps(_mm512_cvtph_ps(h), vb, sum_vec);
+    }
+    _mm512_reduce_add_ps(sum_vec) + super::fused::dot_f16(&row[chunks * 32..], &x[chunks * 16..])
+}
+
+/// AVX-512 dot product of an F32 row read straight from its (possibly
+/// unaligned) bytes.
+///
+/// # Safety
+/// The CPU must support AVX-512F.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+pub unsafe fn dot_f32_avx512(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 4, x.len());
+    let chunks = x.len() / 16;
+    let ptr = row.as_ptr() as *const f32;
+    let mut sum_vec = _mm512_setzero_ps();
+    for i in 0..chunks {
+        let (va, vb) = unsafe {
+            (
+                _mm512_loadu_ps(ptr.add(i * 16)),
+                _mm512_loadu_ps(x.as_ptr().add(i * 16)),
+            )
+        };
+        sum_vec = _mm512_fmadd_ps(va, vb, sum_vec);
+    }
+    _mm512_reduce_add_ps(sum_vec) + super::fused::dot_f32(&row[chunks * 64..], &x[chunks * 16..])
+}
+
+/// Scalar fallback.
+#[cfg(not(target_arch = "x86_64"))]
+pub fn dot_product_avx512(a: &[f32], b: &[f32]) -> f32 {
+    crate::tensor::dot_product(a, b)
+}
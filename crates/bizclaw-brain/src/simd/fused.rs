@@ -0,0 +1,59 @@
+//! Fused dequantize + dot-product kernels.
+//!
+//! Each kernel takes a row of quantized blocks and accumulates its dot
+//! product with an f32 vector block by block, applying the block scale
+//! once per block instead of writing the dequantized row out first.
+//! These are the portable scalar versions; [`super::dot_quantized`]
+//! picks a SIMD one where the target has it.
+
+/// Dot product of a Q4_0 row (18-byte blocks of 32 values) with `x`.
+pub fn dot_q4_0(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 18 * 32, x.len());
+    let mut sum = 0.0f32;
+    for (block, x) in row.chunks_exact(18).zip(x.chunks_exact(32)) {
+        let scale = half::f16::from_le_bytes([block[0], block[1]]).to_f32();
+        let mut acc = 0.0f32;
+        for i in 0..16 {
+            let byte = block[2 + i];
+            acc += ((byte & 0x0F) as f32 - 8.0) * x[i];
+            acc += ((byte >> 4) as f32 - 8.0) * x[i + 16];
+        }
+        sum += acc * scale;
+    }
+    sum
+}
+
+/// Dot product of a Q8_0 row (34-byte blocks of 32 values) with `x`.
+pub fn dot_q8_0(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 34 * 32, x.len());
+    let mut sum = 0.0f32;
+    for (block, x) in row.chunks_exact(34).zip(x.chunks_exact(32)) {
+        let scale = half::f16::from_le_bytes([block[0], block[1]]).to_f32();
+        let acc: f32 = block[2..]
+            .iter()
+            .zip(x)
+            .map(|(&q, &x)| q as i8 as f32 * x)
+            .sum();
+        sum += acc * scale;
+    }
+    sum
+}
+
+/// Dot product of an F16 row with `x`.
+pub fn dot_f16(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 2, x.len());
+    row.chunks_exact(2)
+        .zip(x)
+        .map(|(h, &x)| half::f16::from_le_bytes([h[0], h[1]]).to_f32() * x)
+        .sum()
+}
+
+/// Dot product of an F32 row, read straight from its little-endian
+/// bytes, with `x`.
+pub fn dot_f32(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 4, x.len());
+    row.chunks_exact(4)
+        .zip(x)
+        .map(|(b, &x)| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) * x)
+        .sum()
+}
@@ -6,38 +6,161 @@
 //! Supported architectures:
 //! - ARM64 (aarch64): NEON — 128-bit vectors (Raspberry Pi 4/5, Apple Silicon)
 //! - x86_64 + SSE2: 128-bit vectors (all x86_64 CPUs)
-//! - x86_64 + AVX2: 256-bit vectors (Intel Haswell+, AMD Zen+)
+//! - x86_64 + AVX2/FMA: 256-bit vectors (Intel Haswell+, AMD Zen+)
+//! - x86_64 + AVX-512: 512-bit vectors (Intel Skylake-SP+, AMD Zen 4+)
+//! - wasm32 + SIMD128: 128-bit vectors (browsers)
+//!
+//! x86 kernels are picked at runtime from the features the CPU reports,
+//! so one binary built for baseline x86_64 still uses AVX2 or AVX-512
+//! where the CPU has them.
 
 pub mod avx2;
+pub mod avx512;
+pub mod fused;
 pub mod neon;
 pub mod sse2;
+pub mod wasm;
 
-/// Accelerated dot product — dispatches to best SIMD available.
-pub fn dot_product_simd(a: &[f32], b: &[f32]) -> f32 {
-    debug_assert_eq!(a.len(), b.len());
+use crate::gguf::GgmlType;
+use std::sync::OnceLock;
 
-    #[cfg(target_arch = "aarch64")]
+/// The widest kernel set this CPU can run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimdLevel {
+    Scalar,
+    Sse2,
+    /// AVX2 with FMA and F16C, which every AVX2 CPU also has.
+    Avx2,
+    Avx512,
+    Neon,
+    Simd128,
+}
+
+impl SimdLevel {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Scalar => "scalar",
+            Self::Sse2 => "sse2",
+            Self::Avx2 => "avx2",
+            Self::Avx512 => "avx512",
+            Self::Neon => "neon",
+            Self::Simd128 => "simd128",
+        }
+    }
+}
+
+/// The kernel set dispatched to, detected on first use.
+pub fn simd_level() -> SimdLevel {
+    static LEVEL: OnceLock<SimdLevel> = OnceLock::new();
+    *LEVEL.get_or_init(detect_level)
+}
+
+fn detect_level() -> SimdLevel {
+    #[cfg(target_arch = "x86_64")]
     {
-        neon::dot_product_neon(a, b)
+        let avx2 = is_x86_feature_detected!("avx2")
+            && is_x86_feature_detected!("fma")
+            && is_x86_feature_detected!("f16c");
+        if avx2 && is_x86_feature_detected!("avx512f") {
+            SimdLevel::Avx512
+        } else if avx2 {
+            SimdLevel::Avx2
+        } else {
+            SimdLevel::Sse2
+        }
     }
 
-    #[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+    #[cfg(target_arch = "aarch64")]
     {
-        avx2::dot_product_avx2(a, b)
+        SimdLevel::Neon
     }
 
-    #[cfg(all(target_arch = "x86_64", not(target_feature = "avx2")))]
+    // wasm can't query the host: SIMD128 is there if the module was built
+    // with it
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
     {
-        sse2::dot_product_sse2(a, b)
+        SimdLevel::Simd128
     }
 
-    // Fallback
-    #[cfg(not(any(target_arch = "aarch64", target_arch = "x86_64")))]
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "aarch64",
+        all(target_arch = "wasm32", target_feature = "simd128")
+    )))]
     {
-        crate::tensor::dot_product(a, b)
+        SimdLevel::Scalar
+    }
+}
+
+/// Accelerated dot product — dispatches to best SIMD available.
+pub fn dot_product_simd(a: &[f32], b: &[f32]) -> f32 {
+    debug_assert_eq!(a.len(), b.len());
+
+    match simd_level() {
+        // SAFETY: `simd_level` only reports levels the CPU supports.
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx512 => unsafe { avx512::dot_product_avx512(a, b) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx2 => unsafe { avx2::dot_product_avx2(a, b) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => sse2::dot_product_sse2(a, b),
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => neon::dot_product_neon(a, b),
+        #[cfg(target_arch = "wasm32")]
+        SimdLevel::Simd128 => wasm::dot_product_wasm(a, b),
+        _ => crate::tensor::dot_product(a, b),
     }
 }
 
+/// Whether [`dot_quantized`] has a kernel for `ggml_type`.
+pub fn has_fused_kernel(ggml_type: GgmlType) -> bool {
+    matches!(
+        ggml_type,
+        GgmlType::F32 | GgmlType::F16 | GgmlType::Q4_0 | GgmlType::Q8_0
+    )
+}
+
+/// Fused dequantize + dot product of a weight row stored as `ggml_type`
+/// with `x` — dispatches to the best kernel available.
+///
+/// Returns `None` for types without a fused kernel, which are
+/// dequantized to f32 first instead.
+pub fn dot_quantized(row: &[u8], x: &[f32], ggml_type: GgmlType) -> Option<f32> {
+    let level = simd_level();
+    // SAFETY: `simd_level` only reports levels the CPU supports.
+    let dot = match (level, ggml_type) {
+        #[cfg(target_arch = "x86_64")]
+        (SimdLevel::Avx512, GgmlType::F32) => unsafe { avx512::dot_f32_avx512(row, x) },
+        #[cfg(target_arch = "x86_64")]
+        (SimdLevel::Avx512, GgmlType::F16) => unsafe { avx512::dot_f16_avx512(row, x) },
+        #[cfg(target_arch = "x86_64")]
+        (SimdLevel::Avx512, GgmlType::Q4_0) => unsafe { avx512::dot_q4_0_avx512(row, x) },
+        #[cfg(target_arch = "x86_64")]
+        (SimdLevel::Avx512, GgmlType::Q8_0) => unsafe { avx512::dot_q8_0_avx512(row, x) },
+        #[cfg(target_arch = "x86_64")]
+        (SimdLevel::Avx2, GgmlType::F32) => unsafe { avx2::dot_f32_avx2(row, x) },
+        #[cfg(target_arch = "x86_64")]
+        (SimdLevel::Avx2, GgmlType::F16) => unsafe { avx2::dot_f16_avx2(row, x) },
+        #[cfg(target_arch = "x86_64")]
+        (SimdLevel::Avx2, GgmlType::Q4_0) => unsafe { avx2::dot_q4_0_avx2(row, x) },
+        #[cfg(target_arch = "x86_64")]
+        (SimdLevel::Avx2, GgmlType::Q8_0) => unsafe { avx2::dot_q8_0_avx2(row, x) },
+        #[cfg(target_arch = "aarch64")]
+        (SimdLevel::Neon, GgmlType::F32) => neon::dot_f32_neon(row, x),
+        #[cfg(target_arch = "aarch64")]
+        (SimdLevel::Neon, GgmlType::Q4_0) => neon::dot_q4_0_neon(row, x),
+        #[cfg(target_arch = "aarch64")]
+        (SimdLevel::Neon, GgmlType::Q8_0) => neon::dot_q8_0_neon(row, x),
+        (_, GgmlType::F32) => fused::dot_f32(row, x),
+        (_, GgmlType::F16) => fused::dot_f16(row, x),
+        (_, GgmlType::Q4_0) => fused::dot_q4_0(row, x),
+        (_, GgmlType::Q8_0) => fused::dot_q8_0(row, x),
+        _ => return None,
+    };
+
+    Some(dot)
+}
+
 /// Accelerated matmul using SIMD dot product.
 /// output[rows] = mat[rows x cols] @ vec[cols]
 pub fn matmul_simd(output: &mut [f32], mat: &[f32], vec: &[f32], rows: usize, cols: usize) {
@@ -49,14 +172,120 @@ pub fn matmul_simd(output: &mut [f32], mat: &[f32], vec: &[f32], rows: usize, co
 
 /// Accelerated RMSNorm using SIMD reductions.
 pub fn rmsnorm_simd(output: &mut [f32], input: &[f32], weight: &[f32], eps: f32) {
-    let n = input.len();
+    match simd_level() {
+        // SAFETY: `simd_level` only reports levels the CPU supports.
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx512 | SimdLevel::Avx2 => unsafe {
+            avx2::rmsnorm_avx2(output, input, weight, eps)
+        },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => neon::rmsnorm_neon(output, input, weight, eps),
+        _ => {
+            let n = input.len();
+
+            // Sum of squares using SIMD
+            let ss = dot_product_simd(input, input) / n as f32;
+            let inv_rms = 1.0 / (ss + eps).sqrt();
+
+            for i in 0..n {
+                output[i] = input[i] * inv_rms * weight[i];
+            }
+        }
+    }
+}
+
+/// Accelerated maximum of `values` (−∞ when empty).
+pub fn max_simd(values: &[f32]) -> f32 {
+    match simd_level() {
+        // SAFETY: `simd_level` only reports levels the CPU supports.
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx512 | SimdLevel::Avx2 => unsafe { avx2::max_avx2(values) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => neon::max_neon(values),
+        _ => values.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+    }
+}
+
+/// Accelerated `values[i] = e^(values[i] − max)`, returning their sum —
+/// the exponentiation step of a softmax, online or not.
+///
+/// The SIMD kernels use a polynomial exp accurate to about 1e-7 relative.
+pub fn exp_sub_sum_simd(values: &mut [f32], max: f32) -> f32 {
+    match simd_level() {
+        // SAFETY: `simd_level` only reports levels the CPU supports.
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx512 | SimdLevel::Avx2 => unsafe { avx2::exp_sub_sum_avx2(values, max) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => neon::exp_sub_sum_neon(values, max),
+        _ => {
+            let mut sum = 0.0f32;
+            for v in values.iter_mut() {
+                *v = (*v - max).exp();
+                sum += *v;
+            }
+            sum
+        }
+    }
+}
 
-    // Sum of squares using SIMD
-    let ss = dot_product_simd(input, input) / n as f32;
-    let inv_rms = 1.0 / (ss + eps).sqrt();
+/// Accelerated softmax, with the maximum subtracted before
+/// exponentiating so large logits don't overflow.
+pub fn softmax_simd(values: &mut [f32]) {
+    if values.is_empty() {
+        return;
+    }
+    let max = max_simd(values);
+    let inv_sum = 1.0 / exp_sub_sum_simd(values, max);
+    for v in values.iter_mut() {
+        *v *= inv_sum;
+    }
+}
+
+/// Accelerated SiLU: `values[i] = values[i] * sigmoid(values[i])`.
+pub fn silu_simd(values: &mut [f32]) {
+    swiglu(values, None);
+}
+
+/// Accelerated SwiGLU: `gate[i] = silu(gate[i]) * up[i]`, the FFN
+/// activation and gating in one pass.
+pub fn swiglu_simd(gate: &mut [f32], up: &[f32]) {
+    debug_assert_eq!(gate.len(), up.len());
+    swiglu(gate, Some(up));
+}
+
+fn swiglu(gate: &mut [f32], up: Option<&[f32]>) {
+    match simd_level() {
+        // SAFETY: `simd_level` only reports levels the CPU supports.
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx512 | SimdLevel::Avx2 => unsafe { avx2::swiglu_avx2(gate, up) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => neon::swiglu_neon(gate, up),
+        _ => {
+            crate::tensor::silu(gate);
+            if let Some(up) = up {
+                crate::tensor::elementwise_mul(gate, up);
+            }
+        }
+    }
+}
 
-    for i in 0..n {
-        output[i] = input[i] * inv_rms * weight[i];
+/// Accelerated RoPE rotation of a head's split halves: pair
+/// `(lo[i], hi[i])` is rotated by the angle with cosine `cos[i]` and
+/// sine `sin[i]`.
+pub fn rope_rotate_simd(lo: &mut [f32], hi: &mut [f32], cos: &[f32], sin: &[f32]) {
+    match simd_level() {
+        // SAFETY: `simd_level` only reports levels the CPU supports.
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx512 | SimdLevel::Avx2 => unsafe { avx2::rope_rotate_avx2(lo, hi, cos, sin) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => neon::rope_rotate_neon(lo, hi, cos, sin),
+        _ => {
+            for i in 0..lo.len() {
+                let (x0, x1) = (lo[i], hi[i]);
+                lo[i] = x0 * cos[i] - x1 * sin[i];
+                hi[i] = x0 * sin[i] + x1 * cos[i];
+            }
+        }
     }
 }
 
@@ -70,6 +299,120 @@ mod tests {
         let b = vec![1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0];
         let result = dot_product_simd(&a, &b);
         assert!((result - 36.0).abs() < 1e-4, "got {result}");
+
+        // Lengths that leave a tail after the widest vectors
+        let a: Vec<f32> = (0..37).map(|i| i as f32 * 0.25).collect();
+        let want = crate::tensor::dot_product(&a, &a);
+        let got = dot_product_simd(&a, &a);
+        assert!(
+            (got - want).abs() < 1e-2,
+            "{:?}: {got} != {want}",
+            simd_level()
+        );
+    }
+
+    #[test]
+    fn test_dot_quantized() {
+        let values: Vec<f32> = (0..96)
+            .map(|i| ((i * 37) % 29) as f32 / 29.0 - 0.5)
+            .collect();
+        let x: Vec<f32> = (0..96).map(|i| (i % 7) as f32 - 3.0).collect();
+        for ggml_type in [GgmlType::F32, GgmlType::F16, GgmlType::Q4_0, GgmlType::Q8_0] {
+            let mut row = Vec::new();
+            crate::quant::quantize_row(&values, &mut row, ggml_type).unwrap();
+            let mut dequantized = vec![0.0; 96];
+            crate::quant::dequantize_row(&row, &mut dequantized, 96, ggml_type).unwrap();
+            let want = crate::tensor::dot_product(&dequantized, &x);
+
+            let got = dot_quantized(&row, &x, ggml_type).unwrap();
+            assert!((got - want).abs() < 1e-3, "{ggml_type:?}: {got} != {want}");
+            let scalar = match ggml_type {
+                GgmlType::F32 => fused::dot_f32(&row, &x),
+                GgmlType::F16 => fused::dot_f16(&row, &x),
+                GgmlType::Q4_0 => fused::dot_q4_0(&row, &x),
+                _ => fused::dot_q8_0(&row, &x),
+            };
+            assert!(
+                (scalar - want).abs() < 1e-3,
+                "{ggml_type:?}: {scalar} != {want}"
+            );
+            // The dispatcher only reaches the widest level; check AVX2
+            // separately on AVX-512 machines.
+            #[cfg(target_arch = "x86_64")]
+            if simd_level() == SimdLevel::Avx512 {
+                let avx2 = unsafe {
+                    match ggml_type {
+                        GgmlType::F32 => avx2::dot_f32_avx2(&row, &x),
+                        GgmlType::F16 => avx2::dot_f16_avx2(&row, &x),
+                        GgmlType::Q4_0 => avx2::dot_q4_0_avx2(&row, &x),
+                        _ => avx2::dot_q8_0_avx2(&row, &x),
+                    }
+                };
+                assert!(
+                    (avx2 - want).abs() < 1e-3,
+                    "{ggml_type:?}: {avx2} != {want}"
+                );
+            }
+        }
+        assert!(dot_quantized(&[0; 144], &[0.0; 256], GgmlType::Q4K).is_none());
+    }
+
+    #[test]
+    fn test_elementwise_simd() {
+        let values: Vec<f32> = (0..45).map(|i| (i as f32 - 22.0) * 0.7).collect();
+
+        let mut got = values.clone();
+        let mut want = values.clone();
+        softmax_simd(&mut got);
+        crate::tensor::softmax(&mut want);
+        for (got, want) in got.iter().zip(&want) {
+            assert!((got - want).abs() < 1e-6, "{got} != {want}");
+        }
+        // Large logits don't overflow
+        let mut big = vec![1000.0, 1001.0, 999.0];
+        softmax_simd(&mut big);
+        assert!(big.iter().all(|p| p.is_finite()) && big[1] > big[0]);
+
+        let up: Vec<f32> = (0..45).map(|i| (i % 5) as f32 - 2.0).collect();
+        let mut got = values.clone();
+        swiglu_simd(&mut got, &up);
+        let mut want = values.clone();
+        crate::tensor::silu(&mut want);
+        crate::tensor::elementwise_mul(&mut want, &up);
+        for (got, want) in got.iter().zip(&want) {
+            assert!(
+                (got - want).abs() < 1e-4 * want.abs().max(1.0),
+                "{got} != {want}"
+            );
+        }
+
+        let weight: Vec<f32> = (0..45).map(|i| 1.0 + i as f32 * 0.01).collect();
+        let (mut got, mut want) = (vec![0.0; 45], vec![0.0; 45]);
+        rmsnorm_simd(&mut got, &values, &weight, 1e-6);
+        crate::tensor::rmsnorm(&mut want, &values, &weight, 1e-6);
+        for (got, want) in got.iter().zip(&want) {
+            assert!((got - want).abs() < 1e-5, "{got} != {want}");
+        }
+        assert_eq!(max_simd(&values), 22.0 * 0.7);
+    }
+
+    #[test]
+    fn test_rope_rotate_simd() {
+        // 13 pairs: one full AVX2 vector, a NEON remainder and a tail
+        let lo: Vec<f32> = (0..13).map(|i| i as f32 * 0.3 - 2.0).collect();
+        let hi: Vec<f32> = (0..13).map(|i| 1.5 - i as f32 * 0.2).collect();
+        let angles: Vec<f32> = (0..13).map(|i| i as f32 * 0.7).collect();
+        let cos: Vec<f32> = angles.iter().map(|a| a.cos()).collect();
+        let sin: Vec<f32> = angles.iter().map(|a| a.sin()).collect();
+
+        let (mut got_lo, mut got_hi) = (lo.clone(), hi.clone());
+        rope_rotate_simd(&mut got_lo, &mut got_hi, &cos, &sin);
+        for i in 0..13 {
+            let want_lo = lo[i] * cos[i] - hi[i] * sin[i];
+            let want_hi = lo[i] * sin[i] + hi[i] * cos[i];
+            assert!((got_lo[i] - want_lo).abs() < 1e-5, "{i}");
+            assert!((got_hi[i] - want_hi).abs() < 1e-5, "{i}");
+        }
     }
 
     #[test]
@@ -2,6 +2,9 @@
 //!
 //! Accelerates dot product, matmul, and other tensor ops
 //! on ARM64 processors (Apple Silicon, Raspberry Pi 4/5).
+//!
+//! NEON is baseline on aarch64, so unlike the x86 kernels these need no
+//! runtime check.
 
 #[cfg(target_arch = "aarch64")]
 use std::arch::aarch64::*;
@@ -35,6 +38,231 @@ pub fn dot_product_neon(a: &[f32], b: &[f32]) -> f32 {
     }
 }
 
+/// `acc + x[..16] * q` for 16 signed bytes `q`, widened 4 at a time.
+#[cfg(target_arch = "aarch64")]
+#[inline]
+fn fma_i8x16(acc: float32x4_t, q: int8x16_t, x: &[f32]) -> float32x4_t {
+    debug_assert!(x.len() >= 16);
+    unsafe {
+        let lo = vmovl_s8(vget_low_s8(q));
+        let hi = vmovl_s8(vget_high_s8(q));
+        let mut acc = acc;
+        for (j, half) in [lo, hi].into_iter().enumerate() {
+            let a = vcvtq_f32_s32(vmovl_s16(vget_low_s16(half)));
+            let b = vcvtq_f32_s32(vmovl_s16(vget_high_s16(half)));
+            acc = vfmaq_f32(acc, a, vld1q_f32(x.as_ptr().add(j * 8)));
+            acc = vfmaq_f32(acc, b, vld1q_f32(x.as_ptr().add(j * 8 + 4)));
+        }
+        acc
+    }
+}
+
+/// NEON fused Q4_0 dot product: nibbles are unpacked and widened in
+/// registers, one scale multiply per block.
+#[cfg(target_arch = "aarch64")]
+pub fn dot_q4_0_neon(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 18 * 32, x.len());
+    unsafe {
+        let mut sum = vdupq_n_f32(0.0);
+        let mask = vdupq_n_u8(0x0F);
+        let eight = vdupq_n_s8(8);
+        for (block, x) in row.chunks_exact(18).zip(x.chunks_exact(32)) {
+            let scale = half::f16::from_le_bytes([block[0], block[1]]).to_f32();
+            let bytes = vld1q_u8(block.as_ptr().add(2));
+            let lo = vsubq_s8(vreinterpretq_s8_u8(vandq_u8(bytes, mask)), eight);
+            let hi = vsubq_s8(vreinterpretq_s8_u8(vshrq_n_u8::<4>(bytes)), eight);
+            let acc = fma_i8x16(vdupq_n_f32(0.0), lo, x);
+            let acc = fma_i8x16(acc, hi, &x[16..]);
+            sum = vfmaq_n_f32(sum, acc, scale);
+        }
+        vaddvq_f32(sum)
+    }
+}
+
+/// NEON fused Q8_0 dot product.
+#[cfg(target_arch = "aarch64")]
+pub fn dot_q8_0_neon(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 34 * 32, x.len());
+    unsafe {
+        let mut sum = vdupq_n_f32(0.0);
+        for (block, x) in row.chunks_exact(34).zip(x.chunks_exact(32)) {
+            let scale = half::f16::from_le_bytes([block[0], block[1]]).to_f32();
+            let q = block.as_ptr().add(2) as *const i8;
+            let acc = fma_i8x16(vdupq_n_f32(0.0), vld1q_s8(q), x);
+            let acc = fma_i8x16(acc, vld1q_s8(q.add(16)), &x[16..]);
+            sum = vfmaq_n_f32(sum, acc, scale);
+        }
+        vaddvq_f32(sum)
+    }
+}
+
+/// NEON dot product of an F32 row read straight from its (possibly
+/// unaligned) bytes.
+#[cfg(target_arch = "aarch64")]
+pub fn dot_f32_neon(row: &[u8], x: &[f32]) -> f32 {
+    debug_assert_eq!(row.len() / 4, x.len());
+    let chunks = x.len() / 4;
+    unsafe {
+        let mut sum_vec = vdupq_n_f32(0.0);
+        for i in 0..chunks {
+            // Loaded as bytes: mapped tensors needn't be 4-byte aligned
+            let va = vreinterpretq_f32_u8(vld1q_u8(row.as_ptr().add(i * 16)));
+            let vb = vld1q_f32(x.as_ptr().add(i * 4));
+            sum_vec = vfmaq_f32(sum_vec, va, vb);
+        }
+        vaddvq_f32(sum_vec) + super::fused::dot_f32(&row[chunks * 16..], &x[chunks * 4..])
+    }
+}
+
+/// NEON RMSNorm: the sum of squares and the normalize-and-scale pass
+/// both 4 floats per iteration.
+#[cfg(target_arch = "aarch64")]
+pub fn rmsnorm_neon(output: &mut [f32], input: &[f32], weight: &[f32], eps: f32) {
+    let n = input.len();
+    debug_assert_eq!(output.len(), n);
+    debug_assert_eq!(weight.len(), n);
+    let chunks = n / 4;
+
+    let ss = dot_product_neon(input, input) / n as f32;
+    let inv_rms = 1.0 / (ss + eps).sqrt();
+
+    unsafe {
+        for i in 0..chunks {
+            let offset = i * 4;
+            let v = vmulq_n_f32(vld1q_f32(input.as_ptr().add(offset)), inv_rms);
+            let w = vld1q_f32(weight.as_ptr().add(offset));
+            vst1q_f32(output.as_mut_ptr().add(offset), vmulq_f32(v, w));
+        }
+    }
+    for i in (chunks * 4)..n {
+        output[i] = input[i] * inv_rms * weight[i];
+    }
+}
+
+/// e^x for each lane, as [`super::avx2`]'s `exp_ps`: x = n·ln2 + r, e^r
+/// from Cephes' degree-5 polynomial, 2^n built in the exponent bits.
+#[cfg(target_arch = "aarch64")]
+#[inline]
+#[allow(unused_unsafe)] // the arithmetic intrinsics are safe on newer toolchains
+fn exp_neon(x: float32x4_t) -> float32x4_t {
+    unsafe {
+        let x = vminq_f32(vmaxq_f32(x, vdupq_n_f32(-88.376)), vdupq_n_f32(88.376));
+        let n = vrndmq_f32(vfmaq_n_f32(vdupq_n_f32(0.5), x, std::f32::consts::LOG2_E));
+        let r = vfmsq_f32(x, n, vdupq_n_f32(0.693_359_4));
+        let r = vfmsq_f32(r, n, vdupq_n_f32(-2.121_944_4e-4));
+
+        let mut y = vdupq_n_f32(1.987_569_1e-4);
+        for c in [
+            1.398_199_9e-3,
+            8.333_452e-3,
+            4.166_579_6e-2,
+            0.166_666_65,
+            0.5,
+        ] {
+            y = vfmaq_f32(vdupq_n_f32(c), y, r);
+        }
+        let y = vfmaq_f32(vaddq_f32(r, vdupq_n_f32(1.0)), y, vmulq_f32(r, r));
+
+        let pow2n = vshlq_n_s32::<23>(vaddq_s32(vcvtq_s32_f32(n), vdupq_n_s32(127)));
+        vmulq_f32(y, vreinterpretq_f32_s32(pow2n))
+    }
+}
+
+/// NEON maximum of `values` (−∞ when empty).
+#[cfg(target_arch = "aarch64")]
+pub fn max_neon(values: &[f32]) -> f32 {
+    let chunks = values.len() / 4;
+    let mut max = f32::NEG_INFINITY;
+    unsafe {
+        if chunks > 0 {
+            let mut max_vec = vld1q_f32(values.as_ptr());
+            for i in 1..chunks {
+                max_vec = vmaxq_f32(max_vec, vld1q_f32(values.as_ptr().add(i * 4)));
+            }
+            max = vmaxvq_f32(max_vec);
+        }
+    }
+    values[chunks * 4..].iter().copied().fold(max, f32::max)
+}
+
+/// NEON `values[i] = e^(values[i] − max)`, returning their sum.
+#[cfg(target_arch = "aarch64")]
+pub fn exp_sub_sum_neon(values: &mut [f32], max: f32) -> f32 {
+    let chunks = values.len() / 4;
+    let mut sum = 0.0f32;
+    unsafe {
+        let max_vec = vdupq_n_f32(max);
+        let mut sum_vec = vdupq_n_f32(0.0);
+        for i in 0..chunks {
+            let ptr = values.as_mut_ptr().add(i * 4);
+            let e = exp_neon(vsubq_f32(vld1q_f32(ptr), max_vec));
+            vst1q_f32(ptr, e);
+            sum_vec = vaddq_f32(sum_vec, e);
+        }
+        sum += vaddvq_f32(sum_vec);
+    }
+    for v in &mut values[chunks * 4..] {
+        *v = (*v - max).exp();
+        sum += *v;
+    }
+    sum
+}
+
+/// NEON SwiGLU: `gate[i] = silu(gate[i]) * up[i]`, or plain SiLU of
+/// `gate` when `up` is `None`.
+#[cfg(target_arch = "aarch64")]
+pub fn swiglu_neon(gate: &mut [f32], up: Option<&[f32]>) {
+    debug_assert!(up.is_none_or(|up| up.len() == gate.len()));
+    let chunks = gate.len() / 4;
+    unsafe {
+        for i in 0..chunks {
+            let ptr = gate.as_mut_ptr().add(i * 4);
+            let x = vld1q_f32(ptr);
+            // x / (1 + e^−x)
+            let mut y = vdivq_f32(x, vaddq_f32(vdupq_n_f32(1.0), exp_neon(vnegq_f32(x))));
+            if let Some(up) = up {
+                y = vmulq_f32(y, vld1q_f32(up.as_ptr().add(i * 4)));
+            }
+            vst1q_f32(ptr, y);
+        }
+    }
+    for j in chunks * 4..gate.len() {
+        let x = gate[j];
+        gate[j] = x / (1.0 + (-x).exp()) * up.map_or(1.0, |up| up[j]);
+    }
+}
+
+/// NEON RoPE rotation of split halves: each pair `(lo[i], hi[i])` is
+/// rotated by the angle whose cosine and sine are `cos[i]` and `sin[i]`.
+#[cfg(target_arch = "aarch64")]
+pub fn rope_rotate_neon(lo: &mut [f32], hi: &mut [f32], cos: &[f32], sin: &[f32]) {
+    let n = lo.len();
+    debug_assert!(hi.len() == n && cos.len() == n && sin.len() == n);
+    let chunks = n / 4;
+    unsafe {
+        for i in 0..chunks {
+            let offset = i * 4;
+            let x0 = vld1q_f32(lo.as_ptr().add(offset));
+            let x1 = vld1q_f32(hi.as_ptr().add(offset));
+            let c = vld1q_f32(cos.as_ptr().add(offset));
+            let s = vld1q_f32(sin.as_ptr().add(offset));
+            vst1q_f32(
+                lo.as_mut_ptr().add(offset),
+                vfmsq_f32(vmulq_f32(x0, c), x1, s),
+            );
+            vst1q_f32(
+                hi.as_mut_ptr().add(offset),
+                vfmaq_f32(vmulq_f32(x1, c), x0, s),
+            );
+        }
+    }
+    for i in (chunks * 4)..n {
+        let (x0, x1) = (lo[i], hi[i]);
+        lo[i] = x0 * cos[i] - x1 * sin[i];
+        hi[i] = x0 * sin[i] + x1 * cos[i];
+    }
+}
+
 /// Scalar fallback for non-aarch64.
 #[cfg(not(target_arch = "aarch64"))]
 pub fn dot_product_neon(a: &[f32], b: &[f32]) -> f32 {
@@ -57,6 +285,51 @@ mod tests {
         );
     }
 
+    #[cfg(target_arch = "aarch64")]
+    #[test]
+    fn test_neon_kernels_match_scalar() {
+        use crate::gguf::GgmlType;
+        let values: Vec<f32> = (0..96)
+            .map(|i| ((i * 37) % 29) as f32 / 29.0 - 0.5)
+            .collect();
+        let x: Vec<f32> = (0..96).map(|i| (i % 7) as f32 - 3.0).collect();
+        for (ggml_type, kernel) in [
+            (GgmlType::F32, dot_f32_neon as fn(&[u8], &[f32]) -> f32),
+            (GgmlType::Q4_0, dot_q4_0_neon),
+            (GgmlType::Q8_0, dot_q8_0_neon),
+        ] {
+            let mut row = Vec::new();
+            crate::quant::quantize_row(&values, &mut row, ggml_type).unwrap();
+            let mut dequantized = vec![0.0; 96];
+            crate::quant::dequantize_row(&row, &mut dequantized, 96, ggml_type).unwrap();
+            let want = crate::tensor::dot_product(&dequantized, &x);
+            let got = kernel(&row, &x);
+            assert!((got - want).abs() < 1e-3, "{ggml_type:?}: {got} != {want}");
+        }
+
+        let mut got = values[..37].to_vec();
+        let max = max_neon(&got);
+        assert_eq!(max, got.iter().copied().fold(f32::NEG_INFINITY, f32::max));
+        let sum = exp_sub_sum_neon(&mut got, max);
+        for (got, &v) in got.iter().zip(&values) {
+            assert!((got - (v - max).exp()).abs() < 1e-6);
+        }
+        assert!((sum - got.iter().sum::<f32>()).abs() < 1e-4);
+        let mut got = values[..37].to_vec();
+        swiglu_neon(&mut got, Some(&x[..37]));
+        for ((got, &v), &u) in got.iter().zip(&values).zip(&x) {
+            assert!((got - v / (1.0 + (-v).exp()) * u).abs() < 1e-5);
+        }
+
+        let weight: Vec<f32> = (0..37).map(|i| 1.0 + i as f32 * 0.01).collect();
+        let (mut got, mut want) = (vec![0.0; 37], vec![0.0; 37]);
+        rmsnorm_neon(&mut got, &values[..37], &weight, 1e-6);
+        crate::tensor::rmsnorm(&mut want, &values[..37], &weight, 1e-6);
+        for (got, want) in got.iter().zip(&want) {
+            assert!((got - want).abs() < 1e-5, "{got} != {want}");
+        }
+    }
+
     #[test]
     fn test_neon_dot_product_odd_length() {
         let a = vec![1.0, 2.0, 3.0, 4.0, 5.0]; // 5 elements (not multiple of 4)
@@ -0,0 +1,46 @@
+//! WebAssembly SIMD128 intrinsics for dot product.
+//!
+//! Needs `-C target-feature=+simd128`; every current browser runs it.
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+use std::arch::wasm32::*;
+
+/// SIMD128-accelerated dot product (4 floats per iteration).
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub fn dot_product_wasm(a: &[f32], b: &[f32]) -> f32 {
+    debug_assert_eq!(a.len(), b.len());
+    let n = a.len();
+    let chunks = n / 4;
+
+    let mut sum_vec = f32x4_splat(0.0);
+    for i in 0..chunks {
+        let offset = i * 4;
+        // Unaligned loads are fine in wasm; v128_load has no alignment requirement.
+        let (va, vb) = unsafe {
+            (
+                v128_load(a.as_ptr().add(offset) as *const v128),
+                v128_load(b.as_ptr().add(offset) as *const v128),
+            )
+        };
+        sum_vec = f32x4_add(sum_vec, f32x4_mul(va, vb));
+    }
+
+    // Horizontal sum
+    let mut sum = f32x4_extract_lane::<0>(sum_vec)
+        + f32x4_extract_lane::<1>(sum_vec)
+        + f32x4_extract_lane::<2>(sum_vec)
+        + f32x4_extract_lane::<3>(sum_vec);
+
+    // Tail
+    for i in (chunks * 4)..n {
+        sum += a[i] * b[i];
+    }
+
+    sum
+}
+
+/// Scalar fallback.
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+pub fn dot_product_wasm(a: &[f32], b: &[f32]) -> f32 {
+    crate::tensor::dot_product(a, b)
+}
@@ -0,0 +1,259 @@
+//! Engine snapshots: the state a conversation has built up, saved to a file
+//! so it outlives the process or moves to another host.
+//!
+//! A snapshot holds the brain config, the sampler settings and random
+//! state, the prompt cache with the keys and values of its positions, and
+//! the session metadata. Only the selected KV cache slot is saved or
+//! replaced, see [`BrainEngine::select_slot`]. Model weights are not included: restoring needs
+//! the same model file loaded.
+//!
+//! File layout (little-endian): `BCSS`, version `u32`, header length `u64`,
+//! the JSON header, then per layer the key rows and the value rows of the
+//! cached positions as `f32`.
+
+use crate::sampler::SamplerConfig;
+use crate::{BrainConfig, BrainEngine};
+use bizclaw_core::error::{BizClawError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"BCSS";
+const VERSION: u32 = 1;
+/// Headers are a few KB; anything larger isn't a snapshot.
+const MAX_HEADER: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    /// Model file name, informational: hosts may keep it elsewhere.
+    model: String,
+    file_size: u64,
+    n_layers: usize,
+    kv_dim: usize,
+    vocab_size: u32,
+    config: BrainConfig,
+    sampler: SamplerConfig,
+    rng_state: u64,
+    /// The prompt cache; the rows that follow are these positions.
+    tokens: Vec<u32>,
+    session: BTreeMap<String, String>,
+}
+
+fn corrupt(path: &Path, what: &str) -> BizClawError {
+    BizClawError::Brain(format!("Snapshot {} is corrupt: {what}", path.display()))
+}
+
+/// Split a snapshot file into its header and cache rows.
+fn parse<'a>(path: &Path, bytes: &'a [u8]) -> Result<(Header, &'a [u8])> {
+    if bytes.len() < 16 || &bytes[..4] != MAGIC {
+        return Err(BizClawError::Brain(format!(
+            "{} is not a BizClaw snapshot",
+            path.display()
+        )));
+    }
+    let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    if version != VERSION {
+        return Err(BizClawError::Brain(format!(
+            "Snapshot {} has version {version}; this build reads {VERSION}",
+            path.display()
+        )));
+    }
+    let header_len = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+    if header_len > MAX_HEADER || header_len > (bytes.len() - 16) as u64 {
+        return Err(corrupt(path, "truncated header"));
+    }
+    let (header, body) = bytes[16..].split_at(header_len as usize);
+    let header = serde_json::from_slice(header).map_err(|e| corrupt(path, &e.to_string()))?;
+    Ok((header, body))
+}
+
+impl BrainEngine {
+    /// Caller-defined metadata saved with snapshots, e.g. a session id.
+    pub fn session_metadata(&self) -> &BTreeMap<String, String> {
+        &self.session
+    }
+
+    pub fn set_session_metadata(&mut self, key: &str, value: &str) {
+        self.session.insert(key.to_string(), value.to_string());
+    }
+
+    /// Save the engine state to `path`, replacing it atomically.
+    pub fn snapshot(&self, path: &Path) -> Result<()> {
+        let model = self
+            .model
+            .as_ref()
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?;
+        let n_layers = model.params.n_layers as usize;
+        let kv_dim = model.params.kv_dim() as usize;
+        let header = serde_json::to_vec(&Header {
+            model: model
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            file_size: model.mmap_model.file_size() as u64,
+            n_layers,
+            kv_dim,
+            vocab_size: model.params.vocab_size,
+            config: self.config.clone(),
+            sampler: model.sampler.config().clone(),
+            rng_state: model.sampler.rng().state(),
+            tokens: model.cached.clone(),
+            session: self.session.clone(),
+        })?;
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = std::path::PathBuf::from(tmp);
+        let mut out = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(header.len() as u64).to_le_bytes())?;
+        out.write_all(&header)?;
+        // A sliding-window cache holds at most its window, in slot order.
+        // Rows are saved as f32 whatever the cache stores.
+        let positions = model.cached.len();
+        let (mut key_rows, mut value_rows) = (Vec::new(), Vec::new());
+        for layer in 0..n_layers {
+            for rows in [
+                model.kv_cache.keys(layer, positions, &mut key_rows),
+                model.kv_cache.values(layer, positions, &mut value_rows),
+            ] {
+                for value in rows {
+                    out.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        tracing::info!(
+            "Snapshot saved: {} ({positions} cached tokens)",
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Restore a [`snapshot`](Self::snapshot) taken with the loaded model.
+    /// The engine is left unchanged if it fails.
+    pub fn restore(&mut self, path: &Path) -> Result<()> {
+        let bytes = std::fs::read(path)?;
+        let model = self
+            .model
+            .as_mut()
+            .ok_or_else(|| BizClawError::ModelNotLoaded("call load_model first".into()))?;
+        let (header, body) = parse(path, &bytes)?;
+
+        let n_layers = model.params.n_layers as usize;
+        let kv_dim = model.params.kv_dim() as usize;
+        if header.n_layers != n_layers
+            || header.kv_dim != kv_dim
+            || header.vocab_size != model.params.vocab_size
+            || header.file_size != model.mmap_model.file_size() as u64
+        {
+            return Err(BizClawError::Brain(format!(
+                "Snapshot {} was taken with a different model ({})",
+                path.display(),
+                header.model
+            )));
+        }
+        let positions = header.tokens.len();
+        if positions > model.params.max_seq_len as usize {
+            return Err(BizClawError::ContextOverflow(format!(
+                "Snapshot holds {positions} tokens; the context is {}",
+                model.params.max_seq_len
+            )));
+        }
+        let slots = positions.min(model.kv_cache.capacity());
+        let row_bytes = kv_dim * 4;
+        if body.len() != 2 * n_layers * slots * row_bytes {
+            return Err(corrupt(path, "cache size doesn't match its header"));
+        }
+
+        let mut rows = body.chunks_exact(row_bytes).map(|row| {
+            row.chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect::<Vec<f32>>()
+        });
+        // Replaced wholesale, so copies of the cache know to resync
+        model.kv_cache.reset();
+        for layer in 0..n_layers {
+            for pos in 0..slots {
+                model.kv_cache.store_key(layer, pos, &rows.next().unwrap());
+            }
+            for pos in 0..slots {
+                model
+                    .kv_cache
+                    .store_value(layer, pos, &rows.next().unwrap());
+            }
+        }
+        model.cached = header.tokens;
+        model.sampler.set_config(header.sampler);
+        model.sampler.set_rng_state(header.rng_state);
+        // Threads, tuning, the backend and the KV cache layout suit the
+        // host, not the session.
+        self.config = BrainConfig {
+            threads: self.config.threads,
+            backend: self.config.backend,
+            kv_cache: self.config.kv_cache,
+            slots: self.config.slots,
+            tuning_file: self.config.tuning_file.take(),
+            ..header.config
+        };
+        self.session = header.session;
+        tracing::info!(
+            "Snapshot restored: {} ({positions} cached tokens)",
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(version: u32, header: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(version.to_le_bytes());
+        bytes.extend((header.len() as u64).to_le_bytes());
+        bytes.extend(header);
+        bytes.extend(body);
+        bytes
+    }
+
+    #[test]
+    fn test_parse() {
+        let path = Path::new("session.bcss");
+        let header = serde_json::to_vec(&Header {
+            model: "tiny.gguf".into(),
+            file_size: 1024,
+            n_layers: 1,
+            kv_dim: 2,
+            vocab_size: 32,
+            config: BrainConfig::default(),
+            sampler: SamplerConfig::default(),
+            rng_state: 7,
+            tokens: vec![1, 5],
+            session: BTreeMap::from([("session_id".into(), "abc".into())]),
+        })
+        .unwrap();
+        let rows = [0u8; 2 * 2 * 2 * 4];
+
+        let bytes = file(VERSION, &header, &rows);
+        let (parsed, body) = parse(path, &bytes).unwrap();
+        assert_eq!(parsed.tokens, vec![1, 5]);
+        assert_eq!(parsed.rng_state, 7);
+        assert_eq!(parsed.session["session_id"], "abc");
+        assert_eq!(body.len(), rows.len());
+
+        assert!(parse(path, b"GGUF and more bytes").is_err());
+        assert!(parse(path, &file(VERSION + 1, &header, &rows)).is_err());
+        // The header length points past the end of the file.
+        let mut truncated = file(VERSION, &header, &[]);
+        truncated.truncate(truncated.len() - 1);
+        assert!(parse(path, &truncated).is_err());
+        assert!(parse(path, &file(VERSION, b"{not json", &rows)).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! Host capabilities the engine's speed depends on: SIMD support, memory
+//! and process limits. Backs `bizclaw doctor`.
+//!
+//! Memory and limits are read from `/proc`, so they are only known on Linux.
+
+/// CPU features the matmul kernels can use, and whether this CPU has them.
+pub fn simd_features() -> Vec<(&'static str, bool)> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        vec![
+            ("sse2", is_x86_feature_detected!("sse2")),
+            ("avx", is_x86_feature_detected!("avx")),
+            ("avx2", is_x86_feature_detected!("avx2")),
+            ("fma", is_x86_feature_detected!("fma")),
+            ("f16c", is_x86_feature_detected!("f16c")),
+            ("avx512f", is_x86_feature_detected!("avx512f")),
+        ]
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    {
+        use std::arch::is_aarch64_feature_detected;
+        vec![
+            ("neon", is_aarch64_feature_detected!("neon")),
+            ("dotprod", is_aarch64_feature_detected!("dotprod")),
+            ("fp16", is_aarch64_feature_detected!("fp16")),
+        ]
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        Vec::new()
+    }
+}
+
+/// The features this binary was compiled to use. The hand-written kernels
+/// in [`crate::simd`] pick AVX2 or AVX-512 at runtime regardless; the rest
+/// of the code is vectorized by the compiler only as far as these allow.
+pub fn compiled_features() -> Vec<&'static str> {
+    [
+        ("sse2", cfg!(target_feature = "sse2")),
+        ("avx", cfg!(target_feature = "avx")),
+        ("avx2", cfg!(target_feature = "avx2")),
+        ("fma", cfg!(target_feature = "fma")),
+        ("f16c", cfg!(target_feature = "f16c")),
+        ("avx512f", cfg!(target_feature = "avx512f")),
+        ("neon", cfg!(target_feature = "neon")),
+        ("dotprod", cfg!(target_feature = "dotprod")),
+        ("fp16", cfg!(target_feature = "fp16")),
+        ("simd128", cfg!(target_feature = "simd128")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+/// Physical memory, in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryInfo {
+    pub total: u64,
+    /// Free plus reclaimable page cache.
+    pub available: u64,
+}
+
+pub fn memory_info() -> Option<MemoryInfo> {
+    parse_meminfo(&std::fs::read_to_string("/proc/meminfo").ok()?)
+}
+
+fn parse_meminfo(meminfo: &str) -> Option<MemoryInfo> {
+    let kb = |key: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))?
+            .split_whitespace()
+            .next()?
+            .parse::<u64>()
+            .ok()
+            .map(|kb| kb * 1024)
+    };
+    Some(MemoryInfo {
+        total: kb("MemTotal")?,
+        available: kb("MemAvailable").or_else(|| kb("MemFree"))?,
+    })
+}
+
+/// A soft resource limit of this process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Unlimited,
+    Bytes(u64),
+}
+
+/// How much memory the process may lock: mapped weights beyond it can be
+/// paged out under memory pressure.
+pub fn mlock_limit() -> Option<Limit> {
+    parse_limit(
+        &std::fs::read_to_string("/proc/self/limits").ok()?,
+        "Max locked memory",
+    )
+}
+
+/// How much address space the process may map.
+pub fn address_space_limit() -> Option<Limit> {
+    parse_limit(
+        &std::fs::read_to_string("/proc/self/limits").ok()?,
+        "Max address space",
+    )
+}
+
+fn parse_limit(limits: &str, name: &str) -> Option<Limit> {
+    let soft = limits
+        .lines()
+        .find_map(|line| line.strip_prefix(name))?
+        .split_whitespace()
+        .next()?;
+    match soft {
+        "unlimited" => Some(Limit::Unlimited),
+        bytes => bytes.parse().ok().map(Limit::Bytes),
+    }
+}
+
+/// The most memory maps a process may have (`vm.max_map_count`).
+pub fn max_map_count() -> Option<u64> {
+    std::fs::read_to_string("/proc/sys/vm/max_map_count")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// The CPU's model name, e.g. `AMD Ryzen 7 5800X 8-Core Processor`. Read
+/// from `/proc/cpuinfo`, so only known on Linux.
+pub fn cpu_model() -> Option<String> {
+    parse_cpu_model(&std::fs::read_to_string("/proc/cpuinfo").ok()?)
+}
+
+fn parse_cpu_model(cpuinfo: &str) -> Option<String> {
+    // x86 names the model; ARM boards often only name the board
+    // (`Model`) or the core by implementer and part number.
+    let field = |key: &str| {
+        cpuinfo.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            (name.trim() == key && !value.trim().is_empty()).then(|| value.trim().to_string())
+        })
+    };
+    field("model name").or_else(|| field("Model")).or_else(|| {
+        Some(format!(
+            "{} {}",
+            field("CPU implementer")?,
+            field("CPU part")?
+        ))
+    })
+}
+
+/// One logical CPU per physical core — the first of its SMT siblings —
+/// in CPU order, from `/sys`, so only known on Linux. On hybrid CPUs the
+/// E-cores, which have no siblings, are included after the P-cores.
+pub fn physical_cores() -> Vec<usize> {
+    let Ok(online) = std::fs::read_to_string("/sys/devices/system/cpu/online") else {
+        return Vec::new();
+    };
+    parse_cpu_list(&online)
+        .into_iter()
+        .filter(|&cpu| {
+            let siblings = std::fs::read_to_string(format!(
+                "/sys/devices/system/cpu/cpu{cpu}/topology/thread_siblings_list"
+            ))
+            .map(|list| parse_cpu_list(&list))
+            .unwrap_or_default();
+            siblings.iter().min().is_none_or(|&first| first == cpu)
+        })
+        .collect()
+}
+
+/// Online NUMA nodes and the CPUs of each, from `/sys`, so only known on
+/// Linux. Machines without NUMA report a single node 0.
+pub fn numa_nodes() -> Vec<(usize, Vec<usize>)> {
+    let Ok(online) = std::fs::read_to_string("/sys/devices/system/node/online") else {
+        return Vec::new();
+    };
+    parse_cpu_list(&online)
+        .into_iter()
+        .map(|node| {
+            let cpus =
+                std::fs::read_to_string(format!("/sys/devices/system/node/node{node}/cpulist"))
+                    .map(|list| parse_cpu_list(&list))
+                    .unwrap_or_default();
+            (node, cpus)
+        })
+        .collect()
+}
+
+/// A kernel CPU (or node) list such as `0-3,8,10-11`.
+fn parse_cpu_list(list: &str) -> Vec<usize> {
+    list.trim()
+        .split(',')
+        .filter_map(|range| match range.split_once('-') {
+            Some((first, last)) => Some(first.parse().ok()?..=last.parse().ok()?),
+            None => range.parse().ok().map(|cpu| cpu..=cpu),
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_files() {
+        let meminfo = "MemTotal:       16318412 kB\nMemFree:          811064 kB\n\
+                       MemAvailable:    9120404 kB\n";
+        assert_eq!(
+            parse_meminfo(meminfo),
+            Some(MemoryInfo {
+                total: 16318412 * 1024,
+                available: 9120404 * 1024,
+            })
+        );
+        assert_eq!(parse_meminfo("MemFree: 1 kB\n"), None);
+
+        let limits = "Limit                     Soft Limit           Hard Limit           Units     \n\
+                      Max locked memory         8388608              8388608              bytes     \n\
+                      Max address space         unlimited            unlimited            bytes     \n";
+        assert_eq!(
+            parse_limit(limits, "Max locked memory"),
+            Some(Limit::Bytes(8388608))
+        );
+        assert_eq!(
+            parse_limit(limits, "Max address space"),
+            Some(Limit::Unlimited)
+        );
+        assert_eq!(parse_limit(limits, "Max open files"), None);
+    }
+
+    #[test]
+    fn test_parse_cpu_model() {
+        let x86 = "processor\t: 0\nvendor_id\t: GenuineIntel\n\
+                   model name\t: Intel(R) Core(TM) i5-8250U CPU @ 1.60GHz\n";
+        assert_eq!(
+            parse_cpu_model(x86).as_deref(),
+            Some("Intel(R) Core(TM) i5-8250U CPU @ 1.60GHz")
+        );
+        let pi = "processor\t: 0\nCPU implementer\t: 0x41\nCPU part\t: 0xd08\n\n\
+                  Model\t\t: Raspberry Pi 4 Model B Rev 1.4\n";
+        assert_eq!(
+            parse_cpu_model(pi).as_deref(),
+            Some("Raspberry Pi 4 Model B Rev 1.4")
+        );
+        let arm = "CPU implementer\t: 0x41\nCPU part\t: 0xd0b\n";
+        assert_eq!(parse_cpu_model(arm).as_deref(), Some("0x41 0xd0b"));
+        assert_eq!(parse_cpu_model(""), None);
+    }
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11\n"), [0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(parse_cpu_list("5"), [5]);
+        assert_eq!(parse_cpu_list(""), Vec::<usize>::new());
+    }
+}
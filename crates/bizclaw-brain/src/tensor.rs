@@ -58,6 +58,18 @@ pub fn softmax(values: &mut [f32]) {
     }
 }
 
+/// Soft-capping (Gemma-2): cap * tanh(x / cap), squashing values smoothly
+/// into (-cap, cap). A cap of 0 leaves them unchanged.
+pub fn softcap(values: &mut [f32], cap: f32) {
+    if cap <= 0.0 {
+        return;
+    }
+    let inv_cap = 1.0 / cap;
+    for v in values.iter_mut() {
+        *v = cap * (*v * inv_cap).tanh();
+    }
+}
+
 /// SiLU (Swish) activation: silu(x) = x * sigmoid(x) = x / (1 + exp(-x))
 pub fn silu(values: &mut [f32]) {
     for v in values.iter_mut() {
@@ -121,6 +133,20 @@ mod tests {
         assert!((output[1] - 15.0).abs() < 1e-6); // 4+5+6
     }
 
+    #[test]
+    fn test_softcap() {
+        let mut v = vec![0.0, 1.0, -1.0, 1000.0, -1000.0];
+        softcap(&mut v, 30.0);
+        assert_eq!(v[0], 0.0);
+        // Small values barely move; large ones saturate at the cap.
+        assert!((v[1] - 1.0).abs() < 1e-3 && (v[2] + 1.0).abs() < 1e-3);
+        assert!((v[3] - 30.0).abs() < 1e-4 && (v[4] + 30.0).abs() < 1e-4);
+
+        let mut uncapped = vec![100.0, -5.0];
+        softcap(&mut uncapped, 0.0);
+        assert_eq!(uncapped, [100.0, -5.0]);
+    }
+
     #[test]
     fn test_silu() {
         let mut v = vec![0.0, 1.0, -1.0];
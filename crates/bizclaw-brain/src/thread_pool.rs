@@ -1,5 +1,12 @@
 //! Multi-threaded matrix multiply using rayon.
+//!
+//! Without the `threads` feature (the browser build) rows are computed on
+//! the calling thread.
 
+use crate::gguf::GgmlType;
+use crate::tuning::MatmulTuning;
+use bizclaw_core::error::{BizClawError, Result};
+#[cfg(feature = "threads")]
 use rayon::prelude::*;
 
 /// Parallel matrix-vector multiply: output = mat * vec.
@@ -10,21 +17,292 @@ pub fn matmul_parallel(output: &mut [f32], mat: &[f32], vec_in: &[f32], rows: us
     debug_assert_eq!(vec_in.len(), cols);
     debug_assert_eq!(output.len(), rows);
 
+    #[cfg(feature = "threads")]
     output.par_iter_mut().enumerate().for_each(|(i, out)| {
         let row = &mat[i * cols..(i + 1) * cols];
         *out = crate::tensor::dot_product(row, vec_in);
     });
+
+    #[cfg(not(feature = "threads"))]
+    crate::tensor::matmul(output, mat, vec_in, rows, cols);
+}
+
+/// Matrix-vector multiply with quantized weights: output = mat * vec.
+/// `data` holds `rows` rows of `cols` values in `ggml_type`.
+///
+/// A [`matmul_batch`] of one.
+pub fn matmul_quantized(
+    output: &mut [f32],
+    data: &[u8],
+    ggml_type: GgmlType,
+    vec_in: &[f32],
+    rows: usize,
+    cols: usize,
+    tuning: &MatmulTuning,
+) -> Result<()> {
+    debug_assert_eq!(vec_in.len(), cols);
+    debug_assert_eq!(output.len(), rows);
+    let job = MatmulJob {
+        output,
+        data,
+        ggml_type,
+        input: vec_in,
+    };
+    matmul_batch(vec![job], tuning)
+}
+
+/// One quantized matrix-vector multiply of a [`matmul_batch`]:
+/// `output = data * input`, `data` holding `output.len()` rows of
+/// `input.len()` values in `ggml_type`.
+pub struct MatmulJob<'a> {
+    pub output: &'a mut [f32],
+    pub data: &'a [u8],
+    pub ggml_type: GgmlType,
+    pub input: &'a [f32],
+}
+
+/// Rows of one job, the unit of work threads steal.
+struct Chunk<'a> {
+    output: &'a mut [f32],
+    data: &'a [u8],
+    ggml_type: GgmlType,
+    input: &'a [f32],
+}
+
+/// Bytes of weights per worker below which waking another one costs more
+/// in synchronization than its share of the rows saves.
+const MIN_BYTES_PER_WORKER: usize = 256 * 1024;
+
+/// Run several quantized matmuls as one parallel pass.
+///
+/// The pass gets one worker per [`MIN_BYTES_PER_WORKER`] of weights, up to
+/// the whole pool: a small model's single-token matmuls run on one or two
+/// threads, where waking every core would cost more than it computes, and
+/// large matrices and batches of them spread over all cores. With the
+/// whole pool, the rows of all jobs are cut into chunks of about
+/// `1 / tuning.tasks_per_thread` of a thread's share, whole tiles each, and
+/// threads that finish early steal chunks from busy ones. A matrix in a
+/// slow quant type, or a tall one, then doesn't leave the other cores idle
+/// the way an even split per matrix did.
+///
+/// Types with a fused kernel in [`crate::simd`] are dotted straight from
+/// their blocks; for the rest each chunk dequantizes `tuning.tile_rows`
+/// rows at a time into a scratch tile before taking their dot products, so
+/// no matrix is ever expanded to f32 whole.
+pub fn matmul_batch(jobs: Vec<MatmulJob>, tuning: &MatmulTuning) -> Result<()> {
+    let tile_rows = tuning.tile_rows.max(1);
+    let mut tiled = Vec::with_capacity(jobs.len());
+    for job in jobs {
+        let (rows, cols, ggml_type) = (job.output.len(), job.input.len(), job.ggml_type);
+        let block = ggml_type.block_size();
+        if !cols.is_multiple_of(block) {
+            // Rows don't start on a block boundary: expand the whole matrix.
+            let mut mat = vec![0.0f32; rows * cols];
+            crate::quant::dequantize_row(job.data, &mut mat, rows * cols, ggml_type)?;
+            matmul_parallel(job.output, &mat, job.input, rows, cols);
+            continue;
+        }
+        if job.data.len() < rows * (cols / block * ggml_type.type_size()) {
+            return Err(BizClawError::Inference(format!(
+                "{ggml_type:?} matrix too small: {} bytes for {rows}x{cols}",
+                job.data.len()
+            )));
+        }
+        tiled.push(job);
+    }
+
+    let total_rows: usize = tiled.iter().map(|job| job.output.len()).sum();
+    let bytes = tiled
+        .iter()
+        .map(|job| {
+            job.output.len() * job.input.len() / job.ggml_type.block_size()
+                * job.ggml_type.type_size()
+        })
+        .sum();
+    let workers = workers_for(bytes);
+    // Scaled down, one chunk per worker: any more and idle threads join in
+    let tasks = if workers < num_threads() {
+        workers
+    } else {
+        workers * tuning.tasks_per_thread.max(1)
+    };
+    let chunk_rows = (total_rows.div_ceil(tasks).div_ceil(tile_rows) * tile_rows).max(1);
+    let mut chunks = Vec::with_capacity(total_rows.div_ceil(chunk_rows) + tiled.len());
+    for job in tiled {
+        let cols = job.input.len();
+        let row_bytes = cols / job.ggml_type.block_size() * job.ggml_type.type_size();
+        for (i, output) in job.output.chunks_mut(chunk_rows).enumerate() {
+            let first = i * chunk_rows;
+            chunks.push(Chunk {
+                data: &job.data[first * row_bytes..(first + output.len()) * row_bytes],
+                output,
+                ggml_type: job.ggml_type,
+                input: job.input,
+            });
+        }
+    }
+
+    let run = |chunk: Chunk| -> Result<()> {
+        let (cols, ggml_type) = (chunk.input.len(), chunk.ggml_type);
+        let row_bytes = cols / ggml_type.block_size() * ggml_type.type_size();
+        if crate::simd::has_fused_kernel(ggml_type) {
+            // Dotted straight from the quantized blocks, no f32 tile
+            for (out, row) in chunk
+                .output
+                .iter_mut()
+                .zip(chunk.data.chunks_exact(row_bytes))
+            {
+                *out = crate::simd::dot_quantized(row, chunk.input, ggml_type).unwrap_or_default();
+            }
+            return Ok(());
+        }
+        let mut tile = vec![0.0f32; tile_rows.min(chunk.output.len()) * cols];
+        let tiles = chunk.data.chunks(tile_rows * row_bytes);
+        for (out, bytes) in chunk.output.chunks_mut(tile_rows).zip(tiles) {
+            let n = out.len() * cols;
+            crate::quant::dequantize_row(bytes, &mut tile[..n], n, ggml_type)?;
+            crate::simd::matmul_simd(out, &tile[..n], chunk.input, out.len(), cols);
+        }
+        Ok(())
+    };
+
+    if workers == 1 {
+        return chunks.into_iter().try_for_each(run);
+    }
+    // Split down to single chunks, so an idle thread can steal any of them
+    #[cfg(feature = "threads")]
+    {
+        chunks.into_par_iter().with_max_len(1).try_for_each(run)
+    }
+    #[cfg(not(feature = "threads"))]
+    {
+        chunks.into_iter().try_for_each(run)
+    }
+}
+
+/// Workers worth waking for a pass over `bytes` of weights.
+fn workers_for(bytes: usize) -> usize {
+    (bytes / MIN_BYTES_PER_WORKER).clamp(1, num_threads())
+}
+
+/// Start the global pool with one worker per physical core, each pinned
+/// to its core, up to `threads` of them; with `reserve_runtime_core` the
+/// last core gets no worker, leaving it to the async runtime. Returns the
+/// number of workers.
+///
+/// Must run before anything else uses the pool. Pinning keeps workers off
+/// SMT siblings and stops the scheduler moving them between P- and
+/// E-cores, which makes tokens/sec much steadier on hybrid CPUs. With
+/// [`crate::numa`] placement on, workers alternate between NUMA nodes.
+pub fn pin_workers(threads: usize, reserve_runtime_core: bool) -> Result<usize> {
+    let mut cores = crate::system::physical_cores();
+    if reserve_runtime_core && cores.len() > 1 {
+        cores.pop();
+    }
+    if crate::numa::enabled() {
+        cores = spread_over_nodes(&cores, &crate::system::numa_nodes());
+    }
+    cores.truncate(threads.max(1));
+    if cores.is_empty() {
+        return Err(BizClawError::Brain(
+            "can't pin threads: CPU topology unknown on this platform".into(),
+        ));
+    }
+    start_pinned_pool(cores)
+}
+
+/// `cores` reordered to take one from each NUMA node in turn, so the
+/// first `n` workers are spread as evenly as they can be.
+fn spread_over_nodes(cores: &[usize], nodes: &[(usize, Vec<usize>)]) -> Vec<usize> {
+    let mut per_node: Vec<Vec<usize>> = nodes
+        .iter()
+        .map(|(_, cpus)| cores.iter().copied().filter(|c| cpus.contains(c)).collect())
+        .collect();
+    // Cores the node lists miss go in a group of their own
+    per_node.push(
+        cores
+            .iter()
+            .copied()
+            .filter(|c| !nodes.iter().any(|(_, cpus)| cpus.contains(c)))
+            .collect(),
+    );
+    let longest = per_node.iter().map(Vec::len).max().unwrap_or(0);
+    (0..longest)
+        .flat_map(|i| per_node.iter().filter_map(move |node| node.get(i).copied()))
+        .collect()
+}
+
+#[cfg(all(feature = "threads", target_os = "linux"))]
+fn start_pinned_pool(cores: Vec<usize>) -> Result<usize> {
+    let workers = cores.len();
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(workers)
+        .thread_name(|i| format!("bizclaw-brain-{i}"))
+        .start_handler(move |i| {
+            if let Err(e) = pin_current_thread(cores[i]) {
+                tracing::warn!("Couldn't pin worker {i} to CPU {}: {e}", cores[i]);
+            }
+        })
+        .build_global()
+        .map_err(|e| BizClawError::Brain(format!("can't pin threads: {e}")))?;
+    Ok(workers)
+}
+
+#[cfg(not(all(feature = "threads", target_os = "linux")))]
+fn start_pinned_pool(_cores: Vec<usize>) -> Result<usize> {
+    Err(BizClawError::Brain(
+        "can't pin threads: only supported on Linux with the `threads` feature".into(),
+    ))
+}
+
+#[cfg(all(feature = "threads", target_os = "linux"))]
+fn pin_current_thread(cpu: usize) -> std::io::Result<()> {
+    // SAFETY: `set` is a plain bitmask, fully initialized before use.
+    let status = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if status == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
 }
 
 /// Get the number of available threads.
 pub fn num_threads() -> usize {
-    rayon::current_num_threads()
+    #[cfg(feature = "threads")]
+    {
+        rayon::current_num_threads()
+    }
+    #[cfg(not(feature = "threads"))]
+    {
+        1
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_spread_over_nodes() {
+        let nodes = [(0, vec![0, 1, 2, 3]), (1, vec![4, 5, 6, 7])];
+        assert_eq!(
+            spread_over_nodes(&[0, 1, 2, 4, 5, 6, 7, 9], &nodes),
+            [0, 4, 9, 1, 5, 2, 6, 7]
+        );
+    }
+
+    #[test]
+    fn test_workers_for() {
+        assert_eq!(workers_for(0), 1);
+        assert_eq!(workers_for(MIN_BYTES_PER_WORKER * 2 - 1), 1);
+        assert_eq!(workers_for(MIN_BYTES_PER_WORKER * 2), 2.min(num_threads()));
+        assert_eq!(workers_for(usize::MAX), num_threads());
+    }
+
     #[test]
     fn test_matmul_parallel() {
         let mat = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
@@ -34,4 +312,148 @@ mod tests {
         assert!((output[0] - 6.0).abs() < 1e-6);
         assert!((output[1] - 15.0).abs() < 1e-6);
     }
+
+    #[test]
+    fn test_matmul_quantized_matches_dequantized() {
+        let (rows, cols) = (37, 64);
+        let values: Vec<f32> = (0..rows * cols)
+            .map(|i| ((i * 7919) % 113) as f32 / 113.0 - 0.5)
+            .collect();
+        let vec_in: Vec<f32> = (0..cols).map(|i| (i % 5) as f32 - 2.0).collect();
+        for ggml_type in [GgmlType::F32, GgmlType::F16, GgmlType::Q8_0, GgmlType::Q4_0] {
+            let mut data = Vec::new();
+            crate::quant::quantize_row(&values, &mut data, ggml_type).unwrap();
+            let mut mat = vec![0.0; rows * cols];
+            crate::quant::dequantize_row(&data, &mut mat, rows * cols, ggml_type).unwrap();
+            let mut expected = vec![0.0; rows];
+            crate::tensor::matmul(&mut expected, &mat, &vec_in, rows, cols);
+
+            for (tile_rows, tasks_per_thread) in [(1, 1), (4, 2), (16, 8), (64, 1)] {
+                let tuning = MatmulTuning {
+                    tile_rows,
+                    tasks_per_thread,
+                };
+                let mut output = vec![0.0; rows];
+                matmul_quantized(&mut output, &data, ggml_type, &vec_in, rows, cols, &tuning)
+                    .unwrap();
+                for (got, want) in output.iter().zip(&expected) {
+                    assert!((got - want).abs() < 1e-3, "{ggml_type:?}: {got} != {want}");
+                }
+            }
+        }
+
+        // Several matrices of different types and heights in one batch
+        let mut q8 = Vec::new();
+        crate::quant::quantize_row(&values, &mut q8, GgmlType::Q8_0).unwrap();
+        let q4_values = &values[..5 * cols];
+        let mut q4 = Vec::new();
+        crate::quant::quantize_row(q4_values, &mut q4, GgmlType::Q4_0).unwrap();
+        let (mut out_q8, mut out_q4) = (vec![0.0; rows], vec![0.0; 5]);
+        let jobs = vec![
+            MatmulJob {
+                output: &mut out_q8,
+                data: &q8,
+                ggml_type: GgmlType::Q8_0,
+                input: &vec_in,
+            },
+            MatmulJob {
+                output: &mut out_q4,
+                data: &q4,
+                ggml_type: GgmlType::Q4_0,
+                input: &vec_in,
+            },
+        ];
+        matmul_batch(jobs, &MatmulTuning::default()).unwrap();
+        for (ggml_type, data, output) in [
+            (GgmlType::Q8_0, &q8, &out_q8),
+            (GgmlType::Q4_0, &q4, &out_q4),
+        ] {
+            let n = output.len();
+            let mut mat = vec![0.0; n * cols];
+            crate::quant::dequantize_row(data, &mut mat, n * cols, ggml_type).unwrap();
+            let mut expected = vec![0.0; n];
+            crate::tensor::matmul(&mut expected, &mat, &vec_in, n, cols);
+            for (got, want) in output.iter().zip(&expected) {
+                assert!((got - want).abs() < 1e-3, "{ggml_type:?}: {got} != {want}");
+            }
+        }
+
+        let mut output = vec![0.0; rows];
+        let short = vec![0u8; 10];
+        assert!(
+            matmul_quantized(
+                &mut output,
+                &short,
+                GgmlType::Q8_0,
+                &vec_in,
+                rows,
+                cols,
+                &MatmulTuning::default()
+            )
+            .is_err()
+        );
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_stolen_chunks_match_one_thread() {
+        // Big enough that every worker of the pool takes part, and heights
+        // that no tile or chunk size divides
+        let cols = 512;
+        let matrices: Vec<(GgmlType, usize, Vec<u8>)> =
+            [(GgmlType::Q8_0, 1031), (GgmlType::Q6K, 1301)]
+                .into_iter()
+                .map(|(ggml_type, rows)| {
+                    let values: Vec<f32> = (0..rows * cols)
+                        .map(|i| ((i * 7919) % 251) as f32 / 251.0 - 0.5)
+                        .collect();
+                    let mut data = Vec::new();
+                    crate::quant::quantize_row(&values, &mut data, ggml_type).unwrap();
+                    (ggml_type, rows, data)
+                })
+                .collect();
+        let vec_in: Vec<f32> = (0..cols).map(|i| (i % 7) as f32 / 3.0 - 1.0).collect();
+        let bytes = matrices.iter().map(|(_, _, data)| data.len()).sum();
+        let run = |threads: usize, tuning: &MatmulTuning| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let mut outputs: Vec<Vec<f32>> = matrices
+                .iter()
+                .map(|(_, rows, _)| vec![0.0; *rows])
+                .collect();
+            let jobs = matrices
+                .iter()
+                .zip(&mut outputs)
+                .map(|((ggml_type, _, data), output)| MatmulJob {
+                    output,
+                    data,
+                    ggml_type: *ggml_type,
+                    input: &vec_in,
+                })
+                .collect();
+            pool.install(|| {
+                assert_eq!(workers_for(bytes), threads);
+                matmul_batch(jobs, tuning)
+            })
+            .unwrap();
+            outputs
+        };
+
+        let expected = run(1, &MatmulTuning::default());
+        for (tile_rows, tasks_per_thread) in [(1, 1), (8, 4), (16, 3), (64, 8)] {
+            let tuning = MatmulTuning {
+                tile_rows,
+                tasks_per_thread,
+            };
+            for threads in [2, 3, 4] {
+                assert_eq!(
+                    run(threads, &tuning),
+                    expected,
+                    "{threads} threads, {tuning:?}"
+                );
+            }
+        }
+    }
 }
@@ -3,10 +3,17 @@
 //! Reads vocabulary and merge rules from GGUF metadata and converts
 //! text to/from token IDs.
 
-use crate::gguf::GgufValue;
-use bizclaw_core::error::{BizClawError, Result};
+use crate::gguf::{GgufError, GgufValue};
+use bizclaw_core::error::Result;
 use std::collections::HashMap;
 
+/// Largest vocabulary a model may ship.
+pub const MAX_VOCAB: usize = 1 << 20;
+
+fn invalid(reason: impl Into<String>) -> GgufError {
+    GgufError::InvalidTokenizer(reason.into())
+}
+
 /// BPE tokenizer for LLaMA-family models.
 pub struct BpeTokenizer {
     /// Token ID → string mapping.
@@ -31,25 +38,48 @@ impl BpeTokenizer {
                 GgufValue::Array(arr) => Some(arr),
                 _ => None,
             })
-            .ok_or_else(|| BizClawError::Brain("Missing tokenizer.ggml.tokens".into()))?;
+            .ok_or_else(|| invalid("missing tokenizer.ggml.tokens"))?;
+        if tokens.len() > MAX_VOCAB {
+            return Err(invalid(format!(
+                "{} tokens, over the limit of {MAX_VOCAB}",
+                tokens.len()
+            ))
+            .into());
+        }
 
+        // A token that isn't a string would shift every ID after it.
         let vocab: Vec<String> = tokens
             .iter()
-            .filter_map(|v| v.as_str().map(|s| s.to_string()))
-            .collect();
+            .enumerate()
+            .map(|(id, v)| {
+                v.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| invalid(format!("token {id} is not a string")))
+            })
+            .collect::<std::result::Result<_, _>>()?;
 
         if vocab.is_empty() {
-            return Err(BizClawError::Brain("Empty vocabulary".into()));
+            return Err(invalid("empty vocabulary").into());
         }
 
-        // Extract scores
-        let scores: Vec<f32> = metadata
+        // Extract scores, one per token
+        let mut scores: Vec<f32> = metadata
             .get("tokenizer.ggml.scores")
             .and_then(|v| match v {
                 GgufValue::Array(arr) => Some(arr.iter().filter_map(|v| v.as_f32()).collect()),
                 _ => None,
             })
-            .unwrap_or_else(|| vec![0.0; vocab.len()]);
+            .unwrap_or_default();
+        if scores.len() != vocab.len() {
+            if !scores.is_empty() {
+                tracing::warn!(
+                    "Tokenizer has {} scores for {} tokens; missing ones count as 0",
+                    scores.len(),
+                    vocab.len()
+                );
+            }
+            scores.resize(vocab.len(), 0.0);
+        }
 
         // Build reverse mapping
         let token_to_id: HashMap<String, u32> = vocab
@@ -71,6 +101,15 @@ impl BpeTokenizer {
             .get("tokenizer.ggml.padding_token_id")
             .and_then(|v| v.as_u32())
             .unwrap_or(0);
+        for (role, id) in [("BOS", bos_id), ("EOS", eos_id), ("padding", pad_id)] {
+            if id as usize >= vocab.len() {
+                return Err(invalid(format!(
+                    "{role} token {id} is outside the vocabulary of {}",
+                    vocab.len()
+                ))
+                .into());
+            }
+        }
 
         tracing::info!(
             "Tokenizer loaded: vocab_size={}, bos={}, eos={}",
@@ -192,6 +231,11 @@ impl BpeTokenizer {
             .join("")
     }
 
+    /// All token strings, indexed by token ID.
+    pub fn vocab(&self) -> &[String] {
+        &self.vocab
+    }
+
     /// Get vocabulary size.
     pub fn vocab_size(&self) -> usize {
         self.vocab.len()
@@ -202,3 +246,48 @@ impl BpeTokenizer {
         id == self.bos_id || id == self.eos_id || id == self.pad_id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(tokens: Vec<GgufValue>, bos: u32) -> HashMap<String, GgufValue> {
+        HashMap::from([
+            ("tokenizer.ggml.tokens".into(), GgufValue::Array(tokens)),
+            ("tokenizer.ggml.bos_token_id".into(), GgufValue::U32(bos)),
+            ("tokenizer.ggml.eos_token_id".into(), GgufValue::U32(2)),
+            (
+                "tokenizer.ggml.scores".into(),
+                GgufValue::Array(vec![GgufValue::F32(0.0)]),
+            ),
+        ])
+    }
+
+    fn strings(tokens: &[&str]) -> Vec<GgufValue> {
+        tokens
+            .iter()
+            .map(|t| GgufValue::String(t.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_gguf_rejects_malformed_vocab() {
+        let tokenizer =
+            BpeTokenizer::from_gguf(&metadata(strings(&["<unk>", "<s>", "</s>", "a"]), 1)).unwrap();
+        assert_eq!(tokenizer.vocab_size(), 4);
+        // Short score lists are padded rather than indexed past their end.
+        assert_eq!(tokenizer.scores.len(), 4);
+
+        let mut tokens = strings(&["<unk>", "<s>", "</s>"]);
+        tokens.insert(1, GgufValue::U32(7));
+        let err = BpeTokenizer::from_gguf(&metadata(tokens, 1)).err().unwrap();
+        assert!(err.to_string().contains("token 1 is not a string"));
+
+        let err = BpeTokenizer::from_gguf(&metadata(strings(&["<unk>", "<s>", "</s>"]), 99))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("BOS token 99"));
+
+        assert!(BpeTokenizer::from_gguf(&metadata(Vec::new(), 1)).is_err());
+    }
+}
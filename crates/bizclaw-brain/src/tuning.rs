@@ -0,0 +1,242 @@
+//! Matmul autotuning: the tile and split sizes of
+//! [`matmul_quantized`](crate::thread_pool::matmul_quantized) that run
+//! fastest on this CPU.
+//!
+//! The best sizes depend on cache sizes and core counts, so on first use
+//! [`load_or_tune`] benchmarks every candidate on synthetic `Q4_0` and
+//! `Q8_0` matrices and caches the winner in a JSON file keyed by CPU model
+//! and thread count. Later runs, and other processes on the same machine,
+//! read it from there.
+
+use bizclaw_core::error::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Tile heights tried by [`autotune`].
+pub const TILE_ROWS: &[usize] = &[1, 4, 8, 16, 32, 64];
+/// Tasks per thread tried by [`autotune`].
+pub const TASKS_PER_THREAD: &[usize] = &[1, 2, 4, 8];
+
+const DEFAULT_TILE_ROWS: usize = 8;
+const DEFAULT_TASKS_PER_THREAD: usize = 4;
+
+/// How a quantized matmul is split up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatmulTuning {
+    /// Rows dequantized at a time; their f32 tile should fit in L1/L2.
+    pub tile_rows: usize,
+    /// Chunks of rows per thread; more give idle threads more to steal
+    /// from busy ones, fewer cost less scheduling.
+    pub tasks_per_thread: usize,
+}
+
+impl Default for MatmulTuning {
+    fn default() -> Self {
+        Self {
+            tile_rows: DEFAULT_TILE_ROWS,
+            tasks_per_thread: DEFAULT_TASKS_PER_THREAD,
+        }
+    }
+}
+
+static TILE: AtomicUsize = AtomicUsize::new(DEFAULT_TILE_ROWS);
+static TASKS: AtomicUsize = AtomicUsize::new(DEFAULT_TASKS_PER_THREAD);
+
+/// The tuning the forward pass uses, process-wide.
+pub fn current() -> MatmulTuning {
+    MatmulTuning {
+        tile_rows: TILE.load(Ordering::Relaxed),
+        tasks_per_thread: TASKS.load(Ordering::Relaxed),
+    }
+}
+
+pub fn set(tuning: MatmulTuning) {
+    TILE.store(tuning.tile_rows.max(1), Ordering::Relaxed);
+    TASKS.store(tuning.tasks_per_thread.max(1), Ordering::Relaxed);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+
+/// Benchmarking needs a clock and caching a filesystem, which the browser
+/// build has neither of.
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::*;
+    use crate::gguf::GgmlType;
+    use crate::thread_pool::{matmul_quantized, num_threads};
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    /// Benchmark matrix: the shape of a 1B model's attention projections.
+    const ROWS: usize = 2048;
+    const COLS: usize = 2048;
+    /// Runs per candidate; the fastest counts.
+    const RUNS: usize = 3;
+
+    /// One CPU's result in the cache file.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Tuned {
+        pub tuning: MatmulTuning,
+        /// Benchmark time with `tuning` and with the defaults, in ms.
+        pub tuned_ms: f64,
+        pub default_ms: f64,
+        /// Unix seconds.
+        pub tuned_at: u64,
+    }
+
+    /// The cache key for this machine: CPU model and thread count, since
+    /// the best split depends on both.
+    pub fn cpu_key() -> String {
+        let model =
+            crate::system::cpu_model().unwrap_or_else(|| std::env::consts::ARCH.to_string());
+        format!("{model} ({} threads)", num_threads())
+    }
+
+    fn read_cache(path: &Path) -> BTreeMap<String, Tuned> {
+        std::fs::read(path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    /// The cached result for this CPU, if it has been tuned.
+    pub fn cached(path: &Path) -> Option<Tuned> {
+        read_cache(path).remove(&cpu_key())
+    }
+
+    /// A weight matrix of `ggml_type` with values spread over [-1, 1).
+    fn bench_matrix(ggml_type: GgmlType) -> Result<Vec<u8>> {
+        let values: Vec<f32> = (0..ROWS * COLS)
+            .map(|i| (i.wrapping_mul(2_654_435_761) % 65_536) as f32 / 32_768.0 - 1.0)
+            .collect();
+        let mut data = Vec::new();
+        crate::quant::quantize_row(&values, &mut data, ggml_type)?;
+        Ok(data)
+    }
+
+    /// Time `tuning` on every matrix, summing each one's fastest run.
+    fn time(matrices: &[(GgmlType, Vec<u8>)], tuning: &MatmulTuning) -> Result<Duration> {
+        let input = vec![0.5f32; COLS];
+        let mut output = vec![0.0f32; ROWS];
+        let mut total = Duration::ZERO;
+        for (ggml_type, data) in matrices {
+            let mut best = Duration::MAX;
+            for _ in 0..RUNS {
+                let started = Instant::now();
+                matmul_quantized(&mut output, data, *ggml_type, &input, ROWS, COLS, tuning)?;
+                best = best.min(started.elapsed());
+            }
+            total += best;
+        }
+        Ok(total)
+    }
+
+    /// Benchmark every candidate and return the fastest, with its time and
+    /// the defaults' time. Takes a few seconds.
+    pub fn autotune() -> Result<Tuned> {
+        let matrices = [GgmlType::Q4_0, GgmlType::Q8_0]
+            .into_iter()
+            .map(|t| Ok((t, bench_matrix(t)?)))
+            .collect::<Result<Vec<_>>>()?;
+        // Warm up the thread pool and page in the matrices.
+        time(&matrices, &MatmulTuning::default())?;
+
+        let default_time = time(&matrices, &MatmulTuning::default())?;
+        let mut best = (MatmulTuning::default(), default_time);
+        for &tile_rows in TILE_ROWS {
+            for &tasks_per_thread in TASKS_PER_THREAD {
+                let tuning = MatmulTuning {
+                    tile_rows,
+                    tasks_per_thread,
+                };
+                let elapsed = time(&matrices, &tuning)?;
+                tracing::debug!("Matmul {tuning:?}: {:.2} ms", elapsed.as_secs_f64() * 1e3);
+                if elapsed < best.1 {
+                    best = (tuning, elapsed);
+                }
+            }
+        }
+        Ok(Tuned {
+            tuning: best.0,
+            tuned_ms: best.1.as_secs_f64() * 1e3,
+            default_ms: default_time.as_secs_f64() * 1e3,
+            tuned_at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        })
+    }
+
+    /// Add `tuned` for this CPU to the cache file at `path`, replacing it
+    /// atomically.
+    pub fn save(path: &Path, tuned: &Tuned) -> Result<()> {
+        let mut cache = read_cache(path);
+        cache.insert(cpu_key(), tuned.clone());
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&cache)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Use this CPU's tuning from the cache file at `path`, tuning and
+    /// caching it first if there is none.
+    pub fn load_or_tune(path: &Path) -> Result<MatmulTuning> {
+        if let Some(tuned) = cached(path) {
+            set(tuned.tuning);
+            return Ok(tuned.tuning);
+        }
+        tracing::info!("Tuning matmul kernels for {} (first run only)…", cpu_key());
+        let tuned = autotune()?;
+        tracing::info!(
+            "Matmul tuning: {} rows per tile, {} tasks per thread ({:.1} ms, defaults {:.1} ms)",
+            tuned.tuning.tile_rows,
+            tuned.tuning.tasks_per_thread,
+            tuned.tuned_ms,
+            tuned.default_ms
+        );
+        if let Err(e) = save(path, &tuned) {
+            tracing::warn!(
+                "Couldn't cache the matmul tuning in {}: {e}",
+                path.display()
+            );
+        }
+        set(tuned.tuning);
+        Ok(tuned.tuning)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let path = std::env::temp_dir()
+            .join(format!("bizclaw-tuning-{}", rand::random::<u64>()))
+            .join("tuning.json");
+        assert_eq!(cached(&path), None);
+
+        let tuned = Tuned {
+            tuning: MatmulTuning {
+                tile_rows: 32,
+                tasks_per_thread: 2,
+            },
+            tuned_ms: 10.0,
+            default_ms: 13.0,
+            tuned_at: 1_760_000_000,
+        };
+        save(&path, &tuned).unwrap();
+        assert_eq!(cached(&path), Some(tuned.clone()));
+        // A cached tuning is used without benchmarking.
+        assert_eq!(load_or_tune(&path).unwrap(), tuned.tuning);
+        assert_eq!(current(), tuned.tuning);
+        set(MatmulTuning::default());
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
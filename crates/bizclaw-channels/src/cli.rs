@@ -63,6 +63,8 @@ impl Channel for CliChannel {
                             thread_type: ThreadType::Direct,
                             timestamp: chrono::Utc::now(),
                             reply_to: None,
+                            message_id: None,
+                            media: Vec::new(),
                         };
                     }
                     Ok(None) => break,
@@ -74,7 +76,8 @@ impl Channel for CliChannel {
     }
 
     async fn send(&self, message: OutgoingMessage) -> Result<()> {
-        println!("\n🤖 {}\n", message.content);
+        let text = crate::render::render_text(&message, crate::render::RenderStyle::PlainText);
+        println!("\n🤖 {text}\n");
         Ok(())
     }
 }
@@ -0,0 +1,110 @@
+//! Credential checks for configured channels — one authenticated call per
+//! channel, without starting it. Backs `bizclaw doctor`.
+
+use bizclaw_core::config::ChannelConfig;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::Channel;
+use std::time::Duration;
+
+/// How long one check may take before the channel counts as unreachable.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The outcome of checking one channel's credentials.
+#[derive(Debug)]
+pub struct CredentialCheck {
+    pub channel: &'static str,
+    /// Who the credentials belong to, or why they didn't work.
+    pub result: Result<String>,
+}
+
+/// Check every enabled channel in `config` that authenticates with a
+/// remote service. Zalo sessions and webhooks aren't checked: logging in
+/// to Zalo starts a session, and webhooks have no credentials to test.
+pub async fn check_credentials(config: &ChannelConfig) -> Vec<CredentialCheck> {
+    let mut checks = Vec::new();
+
+    if let Some(tg) = &config.telegram
+        && tg.enabled
+    {
+        let channel = crate::telegram::TelegramChannel::new(crate::telegram::TelegramConfig {
+            bot_token: tg.bot_token.clone(),
+            enabled: true,
+            poll_interval: 1,
+        });
+        let result = timed(async {
+            let me = channel.get_me().await?;
+            Ok(format!("@{}", me.username.unwrap_or(me.first_name)))
+        })
+        .await;
+        checks.push(CredentialCheck {
+            channel: "telegram",
+            result,
+        });
+    }
+
+    if let Some(dc) = &config.discord
+        && dc.enabled
+    {
+        let channel = crate::discord::DiscordChannel::new(crate::discord::DiscordConfig {
+            bot_token: dc.bot_token.clone(),
+            enabled: true,
+            intents: 0,
+        });
+        let result = timed(async { Ok(channel.get_me().await?.username) }).await;
+        checks.push(CredentialCheck {
+            channel: "discord",
+            result,
+        });
+    }
+
+    if let Some(wa) = &config.whatsapp
+        && wa.enabled
+    {
+        let channel = crate::whatsapp::WhatsAppChannel::new(crate::whatsapp::WhatsAppConfig {
+            access_token: wa.access_token.clone(),
+            phone_number_id: wa.phone_number_id.clone(),
+            ..Default::default()
+        });
+        let result = timed(channel.get_phone_number()).await;
+        checks.push(CredentialCheck {
+            channel: "whatsapp",
+            result,
+        });
+    }
+
+    if let Some(em) = &config.email
+        && em.enabled
+    {
+        let mut channel = crate::email::EmailChannel::new(crate::email::EmailConfig {
+            imap_host: em.imap_host.clone(),
+            imap_port: em.imap_port,
+            smtp_host: em.smtp_host.clone(),
+            smtp_port: em.smtp_port,
+            email: em.email.clone(),
+            password: em.password.clone(),
+            ..Default::default()
+        });
+        let result = timed(async {
+            channel.connect().await?;
+            Ok(format!("{} via {}", em.email, em.imap_host))
+        })
+        .await;
+        checks.push(CredentialCheck {
+            channel: "email",
+            result,
+        });
+    }
+
+    checks
+}
+
+async fn timed(check: impl Future<Output = Result<String>>) -> Result<String> {
+    tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| {
+            Err(BizClawError::Timeout(format!(
+                "no answer within {}s",
+                CHECK_TIMEOUT.as_secs()
+            )))
+        })
+}
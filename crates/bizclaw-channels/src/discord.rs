@@ -6,7 +6,7 @@
 use async_trait::async_trait;
 use bizclaw_core::error::{BizClawError, Result};
 use bizclaw_core::traits::Channel;
-use bizclaw_core::types::{IncomingMessage, OutgoingMessage, ThreadType};
+use bizclaw_core::types::{IncomingMessage, MediaKind, MediaRef, OutgoingMessage, ThreadType};
 use futures::stream::Stream;
 use serde::{Deserialize, Serialize};
 use std::pin::Pin;
@@ -62,23 +62,75 @@ impl DiscordChannel {
 
     /// Send a message to a channel.
     pub async fn send_message(&self, channel_id: &str, content: &str) -> Result<()> {
-        let url = format!("https://discord.com/api/v10/channels/{channel_id}/messages");
-        let body = serde_json::json!({ "content": content });
+        self.send_message_body(channel_id, &serde_json::json!({ "content": content }))
+            .await
+            .map(|_| ())
+    }
 
+    /// POST a prebuilt create-message body (e.g. from [`crate::render::discord_payload`]).
+    /// Returns the created message's id.
+    pub async fn send_message_body(
+        &self,
+        channel_id: &str,
+        body: &serde_json::Value,
+    ) -> Result<String> {
+        let url = format!("https://discord.com/api/v10/channels/{channel_id}/messages");
         let response = self
+            .check(self.client.post(&url).json(body), "send")
+            .await?;
+        let created: DiscordMessage = response
+            .json()
+            .await
+            .map_err(|e| BizClawError::Channel(format!("Discord: invalid send response: {e}")))?;
+        Ok(created.id)
+    }
+
+    /// Replace the content of a message the bot sent.
+    pub async fn edit_message(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        content: &str,
+    ) -> Result<()> {
+        let url =
+            format!("https://discord.com/api/v10/channels/{channel_id}/messages/{message_id}");
+        let request = self
             .client
-            .post(&url)
-            .json(&body)
-            .send()
+            .patch(&url)
+            .json(&serde_json::json!({ "content": content }));
+        self.check(request, "edit").await.map(|_| ())
+    }
+
+    /// Delete a message (needs Manage Messages for other users' messages).
+    pub async fn delete_message(&self, channel_id: &str, message_id: &str) -> Result<()> {
+        let url =
+            format!("https://discord.com/api/v10/channels/{channel_id}/messages/{message_id}");
+        self.check(self.client.delete(&url), "delete")
             .await
-            .map_err(|e| BizClawError::Channel(format!("Discord send failed: {e}")))?;
+            .map(|_| ())
+    }
+
+    /// Send a REST request, mapping transport and non-2xx failures.
+    async fn check(
+        &self,
+        request: reqwest::RequestBuilder,
+        action: &str,
+    ) -> Result<reqwest::Response> {
+        let response = request.send().await.map_err(|e| {
+            crate::middleware::retry::transport_error(&format!("Discord {action} failed"), e)
+        })?;
 
         if !response.status().is_success() {
             let status = response.status();
+            let wait = crate::middleware::retry::retry_after(response.headers());
             let text = response.text().await.unwrap_or_default();
-            return Err(BizClawError::Channel(format!("Discord {status}: {text}")));
+            return Err(crate::middleware::retry::status_error(
+                status,
+                format!("Discord {action}: {text}"),
+            )
+            .with_retry_after(wait));
         }
-        Ok(())
+        Ok(response)
     }
 
     /// Send typing indicator.
@@ -96,6 +148,12 @@ impl DiscordChannel {
             .send()
             .await
             .map_err(|e| BizClawError::Channel(format!("getMe failed: {e}")))?;
+        if !response.status().is_success() {
+            return Err(BizClawError::AuthFailed(format!(
+                "Discord rejected the bot token: {}",
+                response.status()
+            )));
+        }
         response
             .json()
             .await
@@ -244,6 +302,8 @@ impl DiscordChannel {
                                                         timestamp: chrono::Utc::now(),
                                                         reply_to: d["referenced_message"]["id"]
                                                             .as_str().map(String::from),
+                                                        message_id: d["id"].as_str().map(String::from),
+                                                        media: parse_attachments(&d["attachments"]),
                                                     };
 
                                                     if tx.send(msg).is_err() {
@@ -340,8 +400,30 @@ impl Channel for DiscordChannel {
     }
 
     async fn send(&self, message: OutgoingMessage) -> Result<()> {
-        self.send_message(&message.thread_id, &message.content)
+        self.send_with_id(message).await.map(|_| ())
+    }
+
+    async fn send_with_id(&self, message: OutgoingMessage) -> Result<Option<String>> {
+        let body = if message.is_rich() {
+            crate::render::discord_payload(&message)
+        } else {
+            serde_json::json!({ "content": message.content })
+        };
+        self.send_message_body(&message.thread_id, &body)
             .await
+            .map(Some)
+    }
+
+    fn supports_edit(&self) -> bool {
+        true
+    }
+
+    async fn edit(&self, thread_id: &str, message_id: &str, content: &str) -> Result<()> {
+        self.edit_message(thread_id, message_id, content).await
+    }
+
+    async fn delete(&self, thread_id: &str, message_id: &str) -> Result<()> {
+        self.delete_message(thread_id, message_id).await
     }
 
     async fn send_typing(&self, thread_id: &str) -> Result<()> {
@@ -349,7 +431,10 @@ impl Channel for DiscordChannel {
     }
 
     async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
-        Ok(Box::new(futures::stream::pending()))
+        // Gateway loop consumes its channel — run it on a fresh instance with the same config
+        Ok(Box::new(
+            DiscordChannel::new(self.config.clone()).start_gateway(),
+        ))
     }
 }
 
@@ -371,3 +456,25 @@ pub struct DiscordMessage {
     pub content: String,
     pub guild_id: Option<String>,
 }
+
+/// Convert a message's `attachments` array into media references.
+fn parse_attachments(attachments: &serde_json::Value) -> Vec<MediaRef> {
+    let Some(list) = attachments.as_array() else {
+        return Vec::new();
+    };
+    list.iter()
+        .filter_map(|a| {
+            let url = a["url"].as_str()?;
+            let mime = a["content_type"].as_str();
+            let mut media = MediaRef::remote(
+                mime.map(MediaKind::from_mime)
+                    .unwrap_or(MediaKind::Document),
+                url,
+            );
+            media.filename = a["filename"].as_str().map(String::from);
+            media.mime_type = mime.map(String::from);
+            media.size = a["size"].as_u64();
+            Some(media)
+        })
+        .collect()
+}
@@ -205,7 +205,9 @@ impl EmailChannel {
                                 content: format!("📧 Subject: {}\n\n{}", em.subject, em.body_text),
                                 thread_type: ThreadType::Direct,
                                 timestamp: chrono::Utc::now(),
-                                reply_to: em.message_id,
+                                reply_to: em.message_id.clone(),
+                                message_id: em.message_id,
+                                media: Vec::new(),
                             };
                             if tx.send(incoming).is_err() {
                                 return;
@@ -283,17 +285,24 @@ impl Channel for EmailChannel {
             .as_deref()
             .map(|r| format!("Re: {r}"))
             .unwrap_or_else(|| "From BizClaw AI".into());
+        let body = crate::render::render_text(&message, crate::render::RenderStyle::PlainText);
         self.send_email(
             &message.thread_id,
             &subject,
-            &message.content,
+            &body,
             message.reply_to.as_deref(),
         )
         .await
     }
 
     async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
-        Ok(Box::new(futures::stream::pending()))
+        // Share last_seen_uid so a re-listen after reconnect doesn't replay old mail
+        let poller = EmailChannel {
+            config: self.config.clone(),
+            connected: true,
+            last_seen_uid: self.last_seen_uid.clone(),
+        };
+        Ok(Box::new(poller.start_polling()))
     }
 }
 
@@ -4,11 +4,20 @@
 //! 25+ channels supported — inspired by OpenFang's 40+ channel architecture.
 
 pub mod cli;
+pub mod diagnostics;
 pub mod discord;
 pub mod email;
+pub mod manager;
+pub mod media;
+pub mod middleware;
+pub mod outbox;
+pub mod pipeline;
+pub mod render;
 pub mod telegram;
+pub mod webchat;
 pub mod webhook;
 pub mod whatsapp;
 pub mod zalo;
 pub mod slack;
+pub mod stdio;
 pub mod adapters;
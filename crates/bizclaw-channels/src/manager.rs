@@ -0,0 +1,1186 @@
+//! Channel manager — runs every configured channel concurrently under supervision.
+//!
+//! Each channel gets its own supervisor task that connects, listens, hands
+//! incoming messages to a [`MessageHandler`] (normally the agent) and sends the
+//! reply back through the same channel. If a stream ends, a health check finds
+//! the channel disconnected, or connect/listen fails, the supervisor reconnects
+//! with exponential backoff.
+//!
+//! With an [`Outbox`] attached, replies are persisted before sending and
+//! drained again after every reconnect and health check.
+//!
+//! Middleware stages (see [`crate::pipeline`]) wrap the handler, so every
+//! channel's messages go through the same pipeline.
+//!
+//! A manager built from config keeps the allowlists and Zalo's rate limits
+//! it was given swappable; [`ChannelReloader`] applies a reloaded config to
+//! them. The other channels' limits are fixed platform defaults.
+//!
+//! With an [`EventBus`] attached, connects and received messages are
+//! published as [`Event`]s. Channel health and sent messages are counted in
+//! the global [`metrics`] registry.
+
+use async_trait::async_trait;
+use crate::media::{MediaStore, TelegramFileFetcher};
+use crate::middleware::rate_limit::{default_limiter, zalo_limiter};
+use crate::middleware::{
+    DedupChannel, DedupStore, MediaChannel, RateLimitedChannel, RateLimiter, ReloadableLimiter,
+    RetryChannel, RetryPolicy, SplitChannel,
+};
+use crate::outbox::{EntryState, Outbox};
+use crate::pipeline::{AccessMiddleware, MessageMiddleware, Pipeline};
+use bizclaw_core::config::BizClawConfig;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::events::{Event, EventBus};
+use bizclaw_core::metrics;
+use bizclaw_core::traits::Channel;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage, ProgressEvent};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{RwLock, watch};
+use tokio::task::JoinHandle;
+
+/// Processes one incoming message, optionally producing a reply.
+#[async_trait]
+pub trait MessageHandler: Send + Sync {
+    async fn handle(&self, message: IncomingMessage) -> Result<Option<OutgoingMessage>>;
+
+    /// Like [`handle`](Self::handle), with a reporter for generation progress.
+    /// Handlers that track tool calls or streaming override this.
+    async fn handle_with_progress(
+        &self,
+        message: IncomingMessage,
+        progress: ProgressReporter,
+    ) -> Result<Option<OutgoingMessage>> {
+        let _ = progress;
+        self.handle(message).await
+    }
+}
+
+/// Forwards generation progress for one thread to its channel.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    channel: SharedChannel,
+    thread_id: String,
+}
+
+impl ProgressReporter {
+    pub fn thread_id(&self) -> &str {
+        &self.thread_id
+    }
+
+    /// Report a stage; failures are logged, never surfaced to the handler.
+    pub async fn report(&self, event: ProgressEvent) {
+        let ch = self.channel.read().await;
+        if let Err(e) = ch.on_progress(&self.thread_id, &event).await {
+            tracing::debug!("[{}] Progress update failed: {e}", ch.name());
+        }
+    }
+}
+
+#[async_trait]
+impl<F, Fut> MessageHandler for F
+where
+    F: Fn(IncomingMessage) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Option<OutgoingMessage>>> + Send,
+{
+    async fn handle(&self, message: IncomingMessage) -> Result<Option<OutgoingMessage>> {
+        (self)(message).await
+    }
+}
+
+/// Supervisor tuning.
+#[derive(Debug, Clone)]
+pub struct SupervisorConfig {
+    /// Delay before the first reconnect attempt.
+    pub initial_backoff: Duration,
+    /// Upper bound for the reconnect delay.
+    pub max_backoff: Duration,
+    /// How often a running channel's `is_connected()` is polled.
+    pub health_check_interval: Duration,
+    /// Give up after this many consecutive failed restarts (None = never).
+    pub max_restarts: Option<u32>,
+    /// Retry policy applied to every outgoing send.
+    pub retry: RetryPolicy,
+    /// How often the typing indicator is refreshed while a reply is generated.
+    pub typing_interval: Duration,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            health_check_interval: Duration::from_secs(30),
+            max_restarts: None,
+            retry: RetryPolicy::default(),
+            typing_interval: Duration::from_secs(4),
+        }
+    }
+}
+
+/// Lifecycle state of a supervised channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelState {
+    Starting,
+    Running,
+    Reconnecting,
+    Stopped,
+    Failed,
+}
+
+/// Point-in-time status of a supervised channel.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelStatus {
+    pub name: String,
+    pub state: ChannelState,
+    pub connected: bool,
+    /// Reconnect attempts since start.
+    pub restarts: u32,
+    /// Failures of any kind: connect/listen, handler and send errors.
+    pub errors: u64,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub messages_in: u64,
+    pub messages_out: u64,
+    /// Time of the last incoming message.
+    pub last_message_at: Option<DateTime<Utc>>,
+}
+
+impl ChannelStatus {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            state: ChannelState::Stopped,
+            connected: false,
+            restarts: 0,
+            errors: 0,
+            last_error: None,
+            last_error_at: None,
+            messages_in: 0,
+            messages_out: 0,
+            last_message_at: None,
+        }
+    }
+
+    fn record_error(&mut self, error: String) {
+        self.errors += 1;
+        self.last_error = Some(error);
+        self.last_error_at = Some(Utc::now());
+    }
+}
+
+/// Shared, cloneable view of the status table.
+///
+/// Channels driven outside a manager (e.g. per-agent bot loops in the
+/// gateway) can report into the same table so operators see one list.
+#[derive(Clone, Default)]
+pub struct ChannelStatusHandle {
+    inner: Arc<Mutex<HashMap<String, ChannelStatus>>>,
+}
+
+impl ChannelStatusHandle {
+    /// Status snapshot of every channel, sorted by name.
+    pub fn statuses(&self) -> Vec<ChannelStatus> {
+        let mut list: Vec<ChannelStatus> = self.inner.lock().unwrap().values().cloned().collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list
+    }
+
+    /// Status of one channel.
+    pub fn status(&self, name: &str) -> Option<ChannelStatus> {
+        self.inner.lock().unwrap().get(name).cloned()
+    }
+
+    /// Names of channels that gave up reconnecting.
+    pub fn failed(&self) -> Vec<String> {
+        self.statuses()
+            .into_iter()
+            .filter(|s| s.state == ChannelState::Failed)
+            .map(|s| s.name)
+            .collect()
+    }
+
+    /// Set a channel's state, adding it to the table if needed.
+    /// An error is counted and kept as `last_error`; entering `Reconnecting`
+    /// counts a restart.
+    pub fn set_state(&self, name: &str, state: ChannelState, error: Option<String>) {
+        let up = if state == ChannelState::Running {
+            1.0
+        } else {
+            0.0
+        };
+        metrics::gauge("bizclaw_channel_up", &[("channel", name)]).set(up);
+        self.update(name, |s| {
+            s.state = state;
+            s.connected = state == ChannelState::Running;
+            if state == ChannelState::Reconnecting {
+                s.restarts += 1;
+            }
+            if let Some(e) = error {
+                s.record_error(e);
+            }
+        });
+    }
+
+    /// Count an incoming message.
+    pub fn record_in(&self, name: &str) {
+        self.update(name, |s| {
+            s.messages_in += 1;
+            s.last_message_at = Some(Utc::now());
+        });
+    }
+
+    /// Count delivered outgoing messages.
+    pub fn record_out(&self, name: &str, count: u64) {
+        self.update(name, |s| s.messages_out += count);
+    }
+
+    /// Count an error without changing state.
+    pub fn record_error(&self, name: &str, error: impl ToString) {
+        self.update(name, |s| s.record_error(error.to_string()));
+    }
+
+    /// Drop a channel from the table.
+    pub fn remove(&self, name: &str) {
+        self.inner.lock().unwrap().remove(name);
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut ChannelStatus)) {
+        let mut map = self.inner.lock().unwrap();
+        f(map
+            .entry(name.to_string())
+            .or_insert_with(|| ChannelStatus::new(name)));
+    }
+}
+
+type SharedChannel = Arc<RwLock<Box<dyn Channel>>>;
+
+/// Owns all channels and their supervisor tasks.
+pub struct ChannelManager {
+    channels: HashMap<String, SharedChannel>,
+    statuses: ChannelStatusHandle,
+    config: SupervisorConfig,
+    dedup: Option<Arc<DedupStore>>,
+    media: Option<Arc<MediaStore>>,
+    outbox: Option<Arc<Outbox>>,
+    rate_limiters: HashMap<String, Arc<dyn RateLimiter>>,
+    middleware: Vec<Arc<dyn MessageMiddleware>>,
+    reloader: ChannelReloader,
+    events: Option<EventBus>,
+    shutdown_tx: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl ChannelManager {
+    pub fn new(config: SupervisorConfig) -> Self {
+        let (shutdown_tx, _) = watch::channel(false);
+        Self {
+            channels: HashMap::new(),
+            statuses: ChannelStatusHandle::default(),
+            config,
+            dedup: None,
+            media: None,
+            outbox: None,
+            rate_limiters: HashMap::new(),
+            middleware: Vec::new(),
+            reloader: ChannelReloader::default(),
+            events: None,
+            shutdown_tx,
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Persist replies in `outbox` until the channel accepts them.
+    pub fn with_outbox(mut self, outbox: Arc<Outbox>) -> Self {
+        self.outbox = Some(outbox);
+        self
+    }
+
+    /// Use `limiter` instead of the platform default for the named channel.
+    /// Applies to channels registered after this call.
+    pub fn with_rate_limiter(mut self, channel: &str, limiter: Arc<dyn RateLimiter>) -> Self {
+        self.rate_limiters.insert(channel.to_string(), limiter);
+        self
+    }
+
+    /// Drop redelivered incoming messages using `store`.
+    /// Applies to channels registered after this call.
+    pub fn with_dedup(mut self, store: Arc<DedupStore>) -> Self {
+        self.dedup = Some(store);
+        self
+    }
+
+    /// Download attachments of incoming messages into `store`.
+    /// Applies to channels registered after this call.
+    pub fn with_media(mut self, store: Arc<MediaStore>) -> Self {
+        self.media = Some(store);
+        self
+    }
+
+    /// Publish channel connects and received messages on `bus`.
+    pub fn with_events(mut self, bus: EventBus) -> Self {
+        self.events = Some(bus);
+        self
+    }
+
+    /// Run incoming messages and replies through `stages`, in order.
+    pub fn with_middleware(
+        mut self,
+        stages: impl IntoIterator<Item = Arc<dyn MessageMiddleware>>,
+    ) -> Self {
+        self.middleware.extend(stages);
+        self
+    }
+
+    /// Build a manager with every enabled channel from `[channel.*]` config,
+    /// allowlists checked first thing and Zalo limited as configured. The
+    /// rest of the pipeline is added with
+    /// [`with_middleware`](Self::with_middleware), see
+    /// [`crate::pipeline::from_config`].
+    pub fn from_config(config: &BizClawConfig) -> Self {
+        let capacity = crate::middleware::dedup::DEFAULT_CAPACITY;
+        let dedup = DedupStore::open(DedupStore::default_path(), capacity).unwrap_or_else(|e| {
+            tracing::warn!("Dedup store unavailable, keeping it in memory: {e}");
+            DedupStore::in_memory(capacity)
+        });
+        let mut manager = Self::new(SupervisorConfig::default()).with_dedup(Arc::new(dedup));
+        match Outbox::open(&Outbox::default_path()) {
+            Ok(outbox) => manager = manager.with_outbox(Arc::new(outbox)),
+            Err(e) => {
+                tracing::warn!("Outbox unavailable, replies are sent without persistence: {e}")
+            }
+        }
+        let ch = &config.channel;
+        let access = Arc::new(AccessMiddleware::from_config(ch));
+        manager.middleware.push(access.clone());
+        manager.reloader.access = Some(access);
+        if let Some(zalo) = &ch.zalo {
+            let limiter = Arc::new(ReloadableLimiter::new(zalo_limiter(&zalo.rate_limit)));
+            manager
+                .reloader
+                .limiters
+                .insert("zalo".into(), limiter.clone());
+            manager = manager.with_rate_limiter("zalo", limiter);
+        }
+        match MediaStore::open(MediaStore::default_path()) {
+            Ok(mut media) => {
+                if let Some(tg) = &ch.telegram
+                    && !tg.bot_token.is_empty()
+                {
+                    media = media.with_fetcher(Arc::new(TelegramFileFetcher::new(&tg.bot_token)));
+                }
+                manager = manager.with_media(Arc::new(media));
+            }
+            Err(e) => tracing::warn!("Media store unavailable, attachments stay remote: {e}"),
+        }
+
+        if let Some(tg) = &ch.telegram
+            && tg.enabled
+            && !tg.bot_token.is_empty()
+        {
+            manager.register(Box::new(crate::telegram::TelegramChannel::new(
+                crate::telegram::TelegramConfig {
+                    bot_token: tg.bot_token.clone(),
+                    enabled: true,
+                    poll_interval: 1,
+                },
+            )));
+        }
+        if let Some(dc) = &ch.discord
+            && dc.enabled
+            && !dc.bot_token.is_empty()
+        {
+            manager.register(Box::new(crate::discord::DiscordChannel::new(
+                crate::discord::DiscordConfig {
+                    bot_token: dc.bot_token.clone(),
+                    enabled: true,
+                    intents: (1 << 0) | (1 << 9) | (1 << 12) | (1 << 15),
+                },
+            )));
+        }
+        if let Some(em) = &ch.email
+            && em.enabled
+            && !em.email.is_empty()
+        {
+            manager.register(Box::new(crate::email::EmailChannel::new(
+                crate::email::EmailConfig {
+                    imap_host: em.imap_host.clone(),
+                    imap_port: em.imap_port,
+                    smtp_host: em.smtp_host.clone(),
+                    smtp_port: em.smtp_port,
+                    email: em.email.clone(),
+                    password: em.password.clone(),
+                    ..Default::default()
+                },
+            )));
+        }
+        if let Some(zalo) = &ch.zalo
+            && zalo.enabled
+        {
+            manager.register(Box::new(crate::zalo::ZaloChannel::new(zalo.clone())));
+        }
+        if let Some(wh) = &ch.webhook
+            && wh.enabled
+        {
+            manager.register(Box::new(crate::webhook::WebhookChannel::new(wh.into())));
+        }
+        manager
+    }
+
+    /// Add a channel. A channel with the same name replaces the previous one.
+    ///
+    /// Outgoing replies are split to the channel's length limit, and each part
+    /// is rate limited and retried on transient failures; incoming duplicates
+    /// are dropped when a dedup store is set, and attachments are downloaded
+    /// when a media store is set.
+    pub fn register(&mut self, mut channel: Box<dyn Channel>) {
+        let limiter = self
+            .rate_limiters
+            .get(channel.name())
+            .cloned()
+            .or_else(|| default_limiter(channel.name()));
+        if let Some(limiter) = limiter {
+            channel = Box::new(RateLimitedChannel::new(channel, limiter));
+        }
+        let channel: Box<dyn Channel> = Box::new(RetryChannel::new(channel, self.config.retry.clone()));
+        let mut channel: Box<dyn Channel> = Box::new(SplitChannel::new(channel));
+        if let Some(store) = &self.dedup {
+            channel = Box::new(DedupChannel::new(channel, store.clone()));
+        }
+        // Outside dedup so redelivered messages are dropped before downloading.
+        if let Some(store) = &self.media {
+            channel = Box::new(MediaChannel::new(channel, store.clone()));
+        }
+        let name = channel.name().to_string();
+        self.statuses.set_state(&name, ChannelState::Stopped, None);
+        self.channels.insert(name, Arc::new(RwLock::new(channel)));
+    }
+
+    /// Remove a channel before `start()`. Returns whether it was registered.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.statuses.remove(name);
+        self.channels.remove(name).is_some()
+    }
+
+    /// Registered channel names.
+    pub fn channel_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.channels.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Status snapshot of every channel, sorted by name.
+    pub fn statuses(&self) -> Vec<ChannelStatus> {
+        self.statuses.statuses()
+    }
+
+    /// Status of one channel.
+    pub fn status(&self, name: &str) -> Option<ChannelStatus> {
+        self.statuses.status(name)
+    }
+
+    /// Cloneable status view that stays valid after the manager is moved.
+    pub fn status_handle(&self) -> ChannelStatusHandle {
+        self.statuses.clone()
+    }
+
+    /// Send a message through a named channel (e.g. proactive notifications).
+    ///
+    /// With an outbox the message is queued first, so it is delivered later
+    /// even if the channel is currently down; [`Delivery::Queued`] says so.
+    /// A message the outbox gives up on is an error.
+    pub async fn send(&self, channel: &str, message: OutgoingMessage) -> Result<Delivery> {
+        send_via(
+            self.get(channel)?,
+            channel,
+            message,
+            self.outbox.as_deref(),
+            &self.statuses,
+        )
+        .await
+    }
+
+    /// Cloneable handle for [`send`](Self::send) from background tasks
+    /// (scheduled jobs). Covers the channels registered so far.
+    pub fn sender(&self) -> ChannelSender {
+        ChannelSender {
+            channels: Arc::new(self.channels.clone()),
+            outbox: self.outbox.clone(),
+            statuses: self.statuses.clone(),
+        }
+    }
+
+    /// Cloneable handle applying reloaded configs to the allowlists and
+    /// rate limits set up by [`from_config`](Self::from_config).
+    pub fn reloader(&self) -> ChannelReloader {
+        self.reloader.clone()
+    }
+
+    /// Edit a message previously sent through a named channel.
+    pub async fn edit(
+        &self,
+        channel: &str,
+        thread_id: &str,
+        message_id: &str,
+        content: &str,
+    ) -> Result<()> {
+        self.get(channel)?
+            .read()
+            .await
+            .edit(thread_id, message_id, content)
+            .await
+    }
+
+    /// Delete a message in a named channel (moderation, retracting replies).
+    pub async fn delete(&self, channel: &str, thread_id: &str, message_id: &str) -> Result<()> {
+        self.get(channel)?
+            .read()
+            .await
+            .delete(thread_id, message_id)
+            .await
+    }
+
+    fn get(&self, channel: &str) -> Result<&SharedChannel> {
+        self.channels
+            .get(channel)
+            .ok_or_else(|| BizClawError::ChannelNotConnected(channel.to_string()))
+    }
+
+    /// Spawn one supervisor per channel. Returns immediately.
+    pub fn start(&mut self, handler: Arc<dyn MessageHandler>) {
+        let handler: Arc<dyn MessageHandler> = if self.middleware.is_empty() {
+            handler
+        } else {
+            Arc::new(Pipeline::new(handler).with_stages(self.middleware.clone()))
+        };
+        for (name, channel) in &self.channels {
+            let supervisor = Supervisor {
+                name: name.clone(),
+                channel: channel.clone(),
+                handler: handler.clone(),
+                outbox: self.outbox.clone(),
+                statuses: self.statuses.clone(),
+                events: self.events.clone(),
+                config: self.config.clone(),
+                shutdown: self.shutdown_tx.subscribe(),
+            };
+            self.tasks.push(tokio::spawn(supervisor.run()));
+        }
+        tracing::info!("📡 ChannelManager started {} channel(s)", self.channels.len());
+    }
+
+    /// Stop all supervisors and disconnect every channel.
+    pub async fn shutdown(&mut self) {
+        let _ = self.shutdown_tx.send(true);
+        for task in self.tasks.drain(..) {
+            let _ = task.await;
+        }
+        for (name, channel) in &self.channels {
+            if let Err(e) = channel.write().await.disconnect().await {
+                tracing::warn!("[{name}] Disconnect failed: {e}");
+            }
+        }
+    }
+}
+
+/// What became of a message handed to [`ChannelManager::send`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// The channel accepted every part.
+    Sent,
+    /// At least one part is waiting in the outbox for a retry.
+    Queued,
+}
+
+/// Applies the channel settings of a reloaded config that take effect
+/// without a restart: `[channel.telegram] allowed_chat_ids`,
+/// `[channel.discord] allowed_channel_ids`, `[channel.zalo.allowlist]` and
+/// Zalo's messages per minute and hour.
+///
+/// Zalo is the only channel with configurable rate limits, so its limiter is
+/// the only one that reloads; the other channels keep their fixed platform
+/// defaults.
+#[derive(Clone, Default)]
+pub struct ChannelReloader {
+    access: Option<Arc<AccessMiddleware>>,
+    limiters: HashMap<String, Arc<ReloadableLimiter>>,
+}
+
+impl ChannelReloader {
+    /// Swap in the allowlists and Zalo rate limits from `config`.
+    pub fn reload(&self, config: &BizClawConfig) {
+        if let Some(access) = &self.access {
+            access.reload(&config.channel);
+        }
+        if let (Some(limiter), Some(zalo)) = (self.limiters.get("zalo"), &config.channel.zalo) {
+            limiter.replace(zalo_limiter(&zalo.rate_limit));
+        }
+    }
+}
+
+/// Sends through a manager's channels without borrowing the manager.
+#[derive(Clone)]
+pub struct ChannelSender {
+    channels: Arc<HashMap<String, SharedChannel>>,
+    outbox: Option<Arc<Outbox>>,
+    statuses: ChannelStatusHandle,
+}
+
+impl ChannelSender {
+    pub async fn send(&self, channel: &str, message: OutgoingMessage) -> Result<Delivery> {
+        let ch = self
+            .channels
+            .get(channel)
+            .ok_or_else(|| BizClawError::ChannelNotConnected(channel.to_string()))?;
+        send_via(ch, channel, message, self.outbox.as_deref(), &self.statuses).await
+    }
+}
+
+async fn send_via(
+    ch: &SharedChannel,
+    channel: &str,
+    message: OutgoingMessage,
+    outbox: Option<&Outbox>,
+    statuses: &ChannelStatusHandle,
+) -> Result<Delivery> {
+    let Some(outbox) = outbox else {
+        ch.read().await.send(message).await?;
+        record_sent(statuses, channel, 1);
+        return Ok(Delivery::Sent);
+    };
+
+    let ids = outbox.enqueue(channel, &message)?;
+    let sent = outbox.drain(ch.read().await.as_ref()).await?;
+    record_sent(statuses, channel, sent);
+    let mut delivery = Delivery::Sent;
+    for id in ids {
+        match outbox.state(id)? {
+            EntryState::Delivered => {}
+            EntryState::Pending => delivery = Delivery::Queued,
+            EntryState::Dead(e) => {
+                return Err(BizClawError::channel(format!(
+                    "{channel}: outbox gave up on the message: {e}"
+                )));
+            }
+        }
+    }
+    Ok(delivery)
+}
+
+fn record_sent(statuses: &ChannelStatusHandle, channel: &str, sent: usize) {
+    statuses.record_out(channel, sent as u64);
+    metrics::counter("bizclaw_messages_sent_total", &[("channel", channel)]).add(sent as u64);
+}
+
+/// Why a running channel stopped listening.
+enum Exit {
+    Shutdown,
+    Restart(String),
+}
+
+struct Supervisor {
+    name: String,
+    channel: SharedChannel,
+    handler: Arc<dyn MessageHandler>,
+    outbox: Option<Arc<Outbox>>,
+    statuses: ChannelStatusHandle,
+    events: Option<EventBus>,
+    config: SupervisorConfig,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl Supervisor {
+    async fn run(mut self) {
+        let mut backoff = self.config.initial_backoff;
+        let mut failures = 0u32;
+
+        loop {
+            self.set_state(ChannelState::Starting, None);
+            let exit = match self.connect_and_listen().await {
+                Ok(stream) => {
+                    self.set_state(ChannelState::Running, None);
+                    self.publish(Event::ChannelConnected {
+                        channel: self.name.clone(),
+                    });
+                    backoff = self.config.initial_backoff;
+                    failures = 0;
+                    self.flush_outbox().await;
+                    self.pump(stream).await
+                }
+                Err(e) => Exit::Restart(e.to_string()),
+            };
+
+            let reason = match exit {
+                Exit::Shutdown => break,
+                Exit::Restart(reason) => reason,
+            };
+
+            failures += 1;
+            if self.config.max_restarts.is_some_and(|max| failures > max) {
+                tracing::error!("[{}] Giving up after {failures} failed restart(s): {reason}", self.name);
+                self.set_state(ChannelState::Failed, Some(reason));
+                return;
+            }
+
+            tracing::warn!("[{}] {reason} — reconnecting in {backoff:?}", self.name);
+            self.set_state(ChannelState::Reconnecting, Some(reason));
+            let _ = self.channel.write().await.disconnect().await;
+
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                _ = self.shutdown.changed() => break,
+            }
+            backoff = (backoff * 2).min(self.config.max_backoff);
+        }
+
+        self.set_state(ChannelState::Stopped, None);
+    }
+
+    async fn connect_and_listen(
+        &self,
+    ) -> Result<Box<dyn futures::Stream<Item = IncomingMessage> + Send + Unpin>> {
+        let mut ch = self.channel.write().await;
+        if !ch.is_connected() {
+            ch.connect().await?;
+        }
+        ch.listen().await
+    }
+
+    async fn pump(
+        &mut self,
+        mut stream: Box<dyn futures::Stream<Item = IncomingMessage> + Send + Unpin>,
+    ) -> Exit {
+        let mut health = tokio::time::interval(self.config.health_check_interval);
+        health.tick().await; // first tick fires immediately
+
+        loop {
+            tokio::select! {
+                incoming = stream.next() => match incoming {
+                    Some(msg) => self.dispatch(msg).await,
+                    None => return Exit::Restart("stream ended".into()),
+                },
+                _ = health.tick() => {
+                    if !self.channel.read().await.is_connected() {
+                        return Exit::Restart("health check failed".into());
+                    }
+                    self.flush_outbox().await;
+                }
+                _ = self.shutdown.changed() => return Exit::Shutdown,
+            }
+        }
+    }
+
+    async fn dispatch(&self, msg: IncomingMessage) {
+        self.statuses.record_in(&self.name);
+        self.publish(Event::MessageReceived {
+            channel: msg.channel.clone(),
+            thread_id: msg.thread_id.clone(),
+            sender_id: msg.sender_id.clone(),
+        });
+        let thread_id = msg.thread_id.clone();
+        let progress = ProgressReporter {
+            channel: self.channel.clone(),
+            thread_id: thread_id.clone(),
+        };
+
+        // Typing states expire after a few seconds; keep refreshing until the handler returns.
+        let typing = tokio::spawn({
+            let channel = self.channel.clone();
+            let thread_id = thread_id.clone();
+            let interval = self.config.typing_interval;
+            async move {
+                loop {
+                    let _ = channel.read().await.indicate_typing(&thread_id).await;
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        });
+        let result = self
+            .handler
+            .handle_with_progress(msg, progress.clone())
+            .await;
+        typing.abort();
+        progress.report(ProgressEvent::Done).await;
+
+        let reply = match result {
+            Ok(Some(reply)) => reply,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::error!("[{}] Handler error (thread={thread_id}): {e}", self.name);
+                self.statuses.record_error(&self.name, e);
+                return;
+            }
+        };
+
+        let ch = self.channel.read().await;
+        if let Some(outbox) = &self.outbox {
+            match outbox.enqueue(&self.name, &reply) {
+                Ok(_) => {
+                    drop(ch);
+                    self.flush_outbox().await;
+                    return;
+                }
+                Err(e) => tracing::warn!(
+                    "[{}] Outbox enqueue failed, sending directly: {e}",
+                    self.name
+                ),
+            }
+        }
+
+        match ch.send(reply).await {
+            Ok(()) => self.statuses.record_out(&self.name, 1),
+            Err(e) => {
+                tracing::error!("[{}] Send failed (thread={thread_id}): {e}", self.name);
+                self.statuses.record_error(&self.name, e);
+            }
+        }
+    }
+
+    /// Deliver queued replies for this channel.
+    async fn flush_outbox(&self) {
+        let Some(outbox) = &self.outbox else {
+            return;
+        };
+        let ch = self.channel.read().await;
+        match outbox.drain(ch.as_ref()).await {
+            Ok(0) => {}
+            Ok(n) => self.statuses.record_out(&self.name, n as u64),
+            Err(e) => {
+                tracing::error!("[{}] Outbox drain failed: {e}", self.name);
+                self.statuses.record_error(&self.name, e);
+            }
+        }
+    }
+
+    fn set_state(&self, state: ChannelState, error: Option<String>) {
+        self.statuses.set_state(&self.name, state, error);
+    }
+
+    fn publish(&self, event: Event) {
+        if let Some(bus) = &self.events {
+            bus.publish(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::types::ThreadType;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Channel whose every listen() yields one message and then ends.
+    struct FlakyChannel {
+        connected: bool,
+        listens: Arc<AtomicU32>,
+        sent: Arc<Mutex<Vec<OutgoingMessage>>>,
+        /// "typing", "tool:<name>" and "done" as the channel saw them.
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Channel for FlakyChannel {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+        async fn connect(&mut self) -> Result<()> {
+            self.connected = true;
+            Ok(())
+        }
+        async fn disconnect(&mut self) -> Result<()> {
+            self.connected = false;
+            Ok(())
+        }
+        fn is_connected(&self) -> bool {
+            self.connected
+        }
+        async fn listen(&self) -> Result<Box<dyn futures::Stream<Item = IncomingMessage> + Send + Unpin>> {
+            let n = self.listens.fetch_add(1, Ordering::SeqCst);
+            let msg = IncomingMessage {
+                channel: "flaky".into(),
+                thread_id: format!("t{n}"),
+                sender_id: "u".into(),
+                sender_name: None,
+                content: format!("msg {n}"),
+                thread_type: ThreadType::Direct,
+                timestamp: chrono::Utc::now(),
+                reply_to: None,
+                message_id: None,
+                media: Vec::new(),
+            };
+            Ok(Box::new(futures::stream::iter(vec![msg])))
+        }
+        async fn send(&self, message: OutgoingMessage) -> Result<()> {
+            self.sent.lock().unwrap().push(message);
+            Ok(())
+        }
+        async fn indicate_typing(&self, _thread_id: &str) -> Result<()> {
+            self.events.lock().unwrap().push("typing".into());
+            Ok(())
+        }
+        async fn on_progress(&self, thread_id: &str, event: &ProgressEvent) -> Result<()> {
+            match event {
+                ProgressEvent::ToolCall { name } => {
+                    self.events.lock().unwrap().push(format!("tool:{name}"))
+                }
+                ProgressEvent::Done => self.events.lock().unwrap().push("done".into()),
+                _ => return self.indicate_typing(thread_id).await,
+            }
+            Ok(())
+        }
+    }
+
+    /// Reports a tool call, then takes a while to answer.
+    struct SlowToolHandler;
+
+    #[async_trait]
+    impl MessageHandler for SlowToolHandler {
+        async fn handle(&self, _message: IncomingMessage) -> Result<Option<OutgoingMessage>> {
+            Ok(None)
+        }
+        async fn handle_with_progress(
+            &self,
+            message: IncomingMessage,
+            progress: ProgressReporter,
+        ) -> Result<Option<OutgoingMessage>> {
+            assert_eq!(progress.thread_id(), message.thread_id);
+            progress
+                .report(ProgressEvent::ToolCall {
+                    name: "web_search".into(),
+                })
+                .await;
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            Ok(Some(OutgoingMessage::text(
+                message.thread_id,
+                "done",
+                message.thread_type,
+            )))
+        }
+    }
+
+    fn fast_config() -> SupervisorConfig {
+        SupervisorConfig {
+            initial_backoff: Duration::from_millis(5),
+            max_backoff: Duration::from_millis(20),
+            health_check_interval: Duration::from_secs(60),
+            max_restarts: None,
+            retry: RetryPolicy::default(),
+            typing_interval: Duration::from_millis(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_routes_replies_and_reconnects() {
+        let listens = Arc::new(AtomicU32::new(0));
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let bus = EventBus::new();
+        let mut published = bus.subscribe();
+        let mut manager = ChannelManager::new(fast_config()).with_events(bus);
+        manager.register(Box::new(FlakyChannel {
+            connected: false,
+            listens: listens.clone(),
+            sent: sent.clone(),
+            events: Arc::new(Mutex::new(Vec::new())),
+        }));
+
+        let handler = |msg: IncomingMessage| async move {
+            Ok::<_, BizClawError>(Some(OutgoingMessage::text(
+                msg.thread_id,
+                format!("echo: {}", msg.content),
+                msg.thread_type,
+            )))
+        };
+        manager.start(Arc::new(handler));
+
+        for _ in 0..100 {
+            if sent.lock().unwrap().len() >= 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        manager.shutdown().await;
+
+        let sent = sent.lock().unwrap();
+        assert!(sent.len() >= 3, "expected replies across reconnects");
+        assert_eq!(sent[0].thread_id, "t0");
+        assert_eq!(sent[0].content, "echo: msg 0");
+
+        let status = manager.status("flaky").unwrap();
+        assert!(status.restarts >= 2);
+        assert!(status.errors >= 2);
+        assert!(status.messages_in >= 3);
+        assert!(status.last_message_at.is_some());
+        assert_eq!(status.state, ChannelState::Stopped);
+        assert!(!status.connected);
+
+        let first = published.try_recv().unwrap().event;
+        assert_eq!(
+            first,
+            Event::ChannelConnected {
+                channel: "flaky".into()
+            }
+        );
+        let second = published.try_recv().unwrap().event;
+        assert_eq!(second.kind(), "message.received");
+    }
+
+    #[test]
+    fn test_status_handle_tracks_external_channels() {
+        let handle = ChannelStatusHandle::default();
+        handle.set_state("telegram:sales", ChannelState::Running, None);
+        handle.record_in("telegram:sales");
+        handle.record_out("telegram:sales", 2);
+        assert!(handle.status("telegram:sales").unwrap().connected);
+
+        handle.set_state("telegram:sales", ChannelState::Failed, Some("401".into()));
+        let status = handle.status("telegram:sales").unwrap();
+        assert!(!status.connected);
+        assert_eq!(status.errors, 1);
+        assert_eq!(status.messages_out, 2);
+        assert_eq!(handle.failed(), vec!["telegram:sales".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_successful_listen_resets_failure_count() {
+        let mut manager = ChannelManager::new(SupervisorConfig {
+            max_restarts: Some(1),
+            ..fast_config()
+        });
+        manager.register(Box::new(FlakyChannel {
+            connected: false,
+            listens: Arc::new(AtomicU32::new(0)),
+            sent: Arc::new(Mutex::new(Vec::new())),
+            events: Arc::new(Mutex::new(Vec::new())),
+        }));
+        manager.start(Arc::new(|_msg: IncomingMessage| async {
+            Ok::<_, BizClawError>(None)
+        }));
+
+        // Each listen ends immediately but resets the failure count on success,
+        // so the channel must keep running rather than fail.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_ne!(manager.status("flaky").unwrap().state, ChannelState::Failed);
+        manager.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_typing_refreshed_while_handling() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = ChannelManager::new(fast_config());
+        manager.register(Box::new(FlakyChannel {
+            connected: false,
+            listens: Arc::new(AtomicU32::new(0)),
+            sent: sent.clone(),
+            events: events.clone(),
+        }));
+        manager.start(Arc::new(SlowToolHandler));
+
+        for _ in 0..100 {
+            if !sent.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        manager.shutdown().await;
+
+        let events = events.lock().unwrap();
+        let first_done = events.iter().position(|e| e == "done").unwrap();
+        let before = &events[..first_done];
+        assert!(before.iter().filter(|e| *e == "typing").count() >= 2);
+        assert!(before.contains(&"tool:web_search".to_string()));
+    }
+
+    /// Rejects every send, transiently or for good.
+    struct DownChannel {
+        retryable: bool,
+    }
+
+    #[async_trait]
+    impl Channel for DownChannel {
+        fn name(&self) -> &str {
+            "down"
+        }
+        async fn connect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        async fn disconnect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn is_connected(&self) -> bool {
+            true
+        }
+        async fn listen(&self) -> Result<Box<dyn futures::Stream<Item = IncomingMessage> + Send + Unpin>> {
+            Ok(Box::new(futures::stream::empty()))
+        }
+        async fn send(&self, _message: OutgoingMessage) -> Result<()> {
+            Err(if self.retryable {
+                BizClawError::Http("502".into())
+            } else {
+                BizClawError::channel("chat not found")
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_reports_queued_and_dropped_messages() {
+        let config = SupervisorConfig {
+            retry: RetryPolicy {
+                max_attempts: 1,
+                ..RetryPolicy::default()
+            },
+            ..fast_config()
+        };
+        let outbox = Outbox::in_memory().unwrap().with_backoff(Duration::ZERO);
+        let mut manager = ChannelManager::new(config).with_outbox(Arc::new(outbox));
+        manager.register(Box::new(FlakyChannel {
+            connected: true,
+            listens: Arc::new(AtomicU32::new(0)),
+            sent: Arc::new(Mutex::new(Vec::new())),
+            events: Arc::new(Mutex::new(Vec::new())),
+        }));
+        let msg = || OutgoingMessage::text("x", "hi", ThreadType::Direct);
+        assert_eq!(manager.send("flaky", msg()).await.unwrap(), Delivery::Sent);
+
+        manager.register(Box::new(DownChannel { retryable: true }));
+        assert_eq!(manager.send("down", msg()).await.unwrap(), Delivery::Queued);
+
+        manager.register(Box::new(DownChannel { retryable: false }));
+        assert!(manager.send("down", msg()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_unknown_channel() {
+        let manager = ChannelManager::new(SupervisorConfig::default());
+        let msg = OutgoingMessage::text("x", "hi", ThreadType::Direct);
+        assert!(manager.send("nope", msg).await.is_err());
+        assert!(manager.delete("nope", "x", "1").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_edit_unsupported_by_default() {
+        let mut manager = ChannelManager::new(fast_config());
+        manager.register(Box::new(FlakyChannel {
+            connected: true,
+            listens: Arc::new(AtomicU32::new(0)),
+            sent: Arc::new(Mutex::new(Vec::new())),
+            events: Arc::new(Mutex::new(Vec::new())),
+        }));
+        let ch = manager.get("flaky").unwrap().read().await;
+        assert!(!ch.supports_edit());
+        let id = ch
+            .send_with_id(OutgoingMessage::text("t", "hi", ThreadType::Direct))
+            .await
+            .unwrap();
+        assert!(id.is_none());
+        drop(ch);
+        assert!(manager.edit("flaky", "t", "1", "edited").await.is_err());
+    }
+}
@@ -0,0 +1,474 @@
+//! Incoming media store.
+//!
+//! Channels attach [`MediaRef`]s that only point at the platform (a CDN URL,
+//! a Telegram `file_id`). [`MediaStore::ingest`] downloads each one through a
+//! matching [`MediaFetcher`], writes it to a content-addressed directory
+//! (`<root>/<sha[..2]>/<sha>.<ext>`) and fills in MIME type, size, hash and
+//! local path, so the brain and file tools never talk to a platform API.
+
+use crate::middleware::retry::{status_error, transport_error};
+use async_trait::async_trait;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::types::{IncomingMessage, MediaKind, MediaRef};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Largest file downloaded by default (20 MB — Telegram's bot download limit).
+pub const DEFAULT_MAX_BYTES: u64 = 20 * 1024 * 1024;
+
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Downloaded content and the MIME type the server reported, if any.
+#[derive(Debug, Clone)]
+pub struct FetchedMedia {
+    pub bytes: Vec<u8>,
+    pub mime_type: Option<String>,
+}
+
+/// Resolves a [`MediaRef::source`] into bytes.
+#[async_trait]
+pub trait MediaFetcher: Send + Sync {
+    /// Whether this fetcher understands `source`.
+    fn supports(&self, source: &str) -> bool;
+
+    /// Download `source`, failing if it is larger than `max_bytes`.
+    async fn fetch(&self, source: &str, max_bytes: u64) -> Result<FetchedMedia>;
+}
+
+/// Plain `http(s)://` downloads.
+pub struct HttpFetcher {
+    client: reqwest::Client,
+}
+
+impl HttpFetcher {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+impl Default for HttpFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl MediaFetcher for HttpFetcher {
+    fn supports(&self, source: &str) -> bool {
+        source.starts_with("https://") || source.starts_with("http://")
+    }
+
+    async fn fetch(&self, source: &str, max_bytes: u64) -> Result<FetchedMedia> {
+        download(&self.client, source, max_bytes).await
+    }
+}
+
+/// `telegram-file:<file_id>` references, resolved through the Bot API `getFile`.
+pub struct TelegramFileFetcher {
+    bot_token: String,
+    client: reqwest::Client,
+}
+
+impl TelegramFileFetcher {
+    pub const PREFIX: &'static str = "telegram-file:";
+
+    pub fn new(bot_token: impl Into<String>) -> Self {
+        Self {
+            bot_token: bot_token.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl MediaFetcher for TelegramFileFetcher {
+    fn supports(&self, source: &str) -> bool {
+        source.starts_with(Self::PREFIX)
+    }
+
+    async fn fetch(&self, source: &str, max_bytes: u64) -> Result<FetchedMedia> {
+        let file_id = source.trim_start_matches(Self::PREFIX);
+        let response = self
+            .client
+            .get(format!(
+                "https://api.telegram.org/bot{}/getFile",
+                self.bot_token
+            ))
+            .query(&[("file_id", file_id)])
+            .send()
+            .await
+            .map_err(|e| transport_error("Telegram getFile", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(status_error(status, format!("Telegram getFile: {body}")));
+        }
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| BizClawError::Channel(format!("Telegram getFile: {e}")))?;
+        let file_path = body["result"]["file_path"].as_str().ok_or_else(|| {
+            BizClawError::Channel(format!("Telegram getFile: no file_path for {file_id}"))
+        })?;
+        let url = format!(
+            "https://api.telegram.org/file/bot{}/{}",
+            self.bot_token, file_path
+        );
+        download(&self.client, &url, max_bytes).await
+    }
+}
+
+/// GET `url` into memory, refusing bodies over `max_bytes`.
+async fn download(client: &reqwest::Client, url: &str, max_bytes: u64) -> Result<FetchedMedia> {
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| transport_error("Media download", e))?;
+    if !response.status().is_success() {
+        return Err(status_error(response.status(), "Media download"));
+    }
+    if response.content_length().is_some_and(|len| len > max_bytes) {
+        return Err(too_large(max_bytes));
+    }
+    let mime_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| transport_error("Media download", e))?
+    {
+        if (bytes.len() + chunk.len()) as u64 > max_bytes {
+            return Err(too_large(max_bytes));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(FetchedMedia { bytes, mime_type })
+}
+
+fn too_large(max_bytes: u64) -> BizClawError {
+    BizClawError::Channel(format!("Media larger than {max_bytes} bytes"))
+}
+
+/// Content-addressed local media store shared by all channels.
+pub struct MediaStore {
+    root: PathBuf,
+    max_bytes: u64,
+    fetchers: Vec<Arc<dyn MediaFetcher>>,
+}
+
+impl MediaStore {
+    /// Open or create a store rooted at `root`, with an [`HttpFetcher`].
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        Ok(Self {
+            root,
+            max_bytes: DEFAULT_MAX_BYTES,
+            fetchers: vec![Arc::new(HttpFetcher::new())],
+        })
+    }
+
+    /// Default store directory (~/.bizclaw/media).
+    pub fn default_path() -> PathBuf {
+        bizclaw_core::config::BizClawConfig::home_dir().join("media")
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Add a fetcher for platform-specific sources (checked before earlier ones).
+    pub fn with_fetcher(mut self, fetcher: Arc<dyn MediaFetcher>) -> Self {
+        self.fetchers.insert(0, fetcher);
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Download every not-yet-stored attachment of `msg`.
+    ///
+    /// Failures are logged and leave the reference remote — a message is
+    /// never dropped because its attachment could not be fetched.
+    pub async fn ingest(&self, msg: &mut IncomingMessage) {
+        for media in msg.media.iter_mut().filter(|m| !m.is_stored()) {
+            if let Err(e) = self.resolve(media).await {
+                tracing::warn!(
+                    "[{}] Could not store media {}: {e}",
+                    msg.channel,
+                    media.source
+                );
+            }
+        }
+    }
+
+    /// Download one reference and complete it in place.
+    pub async fn resolve(&self, media: &mut MediaRef) -> Result<()> {
+        let fetcher = self
+            .fetchers
+            .iter()
+            .find(|f| f.supports(&media.source))
+            .ok_or_else(|| BizClawError::Channel(format!("No fetcher for {}", media.source)))?;
+        let fetched = fetcher.fetch(&media.source, self.max_bytes).await?;
+        let hint = media.mime_type.clone().or(fetched.mime_type);
+        self.store(media, &fetched.bytes, hint.as_deref())
+    }
+
+    /// Write `bytes` into the store and fill in hash, size, MIME type and path.
+    pub fn store(&self, media: &mut MediaRef, bytes: &[u8], mime_hint: Option<&str>) -> Result<()> {
+        let sha = format!("{:x}", Sha256::digest(bytes));
+        let mime = detect_mime(bytes, mime_hint, media.filename.as_deref());
+        let path = self.path_for(&sha, extension_for(&mime));
+
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            // Write-then-rename so a concurrent reader never sees a partial file.
+            let tmp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+            std::fs::write(&tmp, bytes)?;
+            std::fs::rename(&tmp, &path)?;
+        }
+
+        media.kind = MediaKind::from_mime(&mime);
+        media.mime_type = Some(mime);
+        media.size = Some(bytes.len() as u64);
+        media.sha256 = Some(sha);
+        media.local_path = Some(path);
+        Ok(())
+    }
+
+    fn path_for(&self, sha: &str, ext: &str) -> PathBuf {
+        self.root.join(&sha[..2]).join(format!("{sha}.{ext}"))
+    }
+}
+
+/// MIME type from the reported type, else the content's magic bytes, else the
+/// file name's extension.
+pub fn detect_mime(bytes: &[u8], reported: Option<&str>, filename: Option<&str>) -> String {
+    let reported = reported
+        .map(|m| {
+            m.split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_ascii_lowercase()
+        })
+        .filter(|m| !m.is_empty() && m != OCTET_STREAM);
+    reported
+        .or_else(|| sniff_mime(bytes).map(String::from))
+        .or_else(|| {
+            let ext = Path::new(filename?).extension()?.to_str()?;
+            mime_for_extension(ext).map(String::from)
+        })
+        .unwrap_or_else(|| OCTET_STREAM.to_string())
+}
+
+/// MIME type from well-known file signatures.
+pub fn sniff_mime(bytes: &[u8]) -> Option<&'static str> {
+    let mime = match bytes {
+        [0x89, b'P', b'N', b'G', ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', ..] => "image/gif",
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'E',
+            b'B',
+            b'P',
+            ..,
+        ] => "image/webp",
+        [
+            b'R',
+            b'I',
+            b'F',
+            b'F',
+            _,
+            _,
+            _,
+            _,
+            b'W',
+            b'A',
+            b'V',
+            b'E',
+            ..,
+        ] => "audio/wav",
+        [b'%', b'P', b'D', b'F', ..] => "application/pdf",
+        [b'O', b'g', b'g', b'S', ..] => "audio/ogg",
+        [b'I', b'D', b'3', ..] | [0xFF, 0xFB, ..] | [0xFF, 0xF3, ..] => "audio/mpeg",
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => "video/mp4",
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => "video/webm",
+        [b'P', b'K', 0x03, 0x04, ..] => "application/zip",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+fn mime_for_extension(ext: &str) -> Option<&'static str> {
+    let mime = match ext.to_ascii_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "ogg" | "oga" => "audio/ogg",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "zip" => "application/zip",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "json" => "application/json",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+fn extension_for(mime: &str) -> &'static str {
+    match mime {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "application/pdf" => "pdf",
+        "audio/ogg" => "ogg",
+        "audio/mpeg" => "mp3",
+        "audio/wav" => "wav",
+        "video/mp4" => "mp4",
+        "video/webm" => "webm",
+        "application/zip" => "zip",
+        "text/plain" => "txt",
+        "text/csv" => "csv",
+        "application/json" => "json",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => "docx",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => "xlsx",
+        _ => "bin",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::types::ThreadType;
+
+    const PNG: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0];
+
+    fn temp_store() -> MediaStore {
+        let root = std::env::temp_dir().join(format!("bizclaw-media-{}", uuid::Uuid::new_v4()));
+        MediaStore::open(root).unwrap()
+    }
+
+    /// Serves fixed bytes for `test:` sources.
+    struct StaticFetcher;
+
+    #[async_trait]
+    impl MediaFetcher for StaticFetcher {
+        fn supports(&self, source: &str) -> bool {
+            source.starts_with("test:")
+        }
+        async fn fetch(&self, _source: &str, max_bytes: u64) -> Result<FetchedMedia> {
+            if PNG.len() as u64 > max_bytes {
+                return Err(too_large(max_bytes));
+            }
+            Ok(FetchedMedia {
+                bytes: PNG.to_vec(),
+                mime_type: Some(OCTET_STREAM.into()),
+            })
+        }
+    }
+
+    #[test]
+    fn test_sniff_and_detect() {
+        assert_eq!(sniff_mime(PNG), Some("image/png"));
+        assert_eq!(sniff_mime(b"%PDF-1.7"), Some("application/pdf"));
+        assert_eq!(sniff_mime(b"hello"), None);
+        assert_eq!(
+            detect_mime(b"hello", Some("text/plain; charset=utf-8"), None),
+            "text/plain"
+        );
+        assert_eq!(detect_mime(PNG, Some(OCTET_STREAM), None), "image/png");
+        assert_eq!(detect_mime(b"a,b", None, Some("report.CSV")), "text/csv");
+        assert_eq!(detect_mime(b"??", None, None), OCTET_STREAM);
+    }
+
+    #[test]
+    fn test_store_is_content_addressed() {
+        let store = temp_store();
+        let mut a = MediaRef::remote(MediaKind::Document, "x");
+        let mut b = MediaRef::remote(MediaKind::Document, "y");
+        store.store(&mut a, PNG, None).unwrap();
+        store.store(&mut b, PNG, None).unwrap();
+
+        let sha = a.sha256.clone().unwrap();
+        assert_eq!(sha.len(), 64);
+        assert_eq!(a.local_path, b.local_path);
+        assert_eq!(
+            a.local_path.as_deref(),
+            Some(
+                store
+                    .root()
+                    .join(&sha[..2])
+                    .join(format!("{sha}.png"))
+                    .as_path()
+            )
+        );
+        assert_eq!(a.kind, MediaKind::Image);
+        assert_eq!(a.size, Some(PNG.len() as u64));
+        assert_eq!(std::fs::read(a.local_path.unwrap()).unwrap(), PNG);
+        std::fs::remove_dir_all(store.root()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_ingest_keeps_unresolvable_refs() {
+        let store = temp_store().with_fetcher(Arc::new(StaticFetcher));
+        let mut msg = IncomingMessage {
+            channel: "test".into(),
+            thread_id: "t".into(),
+            sender_id: "u".into(),
+            sender_name: None,
+            content: String::new(),
+            thread_type: ThreadType::Direct,
+            timestamp: chrono::Utc::now(),
+            reply_to: None,
+            message_id: None,
+            media: vec![
+                MediaRef::remote(MediaKind::Document, "test:1"),
+                MediaRef::remote(MediaKind::Image, "unknown:2"),
+            ],
+        };
+        store.ingest(&mut msg).await;
+
+        assert!(msg.media[0].is_stored());
+        assert_eq!(msg.media[0].mime_type.as_deref(), Some("image/png"));
+        assert!(!msg.media[1].is_stored());
+
+        let small = temp_store()
+            .with_max_bytes(4)
+            .with_fetcher(Arc::new(StaticFetcher));
+        let mut media = MediaRef::remote(MediaKind::Image, "test:3");
+        assert!(small.resolve(&mut media).await.is_err());
+        std::fs::remove_dir_all(store.root()).ok();
+        std::fs::remove_dir_all(small.root()).ok();
+    }
+}
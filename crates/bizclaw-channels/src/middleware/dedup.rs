@@ -0,0 +1,292 @@
+//! Incoming message deduplication.
+//!
+//! Webhook senders retry, long-polling windows overlap and gateways replay
+//! events after a reconnect — without a guard each copy gets its own agent
+//! reply. [`DedupStore`] remembers the last N message keys in a ring buffer
+//! that is appended to a log file, so duplicates are caught across restarts.
+
+use async_trait::async_trait;
+use bizclaw_core::error::Result;
+use bizclaw_core::traits::Channel;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage, ProgressEvent};
+use std::collections::{HashSet, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio_stream::{Stream, StreamExt};
+
+/// Default number of remembered message keys.
+pub const DEFAULT_CAPACITY: usize = 10_000;
+
+/// Bounded set of recently seen message keys, optionally persisted.
+pub struct DedupStore {
+    capacity: usize,
+    path: Option<PathBuf>,
+    ring: Mutex<Ring>,
+}
+
+#[derive(Default)]
+struct Ring {
+    order: VecDeque<String>,
+    seen: HashSet<String>,
+    /// Lines in the log file — compacted once it reaches twice the capacity.
+    logged: usize,
+}
+
+impl Ring {
+    fn insert(&mut self, key: String, capacity: usize) -> bool {
+        if !self.seen.insert(key.clone()) {
+            return false;
+        }
+        self.order.push_back(key);
+        while self.order.len() > capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+        true
+    }
+}
+
+impl DedupStore {
+    /// Memory-only store (lost on restart).
+    pub fn in_memory(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            path: None,
+            ring: Mutex::new(Ring::default()),
+        }
+    }
+
+    /// Open (or create) a store persisted at `path`, loading the most recent keys.
+    pub fn open(path: impl AsRef<Path>, capacity: usize) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let capacity = capacity.max(1);
+        let mut ring = Ring::default();
+        if path.exists() {
+            let content = std::fs::read_to_string(&path)?;
+            for line in content.lines().filter(|l| !l.is_empty()) {
+                ring.insert(line.to_string(), capacity);
+                ring.logged += 1;
+            }
+        }
+        tracing::debug!("Dedup store loaded {} key(s) from {}", ring.order.len(), path.display());
+
+        Ok(Self {
+            capacity,
+            path: Some(path),
+            ring: Mutex::new(ring),
+        })
+    }
+
+    /// Default log path (~/.bizclaw/dedup.log).
+    pub fn default_path() -> PathBuf {
+        bizclaw_core::config::BizClawConfig::home_dir().join("dedup.log")
+    }
+
+    /// Dedup key for a message, `None` when the channel gave it no id.
+    pub fn key(msg: &IncomingMessage) -> Option<String> {
+        let id = msg.message_id.as_deref()?;
+        Some(format!("{}:{}:{}", msg.channel, msg.thread_id, id).replace('\n', " "))
+    }
+
+    /// Record a message. Returns `false` if it was already seen.
+    /// Messages without an id are always treated as new.
+    pub fn check(&self, msg: &IncomingMessage) -> bool {
+        match Self::key(msg) {
+            Some(key) => self.check_key(&key),
+            None => true,
+        }
+    }
+
+    /// Record a raw key. Returns `false` if it was already seen.
+    pub fn check_key(&self, key: &str) -> bool {
+        let mut ring = self.ring.lock().unwrap();
+        if !ring.insert(key.to_string(), self.capacity) {
+            return false;
+        }
+        if let Err(e) = self.persist(&mut ring, key) {
+            tracing::warn!("Dedup store write failed: {e}");
+        }
+        true
+    }
+
+    /// Number of remembered keys.
+    pub fn len(&self) -> usize {
+        self.ring.lock().unwrap().order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn persist(&self, ring: &mut Ring, key: &str) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if ring.logged + 1 >= self.capacity * 2 {
+            let mut content = ring.order.iter().cloned().collect::<Vec<_>>().join("\n");
+            content.push('\n');
+            std::fs::write(path, content)?;
+            ring.logged = ring.order.len();
+            return Ok(());
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(file, "{key}")?;
+        ring.logged += 1;
+        Ok(())
+    }
+}
+
+/// Wraps a channel and drops incoming messages already recorded in the store.
+pub struct DedupChannel {
+    inner: Box<dyn Channel>,
+    store: Arc<DedupStore>,
+}
+
+impl DedupChannel {
+    pub fn new(inner: Box<dyn Channel>, store: Arc<DedupStore>) -> Self {
+        Self { inner, store }
+    }
+}
+
+#[async_trait]
+impl Channel for DedupChannel {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        self.inner.connect().await
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.inner.disconnect().await
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
+        let store = self.store.clone();
+        let stream = self.inner.listen().await?.filter(move |msg| {
+            let fresh = store.check(msg);
+            if !fresh {
+                tracing::debug!(
+                    "[{}] Dropping duplicate message {:?} (thread={})",
+                    msg.channel,
+                    msg.message_id,
+                    msg.thread_id
+                );
+            }
+            fresh
+        });
+        Ok(Box::new(stream))
+    }
+
+    async fn send(&self, message: OutgoingMessage) -> Result<()> {
+        self.inner.send(message).await
+    }
+
+    async fn send_with_id(&self, message: OutgoingMessage) -> Result<Option<String>> {
+        self.inner.send_with_id(message).await
+    }
+
+    fn supports_edit(&self) -> bool {
+        self.inner.supports_edit()
+    }
+
+    async fn edit(&self, thread_id: &str, message_id: &str, content: &str) -> Result<()> {
+        self.inner.edit(thread_id, message_id, content).await
+    }
+
+    async fn delete(&self, thread_id: &str, message_id: &str) -> Result<()> {
+        self.inner.delete(thread_id, message_id).await
+    }
+
+    async fn send_typing(&self, thread_id: &str) -> Result<()> {
+        self.inner.send_typing(thread_id).await
+    }
+
+    async fn indicate_typing(&self, thread_id: &str) -> Result<()> {
+        self.inner.indicate_typing(thread_id).await
+    }
+
+    async fn on_progress(&self, thread_id: &str, event: &ProgressEvent) -> Result<()> {
+        self.inner.on_progress(thread_id, event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::types::ThreadType;
+
+    fn msg(id: Option<&str>) -> IncomingMessage {
+        IncomingMessage {
+            channel: "telegram".into(),
+            thread_id: "42".into(),
+            sender_id: "u".into(),
+            sender_name: None,
+            content: "hi".into(),
+            thread_type: ThreadType::Direct,
+            timestamp: chrono::Utc::now(),
+            reply_to: None,
+            message_id: id.map(String::from),
+            media: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_duplicates_rejected_and_ring_evicts() {
+        let store = DedupStore::in_memory(2);
+        assert!(store.check(&msg(Some("1"))));
+        assert!(!store.check(&msg(Some("1"))));
+        assert!(store.check(&msg(Some("2"))));
+        assert!(store.check(&msg(Some("3"))));
+        assert_eq!(store.len(), 2);
+        // "1" fell out of the ring.
+        assert!(store.check(&msg(Some("1"))));
+    }
+
+    #[test]
+    fn test_messages_without_id_pass() {
+        let store = DedupStore::in_memory(8);
+        assert!(store.check(&msg(None)));
+        assert!(store.check(&msg(None)));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_persists_across_reopen_and_compacts() {
+        let path = std::env::temp_dir()
+            .join(format!("bizclaw-dedup-{}", uuid::Uuid::new_v4()))
+            .join("dedup.log");
+
+        let store = DedupStore::open(&path, 3).unwrap();
+        for id in 0..10 {
+            assert!(store.check_key(&format!("k{id}")));
+        }
+        drop(store);
+
+        let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+        assert!(lines < 6, "log should be compacted, has {lines} lines");
+
+        let store = DedupStore::open(&path, 3).unwrap();
+        assert_eq!(store.len(), 3);
+        assert!(!store.check_key("k9"));
+        assert!(store.check_key("k0"));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
@@ -0,0 +1,91 @@
+//! Incoming media normalization.
+//!
+//! [`MediaChannel`] runs every incoming message through
+//! [`MediaStore::ingest`], so attachments reach the handler already stored
+//! locally with a MIME type, size and hash.
+
+use crate::media::MediaStore;
+use async_trait::async_trait;
+use bizclaw_core::error::Result;
+use bizclaw_core::traits::Channel;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage, ProgressEvent};
+use futures::StreamExt;
+use std::sync::Arc;
+use tokio_stream::Stream;
+
+/// Wraps a channel and downloads attachments of incoming messages.
+pub struct MediaChannel {
+    inner: Box<dyn Channel>,
+    store: Arc<MediaStore>,
+}
+
+impl MediaChannel {
+    pub fn new(inner: Box<dyn Channel>, store: Arc<MediaStore>) -> Self {
+        Self { inner, store }
+    }
+}
+
+#[async_trait]
+impl Channel for MediaChannel {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        self.inner.connect().await
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.inner.disconnect().await
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
+        let store = self.store.clone();
+        let stream = self.inner.listen().await?.then(move |mut msg| {
+            let store = store.clone();
+            async move {
+                if !msg.media.is_empty() {
+                    store.ingest(&mut msg).await;
+                }
+                msg
+            }
+        });
+        Ok(Box::new(Box::pin(stream)))
+    }
+
+    async fn send(&self, message: OutgoingMessage) -> Result<()> {
+        self.inner.send(message).await
+    }
+
+    async fn send_with_id(&self, message: OutgoingMessage) -> Result<Option<String>> {
+        self.inner.send_with_id(message).await
+    }
+
+    fn supports_edit(&self) -> bool {
+        self.inner.supports_edit()
+    }
+
+    async fn edit(&self, thread_id: &str, message_id: &str, content: &str) -> Result<()> {
+        self.inner.edit(thread_id, message_id, content).await
+    }
+
+    async fn delete(&self, thread_id: &str, message_id: &str) -> Result<()> {
+        self.inner.delete(thread_id, message_id).await
+    }
+
+    async fn send_typing(&self, thread_id: &str) -> Result<()> {
+        self.inner.send_typing(thread_id).await
+    }
+
+    async fn indicate_typing(&self, thread_id: &str) -> Result<()> {
+        self.inner.indicate_typing(thread_id).await
+    }
+
+    async fn on_progress(&self, thread_id: &str, event: &ProgressEvent) -> Result<()> {
+        self.inner.on_progress(thread_id, event).await
+    }
+}
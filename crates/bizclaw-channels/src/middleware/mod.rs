@@ -0,0 +1,15 @@
+//! Channel middleware — [`Channel`](bizclaw_core::traits::Channel) decorators
+//! that shape or guard sends (or filter and enrich incoming messages) while
+//! delegating everything else to the wrapped channel.
+
+pub mod dedup;
+pub mod media;
+pub mod rate_limit;
+pub mod retry;
+pub mod split;
+
+pub use dedup::{DedupChannel, DedupStore};
+pub use media::MediaChannel;
+pub use rate_limit::{RateLimitedChannel, RateLimiter, ReloadableLimiter, TokenBucketLimiter};
+pub use retry::{RetryChannel, RetryPolicy};
+pub use split::{SplitChannel, max_message_len, split_message, split_outgoing};
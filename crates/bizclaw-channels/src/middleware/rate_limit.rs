@@ -0,0 +1,394 @@
+//! Outgoing rate limiting.
+//!
+//! Platforms ban or throttle bots that burst past their limits — Telegram
+//! allows ~30 msg/s overall and ~1 msg/s per chat, Discord buckets per
+//! channel route. [`RateLimitedChannel`] waits on a [`RateLimiter`] before
+//! every send; [`default_limiter`] supplies per-platform token buckets.
+//! Limits set in config (`[channel.zalo.rate_limit]`) go through a
+//! [`ReloadableLimiter`], so edits apply to the running channel.
+
+use async_trait::async_trait;
+use bizclaw_core::config::ZaloRateLimitConfig;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::Channel;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage, ProgressEvent};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio_stream::Stream;
+
+/// Decides when an outgoing message may be sent.
+#[async_trait]
+pub trait RateLimiter: Send + Sync {
+    /// Wait until a message to `thread_id` may be sent, then consume a slot.
+    async fn acquire(&self, thread_id: &str);
+}
+
+/// Token bucket parameters: sustained `per_second` rate with bursts up to `burst`.
+#[derive(Debug, Clone, Copy)]
+pub struct BucketSpec {
+    pub per_second: f64,
+    pub burst: f64,
+}
+
+impl BucketSpec {
+    /// A bucket refilling at `per_second`, which must be positive. `burst`
+    /// below one still lets a single message through.
+    pub fn new(per_second: f64, burst: f64) -> Result<Self> {
+        if !per_second.is_finite() || per_second <= 0.0 {
+            return Err(BizClawError::config(format!(
+                "rate limit must be a positive number of messages per second, got {per_second}"
+            )));
+        }
+        if !burst.is_finite() || burst < 0.0 {
+            return Err(BizClawError::config(format!(
+                "rate limit burst must be a non-negative number, got {burst}"
+            )));
+        }
+        Ok(Self {
+            per_second,
+            burst: burst.max(1.0),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(spec: BucketSpec, now: Instant) -> Self {
+        Self {
+            tokens: spec.burst,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, spec: BucketSpec, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * spec.per_second).min(spec.burst);
+        self.updated = now;
+    }
+
+    /// Time until one token is available (zero if one is available now).
+    fn wait(&self, spec: BucketSpec) -> Duration {
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / spec.per_second)
+        }
+    }
+}
+
+/// Token-bucket limiter with an optional global bucket and an optional bucket per thread.
+pub struct TokenBucketLimiter {
+    global_spec: Option<BucketSpec>,
+    thread_spec: Option<BucketSpec>,
+    state: Mutex<LimiterState>,
+}
+
+struct LimiterState {
+    global: Option<Bucket>,
+    threads: HashMap<String, Bucket>,
+}
+
+/// Per-thread buckets are pruned once this many are tracked.
+const MAX_TRACKED_THREADS: usize = 4096;
+
+impl TokenBucketLimiter {
+    pub fn new(global: Option<BucketSpec>, per_thread: Option<BucketSpec>) -> Self {
+        let now = Instant::now();
+        Self {
+            global_spec: global,
+            thread_spec: per_thread,
+            state: Mutex::new(LimiterState {
+                global: global.map(|spec| Bucket::full(spec, now)),
+                threads: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Try to take a slot; returns how long to wait if none is available.
+    fn try_acquire(&self, thread_id: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        let mut wait = Duration::ZERO;
+
+        if let (Some(spec), Some(bucket)) = (self.global_spec, state.global.as_mut()) {
+            bucket.refill(spec, now);
+            wait = wait.max(bucket.wait(spec));
+        }
+        if let Some(spec) = self.thread_spec {
+            if state.threads.len() >= MAX_TRACKED_THREADS {
+                // Buckets that have refilled completely carry no state worth keeping.
+                state.threads.retain(|_, b| {
+                    b.refill(spec, now);
+                    b.tokens < spec.burst
+                });
+            }
+            let bucket = state
+                .threads
+                .entry(thread_id.to_string())
+                .or_insert_with(|| Bucket::full(spec, now));
+            bucket.refill(spec, now);
+            wait = wait.max(bucket.wait(spec));
+        }
+
+        if !wait.is_zero() {
+            return Some(wait);
+        }
+        if let Some(bucket) = state.global.as_mut() {
+            bucket.tokens -= 1.0;
+        }
+        if self.thread_spec.is_some()
+            && let Some(bucket) = state.threads.get_mut(thread_id)
+        {
+            bucket.tokens -= 1.0;
+        }
+        None
+    }
+}
+
+#[async_trait]
+impl RateLimiter for TokenBucketLimiter {
+    async fn acquire(&self, thread_id: &str) {
+        while let Some(wait) = self.try_acquire(thread_id) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Default limits for a platform, `None` when it needs no client-side limiting.
+pub fn default_limiter(channel: &str) -> Option<Arc<dyn RateLimiter>> {
+    let ((global, global_burst), (per_thread, thread_burst)) = match channel {
+        // 30 msg/s overall, 1 msg/s per chat (short bursts tolerated).
+        "telegram" => ((30.0, 30.0), (1.0, 3.0)),
+        // 50 req/s global; message route allows 5 per 5s per channel.
+        "discord" => ((50.0, 50.0), (1.0, 5.0)),
+        // chat.postMessage: ~1 msg/s per channel.
+        "slack" => ((20.0, 20.0), (1.0, 3.0)),
+        // Cloud API throughput tier 1.
+        "whatsapp" => ((80.0, 80.0), (1.0, 5.0)),
+        "zalo" => ((10.0, 10.0), (1.0, 3.0)),
+        _ => return None,
+    };
+    Some(Arc::new(TokenBucketLimiter::new(
+        BucketSpec::new(global, global_burst).ok(),
+        BucketSpec::new(per_thread, thread_burst).ok(),
+    )))
+}
+
+/// Zalo's limits from `[channel.zalo.rate_limit]`: messages per minute and
+/// per hour overall, where 0 means no limit, on top of the per-chat
+/// default.
+pub fn zalo_limiter(config: &ZaloRateLimitConfig) -> Arc<dyn RateLimiter> {
+    let per = |count: u32, seconds: f64| BucketSpec::new(count as f64 / seconds, count as f64).ok();
+    Arc::new(AllOf(vec![
+        Arc::new(TokenBucketLimiter::new(
+            per(config.max_messages_per_minute, 60.0),
+            BucketSpec::new(1.0, 3.0).ok(),
+        )),
+        Arc::new(TokenBucketLimiter::new(
+            per(config.max_messages_per_hour, 3600.0),
+            None,
+        )),
+    ]))
+}
+
+/// Waits on each limiter in turn.
+struct AllOf(Vec<Arc<dyn RateLimiter>>);
+
+#[async_trait]
+impl RateLimiter for AllOf {
+    async fn acquire(&self, thread_id: &str) {
+        for limiter in &self.0 {
+            limiter.acquire(thread_id).await;
+        }
+    }
+}
+
+/// A limiter that can be replaced while channels wait on it, for limits
+/// read from config. Sends already waiting finish under the old limits.
+pub struct ReloadableLimiter {
+    inner: RwLock<Arc<dyn RateLimiter>>,
+}
+
+impl ReloadableLimiter {
+    pub fn new(limiter: Arc<dyn RateLimiter>) -> Self {
+        Self {
+            inner: RwLock::new(limiter),
+        }
+    }
+
+    pub fn replace(&self, limiter: Arc<dyn RateLimiter>) {
+        *self.inner.write().unwrap() = limiter;
+    }
+}
+
+#[async_trait]
+impl RateLimiter for ReloadableLimiter {
+    async fn acquire(&self, thread_id: &str) {
+        let limiter = self.inner.read().unwrap().clone();
+        limiter.acquire(thread_id).await;
+    }
+}
+
+/// Wraps a channel and waits on a [`RateLimiter`] before every send.
+pub struct RateLimitedChannel {
+    inner: Box<dyn Channel>,
+    limiter: Arc<dyn RateLimiter>,
+}
+
+impl RateLimitedChannel {
+    pub fn new(inner: Box<dyn Channel>, limiter: Arc<dyn RateLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+#[async_trait]
+impl Channel for RateLimitedChannel {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        self.inner.connect().await
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.inner.disconnect().await
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
+        self.inner.listen().await
+    }
+
+    async fn send(&self, message: OutgoingMessage) -> Result<()> {
+        self.limiter.acquire(&message.thread_id).await;
+        self.inner.send(message).await
+    }
+
+    async fn send_with_id(&self, message: OutgoingMessage) -> Result<Option<String>> {
+        self.limiter.acquire(&message.thread_id).await;
+        self.inner.send_with_id(message).await
+    }
+
+    fn supports_edit(&self) -> bool {
+        self.inner.supports_edit()
+    }
+
+    async fn edit(&self, thread_id: &str, message_id: &str, content: &str) -> Result<()> {
+        self.limiter.acquire(thread_id).await;
+        self.inner.edit(thread_id, message_id, content).await
+    }
+
+    async fn delete(&self, thread_id: &str, message_id: &str) -> Result<()> {
+        self.limiter.acquire(thread_id).await;
+        self.inner.delete(thread_id, message_id).await
+    }
+
+    async fn send_typing(&self, thread_id: &str) -> Result<()> {
+        self.inner.send_typing(thread_id).await
+    }
+
+    async fn indicate_typing(&self, thread_id: &str) -> Result<()> {
+        self.inner.indicate_typing(thread_id).await
+    }
+
+    async fn on_progress(&self, thread_id: &str, event: &ProgressEvent) -> Result<()> {
+        self.inner.on_progress(thread_id, event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_then_throttle_per_thread() {
+        let limiter = TokenBucketLimiter::new(None, BucketSpec::new(1.0, 2.0).ok());
+        assert!(limiter.try_acquire("a").is_none());
+        assert!(limiter.try_acquire("a").is_none());
+        let wait = limiter.try_acquire("a").expect("bucket should be empty");
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+        // Other threads have their own bucket.
+        assert!(limiter.try_acquire("b").is_none());
+    }
+
+    #[test]
+    fn test_global_bucket_limits_all_threads() {
+        let limiter = TokenBucketLimiter::new(BucketSpec::new(1.0, 2.0).ok(), None);
+        assert!(limiter.try_acquire("a").is_none());
+        assert!(limiter.try_acquire("b").is_none());
+        assert!(limiter.try_acquire("c").is_some());
+    }
+
+    #[test]
+    fn test_blocked_thread_does_not_consume_global() {
+        let limiter = TokenBucketLimiter::new(
+            BucketSpec::new(1.0, 2.0).ok(),
+            BucketSpec::new(1.0, 1.0).ok(),
+        );
+        assert!(limiter.try_acquire("a").is_none());
+        assert!(limiter.try_acquire("a").is_some());
+        assert!(limiter.try_acquire("b").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_acquire_waits_for_refill() {
+        let limiter = TokenBucketLimiter::new(None, BucketSpec::new(50.0, 1.0).ok());
+        let start = Instant::now();
+        limiter.acquire("a").await;
+        limiter.acquire("a").await;
+        assert!(start.elapsed() >= Duration::from_millis(15));
+    }
+
+    #[test]
+    fn test_rejects_rates_that_never_refill() {
+        assert!(BucketSpec::new(0.0, 5.0).is_err());
+        assert!(BucketSpec::new(-1.0, 5.0).is_err());
+        assert!(BucketSpec::new(f64::NAN, 5.0).is_err());
+        assert!(BucketSpec::new(1.0, -1.0).is_err());
+        assert!(BucketSpec::new(1.0, f64::INFINITY).is_err());
+        assert_eq!(BucketSpec::new(2.0, 0.0).unwrap().burst, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_reloaded_limits_apply() {
+        let soon = Duration::from_millis(50);
+        let mut config = ZaloRateLimitConfig {
+            max_messages_per_minute: 1,
+            ..Default::default()
+        };
+        let limiter = ReloadableLimiter::new(zalo_limiter(&config));
+        limiter.acquire("a").await;
+        assert!(
+            tokio::time::timeout(soon, limiter.acquire("b"))
+                .await
+                .is_err()
+        );
+
+        config.max_messages_per_minute = 0;
+        config.max_messages_per_hour = 0;
+        limiter.replace(zalo_limiter(&config));
+        assert!(
+            tokio::time::timeout(soon, limiter.acquire("b"))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_defaults() {
+        assert!(default_limiter("telegram").is_some());
+        assert!(default_limiter("discord").is_some());
+        assert!(default_limiter("stdio").is_none());
+    }
+}
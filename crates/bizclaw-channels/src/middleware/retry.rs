@@ -0,0 +1,327 @@
+//! Outbound send retry with exponential backoff and jitter.
+//!
+//! A reply that fails on a dropped connection or a 502 is usually fine a
+//! second later. [`RetryChannel`] retries sends whose error is retryable per
+//! [`BizClawError::is_retryable`] and gives up immediately on everything else
+//! (bad token, unknown chat, malformed payload). A rate limit that says how
+//! long to wait is honoured instead of the backoff, unless the wait is longer
+//! than `max_backoff`.
+
+use async_trait::async_trait;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::Channel;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage, ProgressEvent};
+use rand::Rng;
+use std::time::Duration;
+use tokio_stream::Stream;
+
+/// Retry tuning.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry — doubled on every attempt.
+    pub initial_backoff: Duration,
+    /// Upper bound for a single delay.
+    pub max_backoff: Duration,
+    /// Random extra delay as a fraction of the backoff (0.0 – 1.0).
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            jitter: 0.3,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `retry` (0-based), jitter included.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let base = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return base;
+        }
+        base.mul_f64(1.0 + rand::thread_rng().gen_range(0.0..jitter))
+    }
+
+    /// Run `op` until it succeeds, fails permanently, or attempts run out.
+    pub async fn run<T, F, Fut>(&self, label: &str, mut op: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Ok(v) => return Ok(v),
+                Err(e)
+                    if e.is_retryable()
+                        && attempt < self.max_attempts
+                        && e.retry_after().is_none_or(|wait| wait <= self.max_backoff) =>
+                {
+                    let delay = e.retry_after().unwrap_or_else(|| self.backoff(attempt - 1));
+                    tracing::warn!(
+                        "[{label}] Attempt {attempt}/{} failed: {e} — retrying in {delay:?}",
+                        self.max_attempts
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Map a non-success HTTP status to the error taxonomy so retry logic can
+/// classify it: 429 → `RateLimited`, 401/403 → `ChannelAuth`, 408 → `Timeout`,
+/// 5xx → `Http`, anything else → `Channel`. Attach the server's wait with
+/// [`BizClawError::with_retry_after`] and [`retry_after`].
+pub fn status_error(status: reqwest::StatusCode, detail: impl std::fmt::Display) -> BizClawError {
+    use reqwest::StatusCode;
+    let msg = format!("{status}: {detail}");
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => BizClawError::rate_limited(msg, None),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => BizClawError::ChannelAuth(msg),
+        StatusCode::REQUEST_TIMEOUT => BizClawError::Timeout(msg),
+        s if s.is_server_error() => BizClawError::Http(msg),
+        _ => BizClawError::Channel(msg),
+    }
+}
+
+/// The `Retry-After` header of a response, if present and well-formed.
+pub fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()
+        .and_then(bizclaw_core::error::parse_retry_after)
+}
+
+/// Map a reqwest transport error — timeouts and connection failures are transient.
+pub fn transport_error(context: &str, e: reqwest::Error) -> BizClawError {
+    if e.is_timeout() {
+        BizClawError::Timeout(format!("{context}: {e}"))
+    } else if e.is_connect() || e.is_request() {
+        BizClawError::Http(format!("{context}: {e}"))
+    } else {
+        BizClawError::Channel(format!("{context}: {e}"))
+    }
+}
+
+/// Wraps a channel and retries transient send failures.
+pub struct RetryChannel {
+    inner: Box<dyn Channel>,
+    policy: RetryPolicy,
+}
+
+impl RetryChannel {
+    pub fn new(inner: Box<dyn Channel>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+}
+
+#[async_trait]
+impl Channel for RetryChannel {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        self.inner.connect().await
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.inner.disconnect().await
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
+        self.inner.listen().await
+    }
+
+    async fn send(&self, message: OutgoingMessage) -> Result<()> {
+        self.policy
+            .run(self.inner.name(), || self.inner.send(message.clone()))
+            .await
+    }
+
+    async fn send_with_id(&self, message: OutgoingMessage) -> Result<Option<String>> {
+        self.policy
+            .run(self.inner.name(), || {
+                self.inner.send_with_id(message.clone())
+            })
+            .await
+    }
+
+    fn supports_edit(&self) -> bool {
+        self.inner.supports_edit()
+    }
+
+    async fn edit(&self, thread_id: &str, message_id: &str, content: &str) -> Result<()> {
+        self.policy
+            .run(self.inner.name(), || {
+                self.inner.edit(thread_id, message_id, content)
+            })
+            .await
+    }
+
+    async fn delete(&self, thread_id: &str, message_id: &str) -> Result<()> {
+        self.policy
+            .run(self.inner.name(), || {
+                self.inner.delete(thread_id, message_id)
+            })
+            .await
+    }
+
+    async fn send_typing(&self, thread_id: &str) -> Result<()> {
+        self.inner.send_typing(thread_id).await
+    }
+
+    async fn indicate_typing(&self, thread_id: &str) -> Result<()> {
+        self.inner.indicate_typing(thread_id).await
+    }
+
+    async fn on_progress(&self, thread_id: &str, event: &ProgressEvent) -> Result<()> {
+        self.inner.on_progress(thread_id, event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::types::ThreadType;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// Fails the first `fail` sends with the error built by `err`.
+    struct Failing {
+        fail: u32,
+        calls: Arc<AtomicU32>,
+        err: fn() -> BizClawError,
+    }
+
+    #[async_trait]
+    impl Channel for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+        async fn connect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        async fn disconnect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn is_connected(&self) -> bool {
+            true
+        }
+        async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
+            Ok(Box::new(tokio_stream::empty()))
+        }
+        async fn send(&self, _message: OutgoingMessage) -> Result<()> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.fail {
+                Err((self.err)())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            jitter: 0.5,
+        }
+    }
+
+    fn channel(fail: u32, err: fn() -> BizClawError) -> (RetryChannel, Arc<AtomicU32>) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let inner = Failing {
+            fail,
+            calls: calls.clone(),
+            err,
+        };
+        (RetryChannel::new(Box::new(inner), fast()), calls)
+    }
+
+    fn msg() -> OutgoingMessage {
+        OutgoingMessage::text("t", "hi", ThreadType::Direct)
+    }
+
+    #[tokio::test]
+    async fn test_transient_errors_are_retried() {
+        let (ch, calls) = channel(2, || BizClawError::Http("502".into()));
+        ch.send(msg()).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let (ch, calls) = channel(10, || BizClawError::Timeout("slow".into()));
+        assert!(matches!(ch.send(msg()).await, Err(BizClawError::Timeout(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_fail_fast() {
+        let (ch, calls) = channel(10, || BizClawError::AuthFailed("bad token".into()));
+        assert!(ch.send(msg()).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_honours_retry_after() {
+        let (ch, calls) = channel(1, || {
+            BizClawError::rate_limited("429", Some(Duration::from_millis(2)))
+        });
+        ch.send(msg()).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Asked to wait longer than max_backoff: surface the error instead.
+        let (ch, calls) = channel(10, || {
+            BizClawError::rate_limited("429", Some(Duration::from_secs(60)))
+        });
+        let err = ch.send(msg()).await.unwrap_err();
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(60)));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..fast()
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(1));
+        assert_eq!(policy.backoff(1), Duration::from_millis(2));
+        assert_eq!(policy.backoff(10), Duration::from_millis(4));
+    }
+
+    #[test]
+    fn test_status_classification() {
+        use reqwest::StatusCode;
+        assert!(status_error(StatusCode::TOO_MANY_REQUESTS, "").is_retryable());
+        assert!(status_error(StatusCode::BAD_GATEWAY, "").is_retryable());
+        assert!(status_error(StatusCode::REQUEST_TIMEOUT, "").is_retryable());
+        assert_eq!(
+            status_error(StatusCode::FORBIDDEN, "").code(),
+            "channel_auth"
+        );
+        assert!(!status_error(StatusCode::FORBIDDEN, "").is_retryable());
+        assert!(!status_error(StatusCode::BAD_REQUEST, "").is_retryable());
+    }
+}
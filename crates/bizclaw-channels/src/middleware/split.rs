@@ -0,0 +1,411 @@
+//! Channel-aware message splitting.
+//!
+//! Model output regularly exceeds platform limits (Discord rejects anything
+//! over 2000 characters). [`split_message`] breaks text at paragraph and
+//! code-block boundaries first, then lines, then words, and re-opens code
+//! fences in every part so each message renders on its own.
+
+use async_trait::async_trait;
+use bizclaw_core::error::Result;
+use bizclaw_core::traits::Channel;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage, ProgressEvent};
+use tokio_stream::Stream;
+
+const FENCE: &str = "```";
+
+/// Maximum characters per message for a channel, `None` if unbounded.
+pub fn max_message_len(channel: &str) -> Option<usize> {
+    match channel {
+        "telegram" | "whatsapp" => Some(4096),
+        "discord" | "zalo" => Some(2000),
+        "slack" => Some(40000),
+        "messenger" | "facebook" => Some(2000),
+        "line" => Some(5000),
+        "matrix" => Some(32000),
+        _ => None,
+    }
+}
+
+/// Split `text` into parts of at most `limit` characters.
+///
+/// Boundaries are tried in order: paragraph / code block, line, word, and
+/// finally a hard cut. A code block that has to be split is closed at the end
+/// of each part and re-opened (with its language tag) at the start of the next.
+pub fn split_message(text: &str, limit: usize) -> Vec<String> {
+    if limit == 0 || width(text) <= limit {
+        return vec![text.to_string()];
+    }
+
+    let mut pieces = Vec::new();
+    for block in blocks(text) {
+        match block {
+            Block::Text(t) if width(&t) > limit => pieces.extend(split_lines(&t, limit)),
+            Block::Code(c) if width(&c) > limit => pieces.extend(split_code(&c, limit)),
+            Block::Text(t) | Block::Code(t) => pieces.push(t),
+        }
+    }
+    pack(pieces, "\n\n", limit)
+}
+
+/// The messages [`SplitChannel`] sends for `message` at `limit`: one per
+/// part, only the first keeping `reply_to`. Rich messages and text within
+/// the limit come back whole.
+pub fn split_outgoing(message: &OutgoingMessage, limit: Option<usize>) -> Vec<OutgoingMessage> {
+    let Some(limit) = limit.filter(|&limit| !message.is_rich() && width(&message.content) > limit)
+    else {
+        return vec![message.clone()];
+    };
+    split_message(&message.content, limit)
+        .into_iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let mut msg = message.clone();
+            msg.content = part;
+            if i > 0 {
+                msg.reply_to = None;
+            }
+            msg
+        })
+        .collect()
+}
+
+/// Wraps a channel and splits over-long plain-text replies into several sends.
+///
+/// Only the first part keeps `reply_to`; rich messages (with content blocks)
+/// are passed through untouched since their size is decided by the renderer.
+pub struct SplitChannel {
+    inner: Box<dyn Channel>,
+    limit: Option<usize>,
+}
+
+impl SplitChannel {
+    /// Wrap a channel using its default limit from [`max_message_len`].
+    pub fn new(inner: Box<dyn Channel>) -> Self {
+        let limit = max_message_len(inner.name());
+        Self { inner, limit }
+    }
+
+    /// Override the per-message limit.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn needs_split(&self, message: &OutgoingMessage) -> bool {
+        self.limit
+            .is_some_and(|limit| !message.is_rich() && width(&message.content) > limit)
+    }
+}
+
+#[async_trait]
+impl Channel for SplitChannel {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        self.inner.connect().await
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.inner.disconnect().await
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
+        self.inner.listen().await
+    }
+
+    async fn send(&self, message: OutgoingMessage) -> Result<()> {
+        if !self.needs_split(&message) {
+            return self.inner.send(message).await;
+        }
+        self.send_with_id(message).await.map(|_| ())
+    }
+
+    /// Returns the id of the last part, where any continuation belongs.
+    async fn send_with_id(&self, message: OutgoingMessage) -> Result<Option<String>> {
+        if !self.needs_split(&message) {
+            return self.inner.send_with_id(message).await;
+        }
+
+        let parts = split_outgoing(&message, self.limit);
+        tracing::debug!(
+            "[{}] Splitting {} chars into {} parts",
+            self.name(),
+            width(&message.content),
+            parts.len()
+        );
+        let mut last_id = None;
+        for part in parts {
+            last_id = self.inner.send_with_id(part).await?;
+        }
+        Ok(last_id)
+    }
+
+    fn supports_edit(&self) -> bool {
+        self.inner.supports_edit()
+    }
+
+    async fn edit(&self, thread_id: &str, message_id: &str, content: &str) -> Result<()> {
+        self.inner.edit(thread_id, message_id, content).await
+    }
+
+    async fn delete(&self, thread_id: &str, message_id: &str) -> Result<()> {
+        self.inner.delete(thread_id, message_id).await
+    }
+
+    async fn send_typing(&self, thread_id: &str) -> Result<()> {
+        self.inner.send_typing(thread_id).await
+    }
+
+    async fn indicate_typing(&self, thread_id: &str) -> Result<()> {
+        self.inner.indicate_typing(thread_id).await
+    }
+
+    async fn on_progress(&self, thread_id: &str, event: &ProgressEvent) -> Result<()> {
+        self.inner.on_progress(thread_id, event).await
+    }
+}
+
+enum Block {
+    Text(String),
+    Code(String),
+}
+
+fn width(s: &str) -> usize {
+    s.chars().count()
+}
+
+fn is_fence(line: &str) -> bool {
+    line.trim_start().starts_with(FENCE)
+}
+
+/// Break text into paragraphs (blank-line separated) and fenced code blocks.
+fn blocks(text: &str) -> Vec<Block> {
+    let mut out = Vec::new();
+    let mut cur: Vec<&str> = Vec::new();
+    let mut in_code = false;
+
+    for line in text.split('\n') {
+        if in_code {
+            cur.push(line);
+            if is_fence(line) {
+                out.push(Block::Code(cur.join("\n")));
+                cur.clear();
+                in_code = false;
+            }
+        } else if is_fence(line) {
+            if !cur.is_empty() {
+                out.push(Block::Text(cur.join("\n")));
+                cur.clear();
+            }
+            cur.push(line);
+            in_code = true;
+        } else if line.trim().is_empty() {
+            if !cur.is_empty() {
+                out.push(Block::Text(cur.join("\n")));
+                cur.clear();
+            }
+        } else {
+            cur.push(line);
+        }
+    }
+    if !cur.is_empty() {
+        let joined = cur.join("\n");
+        out.push(if in_code { Block::Code(joined) } else { Block::Text(joined) });
+    }
+    out
+}
+
+/// Greedily join units with `sep` while staying within `limit`.
+/// Every unit must already fit on its own.
+fn pack(units: Vec<String>, sep: &str, limit: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut cur: Option<String> = None;
+    for unit in units {
+        cur = Some(match cur.take() {
+            None => unit,
+            Some(mut c) if width(&c) + width(sep) + width(&unit) <= limit => {
+                c.push_str(sep);
+                c.push_str(&unit);
+                c
+            }
+            Some(c) => {
+                out.push(c);
+                unit
+            }
+        });
+    }
+    out.extend(cur);
+    out
+}
+
+fn split_lines(text: &str, limit: usize) -> Vec<String> {
+    let lines = text.split('\n').flat_map(|l| split_words(l, limit)).collect();
+    pack(lines, "\n", limit)
+}
+
+fn split_words(line: &str, limit: usize) -> Vec<String> {
+    if width(line) <= limit {
+        return vec![line.to_string()];
+    }
+    let words = line
+        .split_inclusive(' ')
+        .flat_map(|w| hard_split(w, limit))
+        .collect();
+    pack(words, "", limit)
+        .into_iter()
+        .map(|p| p.trim_end().to_string())
+        .collect()
+}
+
+fn hard_split(s: &str, limit: usize) -> Vec<String> {
+    let chars: Vec<char> = s.chars().collect();
+    chars.chunks(limit).map(|c| c.iter().collect()).collect()
+}
+
+/// Split one fenced block, re-fencing every part with the original opener.
+fn split_code(block: &str, limit: usize) -> Vec<String> {
+    let mut lines: Vec<&str> = block.split('\n').collect();
+    let opener = lines.remove(0);
+    if lines.last().is_some_and(|l| is_fence(l)) {
+        lines.pop();
+    }
+
+    // opener + "\n" + body + "\n" + closer
+    let overhead = width(opener) + FENCE.len() + 2;
+    if limit <= overhead {
+        return split_lines(block, limit);
+    }
+    let budget = limit - overhead;
+
+    let body = lines
+        .iter()
+        .flat_map(|l| hard_split_or_keep(l, budget))
+        .collect();
+    pack(body, "\n", budget)
+        .into_iter()
+        .map(|b| format!("{opener}\n{b}\n{FENCE}"))
+        .collect()
+}
+
+/// Code lines are never re-flowed at spaces — indentation matters.
+fn hard_split_or_keep(line: &str, limit: usize) -> Vec<String> {
+    if width(line) <= limit {
+        vec![line.to_string()]
+    } else {
+        hard_split(line, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::types::ThreadType;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_short_text_untouched() {
+        assert_eq!(split_message("hello", 10), vec!["hello"]);
+    }
+
+    #[test]
+    fn test_splits_at_paragraphs() {
+        let text = "aaaa aaaa\n\nbbbb bbbb\n\ncccc";
+        let parts = split_message(text, 12);
+        assert_eq!(parts, vec!["aaaa aaaa", "bbbb bbbb", "cccc"]);
+        assert_eq!(split_message(text, 20), vec!["aaaa aaaa\n\nbbbb bbbb", "cccc"]);
+    }
+
+    #[test]
+    fn test_long_line_splits_at_words() {
+        let parts = split_message("one two three four five", 9);
+        assert!(parts.iter().all(|p| width(p) <= 9));
+        assert_eq!(parts.join(" "), "one two three four five");
+    }
+
+    #[test]
+    fn test_code_fences_preserved_across_parts() {
+        let code: Vec<String> = (0..10).map(|i| format!("let x{i} = {i};")).collect();
+        let text = format!("Intro\n\n```rust\n{}\n```\n\nDone", code.join("\n"));
+        let parts = split_message(&text, 60);
+
+        assert!(parts.len() > 2);
+        for p in &parts {
+            assert!(width(p) <= 60, "part too long: {p:?}");
+            assert_eq!(p.matches(FENCE).count() % 2, 0, "unbalanced fence: {p:?}");
+        }
+        let code_parts: Vec<_> = parts.iter().filter(|p| p.contains("let x")).collect();
+        assert!(code_parts.iter().all(|p| p.contains("```rust\n")));
+        assert!(parts.last().unwrap().ends_with("Done"));
+    }
+
+    #[test]
+    fn test_hard_split_multibyte() {
+        let text = "đ".repeat(25);
+        let parts = split_message(&text, 10);
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts.concat(), text);
+    }
+
+    struct Recorder(Arc<Mutex<Vec<OutgoingMessage>>>);
+
+    #[async_trait]
+    impl Channel for Recorder {
+        fn name(&self) -> &str {
+            "discord"
+        }
+        async fn connect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        async fn disconnect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn is_connected(&self) -> bool {
+            true
+        }
+        async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
+            Ok(Box::new(tokio_stream::empty()))
+        }
+        async fn send(&self, message: OutgoingMessage) -> Result<()> {
+            self.0.lock().unwrap().push(message);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_split_channel_sends_parts() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let channel = SplitChannel::new(Box::new(Recorder(sent.clone())));
+        assert_eq!(channel.limit, Some(2000));
+
+        let mut msg = OutgoingMessage::text("c1", "x".repeat(4500), ThreadType::Group);
+        msg.reply_to = Some("m1".into());
+        channel.send(msg).await.unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0].reply_to.as_deref(), Some("m1"));
+        assert!(sent[1].reply_to.is_none());
+        assert!(sent.iter().all(|m| m.thread_id == "c1" && width(&m.content) <= 2000));
+    }
+
+    #[test]
+    fn test_split_outgoing() {
+        let mut msg = OutgoingMessage::text("c1", "x".repeat(25), ThreadType::Group);
+        msg.reply_to = Some("m1".into());
+        assert_eq!(split_outgoing(&msg, None).len(), 1);
+        assert_eq!(split_outgoing(&msg, Some(25)).len(), 1);
+
+        let parts = split_outgoing(&msg, Some(10));
+        assert_eq!(parts.len(), 3);
+        assert_eq!(parts[0].reply_to.as_deref(), Some("m1"));
+        assert!(parts[1..].iter().all(|m| m.reply_to.is_none()));
+        let joined: String = parts.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(joined, msg.content);
+    }
+}
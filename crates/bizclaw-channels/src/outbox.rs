@@ -0,0 +1,471 @@
+//! Durable outbound message queue (SQLite).
+//!
+//! Replies are written to the outbox before they are sent and removed only
+//! after the channel accepts them, so a crash or an offline channel never
+//! loses a reply. Entries are drained in insertion order; when a send for a
+//! thread fails, later entries of that thread wait so replies never arrive
+//! out of order.
+//!
+//! A reply too long for the channel is queued as the parts the channel will
+//! split it into, so a failed part is retried on its own and the parts
+//! already delivered are never sent twice.
+
+use crate::middleware::split::{max_message_len, split_outgoing};
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::Channel;
+use bizclaw_core::types::OutgoingMessage;
+use rusqlite::{Connection, OptionalExtension, params};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A queued outgoing message.
+#[derive(Debug, Clone)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub channel: String,
+    pub message: OutgoingMessage,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    /// Unix millis before which the entry is not retried.
+    pub next_attempt_at: i64,
+}
+
+/// Where a queued entry stands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryState {
+    /// Sent and removed from the outbox.
+    Delivered,
+    /// Waiting for its first send or a retry.
+    Pending,
+    /// Given up on, with the last send error.
+    Dead(String),
+}
+
+/// SQLite-backed outbox shared by all channels.
+pub struct Outbox {
+    conn: Mutex<Connection>,
+    /// Serializes drains so one entry is never sent twice concurrently.
+    drain_lock: tokio::sync::Mutex<()>,
+    max_attempts: u32,
+    base_backoff: Duration,
+    max_backoff: Duration,
+}
+
+fn db_err(e: rusqlite::Error) -> BizClawError {
+    BizClawError::Database(format!("Outbox: {e}"))
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+impl Outbox {
+    /// Open or create the outbox database.
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path).map_err(db_err)?;
+        conn.execute_batch("PRAGMA journal_mode=WAL;").ok();
+        Self::init(conn)
+    }
+
+    /// In-memory outbox (tests, ephemeral runs).
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(db_err)?)
+    }
+
+    /// Default database path (~/.bizclaw/outbox.db).
+    pub fn default_path() -> PathBuf {
+        bizclaw_core::config::BizClawConfig::home_dir().join("outbox.db")
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                channel TEXT NOT NULL,
+                thread_id TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT,
+                next_attempt_at INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_outbox_pending ON outbox(channel, status, id);",
+        )
+        .map_err(db_err)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            drain_lock: tokio::sync::Mutex::new(()),
+            max_attempts: 10,
+            base_backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(300),
+        })
+    }
+
+    /// Give up on an entry after this many failed sends (default 10).
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Delay before retrying a failed entry — doubled per attempt (default 2s, capped at 5 min).
+    pub fn with_backoff(mut self, base: Duration) -> Self {
+        self.base_backoff = base;
+        self
+    }
+
+    /// Queue a message for `channel`, one entry per part the channel splits
+    /// it into. Returns the entry ids in delivery order.
+    pub fn enqueue(&self, channel: &str, message: &OutgoingMessage) -> Result<Vec<i64>> {
+        let parts = split_outgoing(message, max_message_len(channel));
+        let created_at = chrono::Utc::now().to_rfc3339();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_err)?;
+        let mut ids = Vec::with_capacity(parts.len());
+        for part in &parts {
+            tx.execute(
+                "INSERT INTO outbox (channel, thread_id, payload, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    channel,
+                    part.thread_id,
+                    serde_json::to_string(part)?,
+                    created_at
+                ],
+            )
+            .map_err(db_err)?;
+            ids.push(tx.last_insert_rowid());
+        }
+        tx.commit().map_err(db_err)?;
+        Ok(ids)
+    }
+
+    /// Where the entry `id` stands.
+    pub fn state(&self, id: i64) -> Result<EntryState> {
+        let conn = self.conn.lock().unwrap();
+        let row = conn
+            .query_row(
+                "SELECT status, last_error FROM outbox WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)),
+            )
+            .optional()
+            .map_err(db_err)?;
+        Ok(match row {
+            None => EntryState::Delivered,
+            Some((status, error)) if status == "dead" => {
+                EntryState::Dead(error.unwrap_or_default())
+            }
+            Some(_) => EntryState::Pending,
+        })
+    }
+
+    /// Pending entries of a channel in delivery order.
+    pub fn pending(&self, channel: &str) -> Result<Vec<OutboxEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, channel, payload, attempts, last_error, next_attempt_at
+                 FROM outbox WHERE channel = ?1 AND status = 'pending' ORDER BY id ASC",
+            )
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![channel], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, u32>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, i64>(5)?,
+                ))
+            })
+            .map_err(db_err)?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (id, channel, payload, attempts, last_error, next_attempt_at) =
+                row.map_err(db_err)?;
+            match serde_json::from_str(&payload) {
+                Ok(message) => entries.push(OutboxEntry {
+                    id,
+                    channel,
+                    message,
+                    attempts,
+                    last_error,
+                    next_attempt_at,
+                }),
+                Err(e) => tracing::warn!("Outbox entry {id} has an unreadable payload: {e}"),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Number of pending entries for a channel.
+    pub fn pending_count(&self, channel: &str) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM outbox WHERE channel = ?1 AND status = 'pending'",
+            params![channel],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|n| n as usize)
+        .map_err(db_err)
+    }
+
+    /// Number of entries that were given up on for a channel.
+    pub fn dead_count(&self, channel: &str) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT COUNT(*) FROM outbox WHERE channel = ?1 AND status = 'dead'",
+            params![channel],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|n| n as usize)
+        .map_err(db_err)
+    }
+
+    /// Send every due entry of `channel`. Returns the number delivered.
+    ///
+    /// Transient failures are rescheduled with backoff and hold back the rest
+    /// of that thread; permanent failures (or too many attempts) are marked
+    /// dead so they stop blocking the thread.
+    pub async fn drain(&self, channel: &dyn Channel) -> Result<usize> {
+        let _guard = self.drain_lock.lock().await;
+        let name = channel.name().to_string();
+        let now = now_ms();
+        let mut blocked: HashSet<String> = HashSet::new();
+        let mut delivered = 0;
+
+        for entry in self.pending(&name)? {
+            let thread_id = entry.message.thread_id.clone();
+            if blocked.contains(&thread_id) {
+                continue;
+            }
+            if entry.next_attempt_at > now {
+                blocked.insert(thread_id);
+                continue;
+            }
+
+            match channel.send(entry.message).await {
+                Ok(()) => {
+                    self.delete(entry.id)?;
+                    delivered += 1;
+                }
+                Err(e) => {
+                    let attempts = entry.attempts + 1;
+                    if !e.is_retryable() || attempts >= self.max_attempts {
+                        tracing::error!(
+                            "[{name}] Dropping outbox entry {} after {attempts} attempt(s): {e}",
+                            entry.id
+                        );
+                        self.mark(entry.id, "dead", attempts, &e.to_string(), 0)?;
+                    } else {
+                        let backoff = self
+                            .base_backoff
+                            .saturating_mul(1 << (attempts - 1).min(16))
+                            .min(self.max_backoff);
+                        let delay = e.retry_after().unwrap_or(backoff);
+                        tracing::warn!(
+                            "[{name}] Outbox entry {} failed ({e}), retrying in {delay:?}",
+                            entry.id
+                        );
+                        let retry_at = now + delay.as_millis() as i64;
+                        self.mark(entry.id, "pending", attempts, &e.to_string(), retry_at)?;
+                        blocked.insert(thread_id);
+                    }
+                }
+            }
+        }
+        Ok(delivered)
+    }
+
+    fn delete(&self, id: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM outbox WHERE id = ?1", params![id])
+            .map_err(db_err)?;
+        Ok(())
+    }
+
+    fn mark(&self, id: i64, status: &str, attempts: u32, error: &str, retry_at: i64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE outbox SET status = ?2, attempts = ?3, last_error = ?4, next_attempt_at = ?5
+             WHERE id = ?1",
+            params![id, status, attempts, error, retry_at],
+        )
+        .map_err(db_err)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bizclaw_core::types::{IncomingMessage, ThreadType};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio_stream::Stream;
+
+    /// Rejects sends to thread "a" while `down` is set.
+    struct Partial {
+        down: AtomicBool,
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Channel for Partial {
+        fn name(&self) -> &str {
+            "partial"
+        }
+        async fn connect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        async fn disconnect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn is_connected(&self) -> bool {
+            true
+        }
+        async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
+            Ok(Box::new(tokio_stream::empty()))
+        }
+        async fn send(&self, message: OutgoingMessage) -> Result<()> {
+            if message.thread_id == "a" && self.down.load(Ordering::SeqCst) {
+                return Err(BizClawError::Http("502".into()));
+            }
+            self.sent.lock().unwrap().push(message.content);
+            Ok(())
+        }
+    }
+
+    fn msg(thread: &str, text: &str) -> OutgoingMessage {
+        OutgoingMessage::text(thread, text, ThreadType::Direct)
+    }
+
+    #[tokio::test]
+    async fn test_failed_thread_keeps_order_others_proceed() {
+        let outbox = Outbox::in_memory().unwrap().with_backoff(Duration::ZERO);
+        let channel = Partial {
+            down: AtomicBool::new(true),
+            sent: Mutex::new(Vec::new()),
+        };
+        outbox.enqueue("partial", &msg("a", "a1")).unwrap();
+        outbox.enqueue("partial", &msg("b", "b1")).unwrap();
+        outbox.enqueue("partial", &msg("a", "a2")).unwrap();
+
+        assert_eq!(outbox.drain(&channel).await.unwrap(), 1);
+        assert_eq!(*channel.sent.lock().unwrap(), vec!["b1"]);
+        assert_eq!(outbox.pending_count("partial").unwrap(), 2);
+
+        channel.down.store(false, Ordering::SeqCst);
+        assert_eq!(outbox.drain(&channel).await.unwrap(), 2);
+        assert_eq!(*channel.sent.lock().unwrap(), vec!["b1", "a1", "a2"]);
+        assert_eq!(outbox.pending_count("partial").unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let outbox = Outbox::in_memory()
+            .unwrap()
+            .with_backoff(Duration::ZERO)
+            .with_max_attempts(2);
+        let channel = Partial {
+            down: AtomicBool::new(true),
+            sent: Mutex::new(Vec::new()),
+        };
+        outbox.enqueue("partial", &msg("a", "a1")).unwrap();
+
+        outbox.drain(&channel).await.unwrap();
+        outbox.drain(&channel).await.unwrap();
+        assert_eq!(outbox.pending_count("partial").unwrap(), 0);
+        assert_eq!(outbox.dead_count("partial").unwrap(), 1);
+    }
+
+    /// Fails every send of a part containing "fail" while `failing` is set.
+    struct Flaky {
+        failing: AtomicBool,
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Channel for Flaky {
+        fn name(&self) -> &str {
+            "discord"
+        }
+        async fn connect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        async fn disconnect(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn is_connected(&self) -> bool {
+            true
+        }
+        async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
+            Ok(Box::new(tokio_stream::empty()))
+        }
+        async fn send(&self, message: OutgoingMessage) -> Result<()> {
+            if message.content.contains("fail") && self.failing.load(Ordering::SeqCst) {
+                return Err(BizClawError::Http("502".into()));
+            }
+            self.sent.lock().unwrap().push(message.content);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_part_is_retried_alone() {
+        let outbox = Outbox::in_memory().unwrap().with_backoff(Duration::ZERO);
+        let channel = Flaky {
+            failing: AtomicBool::new(true),
+            sent: Mutex::new(Vec::new()),
+        };
+        // Four paragraphs, each near Discord's 2000 character limit
+        let paragraphs = ["a", "b", "fail", "d"].map(|p| format!("{p} {}", "x".repeat(1900)));
+        let ids = outbox
+            .enqueue("discord", &msg("t", &paragraphs.join("\n\n")))
+            .unwrap();
+        assert_eq!(ids.len(), 4);
+
+        assert_eq!(outbox.drain(&channel).await.unwrap(), 2);
+        assert_eq!(outbox.state(ids[1]).unwrap(), EntryState::Delivered);
+        assert_eq!(outbox.state(ids[2]).unwrap(), EntryState::Pending);
+
+        channel.failing.store(false, Ordering::SeqCst);
+        assert_eq!(outbox.drain(&channel).await.unwrap(), 2);
+        assert_eq!(*channel.sent.lock().unwrap(), paragraphs);
+    }
+
+    #[tokio::test]
+    async fn test_state_reports_dead_entries() {
+        let outbox = Outbox::in_memory().unwrap().with_max_attempts(1);
+        let channel = Partial {
+            down: AtomicBool::new(true),
+            sent: Mutex::new(Vec::new()),
+        };
+        let ids = outbox.enqueue("partial", &msg("a", "a1")).unwrap();
+        outbox.drain(&channel).await.unwrap();
+        assert!(matches!(outbox.state(ids[0]).unwrap(), EntryState::Dead(e) if e.contains("502")));
+    }
+
+    #[test]
+    fn test_entries_survive_reopen() {
+        let path = std::env::temp_dir().join(format!("bizclaw-outbox-{}.db", uuid::Uuid::new_v4()));
+        {
+            let outbox = Outbox::open(&path).unwrap();
+            outbox.enqueue("telegram", &msg("42", "queued")).unwrap();
+        }
+        let outbox = Outbox::open(&path).unwrap();
+        let pending = outbox.pending("telegram").unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].message.content, "queued");
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,175 @@
+//! Allowlists — drops messages from chats a channel isn't allowed to answer.
+//!
+//! Read from `[channel.telegram] allowed_chat_ids`, `[channel.discord]
+//! allowed_channel_ids` and `[channel.zalo.allowlist]`. An empty list
+//! allows everyone, as does a Zalo allowlist with `block_strangers` off.
+//! [`AccessMiddleware::reload`] swaps the lists while messages flow, so
+//! allowlist edits apply without a restart.
+
+use super::{Flow, MessageContext, MessageMiddleware};
+use async_trait::async_trait;
+use bizclaw_core::config::ChannelConfig;
+use bizclaw_core::error::Result;
+use bizclaw_core::types::{IncomingMessage, ThreadType};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+/// The allowlists of every channel, as of one config.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessPolicy {
+    telegram_chats: HashSet<String>,
+    discord_channels: HashSet<String>,
+    zalo_users: HashSet<String>,
+    zalo_groups: HashSet<String>,
+}
+
+impl AccessPolicy {
+    pub fn from_config(config: &ChannelConfig) -> Self {
+        let mut policy = Self::default();
+        if let Some(tg) = &config.telegram {
+            policy.telegram_chats = tg.allowed_chat_ids.iter().map(i64::to_string).collect();
+        }
+        if let Some(dc) = &config.discord {
+            policy.discord_channels = dc.allowed_channel_ids.iter().map(u64::to_string).collect();
+        }
+        if let Some(zalo) = &config.zalo
+            && zalo.allowlist.block_strangers
+        {
+            policy.zalo_users = zalo.allowlist.user_ids.iter().cloned().collect();
+            policy.zalo_groups = zalo.allowlist.group_ids.iter().cloned().collect();
+        }
+        policy
+    }
+
+    /// Whether `message` may be answered.
+    pub fn allows(&self, message: &IncomingMessage) -> bool {
+        let listed = |list: &HashSet<String>, id: &str| list.is_empty() || list.contains(id);
+        match message.channel.as_str() {
+            "telegram" => listed(&self.telegram_chats, &message.thread_id),
+            "discord" => listed(&self.discord_channels, &message.thread_id),
+            "zalo" if self.zalo_users.is_empty() && self.zalo_groups.is_empty() => true,
+            "zalo" => match message.thread_type {
+                ThreadType::Group => self.zalo_groups.contains(&message.thread_id),
+                ThreadType::Direct => self.zalo_users.contains(&message.sender_id),
+            },
+            _ => true,
+        }
+    }
+}
+
+/// Drops messages its [`AccessPolicy`] doesn't allow.
+pub struct AccessMiddleware {
+    policy: RwLock<Arc<AccessPolicy>>,
+}
+
+impl AccessMiddleware {
+    pub fn new(policy: AccessPolicy) -> Self {
+        Self {
+            policy: RwLock::new(Arc::new(policy)),
+        }
+    }
+
+    pub fn from_config(config: &ChannelConfig) -> Self {
+        Self::new(AccessPolicy::from_config(config))
+    }
+
+    /// Use the allowlists of a reloaded config from the next message on.
+    pub fn reload(&self, config: &ChannelConfig) {
+        let policy = AccessPolicy::from_config(config);
+        let mut current = self.policy.write().unwrap();
+        if **current != policy {
+            tracing::info!("Channel allowlists reloaded");
+            *current = Arc::new(policy);
+        }
+    }
+
+    fn policy(&self) -> Arc<AccessPolicy> {
+        self.policy.read().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl MessageMiddleware for AccessMiddleware {
+    fn name(&self) -> &str {
+        "access"
+    }
+
+    async fn incoming(&self, message: IncomingMessage, _ctx: &mut MessageContext) -> Result<Flow> {
+        if self.policy().allows(&message) {
+            Ok(Flow::Continue(message))
+        } else {
+            tracing::info!(
+                "[{}] Ignoring message from {} in {}: not on the allowlist",
+                message.channel,
+                message.sender_id,
+                message.thread_id
+            );
+            Ok(Flow::Drop)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::config::{TelegramChannelConfig, ZaloChannelConfig};
+
+    fn message(channel: &str, thread_id: &str, sender_id: &str, group: bool) -> IncomingMessage {
+        IncomingMessage {
+            channel: channel.into(),
+            thread_id: thread_id.into(),
+            sender_id: sender_id.into(),
+            sender_name: None,
+            content: "hi".into(),
+            thread_type: if group {
+                ThreadType::Group
+            } else {
+                ThreadType::Direct
+            },
+            timestamp: chrono::Utc::now(),
+            reply_to: None,
+            message_id: None,
+            media: Vec::new(),
+        }
+    }
+
+    async fn passes(stage: &AccessMiddleware, message: IncomingMessage) -> bool {
+        let flow = stage.incoming(message, &mut MessageContext::new()).await;
+        matches!(flow, Ok(Flow::Continue(_)))
+    }
+
+    #[tokio::test]
+    async fn test_allowlists_apply_and_reload() {
+        let mut config = ChannelConfig {
+            telegram: Some(TelegramChannelConfig {
+                enabled: true,
+                bot_token: "t".into(),
+                allowed_chat_ids: vec![42],
+            }),
+            zalo: Some(ZaloChannelConfig::default()),
+            ..Default::default()
+        };
+        let stage = AccessMiddleware::from_config(&config);
+        assert!(passes(&stage, message("telegram", "42", "u1", false)).await);
+        assert!(!passes(&stage, message("telegram", "7", "u1", false)).await);
+        // Nothing listed for Zalo, and other channels have no allowlist.
+        assert!(passes(&stage, message("zalo", "g1", "u1", true)).await);
+        assert!(passes(&stage, message("discord", "7", "u1", false)).await);
+
+        config.telegram.as_mut().unwrap().allowed_chat_ids = vec![7];
+        let zalo = config.zalo.as_mut().unwrap();
+        zalo.allowlist.user_ids = vec!["u1".into()];
+        zalo.allowlist.group_ids = vec!["g1".into()];
+        stage.reload(&config);
+        assert!(!passes(&stage, message("telegram", "42", "u1", false)).await);
+        assert!(passes(&stage, message("telegram", "7", "u1", false)).await);
+        assert!(passes(&stage, message("zalo", "g1", "u2", true)).await);
+        assert!(!passes(&stage, message("zalo", "g2", "u1", true)).await);
+        assert!(passes(&stage, message("zalo", "u1", "u1", false)).await);
+        assert!(!passes(&stage, message("zalo", "u2", "u2", false)).await);
+
+        config.zalo.as_mut().unwrap().allowlist.block_strangers = false;
+        stage.reload(&config);
+        assert!(passes(&stage, message("zalo", "u2", "u2", false)).await);
+    }
+}
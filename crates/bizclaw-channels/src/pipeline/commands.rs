@@ -0,0 +1,173 @@
+//! Command parsing — recognizes `/command args` messages.
+//!
+//! Telegram appends the bot's username in groups (`/reset@shop_bot`), other
+//! channels don't; the suffix is stripped so handlers see one form. The
+//! parsed command is put in [`MessageContext::command`], and commands
+//! registered with [`CommandMiddleware::with_command`] are answered right
+//! here without reaching the handler.
+
+use super::{Flow, MessageContext, MessageMiddleware};
+use async_trait::async_trait;
+use bizclaw_core::error::Result;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage};
+use std::collections::HashMap;
+
+/// A `/command` message, split up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedCommand {
+    /// Lowercase, without the slash.
+    pub name: String,
+    /// Everything after the command word, trimmed.
+    pub args: String,
+    /// `shop_bot` in `/reset@shop_bot`.
+    pub bot: Option<String>,
+}
+
+impl ParsedCommand {
+    /// `None` unless the text starts with `/` and a command word.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim_start();
+        let rest = text.strip_prefix('/')?;
+        let (word, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let (name, bot) = match word.split_once('@') {
+            Some((name, bot)) => (name, Some(bot.to_string())),
+            None => (word, None),
+        };
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return None;
+        }
+        Some(Self {
+            name: name.to_ascii_lowercase(),
+            args: args.trim().to_string(),
+            bot,
+        })
+    }
+
+    /// `/name args`, without the bot suffix.
+    pub fn to_text(&self) -> String {
+        if self.args.is_empty() {
+            format!("/{}", self.name)
+        } else {
+            format!("/{} {}", self.name, self.args)
+        }
+    }
+}
+
+type CommandFn = Box<dyn Fn(&ParsedCommand, &IncomingMessage) -> String + Send + Sync>;
+
+/// Parses commands and answers the ones registered on it.
+#[derive(Default)]
+pub struct CommandMiddleware {
+    commands: HashMap<String, CommandFn>,
+}
+
+impl CommandMiddleware {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer `/name` with `reply(command, message)` instead of the handler.
+    pub fn with_command(
+        mut self,
+        name: &str,
+        reply: impl Fn(&ParsedCommand, &IncomingMessage) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.commands.insert(
+            name.trim_start_matches('/').to_ascii_lowercase(),
+            Box::new(reply),
+        );
+        self
+    }
+}
+
+#[async_trait]
+impl MessageMiddleware for CommandMiddleware {
+    fn name(&self) -> &str {
+        "commands"
+    }
+
+    async fn incoming(
+        &self,
+        mut message: IncomingMessage,
+        ctx: &mut MessageContext,
+    ) -> Result<Flow> {
+        let Some(command) = ParsedCommand::parse(&message.content) else {
+            return Ok(Flow::Continue(message));
+        };
+        if let Some(reply) = self.commands.get(&command.name) {
+            let text = reply(&command, &message);
+            ctx.command = Some(command);
+            return Ok(Flow::Reply(OutgoingMessage::text(
+                &message.thread_id,
+                text,
+                message.thread_type.clone(),
+            )));
+        }
+        message.content = command.to_text();
+        ctx.command = Some(command);
+        Ok(Flow::Continue(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::types::ThreadType;
+
+    #[test]
+    fn test_parse() {
+        let cmd = ParsedCommand::parse("/Reset@shop_bot  now ").unwrap();
+        assert_eq!(cmd.name, "reset");
+        assert_eq!(cmd.args, "now");
+        assert_eq!(cmd.bot.as_deref(), Some("shop_bot"));
+        assert_eq!(cmd.to_text(), "/reset now");
+
+        assert_eq!(ParsedCommand::parse("/help").unwrap().args, "");
+        assert!(ParsedCommand::parse("hello /help").is_none());
+        assert!(ParsedCommand::parse("/").is_none());
+        assert!(ParsedCommand::parse("/usr/bin/ls").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_commands_are_normalized_or_answered() {
+        let middleware = CommandMiddleware::new()
+            .with_command("/ping", |_, msg| format!("pong {}", msg.sender_id));
+        let message = |content: &str| IncomingMessage {
+            channel: "telegram".into(),
+            thread_id: "t1".into(),
+            sender_id: "u1".into(),
+            sender_name: None,
+            content: content.into(),
+            thread_type: ThreadType::Group,
+            timestamp: chrono::Utc::now(),
+            reply_to: None,
+            message_id: None,
+            media: Vec::new(),
+        };
+
+        let mut ctx = MessageContext::new();
+        match middleware
+            .incoming(message("/reset@shop_bot"), &mut ctx)
+            .await
+            .unwrap()
+        {
+            Flow::Continue(msg) => assert_eq!(msg.content, "/reset"),
+            _ => panic!("expected the message to continue"),
+        }
+        assert_eq!(ctx.command.unwrap().name, "reset");
+
+        let mut ctx = MessageContext::new();
+        match middleware
+            .incoming(message("/PING"), &mut ctx)
+            .await
+            .unwrap()
+        {
+            Flow::Reply(reply) => assert_eq!(reply.content, "pong u1"),
+            _ => panic!("expected a reply"),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Message logging — one line per message and per reply.
+//!
+//! Only sizes and ids are logged, never the text, so logs don't collect
+//! customer conversations.
+
+use super::{Flow, MessageContext, MessageMiddleware};
+use async_trait::async_trait;
+use bizclaw_core::error::Result;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage};
+
+pub struct LoggingMiddleware;
+
+#[async_trait]
+impl MessageMiddleware for LoggingMiddleware {
+    fn name(&self) -> &str {
+        "logging"
+    }
+
+    async fn incoming(&self, message: IncomingMessage, _ctx: &mut MessageContext) -> Result<Flow> {
+        tracing::info!(
+            "📨 [{}] thread={} sender={} {} chars, {} attachment(s)",
+            message.channel,
+            message.thread_id,
+            message.sender_id,
+            message.content.chars().count(),
+            message.media.len()
+        );
+        Ok(Flow::Continue(message))
+    }
+
+    async fn outgoing(
+        &self,
+        incoming: &IncomingMessage,
+        reply: OutgoingMessage,
+        ctx: &mut MessageContext,
+    ) -> Result<Option<OutgoingMessage>> {
+        tracing::info!(
+            "📤 [{}] thread={} {} chars in {}ms",
+            incoming.channel,
+            reply.thread_id,
+            reply.content.chars().count(),
+            ctx.received_at.elapsed().as_millis()
+        );
+        if !ctx.annotations.is_empty() {
+            let mut notes: Vec<String> = ctx
+                .annotations
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect();
+            notes.sort();
+            tracing::debug!("[{}] {}", incoming.channel, notes.join(" "));
+        }
+        Ok(Some(reply))
+    }
+}
@@ -0,0 +1,350 @@
+//! Message pipeline — ordered middleware around a [`MessageHandler`].
+//!
+//! Channel middleware (see [`crate::middleware`]) wraps a single channel's
+//! transport. Pipeline stages instead see every message on its way to the
+//! handler and every reply on its way back, whatever the channel:
+//!
+//! ```text
+//! incoming → stage 1 → stage 2 → … → handler
+//! reply    ← stage 1 ← stage 2 ← … ←
+//! ```
+//!
+//! A stage can rewrite the message, answer it itself or drop it
+//! ([`Flow`]), and leave notes in the [`MessageContext`] for the stages
+//! after it and for the reply path. When a stage answers or drops a
+//! message, only the stages before it see the reply.
+//!
+//! The built-in stages are selected by name with `[channel] middleware`.
+//! `pii` is added whenever `[pii]` is enabled, first unless the list places
+//! it, so no later stage sees the unmasked text; `moderation` is added
+//! whenever `[moderation]` is enabled, last unless the list places it.
+//! A manager built from config puts [`AccessMiddleware`] before them all,
+//! so nothing else sees messages from chats off the allowlists.
+
+pub mod access;
+pub mod commands;
+pub mod logging;
+pub mod moderation;
+pub mod pii;
+pub mod trim;
+
+pub use access::AccessMiddleware;
+pub use commands::{CommandMiddleware, ParsedCommand};
+pub use logging::LoggingMiddleware;
+pub use moderation::{ModerationClassifier, ModerationMiddleware, ProviderClassifier};
+pub use pii::PiiMiddleware;
+pub use trim::TrimMiddleware;
+
+use crate::manager::{MessageHandler, ProgressReporter};
+use async_trait::async_trait;
+use bizclaw_core::config::BizClawConfig;
+use bizclaw_core::error::Result;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// What a stage decided about an incoming message.
+pub enum Flow {
+    /// Pass the (possibly rewritten) message on.
+    Continue(IncomingMessage),
+    /// Answer without asking the handler.
+    Reply(OutgoingMessage),
+    /// Ignore the message.
+    Drop,
+}
+
+/// Per-message state shared by the stages.
+#[derive(Debug, Clone)]
+pub struct MessageContext {
+    pub received_at: Instant,
+    /// Set by [`CommandMiddleware`] when the message is a `/command`.
+    pub command: Option<ParsedCommand>,
+    /// Free-form notes, e.g. `"moderation" → "flagged"`.
+    pub annotations: HashMap<String, String>,
+}
+
+impl MessageContext {
+    pub fn new() -> Self {
+        Self {
+            received_at: Instant::now(),
+            command: None,
+            annotations: HashMap::new(),
+        }
+    }
+
+    pub fn annotate(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.annotations.insert(key.into(), value.into());
+    }
+}
+
+impl Default for MessageContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One stage of the pipeline. Both hooks default to passing through.
+#[async_trait]
+pub trait MessageMiddleware: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn incoming(&self, message: IncomingMessage, ctx: &mut MessageContext) -> Result<Flow> {
+        let _ = ctx;
+        Ok(Flow::Continue(message))
+    }
+
+    /// Called with the reply to `incoming`; `None` suppresses it.
+    async fn outgoing(
+        &self,
+        incoming: &IncomingMessage,
+        reply: OutgoingMessage,
+        ctx: &mut MessageContext,
+    ) -> Result<Option<OutgoingMessage>> {
+        let _ = (incoming, ctx);
+        Ok(Some(reply))
+    }
+}
+
+/// A [`MessageHandler`] that runs its stages around an inner handler.
+pub struct Pipeline {
+    stages: Vec<Arc<dyn MessageMiddleware>>,
+    handler: Arc<dyn MessageHandler>,
+}
+
+impl Pipeline {
+    pub fn new(handler: Arc<dyn MessageHandler>) -> Self {
+        Self {
+            stages: Vec::new(),
+            handler,
+        }
+    }
+
+    /// Append a stage; stages run in the order they are added.
+    pub fn with(mut self, stage: Arc<dyn MessageMiddleware>) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    pub fn with_stages(
+        mut self,
+        stages: impl IntoIterator<Item = Arc<dyn MessageMiddleware>>,
+    ) -> Self {
+        self.stages.extend(stages);
+        self
+    }
+
+    pub fn stage_names(&self) -> Vec<&str> {
+        self.stages.iter().map(|s| s.name()).collect()
+    }
+
+    async fn run(
+        &self,
+        message: IncomingMessage,
+        progress: Option<ProgressReporter>,
+    ) -> Result<Option<OutgoingMessage>> {
+        let mut ctx = MessageContext::new();
+        let original = message.clone();
+        // `None` once a stage has answered.
+        let mut current = Some(message);
+        let mut reply = None;
+        let mut passed = 0;
+
+        for stage in &self.stages {
+            let Some(message) = current.take() else { break };
+            match stage.incoming(message, &mut ctx).await? {
+                Flow::Continue(next) => {
+                    current = Some(next);
+                    passed += 1;
+                }
+                Flow::Reply(answer) => reply = Some(answer),
+                Flow::Drop => {
+                    tracing::debug!(
+                        "[{}] Message dropped by {} (thread={})",
+                        original.channel,
+                        stage.name(),
+                        original.thread_id
+                    );
+                    return Ok(None);
+                }
+            }
+        }
+
+        let incoming = match current {
+            Some(message) => {
+                reply = match progress {
+                    Some(progress) => {
+                        self.handler
+                            .handle_with_progress(message.clone(), progress)
+                            .await?
+                    }
+                    None => self.handler.handle(message.clone()).await?,
+                };
+                message
+            }
+            None => original,
+        };
+
+        for stage in self.stages[..passed].iter().rev() {
+            let Some(answer) = reply.take() else { break };
+            reply = stage.outgoing(&incoming, answer, &mut ctx).await?;
+        }
+        Ok(reply)
+    }
+}
+
+#[async_trait]
+impl MessageHandler for Pipeline {
+    async fn handle(&self, message: IncomingMessage) -> Result<Option<OutgoingMessage>> {
+        self.run(message, None).await
+    }
+
+    async fn handle_with_progress(
+        &self,
+        message: IncomingMessage,
+        progress: ProgressReporter,
+    ) -> Result<Option<OutgoingMessage>> {
+        self.run(message, Some(progress)).await
+    }
+}
+
+/// The built-in stages named in `[channel] middleware`, in order. Unknown
+/// names are logged and skipped. `classifier` backs the moderation stage
+/// when `[moderation] classifier` is on.
+pub fn from_config(
+    config: &BizClawConfig,
+    classifier: Option<Arc<dyn ModerationClassifier>>,
+) -> Vec<Arc<dyn MessageMiddleware>> {
+    let mut moderation = moderation::from_config(&config.moderation, classifier)
+        .map(|stage| Arc::new(stage) as Arc<dyn MessageMiddleware>);
+    let mut pii =
+        pii::from_config(config).map(|stage| Arc::new(stage) as Arc<dyn MessageMiddleware>);
+    let mut stages: Vec<Arc<dyn MessageMiddleware>> = Vec::new();
+    if !config.channel.middleware.iter().any(|name| name == "pii") {
+        stages.extend(pii.take());
+    }
+    for name in &config.channel.middleware {
+        match name.as_str() {
+            "logging" => stages.push(Arc::new(LoggingMiddleware)),
+            "trim" => stages.push(Arc::new(TrimMiddleware)),
+            "commands" => stages.push(Arc::new(CommandMiddleware::new())),
+            "moderation" => stages.extend(moderation.take()),
+            "pii" => stages.extend(pii.take()),
+            other => tracing::warn!("Unknown middleware in [channel] middleware: {other}"),
+        }
+    }
+    stages.extend(moderation);
+    stages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::error::BizClawError;
+    use bizclaw_core::types::ThreadType;
+    use std::sync::Mutex;
+
+    /// Records what it sees under its name.
+    struct Recorder {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        flow: fn(IncomingMessage) -> Flow,
+    }
+
+    #[async_trait]
+    impl MessageMiddleware for Recorder {
+        fn name(&self) -> &str {
+            self.name
+        }
+        async fn incoming(
+            &self,
+            message: IncomingMessage,
+            ctx: &mut MessageContext,
+        ) -> Result<Flow> {
+            self.log.lock().unwrap().push(format!("in {}", self.name));
+            ctx.annotate(self.name, "seen");
+            Ok((self.flow)(message))
+        }
+        async fn outgoing(
+            &self,
+            _incoming: &IncomingMessage,
+            mut reply: OutgoingMessage,
+            ctx: &mut MessageContext,
+        ) -> Result<Option<OutgoingMessage>> {
+            self.log.lock().unwrap().push(format!("out {}", self.name));
+            assert!(ctx.annotations.contains_key(self.name));
+            reply.content.push_str(&format!(" +{}", self.name));
+            Ok(Some(reply))
+        }
+    }
+
+    fn incoming(content: &str) -> IncomingMessage {
+        IncomingMessage {
+            channel: "test".into(),
+            thread_id: "t1".into(),
+            sender_id: "u1".into(),
+            sender_name: None,
+            content: content.into(),
+            thread_type: ThreadType::Direct,
+            timestamp: chrono::Utc::now(),
+            reply_to: None,
+            message_id: None,
+            media: Vec::new(),
+        }
+    }
+
+    /// A recorded stage's name and what it does with each message.
+    type Stage = (&'static str, fn(IncomingMessage) -> Flow);
+
+    fn build(log: &Arc<Mutex<Vec<String>>>, flows: &[Stage]) -> Pipeline {
+        let echo = |msg: IncomingMessage| async move {
+            Ok::<_, BizClawError>(Some(OutgoingMessage::text(
+                &msg.thread_id,
+                format!("echo {}", msg.content),
+                msg.thread_type,
+            )))
+        };
+        let mut pipeline = Pipeline::new(Arc::new(echo));
+        for &(name, flow) in flows {
+            pipeline = pipeline.with(Arc::new(Recorder {
+                name,
+                log: log.clone(),
+                flow,
+            }));
+        }
+        pipeline
+    }
+
+    #[tokio::test]
+    async fn test_stages_run_in_order_around_handler() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let upper = |mut m: IncomingMessage| {
+            m.content = m.content.to_uppercase();
+            Flow::Continue(m)
+        };
+        let pipeline = build(&log, &[("a", Flow::Continue), ("b", upper)]);
+        let reply = pipeline.handle(incoming("hi")).await.unwrap().unwrap();
+        assert_eq!(reply.content, "echo HI +b +a");
+        assert_eq!(*log.lock().unwrap(), ["in a", "in b", "out b", "out a"]);
+    }
+
+    #[tokio::test]
+    async fn test_short_circuit_and_drop() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let answer = |m: IncomingMessage| {
+            Flow::Reply(OutgoingMessage::text(&m.thread_id, "pong", m.thread_type))
+        };
+        let pipeline = build(
+            &log,
+            &[("a", Flow::Continue), ("b", answer), ("c", Flow::Continue)],
+        );
+        let reply = pipeline.handle(incoming("/ping")).await.unwrap().unwrap();
+        assert_eq!(reply.content, "pong +a");
+        assert_eq!(*log.lock().unwrap(), ["in a", "in b", "out a"]);
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let pipeline = build(&log, &[("a", |_| Flow::Drop), ("b", Flow::Continue)]);
+        assert!(pipeline.handle(incoming("spam")).await.unwrap().is_none());
+        assert_eq!(*log.lock().unwrap(), ["in a"]);
+    }
+}
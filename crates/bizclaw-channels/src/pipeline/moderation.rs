@@ -0,0 +1,545 @@
+//! Content moderation — screens user messages and model replies.
+//!
+//! Text is checked against the `[moderation]` keywords and patterns first;
+//! with a [`ModerationClassifier`] attached, text the rules let through is
+//! also classified by a model. A flagged message is either blocked (replaced
+//! by `block_message`) or redacted (matches masked). A classifier flag can't
+//! be redacted, so it always blocks.
+//!
+//! Every decision is logged as an `[audit]` warning and, with `audit_log`,
+//! appended to `~/.bizclaw/moderation.jsonl` — without the flagged text.
+//!
+//! The classifier fails open: if the model can't be reached, the rules'
+//! verdict stands.
+
+use super::{Flow, MessageContext, MessageMiddleware};
+use async_trait::async_trait;
+use bizclaw_core::config::{BizClawConfig, ModerationConfig};
+use bizclaw_core::error::Result;
+use bizclaw_core::traits::Provider;
+use bizclaw_core::traits::provider::GenerateParams;
+use bizclaw_core::types::{IncomingMessage, Message, OutgoingMessage};
+use regex::Regex;
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const REDACTION: &str = "***";
+
+/// What happens to flagged text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationAction {
+    Block,
+    Redact,
+}
+
+impl ModerationAction {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "block" => Some(Self::Block),
+            "redact" => Some(Self::Redact),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Block => "block",
+            Self::Redact => "redact",
+        }
+    }
+}
+
+/// Decides whether text is acceptable; used after the rules.
+#[async_trait]
+pub trait ModerationClassifier: Send + Sync {
+    /// `Some(category)` when `text` should be flagged.
+    async fn classify(&self, text: &str) -> Result<Option<String>>;
+}
+
+const CLASSIFIER_PROMPT: &str = "You are a content moderation classifier for a business chat \
+assistant. Decide whether the user's text contains harassment, hate speech, sexual content \
+involving minors, threats of violence, self-harm encouragement, scams or other illegal activity. \
+Reply with exactly SAFE, or FLAGGED: <category> — nothing else.";
+
+/// Asks a chat model to classify text.
+pub struct ProviderClassifier {
+    provider: Arc<dyn Provider>,
+    params: GenerateParams,
+}
+
+impl ProviderClassifier {
+    pub fn new(provider: Arc<dyn Provider>, model: &str) -> Self {
+        Self {
+            provider,
+            params: GenerateParams {
+                model: model.to_string(),
+                temperature: 0.0,
+                max_tokens: 20,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// `[moderation] classifier_model` on `provider`, `default_model` if unset.
+    pub fn from_config(provider: Arc<dyn Provider>, config: &BizClawConfig) -> Self {
+        let model = match config.moderation.classifier_model.as_str() {
+            "" => config.default_model.as_str(),
+            model => model,
+        };
+        Self::new(provider, model)
+    }
+}
+
+#[async_trait]
+impl ModerationClassifier for ProviderClassifier {
+    async fn classify(&self, text: &str) -> Result<Option<String>> {
+        let messages = [Message::system(CLASSIFIER_PROMPT), Message::user(text)];
+        let response = self.provider.chat(&messages, &[], &self.params).await?;
+        Ok(parse_verdict(&response.content.unwrap_or_default()))
+    }
+}
+
+/// `FLAGGED: scam` → `Some("scam")`; anything not starting with FLAGGED is
+/// safe.
+fn parse_verdict(answer: &str) -> Option<String> {
+    let answer = answer.trim();
+    let head = answer.get(..7)?;
+    if !head.eq_ignore_ascii_case("flagged") {
+        return None;
+    }
+    let category = answer[7..].trim_start_matches([':', ' ']).trim();
+    Some(if category.is_empty() {
+        "flagged".to_string()
+    } else {
+        category.to_lowercase()
+    })
+}
+
+/// Compiled keyword and pattern rules.
+#[derive(Debug, Clone, Default)]
+pub struct ModerationRules {
+    rules: Vec<(String, Regex)>,
+}
+
+impl ModerationRules {
+    /// Invalid patterns are logged and skipped.
+    pub fn new(keywords: &[String], patterns: &[String]) -> Self {
+        let mut rules = Vec::new();
+        for keyword in keywords.iter().map(|k| k.trim()).filter(|k| !k.is_empty()) {
+            // Word boundaries only where the keyword starts or ends with a
+            // word character, so "ass" doesn't match "class".
+            let boundary = |c: Option<char>| {
+                if c.is_some_and(|c| c.is_alphanumeric()) {
+                    r"\b"
+                } else {
+                    ""
+                }
+            };
+            let pattern = format!(
+                "(?i){}{}{}",
+                boundary(keyword.chars().next()),
+                regex::escape(keyword),
+                boundary(keyword.chars().last())
+            );
+            if let Ok(re) = Regex::new(&pattern) {
+                rules.push((format!("keyword:{keyword}"), re));
+            }
+        }
+        for pattern in patterns {
+            match Regex::new(pattern) {
+                Ok(re) => rules.push((format!("pattern:{pattern}"), re)),
+                Err(e) => tracing::warn!("Invalid [moderation] pattern '{pattern}': {e}"),
+            }
+        }
+        Self { rules }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Names of the rules `text` breaks.
+    pub fn matches(&self, text: &str) -> Vec<&str> {
+        self.rules
+            .iter()
+            .filter(|(_, re)| re.is_match(text))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// `text` with every match masked.
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (_, re) in &self.rules {
+            if re.is_match(&text) {
+                text = re.replace_all(&text, REDACTION).into_owned();
+            }
+        }
+        text
+    }
+}
+
+/// One moderation decision, as written to the audit log.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModerationEvent {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub channel: String,
+    pub thread_id: String,
+    pub sender_id: String,
+    /// `input` or `output`.
+    pub direction: &'static str,
+    /// `block` or `redact`.
+    pub action: &'static str,
+    /// Matched rules, or `classifier:<category>`.
+    pub reasons: Vec<String>,
+}
+
+/// Append-only JSON lines file of moderation events.
+pub struct AuditLog {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn default_path() -> PathBuf {
+        BizClawConfig::home_dir().join("moderation.jsonl")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, event: &ModerationEvent) -> Result<()> {
+        let line = serde_json::to_string(event)?;
+        let _guard = self.lock.lock().unwrap();
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{line}")?;
+        Ok(())
+    }
+}
+
+/// The moderation pipeline stage.
+pub struct ModerationMiddleware {
+    rules: ModerationRules,
+    action: ModerationAction,
+    block_message: String,
+    check_input: bool,
+    check_output: bool,
+    classifier: Option<Arc<dyn ModerationClassifier>>,
+    audit: Option<AuditLog>,
+}
+
+/// Why a text was flagged, and whether the flag can be redacted.
+struct Verdict {
+    reasons: Vec<String>,
+    redactable: bool,
+}
+
+impl ModerationMiddleware {
+    pub fn from_config(config: &ModerationConfig) -> Self {
+        let action = ModerationAction::parse(&config.action).unwrap_or_else(|| {
+            tracing::warn!(
+                "Unknown [moderation] action '{}', blocking instead",
+                config.action
+            );
+            ModerationAction::Block
+        });
+        Self {
+            rules: ModerationRules::new(&config.keywords, &config.patterns),
+            action,
+            block_message: config.block_message.clone(),
+            check_input: config.check_input,
+            check_output: config.check_output,
+            classifier: None,
+            audit: config
+                .audit_log
+                .then(|| AuditLog::new(AuditLog::default_path())),
+        }
+    }
+
+    pub fn with_classifier(mut self, classifier: Arc<dyn ModerationClassifier>) -> Self {
+        self.classifier = Some(classifier);
+        self
+    }
+
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit = Some(log);
+        self
+    }
+
+    async fn check(&self, text: &str) -> Option<Verdict> {
+        if text.trim().is_empty() {
+            return None;
+        }
+        let matched = self.rules.matches(text);
+        if !matched.is_empty() {
+            return Some(Verdict {
+                reasons: matched.into_iter().map(String::from).collect(),
+                redactable: true,
+            });
+        }
+        let classifier = self.classifier.as_ref()?;
+        match classifier.classify(text).await {
+            Ok(Some(category)) => Some(Verdict {
+                reasons: vec![format!("classifier:{category}")],
+                redactable: false,
+            }),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::warn!("Moderation classifier failed, using rules only: {e}");
+                None
+            }
+        }
+    }
+
+    /// The action taken for `verdict`; redaction falls back to blocking
+    /// when there is nothing to mask.
+    fn action_for(&self, verdict: &Verdict) -> ModerationAction {
+        if verdict.redactable {
+            self.action
+        } else {
+            ModerationAction::Block
+        }
+    }
+
+    fn audit(
+        &self,
+        message: &IncomingMessage,
+        direction: &'static str,
+        action: ModerationAction,
+        verdict: Verdict,
+        ctx: &mut MessageContext,
+    ) {
+        tracing::warn!(
+            "[audit] Moderation {} {direction} in {}:{} from {} ({})",
+            action.as_str(),
+            message.channel,
+            message.thread_id,
+            message.sender_id,
+            verdict.reasons.join(", ")
+        );
+        ctx.annotate(
+            format!("moderation.{direction}"),
+            format!("{}:{}", action.as_str(), verdict.reasons.join(",")),
+        );
+        if let Some(log) = &self.audit {
+            let event = ModerationEvent {
+                timestamp: chrono::Utc::now(),
+                channel: message.channel.clone(),
+                thread_id: message.thread_id.clone(),
+                sender_id: message.sender_id.clone(),
+                direction,
+                action: action.as_str(),
+                reasons: verdict.reasons,
+            };
+            if let Err(e) = log.record(&event) {
+                tracing::warn!("Failed to write {}: {e}", log.path().display());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl MessageMiddleware for ModerationMiddleware {
+    fn name(&self) -> &str {
+        "moderation"
+    }
+
+    async fn incoming(
+        &self,
+        mut message: IncomingMessage,
+        ctx: &mut MessageContext,
+    ) -> Result<Flow> {
+        if !self.check_input {
+            return Ok(Flow::Continue(message));
+        }
+        let Some(verdict) = self.check(&message.content).await else {
+            return Ok(Flow::Continue(message));
+        };
+        let action = self.action_for(&verdict);
+        self.audit(&message, "input", action, verdict, ctx);
+        match action {
+            ModerationAction::Block => Ok(Flow::Reply(OutgoingMessage::text(
+                &message.thread_id,
+                &self.block_message,
+                message.thread_type.clone(),
+            ))),
+            ModerationAction::Redact => {
+                message.content = self.rules.redact(&message.content);
+                Ok(Flow::Continue(message))
+            }
+        }
+    }
+
+    async fn outgoing(
+        &self,
+        incoming: &IncomingMessage,
+        mut reply: OutgoingMessage,
+        ctx: &mut MessageContext,
+    ) -> Result<Option<OutgoingMessage>> {
+        if !self.check_output {
+            return Ok(Some(reply));
+        }
+        let Some(verdict) = self.check(&reply.content).await else {
+            return Ok(Some(reply));
+        };
+        let action = self.action_for(&verdict);
+        self.audit(incoming, "output", action, verdict, ctx);
+        match action {
+            ModerationAction::Block => {
+                reply.content = self.block_message.clone();
+                reply.blocks.clear();
+            }
+            // Blocks carry their own copies of the text; only the plain
+            // version can be masked reliably.
+            ModerationAction::Redact => {
+                reply.content = self.rules.redact(&reply.content);
+                reply.blocks.clear();
+            }
+        }
+        Ok(Some(reply))
+    }
+}
+
+/// The stage for `[moderation]`, `None` when moderation is disabled.
+pub fn from_config(
+    config: &ModerationConfig,
+    classifier: Option<Arc<dyn ModerationClassifier>>,
+) -> Option<ModerationMiddleware> {
+    if !config.enabled {
+        return None;
+    }
+    let mut stage = ModerationMiddleware::from_config(config);
+    if config.classifier {
+        match classifier {
+            Some(classifier) => stage = stage.with_classifier(classifier),
+            None => tracing::warn!("[moderation] classifier unavailable, using rules only"),
+        }
+    }
+    if stage.rules.is_empty() && stage.classifier.is_none() {
+        tracing::warn!("[moderation] is enabled without keywords, patterns or classifier");
+    }
+    Some(stage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::types::ThreadType;
+
+    struct FlagScams;
+
+    #[async_trait]
+    impl ModerationClassifier for FlagScams {
+        async fn classify(&self, text: &str) -> Result<Option<String>> {
+            Ok(text.contains("wire me").then(|| "scam".to_string()))
+        }
+    }
+
+    fn config(action: &str) -> ModerationConfig {
+        ModerationConfig {
+            enabled: true,
+            keywords: vec!["casino".into(), "lừa đảo".into()],
+            patterns: vec![r"\d{4}-\d{4}".into(), "(".into()],
+            action: action.into(),
+            audit_log: false,
+            ..Default::default()
+        }
+    }
+
+    fn message(content: &str) -> IncomingMessage {
+        IncomingMessage {
+            channel: "zalo".into(),
+            thread_id: "t1".into(),
+            sender_id: "u1".into(),
+            sender_name: None,
+            content: content.into(),
+            thread_type: ThreadType::Direct,
+            timestamp: chrono::Utc::now(),
+            reply_to: None,
+            message_id: None,
+            media: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let c = config("block");
+        let rules = ModerationRules::new(&c.keywords, &c.patterns);
+        assert_eq!(rules.matches("Best CASINO in town"), ["keyword:casino"]);
+        assert!(rules.matches("casinos").is_empty());
+        assert_eq!(rules.matches("Đây là Lừa Đảo").len(), 1);
+        assert_eq!(rules.redact("casino code 1234-5678"), "*** code ***");
+    }
+
+    #[test]
+    fn test_parse_verdict() {
+        assert_eq!(parse_verdict("SAFE"), None);
+        assert_eq!(parse_verdict("FLAGGED: Scam"), Some("scam".into()));
+        assert_eq!(parse_verdict("flagged"), Some("flagged".into()));
+        assert_eq!(parse_verdict("ok"), None);
+    }
+
+    #[tokio::test]
+    async fn test_block_input_and_redact_output() {
+        let path =
+            std::env::temp_dir().join(format!("bizclaw-moderation-{}.jsonl", uuid::Uuid::new_v4()));
+        let stage = ModerationMiddleware::from_config(&config("redact"))
+            .with_classifier(Arc::new(FlagScams))
+            .with_audit_log(AuditLog::new(&path));
+        let mut ctx = MessageContext::new();
+
+        // Rule matches are masked and the message goes on.
+        match stage
+            .incoming(message("casino tonight?"), &mut ctx)
+            .await
+            .unwrap()
+        {
+            Flow::Continue(msg) => assert_eq!(msg.content, "*** tonight?"),
+            _ => panic!("expected the message to continue"),
+        }
+        // Classifier flags can't be masked, so they block.
+        match stage
+            .incoming(message("wire me 100$"), &mut ctx)
+            .await
+            .unwrap()
+        {
+            Flow::Reply(reply) => assert_eq!(reply.content, "Sorry, I can't help with that."),
+            _ => panic!("expected a block"),
+        }
+        let reply = OutgoingMessage::text("t1", "Call 0909-1234", ThreadType::Direct);
+        let reply = stage
+            .outgoing(&message("hi"), reply, &mut ctx)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.content, "Call ***");
+        assert!(ctx.annotations["moderation.output"].starts_with("redact:"));
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let events: Vec<serde_json::Value> = log
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1]["action"], "block");
+        assert_eq!(events[1]["reasons"][0], "classifier:scam");
+        assert!(!log.contains("wire me"));
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,355 @@
+//! PII redaction — masks personal data before it reaches the model.
+//!
+//! Emails, phone numbers, card numbers and national IDs (Vietnamese CCCD,
+//! US SSN) in incoming messages are replaced by placeholders such as
+//! `[email]`, so they never end up in provider requests, conversation
+//! history or logs. Card numbers are only masked when they pass the Luhn
+//! check, which keeps order numbers and amounts intact.
+//!
+//! The `[pii]` policy decides per channel whether to mask always, only
+//! when the provider is a remote API, or not at all.
+
+use super::{Flow, MessageContext, MessageMiddleware};
+use async_trait::async_trait;
+use bizclaw_core::config::{BizClawConfig, PiiConfig};
+use bizclaw_core::error::Result;
+use bizclaw_core::types::IncomingMessage;
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+/// Providers that run on this machine; nothing sent to them leaves it.
+const LOCAL_PROVIDERS: &[&str] = &["brain", "ollama", "llamacpp", "vllm"];
+
+/// A kind of personal data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PiiKind {
+    Email,
+    Phone,
+    Card,
+    NationalId,
+}
+
+impl PiiKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "email" => Some(Self::Email),
+            "phone" => Some(Self::Phone),
+            "card" => Some(Self::Card),
+            "national_id" | "id" => Some(Self::NationalId),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Phone => "phone",
+            Self::Card => "card",
+            Self::NationalId => "national_id",
+        }
+    }
+
+    fn placeholder(self) -> &'static str {
+        match self {
+            Self::Email => "[email]",
+            Self::Phone => "[phone]",
+            Self::Card => "[card]",
+            Self::NationalId => "[id]",
+        }
+    }
+
+    fn pattern(self) -> &'static str {
+        match self {
+            Self::Email => r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}",
+            Self::Phone => r"(?:\+\d{1,3}[ .-]?|\b)\d{2,4}[ .-]?\d{3}[ .-]?\d{3,4}\b",
+            Self::Card => r"\b\d(?:[ -]?\d){12,18}\b",
+            // SSN, then CCCD (12 digits, leading 0).
+            Self::NationalId => r"\b\d{3}-\d{2}-\d{4}\b|\b0\d{11}\b",
+        }
+    }
+
+    /// Extra checks a regex match must pass.
+    fn accepts(self, matched: &str) -> bool {
+        let digits: Vec<u32> = matched.chars().filter_map(|c| c.to_digit(10)).collect();
+        match self {
+            Self::Phone => (9..=15).contains(&digits.len()),
+            Self::Card => luhn_valid(&digits),
+            Self::Email | Self::NationalId => true,
+        }
+    }
+}
+
+/// The Luhn checksum every payment card number satisfies.
+fn luhn_valid(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (1, doubled) if doubled > 9 => doubled - 9,
+            (1, doubled) => doubled,
+            _ => d,
+        })
+        .sum();
+    !digits.is_empty() && sum.is_multiple_of(10)
+}
+
+/// When a channel's messages are masked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiPolicy {
+    Mask,
+    /// Only when the provider is a remote API.
+    Remote,
+    Off,
+}
+
+impl PiiPolicy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "mask" => Some(Self::Mask),
+            "remote" => Some(Self::Remote),
+            "off" => Some(Self::Off),
+            _ => None,
+        }
+    }
+}
+
+/// Compiled detectors for the configured kinds.
+#[derive(Debug, Clone)]
+pub struct PiiRedactor {
+    detectors: Vec<(PiiKind, Regex)>,
+}
+
+impl PiiRedactor {
+    /// Card numbers are checked before national IDs and phones, which
+    /// would otherwise claim their digits.
+    pub fn new(kinds: &[PiiKind]) -> Self {
+        let order = [
+            PiiKind::Email,
+            PiiKind::Card,
+            PiiKind::NationalId,
+            PiiKind::Phone,
+        ];
+        let detectors = order
+            .into_iter()
+            .filter(|kind| kinds.contains(kind))
+            .map(|kind| (kind, Regex::new(kind.pattern()).expect("valid PII pattern")))
+            .collect();
+        Self { detectors }
+    }
+
+    /// `text` with personal data replaced, and how many of each kind.
+    pub fn redact(&self, text: &str) -> (String, Vec<(PiiKind, usize)>) {
+        let mut text = text.to_string();
+        let mut found = Vec::new();
+        for (kind, re) in &self.detectors {
+            let mut count = 0;
+            let masked = re.replace_all(&text, |caps: &Captures| {
+                let matched = &caps[0];
+                if kind.accepts(matched) {
+                    count += 1;
+                    kind.placeholder().to_string()
+                } else {
+                    matched.to_string()
+                }
+            });
+            if count > 0 {
+                text = masked.into_owned();
+                found.push((*kind, count));
+            }
+        }
+        (text, found)
+    }
+}
+
+/// The PII pipeline stage.
+pub struct PiiMiddleware {
+    redactor: PiiRedactor,
+    /// Whether messages get masked when the policy says `remote`.
+    remote_provider: bool,
+    policy: PiiPolicy,
+    channels: HashMap<String, PiiPolicy>,
+}
+
+impl PiiMiddleware {
+    /// `remote_provider` tells the `remote` policy whether prompts leave
+    /// the machine.
+    pub fn from_config(config: &PiiConfig, remote_provider: bool) -> Self {
+        let policy = |s: &str| {
+            PiiPolicy::parse(s).unwrap_or_else(|| {
+                tracing::warn!("Unknown [pii] policy '{s}', masking instead");
+                PiiPolicy::Mask
+            })
+        };
+        let mut kinds = Vec::new();
+        for kind in &config.kinds {
+            match PiiKind::parse(kind) {
+                Some(kind) => kinds.push(kind),
+                None => tracing::warn!("Unknown [pii] kind '{kind}'"),
+            }
+        }
+        Self {
+            redactor: PiiRedactor::new(&kinds),
+            remote_provider,
+            policy: policy(&config.policy),
+            channels: config
+                .channels
+                .iter()
+                .map(|(channel, p)| (channel.clone(), policy(p)))
+                .collect(),
+        }
+    }
+
+    fn masks(&self, channel: &str) -> bool {
+        match self.channels.get(channel).copied().unwrap_or(self.policy) {
+            PiiPolicy::Mask => true,
+            PiiPolicy::Remote => self.remote_provider,
+            PiiPolicy::Off => false,
+        }
+    }
+}
+
+#[async_trait]
+impl MessageMiddleware for PiiMiddleware {
+    fn name(&self) -> &str {
+        "pii"
+    }
+
+    async fn incoming(
+        &self,
+        mut message: IncomingMessage,
+        ctx: &mut MessageContext,
+    ) -> Result<Flow> {
+        if !self.masks(&message.channel) {
+            return Ok(Flow::Continue(message));
+        }
+        let (masked, found) = self.redactor.redact(&message.content);
+        if !found.is_empty() {
+            let summary: Vec<String> = found
+                .iter()
+                .map(|(kind, n)| format!("{}:{n}", kind.as_str()))
+                .collect();
+            tracing::debug!(
+                "[{}] Masked personal data in thread={} ({})",
+                message.channel,
+                message.thread_id,
+                summary.join(", ")
+            );
+            ctx.annotate("pii", summary.join(","));
+            message.content = masked;
+        }
+        Ok(Flow::Continue(message))
+    }
+}
+
+/// Whether the configured provider sends prompts off this machine.
+pub fn provider_is_remote(config: &BizClawConfig) -> bool {
+    let provider = if config.llm.provider.is_empty() {
+        &config.default_provider
+    } else {
+        &config.llm.provider
+    };
+    if let Some(url) = provider.strip_prefix("custom:") {
+        return !["localhost", "127.0.0.1", "[::1]"]
+            .iter()
+            .any(|host| url.contains(host));
+    }
+    !LOCAL_PROVIDERS.contains(&provider.as_str())
+}
+
+/// The stage for `[pii]`, `None` when it is disabled.
+pub fn from_config(config: &BizClawConfig) -> Option<PiiMiddleware> {
+    config
+        .pii
+        .enabled
+        .then(|| PiiMiddleware::from_config(&config.pii, provider_is_remote(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::types::ThreadType;
+
+    fn all() -> PiiRedactor {
+        PiiRedactor::new(&[
+            PiiKind::Email,
+            PiiKind::Phone,
+            PiiKind::Card,
+            PiiKind::NationalId,
+        ])
+    }
+
+    #[test]
+    fn test_redact() {
+        let (text, found) = all().redact(
+            "Mail an.nguyen@shop.com.vn or call +84 909 123 456 / 0909123456. \
+             Card 4111 1111 1111 1111, CCCD 001099012345, SSN 123-45-6789.",
+        );
+        assert_eq!(
+            text,
+            "Mail [email] or call [phone] / [phone]. Card [card], CCCD [id], SSN [id]."
+        );
+        assert_eq!(
+            found,
+            [
+                (PiiKind::Email, 1),
+                (PiiKind::Card, 1),
+                (PiiKind::NationalId, 2),
+                (PiiKind::Phone, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_pii_numbers_are_kept() {
+        let text = "Order 12345678 costs 1.250.000đ, card 4111 1111 1111 1112, on 2026-03-02.";
+        let (masked, found) = all().redact(text);
+        assert_eq!(masked, text);
+        assert!(found.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_channel_policies() {
+        let config = PiiConfig {
+            enabled: true,
+            policy: "remote".into(),
+            channels: [
+                ("zalo".into(), "off".into()),
+                ("webchat".into(), "mask".into()),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        async fn content(stage: &PiiMiddleware, channel: &str) -> String {
+            let message = IncomingMessage {
+                channel: channel.into(),
+                thread_id: "t1".into(),
+                sender_id: "u1".into(),
+                sender_name: None,
+                content: "me@example.com".into(),
+                thread_type: ThreadType::Direct,
+                timestamp: chrono::Utc::now(),
+                reply_to: None,
+                message_id: None,
+                media: Vec::new(),
+            };
+            match stage
+                .incoming(message, &mut MessageContext::new())
+                .await
+                .unwrap()
+            {
+                Flow::Continue(msg) => msg.content,
+                _ => panic!("expected the message to continue"),
+            }
+        }
+
+        let remote = PiiMiddleware::from_config(&config, true);
+        assert_eq!(content(&remote, "telegram").await, "[email]");
+        assert_eq!(content(&remote, "zalo").await, "me@example.com");
+
+        let local = PiiMiddleware::from_config(&config, false);
+        assert_eq!(content(&local, "telegram").await, "me@example.com");
+        assert_eq!(content(&local, "webchat").await, "[email]");
+    }
+}
@@ -0,0 +1,45 @@
+//! Whitespace trimming — drops blank messages and blank replies.
+
+use super::{Flow, MessageContext, MessageMiddleware};
+use async_trait::async_trait;
+use bizclaw_core::error::Result;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage};
+
+/// Trims incoming text and replies. A message with neither text nor
+/// attachments is dropped; so is a reply with neither text nor blocks.
+pub struct TrimMiddleware;
+
+#[async_trait]
+impl MessageMiddleware for TrimMiddleware {
+    fn name(&self) -> &str {
+        "trim"
+    }
+
+    async fn incoming(
+        &self,
+        mut message: IncomingMessage,
+        _ctx: &mut MessageContext,
+    ) -> Result<Flow> {
+        let trimmed = message.content.trim();
+        if trimmed.len() != message.content.len() {
+            message.content = trimmed.to_string();
+        }
+        if message.content.is_empty() && message.media.is_empty() {
+            return Ok(Flow::Drop);
+        }
+        Ok(Flow::Continue(message))
+    }
+
+    async fn outgoing(
+        &self,
+        _incoming: &IncomingMessage,
+        mut reply: OutgoingMessage,
+        _ctx: &mut MessageContext,
+    ) -> Result<Option<OutgoingMessage>> {
+        let trimmed = reply.content.trim();
+        if trimmed.len() != reply.content.len() {
+            reply.content = trimmed.to_string();
+        }
+        Ok((!reply.content.is_empty() || reply.is_rich()).then_some(reply))
+    }
+}
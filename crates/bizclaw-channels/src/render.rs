@@ -0,0 +1,445 @@
+//! Per-channel rendering of structured [`OutgoingMessage`] content.
+//!
+//! Each channel renders blocks as richly as it can and degrades the rest:
+//! Discord gets native embeds and buttons, Telegram gets formatted text with
+//! an inline keyboard, Slack gets mrkdwn, and SMS-like channels get plain text.
+//! Messages without blocks are left untouched — `content` is sent as before.
+
+use bizclaw_core::types::{Attachment, Button, ContentBlock, Embed, OutgoingMessage};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Text flavour a channel understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStyle {
+    /// Discord markdown (CommonMark-like).
+    Discord,
+    /// Telegram legacy `Markdown` parse mode.
+    Telegram,
+    /// Slack mrkdwn.
+    Slack,
+    /// No markup at all (SMS, email text, Zalo, stdio).
+    PlainText,
+}
+
+impl RenderStyle {
+    /// Best style for a channel name.
+    pub fn for_channel(channel: &str) -> Self {
+        match channel {
+            "discord" | "webchat" => Self::Discord,
+            "telegram" => Self::Telegram,
+            "slack" => Self::Slack,
+            _ => Self::PlainText,
+        }
+    }
+}
+
+/// Which block kinds the caller renders natively (and so should be skipped in text).
+#[derive(Debug, Clone, Copy, Default)]
+struct Native {
+    embeds: bool,
+    buttons: bool,
+}
+
+/// Render the whole message as a single text body in `style`.
+/// Returns `content` unchanged when the message has no blocks.
+pub fn render_text(msg: &OutgoingMessage, style: RenderStyle) -> String {
+    render_body(msg, style, Native::default())
+}
+
+/// Telegram `sendMessage` body: formatted text plus an inline keyboard for buttons.
+pub fn telegram_payload(chat_id: i64, msg: &OutgoingMessage) -> serde_json::Value {
+    let native = Native {
+        embeds: false,
+        buttons: true,
+    };
+    let mut body = serde_json::json!({
+        "chat_id": chat_id,
+        "text": render_body(msg, RenderStyle::Telegram, native),
+        "parse_mode": "Markdown",
+    });
+
+    let rows: Vec<Vec<serde_json::Value>> = buttons(msg)
+        .map(|row| {
+            row.iter()
+                .map(|b| match &b.url {
+                    Some(url) => serde_json::json!({"text": b.label, "url": url}),
+                    None => serde_json::json!({
+                        "text": b.label,
+                        "callback_data": truncate_bytes(b.payload.as_deref().unwrap_or(&b.label), 64),
+                    }),
+                })
+                .collect()
+        })
+        .collect();
+    if !rows.is_empty() {
+        body["reply_markup"] = serde_json::json!({ "inline_keyboard": rows });
+    }
+    body
+}
+
+/// Discord create-message body: markdown content, embeds and button components.
+pub fn discord_payload(msg: &OutgoingMessage) -> serde_json::Value {
+    let native = Native {
+        embeds: true,
+        buttons: true,
+    };
+    let mut body = serde_json::json!({
+        "content": render_body(msg, RenderStyle::Discord, native),
+    });
+
+    let embeds: Vec<serde_json::Value> = msg
+        .blocks
+        .iter()
+        .filter_map(|b| match b {
+            ContentBlock::Embed(e) => Some(discord_embed(e)),
+            _ => None,
+        })
+        .take(10) // Discord limit
+        .collect();
+    if !embeds.is_empty() {
+        body["embeds"] = serde_json::Value::Array(embeds);
+    }
+
+    let rows: Vec<serde_json::Value> = buttons(msg)
+        .take(5) // Discord: max 5 action rows × 5 buttons
+        .map(|row| {
+            let components: Vec<serde_json::Value> = row
+                .iter()
+                .take(5)
+                .map(|b| match &b.url {
+                    // style 5 = link, 1 = primary
+                    Some(url) => serde_json::json!({"type": 2, "style": 5, "label": b.label, "url": url}),
+                    None => serde_json::json!({
+                        "type": 2,
+                        "style": 1,
+                        "label": b.label,
+                        "custom_id": truncate_bytes(b.payload.as_deref().unwrap_or(&b.label), 100),
+                    }),
+                })
+                .collect();
+            serde_json::json!({"type": 1, "components": components})
+        })
+        .collect();
+    if !rows.is_empty() {
+        body["components"] = serde_json::Value::Array(rows);
+    }
+    body
+}
+
+fn discord_embed(e: &Embed) -> serde_json::Value {
+    let mut v = serde_json::json!({});
+    if let Some(t) = &e.title {
+        v["title"] = t.as_str().into();
+    }
+    if let Some(d) = &e.description {
+        v["description"] = d.as_str().into();
+    }
+    if let Some(u) = &e.url {
+        v["url"] = u.as_str().into();
+    }
+    if let Some(c) = e.color {
+        v["color"] = c.into();
+    }
+    if !e.fields.is_empty() {
+        v["fields"] = e
+            .fields
+            .iter()
+            .map(|f| serde_json::json!({"name": f.name, "value": f.value, "inline": f.inline}))
+            .collect();
+    }
+    if let Some(img) = &e.image_url {
+        v["image"] = serde_json::json!({ "url": img });
+    }
+    if let Some(f) = &e.footer {
+        v["footer"] = serde_json::json!({ "text": f });
+    }
+    v
+}
+
+/// Each `Buttons` block is one row.
+fn buttons(msg: &OutgoingMessage) -> impl Iterator<Item = &Vec<Button>> {
+    msg.blocks.iter().filter_map(|b| match b {
+        ContentBlock::Buttons { buttons } if !buttons.is_empty() => Some(buttons),
+        _ => None,
+    })
+}
+
+fn render_body(msg: &OutgoingMessage, style: RenderStyle, native: Native) -> String {
+    if msg.blocks.is_empty() {
+        return msg.content.clone();
+    }
+
+    let parts: Vec<String> = msg
+        .blocks
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::Text { text } => Some(escape(text, style)),
+            ContentBlock::Markdown { markdown } => Some(convert_markdown(markdown, style)),
+            ContentBlock::Attachment(a) => Some(attachment_line(a, style)),
+            ContentBlock::Buttons { .. } if native.buttons => None,
+            ContentBlock::Buttons { buttons } => Some(button_list(buttons, style)),
+            ContentBlock::Embed(_) if native.embeds => None,
+            ContentBlock::Embed(e) => Some(embed_text(e, style)),
+        })
+        .filter(|s| !s.is_empty())
+        .collect();
+    parts.join("\n\n")
+}
+
+fn bold(text: &str, style: RenderStyle) -> String {
+    match style {
+        RenderStyle::Discord => format!("**{text}**"),
+        RenderStyle::Telegram | RenderStyle::Slack => format!("*{text}*"),
+        RenderStyle::PlainText => text.to_string(),
+    }
+}
+
+fn link(label: &str, url: &str, style: RenderStyle) -> String {
+    match style {
+        RenderStyle::Discord | RenderStyle::Telegram => format!("[{label}]({url})"),
+        RenderStyle::Slack => format!("<{url}|{label}>"),
+        RenderStyle::PlainText => format!("{label} ({url})"),
+    }
+}
+
+fn attachment_line(a: &Attachment, style: RenderStyle) -> String {
+    let label = a
+        .caption
+        .as_deref()
+        .or(a.filename.as_deref())
+        .unwrap_or("Attachment");
+    match style {
+        // Bare URL lets Discord auto-embed images/video.
+        RenderStyle::Discord => format!("📎 {}\n{}", escape(label, style), a.url),
+        _ => format!("📎 {}", link(&escape(label, style), &a.url, style)),
+    }
+}
+
+fn button_list(buttons: &[Button], style: RenderStyle) -> String {
+    buttons
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            let label = escape(&b.label, style);
+            match &b.url {
+                Some(url) => format!("{}. {}", i + 1, link(&label, url, style)),
+                None => format!("{}. {label}", i + 1),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn embed_text(e: &Embed, style: RenderStyle) -> String {
+    let mut lines = Vec::new();
+    if let Some(title) = &e.title {
+        let title = escape(title, style);
+        lines.push(match &e.url {
+            Some(url) if style != RenderStyle::PlainText => bold(&link(&title, url, style), style),
+            _ => bold(&title, style),
+        });
+    }
+    if let Some(d) = &e.description {
+        lines.push(convert_markdown(d, style));
+    }
+    for f in &e.fields {
+        lines.push(format!(
+            "{}: {}",
+            bold(&escape(&f.name, style), style),
+            convert_markdown(&f.value, style)
+        ));
+    }
+    if let Some(img) = &e.image_url {
+        lines.push(img.clone());
+    }
+    if style == RenderStyle::PlainText
+        && let Some(url) = &e.url
+    {
+        lines.push(url.clone());
+    }
+    if let Some(footer) = &e.footer {
+        lines.push(escape(footer, style));
+    }
+    lines.join("\n")
+}
+
+/// Escape literal text so the channel doesn't interpret it as markup.
+fn escape(text: &str, style: RenderStyle) -> String {
+    let special: &[char] = match style {
+        RenderStyle::Discord => &['*', '_', '`', '~', '|', '\\'],
+        RenderStyle::Telegram => &['*', '_', '`', '['],
+        RenderStyle::Slack => return text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;"),
+        RenderStyle::PlainText => return text.to_string(),
+    };
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn md_link_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\[([^\]]+)\]\(([^)\s]+)\)").unwrap())
+}
+
+fn md_bold_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\*\*(.+?)\*\*|__(.+?)__").unwrap())
+}
+
+fn md_heading_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?m)^#{1,6}\s+(.+)$").unwrap())
+}
+
+/// Convert CommonMark-style markdown to the target flavour.
+fn convert_markdown(md: &str, style: RenderStyle) -> String {
+    match style {
+        RenderStyle::Discord => md.to_string(),
+        RenderStyle::Telegram => {
+            let s = md_heading_re().replace_all(md, "**$1**");
+            md_bold_re()
+                .replace_all(&s, |c: &regex::Captures| {
+                    format!("*{}*", c.get(1).or(c.get(2)).map_or("", |m| m.as_str()))
+                })
+                .into_owned()
+        }
+        RenderStyle::Slack => {
+            let s = md_heading_re().replace_all(md, "**$1**");
+            let s = md_link_re().replace_all(&s, "<$2|$1>");
+            let s = md_bold_re().replace_all(&s, |c: &regex::Captures| {
+                format!("*{}*", c.get(1).or(c.get(2)).map_or("", |m| m.as_str()))
+            });
+            s.replace("~~", "~")
+        }
+        RenderStyle::PlainText => {
+            let s = md_heading_re().replace_all(md, "$1");
+            let s = md_link_re().replace_all(&s, "$1 ($2)");
+            let s = md_bold_re().replace_all(&s, |c: &regex::Captures| {
+                c.get(1).or(c.get(2)).map_or("", |m| m.as_str()).to_string()
+            });
+            s.replace("~~", "").replace('`', "")
+        }
+    }
+}
+
+fn truncate_bytes(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::types::{EmbedField, ThreadType};
+
+    fn rich(blocks: Vec<ContentBlock>) -> OutgoingMessage {
+        OutgoingMessage::text("123", "fallback", ThreadType::Direct).with_blocks(blocks)
+    }
+
+    #[test]
+    fn test_plain_message_untouched() {
+        let msg = OutgoingMessage::text("1", "**keep** as-is", ThreadType::Direct);
+        assert_eq!(render_text(&msg, RenderStyle::PlainText), "**keep** as-is");
+    }
+
+    #[test]
+    fn test_markdown_degrades_per_style() {
+        let msg = rich(vec![ContentBlock::Markdown {
+            markdown: "# Title\n**bold** and [docs](https://x.io)".into(),
+        }]);
+        assert_eq!(
+            render_text(&msg, RenderStyle::PlainText),
+            "Title\nbold and docs (https://x.io)"
+        );
+        assert_eq!(
+            render_text(&msg, RenderStyle::Telegram),
+            "*Title*\n*bold* and [docs](https://x.io)"
+        );
+        assert_eq!(
+            render_text(&msg, RenderStyle::Slack),
+            "*Title*\n*bold* and <https://x.io|docs>"
+        );
+    }
+
+    #[test]
+    fn test_discord_payload_native_embed_and_buttons() {
+        let msg = rich(vec![
+            ContentBlock::Text { text: "Order *42*".into() },
+            ContentBlock::Embed(Embed {
+                title: Some("Invoice".into()),
+                fields: vec![EmbedField {
+                    name: "Total".into(),
+                    value: "100k".into(),
+                    inline: true,
+                }],
+                ..Default::default()
+            }),
+            ContentBlock::Buttons {
+                buttons: vec![Button::reply("Pay", "pay_42"), Button::link("View", "https://x.io")],
+            },
+        ]);
+        let body = discord_payload(&msg);
+        assert_eq!(body["content"], "Order \\*42\\*");
+        assert_eq!(body["embeds"][0]["title"], "Invoice");
+        assert_eq!(body["embeds"][0]["fields"][0]["inline"], true);
+        assert_eq!(body["components"][0]["components"][0]["custom_id"], "pay_42");
+        assert_eq!(body["components"][0]["components"][1]["style"], 5);
+    }
+
+    #[test]
+    fn test_telegram_payload_keyboard_and_embed_text() {
+        let msg = rich(vec![
+            ContentBlock::Embed(Embed {
+                title: Some("Invoice".into()),
+                description: Some("Due **today**".into()),
+                ..Default::default()
+            }),
+            ContentBlock::Buttons {
+                buttons: vec![Button::reply("Pay", "pay_42")],
+            },
+        ]);
+        let body = telegram_payload(123, &msg);
+        assert_eq!(body["text"], "*Invoice*\nDue *today*");
+        assert_eq!(
+            body["reply_markup"]["inline_keyboard"][0][0]["callback_data"],
+            "pay_42"
+        );
+    }
+
+    #[test]
+    fn test_plain_text_buttons_and_attachments() {
+        let msg = rich(vec![
+            ContentBlock::Attachment(Attachment {
+                url: "https://x.io/a.pdf".into(),
+                filename: Some("a.pdf".into()),
+                mime_type: None,
+                caption: None,
+            }),
+            ContentBlock::Buttons {
+                buttons: vec![Button::reply("Yes", "y"), Button::link("Docs", "https://d")],
+            },
+        ]);
+        assert_eq!(
+            render_text(&msg, RenderStyle::PlainText),
+            "📎 a.pdf (https://x.io/a.pdf)\n\n1. Yes\n2. Docs (https://d)"
+        );
+    }
+
+    #[test]
+    fn test_truncate_bytes_char_boundary() {
+        assert_eq!(truncate_bytes("ăăă", 3), "ă");
+        assert_eq!(truncate_bytes("abc", 64), "abc");
+    }
+}
@@ -58,8 +58,8 @@ impl SlackChannel {
         }
     }
 
-    /// Send a message to a Slack channel or thread.
-    async fn post_message(&self, channel: &str, text: &str, thread_ts: Option<&str>) -> Result<()> {
+    /// Send a message to a Slack channel or thread. Returns the message `ts`.
+    async fn post_message(&self, channel: &str, text: &str, thread_ts: Option<&str>) -> Result<String> {
         let mut body = serde_json::json!({
             "channel": channel,
             "text": text,
@@ -68,19 +68,40 @@ impl SlackChannel {
             body["thread_ts"] = serde_json::Value::String(ts.to_string());
         }
 
+        let resp = self.call("chat.postMessage", &body).await?;
+        resp["ts"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| BizClawError::Channel("Slack chat.postMessage: no ts in response".into()))
+    }
+
+    /// Call a Web API method, failing on transport errors, non-2xx or `"ok": false`.
+    async fn call(&self, method: &str, body: &serde_json::Value) -> Result<serde_json::Value> {
         let resp = self.client
-            .post("https://slack.com/api/chat.postMessage")
+            .post(format!("https://slack.com/api/{method}"))
             .header("Authorization", format!("Bearer {}", self.config.bot_token))
-            .json(&body)
+            .json(body)
             .send()
             .await
-            .map_err(|e| BizClawError::Channel(format!("Slack API error: {e}")))?;
+            .map_err(|e| crate::middleware::retry::transport_error("Slack API error", e))?;
 
         let status = resp.status();
         if !status.is_success() {
-            return Err(BizClawError::Channel(format!("Slack API {status}")));
+            let wait = crate::middleware::retry::retry_after(resp.headers());
+            return Err(
+                crate::middleware::retry::status_error(status, format!("Slack {method}"))
+                    .with_retry_after(wait),
+            );
         }
-        Ok(())
+        let body: serde_json::Value = resp.json().await
+            .map_err(|e| BizClawError::Channel(format!("Slack {method} response: {e}")))?;
+        if body["ok"].as_bool() != Some(true) {
+            return Err(BizClawError::Channel(format!(
+                "Slack {method} failed: {}",
+                body["error"].as_str().unwrap_or("unknown")
+            )));
+        }
+        Ok(body)
     }
 
     /// Parse a Slack Events API payload.
@@ -110,6 +131,11 @@ impl SlackChannel {
             },
             timestamp: chrono::Utc::now(),
             reply_to: event["thread_ts"].as_str().map(String::from),
+            message_id: event["client_msg_id"]
+                .as_str()
+                .or(event["ts"].as_str())
+                .map(String::from),
+            media: Vec::new(),
         })
     }
 }
@@ -152,12 +178,29 @@ impl Channel for SlackChannel {
     fn is_connected(&self) -> bool { self.connected }
 
     async fn send(&self, message: OutgoingMessage) -> Result<()> {
+        self.send_with_id(message).await.map(|_| ())
+    }
+
+    async fn send_with_id(&self, message: OutgoingMessage) -> Result<Option<String>> {
         let channel = if message.thread_id.is_empty() {
             &self.config.default_channel
         } else {
             &message.thread_id
         };
-        self.post_message(channel, &message.content, message.reply_to.as_deref()).await
+        let text = crate::render::render_text(&message, crate::render::RenderStyle::Slack);
+        self.post_message(channel, &text, message.reply_to.as_deref()).await.map(Some)
+    }
+
+    fn supports_edit(&self) -> bool { true }
+
+    async fn edit(&self, thread_id: &str, message_id: &str, content: &str) -> Result<()> {
+        let body = serde_json::json!({ "channel": thread_id, "ts": message_id, "text": content });
+        self.call("chat.update", &body).await.map(|_| ())
+    }
+
+    async fn delete(&self, thread_id: &str, message_id: &str) -> Result<()> {
+        let body = serde_json::json!({ "channel": thread_id, "ts": message_id });
+        self.call("chat.delete", &body).await.map(|_| ())
     }
 
     async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
@@ -0,0 +1,176 @@
+//! Stdio channel — line-oriented stdin/stdout transport.
+//!
+//! Unlike the interactive [`CliChannel`](crate::cli::CliChannel), this channel
+//! prints nothing but replies (one per line) and can be built over any async
+//! reader/writer pair, so the agent + channel pipeline can be driven from
+//! shell pipes or integration tests without network credentials.
+
+use async_trait::async_trait;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::Channel;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage, ThreadType};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio_stream::Stream;
+
+type Reader = Box<dyn AsyncBufRead + Send + Unpin>;
+type Writer = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Line-based channel over stdin/stdout (or any reader/writer pair).
+pub struct StdioChannel {
+    connected: bool,
+    thread_id: String,
+    sender_id: String,
+    input: std::sync::Mutex<Option<Reader>>,
+    output: tokio::sync::Mutex<Writer>,
+}
+
+impl StdioChannel {
+    /// Channel over the process stdin/stdout.
+    pub fn new() -> Self {
+        Self::with_io(BufReader::new(tokio::io::stdin()), tokio::io::stdout())
+    }
+
+    /// Channel over an arbitrary reader/writer pair.
+    pub fn with_io<R, W>(reader: R, writer: W) -> Self
+    where
+        R: AsyncBufRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        Self {
+            connected: false,
+            thread_id: "stdio".into(),
+            sender_id: "stdin".into(),
+            input: std::sync::Mutex::new(Some(Box::new(reader))),
+            output: tokio::sync::Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Override the thread id stamped on incoming messages.
+    pub fn with_thread_id(mut self, thread_id: impl Into<String>) -> Self {
+        self.thread_id = thread_id.into();
+        self
+    }
+}
+
+impl Default for StdioChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Channel for StdioChannel {
+    fn name(&self) -> &str {
+        "stdio"
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        self.connected = true;
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.connected = false;
+        let mut out = self.output.lock().await;
+        out.flush().await?;
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
+        let reader = self
+            .input
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| BizClawError::Channel("Stdio channel is already listening".into()))?;
+        let thread_id = self.thread_id.clone();
+        let sender_id = self.sender_id.clone();
+
+        let stream = async_stream::stream! {
+            let mut lines = reader.lines();
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) => {
+                        let line = line.trim();
+                        if line.is_empty() { continue; }
+                        if line == "/quit" || line == "/exit" { break; }
+                        yield IncomingMessage {
+                            channel: "stdio".into(),
+                            thread_id: thread_id.clone(),
+                            sender_id: sender_id.clone(),
+                            sender_name: None,
+                            content: line.to_string(),
+                            thread_type: ThreadType::Direct,
+                            timestamp: chrono::Utc::now(),
+                            reply_to: None,
+                            message_id: None,
+                            media: Vec::new(),
+                        };
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::warn!("[stdio] Read error: {e}");
+                        break;
+                    }
+                }
+            }
+        };
+        Ok(Box::new(Box::pin(stream)))
+    }
+
+    async fn send(&self, message: OutgoingMessage) -> Result<()> {
+        let text = crate::render::render_text(&message, crate::render::RenderStyle::PlainText);
+        let mut out = self.output.lock().await;
+        out.write_all(text.as_bytes()).await?;
+        if !text.ends_with('\n') {
+            out.write_all(b"\n").await?;
+        }
+        out.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_listen_reads_lines_until_quit() {
+        let input: &'static [u8] = b"hello\n\n  world  \n/quit\nignored\n";
+        let (writer, _reader) = tokio::io::duplex(64);
+        let channel = StdioChannel::with_io(input, writer).with_thread_id("t1");
+
+        let msgs: Vec<_> = channel.listen().await.unwrap().collect().await;
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].content, "hello");
+        assert_eq!(msgs[1].content, "world");
+        assert_eq!(msgs[0].thread_id, "t1");
+        assert_eq!(msgs[0].channel, "stdio");
+
+        assert!(channel.listen().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_writes_one_line_per_reply() {
+        let (writer, mut reader) = tokio::io::duplex(64);
+        let channel = StdioChannel::with_io(&b""[..], writer);
+
+        for text in ["first", "second\n"] {
+            channel
+                .send(OutgoingMessage::text("stdio", text, ThreadType::Direct))
+                .await
+                .unwrap();
+        }
+        drop(channel);
+
+        let mut out = String::new();
+        reader.read_to_string(&mut out).await.unwrap();
+        assert_eq!(out, "first\nsecond\n");
+    }
+}
@@ -3,10 +3,11 @@
 use async_trait::async_trait;
 use bizclaw_core::error::{BizClawError, Result};
 use bizclaw_core::traits::Channel;
-use bizclaw_core::types::{IncomingMessage, OutgoingMessage, ThreadType};
+use bizclaw_core::types::{IncomingMessage, MediaKind, MediaRef, OutgoingMessage, ThreadType};
 use futures::stream::Stream;
 use serde::{Deserialize, Serialize};
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 
 /// Telegram channel configuration.
@@ -30,7 +31,7 @@ fn default_poll_interval() -> u64 {
 pub struct TelegramChannel {
     config: TelegramConfig,
     client: reqwest::Client,
-    last_update_id: i64,
+    last_update_id: Arc<Mutex<i64>>,
     connected: bool,
 }
 
@@ -39,7 +40,7 @@ impl TelegramChannel {
         Self {
             config,
             client: reqwest::Client::new(),
-            last_update_id: 0,
+            last_update_id: Arc::new(Mutex::new(0)),
             connected: false,
         }
     }
@@ -57,7 +58,7 @@ impl TelegramChannel {
             .client
             .get(self.api_url("getUpdates"))
             .query(&[
-                ("offset", (self.last_update_id + 1).to_string()),
+                ("offset", (self.last_update_id() + 1).to_string()),
                 ("timeout", "30".into()),
                 ("allowed_updates", "[\"message\"]".into()),
             ])
@@ -79,39 +80,87 @@ impl TelegramChannel {
 
         let updates = body.result.unwrap_or_default();
         if let Some(last) = updates.last() {
-            self.last_update_id = last.update_id;
+            *self.last_update_id.lock().unwrap() = last.update_id;
         }
         Ok(updates)
     }
 
+    /// The id of the last update received; the next poll asks for later
+    /// ones, which also confirms this one to Telegram.
+    pub fn last_update_id(&self) -> i64 {
+        *self.last_update_id.lock().unwrap()
+    }
+
     /// Send a text message.
     pub async fn send_message(&self, chat_id: i64, text: &str) -> Result<()> {
+        self.send_message_body(&text_body(chat_id, text))
+            .await
+            .map(|_| ())
+    }
+
+    /// POST a prebuilt `sendMessage` body (e.g. from [`crate::render::telegram_payload`]).
+    /// Returns the sent message's id.
+    pub async fn send_message_body(&self, body: &serde_json::Value) -> Result<String> {
+        let sent = self.call("sendMessage", body).await?;
+        sent["message_id"]
+            .as_i64()
+            .map(|id| id.to_string())
+            .ok_or_else(|| BizClawError::Channel("sendMessage: no message_id in response".into()))
+    }
+
+    /// Replace the text of a message the bot sent.
+    pub async fn edit_message_text(&self, chat_id: i64, message_id: i64, text: &str) -> Result<()> {
         let body = serde_json::json!({
             "chat_id": chat_id,
+            "message_id": message_id,
             "text": text,
             "parse_mode": "Markdown",
         });
+        self.call("editMessageText", &body).await.map(|_| ())
+    }
+
+    /// Delete a message (the bot's own, or any message if it is a group admin).
+    pub async fn delete_message(&self, chat_id: i64, message_id: i64) -> Result<()> {
+        let body = serde_json::json!({
+            "chat_id": chat_id,
+            "message_id": message_id,
+        });
+        self.call("deleteMessage", &body).await.map(|_| ())
+    }
 
+    /// POST a Bot API method and return its `result`.
+    async fn call(&self, method: &str, body: &serde_json::Value) -> Result<serde_json::Value> {
         let response = self
             .client
-            .post(self.api_url("sendMessage"))
-            .json(&body)
+            .post(self.api_url(method))
+            .json(body)
             .send()
             .await
-            .map_err(|e| BizClawError::Channel(format!("sendMessage failed: {e}")))?;
+            .map_err(|e| {
+                crate::middleware::retry::transport_error(&format!("{method} failed"), e)
+            })?;
 
+        let status = response.status();
         let result: TelegramApiResponse<serde_json::Value> = response
             .json()
             .await
-            .map_err(|e| BizClawError::Channel(format!("Invalid send response: {e}")))?;
+            .map_err(|e| BizClawError::Channel(format!("Invalid {method} response: {e}")))?;
 
         if !result.ok {
-            return Err(BizClawError::Channel(format!(
-                "Send failed: {}",
-                result.description.unwrap_or_default()
-            )));
+            let wait = result
+                .parameters
+                .and_then(|p| p.retry_after)
+                .map(std::time::Duration::from_secs);
+            return Err(crate::middleware::retry::status_error(
+                status,
+                format!(
+                    "{method} failed: {}",
+                    result.description.unwrap_or_default()
+                ),
+            )
+            .with_retry_after(wait));
         }
-        Ok(())
+        Ok(result.result.unwrap_or_default())
     }
 
     /// Send typing indicator.
@@ -155,6 +204,11 @@ impl TelegramChannel {
             tracing::info!("Telegram polling loop started");
 
             loop {
+                // A re-listen polls on; two pollers would conflict
+                if tx.is_closed() {
+                    tracing::info!("Telegram polling stopped (receiver dropped)");
+                    return;
+                }
                 match channel.get_updates().await {
                     Ok(updates) => {
                         for update in updates {
@@ -224,11 +278,35 @@ impl Channel for TelegramChannel {
     }
 
     async fn send(&self, message: OutgoingMessage) -> Result<()> {
-        let chat_id: i64 = message
-            .thread_id
-            .parse()
-            .map_err(|_| BizClawError::Channel("Invalid chat_id".into()))?;
-        self.send_message(chat_id, &message.content).await
+        self.send_with_id(message).await.map(|_| ())
+    }
+
+    async fn send_with_id(&self, message: OutgoingMessage) -> Result<Option<String>> {
+        let chat_id = parse_chat_id(&message.thread_id)?;
+        let body = if message.is_rich() {
+            crate::render::telegram_payload(chat_id, &message)
+        } else {
+            text_body(chat_id, &message.content)
+        };
+        self.send_message_body(&body).await.map(Some)
+    }
+
+    fn supports_edit(&self) -> bool {
+        true
+    }
+
+    async fn edit(&self, thread_id: &str, message_id: &str, content: &str) -> Result<()> {
+        self.edit_message_text(
+            parse_chat_id(thread_id)?,
+            parse_message_id(message_id)?,
+            content,
+        )
+        .await
+    }
+
+    async fn delete(&self, thread_id: &str, message_id: &str) -> Result<()> {
+        self.delete_message(parse_chat_id(thread_id)?, parse_message_id(message_id)?)
+            .await
     }
 
     async fn send_typing(&self, thread_id: &str) -> Result<()> {
@@ -239,12 +317,38 @@ impl Channel for TelegramChannel {
     }
 
     async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
-        // For listen(), return a pending stream
-        // For actual polling, use start_polling() which consumes self
-        Ok(Box::new(futures::stream::pending()))
+        // Share last_update_id so a re-listen after reconnect doesn't replay
+        // updates already handled
+        let poller = TelegramChannel {
+            config: self.config.clone(),
+            client: self.client.clone(),
+            last_update_id: self.last_update_id.clone(),
+            connected: true,
+        };
+        Ok(Box::new(poller.start_polling()))
     }
 }
 
+fn text_body(chat_id: i64, text: &str) -> serde_json::Value {
+    serde_json::json!({
+        "chat_id": chat_id,
+        "text": text,
+        "parse_mode": "Markdown",
+    })
+}
+
+fn parse_chat_id(thread_id: &str) -> Result<i64> {
+    thread_id
+        .parse()
+        .map_err(|_| BizClawError::Channel("Invalid chat_id".into()))
+}
+
+fn parse_message_id(message_id: &str) -> Result<i64> {
+    message_id
+        .parse()
+        .map_err(|_| BizClawError::Channel(format!("Invalid Telegram message_id: {message_id}")))
+}
+
 // --- Telegram API Types ---
 
 #[derive(Debug, Deserialize)]
@@ -252,6 +356,14 @@ pub struct TelegramApiResponse<T> {
     pub ok: bool,
     pub result: Option<T>,
     pub description: Option<String>,
+    #[serde(default)]
+    pub parameters: Option<TelegramResponseParameters>,
+}
+
+/// Why a request failed, e.g. how long to back off after a 429.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelegramResponseParameters {
+    pub retry_after: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -268,6 +380,55 @@ pub struct TelegramMessage {
     pub text: Option<String>,
     pub date: i64,
     pub reply_to_message: Option<Box<TelegramMessage>>,
+    #[serde(default)]
+    pub caption: Option<String>,
+    /// Available sizes of a photo, smallest first.
+    #[serde(default)]
+    pub photo: Option<Vec<TelegramFile>>,
+    #[serde(default)]
+    pub document: Option<TelegramFile>,
+    #[serde(default)]
+    pub audio: Option<TelegramFile>,
+    #[serde(default)]
+    pub voice: Option<TelegramFile>,
+    #[serde(default)]
+    pub video: Option<TelegramFile>,
+}
+
+/// A file attached to a message (photo size, document, audio, voice, video).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelegramFile {
+    pub file_id: String,
+    #[serde(default)]
+    pub file_name: Option<String>,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    #[serde(default)]
+    pub file_size: Option<u64>,
+}
+
+impl TelegramMessage {
+    /// Attached files as media references (`telegram-file:<file_id>`), largest photo only.
+    pub fn media(&self) -> Vec<MediaRef> {
+        let files = [
+            (MediaKind::Image, self.photo.as_ref().and_then(|p| p.last())),
+            (MediaKind::Document, self.document.as_ref()),
+            (MediaKind::Audio, self.audio.as_ref()),
+            (MediaKind::Audio, self.voice.as_ref()),
+            (MediaKind::Video, self.video.as_ref()),
+        ];
+        files
+            .into_iter()
+            .filter_map(|(kind, file)| {
+                let file = file?;
+                let mut media = MediaRef::remote(kind, format!("telegram-file:{}", file.file_id));
+                media.filename = file.file_name.clone();
+                media.mime_type = file.mime_type.clone();
+                media.size = file.file_size;
+                Some(media)
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -291,7 +452,12 @@ impl TelegramUpdate {
     /// Convert to BizClaw IncomingMessage.
     pub fn to_incoming(&self) -> Option<IncomingMessage> {
         let msg = self.message.as_ref()?;
-        let text = msg.text.as_ref()?;
+        let media = msg.media();
+        let text = match msg.text.as_ref().or(msg.caption.as_ref()) {
+            Some(text) => text.clone(),
+            None if !media.is_empty() => String::new(),
+            None => return None,
+        };
         let from = msg.from.as_ref()?;
 
         // Skip bot messages
@@ -311,7 +477,7 @@ impl TelegramUpdate {
                     .map(|l| format!(" {l}"))
                     .unwrap_or_default()
             )),
-            content: text.clone(),
+            content: text,
             thread_type: match msg.chat.chat_type.as_str() {
                 "private" => ThreadType::Direct,
                 _ => ThreadType::Group,
@@ -321,6 +487,8 @@ impl TelegramUpdate {
                 .reply_to_message
                 .as_ref()
                 .map(|r| r.message_id.to_string()),
+            message_id: Some(self.update_id.to_string()),
+            media,
         })
     }
 }
@@ -0,0 +1,343 @@
+//! WebChat channel — bridges gateway WebSocket sessions into the channel abstraction.
+//!
+//! Each browser session is one thread: the gateway opens a [`WebChatSession`]
+//! per socket via a [`WebChatHandle`], pushes visitor messages into it, and
+//! forwards whatever the channel `send()`s for that thread back over the socket.
+//! The agent side only ever sees a normal `Channel`.
+//!
+//! A session id is bound to a resume token when first opened through
+//! [`WebChatHandle::resume_session`]; reconnecting to that id needs the
+//! token, so knowing another visitor's id isn't enough to read their replies.
+
+use async_trait::async_trait;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::Channel;
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage, ThreadType};
+use futures::stream::Stream;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+
+/// session id → (generation, reply sender). The generation lets a dropped
+/// socket tell whether its id has since been re-opened by a newer one.
+type SessionMap = Arc<RwLock<HashMap<String, (u64, mpsc::UnboundedSender<OutgoingMessage>)>>>;
+
+/// session id → resume token, kept after the socket closes so the visitor
+/// can reconnect.
+type TokenMap = Arc<Mutex<HashMap<String, String>>>;
+
+/// Tokens kept for closed sessions before the oldest are forgotten.
+const MAX_RESUMABLE_SESSIONS: usize = 10_000;
+
+/// WebChat channel — one thread per gateway WebSocket session.
+pub struct WebChatChannel {
+    connected: bool,
+    inbound_tx: mpsc::UnboundedSender<IncomingMessage>,
+    inbound_rx: Mutex<Option<mpsc::UnboundedReceiver<IncomingMessage>>>,
+    sessions: SessionMap,
+    tokens: TokenMap,
+    next_generation: Arc<AtomicU64>,
+}
+
+impl WebChatChannel {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            connected: false,
+            inbound_tx: tx,
+            inbound_rx: Mutex::new(Some(rx)),
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            tokens: Arc::new(Mutex::new(HashMap::new())),
+            next_generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Cloneable handle the gateway uses to open sessions.
+    pub fn handle(&self) -> WebChatHandle {
+        WebChatHandle {
+            inbound_tx: self.inbound_tx.clone(),
+            sessions: self.sessions.clone(),
+            tokens: self.tokens.clone(),
+            next_generation: self.next_generation.clone(),
+        }
+    }
+
+    /// Number of open sessions.
+    pub fn session_count(&self) -> usize {
+        self.sessions.read().unwrap().len()
+    }
+}
+
+impl Default for WebChatChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Gateway-side handle for opening WebChat sessions.
+#[derive(Clone)]
+pub struct WebChatHandle {
+    inbound_tx: mpsc::UnboundedSender<IncomingMessage>,
+    sessions: SessionMap,
+    tokens: TokenMap,
+    next_generation: Arc<AtomicU64>,
+}
+
+impl WebChatHandle {
+    /// Register a new session. Reusing a live id replaces the previous socket.
+    pub fn open_session(&self, session_id: impl Into<String>) -> WebChatSession {
+        let id = session_id.into();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        self.sessions
+            .write()
+            .unwrap()
+            .insert(id.clone(), (generation, tx));
+        tracing::debug!("[webchat] Session opened: {id}");
+        WebChatSession {
+            id,
+            token: String::new(),
+            generation,
+            inbound_tx: self.inbound_tx.clone(),
+            outbound_rx: rx,
+            sessions: self.sessions.clone(),
+        }
+    }
+
+    /// Open `session_id` for a visitor presenting `token`. An id seen for
+    /// the first time is bound to a fresh token, which the visitor gets from
+    /// [`WebChatSession::token`]; an id already bound only opens with it.
+    pub fn resume_session(&self, session_id: &str, token: Option<&str>) -> Result<WebChatSession> {
+        let token = {
+            let mut tokens = self.tokens.lock().unwrap();
+            match tokens.get(session_id) {
+                Some(bound) if token.is_some_and(|token| same_token(token, bound)) => bound.clone(),
+                Some(_) => {
+                    return Err(BizClawError::security(format!(
+                        "webchat session {session_id} belongs to another visitor"
+                    )));
+                }
+                None => {
+                    if tokens.len() >= MAX_RESUMABLE_SESSIONS {
+                        let open = self.sessions.read().unwrap();
+                        tokens.retain(|id, _| open.contains_key(id));
+                    }
+                    let token = uuid::Uuid::new_v4().simple().to_string();
+                    tokens.insert(session_id.to_string(), token.clone());
+                    token
+                }
+            }
+        };
+        let mut session = self.open_session(session_id);
+        session.token = token;
+        Ok(session)
+    }
+
+    /// Whether a session is currently open.
+    pub fn is_open(&self, session_id: &str) -> bool {
+        self.sessions.read().unwrap().contains_key(session_id)
+    }
+}
+
+/// One visitor connection. Dropping it closes the thread.
+pub struct WebChatSession {
+    id: String,
+    token: String,
+    generation: u64,
+    inbound_tx: mpsc::UnboundedSender<IncomingMessage>,
+    outbound_rx: mpsc::UnboundedReceiver<OutgoingMessage>,
+    sessions: SessionMap,
+}
+
+impl WebChatSession {
+    /// Session id — also the thread id on the channel.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Token that resumes this session, empty unless it was opened with
+    /// [`WebChatHandle::resume_session`].
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Push a visitor message into the channel.
+    pub fn submit(&self, content: impl Into<String>, sender_name: Option<String>) -> Result<()> {
+        let msg = IncomingMessage {
+            channel: "webchat".into(),
+            thread_id: self.id.clone(),
+            sender_id: self.id.clone(),
+            sender_name,
+            content: content.into(),
+            thread_type: ThreadType::Direct,
+            timestamp: chrono::Utc::now(),
+            reply_to: None,
+            message_id: None,
+            media: Vec::new(),
+        };
+        self.inbound_tx
+            .send(msg)
+            .map_err(|_| BizClawError::ChannelNotConnected("webchat".into()))
+    }
+
+    /// Next reply addressed to this session.
+    pub async fn recv(&mut self) -> Option<OutgoingMessage> {
+        self.outbound_rx.recv().await
+    }
+}
+
+impl Drop for WebChatSession {
+    fn drop(&mut self) {
+        let mut sessions = self.sessions.write().unwrap();
+        // Only remove our own entry — the id may have been re-opened by a newer socket.
+        if sessions
+            .get(&self.id)
+            .is_some_and(|(generation, _)| *generation == self.generation)
+        {
+            sessions.remove(&self.id);
+            tracing::debug!("[webchat] Session closed: {}", self.id);
+        }
+    }
+}
+
+/// Compare tokens without leaking how much of them matched.
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+/// Stream of visitor messages across all sessions.
+pub struct WebChatStream {
+    rx: mpsc::UnboundedReceiver<IncomingMessage>,
+}
+
+impl Stream for WebChatStream {
+    type Item = IncomingMessage;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+#[async_trait]
+impl Channel for WebChatChannel {
+    fn name(&self) -> &str {
+        "webchat"
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        self.connected = true;
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        self.connected = false;
+        self.sessions.write().unwrap().clear();
+        Ok(())
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
+        let rx = self
+            .inbound_rx
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| BizClawError::Channel("WebChat channel is already listening".into()))?;
+        Ok(Box::new(WebChatStream { rx }))
+    }
+
+    async fn send(&self, message: OutgoingMessage) -> Result<()> {
+        let tx = self
+            .sessions
+            .read()
+            .unwrap()
+            .get(&message.thread_id)
+            .map(|(_, tx)| tx.clone())
+            .ok_or_else(|| {
+                BizClawError::ChannelNotConnected(format!("webchat session {}", message.thread_id))
+            })?;
+        tx.send(message)
+            .map_err(|_| BizClawError::ChannelNotConnected("webchat session closed".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn reply(thread_id: &str, content: &str) -> OutgoingMessage {
+        OutgoingMessage::text(thread_id, content, ThreadType::Direct)
+    }
+
+    #[tokio::test]
+    async fn test_session_roundtrip() {
+        let channel = WebChatChannel::new();
+        let handle = channel.handle();
+        let mut stream = channel.listen().await.unwrap();
+
+        let mut a = handle.open_session("sess-a");
+        let mut b = handle.open_session("sess-b");
+        a.submit("hi from a", None).unwrap();
+
+        let incoming = stream.next().await.unwrap();
+        assert_eq!(incoming.channel, "webchat");
+        assert_eq!(incoming.thread_id, "sess-a");
+
+        channel.send(reply("sess-b", "to b")).await.unwrap();
+        channel.send(reply("sess-a", "to a")).await.unwrap();
+        assert_eq!(a.recv().await.unwrap().content, "to a");
+        assert_eq!(b.recv().await.unwrap().content, "to b");
+    }
+
+    #[tokio::test]
+    async fn test_drop_closes_session() {
+        let channel = WebChatChannel::new();
+        let handle = channel.handle();
+        let session = handle.open_session("gone");
+        assert!(handle.is_open("gone"));
+        drop(session);
+        assert!(!handle.is_open("gone"));
+        assert!(channel.send(reply("gone", "x")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resume_needs_the_session_token() {
+        let channel = WebChatChannel::new();
+        let handle = channel.handle();
+        let first = handle.resume_session("s", None).unwrap();
+        let token = first.token().to_string();
+        assert!(!token.is_empty());
+        drop(first);
+
+        assert!(handle.resume_session("s", None).is_err());
+        assert!(handle.resume_session("s", Some("guess")).is_err());
+        let resumed = handle.resume_session("s", Some(&token)).unwrap();
+        assert_eq!(resumed.token(), token);
+        // Another id gets its own token
+        let other = handle.resume_session("t", Some(&token)).unwrap();
+        assert_ne!(other.token(), token);
+    }
+
+    #[tokio::test]
+    async fn test_reopen_keeps_newer_session() {
+        let channel = WebChatChannel::new();
+        let handle = channel.handle();
+        let old = handle.open_session("s");
+        let mut new = handle.open_session("s");
+        drop(old);
+        assert_eq!(channel.session_count(), 1);
+        channel.send(reply("s", "still here")).await.unwrap();
+        assert_eq!(new.recv().await.unwrap().content, "still here");
+    }
+}
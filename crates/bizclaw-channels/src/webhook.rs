@@ -1,29 +1,124 @@
 //! Webhook channel — receive inbound HTTP webhooks and send outbound.
 //!
 //! Useful for integrating with external systems (Zapier, n8n, custom APIs).
+//! Inbound payloads arrive through a gateway endpoint and are verified with
+//! HMAC-SHA256 (`X-Webhook-Signature: sha256=<hex>`); outbound replies are
+//! POSTed to `outbound_url` with exponential-backoff retry.
 
 use async_trait::async_trait;
 use bizclaw_core::error::{BizClawError, Result};
 use bizclaw_core::traits::Channel;
 use bizclaw_core::types::{IncomingMessage, OutgoingMessage, ThreadType};
-use futures::stream::{self, Stream};
+use futures::stream::Stream;
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
 use tokio::sync::mpsc;
 
+type HmacSha256 = Hmac<sha2::Sha256>;
+
+/// Header carrying the HMAC-SHA256 signature of the raw request body.
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// Default gateway path for inbound webhooks.
+pub const DEFAULT_INBOUND_PATH: &str = "/api/v1/webhook/inbound";
+
 /// Webhook channel configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WebhookConfig {
     /// URL to send outbound messages to.
     pub outbound_url: Option<String>,
-    /// Secret for verifying inbound webhooks.
+    /// Secret for signing outbound and verifying inbound webhooks.
     pub secret: Option<String>,
     #[serde(default = "default_true")]
     pub enabled: bool,
+    /// Gateway path that receives inbound webhooks.
+    #[serde(default = "default_inbound_path")]
+    pub inbound_path: String,
+    /// Retries after the first failed outbound POST.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Initial retry delay — doubled on every attempt.
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
 }
 
 fn default_true() -> bool {
     true
 }
+fn default_inbound_path() -> String {
+    DEFAULT_INBOUND_PATH.into()
+}
+fn default_max_retries() -> u32 {
+    3
+}
+fn default_retry_backoff_ms() -> u64 {
+    500
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            outbound_url: None,
+            secret: None,
+            enabled: true,
+            inbound_path: default_inbound_path(),
+            max_retries: default_max_retries(),
+            retry_backoff_ms: default_retry_backoff_ms(),
+        }
+    }
+}
+
+impl From<&bizclaw_core::config::WebhookChannelConfig> for WebhookConfig {
+    fn from(cfg: &bizclaw_core::config::WebhookChannelConfig) -> Self {
+        let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+        Self {
+            outbound_url: non_empty(&cfg.outbound_url),
+            secret: non_empty(&cfg.secret),
+            enabled: cfg.enabled,
+            inbound_path: cfg.path.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Compute the hex-encoded HMAC-SHA256 signature of a payload.
+pub fn sign(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Verify a signature produced by [`sign`]. Accepts an optional `sha256=` prefix.
+/// Comparison is constant-time.
+pub fn verify_signature(secret: &str, payload: &[u8], signature: &str) -> bool {
+    let hex = signature.trim();
+    let hex = hex.strip_prefix("sha256=").unwrap_or(hex);
+    let Some(expected) = decode_hex(hex) else {
+        return false;
+    };
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload);
+    mac.verify_slice(&expected).is_ok()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
 
 /// Webhook channel.
 pub struct WebhookChannel {
@@ -32,8 +127,8 @@ pub struct WebhookChannel {
     connected: bool,
     /// Sender for injecting inbound messages.
     inbound_tx: mpsc::UnboundedSender<IncomingMessage>,
-    #[allow(dead_code)] // Part of channel architecture — consumed when listen() is fully implemented
-    inbound_rx: Option<mpsc::UnboundedReceiver<IncomingMessage>>,
+    /// Receiver handed out once by `listen()`.
+    inbound_rx: Mutex<Option<mpsc::UnboundedReceiver<IncomingMessage>>>,
 }
 
 impl WebhookChannel {
@@ -44,10 +139,20 @@ impl WebhookChannel {
             client: reqwest::Client::new(),
             connected: false,
             inbound_tx: tx,
-            inbound_rx: Some(rx),
+            inbound_rx: Mutex::new(Some(rx)),
         }
     }
 
+    /// Gateway path this channel expects inbound webhooks on.
+    pub fn inbound_path(&self) -> &str {
+        &self.config.inbound_path
+    }
+
+    /// Sender handle for HTTP handlers that outlive a borrow of the channel.
+    pub fn inbound_sender(&self) -> mpsc::UnboundedSender<IncomingMessage> {
+        self.inbound_tx.clone()
+    }
+
     /// Inject an inbound message (called from HTTP handler).
     pub fn inject_message(&self, msg: IncomingMessage) -> Result<()> {
         self.inbound_tx
@@ -55,15 +160,21 @@ impl WebhookChannel {
             .map_err(|_| BizClawError::Channel("Webhook receiver closed".into()))
     }
 
+    /// Verify, parse and inject a raw inbound request in one step.
+    pub fn handle_inbound(&self, payload: &str, signature: Option<&str>) -> Result<()> {
+        let msg = self.parse_inbound(payload, signature)?;
+        self.inject_message(msg)
+    }
+
     /// Parse and verify an inbound webhook payload.
+    ///
+    /// When a secret is configured the signature is mandatory.
     pub fn parse_inbound(&self, payload: &str, signature: Option<&str>) -> Result<IncomingMessage> {
-        // Verify signature if secret is configured
-        if let (Some(secret), Some(sig)) = (&self.config.secret, signature) {
-            use sha2::{Digest, Sha256};
-            let mut hasher = Sha256::new();
-            hasher.update(format!("{secret}{payload}"));
-            let expected = format!("{:x}", hasher.finalize());
-            if expected != sig {
+        if let Some(secret) = &self.config.secret {
+            let sig = signature.ok_or_else(|| {
+                BizClawError::AuthFailed(format!("Missing {SIGNATURE_HEADER} header"))
+            })?;
+            if !verify_signature(secret, payload.as_bytes(), sig) {
                 return Err(BizClawError::AuthFailed("Invalid webhook signature".into()));
             }
         }
@@ -71,17 +182,95 @@ impl WebhookChannel {
         let json: serde_json::Value = serde_json::from_str(payload)
             .map_err(|e| BizClawError::Channel(format!("Invalid webhook JSON: {e}")))?;
 
+        let content = json["content"].as_str().unwrap_or("");
+        if content.is_empty() {
+            return Err(BizClawError::Channel(
+                "Missing 'content' field in webhook payload".into(),
+            ));
+        }
+
         Ok(IncomingMessage {
             channel: "webhook".into(),
             thread_id: json["thread_id"].as_str().unwrap_or("webhook").into(),
             sender_id: json["sender_id"].as_str().unwrap_or("external").into(),
             sender_name: json["sender_name"].as_str().map(String::from),
-            content: json["content"].as_str().unwrap_or("").into(),
-            thread_type: ThreadType::Direct,
+            content: content.into(),
+            thread_type: match json["thread_type"].as_str() {
+                Some("group") => ThreadType::Group,
+                _ => ThreadType::Direct,
+            },
             timestamp: chrono::Utc::now(),
-            reply_to: None,
+            reply_to: json["reply_to"].as_str().map(String::from),
+            message_id: json["message_id"].as_str().map(String::from),
+            media: Vec::new(),
         })
     }
+
+    /// POST a JSON body, retrying transport errors, 429 and 5xx with exponential backoff.
+    async fn post_with_retry(&self, url: &str, body: &serde_json::Value) -> Result<()> {
+        let payload = serde_json::to_string(body)?;
+        let signature = self
+            .config
+            .secret
+            .as_deref()
+            .map(|s| format!("sha256={}", sign(s, payload.as_bytes())));
+
+        let mut attempt = 0u32;
+        loop {
+            let mut req = self
+                .client
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(payload.clone());
+            if let Some(sig) = &signature {
+                req = req.header(SIGNATURE_HEADER, sig);
+            }
+
+            let error = match req.send().await {
+                Ok(resp) if resp.status().is_success() => return Ok(()),
+                Ok(resp)
+                    if resp.status().is_server_error()
+                        || resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    format!("HTTP {}", resp.status())
+                }
+                Ok(resp) => {
+                    return Err(BizClawError::Channel(format!(
+                        "Webhook send rejected: HTTP {}",
+                        resp.status()
+                    )));
+                }
+                Err(e) => e.to_string(),
+            };
+
+            if attempt >= self.config.max_retries {
+                return Err(BizClawError::Channel(format!(
+                    "Webhook send failed after {} attempt(s): {error}",
+                    attempt + 1
+                )));
+            }
+            let delay = self
+                .config
+                .retry_backoff_ms
+                .saturating_mul(1u64 << attempt.min(16));
+            tracing::warn!("[webhook] Send failed ({error}), retrying in {delay}ms...");
+            tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Stream of inbound webhook messages.
+pub struct WebhookStream {
+    rx: mpsc::UnboundedReceiver<IncomingMessage>,
+}
+
+impl Stream for WebhookStream {
+    type Item = IncomingMessage;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
 }
 
 #[async_trait]
@@ -92,7 +281,10 @@ impl Channel for WebhookChannel {
 
     async fn connect(&mut self) -> Result<()> {
         self.connected = true;
-        tracing::info!("Webhook channel connected");
+        tracing::info!(
+            "Webhook channel connected (inbound at {})",
+            self.config.inbound_path
+        );
         Ok(())
     }
 
@@ -107,38 +299,38 @@ impl Channel for WebhookChannel {
 
     async fn send(&self, message: OutgoingMessage) -> Result<()> {
         if let Some(url) = &self.config.outbound_url {
-            let body = serde_json::json!({
+            let mut body = serde_json::json!({
                 "thread_id": message.thread_id,
                 "content": message.content,
                 "reply_to": message.reply_to,
             });
-
-            self.client
-                .post(url)
-                .json(&body)
-                .send()
-                .await
-                .map_err(|e| BizClawError::Channel(format!("Webhook send failed: {e}")))?;
+            if message.is_rich() {
+                body["blocks"] = serde_json::to_value(&message.blocks)?;
+            }
+            self.post_with_retry(url, &body).await?;
         }
         Ok(())
     }
 
     async fn listen(&self) -> Result<Box<dyn Stream<Item = IncomingMessage> + Send + Unpin>> {
-        Ok(Box::new(stream::pending()))
+        let rx = self
+            .inbound_rx
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| BizClawError::Channel("Webhook channel is already listening".into()))?;
+        Ok(Box::new(WebhookStream { rx }))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use futures::StreamExt;
 
     #[test]
     fn test_parse_inbound_no_secret() {
-        let channel = WebhookChannel::new(WebhookConfig {
-            outbound_url: None,
-            secret: None,
-            enabled: true,
-        });
+        let channel = WebhookChannel::new(WebhookConfig::default());
 
         let payload = r#"{"content":"hello","sender_id":"user1","thread_id":"t1"}"#;
         let msg = channel.parse_inbound(payload, None).unwrap();
@@ -146,4 +338,52 @@ mod tests {
         assert_eq!(msg.sender_id, "user1");
         assert_eq!(msg.channel, "webhook");
     }
+
+    #[test]
+    fn test_hmac_signature_roundtrip() {
+        let sig = sign("s3cret", b"body");
+        assert_eq!(sig.len(), 64);
+        assert!(verify_signature("s3cret", b"body", &sig));
+        assert!(verify_signature("s3cret", b"body", &format!("sha256={sig}")));
+        assert!(!verify_signature("other", b"body", &sig));
+        assert!(!verify_signature("s3cret", b"tampered", &sig));
+        assert!(!verify_signature("s3cret", b"body", "not-hex"));
+    }
+
+    #[test]
+    fn test_parse_inbound_requires_signature_when_secret_set() {
+        let channel = WebhookChannel::new(WebhookConfig {
+            secret: Some("s3cret".into()),
+            ..Default::default()
+        });
+        let payload = r#"{"content":"hi"}"#;
+
+        assert!(channel.parse_inbound(payload, None).is_err());
+        assert!(channel.parse_inbound(payload, Some("deadbeef")).is_err());
+
+        let sig = sign("s3cret", payload.as_bytes());
+        let msg = channel.parse_inbound(payload, Some(&sig)).unwrap();
+        assert_eq!(msg.content, "hi");
+        assert_eq!(msg.thread_id, "webhook");
+    }
+
+    #[test]
+    fn test_parse_inbound_rejects_empty_content() {
+        let channel = WebhookChannel::new(WebhookConfig::default());
+        assert!(channel.parse_inbound(r#"{"sender_id":"x"}"#, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_listen_yields_injected_messages() {
+        let channel = WebhookChannel::new(WebhookConfig::default());
+        let mut stream = channel.listen().await.unwrap();
+        assert!(channel.listen().await.is_err());
+
+        channel
+            .handle_inbound(r#"{"content":"ping","thread_id":"t9"}"#, None)
+            .unwrap();
+        let msg = stream.next().await.unwrap();
+        assert_eq!(msg.content, "ping");
+        assert_eq!(msg.thread_id, "t9");
+    }
 }
@@ -123,6 +123,36 @@ impl WhatsAppChannel {
 
         Ok(())
     }
+
+    /// The display number of the configured phone number ID.
+    pub async fn get_phone_number(&self) -> Result<String> {
+        let url = format!(
+            "https://graph.facebook.com/v21.0/{}",
+            self.config.phone_number_id
+        );
+        let response = self
+            .client
+            .get(&url)
+            .bearer_auth(&self.config.access_token)
+            .send()
+            .await
+            .map_err(|e| BizClawError::Channel(format!("WhatsApp API request failed: {e}")))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(BizClawError::AuthFailed(format!(
+                "WhatsApp API error {status}: {error_text}"
+            )));
+        }
+        let result: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| BizClawError::Channel(format!("Invalid WhatsApp response: {e}")))?;
+        Ok(result["display_phone_number"]
+            .as_str()
+            .unwrap_or(&self.config.phone_number_id)
+            .to_string())
+    }
 }
 
 #[async_trait]
@@ -195,7 +225,8 @@ impl Channel for WhatsAppChannel {
     }
 
     async fn send(&self, message: OutgoingMessage) -> Result<()> {
-        self.send_text_message(&message.thread_id, &message.content)
+        let text = crate::render::render_text(&message, crate::render::RenderStyle::PlainText);
+        self.send_text_message(&message.thread_id, &text)
             .await?;
         Ok(())
     }
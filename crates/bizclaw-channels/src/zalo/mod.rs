@@ -147,11 +147,12 @@ impl Channel for ZaloChannel {
             .as_ref()
             .ok_or_else(|| BizClawError::Channel("Zalo not logged in".into()))?;
 
+        let text = crate::render::render_text(&message, crate::render::RenderStyle::PlainText);
         self.messaging
             .send_text(
                 &message.thread_id,
                 ZaloThreadType::User,
-                &message.content,
+                &text,
                 cookie,
             )
             .await?;
@@ -61,8 +61,9 @@ impl Channel for ZaloOfficialChannel {
             .access_token
             .as_ref()
             .ok_or_else(|| BizClawError::Channel("No access token".into()))?;
+        let text = crate::render::render_text(&message, crate::render::RenderStyle::PlainText);
         self.business
-            .send_oa_message(&message.thread_id, &message.content, token)
+            .send_oa_message(&message.thread_id, &text, token)
             .await
     }
 
@@ -0,0 +1,188 @@
+//! Running as a service: PID file, systemd readiness notification and the
+//! signals a service manager sends.
+//!
+//! With `Type=notify`, systemd waits for [`notify_ready`] before it counts
+//! the service as started, and `systemctl reload` sends SIGHUP, which the
+//! [`ConfigWatcher`](crate::reload::ConfigWatcher) answers by reloading the
+//! config. SIGTERM and Ctrl+C resolve [`shutdown_signal`], after which the
+//! server stops accepting work and drains what is in flight.
+
+use crate::error::{BizClawError, Result};
+use std::path::{Path, PathBuf};
+
+/// A file holding this process's ID, removed when dropped.
+#[derive(Debug)]
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the PID to `path`. Fails if the file names a process that is
+    /// still running; a stale file from a crashed run is replaced.
+    pub fn create(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if let Some(pid) = read_pid(&path)
+            && pid != std::process::id()
+            && process_running(pid)
+        {
+            return Err(BizClawError::Config(format!(
+                "BizClaw is already running as PID {pid} ({})",
+                path.display()
+            )));
+        }
+        if let Some(dir) = path.parent()
+            && !dir.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, format!("{}\n", std::process::id()))?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if read_pid(&self.path) == Some(std::process::id()) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Whether `pid` is alive, where `/proc` can tell; assumed alive elsewhere.
+fn process_running(pid: u32) -> bool {
+    let proc = Path::new("/proc");
+    !proc.exists() || proc.join(pid.to_string()).exists()
+}
+
+/// Send `state` to the service manager over `$NOTIFY_SOCKET`, as
+/// `sd_notify(3)` does. Returns whether it was sent; without systemd
+/// there is no socket and nothing to do.
+pub fn notify(state: &str) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::net::UnixDatagram;
+
+        let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+            return false;
+        };
+        let Ok(sender) = UnixDatagram::unbound() else {
+            return false;
+        };
+        let socket = socket.to_string_lossy();
+        let sent = match socket.strip_prefix('@') {
+            #[cfg(target_os = "linux")]
+            Some(name) => {
+                use std::os::linux::net::SocketAddrExt;
+                std::os::unix::net::SocketAddr::from_abstract_name(name)
+                    .and_then(|addr| sender.send_to_addr(state.as_bytes(), &addr))
+            }
+            #[cfg(not(target_os = "linux"))]
+            Some(_) => return false,
+            None => sender.send_to(state.as_bytes(), Path::new(&*socket)),
+        };
+        if let Err(e) = &sent {
+            tracing::warn!("sd_notify {state:?} failed: {e}");
+        }
+        sent.is_ok()
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = state;
+        false
+    }
+}
+
+/// Tell systemd the service is up, and start its watchdog pings if the
+/// unit sets `WatchdogSec`.
+pub fn notify_ready() {
+    if notify(&format!("READY=1\nMAINPID={}", std::process::id())) {
+        spawn_watchdog();
+    }
+}
+
+/// Tell systemd the service is shutting down.
+pub fn notify_stopping() {
+    notify("STOPPING=1");
+}
+
+/// Ping the watchdog at half of `$WATCHDOG_USEC`.
+fn spawn_watchdog() {
+    let Some(usec) = std::env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .filter(|&usec| usec > 0)
+    else {
+        return;
+    };
+    let period = std::time::Duration::from_micros(usec / 2);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            notify("WATCHDOG=1");
+        }
+    });
+}
+
+/// Resolve on Ctrl+C or SIGTERM, returning the signal's name.
+pub async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = term.recv() => "SIGTERM",
+            },
+            Err(e) => {
+                tracing::warn!("Can't listen for SIGTERM: {e}");
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+
+    #[cfg(all(not(unix), not(target_arch = "wasm32")))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl+C"
+    }
+
+    // No signals in a browser.
+    #[cfg(target_arch = "wasm32")]
+    {
+        std::future::pending().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file() {
+        let dir = std::env::temp_dir().join(format!("bizclaw-pid-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("bizclaw.pid");
+
+        // A stale file from a process that no longer exists is replaced.
+        std::fs::create_dir_all(&dir).unwrap();
+        if Path::new("/proc").exists() {
+            std::fs::write(&path, "4294967295\n").unwrap();
+        }
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+
+        drop(pid_file);
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,5 +1,11 @@
 //! Unified error types for BizClaw.
+//!
+//! Callers decide what to do with a failure through [`BizClawError::is_retryable`],
+//! [`BizClawError::retry_after`] and [`BizClawError::code`] instead of
+//! matching on message text. Codes are stable snake_case strings meant for
+//! API clients and logs.
 
+use std::time::Duration;
 use thiserror::Error;
 
 /// Result type alias using BizClawError.
@@ -20,6 +26,9 @@ pub enum BizClawError {
     #[error("API key not configured for provider: {0}")]
     ApiKeyMissing(String),
 
+    #[error("Context window exceeded: {0}")]
+    ContextOverflow(String),
+
     // Channel errors
     #[error("Channel error: {0}")]
     Channel(String),
@@ -27,6 +36,9 @@ pub enum BizClawError {
     #[error("Channel not connected: {0}")]
     ChannelNotConnected(String),
 
+    #[error("Channel authentication failed: {0}")]
+    ChannelAuth(String),
+
     #[error("Authentication failed: {0}")]
     AuthFailed(String),
 
@@ -41,6 +53,9 @@ pub enum BizClawError {
     #[error("Model load error: {0}")]
     ModelLoad(String),
 
+    #[error("Model not loaded: {0}")]
+    ModelNotLoaded(String),
+
     #[error("GGUF parse error: {0}")]
     GgufParse(String),
 
@@ -85,8 +100,12 @@ pub enum BizClawError {
     #[error("Timeout: {0}")]
     Timeout(String),
 
-    #[error("Rate limited: {0}")]
-    RateLimited(String),
+    #[error("Rate limited: {message}")]
+    RateLimited {
+        message: String,
+        /// How long the other side asked us to wait, when it said.
+        retry_after: Option<Duration>,
+    },
 
     // Orchestration errors
     #[error("Delegation error: {0}")]
@@ -138,6 +157,113 @@ impl BizClawError {
     pub fn security(msg: impl Into<String>) -> Self {
         Self::Security(msg.into())
     }
+
+    pub fn rate_limited(msg: impl Into<String>, retry_after: Option<Duration>) -> Self {
+        Self::RateLimited {
+            message: msg.into(),
+            retry_after,
+        }
+    }
+
+    /// Attach a server-provided wait to a `RateLimited` error; other errors
+    /// are returned unchanged.
+    pub fn with_retry_after(self, wait: Option<Duration>) -> Self {
+        match self {
+            Self::RateLimited {
+                message,
+                retry_after,
+            } => Self::RateLimited {
+                message,
+                retry_after: wait.or(retry_after),
+            },
+            other => other,
+        }
+    }
+
+    /// Whether the failed operation may succeed if retried — network errors,
+    /// timeouts, rate limits and dropped connections. Auth, config and
+    /// validation errors are permanent.
+    pub fn is_retryable(&self) -> bool {
+        use std::io::ErrorKind;
+        match self {
+            Self::Http(_)
+            | Self::Timeout(_)
+            | Self::RateLimited { .. }
+            | Self::ChannelNotConnected(_) => true,
+            Self::Io(e) => matches!(
+                e.kind(),
+                ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::ConnectionRefused
+                    | ErrorKind::BrokenPipe
+                    | ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+                    | ErrorKind::UnexpectedEof
+            ),
+            _ => false,
+        }
+    }
+
+    /// How long to wait before retrying, when the failure said so.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Stable machine-readable code, e.g. `rate_limited`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Provider(_) => "provider_error",
+            Self::ProviderNotFound(_) => "provider_not_found",
+            Self::ModelNotFound(_) => "model_not_found",
+            Self::ApiKeyMissing(_) => "api_key_missing",
+            Self::ContextOverflow(_) => "context_overflow",
+            Self::Channel(_) => "channel_error",
+            Self::ChannelNotConnected(_) => "channel_not_connected",
+            Self::ChannelAuth(_) => "channel_auth",
+            Self::AuthFailed(_) => "auth_failed",
+            Self::Memory(_) => "memory_error",
+            Self::Brain(_) => "brain_error",
+            Self::ModelLoad(_) => "model_load_error",
+            Self::ModelNotLoaded(_) => "model_not_loaded",
+            Self::GgufParse(_) => "gguf_parse_error",
+            Self::Inference(_) => "inference_error",
+            Self::Tool(_) => "tool_error",
+            Self::ToolNotFound(_) => "tool_not_found",
+            Self::Security(_) => "security_violation",
+            Self::PermissionDenied(_) => "permission_denied",
+            Self::Config(_) => "config_error",
+            Self::ConfigNotFound(_) => "config_not_found",
+            Self::Gateway(_) => "gateway_error",
+            Self::Io(_) => "io_error",
+            Self::Json(_) => "json_error",
+            Self::Http(_) => "http_error",
+            Self::Timeout(_) => "timeout",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::Delegation(_) => "delegation_error",
+            Self::AgentNotFound(_) => "agent_not_found",
+            Self::NoPermission(_) => "no_permission",
+            Self::Team(_) => "team_error",
+            Self::Handoff(_) => "handoff_error",
+            Self::EvaluateLoop(_) => "evaluate_loop_error",
+            Self::QualityGate(_) => "quality_gate_failed",
+            Self::Database(_) => "database_error",
+            Self::Other(_) => "internal_error",
+        }
+    }
+}
+
+/// Parse an HTTP `Retry-After` value — delay seconds or an HTTP date.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = at.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
 }
 
 #[cfg(test)]
@@ -165,6 +291,58 @@ mod tests {
         assert!(matches!(e4, BizClawError::Security(_)));
     }
 
+    #[test]
+    fn test_is_retryable() {
+        assert!(BizClawError::rate_limited("429", None).is_retryable());
+        assert!(BizClawError::Timeout("30s".into()).is_retryable());
+        assert!(!BizClawError::AuthFailed("bad token".into()).is_retryable());
+        assert!(!BizClawError::ChannelAuth("revoked".into()).is_retryable());
+        assert!(!BizClawError::ContextOverflow("9000 > 8192".into()).is_retryable());
+        assert!(!BizClawError::ModelNotLoaded("llama".into()).is_retryable());
+        assert!(!BizClawError::Channel("chat not found".into()).is_retryable());
+
+        let reset = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        assert!(BizClawError::from(reset).is_retryable());
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        assert!(!BizClawError::from(missing).is_retryable());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let err = BizClawError::rate_limited("slow down", None);
+        assert_eq!(err.retry_after(), None);
+        let err = err.with_retry_after(Some(Duration::from_secs(7)));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(7)));
+        assert_eq!(err.to_string(), "Rate limited: slow down");
+
+        let other =
+            BizClawError::Timeout("t".into()).with_retry_after(Some(Duration::from_secs(1)));
+        assert_eq!(other.retry_after(), None);
+
+        assert_eq!(parse_retry_after(" 30 "), Some(Duration::from_secs(30)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn test_codes() {
+        assert_eq!(BizClawError::rate_limited("r", None).code(), "rate_limited");
+        assert_eq!(BizClawError::Timeout("t".into()).code(), "timeout");
+        assert_eq!(
+            BizClawError::ModelNotLoaded("m".into()).code(),
+            "model_not_loaded"
+        );
+        assert_eq!(
+            BizClawError::ContextOverflow("c".into()).code(),
+            "context_overflow"
+        );
+        assert_eq!(BizClawError::ChannelAuth("c".into()).code(), "channel_auth");
+        assert_eq!(BizClawError::Other("o".into()).code(), "internal_error");
+    }
+
     #[test]
     fn test_io_error_conversion() {
         let io_err = std::io::Error::new(std::io::ErrorKind::NotFound, "file not found");
@@ -186,12 +364,15 @@ mod tests {
             BizClawError::ProviderNotFound("p".into()),
             BizClawError::ModelNotFound("m".into()),
             BizClawError::ApiKeyMissing("k".into()),
+            BizClawError::ContextOverflow("o".into()),
             BizClawError::Channel("c".into()),
             BizClawError::ChannelNotConnected("c".into()),
+            BizClawError::ChannelAuth("c".into()),
             BizClawError::AuthFailed("a".into()),
             BizClawError::Memory("m".into()),
             BizClawError::Brain("b".into()),
             BizClawError::ModelLoad("l".into()),
+            BizClawError::ModelNotLoaded("n".into()),
             BizClawError::GgufParse("g".into()),
             BizClawError::Inference("i".into()),
             BizClawError::Tool("t".into()),
@@ -203,7 +384,7 @@ mod tests {
             BizClawError::Gateway("g".into()),
             BizClawError::Http("h".into()),
             BizClawError::Timeout("t".into()),
-            BizClawError::RateLimited("r".into()),
+            BizClawError::rate_limited("r", None),
             BizClawError::Delegation("d".into()),
             BizClawError::AgentNotFound("a".into()),
             BizClawError::NoPermission("n".into()),
@@ -215,12 +396,14 @@ mod tests {
             BizClawError::Other("o".into()),
         ];
 
+        let mut codes = std::collections::HashSet::new();
         for err in &errors {
             let display = err.to_string();
             assert!(!display.is_empty(), "Error should have display: {:?}", err);
+            assert!(codes.insert(err.code()), "Duplicate code: {}", err.code());
         }
-        // There should be 31 variants
-        assert_eq!(errors.len(), 31);
+        // There should be 34 variants (Io and Json are covered by the conversion tests)
+        assert_eq!(errors.len(), 34);
     }
 
     #[test]
@@ -0,0 +1,227 @@
+//! Event bus — typed events shared across crates.
+//!
+//! Components publish what happened (a message arrived, a reply was
+//! generated, a tool ran) on an [`EventBus`] without knowing who listens;
+//! the gateway's activity feed, metrics and the audit [`EventLog`] each
+//! subscribe to it. Publishing never blocks: a subscriber that falls more
+//! than the bus capacity behind loses the oldest events.
+
+use crate::config::BizClawConfig;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// Something that happened in one of the subsystems.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A channel delivered a message.
+    MessageReceived {
+        channel: String,
+        thread_id: String,
+        sender_id: String,
+    },
+    /// A model was asked for a reply.
+    GenerationStarted {
+        channel: String,
+        thread_id: String,
+        model: String,
+    },
+    /// A reply was generated, or generating it failed.
+    GenerationFinished {
+        channel: String,
+        thread_id: String,
+        model: String,
+        duration_ms: u64,
+        success: bool,
+    },
+    /// A channel connected, or reconnected.
+    ChannelConnected { channel: String },
+    /// A tool call ran.
+    ToolExecuted {
+        tool: String,
+        duration_ms: u64,
+        success: bool,
+    },
+}
+
+impl Event {
+    /// Dotted name, e.g. `message.received`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::MessageReceived { .. } => "message.received",
+            Self::GenerationStarted { .. } => "generation.started",
+            Self::GenerationFinished { .. } => "generation.finished",
+            Self::ChannelConnected { .. } => "channel.connected",
+            Self::ToolExecuted { .. } => "tool.executed",
+        }
+    }
+}
+
+/// An event and when it was published.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventRecord {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Broadcasts events to every subscriber. Clones share the same bus.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<EventRecord>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// A bus keeping up to `capacity` events for slow subscribers.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        Self { tx }
+    }
+
+    /// Publish `event`; dropped when nobody is subscribed.
+    pub fn publish(&self, event: Event) {
+        let _ = self.tx.send(EventRecord {
+            timestamp: chrono::Utc::now(),
+            event,
+        });
+    }
+
+    /// Receive the events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<EventRecord> {
+        self.tx.subscribe()
+    }
+
+    /// Run `f` for each event in a background task for as long as the bus
+    /// exists. `name` labels the warning logged when `f` falls behind.
+    pub fn spawn_subscriber<F>(&self, name: &'static str, mut f: F) -> tokio::task::JoinHandle<()>
+    where
+        F: FnMut(EventRecord) + Send + 'static,
+    {
+        let mut rx = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(record) => f(record),
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!("{name} fell behind, {missed} event(s) dropped");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Append-only JSON lines file of events — the audit log.
+pub struct EventLog {
+    path: PathBuf,
+}
+
+impl EventLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn default_path() -> PathBuf {
+        BizClawConfig::home_dir().join("events.jsonl")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, record: &EventRecord) -> Result<()> {
+        let line = serde_json::to_string(record)?;
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{line}")?;
+        Ok(())
+    }
+
+    /// Record every event published on `bus` from now on.
+    pub fn spawn(self, bus: &EventBus) -> tokio::task::JoinHandle<()> {
+        bus.spawn_subscriber("Event log", move |record| {
+            if let Err(e) = self.record(&record) {
+                tracing::warn!("Failed to write {}: {e}", self.path.display());
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_publish_and_subscribe() {
+        let bus = EventBus::new();
+        // Nobody listening yet: dropped.
+        bus.publish(Event::ChannelConnected {
+            channel: "telegram".into(),
+        });
+        let mut rx = bus.subscribe();
+        bus.clone().publish(Event::ToolExecuted {
+            tool: "web_search".into(),
+            duration_ms: 12,
+            success: true,
+        });
+        let record = rx.recv().await.unwrap();
+        assert_eq!(record.event.kind(), "tool.executed");
+        assert!(rx.try_recv().is_err());
+
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["type"], "tool_executed");
+        assert_eq!(json["tool"], "web_search");
+        assert!(json["timestamp"].is_string());
+        let back: EventRecord = serde_json::from_value(json).unwrap();
+        assert_eq!(back, record);
+    }
+
+    #[tokio::test]
+    async fn test_event_log() {
+        let path =
+            std::env::temp_dir().join(format!("bizclaw-events-{}.jsonl", uuid::Uuid::new_v4()));
+        let bus = EventBus::new();
+        let task = EventLog::new(&path).spawn(&bus);
+        bus.publish(Event::MessageReceived {
+            channel: "zalo".into(),
+            thread_id: "t1".into(),
+            sender_id: "u1".into(),
+        });
+        bus.publish(Event::ChannelConnected {
+            channel: "zalo".into(),
+        });
+        drop(bus);
+        task.await.unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let kinds: Vec<String> = content
+            .lines()
+            .map(|line| serde_json::from_str::<EventRecord>(line).unwrap())
+            .map(|record| record.event.kind().to_string())
+            .collect();
+        assert_eq!(kinds, ["message.received", "channel.connected"]);
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -4,9 +4,18 @@
 //! Every subsystem is a trait — swap implementations with a config change.
 
 pub mod config;
+pub mod daemon;
 pub mod error;
+pub mod events;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod logging;
+pub mod metrics;
+pub mod reload;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod telemetry;
 pub mod traits;
 pub mod types;
 
-pub use config::BizClawConfig;
+pub use config::{AppConfig, BizClawConfig};
 pub use error::{BizClawError, Result};
+pub use events::{Event, EventBus};
@@ -0,0 +1,316 @@
+//! Logging — the `tracing` subscriber, set up from `[logging]`.
+//!
+//! Logs go to stderr in the `pretty`, `compact` or `json` format, and
+//! optionally to a file ([`RotatingFile`]) that starts over once it reaches
+//! `max_size_mb` or an hour or day boundary passes, keeping `max_files` old
+//! files as `bizclaw.log.1`, `bizclaw.log.2`, … With a file, stderr only
+//! gets warnings and errors.
+//!
+//! The gateway opens a `request` span carrying a `request_id` for every
+//! HTTP request, and the agent and brain open theirs inside it, so in the
+//! `json` format every line of one request can be found by its id:
+//!
+//! ```text
+//! {"level":"DEBUG","fields":{"message":"decoded"},"spans":[{"name":"request","request_id":"6f1c…"},{"name":"agent"},{"name":"brain"}]}
+//! ```
+
+use crate::config::LoggingConfig;
+use crate::error::{BizClawError, Result};
+use chrono::{DateTime, Duration, Local, NaiveTime, Timelike};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Formats accepted in `[logging] format`.
+pub const FORMATS: &[&str] = &["pretty", "compact", "json"];
+
+/// When a log file starts over regardless of its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Never,
+    Hourly,
+    Daily,
+}
+
+impl Rotation {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "" | "never" => Ok(Self::Never),
+            "hourly" => Ok(Self::Hourly),
+            "daily" => Ok(Self::Daily),
+            other => Err(BizClawError::Config(format!(
+                "Unknown log rotation '{other}'; use never, hourly or daily"
+            ))),
+        }
+    }
+
+    /// The first boundary after `now`.
+    fn next_after(self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Self::Never => None,
+            Self::Hourly => {
+                let hour = now.with_minute(0)?.with_second(0)?.with_nanosecond(0)?;
+                Some(hour + Duration::hours(1))
+            }
+            Self::Daily => now
+                .date_naive()
+                .succ_opt()?
+                .and_time(NaiveTime::MIN)
+                .and_local_timezone(Local)
+                .earliest(),
+        }
+    }
+}
+
+/// A log file that rotates by size and time.
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    /// 0 means no size limit.
+    max_bytes: u64,
+    max_files: usize,
+    rotation: Rotation,
+    next_rotation: Option<DateTime<Local>>,
+}
+
+impl RotatingFile {
+    /// Open `path` for appending, creating its directory.
+    pub fn open(
+        path: &Path,
+        max_bytes: u64,
+        rotation: Rotation,
+        max_files: usize,
+    ) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = Self::append(path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            written,
+            max_bytes,
+            max_files,
+            rotation,
+            next_rotation: rotation.next_after(Local::now()),
+        })
+    }
+
+    fn append(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// `path` of the `n`th most recent rotated file.
+    fn numbered(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+
+    fn due(&self, now: DateTime<Local>, incoming: usize) -> bool {
+        let too_big = self.max_bytes > 0
+            && self.written > 0
+            && self.written + incoming as u64 > self.max_bytes;
+        too_big || self.next_rotation.is_some_and(|at| now >= at)
+    }
+
+    /// Move the current file to `.1`, shifting older ones up and dropping
+    /// the oldest, and start a new one.
+    fn rotate(&mut self, now: DateTime<Local>) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            self.file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&self.path)?;
+        } else {
+            for n in (1..self.max_files).rev() {
+                match std::fs::rename(self.numbered(n), self.numbered(n + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            std::fs::rename(&self.path, self.numbered(1))?;
+            self.file = Self::append(&self.path)?;
+        }
+        self.written = 0;
+        self.next_rotation = self.rotation.next_after(now);
+        Ok(())
+    }
+
+    fn write_at(&mut self, now: DateTime<Local>, buf: &[u8]) -> io::Result<usize> {
+        if self.due(now, buf.len()) {
+            self.rotate(now)?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_at(Local::now(), buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// The filter used when `RUST_LOG` isn't set: `level` for BizClaw's crates
+/// (`debug` with `--verbose`), then the per-module levels.
+pub fn filter_directives(config: &LoggingConfig, verbose: bool) -> String {
+    let level = if verbose {
+        "debug"
+    } else {
+        config.level.as_str()
+    };
+    let mut directives = vec![format!("bizclaw={level}")];
+    directives.extend(
+        config
+            .modules
+            .iter()
+            .map(|(target, level)| format!("{target}={level}")),
+    );
+    directives.join(",")
+}
+
+fn fmt_layer<S, W>(format: &str, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match format {
+        "json" => layer
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+        "compact" => layer.compact().boxed(),
+        _ => layer.boxed(),
+    }
+}
+
+/// Install the global subscriber. Call once, early in `main`.
+pub fn init(config: &LoggingConfig, verbose: bool) -> Result<()> {
+    if !FORMATS.contains(&config.format.as_str()) {
+        return Err(BizClawError::Config(format!(
+            "Unknown log format '{}'; use pretty, compact or json",
+            config.format
+        )));
+    }
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(filter_directives(config, verbose)))
+        .map_err(|e| BizClawError::Config(format!("Invalid [logging] level: {e}")))?;
+
+    let file = match config.file_path() {
+        Some(path) => Some(RotatingFile::open(
+            &path,
+            config.max_size_mb * 1024 * 1024,
+            Rotation::parse(&config.rotation)?,
+            config.max_files,
+        )?),
+        None => None,
+    };
+    let stderr_level = if file.is_some() {
+        tracing::Level::WARN
+    } else {
+        tracing::Level::TRACE
+    };
+    let ansi = io::IsTerminal::is_terminal(&io::stderr());
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(file.map(|file| fmt_layer(&config.format, Mutex::new(file), false)))
+        .with(fmt_layer(
+            &config.format,
+            io::stderr.with_max_level(stderr_level),
+            ansi,
+        ))
+        .try_init()
+        .map_err(|e| BizClawError::Other(format!("Logging already initialized: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_rotation_boundaries() {
+        let now = Local.with_ymd_and_hms(2026, 3, 14, 15, 9, 26).unwrap();
+        assert_eq!(Rotation::Never.next_after(now), None);
+        assert_eq!(
+            Rotation::Hourly.next_after(now),
+            Some(Local.with_ymd_and_hms(2026, 3, 14, 16, 0, 0).unwrap())
+        );
+        assert_eq!(
+            Rotation::Daily.next_after(now),
+            Some(Local.with_ymd_and_hms(2026, 3, 15, 0, 0, 0).unwrap())
+        );
+        assert!(Rotation::parse("weekly").is_err());
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let dir = std::env::temp_dir().join(format!("bizclaw-logs-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("bizclaw.log");
+        let read = |p: PathBuf| std::fs::read_to_string(p).unwrap_or_default();
+
+        // By size: a line that would pass 10 bytes starts a new file.
+        let mut file = RotatingFile::open(&path, 10, Rotation::Never, 2).unwrap();
+        for line in ["one\n", "two\n", "three\n", "four\n", "five\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+        assert_eq!(read(path.clone()), "four\nfive\n");
+        assert_eq!(read(file.numbered(1)), "three\n");
+        assert_eq!(read(file.numbered(2)), "one\ntwo\n");
+        assert!(!file.numbered(3).exists());
+
+        // By time: the first write past the boundary starts a new file.
+        let mut file = RotatingFile::open(&path, 0, Rotation::Hourly, 2).unwrap();
+        let later = file.next_rotation.unwrap() + Duration::minutes(1);
+        file.write_at(later, b"six\n").unwrap();
+        file.flush().unwrap();
+        assert_eq!(read(path.clone()), "six\n");
+        assert_eq!(read(file.numbered(1)), "four\nfive\n");
+        assert_eq!(read(file.numbered(2)), "three\n");
+        assert!(file.next_rotation.unwrap() > later);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_filter_directives() {
+        let mut config = LoggingConfig::default();
+        assert_eq!(filter_directives(&config, false), "bizclaw=info");
+        assert_eq!(filter_directives(&config, true), "bizclaw=debug");
+
+        config.level = "warn".into();
+        config
+            .modules
+            .insert("bizclaw_brain".into(), "trace".into());
+        config.modules.insert("tower_http".into(), "debug".into());
+        assert_eq!(
+            filter_directives(&config, false),
+            "bizclaw=warn,bizclaw_brain=trace,tower_http=debug"
+        );
+    }
+}
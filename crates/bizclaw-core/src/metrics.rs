@@ -0,0 +1,647 @@
+//! Metrics — counters, gauges and histograms shared by every subsystem.
+//!
+//! Instruments are looked up by name and labels on a [`Metrics`] registry,
+//! normally the process-wide [`global`] one, and are cheap to update from
+//! any thread:
+//!
+//! ```ignore
+//! metrics::counter("bizclaw_messages_received_total", &[("channel", "zalo")]).inc();
+//! metrics::histogram("bizclaw_tool_duration_seconds", &[("tool", name)]).observe(secs);
+//! ```
+//!
+//! Exporters ([`MetricsExporter`]) take a [`Sample`] snapshot periodically —
+//! `log` writes it to the log, `prometheus` to a text file in the Prometheus
+//! exposition format for the node exporter's textfile collector. The gateway
+//! also serves [`render_prometheus`] at `/metrics`. [`spawn_event_metrics`]
+//! derives the message, generation and tool metrics from the event bus.
+
+use crate::config::MetricsConfig;
+use crate::error::{BizClawError, Result};
+use crate::events::{Event, EventBus};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// Histogram buckets, in seconds, when none are given.
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// Exporter names accepted in `[metrics] exporters`.
+pub const EXPORTERS: &[&str] = &["log", "prometheus"];
+
+/// A monotonically increasing count.
+#[derive(Debug, Clone, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A value that goes up and down.
+#[derive(Debug, Clone, Default)]
+pub struct Gauge(Arc<AtomicU64>);
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn add(&self, delta: f64) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + delta).to_bits())
+            });
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// A distribution of observed values over fixed buckets.
+#[derive(Debug, Clone)]
+pub struct Histogram(Arc<Mutex<HistogramData>>);
+
+#[derive(Debug)]
+struct HistogramData {
+    bounds: Vec<f64>,
+    /// Per bucket, not cumulative; the last one is `+Inf`.
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        Self(Arc::new(Mutex::new(HistogramData {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+            count: 0,
+        })))
+    }
+
+    pub fn observe(&self, value: f64) {
+        let mut data = self.0.lock().unwrap();
+        let bucket = data
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(data.bounds.len());
+        data.counts[bucket] += 1;
+        data.sum += value;
+        data.count += 1;
+    }
+
+    /// Observe a duration in seconds.
+    pub fn observe_duration(&self, duration: Duration) {
+        self.observe(duration.as_secs_f64());
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        let data = self.0.lock().unwrap();
+        let mut cumulative = 0;
+        let buckets = data
+            .bounds
+            .iter()
+            .zip(&data.counts)
+            .map(|(bound, count)| {
+                cumulative += count;
+                (*bound, cumulative)
+            })
+            .collect();
+        HistogramSnapshot {
+            buckets,
+            sum: data.sum,
+            count: data.count,
+        }
+    }
+}
+
+/// A histogram's state: cumulative counts per upper bound, plus the totals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    pub buckets: Vec<(f64, u64)>,
+    pub sum: f64,
+    pub count: u64,
+}
+
+/// The value of one metric in a snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Counter(u64),
+    Gauge(f64),
+    Histogram(HistogramSnapshot),
+}
+
+impl Value {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Counter(_) => "counter",
+            Self::Gauge(_) => "gauge",
+            Self::Histogram(_) => "histogram",
+        }
+    }
+}
+
+/// One metric in a snapshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone)]
+enum Instrument {
+    Counter(Counter),
+    Gauge(Gauge),
+    Histogram(Histogram),
+}
+
+type Key = (String, Vec<(String, String)>);
+
+/// A registry of instruments. Clones share the same instruments.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    instruments: Arc<Mutex<BTreeMap<Key, Instrument>>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The counter `name` with `labels`, created on first use.
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> Counter {
+        match self.instrument(name, labels, || Instrument::Counter(Counter::default())) {
+            Instrument::Counter(counter) => counter,
+            _ => {
+                type_mismatch(name, "counter");
+                Counter::default()
+            }
+        }
+    }
+
+    /// The gauge `name` with `labels`, created on first use.
+    pub fn gauge(&self, name: &str, labels: &[(&str, &str)]) -> Gauge {
+        match self.instrument(name, labels, || Instrument::Gauge(Gauge::default())) {
+            Instrument::Gauge(gauge) => gauge,
+            _ => {
+                type_mismatch(name, "gauge");
+                Gauge::default()
+            }
+        }
+    }
+
+    /// The histogram `name` with `labels` over [`DEFAULT_BUCKETS`].
+    pub fn histogram(&self, name: &str, labels: &[(&str, &str)]) -> Histogram {
+        self.histogram_with_buckets(name, labels, DEFAULT_BUCKETS)
+    }
+
+    /// The histogram `name` with `labels`; `buckets` only apply when it is
+    /// created.
+    pub fn histogram_with_buckets(
+        &self,
+        name: &str,
+        labels: &[(&str, &str)],
+        buckets: &[f64],
+    ) -> Histogram {
+        match self.instrument(name, labels, || {
+            Instrument::Histogram(Histogram::new(buckets))
+        }) {
+            Instrument::Histogram(histogram) => histogram,
+            _ => {
+                type_mismatch(name, "histogram");
+                Histogram::new(buckets)
+            }
+        }
+    }
+
+    /// Look up or create an instrument. A name already used by another kind
+    /// of instrument gets a detached one, so the caller still works.
+    fn instrument(
+        &self,
+        name: &str,
+        labels: &[(&str, &str)],
+        create: impl FnOnce() -> Instrument,
+    ) -> Instrument {
+        let mut labels: Vec<(String, String)> = labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        labels.sort();
+        self.instruments
+            .lock()
+            .unwrap()
+            .entry((name.to_string(), labels))
+            .or_insert_with(create)
+            .clone()
+    }
+
+    /// The current value of every instrument, sorted by name and labels.
+    pub fn snapshot(&self) -> Vec<Sample> {
+        let instruments = self.instruments.lock().unwrap();
+        instruments
+            .iter()
+            .map(|((name, labels), instrument)| Sample {
+                name: name.clone(),
+                labels: labels.clone(),
+                value: match instrument {
+                    Instrument::Counter(c) => Value::Counter(c.get()),
+                    Instrument::Gauge(g) => Value::Gauge(g.get()),
+                    Instrument::Histogram(h) => Value::Histogram(h.snapshot()),
+                },
+            })
+            .collect()
+    }
+}
+
+fn type_mismatch(name: &str, wanted: &str) {
+    tracing::warn!("Metric {name} is already registered as another type than {wanted}");
+}
+
+/// The process-wide registry.
+pub fn global() -> &'static Metrics {
+    static GLOBAL: OnceLock<Metrics> = OnceLock::new();
+    GLOBAL.get_or_init(Metrics::new)
+}
+
+/// [`Metrics::counter`] on the [`global`] registry.
+pub fn counter(name: &str, labels: &[(&str, &str)]) -> Counter {
+    global().counter(name, labels)
+}
+
+/// [`Metrics::gauge`] on the [`global`] registry.
+pub fn gauge(name: &str, labels: &[(&str, &str)]) -> Gauge {
+    global().gauge(name, labels)
+}
+
+/// [`Metrics::histogram`] on the [`global`] registry.
+pub fn histogram(name: &str, labels: &[(&str, &str)]) -> Histogram {
+    global().histogram(name, labels)
+}
+
+/// Count a failure in `bizclaw_errors_total` by its
+/// [`code`](BizClawError::code), never its message.
+pub fn count_error(error: &BizClawError) {
+    counter("bizclaw_errors_total", &[("class", error.code())]).inc();
+}
+
+/// Render `samples` in the Prometheus text exposition format.
+pub fn render_prometheus(samples: &[Sample]) -> String {
+    let mut out = String::new();
+    let mut last_name = None;
+    for sample in samples {
+        if last_name != Some(&sample.name) {
+            let _ = writeln!(out, "# TYPE {} {}", sample.name, sample.value.type_name());
+            last_name = Some(&sample.name);
+        }
+        match &sample.value {
+            Value::Counter(v) => {
+                let _ = writeln!(
+                    out,
+                    "{}{} {v}",
+                    sample.name,
+                    label_set(&sample.labels, None)
+                );
+            }
+            Value::Gauge(v) => {
+                let _ = writeln!(
+                    out,
+                    "{}{} {v}",
+                    sample.name,
+                    label_set(&sample.labels, None)
+                );
+            }
+            Value::Histogram(h) => {
+                for (bound, count) in &h.buckets {
+                    let le = bound.to_string();
+                    let labels = label_set(&sample.labels, Some(&le));
+                    let _ = writeln!(out, "{}_bucket{labels} {count}", sample.name);
+                }
+                let labels = label_set(&sample.labels, Some("+Inf"));
+                let _ = writeln!(out, "{}_bucket{labels} {}", sample.name, h.count);
+                let labels = label_set(&sample.labels, None);
+                let _ = writeln!(out, "{}_sum{labels} {}", sample.name, h.sum);
+                let _ = writeln!(out, "{}_count{labels} {}", sample.name, h.count);
+            }
+        }
+    }
+    out
+}
+
+/// `{k="v",…}`, with `le` last when given; empty without labels.
+fn label_set(labels: &[(String, String)], le: Option<&str>) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{k}=\"{}\"", escape_label(v)))
+        .collect();
+    if let Some(le) = le {
+        pairs.push(format!("le=\"{le}\""));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Where a metrics snapshot is sent.
+pub trait MetricsExporter: Send + Sync {
+    fn name(&self) -> &str;
+    fn export(&self, samples: &[Sample]) -> Result<()>;
+}
+
+/// Writes each counter and gauge, and each histogram's count and mean, to
+/// the log.
+pub struct LogExporter;
+
+impl MetricsExporter for LogExporter {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    fn export(&self, samples: &[Sample]) -> Result<()> {
+        for sample in samples {
+            let series = format!("{}{}", sample.name, label_set(&sample.labels, None));
+            match &sample.value {
+                Value::Counter(v) => tracing::info!("📊 {series} = {v}"),
+                Value::Gauge(v) => tracing::info!("📊 {series} = {v}"),
+                Value::Histogram(h) if h.count > 0 => tracing::info!(
+                    "📊 {series} count={} mean={:.3}",
+                    h.count,
+                    h.sum / h.count as f64
+                ),
+                Value::Histogram(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the Prometheus text format to a file, replacing it atomically.
+pub struct PrometheusExporter {
+    path: PathBuf,
+}
+
+impl PrometheusExporter {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl MetricsExporter for PrometheusExporter {
+    fn name(&self) -> &str {
+        "prometheus"
+    }
+
+    fn export(&self, samples: &[Sample]) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Scrapers never see a half-written file.
+        let tmp = self.path.with_extension("prom.tmp");
+        std::fs::write(&tmp, render_prometheus(samples))?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// The exporters named in `[metrics] exporters`.
+pub fn exporters_from_config(config: &MetricsConfig) -> Result<Vec<Box<dyn MetricsExporter>>> {
+    config
+        .exporters
+        .iter()
+        .map(|name| match name.as_str() {
+            "log" => Ok(Box::new(LogExporter) as Box<dyn MetricsExporter>),
+            "prometheus" => Ok(Box::new(PrometheusExporter::new(config.textfile_path()))
+                as Box<dyn MetricsExporter>),
+            other => Err(BizClawError::Config(format!(
+                "Unknown metrics exporter: {other}"
+            ))),
+        })
+        .collect()
+}
+
+/// Export a snapshot of `metrics` to every exporter each `interval`.
+pub fn spawn_exporters(
+    metrics: Metrics,
+    exporters: Vec<Box<dyn MetricsExporter>>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            let samples = metrics.snapshot();
+            for exporter in &exporters {
+                if let Err(e) = exporter.export(&samples) {
+                    tracing::warn!("Metrics exporter {} failed: {e}", exporter.name());
+                }
+            }
+        }
+    })
+}
+
+/// Start the `[metrics]` exporters on the [`global`] registry, if any.
+pub fn start_from_config(config: &MetricsConfig) -> Option<tokio::task::JoinHandle<()>> {
+    match exporters_from_config(config) {
+        Ok(exporters) if !exporters.is_empty() => Some(spawn_exporters(
+            global().clone(),
+            exporters,
+            Duration::from_secs(config.interval_seconds),
+        )),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!("Metrics export disabled: {e}");
+            None
+        }
+    }
+}
+
+/// Count the events published on `bus` in `metrics`:
+/// `bizclaw_messages_received_total`, `bizclaw_generations_total`,
+/// `bizclaw_generation_duration_seconds`, `bizclaw_tool_calls_total`,
+/// `bizclaw_tool_duration_seconds` and `bizclaw_channel_connects_total`.
+pub fn spawn_event_metrics(bus: &EventBus, metrics: Metrics) -> tokio::task::JoinHandle<()> {
+    bus.spawn_subscriber("Event metrics", move |record| {
+        record_event(&metrics, &record.event)
+    })
+}
+
+fn record_event(metrics: &Metrics, event: &Event) {
+    let status = |success: bool| if success { "ok" } else { "error" };
+    match event {
+        Event::MessageReceived { channel, .. } => metrics
+            .counter("bizclaw_messages_received_total", &[("channel", channel)])
+            .inc(),
+        Event::GenerationStarted { .. } => {}
+        Event::GenerationFinished {
+            channel,
+            duration_ms,
+            success,
+            ..
+        } => {
+            metrics
+                .counter(
+                    "bizclaw_generations_total",
+                    &[("channel", channel), ("status", status(*success))],
+                )
+                .inc();
+            metrics
+                .histogram(
+                    "bizclaw_generation_duration_seconds",
+                    &[("channel", channel)],
+                )
+                .observe(*duration_ms as f64 / 1000.0);
+        }
+        Event::ChannelConnected { channel } => metrics
+            .counter("bizclaw_channel_connects_total", &[("channel", channel)])
+            .inc(),
+        Event::ToolExecuted {
+            tool,
+            duration_ms,
+            success,
+        } => {
+            metrics
+                .counter(
+                    "bizclaw_tool_calls_total",
+                    &[("tool", tool), ("status", status(*success))],
+                )
+                .inc();
+            metrics
+                .histogram("bizclaw_tool_duration_seconds", &[("tool", tool)])
+                .observe(*duration_ms as f64 / 1000.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruments() {
+        let metrics = Metrics::new();
+        metrics
+            .counter("requests_total", &[("channel", "zalo")])
+            .inc();
+        metrics
+            .counter("requests_total", &[("channel", "zalo")])
+            .add(2);
+        metrics
+            .counter("requests_total", &[("channel", "slack")])
+            .inc();
+        let gauge = metrics.gauge("queue_depth", &[]);
+        gauge.set(4.0);
+        gauge.add(-1.5);
+        let histogram = metrics.histogram_with_buckets("latency_seconds", &[], &[0.1, 1.0]);
+        histogram.observe(0.0625);
+        histogram.observe(0.5);
+        histogram.observe(3.1875);
+        // A name taken by another kind gets a detached instrument.
+        metrics
+            .gauge("requests_total", &[("channel", "zalo")])
+            .set(9.0);
+
+        let samples = metrics.snapshot();
+        let names: Vec<&str> = samples.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "latency_seconds",
+                "queue_depth",
+                "requests_total",
+                "requests_total"
+            ]
+        );
+        assert_eq!(
+            samples[0].value,
+            Value::Histogram(HistogramSnapshot {
+                buckets: vec![(0.1, 1), (1.0, 2)],
+                sum: 3.75,
+                count: 3,
+            })
+        );
+        assert_eq!(samples[1].value, Value::Gauge(2.5));
+        assert_eq!(samples[2].labels, [("channel".into(), "slack".into())]);
+        assert_eq!(samples[3].value, Value::Counter(3));
+    }
+
+    #[test]
+    fn test_render_prometheus() {
+        let metrics = Metrics::new();
+        metrics
+            .counter("bizclaw_messages_received_total", &[("channel", "zalo")])
+            .add(7);
+        metrics
+            .histogram_with_buckets("bizclaw_tool_duration_seconds", &[("tool", "a\"b")], &[1.0])
+            .observe(0.5);
+        let text = render_prometheus(&metrics.snapshot());
+        assert_eq!(
+            text,
+            "# TYPE bizclaw_messages_received_total counter\n\
+             bizclaw_messages_received_total{channel=\"zalo\"} 7\n\
+             # TYPE bizclaw_tool_duration_seconds histogram\n\
+             bizclaw_tool_duration_seconds_bucket{tool=\"a\\\"b\",le=\"1\"} 1\n\
+             bizclaw_tool_duration_seconds_bucket{tool=\"a\\\"b\",le=\"+Inf\"} 1\n\
+             bizclaw_tool_duration_seconds_sum{tool=\"a\\\"b\"} 0.5\n\
+             bizclaw_tool_duration_seconds_count{tool=\"a\\\"b\"} 1\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_metrics() {
+        let bus = EventBus::new();
+        let metrics = Metrics::new();
+        let task = spawn_event_metrics(&bus, metrics.clone());
+        bus.publish(Event::GenerationFinished {
+            channel: "telegram".into(),
+            thread_id: "1".into(),
+            model: "m".into(),
+            duration_ms: 1500,
+            success: false,
+        });
+        drop(bus);
+        task.await.unwrap();
+
+        let errors = metrics.counter(
+            "bizclaw_generations_total",
+            &[("status", "error"), ("channel", "telegram")],
+        );
+        assert_eq!(errors.get(), 1);
+        let samples = metrics.snapshot();
+        let duration = samples
+            .iter()
+            .find(|s| s.name == "bizclaw_generation_duration_seconds")
+            .unwrap();
+        let Value::Histogram(h) = &duration.value else {
+            panic!("not a histogram");
+        };
+        assert_eq!((h.count, h.sum), (1, 1.5));
+    }
+}
@@ -0,0 +1,313 @@
+//! Config hot reload.
+//!
+//! [`ConfigWatcher`] polls the config file and, when it changes, loads it
+//! again and publishes a [`ConfigReload`] listing what changed. Settings
+//! under [`HOT_RELOADABLE`] are applied by the running components: the
+//! agent and its provider take personas, prompts, routing, guardrails and
+//! `brain.max_tokens`, the channel manager takes allowlists and Zalo's rate
+//! limits. Anything else is reported as needing a restart. An edit that
+//! fails to parse or validate is logged and the running config is kept.
+
+use crate::config::BizClawConfig;
+use crate::error::Result;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+
+/// Settings applied without a restart; a path covers everything below it.
+pub const HOT_RELOADABLE: &[&str] = &[
+    "personas",
+    "default_temperature",
+    "brain.max_tokens",
+    "language",
+    "templates",
+    "routing",
+    "guardrails",
+    "channel.telegram.allowed_chat_ids",
+    "channel.discord.allowed_channel_ids",
+    "channel.zalo.allowlist",
+    "channel.zalo.rate_limit.max_messages_per_minute",
+    "channel.zalo.rate_limit.max_messages_per_hour",
+];
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// What a reload changed, as dotted config paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigChange {
+    /// Now in effect.
+    pub applied: Vec<String>,
+    /// In effect after the next restart.
+    pub restart_required: Vec<String>,
+}
+
+impl ConfigChange {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.restart_required.is_empty()
+    }
+}
+
+impl std::fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |paths: &[String]| {
+            if paths.is_empty() {
+                "none".to_string()
+            } else {
+                paths.join(", ")
+            }
+        };
+        write!(
+            f,
+            "applied: {}; restart required: {}",
+            list(&self.applied),
+            list(&self.restart_required)
+        )
+    }
+}
+
+/// A config file change that was loaded.
+#[derive(Debug, Clone)]
+pub struct ConfigReload {
+    pub config: Arc<BizClawConfig>,
+    pub change: ConfigChange,
+}
+
+/// The settings that differ between `old` and `new`.
+pub fn diff(old: &BizClawConfig, new: &BizClawConfig) -> ConfigChange {
+    let (Ok(old), Ok(new)) = (serde_json::to_value(old), serde_json::to_value(new)) else {
+        return ConfigChange::default();
+    };
+    let mut paths = Vec::new();
+    changed_paths("", &old, &new, &mut paths);
+    let (applied, restart_required): (Vec<_>, Vec<_>) =
+        paths.into_iter().partition(|path| is_hot_reloadable(path));
+    ConfigChange {
+        applied,
+        restart_required,
+    }
+}
+
+fn is_hot_reloadable(path: &str) -> bool {
+    HOT_RELOADABLE.iter().any(|prefix| {
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+/// Collect the paths of the leaves that differ. Lists count as one value.
+fn changed_paths(prefix: &str, old: &Value, new: &Value, out: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => changed_paths(&path, old, new, out),
+                    _ => out.push(path),
+                }
+            }
+        }
+        _ if old != new => out.push(prefix.to_string()),
+        _ => {}
+    }
+}
+
+/// Polls a config file for changes.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    interval: Duration,
+    current: Arc<BizClawConfig>,
+    /// Modification time and size at the last check.
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl ConfigWatcher {
+    /// Watch `path`, which `config` was loaded from.
+    pub fn new(path: impl Into<PathBuf>, config: BizClawConfig) -> Self {
+        let path = path.into();
+        Self {
+            stamp: stamp(&path),
+            path,
+            interval: DEFAULT_INTERVAL,
+            current: Arc::new(config),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Load the file again if it was modified since the last check.
+    /// `Ok(None)` when it wasn't, or when no setting changed.
+    pub fn poll(&mut self) -> Result<Option<ConfigReload>> {
+        let stamp = stamp(&self.path);
+        if stamp == self.stamp {
+            return Ok(None);
+        }
+        self.stamp = stamp;
+        // A deleted file keeps the running config.
+        if stamp.is_none() {
+            return Ok(None);
+        }
+        self.reload()
+    }
+
+    /// Load the file again whether or not it was modified, as SIGHUP asks.
+    /// `Ok(None)` when no setting changed.
+    pub fn reload(&mut self) -> Result<Option<ConfigReload>> {
+        self.stamp = stamp(&self.path);
+        let config = BizClawConfig::load_from(&self.path)?;
+        let change = diff(&self.current, &config);
+        if change.is_empty() {
+            return Ok(None);
+        }
+        self.current = Arc::new(config);
+        Ok(Some(ConfigReload {
+            config: self.current.clone(),
+            change,
+        }))
+    }
+
+    /// Poll in the background for the life of the process, and reload on
+    /// SIGHUP. Subscribe to the returned sender to receive reloads.
+    pub fn spawn(mut self) -> broadcast::Sender<ConfigReload> {
+        let (tx, _) = broadcast::channel(16);
+        let sender = tx.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut hangups = Hangups::new();
+            loop {
+                let result = tokio::select! {
+                    _ = ticker.tick() => self.poll(),
+                    _ = hangups.recv() => {
+                        tracing::info!("SIGHUP received, reloading {}", self.path.display());
+                        crate::daemon::notify("RELOADING=1");
+                        let result = self.reload();
+                        crate::daemon::notify("READY=1");
+                        result
+                    }
+                };
+                match result {
+                    Ok(Some(reload)) => {
+                        tracing::info!("🔄 Config reloaded — {}", reload.change);
+                        if !reload.change.restart_required.is_empty() {
+                            tracing::warn!(
+                                "Restart BizClaw to apply: {}",
+                                reload.change.restart_required.join(", ")
+                            );
+                        }
+                        let _ = tx.send(reload);
+                    }
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("Config change not applied: {e}"),
+                }
+            }
+        });
+        sender
+    }
+}
+
+/// SIGHUP deliveries; never resolves where there are none.
+struct Hangups {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl Hangups {
+    fn new() -> Self {
+        Self {
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .map_err(|e| tracing::warn!("Can't listen for SIGHUP: {e}"))
+                .ok(),
+        }
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = self.signal.as_mut() {
+            signal.recv().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let old = BizClawConfig::default();
+        let mut new = old.clone();
+        new.default_temperature = 0.2;
+        new.language.default = "vi".into();
+        new.gateway.port = 8080;
+        new.llm.model = "gpt-4o".into();
+        let change = diff(&old, &new);
+        assert_eq!(change.applied, ["default_temperature", "language.default"]);
+        assert_eq!(change.restart_required, ["LLM.model", "gateway.port"]);
+        assert!(diff(&old, &old.clone()).is_empty());
+
+        let mut old = BizClawConfig::default();
+        old.channel.zalo = Some(Default::default());
+        let mut new = old.clone();
+        let zalo = new.channel.zalo.as_mut().unwrap();
+        zalo.allowlist.user_ids = vec!["u1".into()];
+        zalo.rate_limit.max_messages_per_minute = 5;
+        zalo.rate_limit.cooldown_on_error_ms = 1;
+        let change = diff(&old, &new);
+        assert_eq!(
+            change.applied,
+            [
+                "channel.zalo.allowlist.user_ids",
+                "channel.zalo.rate_limit.max_messages_per_minute"
+            ]
+        );
+        assert_eq!(
+            change.restart_required,
+            ["channel.zalo.rate_limit.cooldown_on_error_ms"]
+        );
+    }
+
+    #[test]
+    fn test_poll() {
+        let path =
+            std::env::temp_dir().join(format!("bizclaw-reload-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "default_temperature = 0.7\n").unwrap();
+        let config = BizClawConfig::load_from(&path).unwrap();
+        let mut watcher = ConfigWatcher::new(&path, config);
+        assert!(watcher.poll().unwrap().is_none());
+
+        std::fs::write(
+            &path,
+            "default_temperature = 0.25\n[gateway]\nport = 9000\n",
+        )
+        .unwrap();
+        let reload = watcher.poll().unwrap().unwrap();
+        assert_eq!(reload.config.default_temperature, 0.25);
+        assert_eq!(reload.change.applied, ["default_temperature"]);
+        assert_eq!(reload.change.restart_required, ["gateway.port"]);
+
+        // An invalid edit is rejected and the running config kept.
+        std::fs::write(&path, "default_temperature = 9.0\n").unwrap();
+        assert!(watcher.poll().is_err());
+        assert!(watcher.poll().unwrap().is_none());
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,386 @@
+//! Telemetry — opt-in, anonymous usage reports that show which model
+//! architectures and quantization formats are worth optimizing first.
+//!
+//! Nothing is collected unless `[telemetry] enabled = true`. A report holds
+//! aggregate counts taken from the [`metrics`](crate::metrics) registry
+//! since the previous one, and only from an allow-list:
+//!
+//! - models loaded, by architecture and main tensor type (`llama/q4_0`),
+//! - prompt and generated tokens, and decode throughput,
+//! - generations by outcome, and errors by [class](crate::BizClawError::code),
+//! - the BizClaw version, OS and CPU architecture.
+//!
+//! Never message content, prompts, model file names, channel or user ids.
+//! Reports carry a random install id kept in the spool directory; deleting
+//! it starts a new one.
+//!
+//! Each `interval_hours` the report is written to the spool directory as
+//! JSON, where it can be read before it leaves the machine. With an
+//! `endpoint`, spooled reports are then POSTed and deleted once accepted;
+//! ones that fail stay for the next round.
+
+use crate::config::TelemetryConfig;
+use crate::error::{BizClawError, Result};
+use crate::metrics::{self, Sample, Value};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Reports kept in the spool when none can be sent; older ones are dropped.
+const MAX_SPOOLED: usize = 30;
+
+/// Counts since the process started, read from the metrics registry.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Totals {
+    models: BTreeMap<String, u64>,
+    prompt_tokens: u64,
+    generated_tokens: u64,
+    decode_seconds: f64,
+    generations: BTreeMap<String, u64>,
+    errors: BTreeMap<String, u64>,
+}
+
+fn label<'a>(sample: &'a Sample, key: &str) -> Option<&'a str> {
+    sample
+        .labels
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+impl Totals {
+    fn from_samples(samples: &[Sample]) -> Self {
+        let mut totals = Self::default();
+        for sample in samples {
+            match (sample.name.as_str(), &sample.value) {
+                ("bizclaw_brain_models_loaded_total", Value::Counter(n)) => {
+                    let arch = label(sample, "arch").unwrap_or("unknown");
+                    let quant = label(sample, "quant").unwrap_or("unknown");
+                    *totals.models.entry(format!("{arch}/{quant}")).or_default() += n;
+                }
+                ("bizclaw_brain_prompt_tokens_total", Value::Counter(n)) => {
+                    totals.prompt_tokens += n
+                }
+                ("bizclaw_brain_generated_tokens_total", Value::Counter(n)) => {
+                    totals.generated_tokens += n
+                }
+                ("bizclaw_brain_decode_seconds", Value::Histogram(h)) => {
+                    totals.decode_seconds += h.sum
+                }
+                // Summed over channels: channel names are the operator's.
+                ("bizclaw_generations_total", Value::Counter(n)) => {
+                    let status = label(sample, "status").unwrap_or("unknown");
+                    *totals.generations.entry(status.to_string()).or_default() += n;
+                }
+                ("bizclaw_errors_total", Value::Counter(n)) => {
+                    let class = label(sample, "class").unwrap_or("unknown");
+                    *totals.errors.entry(class.to_string()).or_default() += n;
+                }
+                _ => {}
+            }
+        }
+        totals
+    }
+}
+
+/// Counts in `now` that aren't in `before`, dropping the zeros.
+fn delta(now: &BTreeMap<String, u64>, before: &BTreeMap<String, u64>) -> BTreeMap<String, u64> {
+    now.iter()
+        .map(|(k, n)| (k.clone(), n.saturating_sub(*before.get(k).unwrap_or(&0))))
+        .filter(|(_, n)| *n > 0)
+        .collect()
+}
+
+/// One report, as spooled and sent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub install_id: String,
+    pub version: String,
+    pub os: String,
+    pub cpu: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    /// Loads by `architecture/tensor type`.
+    pub models: BTreeMap<String, u64>,
+    pub prompt_tokens: u64,
+    pub generated_tokens: u64,
+    /// Generated tokens per second of decoding, if any were generated.
+    pub tokens_per_second: Option<f64>,
+    /// Generations by outcome, `ok` or `error`.
+    pub generations: BTreeMap<String, u64>,
+    /// Errors by class, e.g. `gguf_parse_error`.
+    pub errors: BTreeMap<String, u64>,
+}
+
+impl Report {
+    fn is_empty(&self) -> bool {
+        self.models.is_empty()
+            && self.generated_tokens == 0
+            && self.generations.is_empty()
+            && self.errors.is_empty()
+    }
+}
+
+/// Builds reports, spools them and sends the spool.
+pub struct Reporter {
+    dir: PathBuf,
+    endpoint: String,
+    install_id: String,
+    last: Totals,
+    since: DateTime<Utc>,
+}
+
+impl Reporter {
+    /// A reporter spooling to `config`'s directory, creating it and the
+    /// install id.
+    pub fn new(config: &TelemetryConfig) -> Result<Self> {
+        let dir = config.dir_path();
+        std::fs::create_dir_all(dir.join("spool"))?;
+        Ok(Self {
+            install_id: install_id(&dir)?,
+            dir,
+            endpoint: config.endpoint.clone(),
+            last: Totals::default(),
+            since: Utc::now(),
+        })
+    }
+
+    fn spool(&self) -> PathBuf {
+        self.dir.join("spool")
+    }
+
+    /// The report for the counts in `samples` since the previous call.
+    fn report(&mut self, samples: &[Sample], now: DateTime<Utc>) -> Report {
+        let totals = Totals::from_samples(samples);
+        let generated_tokens = totals
+            .generated_tokens
+            .saturating_sub(self.last.generated_tokens);
+        let decode_seconds = totals.decode_seconds - self.last.decode_seconds;
+        let report = Report {
+            install_id: self.install_id.clone(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            cpu: std::env::consts::ARCH.to_string(),
+            period_start: self.since,
+            period_end: now,
+            models: delta(&totals.models, &self.last.models),
+            prompt_tokens: totals.prompt_tokens.saturating_sub(self.last.prompt_tokens),
+            generated_tokens,
+            tokens_per_second: (generated_tokens > 0 && decode_seconds > 0.0)
+                .then(|| generated_tokens as f64 / decode_seconds),
+            generations: delta(&totals.generations, &self.last.generations),
+            errors: delta(&totals.errors, &self.last.errors),
+        };
+        self.last = totals;
+        self.since = now;
+        report
+    }
+
+    /// Spool the report for `samples`, unless nothing happened since the
+    /// last one. Returns its file.
+    pub fn collect(&mut self, samples: &[Sample]) -> Result<Option<PathBuf>> {
+        let now = Utc::now();
+        let report = self.report(samples, now);
+        if report.is_empty() {
+            return Ok(None);
+        }
+        let path = self
+            .spool()
+            .join(format!("{}.json", now.format("%Y%m%dT%H%M%S%.3fZ")));
+        std::fs::write(&path, serde_json::to_vec_pretty(&report)?)?;
+
+        let pending = self.pending()?;
+        if pending.len() > MAX_SPOOLED {
+            for old in &pending[..pending.len() - MAX_SPOOLED] {
+                std::fs::remove_file(old).ok();
+            }
+        }
+        Ok(Some(path))
+    }
+
+    /// Spooled reports, oldest first.
+    pub fn pending(&self) -> Result<Vec<PathBuf>> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(self.spool())?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    /// POST the spooled reports to the endpoint, deleting each once it's
+    /// accepted. Stops at the first failure. Returns how many were sent.
+    pub async fn send(&self, client: &reqwest::Client) -> Result<usize> {
+        if self.endpoint.is_empty() {
+            return Ok(0);
+        }
+        let mut sent = 0;
+        for path in self.pending()? {
+            let body = std::fs::read(&path)?;
+            client
+                .post(&self.endpoint)
+                .header("content-type", "application/json")
+                .body(body)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| BizClawError::Http(format!("Telemetry upload failed: {e}")))?;
+            std::fs::remove_file(&path)?;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+}
+
+/// The install id in `dir`, created on first use.
+fn install_id(dir: &Path) -> Result<String> {
+    let path = dir.join("id");
+    match std::fs::read_to_string(&path) {
+        Ok(id) if !id.trim().is_empty() => Ok(id.trim().to_string()),
+        _ => {
+            let id = uuid::Uuid::new_v4().to_string();
+            std::fs::write(&path, &id)?;
+            Ok(id)
+        }
+    }
+}
+
+/// Start reporting every `interval_hours` on the [`global`](metrics::global)
+/// registry, if `[telemetry]` is enabled.
+pub fn start_from_config(config: &TelemetryConfig) -> Option<tokio::task::JoinHandle<()>> {
+    if !config.enabled {
+        return None;
+    }
+    let mut reporter = match Reporter::new(config) {
+        Ok(reporter) => reporter,
+        Err(e) => {
+            tracing::warn!("Telemetry disabled: {e}");
+            return None;
+        }
+    };
+    tracing::info!(
+        "📮 Anonymous telemetry on: reports spool to {}{}",
+        reporter.spool().display(),
+        if config.endpoint.is_empty() {
+            String::new()
+        } else {
+            format!(" and go to {}", config.endpoint)
+        }
+    );
+    let interval = Duration::from_secs(config.interval_hours * 3600);
+    Some(tokio::spawn(async move {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .unwrap_or_default();
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        // The first tick fires at once; there's nothing to report yet.
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if let Err(e) = reporter.collect(&metrics::global().snapshot()) {
+                tracing::warn!("Telemetry report failed: {e}");
+            }
+            match reporter.send(&client).await {
+                Ok(0) => {}
+                Ok(sent) => tracing::debug!("Sent {sent} telemetry reports"),
+                Err(e) => tracing::debug!("{e}"),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+
+    fn reporter() -> Reporter {
+        let dir = std::env::temp_dir().join(format!("bizclaw-telemetry-{}", uuid::Uuid::new_v4()));
+        Reporter::new(&TelemetryConfig {
+            enabled: true,
+            dir: dir.to_string_lossy().into_owned(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_report_is_a_delta_of_allowed_metrics() {
+        let metrics = Metrics::new();
+        let mut reporter = reporter();
+        let now = Utc::now();
+
+        metrics
+            .counter(
+                "bizclaw_brain_models_loaded_total",
+                &[("arch", "llama"), ("quant", "q4_0")],
+            )
+            .inc();
+        metrics
+            .counter("bizclaw_brain_generated_tokens_total", &[])
+            .add(100);
+        metrics
+            .histogram("bizclaw_brain_decode_seconds", &[])
+            .observe(4.0);
+        for channel in ["telegram", "zalo"] {
+            metrics
+                .counter(
+                    "bizclaw_generations_total",
+                    &[("channel", channel), ("status", "ok")],
+                )
+                .inc();
+        }
+        metrics
+            .counter("bizclaw_errors_total", &[("class", "gguf_parse_error")])
+            .inc();
+        // Not on the allow-list.
+        metrics
+            .counter("bizclaw_messages_received_total", &[("channel", "zalo")])
+            .inc();
+
+        let report = reporter.report(&metrics.snapshot(), now);
+        assert_eq!(report.models["llama/q4_0"], 1);
+        assert_eq!(report.generated_tokens, 100);
+        assert_eq!(report.tokens_per_second, Some(25.0));
+        assert_eq!(report.generations["ok"], 2);
+        assert_eq!(report.errors["gguf_parse_error"], 1);
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(!json.contains("zalo") && !json.contains("telegram"));
+
+        // The next report only has what happened since.
+        metrics
+            .counter("bizclaw_brain_generated_tokens_total", &[])
+            .add(10);
+        let report = reporter.report(&metrics.snapshot(), now);
+        assert!(report.models.is_empty() && report.errors.is_empty());
+        assert_eq!(report.generated_tokens, 10);
+        assert_eq!(report.tokens_per_second, None);
+
+        let report = reporter.report(&metrics.snapshot(), now);
+        assert!(report.is_empty());
+        std::fs::remove_dir_all(&reporter.dir).ok();
+    }
+
+    #[test]
+    fn test_spool() {
+        let metrics = Metrics::new();
+        let mut reporter = reporter();
+        let id = reporter.install_id.clone();
+        assert_eq!(install_id(&reporter.dir).unwrap(), id);
+
+        assert_eq!(reporter.collect(&metrics.snapshot()).unwrap(), None);
+        metrics
+            .counter("bizclaw_errors_total", &[("class", "timeout")])
+            .inc();
+        let path = reporter.collect(&metrics.snapshot()).unwrap().unwrap();
+        assert_eq!(reporter.pending().unwrap(), vec![path.clone()]);
+        let report: Report = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(report.install_id, id);
+        assert_eq!(report.errors["timeout"], 1);
+        std::fs::remove_dir_all(&reporter.dir).ok();
+    }
+}
@@ -3,8 +3,8 @@
 use async_trait::async_trait;
 use tokio_stream::Stream;
 
-use crate::error::Result;
-use crate::types::{IncomingMessage, OutgoingMessage};
+use crate::error::{BizClawError, Result};
+use crate::types::{IncomingMessage, OutgoingMessage, ProgressEvent};
 
 /// Channel trait — every communication interface implements this.
 #[async_trait]
@@ -28,9 +28,55 @@ pub trait Channel: Send + Sync {
     /// Send a message to a thread.
     async fn send(&self, message: OutgoingMessage) -> Result<()>;
 
+    /// Send a message and return its platform id, if the platform reports one.
+    /// The id is what [`edit`](Self::edit) and [`delete`](Self::delete) take.
+    async fn send_with_id(&self, message: OutgoingMessage) -> Result<Option<String>> {
+        self.send(message).await.map(|_| None)
+    }
+
+    /// Whether sent messages can be edited (streaming replies via edits).
+    fn supports_edit(&self) -> bool {
+        false
+    }
+
+    /// Replace the text of a previously sent message.
+    async fn edit(&self, thread_id: &str, message_id: &str, content: &str) -> Result<()> {
+        let _ = (thread_id, message_id, content);
+        Err(BizClawError::Channel(format!(
+            "{} does not support editing messages",
+            self.name()
+        )))
+    }
+
+    /// Delete a message.
+    async fn delete(&self, thread_id: &str, message_id: &str) -> Result<()> {
+        let _ = (thread_id, message_id);
+        Err(BizClawError::Channel(format!(
+            "{} does not support deleting messages",
+            self.name()
+        )))
+    }
+
     /// Send a typing indicator.
     async fn send_typing(&self, thread_id: &str) -> Result<()> {
         let _ = thread_id;
         Ok(()) // Default no-op
     }
+
+    /// Show the bot as typing in a thread.
+    /// Platforms expire the state after a few seconds, so callers refresh it
+    /// while work is ongoing. Defaults to `send_typing`.
+    async fn indicate_typing(&self, thread_id: &str) -> Result<()> {
+        self.send_typing(thread_id).await
+    }
+
+    /// Report generation progress for a thread.
+    /// Default: show typing while active, nothing once done.
+    async fn on_progress(&self, thread_id: &str, event: &ProgressEvent) -> Result<()> {
+        if event.is_active() {
+            self.indicate_typing(thread_id).await
+        } else {
+            Ok(())
+        }
+    }
 }
@@ -1,9 +1,17 @@
 //! LLM Provider trait — swappable AI backends.
+//!
+//! The local brain and every cloud API implement [`Provider`], so the agent
+//! and gateway switch between them by configuration alone.
 
 use async_trait::async_trait;
+use std::pin::Pin;
 
 use crate::error::Result;
-use crate::types::{Message, ModelInfo, ProviderResponse, ToolDefinition};
+use crate::types::{Message, ModelInfo, ProviderResponse, StreamChunk, ToolDefinition};
+
+/// Chunks of a streamed response, ending after the chunk with a
+/// `finish_reason` or at the first error.
+pub type ProviderStream = Pin<Box<dyn futures::Stream<Item = Result<StreamChunk>> + Send>>;
 
 /// Configuration for generation parameters.
 #[derive(Debug, Clone)]
@@ -41,9 +49,34 @@ pub trait Provider: Send + Sync {
         params: &GenerateParams,
     ) -> Result<ProviderResponse>;
 
+    /// Stream a chat completion as it is generated. Providers without native
+    /// streaming return the whole response as one chunk.
+    async fn stream(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderStream> {
+        let response = self.chat(messages, tools, params).await?;
+        let chunk = StreamChunk::from(response);
+        Ok(Box::pin(futures::stream::once(async move { Ok(chunk) })))
+    }
+
     /// List available models for this provider.
     async fn list_models(&self) -> Result<Vec<ModelInfo>>;
 
     /// Check if the provider is available and configured.
     async fn health_check(&self) -> Result<bool>;
+
+    /// Embed each text as a vector, for semantic search.
+    async fn embed(&self, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Err(crate::error::BizClawError::Provider(format!(
+            "{} does not support embeddings",
+            self.name()
+        )))
+    }
+
+    /// Take up the settings of a reloaded config that the provider holds
+    /// itself rather than reading from each request's [`GenerateParams`].
+    async fn reload(&self, _config: &crate::config::BizClawConfig) {}
 }
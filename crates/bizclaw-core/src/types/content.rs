@@ -0,0 +1,121 @@
+//! Structured outgoing content — text, markdown, attachments, buttons, embeds.
+//!
+//! Channels render these blocks as richly as they can and degrade the rest
+//! (Discord embeds → Telegram formatted text → SMS plain text).
+
+use serde::{Deserialize, Serialize};
+
+/// One block of structured message content.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentBlock {
+    /// Plain text, never interpreted as markup.
+    Text { text: String },
+    /// CommonMark-style markdown (bold, italic, code, links).
+    Markdown { markdown: String },
+    /// File or media reference.
+    Attachment(Attachment),
+    /// Buttons / quick replies shown under the message.
+    Buttons { buttons: Vec<Button> },
+    /// Rich card (Discord-style embed).
+    Embed(Embed),
+}
+
+/// File or media attached to a message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+}
+
+/// A button or quick reply. Link buttons carry `url`; reply buttons carry `payload`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Button {
+    pub label: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl Button {
+    /// Quick-reply button — sends `payload` (or the label) back when pressed.
+    pub fn reply(label: impl Into<String>, payload: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            payload: Some(payload.into()),
+            url: None,
+        }
+    }
+
+    /// Link button.
+    pub fn link(label: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            payload: None,
+            url: Some(url.into()),
+        }
+    }
+}
+
+/// Rich card content.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Embed {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// RGB color, e.g. `0x5865F2`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<EmbedField>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub footer: Option<String>,
+}
+
+/// Name/value row inside an embed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmbedField {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub inline: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_json_is_tagged() {
+        let block = ContentBlock::Markdown {
+            markdown: "**hi**".into(),
+        };
+        let json = serde_json::to_value(&block).unwrap();
+        assert_eq!(json["type"], "markdown");
+        assert_eq!(json["markdown"], "**hi**");
+
+        let att: ContentBlock =
+            serde_json::from_str(r#"{"type":"attachment","url":"https://x/y.png"}"#).unwrap();
+        assert!(matches!(att, ContentBlock::Attachment(a) if a.url == "https://x/y.png"));
+    }
+
+    #[test]
+    fn test_button_constructors() {
+        let b = Button::reply("Yes", "confirm");
+        assert_eq!(b.payload.as_deref(), Some("confirm"));
+        assert!(b.url.is_none());
+        let l = Button::link("Docs", "https://bizclaw.vn");
+        assert_eq!(l.url.as_deref(), Some("https://bizclaw.vn"));
+    }
+}
@@ -0,0 +1,80 @@
+//! Media attached to incoming messages.
+//!
+//! Channels fill in what the platform tells them (`source`, maybe a file name
+//! or MIME type); the media pipeline downloads the file into a local
+//! content-addressed store and completes `sha256`, `size` and `local_path`.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Broad media category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Image,
+    Audio,
+    Video,
+    Document,
+}
+
+impl MediaKind {
+    /// Category for a MIME type (`application/*` and unknown types are documents).
+    pub fn from_mime(mime: &str) -> Self {
+        match mime.split('/').next().unwrap_or("") {
+            "image" => Self::Image,
+            "audio" => Self::Audio,
+            "video" => Self::Video,
+            _ => Self::Document,
+        }
+    }
+}
+
+/// Reference to a media file, remote until stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MediaRef {
+    pub kind: MediaKind,
+    /// Where the channel says the file lives — a URL or a `<platform>-file:<id>` reference.
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Hex SHA-256 of the content, set once stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Path inside the local media store, set once stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_path: Option<PathBuf>,
+}
+
+impl MediaRef {
+    /// A not-yet-downloaded reference.
+    pub fn remote(kind: MediaKind, source: impl Into<String>) -> Self {
+        Self {
+            kind,
+            source: source.into(),
+            filename: None,
+            mime_type: None,
+            size: None,
+            sha256: None,
+            local_path: None,
+        }
+    }
+
+    pub fn with_filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    pub fn with_mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    /// Whether the file has been downloaded into the local store.
+    pub fn is_stored(&self) -> bool {
+        self.local_path.is_some()
+    }
+}
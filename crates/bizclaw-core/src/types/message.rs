@@ -89,15 +89,54 @@ pub struct IncomingMessage {
     pub thread_type: ThreadType,
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub reply_to: Option<String>,
+    /// Platform message/update id — used to drop redelivered duplicates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// Attached images, voice notes, files — see [`super::MediaRef`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub media: Vec<super::MediaRef>,
 }
 
 /// Outgoing message to a channel.
+///
+/// `content` is always a plain-text/markdown fallback. `blocks` optionally
+/// carries structured content that channels render as richly as they support.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutgoingMessage {
     pub thread_id: String,
     pub content: String,
     pub thread_type: ThreadType,
     pub reply_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<super::ContentBlock>,
+}
+
+impl OutgoingMessage {
+    /// Plain message with no structured blocks.
+    pub fn text(
+        thread_id: impl Into<String>,
+        content: impl Into<String>,
+        thread_type: ThreadType,
+    ) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            content: content.into(),
+            thread_type,
+            reply_to: None,
+            blocks: vec![],
+        }
+    }
+
+    /// Attach structured content blocks.
+    pub fn with_blocks(mut self, blocks: Vec<super::ContentBlock>) -> Self {
+        self.blocks = blocks;
+        self
+    }
+
+    /// Whether the message carries structured content.
+    pub fn is_rich(&self) -> bool {
+        !self.blocks.is_empty()
+    }
 }
 
 /// Thread type for channel messages.
@@ -108,6 +147,27 @@ pub enum ThreadType {
     Group,
 }
 
+/// What the agent is doing while a reply is being generated.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// Waiting on the model.
+    Thinking,
+    /// Running a tool.
+    ToolCall { name: String },
+    /// Reply text is being produced.
+    Writing,
+    /// Generation finished (successfully or not).
+    Done,
+}
+
+impl ProgressEvent {
+    /// Whether the user should see the bot as busy during this stage.
+    pub fn is_active(&self) -> bool {
+        !matches!(self, Self::Done)
+    }
+}
+
 /// Response from an LLM provider.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderResponse {
@@ -138,13 +198,54 @@ impl ProviderResponse {
 }
 
 /// Token usage statistics.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
 }
 
+/// Sums the usage of several requests, e.g. the rounds of a tool loop.
+impl std::ops::AddAssign<&Usage> for Usage {
+    fn add_assign(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+/// A piece of a streamed response. Text arrives as deltas; tool calls,
+/// usage and the finish reason arrive whole, usually on the last chunk.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamChunk {
+    /// Text generated since the previous chunk.
+    pub delta: String,
+    pub tool_calls: Vec<super::ToolCall>,
+    pub finish_reason: Option<String>,
+    pub usage: Option<Usage>,
+}
+
+impl StreamChunk {
+    pub fn text(delta: impl Into<String>) -> Self {
+        Self {
+            delta: delta.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<ProviderResponse> for StreamChunk {
+    /// The whole response as a single, final chunk.
+    fn from(response: ProviderResponse) -> Self {
+        Self {
+            delta: response.content.unwrap_or_default(),
+            tool_calls: response.tool_calls,
+            finish_reason: response.finish_reason.or_else(|| Some("stop".into())),
+            usage: response.usage,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -179,10 +280,34 @@ mod tests {
         assert_eq!(parsed.role, Role::User);
     }
 
+    #[test]
+    fn test_outgoing_blocks_default_empty() {
+        let json = r#"{"thread_id":"t","content":"hi","thread_type":"direct","reply_to":null}"#;
+        let msg: OutgoingMessage = serde_json::from_str(json).unwrap();
+        assert!(!msg.is_rich());
+        assert!(!serde_json::to_string(&msg).unwrap().contains("blocks"));
+
+        let rich = OutgoingMessage::text("t", "hi", ThreadType::Direct).with_blocks(vec![
+            crate::types::ContentBlock::Text { text: "hi".into() },
+        ]);
+        assert!(rich.is_rich());
+    }
+
     #[test]
     fn test_provider_response() {
         let resp = ProviderResponse::text("hello");
         assert_eq!(resp.content, Some("hello".into()));
         assert!(resp.tool_calls.is_empty());
     }
+
+    #[test]
+    fn test_stream_chunk_from_response() {
+        let chunk = StreamChunk::from(ProviderResponse::text("hello"));
+        assert_eq!(chunk.delta, "hello");
+        assert_eq!(chunk.finish_reason.as_deref(), Some("stop"));
+
+        let chunk = StreamChunk::text("he");
+        assert_eq!(chunk.delta, "he");
+        assert!(chunk.finish_reason.is_none());
+    }
 }
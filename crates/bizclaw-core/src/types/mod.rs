@@ -1,10 +1,14 @@
 //! BizClaw message types, tool calls, model info, and orchestration primitives.
 
+pub mod content;
+pub mod media;
 pub mod message;
 pub mod model;
 pub mod orchestration;
 pub mod tool_call;
 
+pub use content::*;
+pub use media::*;
 pub use message::*;
 pub use model::*;
 pub use orchestration::*;
@@ -0,0 +1,112 @@
+/* BizClaw brain engine C API. Generated by cbindgen; do not edit. */
+
+#ifndef BIZCLAW_H
+#define BIZCLAW_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Result of an API call.
+typedef enum BizclawStatus {
+  BIZCLAW_STATUS_OK = 0,
+  // A null pointer, or a string that isn't UTF-8.
+  BIZCLAW_STATUS_INVALID_ARGUMENT = 1,
+  // The model couldn't be loaded, or none is loaded.
+  BIZCLAW_STATUS_MODEL = 2,
+  // Generation or embedding failed.
+  BIZCLAW_STATUS_INFERENCE = 3,
+  // The engine panicked; it shouldn't be used again.
+  BIZCLAW_STATUS_PANIC = 4,
+} BizclawStatus;
+
+// An inference engine. Opaque to C.
+typedef struct BizclawEngine BizclawEngine;
+
+// Engine settings; pass null to `bizclaw_engine_new` for the defaults.
+typedef struct BizclawConfig {
+  uint32_t threads;
+  // Positions the KV cache holds, prompt and output together.
+  uint32_t context_length;
+  float temperature;
+  float top_p;
+} BizclawConfig;
+
+// Called with each generated piece of text, NUL-terminated and valid only
+// during the call. Return false to stop generating.
+typedef bool (*BizclawTokenCallback)(const char *piece, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The library version, e.g. `"0.2.0"`. Static; don't free it.
+const char *bizclaw_version(void);
+
+// The last error on this thread, or null. Valid until the next failing
+// call on this thread; don't free it.
+const char *bizclaw_last_error(void);
+
+// Create an engine with no model loaded. Free it with `bizclaw_engine_free`.
+//
+// # Safety
+// `config` must be null or point to a valid `BizclawConfig`.
+BizclawEngine *bizclaw_engine_new(const BizclawConfig *config);
+
+// Free an engine and its model. Null is ignored.
+//
+// # Safety
+// `engine` must be null or come from `bizclaw_engine_new`, and not be
+// used afterwards.
+void bizclaw_engine_free(BizclawEngine *engine);
+
+// Load a GGUF model, replacing any loaded one.
+//
+// # Safety
+// `engine` must come from `bizclaw_engine_new`; `path` must be a
+// NUL-terminated string.
+BizclawStatus bizclaw_engine_load_model(BizclawEngine *engine, const char *path);
+
+// Generate up to `max_tokens` tokens after `prompt`. The prompt is sent
+// as is: apply the model's chat template first. `callback`, if not null,
+// gets each piece as it is sampled. On success `*out` holds the whole
+// output, to be freed with `bizclaw_string_free`.
+//
+// # Safety
+// `engine` must come from `bizclaw_engine_new`, `prompt` must be a
+// NUL-terminated string and `out` must be valid for writes.
+BizclawStatus bizclaw_generate(BizclawEngine *engine,
+                               const char *prompt,
+                               uint32_t max_tokens,
+                               BizclawTokenCallback callback,
+                               void *user_data,
+                               char **out);
+
+// Embed `text` as an L2-normalized vector. On success `*out` points to
+// `*len` floats, to be freed with `bizclaw_embedding_free`.
+//
+// # Safety
+// `engine` must come from `bizclaw_engine_new`, `text` must be a
+// NUL-terminated string, and `out` and `len` must be valid for writes.
+BizclawStatus bizclaw_embed(BizclawEngine *engine, const char *text, float **out, size_t *len);
+
+// Free a string returned by the library. Null is ignored.
+//
+// # Safety
+// `s` must be null or come from this library, and not be used afterwards.
+void bizclaw_string_free(char *s);
+
+// Free an embedding returned by `bizclaw_embed`. Null is ignored.
+//
+// # Safety
+// `data` and `len` must be what `bizclaw_embed` returned, and the
+// embedding must not be used afterwards.
+void bizclaw_embedding_free(float *data, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BIZCLAW_H */
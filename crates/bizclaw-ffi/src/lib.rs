@@ -0,0 +1,339 @@
+//! # BizClaw FFI
+//! A C API for the brain engine, so applications in other languages can
+//! run GGUF models in-process. The header is `include/bizclaw.h`.
+//!
+//! Every call returns a [`BizclawStatus`]; on failure,
+//! [`bizclaw_last_error`] describes what went wrong on the calling thread.
+//! Strings and embeddings the library returns are freed with
+//! [`bizclaw_string_free`] and [`bizclaw_embedding_free`]. An engine is not
+//! thread-safe: use one per thread, or serialize calls to it.
+
+use bizclaw_brain::{BrainConfig, BrainEngine};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::Path;
+
+/// Result of an API call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BizclawStatus {
+    Ok = 0,
+    /// A null pointer, or a string that isn't UTF-8.
+    InvalidArgument = 1,
+    /// The model couldn't be loaded, or none is loaded.
+    Model = 2,
+    /// Generation or embedding failed.
+    Inference = 3,
+    /// The engine panicked; it shouldn't be used again.
+    Panic = 4,
+}
+
+/// Engine settings; pass null to `bizclaw_engine_new` for the defaults.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BizclawConfig {
+    pub threads: u32,
+    /// Positions the KV cache holds, prompt and output together.
+    pub context_length: u32,
+    pub temperature: f32,
+    pub top_p: f32,
+}
+
+/// An inference engine. Opaque to C.
+pub struct BizclawEngine {
+    engine: BrainEngine,
+}
+
+/// Called with each generated piece of text, NUL-terminated and valid only
+/// during the call. Return false to stop generating.
+pub type BizclawTokenCallback =
+    Option<unsafe extern "C" fn(piece: *const c_char, user_data: *mut c_void) -> bool>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: impl Into<String>) {
+    let message = message.into().replace('\0', " ");
+    LAST_ERROR.with(|e| *e.borrow_mut() = CString::new(message).ok());
+}
+
+/// Run `f`, turning a panic into `BizclawStatus::Panic`.
+fn guard(f: impl FnOnce() -> BizclawStatus) -> BizclawStatus {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".into());
+        set_error(format!("Engine panicked: {message}"));
+        BizclawStatus::Panic
+    })
+}
+
+/// Borrow a C string as UTF-8, recording an error if it isn't one.
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Option<&'a str> {
+    if ptr.is_null() {
+        set_error(format!("{name} is null"));
+        return None;
+    }
+    match unsafe { CStr::from_ptr(ptr) }.to_str() {
+        Ok(s) => Some(s),
+        Err(_) => {
+            set_error(format!("{name} is not valid UTF-8"));
+            None
+        }
+    }
+}
+
+/// The library version, e.g. `"0.2.0"`. Static; don't free it.
+#[unsafe(no_mangle)]
+pub extern "C" fn bizclaw_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// The last error on this thread, or null. Valid until the next failing
+/// call on this thread; don't free it.
+#[unsafe(no_mangle)]
+pub extern "C" fn bizclaw_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(std::ptr::null(), |s| s.as_ptr()))
+}
+
+/// Create an engine with no model loaded. Free it with `bizclaw_engine_free`.
+///
+/// # Safety
+/// `config` must be null or point to a valid `BizclawConfig`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bizclaw_engine_new(config: *const BizclawConfig) -> *mut BizclawEngine {
+    let mut brain = BrainConfig::default();
+    if let Some(config) = unsafe { config.as_ref() } {
+        brain.threads = config.threads;
+        brain.context_length = config.context_length;
+        brain.temperature = config.temperature;
+        brain.top_p = config.top_p;
+    }
+    Box::into_raw(Box::new(BizclawEngine {
+        engine: BrainEngine::new(brain),
+    }))
+}
+
+/// Free an engine and its model. Null is ignored.
+///
+/// # Safety
+/// `engine` must be null or come from `bizclaw_engine_new`, and not be
+/// used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bizclaw_engine_free(engine: *mut BizclawEngine) {
+    if !engine.is_null() {
+        drop(unsafe { Box::from_raw(engine) });
+    }
+}
+
+/// Load a GGUF model, replacing any loaded one.
+///
+/// # Safety
+/// `engine` must come from `bizclaw_engine_new`; `path` must be a
+/// NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bizclaw_engine_load_model(
+    engine: *mut BizclawEngine,
+    path: *const c_char,
+) -> BizclawStatus {
+    let Some(engine) = (unsafe { engine.as_mut() }) else {
+        set_error("engine is null");
+        return BizclawStatus::InvalidArgument;
+    };
+    let Some(path) = (unsafe { str_arg(path, "path") }) else {
+        return BizclawStatus::InvalidArgument;
+    };
+    guard(|| match engine.engine.load_model(Path::new(path)) {
+        Ok(()) => BizclawStatus::Ok,
+        Err(e) => {
+            set_error(e.to_string());
+            BizclawStatus::Model
+        }
+    })
+}
+
+/// Generate up to `max_tokens` tokens after `prompt`. The prompt is sent
+/// as is: apply the model's chat template first. `callback`, if not null,
+/// gets each piece as it is sampled. On success `*out` holds the whole
+/// output, to be freed with `bizclaw_string_free`.
+///
+/// # Safety
+/// `engine` must come from `bizclaw_engine_new`, `prompt` must be a
+/// NUL-terminated string and `out` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bizclaw_generate(
+    engine: *mut BizclawEngine,
+    prompt: *const c_char,
+    max_tokens: u32,
+    callback: BizclawTokenCallback,
+    user_data: *mut c_void,
+    out: *mut *mut c_char,
+) -> BizclawStatus {
+    let Some(engine) = (unsafe { engine.as_mut() }) else {
+        set_error("engine is null");
+        return BizclawStatus::InvalidArgument;
+    };
+    let Some(prompt) = (unsafe { str_arg(prompt, "prompt") }) else {
+        return BizclawStatus::InvalidArgument;
+    };
+    if out.is_null() {
+        set_error("out is null");
+        return BizclawStatus::InvalidArgument;
+    }
+    guard(|| {
+        let result = engine.engine.generate_stream(prompt, max_tokens, |piece| {
+            let Some(callback) = callback else {
+                return true;
+            };
+            match CString::new(piece) {
+                Ok(piece) => unsafe { callback(piece.as_ptr(), user_data) },
+                Err(_) => true,
+            }
+        });
+        match result {
+            Ok(text) => {
+                unsafe {
+                    *out = CString::new(text.replace('\0', ""))
+                        .unwrap_or_default()
+                        .into_raw()
+                };
+                BizclawStatus::Ok
+            }
+            Err(e) => {
+                let status = if engine.engine.is_loaded() {
+                    BizclawStatus::Inference
+                } else {
+                    BizclawStatus::Model
+                };
+                set_error(e.to_string());
+                status
+            }
+        }
+    })
+}
+
+/// Embed `text` as an L2-normalized vector. On success `*out` points to
+/// `*len` floats, to be freed with `bizclaw_embedding_free`.
+///
+/// # Safety
+/// `engine` must come from `bizclaw_engine_new`, `text` must be a
+/// NUL-terminated string, and `out` and `len` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bizclaw_embed(
+    engine: *mut BizclawEngine,
+    text: *const c_char,
+    out: *mut *mut f32,
+    len: *mut usize,
+) -> BizclawStatus {
+    let Some(engine) = (unsafe { engine.as_mut() }) else {
+        set_error("engine is null");
+        return BizclawStatus::InvalidArgument;
+    };
+    let Some(text) = (unsafe { str_arg(text, "text") }) else {
+        return BizclawStatus::InvalidArgument;
+    };
+    if out.is_null() || len.is_null() {
+        set_error("out or len is null");
+        return BizclawStatus::InvalidArgument;
+    }
+    guard(|| match engine.engine.embed(text) {
+        Ok(embedding) => {
+            let embedding = embedding.into_boxed_slice();
+            unsafe {
+                *len = embedding.len();
+                *out = Box::into_raw(embedding).cast();
+            }
+            BizclawStatus::Ok
+        }
+        Err(e) => {
+            set_error(e.to_string());
+            if engine.engine.is_loaded() {
+                BizclawStatus::Inference
+            } else {
+                BizclawStatus::Model
+            }
+        }
+    })
+}
+
+/// Free a string returned by the library. Null is ignored.
+///
+/// # Safety
+/// `s` must be null or come from this library, and not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bizclaw_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// Free an embedding returned by `bizclaw_embed`. Null is ignored.
+///
+/// # Safety
+/// `data` and `len` must be what `bizclaw_embed` returned, and the
+/// embedding must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bizclaw_embedding_free(data: *mut f32, len: usize) {
+    if !data.is_null() {
+        drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(data, len)) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        let ptr = bizclaw_last_error();
+        assert!(!ptr.is_null());
+        unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_errors_without_a_model() {
+        unsafe {
+            let engine = bizclaw_engine_new(std::ptr::null());
+            let prompt = CString::new("Hello").unwrap();
+            let mut out = std::ptr::null_mut();
+            assert_eq!(
+                bizclaw_generate(
+                    engine,
+                    prompt.as_ptr(),
+                    8,
+                    None,
+                    std::ptr::null_mut(),
+                    &mut out
+                ),
+                BizclawStatus::Model
+            );
+            assert!(out.is_null());
+            assert!(last_error().contains("load_model"));
+
+            let path = CString::new("/nonexistent/model.gguf").unwrap();
+            assert_eq!(
+                bizclaw_engine_load_model(engine, path.as_ptr()),
+                BizclawStatus::Model
+            );
+            assert_eq!(
+                bizclaw_engine_load_model(engine, std::ptr::null()),
+                BizclawStatus::InvalidArgument
+            );
+            assert_eq!(last_error(), "path is null");
+
+            let (mut data, mut len) = (std::ptr::null_mut(), 0);
+            assert_eq!(
+                bizclaw_embed(engine, prompt.as_ptr(), &mut data, &mut len),
+                BizclawStatus::Model
+            );
+            bizclaw_engine_free(engine);
+        }
+        let version = unsafe { CStr::from_ptr(bizclaw_version()) };
+        assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+    }
+}
@@ -0,0 +1,151 @@
+//! In-browser inference for the dashboard chat.
+//!
+//! With `brain.browser_model` set, the chat can download that GGUF file and
+//! run it in a Web Worker on the `bizclaw-wasm` build of the brain, which is
+//! served from `~/.bizclaw/wasm` (the `wasm-pack` output). The gateway only
+//! serves files and renders the chat template; tokens never touch the server.
+
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use bizclaw_core::config::BizClawConfig;
+use bizclaw_core::types::Message;
+use bizclaw_providers::chat_template::ChatTemplate;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+
+use super::server::AppState;
+
+/// Where `wasm-pack build crates/bizclaw-wasm --out-dir` should put the bundle.
+fn bundle_dir() -> std::path::PathBuf {
+    BizClawConfig::home_dir().join("wasm")
+}
+
+fn not_found(message: &str) -> Response {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(Body::from(message.to_string()))
+        .unwrap()
+}
+
+/// Stream a file in 64 KiB chunks, so large models aren't read into memory.
+async fn file_response(path: &std::path::Path, content_type: &str, cache: &str) -> Response {
+    let Ok(file) = tokio::fs::File::open(path).await else {
+        return not_found("Not Found");
+    };
+    let len = file.metadata().await.map(|m| m.len()).unwrap_or(0);
+    let chunks = futures::stream::unfold(file, |mut file| async move {
+        let mut buf = vec![0u8; 64 * 1024];
+        match file.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok::<_, std::io::Error>(buf), file))
+            }
+            Err(e) => Some((Err(e), file)),
+        }
+    });
+    Response::builder()
+        .header("Content-Type", content_type)
+        .header("Content-Length", len)
+        .header("Cache-Control", cache)
+        .body(Body::from_stream(chunks))
+        .unwrap()
+}
+
+/// Whether in-browser inference is available, and what the chat needs to run it.
+pub async fn info(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let Some(model) = state.full_config.lock().unwrap().brain.browser_model_path() else {
+        return Json(json!({
+            "ok": true, "enabled": false,
+            "error": "Set brain.browser_model to a small GGUF model",
+        }));
+    };
+    let Ok(meta) = tokio::fs::metadata(&model).await else {
+        return Json(json!({
+            "ok": true, "enabled": false,
+            "error": format!("Browser model not found: {}", model.display()),
+        }));
+    };
+    if !bundle_dir().join("bizclaw_wasm.js").exists() {
+        return Json(json!({
+            "ok": true, "enabled": false,
+            "error": format!(
+                "WASM bundle not found; run `wasm-pack build crates/bizclaw-wasm --target web --out-dir {}`",
+                bundle_dir().display()
+            ),
+        }));
+    }
+
+    let name = model
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let template = ChatTemplate::detect(&name);
+    Json(json!({
+        "ok": true,
+        "enabled": true,
+        "model": name,
+        "size": meta.len(),
+        "model_url": "/api/v1/browser-brain/model",
+        "bundle_url": "/static/wasm/bizclaw_wasm.js",
+        "template": format!("{template:?}"),
+        "stop": template.stop_sequences(),
+    }))
+}
+
+/// The browser model file.
+pub async fn model(State(state): State<Arc<AppState>>) -> Response {
+    let Some(path) = state.full_config.lock().unwrap().brain.browser_model_path() else {
+        return not_found("No browser model configured");
+    };
+    file_response(&path, "application/octet-stream", "private, max-age=86400").await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PromptRequest {
+    pub messages: Vec<Message>,
+}
+
+/// Render a conversation in the browser model's chat template.
+pub async fn prompt(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<PromptRequest>,
+) -> Json<Value> {
+    let model = state.full_config.lock().unwrap().brain.browser_model_path();
+    let name = model
+        .as_ref()
+        .and_then(|m| m.file_name())
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let template = ChatTemplate::detect(&name);
+    Json(json!({
+        "ok": true,
+        "prompt": template.render(&req.messages),
+        "stop": template.stop_sequences(),
+    }))
+}
+
+/// Files of the `bizclaw-wasm` bundle (/static/wasm/*).
+pub async fn bundle_file(Path(name): Path<String>) -> Response {
+    let valid = !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid {
+        return not_found("Not Found");
+    }
+    let content_type = match name.rsplit('.').next() {
+        Some("wasm") => "application/wasm",
+        Some("js") => "application/javascript; charset=utf-8",
+        Some("ts") => "text/plain; charset=utf-8",
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    };
+    file_response(&bundle_dir().join(name), content_type, "no-cache").await
+}
@@ -39,6 +39,15 @@ pub fn dashboard_static_files() -> HashMap<&'static str, (&'static str, &'static
         ),
     );
 
+    // In-browser inference worker (WebChat)
+    files.insert(
+        "/static/dashboard/brain-worker.js",
+        (
+            include_str!("dashboard/brain-worker.js"),
+            "application/javascript; charset=utf-8",
+        ),
+    );
+
     // i18n
     files.insert(
         "/static/dashboard/i18n/vi.js",
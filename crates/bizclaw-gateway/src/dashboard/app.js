@@ -166,9 +166,85 @@ function ChatPage({ config, lang }) {
   const [sessions, setSessions] = useState([{ id: 'main', name: 'Main Chat', icon: '🤖', time: 'now', count: 0 }]);
   const [activeSession, setActiveSession] = useState('main');
   const [wsInfo, setWsInfo] = useState({});
+  // In-browser inference: the gateway's browser model, run in a worker
+  const [browserBrain, setBrowserBrain] = useState(null);
+  const [local, setLocal] = useState(null);
+  const workerRef = useRef(null);
   const messagesEndRef = useRef(null);
   const inputRef = useRef(null);
 
+  useEffect(() => {
+    authFetch('/api/v1/browser-brain').then(r => r.json()).then(setBrowserBrain).catch(() => {});
+    return () => workerRef.current && workerRef.current.terminate();
+  }, []);
+
+  const toggleLocal = () => {
+    if (workerRef.current) {
+      workerRef.current.terminate();
+      workerRef.current = null;
+      setLocal(null);
+      return;
+    }
+    const worker = new Worker('/static/dashboard/brain-worker.js', { type: 'module' });
+    worker.onmessage = (e) => {
+      const msg = e.data;
+      switch (msg.type) {
+        case 'progress':
+          setLocal({ status: 'loading', progress: msg.total ? Math.round(msg.loaded * 100 / msg.total) : null });
+          break;
+        case 'ready':
+          setLocal({ status: 'ready', info: msg.info });
+          setMessages(prev => [...prev, { type: 'system', content: `${t('chat.local_ready', lang)}\n📦 ${msg.info}${msg.features.length ? ' · ' + msg.features.join(', ') : ''}` }]);
+          break;
+        case 'chunk':
+          setStreamContent(prev => prev + msg.content);
+          break;
+        case 'done':
+          setMessages(prev => [...prev, { type: 'bot', content: msg.content, provider: 'browser', model: browserBrain?.model }]);
+          setStreamContent('');
+          setThinking(false);
+          break;
+        case 'error':
+          setMessages(prev => [...prev, { type: 'system', content: '❌ Error: ' + msg.error, error: true }]);
+          setStreamContent('');
+          setThinking(false);
+          if (msg.id === undefined) {
+            worker.terminate();
+            workerRef.current = null;
+            setLocal(null);
+          }
+          break;
+      }
+    };
+    workerRef.current = worker;
+    setLocal({ status: 'loading', progress: 0 });
+    worker.postMessage({
+      type: 'load',
+      bundleUrl: browserBrain.bundle_url,
+      modelUrl: browserBrain.model_url + '?code=' + encodeURIComponent(pairingCode),
+    });
+  };
+
+  const sendLocal = async (text) => {
+    // Recent turns only: the browser model has a small context
+    const history = messages
+      .filter(m => m.type === 'user' || m.type === 'bot')
+      .slice(-8)
+      .map(m => ({ role: m.type === 'user' ? 'user' : 'assistant', content: m.content }));
+    try {
+      const res = await authFetch('/api/v1/browser-brain/prompt', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ messages: [...history, { role: 'user', content: text }] }),
+      });
+      const { prompt, stop } = await res.json();
+      workerRef.current.postMessage({ type: 'generate', id: Date.now(), prompt, maxTokens: 256, stop });
+    } catch (e) {
+      setMessages(prev => [...prev, { type: 'system', content: '❌ Error: ' + e.message, error: true }]);
+      setThinking(false);
+    }
+  };
+
   // Auto-scroll to bottom
   useEffect(() => {
     if (messagesEndRef.current) {
@@ -277,6 +353,11 @@ function ChatPage({ config, lang }) {
     setMessages(prev => [...prev, { type: 'user', content: text }]);
     setThinking(true);
 
+    if (local?.status === 'ready') {
+      sendLocal(text);
+      return;
+    }
+
     // Send via WebSocket
     if (window._ws && window._ws.readyState === 1) {
       window._ws.send(JSON.stringify({ type: 'chat', content: text, stream: true }));
@@ -353,10 +434,16 @@ function ChatPage({ config, lang }) {
             <span class="chat-target-icon">🤖</span>
             <div>
               <div class="chat-target-name">${config?.agent_name || 'BizClaw AI'}</div>
-              <div class="chat-target-sub">${wsInfo.provider || config?.default_provider || '—'} · ${wsInfo.model || '—'}${wsInfo.agent_engine ? ' · 🧠 Agent' : ''}</div>
+              <div class="chat-target-sub">${local?.status === 'ready'
+                ? `🖥️ ${t('chat.local', lang)} · ${browserBrain?.model || '—'}`
+                : `${wsInfo.provider || config?.default_provider || '—'} · ${wsInfo.model || '—'}${wsInfo.agent_engine ? ' · 🧠 Agent' : ''}`}</div>
             </div>
           </div>
           <div style="display:flex;gap:6px;align-items:center">
+            ${browserBrain?.enabled && html`<button class="btn btn-sm ${local ? 'btn-primary' : 'btn-outline'}" onClick=${toggleLocal}
+              title=${`${browserBrain.model} (${Math.round(browserBrain.size / 1048576)} MB)`}>
+              🖥️ ${local?.status === 'loading' ? `${t('chat.local_loading', lang)}${local.progress != null ? ' ' + local.progress + '%' : ''}` : t('chat.local', lang)}
+            </button>`}
             <span class="badge ${thinking ? 'badge-yellow pulse' : 'badge-green'}">${thinking ? '⏳ thinking' : '● ready'}</span>
             <button class="btn btn-outline btn-sm" onClick=${() => setMessages([])} title="Clear">🗑️</button>
           </div>
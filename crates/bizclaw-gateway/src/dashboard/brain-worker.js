@@ -0,0 +1,32 @@
+// BizClaw — in-browser inference worker for the WebChat.
+// Runs the bizclaw-wasm brain off the main thread so the UI stays responsive.
+//
+// Messages in:  { type: 'load', bundleUrl, modelUrl }
+//               { type: 'generate', id, prompt, maxTokens, stop }
+// Messages out: { type: 'progress', loaded, total } · { type: 'ready', info, features }
+//               { type: 'chunk', id, content } · { type: 'done', id, content }
+//               { type: 'error', id, error }
+
+let brain = null;
+
+self.onmessage = async (e) => {
+  const msg = e.data;
+  try {
+    if (msg.type === 'load') {
+      const wasm = await import(msg.bundleUrl);
+      await wasm.default();
+      brain = await wasm.Brain.load(msg.modelUrl, (loaded, total) => {
+        self.postMessage({ type: 'progress', loaded, total });
+      });
+      self.postMessage({ type: 'ready', info: brain.modelInfo, features: wasm.features() });
+    } else if (msg.type === 'generate') {
+      if (!brain) throw new Error('Model not loaded');
+      const text = brain.generate(msg.prompt, msg.maxTokens || 256, msg.stop || [], (piece) => {
+        self.postMessage({ type: 'chunk', id: msg.id, content: piece });
+      });
+      self.postMessage({ type: 'done', id: msg.id, content: text });
+    }
+  } catch (err) {
+    self.postMessage({ type: 'error', id: msg.id, error: String(err && err.message || err) });
+  }
+};
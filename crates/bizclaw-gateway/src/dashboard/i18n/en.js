@@ -28,6 +28,7 @@ export const en = {
   'chat.thinking':'BizClaw is thinking','chat.placeholder':'Type your message...','chat.send':'Send ↑',
   'chat.history_cleared':'💬 Chat history cleared',
   'chat.help':'Available commands:\n/status — Show agent status\n/reset — Clear chat history\n/help — Show this help',
+  'chat.local':'In-browser','chat.local_loading':'Loading','chat.local_ready':'🖥️ Model loaded — replies now run in your browser, nothing is sent to the server.',
   // Status
   'status.connected':'Connected','status.disconnected':'Disconnected',
   // Settings
@@ -28,6 +28,7 @@ export const vi = {
   'chat.thinking':'BizClaw đang suy nghĩ','chat.placeholder':'Nhập tin nhắn...','chat.send':'Gửi ↑',
   'chat.history_cleared':'💬 Đã xóa lịch sử trò chuyện',
   'chat.help':'Lệnh có sẵn:\n/status — Xem trạng thái agent\n/reset — Xóa lịch sử chat\n/help — Hiện trợ giúp',
+  'chat.local':'Trên trình duyệt','chat.local_loading':'Đang tải','chat.local_ready':'🖥️ Đã tải model — câu trả lời chạy ngay trên trình duyệt, không gửi lên máy chủ.',
   // Status
   'status.connected':'Đã kết nối','status.disconnected':'Đã ngắt kết nối',
   // Settings
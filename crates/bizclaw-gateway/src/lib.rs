@@ -1,6 +1,7 @@
 //! # BizClaw Gateway
 //! HTTP/WebSocket gateway API with embedded web dashboard.
 
+pub mod browser_brain;
 pub mod dashboard;
 pub mod db;
 pub mod openai_compat;
@@ -8,9 +9,22 @@ pub mod routes;
 pub mod server;
 pub mod ws;
 
-use bizclaw_core::config::GatewayConfig;
+use bizclaw_core::config::{BizClawConfig, GatewayConfig};
+use bizclaw_core::events::EventBus;
+use std::path::PathBuf;
 
 /// Start the gateway HTTP server.
 pub async fn start_server(config: &GatewayConfig) -> anyhow::Result<()> {
     server::start(config).await
 }
+
+/// Start the gateway HTTP server with `full_config`, saved back to
+/// `config_path` from the settings UI, reporting on `events`.
+pub async fn start_server_with(
+    config: &GatewayConfig,
+    full_config: BizClawConfig,
+    config_path: PathBuf,
+    events: EventBus,
+) -> anyhow::Result<()> {
+    server::start_with(config, full_config, config_path, events).await
+}
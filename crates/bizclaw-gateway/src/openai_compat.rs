@@ -4,12 +4,17 @@
 //! can use BizClaw as a proxy by pointing to `http://localhost:3579/v1`.
 //!
 //! Authentication: `Authorization: Bearer <pairing-code>` or `api-key` header.
+//! Keys listed in a `[[personas]]` entry are accepted too, and the default
+//! agent answers those requests with that persona's prompt.
 
 use axum::extract::State;
+use axum::response::{IntoResponse, Response};
 use axum::{Json, http::StatusCode};
+use bizclaw_core::error::BizClawError;
+use bizclaw_core::events::{Event, EventBus, EventRecord};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use super::server::AppState;
 
@@ -98,15 +103,96 @@ fn extract_api_key(headers: &axum::http::HeaderMap) -> Option<String> {
 /// Validate API key against pairing code. Returns true if valid.
 fn validate_key(state: &AppState, key: &str) -> bool {
     let stored = state.pairing_code.lock().unwrap().clone();
-    // Constant-time comparison
-    if key.len() != stored.len() {
-        return false;
+    bizclaw_agent::persona::constant_time_eq(key, &stored)
+}
+
+/// The persona whose `api_keys` include `key`.
+fn persona_for_key(state: &AppState, key: &str) -> Option<bizclaw_agent::persona::Persona> {
+    let config = state.full_config.lock().unwrap();
+    bizclaw_agent::persona::PersonaSet::from_config(
+        &config.personas,
+        &config.identity,
+        &Default::default(),
+    )
+    .for_api_key(key)
+    .cloned()
+}
+
+// ─── Errors ──────────────────────────────────────────────────────────────────
+
+/// A failed API request. Agent failures are reported in OpenAI's envelope,
+/// `{"error": {"message", "type", "code"}}`, where `code` is
+/// [`BizClawError::code`]; rate limits carry `Retry-After`.
+#[derive(Debug)]
+pub enum ApiError {
+    /// A bare status, e.g. a missing API key.
+    Status(StatusCode),
+    Failed(BizClawError),
+}
+
+impl From<StatusCode> for ApiError {
+    fn from(status: StatusCode) -> Self {
+        Self::Status(status)
+    }
+}
+
+impl From<BizClawError> for ApiError {
+    fn from(err: BizClawError) -> Self {
+        Self::Failed(err)
+    }
+}
+
+/// The HTTP status reported for `err`.
+fn error_status(err: &BizClawError) -> StatusCode {
+    match err {
+        BizClawError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        BizClawError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        BizClawError::ModelNotLoaded(_) => StatusCode::SERVICE_UNAVAILABLE,
+        BizClawError::ContextOverflow(_) => StatusCode::BAD_REQUEST,
+        BizClawError::ModelNotFound(_) | BizClawError::AgentNotFound(_) => StatusCode::NOT_FOUND,
+        BizClawError::PermissionDenied(_)
+        | BizClawError::NoPermission(_)
+        | BizClawError::Security(_) => StatusCode::FORBIDDEN,
+        // The upstream provider failed, not the caller's request
+        BizClawError::Provider(_)
+        | BizClawError::Http(_)
+        | BizClawError::AuthFailed(_)
+        | BizClawError::ApiKeyMissing(_) => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let err = match self {
+            Self::Status(status) => return status.into_response(),
+            Self::Failed(err) => err,
+        };
+        let status = error_status(&err);
+        let kind = if status == StatusCode::TOO_MANY_REQUESTS {
+            "rate_limit_error"
+        } else if status.is_client_error() {
+            "invalid_request_error"
+        } else {
+            "api_error"
+        };
+        let body = Json(json!({
+            "error": {
+                "message": err.to_string(),
+                "type": kind,
+                "code": err.code(),
+            }
+        }));
+        match err.retry_after() {
+            Some(wait) => (
+                status,
+                [("Retry-After", wait.as_secs().max(1).to_string())],
+                body,
+            )
+                .into_response(),
+            None => (status, body).into_response(),
+        }
     }
-    key.as_bytes()
-        .iter()
-        .zip(stored.as_bytes().iter())
-        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
-        == 0
 }
 
 // ─── POST /v1/chat/completions ───────────────────────────────────────────────
@@ -115,11 +201,12 @@ pub async fn chat_completions(
     State(state): State<Arc<AppState>>,
     headers: axum::http::HeaderMap,
     Json(req): Json<ChatCompletionRequest>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Auth check
     let key = extract_api_key(&headers).ok_or(StatusCode::UNAUTHORIZED)?;
-    if !validate_key(&state, &key) {
-        return Err(StatusCode::UNAUTHORIZED);
+    let persona = persona_for_key(&state, &key);
+    if persona.is_none() && !validate_key(&state, &key) {
+        return Err(StatusCode::UNAUTHORIZED.into());
     }
 
     let start = std::time::Instant::now();
@@ -133,33 +220,42 @@ pub async fn chat_completions(
         .and_then(|m| m.content.as_deref())
         .unwrap_or("");
 
-    let response_text = {
+    state.events.publish(Event::GenerationStarted {
+        channel: "api".into(),
+        thread_id: String::new(),
+        model: req.model.clone(),
+    });
+    let result = {
         // Try to find agent by model name first
         let mut orch = state.orchestrator.lock().await;
         if let Some(agent) = orch.get_agent_mut(&req.model) {
             // Use the named agent
-            match agent.process(user_content).await {
-                Ok(r) => r,
-                Err(e) => format!("Error: {e}"),
-            }
+            agent.process(user_content).await
         } else {
             // Fallback to default agent
             drop(orch);
             let mut agent_lock = state.agent.lock().await;
             if let Some(agent) = agent_lock.as_mut() {
-                match agent.process(user_content).await {
-                    Ok(r) => r,
-                    Err(e) => format!("Error: {e}"),
+                // Answer as the key's persona, then restore the agent's own prompt
+                let saved_prompt = persona.as_ref().map(|p| {
+                    let saved = agent.system_prompt().to_string();
+                    agent.set_system_prompt(&p.system_prompt);
+                    saved
+                });
+                let result = agent.process(user_content).await;
+                if let Some(saved) = saved_prompt {
+                    agent.set_system_prompt(&saved);
                 }
+                result
             } else {
-                return Err(StatusCode::SERVICE_UNAVAILABLE);
+                return Err(StatusCode::SERVICE_UNAVAILABLE.into());
             }
         }
     };
 
     let elapsed = start.elapsed();
     let est_prompt_tokens = (user_content.len() / 4) as u32;
-    let est_completion_tokens = (response_text.len() / 4) as u32;
+    let est_completion_tokens = result.as_ref().map_or(0, |text| (text.len() / 4) as u32);
 
     // Record trace
     {
@@ -172,11 +268,16 @@ pub async fn chat_completions(
             completion_tokens: est_completion_tokens,
             total_tokens: est_prompt_tokens + est_completion_tokens,
             latency_ms: elapsed.as_millis() as u64,
-            cost_usd: estimate_cost(&req.model, est_prompt_tokens, est_completion_tokens),
+            cost_usd: bizclaw_providers::pricing::estimate_cost(
+                "bizclaw",
+                &req.model,
+                est_prompt_tokens,
+                est_completion_tokens,
+            ),
             cache_hit: false,
-            status: "ok".into(),
+            status: result.as_ref().map_or_else(|e| e.code(), |_| "ok").into(),
             tool_calls: 0,
-            error: None,
+            error: result.as_ref().err().map(|e| e.to_string()),
         };
         let mut traces = state.traces.lock().unwrap();
         // Cap at 10,000 traces to prevent unbounded memory growth
@@ -186,13 +287,18 @@ pub async fn chat_completions(
         traces.push(trace);
     }
 
-    // Broadcast activity event via WebSocket
-    let _ = state.activity_tx.send(ActivityEvent {
-        event_type: "llm.completed".into(),
-        agent: req.model.clone(),
-        detail: format!("{}tok in {}ms", est_prompt_tokens + est_completion_tokens, elapsed.as_millis()),
-        timestamp: chrono::Utc::now(),
+    // Reaches the dashboards through the activity feed
+    state.events.publish(Event::GenerationFinished {
+        channel: "api".into(),
+        thread_id: String::new(),
+        model: req.model.clone(),
+        duration_ms: elapsed.as_millis() as u64,
+        success: result.is_ok(),
     });
+    if let Err(e) = &result {
+        bizclaw_core::metrics::count_error(e);
+    }
+    let response_text = result?;
 
     let response = json!({
         "id": format!("chatcmpl-{}", uuid::Uuid::new_v4().to_string().replace("-", "")[..24].to_string()),
@@ -250,6 +356,20 @@ pub async fn list_models(
         "owned_by": "bizclaw",
     }));
 
+    // And the GGUF models pulled with `bizclaw pull`
+    use bizclaw_providers::hub::ModelRegistry;
+    match ModelRegistry::open(&ModelRegistry::default_dir()) {
+        Ok(registry) => models.extend(registry.models().map(|m| {
+            json!({
+                "id": m.name,
+                "object": "model",
+                "created": m.pulled_at,
+                "owned_by": "local",
+            })
+        })),
+        Err(e) => tracing::warn!("Model registry unreadable: {e}"),
+    }
+
     Ok(Json(json!({
         "object": "list",
         "data": models,
@@ -285,27 +405,69 @@ pub struct ActivityEvent {
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
-// ─── Cost estimation ─────────────────────────────────────────────────────────
-
-/// Rough cost estimation per model (USD per 1M tokens).
-fn estimate_cost(model: &str, prompt_tokens: u32, completion_tokens: u32) -> f64 {
-    let (input_rate, output_rate) = match model {
-        m if m.contains("gpt-4o-mini") => (0.15, 0.60),
-        m if m.contains("gpt-4o") => (2.50, 10.00),
-        m if m.contains("gpt-4") => (30.00, 60.00),
-        m if m.contains("claude-3-5-sonnet") || m.contains("claude-4") => (3.00, 15.00),
-        m if m.contains("claude-3-5-haiku") => (0.80, 4.00),
-        m if m.contains("gemini-2.0-flash") => (0.075, 0.30),
-        m if m.contains("gemini") => (0.50, 1.50),
-        m if m.contains("deepseek") => (0.14, 0.28),
-        m if m.contains("groq") || m.contains("llama") => (0.05, 0.10),
-        m if m.contains("mistral") => (0.25, 0.25),
-        _ => (1.00, 3.00), // default estimate
-    };
+/// Activity events kept for REST polling.
+const MAX_ACTIVITY_LOG: usize = 1_000;
 
-    let input_cost = (prompt_tokens as f64 / 1_000_000.0) * input_rate;
-    let output_cost = (completion_tokens as f64 / 1_000_000.0) * output_rate;
-    ((input_cost + output_cost) * 100_000.0).round() / 100_000.0
+impl From<&EventRecord> for ActivityEvent {
+    fn from(record: &EventRecord) -> Self {
+        let outcome = |success: bool, duration_ms: u64| {
+            if success {
+                format!("{duration_ms}ms")
+            } else {
+                format!("failed after {duration_ms}ms")
+            }
+        };
+        let (agent, detail) = match &record.event {
+            Event::MessageReceived {
+                channel,
+                thread_id,
+                sender_id,
+            } => (channel.clone(), format!("from {sender_id} in {thread_id}")),
+            Event::GenerationStarted { channel, model, .. } => (channel.clone(), model.clone()),
+            Event::GenerationFinished {
+                channel,
+                model,
+                duration_ms,
+                success,
+                ..
+            } => (
+                channel.clone(),
+                format!("{model} {}", outcome(*success, *duration_ms)),
+            ),
+            Event::ChannelConnected { channel } => (channel.clone(), "connected".into()),
+            Event::ToolExecuted {
+                tool,
+                duration_ms,
+                success,
+            } => (tool.clone(), outcome(*success, *duration_ms)),
+        };
+        Self {
+            event_type: record.event.kind().into(),
+            agent,
+            detail,
+            timestamp: record.timestamp,
+        }
+    }
+}
+
+/// Feed the events published on `bus` to the dashboards: broadcast on `tx`
+/// and kept in `log` for polling.
+pub fn spawn_activity_feed(
+    bus: &EventBus,
+    tx: tokio::sync::broadcast::Sender<ActivityEvent>,
+    log: Arc<Mutex<Vec<ActivityEvent>>>,
+) -> tokio::task::JoinHandle<()> {
+    bus.spawn_subscriber("Activity feed", move |record| {
+        let event = ActivityEvent::from(&record);
+        {
+            let mut log = log.lock().unwrap();
+            if log.len() >= MAX_ACTIVITY_LOG {
+                log.drain(..MAX_ACTIVITY_LOG / 10);
+            }
+            log.push(event.clone());
+        }
+        let _ = tx.send(event);
+    })
 }
 
 // ─── Trace API Handlers ──────────────────────────────────────────────────────
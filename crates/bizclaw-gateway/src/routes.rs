@@ -100,6 +100,41 @@ pub async fn health_check() -> Json<serde_json::Value> {
     }))
 }
 
+/// Readiness probe — 503 until the agent is up or while a channel has given up.
+pub async fn readiness_check(
+    State(state): State<Arc<AppState>>,
+) -> (axum::http::StatusCode, Json<serde_json::Value>) {
+    let agent_ready = state.agent.lock().await.is_some();
+    let failed = state.channel_status.failed();
+    let ready = agent_ready && failed.is_empty();
+    let code = if ready {
+        axum::http::StatusCode::OK
+    } else {
+        axum::http::StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        code,
+        Json(serde_json::json!({
+            "ready": ready,
+            "agent": agent_ready,
+            "failed_channels": failed,
+            "channels": state.channel_status.statuses(),
+        })),
+    )
+}
+
+/// Prometheus scrape endpoint — every metric in the global registry.
+pub async fn metrics() -> ([(axum::http::header::HeaderName, &'static str); 1], String) {
+    use bizclaw_core::metrics;
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        metrics::render_prometheus(&metrics::global().snapshot()),
+    )
+}
+
 /// System information endpoint.
 pub async fn system_info(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
     let uptime = state.start_time.elapsed();
@@ -556,10 +591,17 @@ pub async fn update_channel(
                 secret_val.to_string()
             };
             let outbound_url = req.get("webhook_url").and_then(|v| v.as_str()).unwrap_or("").to_string();
+            let path = req.get("webhook_path")
+                .and_then(|v| v.as_str())
+                .filter(|p| p.starts_with('/'))
+                .map(String::from)
+                .or_else(|| cfg.channel.webhook.as_ref().map(|wh| wh.path.clone()))
+                .unwrap_or_else(|| bizclaw_channels::webhook::DEFAULT_INBOUND_PATH.to_string());
             cfg.channel.webhook = Some(bizclaw_core::config::WebhookChannelConfig {
                 enabled,
                 secret,
                 outbound_url,
+                path,
             });
         }
         _ => {
@@ -742,8 +784,11 @@ pub async fn save_channel_instance(
             "webhook" => {
                 let outbound = sync_body.get("webhook_url").and_then(|v| v.as_str()).unwrap_or("").to_string();
                 let secret = sync_body.get("webhook_secret").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                let path = full_cfg.channel.webhook.as_ref()
+                    .map(|wh| wh.path.clone())
+                    .unwrap_or_else(|| bizclaw_channels::webhook::DEFAULT_INBOUND_PATH.to_string());
                 full_cfg.channel.webhook = Some(bizclaw_core::config::WebhookChannelConfig {
-                    enabled: true, secret, outbound_url: outbound,
+                    enabled: true, secret, outbound_url: outbound, path,
                 });
             }
             _ => {} // Other types handled as-is
@@ -833,11 +878,7 @@ pub async fn webhook_inbound(
     // Verify signature if secret configured
     if !secret.is_empty() {
         let sig = headers.get("x-webhook-signature").and_then(|v| v.to_str().ok()).unwrap_or("");
-        use sha2::{Digest, Sha256};
-        let mut hasher = Sha256::new();
-        hasher.update(format!("{secret}{body}"));
-        let expected = format!("{:x}", hasher.finalize());
-        if expected != sig {
+        if !bizclaw_channels::webhook::verify_signature(&secret, body.as_bytes(), sig) {
             return Json(serde_json::json!({"ok": false, "error": "Invalid webhook signature"}));
         }
     }
@@ -852,6 +893,12 @@ pub async fn webhook_inbound(
     if content.is_empty() {
         return Json(serde_json::json!({"ok": false, "error": "'content' field required"}));
     }
+    if let Some(id) = json["message_id"].as_str()
+        && !state.inbound_dedup.check_key(&format!("webhook:{agent_name}:{id}"))
+    {
+        tracing::debug!("[webhook] Duplicate delivery {id} ignored");
+        return Json(serde_json::json!({"ok": true, "duplicate": true}));
+    }
 
     tracing::info!("[webhook] {} → agent '{}': {}", sender, agent_name, safe_truncate(&content, 100));
 
@@ -928,6 +975,8 @@ pub async fn spawn_telegram_polling(
     let bot_token_for_state = bot_token.clone();
 
     tokio::spawn(async move {
+        use bizclaw_channels::manager::ChannelState;
+
         let mut channel = bizclaw_channels::telegram::TelegramChannel::new(
             bizclaw_channels::telegram::TelegramConfig {
                 bot_token: bot_token.clone(),
@@ -935,23 +984,31 @@ pub async fn spawn_telegram_polling(
                 poll_interval: 1,
             },
         );
+        let status = state_clone.channel_status.clone();
+        let status_name = format!("telegram:{agent_name_clone}");
+        status.set_state(&status_name, ChannelState::Running, None);
 
         loop {
             tokio::select! {
                 _ = stop_rx.notified() => {
                     tracing::info!("[telegram] Polling stopped for agent '{}'", agent_name_clone);
+                    status.set_state(&status_name, ChannelState::Stopped, None);
                     break;
                 }
                 result = channel.get_updates() => {
                     match result {
                         Ok(updates) => {
+                            status.set_state(&status_name, ChannelState::Running, None);
                             for update in updates {
-                                if let Some(msg) = update.to_incoming() {
+                                if let Some(msg) = update.to_incoming()
+                                    && state_clone.inbound_dedup.check(&msg)
+                                {
                                     let chat_id: i64 = msg.thread_id.parse().unwrap_or(0);
                                     let sender = msg.sender_name.clone().unwrap_or_default();
                                     let text = msg.content.clone();
 
                                     tracing::info!("[telegram] {} → agent '{}': {}", sender, agent_name_clone, safe_truncate(&text, 100));
+                                    status.record_in(&status_name);
                                     let _ = channel.send_typing(chat_id).await;
 
                                     // Route to agent
@@ -963,14 +1020,19 @@ pub async fn spawn_telegram_polling(
                                         }
                                     };
 
-                                    if let Err(e) = channel.send_message(chat_id, &response).await {
-                                        tracing::error!("[telegram] Reply failed: {e}");
+                                    match channel.send_message(chat_id, &response).await {
+                                        Ok(()) => status.record_out(&status_name, 1),
+                                        Err(e) => {
+                                            tracing::error!("[telegram] Reply failed: {e}");
+                                            status.record_error(&status_name, e);
+                                        }
                                     }
                                 }
                             }
                         }
                         Err(e) => {
                             tracing::error!("[telegram] Polling error for '{}': {e}", agent_name_clone);
+                            status.set_state(&status_name, ChannelState::Reconnecting, Some(e.to_string()));
                             tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
                         }
                     }
@@ -1807,11 +1869,7 @@ pub async fn _webhook_inbound_legacy(
         }
 
         // HMAC-SHA256 verification
-        use sha2::{Digest, Sha256};
-        let mut hasher = Sha256::new();
-        hasher.update(format!("{secret}{body}"));
-        let expected = format!("{:x}", hasher.finalize());
-        if expected != signature {
+        if !bizclaw_channels::webhook::verify_signature(&secret, body.as_bytes(), signature) {
             tracing::warn!("[webhook] Invalid signature from inbound request");
             return Json(serde_json::json!({
                 "ok": false,
@@ -2539,7 +2597,9 @@ pub async fn connect_telegram(
                     match result {
                         Ok(updates) => {
                             for update in updates {
-                                if let Some(msg) = update.to_incoming() {
+                                if let Some(msg) = update.to_incoming()
+                                    && state_clone.inbound_dedup.check(&msg)
+                                {
                                     let chat_id: i64 = msg.thread_id.parse().unwrap_or(0);
                                     let sender = msg.sender_name.clone().unwrap_or_default();
                                     let text = msg.content.clone();
@@ -2993,6 +3053,10 @@ mod tests {
             traces: Arc::new(Mutex::new(Vec::new())),
             activity_tx,
             activity_log: Arc::new(Mutex::new(Vec::new())),
+            events: Default::default(),
+            webchat: bizclaw_channels::webchat::WebChatChannel::new().handle(),
+            channel_status: Default::default(),
+            inbound_dedup: Arc::new(bizclaw_channels::middleware::DedupStore::in_memory(64)),
         }))
     }
 
@@ -3228,6 +3292,29 @@ mod tests {
         let json = result.0;
         assert!(json["ok"].as_bool().unwrap());
     }
+
+    #[tokio::test]
+    async fn test_api_error_envelope() {
+        use crate::openai_compat::ApiError;
+        use axum::response::IntoResponse;
+        use bizclaw_core::error::BizClawError;
+
+        let err = BizClawError::rate_limited("slow down", Some(std::time::Duration::from_secs(30)));
+        let response = ApiError::from(err).into_response();
+        assert_eq!(response.status(), axum::http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["Retry-After"], "30");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"]["code"], "rate_limited");
+        assert_eq!(json["error"]["type"], "rate_limit_error");
+
+        let err = BizClawError::ContextOverflow("9000 tokens".into());
+        let response = ApiError::from(err).into_response();
+        assert_eq!(response.status(), axum::http::StatusCode::BAD_REQUEST);
+        assert!(response.headers().get("Retry-After").is_none());
+    }
 }
 
 // ═══════════════════════════════════════════════════════
@@ -3698,6 +3785,304 @@ pub async fn orch_list_traces(
     Json(serde_json::json!({"ok": true, "traces": items, "count": items.len()}))
 }
 
+/// Token and cost totals of a month, from the usage store the channel
+/// agent records into, with the configured budgets.
+/// GET /api/v1/usage?month=2026-10&group=model
+pub async fn usage_summary(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Json<serde_json::Value> {
+    use bizclaw_memory::usage::{UsageGroup, UsageStore, current_month};
+
+    let month = params.get("month").cloned().unwrap_or_else(current_month);
+    let group_name = params.get("group").map(|s| s.as_str()).unwrap_or("model");
+    let Some(group) = UsageGroup::parse(group_name) else {
+        return Json(serde_json::json!({
+            "ok": false,
+            "error": "group must be provider, model, channel or user",
+        }));
+    };
+    let store = match UsageStore::open(UsageStore::default_path()) {
+        Ok(store) => store,
+        Err(e) => return internal_error("usage_summary", e),
+    };
+    let (total, groups) = match (store.total(&month), store.summary(&month, group)) {
+        (Ok(total), Ok(groups)) => (total, groups),
+        (Err(e), _) | (_, Err(e)) => return internal_error("usage_summary", e),
+    };
+    let usage = state.full_config.lock().unwrap().usage.clone();
+    Json(serde_json::json!({
+        "ok": true,
+        "month": month,
+        "group": group_name,
+        "total": total,
+        "groups": groups,
+        "enabled": usage.enabled,
+        "monthly_budget_usd": usage.monthly_budget_usd,
+        "user_monthly_budget_usd": usage.user_monthly_budget_usd,
+        "fallback": usage.fallback,
+    }))
+}
+
+/// Prompt templates: the built-ins and the files in `[templates] dir`.
+/// GET /api/v1/templates
+pub async fn list_templates(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    use bizclaw_agent::template::{PromptTemplates, TemplateSource};
+
+    let config = state.full_config.lock().unwrap().templates.clone();
+    let templates = PromptTemplates::from_config(&config);
+    let entry = |name: &str, persona: Option<&str>| {
+        serde_json::json!({
+            "name": name,
+            "persona": persona,
+            "source": match templates.source(name, persona) {
+                Some(TemplateSource::Builtin) => "builtin",
+                _ => "file",
+            },
+            "text": templates.get(name, persona),
+        })
+    };
+    let mut items: Vec<serde_json::Value> = templates
+        .names()
+        .into_iter()
+        .map(|name| entry(name, None))
+        .collect();
+    for persona in templates.personas() {
+        for name in templates.persona_names(persona) {
+            items.push(entry(name, Some(persona)));
+        }
+    }
+    Json(serde_json::json!({
+        "ok": true,
+        "dir": config.templates_dir().display().to_string(),
+        "vars": config.vars,
+        "templates": items,
+    }))
+}
+
+/// The file a template is saved in, if `name` and `persona` are usable as
+/// file names.
+fn template_path(
+    config: &bizclaw_core::config::TemplatesConfig,
+    name: &str,
+    persona: Option<&str>,
+) -> Option<std::path::PathBuf> {
+    use bizclaw_agent::template::{EXTENSIONS, is_valid_name};
+
+    let mut dir = config.templates_dir();
+    if let Some(persona) = persona.filter(|p| !p.is_empty()) {
+        let persona = persona.to_lowercase();
+        if !is_valid_name(&persona) {
+            return None;
+        }
+        dir.push(persona);
+    }
+    is_valid_name(name).then(|| dir.join(format!("{name}.{}", EXTENSIONS[0])))
+}
+
+/// Save a template file. The agent picks it up on its next config reload.
+/// PUT /api/v1/templates/{name}  {"text": "...", "persona": "sales"}
+pub async fn save_template(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+    Json(body): Json<serde_json::Value>,
+) -> Json<serde_json::Value> {
+    let Some(text) = body["text"].as_str() else {
+        return Json(serde_json::json!({"ok": false, "error": "text is required"}));
+    };
+    let config = state.full_config.lock().unwrap().templates.clone();
+    let Some(path) = template_path(&config, &name, body["persona"].as_str()) else {
+        return Json(serde_json::json!({
+            "ok": false,
+            "error": "Names may only contain letters, digits, '-' and '_'",
+        }));
+    };
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&path, text));
+    match written {
+        Ok(()) => Json(serde_json::json!({"ok": true, "path": path.display().to_string()})),
+        Err(e) => internal_error("save_template", e),
+    }
+}
+
+/// Delete a template file, restoring the built-in if there is one.
+/// DELETE /api/v1/templates/{name}?persona=sales
+pub async fn delete_template(
+    State(state): State<Arc<AppState>>,
+    axum::extract::Path(name): axum::extract::Path<String>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Json<serde_json::Value> {
+    let config = state.full_config.lock().unwrap().templates.clone();
+    let persona = params.get("persona").map(|s| s.as_str());
+    let Some(path) = template_path(&config, &name, persona) else {
+        return Json(serde_json::json!({"ok": false, "error": "Invalid template name"}));
+    };
+    let mut deleted = false;
+    for extension in bizclaw_agent::template::EXTENSIONS {
+        match std::fs::remove_file(path.with_extension(extension)) {
+            Ok(()) => deleted = true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return internal_error("delete_template", e),
+        }
+    }
+    Json(serde_json::json!({"ok": deleted}))
+}
+
+/// Export a thread's stored history, as a download.
+/// GET /api/v1/conversations/{channel}/{thread_id}/export?format=markdown
+pub async fn export_conversation(
+    axum::extract::Path((channel, thread_id)): axum::extract::Path<(String, String)>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> axum::response::Response {
+    use axum::response::IntoResponse;
+    use bizclaw_memory::export::{ExportFormat, ThreadExport};
+    use bizclaw_memory::history::HistoryStore;
+
+    let format_name = params.get("format").map(|s| s.as_str()).unwrap_or("json");
+    let Some(format) = ExportFormat::parse(format_name) else {
+        return Json(serde_json::json!({"ok": false, "error": "format must be json or markdown"}))
+            .into_response();
+    };
+    let rendered = HistoryStore::open(HistoryStore::default_path())
+        .and_then(|store| ThreadExport::from_store(&store, &channel, &thread_id))
+        .and_then(|export| export.render(format));
+    match rendered {
+        Ok(body) => {
+            let file_name: String = format!("{channel}-{thread_id}")
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '-' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            axum::response::Response::builder()
+                .header(axum::http::header::CONTENT_TYPE, format.content_type())
+                .header(
+                    axum::http::header::CONTENT_DISPOSITION,
+                    format!(
+                        "attachment; filename=\"{file_name}.{}\"",
+                        format.extension()
+                    ),
+                )
+                .body(axum::body::Body::from(body))
+                .unwrap()
+        }
+        Err(e) => internal_error("export_conversation", e).into_response(),
+    }
+}
+
+/// Import a JSON export, into its own thread or the one given.
+/// POST /api/v1/conversations/import?channel=zalo&thread_id=9&replace=true
+pub async fn import_conversation(
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+    body: String,
+) -> Json<serde_json::Value> {
+    use bizclaw_memory::export::ThreadExport;
+    use bizclaw_memory::history::HistoryStore;
+
+    let mut export = match ThreadExport::from_json(&body) {
+        Ok(export) => export,
+        Err(e) => {
+            return Json(serde_json::json!({"ok": false, "error": format!("Invalid export: {e}")}));
+        }
+    };
+    if let Some(channel) = params.get("channel").filter(|c| !c.is_empty()) {
+        export.channel = channel.clone();
+    }
+    if let Some(thread_id) = params.get("thread_id").filter(|t| !t.is_empty()) {
+        export.thread_id = thread_id.clone();
+    }
+    let replace = params.get("replace").is_some_and(|r| r == "true");
+    let imported = HistoryStore::open(HistoryStore::default_path())
+        .and_then(|store| export.import_into(&store, replace));
+    match imported {
+        Ok(count) => Json(serde_json::json!({
+            "ok": true,
+            "channel": export.channel,
+            "thread_id": export.thread_id,
+            "imported": count,
+        })),
+        Err(e) => internal_error("import_conversation", e),
+    }
+}
+
+/// Message analytics per day or week, optionally per channel, persona or
+/// model, with tool usage over the same range.
+/// GET /api/v1/analytics/report?period=weekly&days=28&group=channel
+pub async fn analytics_report(
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Json<serde_json::Value> {
+    use bizclaw_memory::analytics::{self, AnalyticsGroup, AnalyticsStore, ReportPeriod};
+
+    let period_name = params.get("period").map(|s| s.as_str()).unwrap_or("daily");
+    let Some(period) = ReportPeriod::parse(period_name) else {
+        return Json(serde_json::json!({"ok": false, "error": "period must be daily or weekly"}));
+    };
+    let group = match params.get("group").map(|s| s.as_str()) {
+        None | Some("") => None,
+        Some(name) => match AnalyticsGroup::parse(name) {
+            Some(group) => Some(group),
+            None => {
+                return Json(serde_json::json!({
+                    "ok": false,
+                    "error": "group must be channel, persona or model",
+                }));
+            }
+        },
+    };
+    let days: u32 = params
+        .get("days")
+        .and_then(|d| d.parse().ok())
+        .unwrap_or(30);
+    let since = analytics::days_ago(days.max(1));
+    let store = match AnalyticsStore::open(AnalyticsStore::default_path()) {
+        Ok(store) => store,
+        Err(e) => return internal_error("analytics_report", e),
+    };
+    let (rows, tools) = match (store.report(period, since, group), store.tool_counts(since)) {
+        (Ok(rows), Ok(tools)) => (rows, tools),
+        (Err(e), _) | (_, Err(e)) => return internal_error("analytics_report", e),
+    };
+    let tools: Vec<serde_json::Value> = tools
+        .into_iter()
+        .map(|(tool, calls)| serde_json::json!({"tool": tool, "calls": calls}))
+        .collect();
+    Json(serde_json::json!({
+        "ok": true,
+        "period": period_name,
+        "since": since.to_rfc3339(),
+        "rows": rows,
+        "tools": tools,
+    }))
+}
+
+/// Feedback on a thread's last reply, for channels with native reactions.
+/// POST /api/v1/analytics/feedback {"channel", "thread_id", "score": 1 | -1}
+pub async fn analytics_feedback(Json(body): Json<serde_json::Value>) -> Json<serde_json::Value> {
+    use bizclaw_memory::analytics::AnalyticsStore;
+
+    let channel = body["channel"].as_str().unwrap_or_default();
+    let thread_id = body["thread_id"].as_str().unwrap_or_default();
+    let score = body["score"].as_i64().unwrap_or_default();
+    if channel.is_empty() || thread_id.is_empty() || score == 0 {
+        return Json(serde_json::json!({
+            "ok": false,
+            "error": "channel, thread_id and a non-zero score are required",
+        }));
+    }
+    let recorded = AnalyticsStore::open(AnalyticsStore::default_path())
+        .and_then(|store| store.record_feedback(channel, thread_id, score.signum() as i8));
+    match recorded {
+        Ok(found) => Json(serde_json::json!({"ok": found, "recorded": found})),
+        Err(e) => internal_error("analytics_feedback", e),
+    }
+}
+
 // ═══ MCP Servers API ═══
 pub async fn mcp_list_servers(
     State(state): State<Arc<AppState>>,
@@ -45,6 +45,15 @@ pub struct AppState {
     pub activity_tx: tokio::sync::broadcast::Sender<super::openai_compat::ActivityEvent>,
     /// Activity log — keeps recent events for REST polling.
     pub activity_log: Arc<Mutex<Vec<super::openai_compat::ActivityEvent>>>,
+    /// Event bus — channels, agents and the API publish here; the activity
+    /// feed and the audit log subscribe.
+    pub events: bizclaw_core::EventBus,
+    /// WebChat channel handle — opens one channel thread per `/ws/webchat` socket.
+    pub webchat: bizclaw_channels::webchat::WebChatHandle,
+    /// Health of every channel — supervised ones and per-agent bot loops.
+    pub channel_status: bizclaw_channels::manager::ChannelStatusHandle,
+    /// Recently seen inbound message ids — drops webhook retries and polling replays.
+    pub inbound_dedup: Arc<bizclaw_channels::middleware::DedupStore>,
 }
 
 /// State for an active Telegram bot connected to an agent.
@@ -213,12 +222,51 @@ async fn security_headers(
     response
 }
 
-/// Build the Axum router with all routes.
-pub fn build_router(state: AppState) -> Router {
+/// Header correlating a request's log lines, see [`bizclaw_core::logging`].
+const REQUEST_ID: &str = "x-request-id";
+
+/// Give every request an `X-Request-Id` — the client's, or a new one — and
+/// echo it on the response.
+async fn request_id(
+    mut req: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let id = req
+        .headers()
+        .get(REQUEST_ID)
+        .filter(|id| !id.is_empty() && id.len() <= 128)
+        .cloned()
+        .unwrap_or_else(|| {
+            axum::http::HeaderValue::from_str(&uuid::Uuid::new_v4().to_string()).unwrap()
+        });
+    req.headers_mut().insert(REQUEST_ID, id.clone());
+    let mut response = next.run(req).await;
+    response.headers_mut().insert(REQUEST_ID, id);
+    response
+}
+
+/// The `request` span that agent and brain spans nest under.
+fn request_span(req: &axum::http::Request<axum::body::Body>) -> tracing::Span {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %req.method(),
+        path = %req.uri().path(),
+        request_id,
+    )
+}
+
+/// Build the Axum router with all routes. Fails if the custom webhook path
+/// collides with one of them.
+pub fn build_router(state: AppState) -> anyhow::Result<Router> {
     build_router_from_arc(Arc::new(state))
 }
 
-pub fn build_router_from_arc(shared: Arc<AppState>) -> Router {
+pub fn build_router_from_arc(shared: Arc<AppState>) -> anyhow::Result<Router> {
 
     // Protected routes — require valid pairing code
     let protected = Router::new()
@@ -365,15 +413,48 @@ pub fn build_router_from_arc(shared: Arc<AppState>) -> Router {
             "/api/v1/brain/personalize",
             post(super::routes::brain_personalize),
         )
+        // In-browser inference for the dashboard chat
+        .route("/api/v1/browser-brain", get(super::browser_brain::info))
+        .route(
+            "/api/v1/browser-brain/model",
+            get(super::browser_brain::model),
+        )
+        .route(
+            "/api/v1/browser-brain/prompt",
+            post(super::browser_brain::prompt),
+        )
         // Health Check
         .route("/api/v1/health", get(super::routes::system_health_check))
         // LLM Traces & Cost API
         .route("/api/v1/traces", get(super::openai_compat::list_traces))
         .route("/api/v1/traces/cost", get(super::openai_compat::cost_breakdown))
         .route("/api/v1/activity", get(super::openai_compat::list_activity))
+        .route("/api/v1/usage", get(super::routes::usage_summary))
+        .route(
+            "/api/v1/analytics/report",
+            get(super::routes::analytics_report),
+        )
+        .route(
+            "/api/v1/analytics/feedback",
+            post(super::routes::analytics_feedback),
+        )
+        .route("/api/v1/templates", get(super::routes::list_templates))
+        .route(
+            "/api/v1/templates/{name}",
+            put(super::routes::save_template).delete(super::routes::delete_template),
+        )
+        .route(
+            "/api/v1/conversations/{channel}/{thread_id}/export",
+            get(super::routes::export_conversation),
+        )
+        .route(
+            "/api/v1/conversations/import",
+            post(super::routes::import_conversation),
+        )
         // MCP Servers API (stub — returns configured MCP servers)
         .route("/api/v1/mcp/servers", get(super::routes::mcp_list_servers))
         .route("/ws", get(super::ws::ws_handler))
+        .route("/ws/webchat", get(super::ws::webchat_ws_handler))
         .route_layer(axum::middleware::from_fn_with_state(
             shared.clone(),
             require_pairing,
@@ -384,7 +465,13 @@ pub fn build_router_from_arc(shared: Arc<AppState>) -> Router {
         .route("/", get(dashboard_page))
         .route("/legacy", get(legacy_dashboard_page))
         .route("/static/dashboard/*path", get(dashboard_static))
+        .route(
+            "/static/wasm/{name}",
+            get(super::browser_brain::bundle_file),
+        )
         .route("/health", get(super::routes::health_check))
+        .route("/readyz", get(super::routes::readiness_check))
+        .route("/metrics", get(super::routes::metrics))
         .route("/api/v1/verify-pairing", post(verify_pairing))
         // WhatsApp webhook — must be public for Meta verification
         .route(
@@ -392,16 +479,36 @@ pub fn build_router_from_arc(shared: Arc<AppState>) -> Router {
             get(super::routes::whatsapp_webhook_verify).post(super::routes::whatsapp_webhook),
         )
         // Webhook inbound — public, auth via HMAC signature in header
-        .route("/api/v1/webhook/inbound", post(super::routes::webhook_inbound))
+        .route(
+            bizclaw_channels::webhook::DEFAULT_INBOUND_PATH,
+            post(super::routes::webhook_inbound),
+        )
         // OpenAI-Compatible API — public with own auth (Bearer token)
         .route("/v1/chat/completions", post(super::openai_compat::chat_completions))
         .route("/v1/models", get(super::openai_compat::list_models));
 
+    // Custom webhook inbound path from [channel.webhook] path = "..."
+    let webhook = shared.full_config.lock().unwrap().channel.webhook.clone();
+    if let Some(problem) = webhook.as_ref().and_then(|wh| wh.path_problem()) {
+        // axum would panic on a route that is already taken
+        anyhow::bail!("can't mount the inbound webhook: {problem}");
+    }
+    let custom_webhook_path = webhook
+        .map(|wh| wh.path)
+        .filter(|p| p != bizclaw_channels::webhook::DEFAULT_INBOUND_PATH);
+    let public = match custom_webhook_path {
+        Some(path) => {
+            tracing::info!("[webhook] Inbound endpoint also mounted at {path}");
+            public.route(&path, post(super::routes::webhook_inbound))
+        }
+        None => public,
+    };
+
     // SPA fallback — serve dashboard HTML for all frontend routes
     // so that /dashboard, /chat, /settings etc. all work with path-based routing
     let spa_fallback = Router::new().fallback(get(dashboard_page));
 
-    protected
+    Ok(protected
         .merge(public)
         .merge(spa_fallback)
         .layer({
@@ -429,25 +536,45 @@ pub fn build_router_from_arc(shared: Arc<AppState>) -> Router {
                 cors.allow_origin(Any)
             }
         })
-        .layer(TraceLayer::new_for_http())
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        // Outside the trace layer, so the span sees the id
+        .layer(axum::middleware::from_fn(request_id))
         // Security headers
         .layer(axum::middleware::from_fn(security_headers))
         // H1 FIX: Limit request body size (5MB — allows file uploads for knowledge base)
         .layer(DefaultBodyLimit::max(5_242_880))
-        .with_state(shared)
+        .with_state(shared))
 }
 
 /// Start the HTTP server.
 pub async fn start(config: &GatewayConfig) -> anyhow::Result<()> {
     // Load full config for settings UI
-    let config_path = std::env::var("BIZCLAW_CONFIG")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| BizClawConfig::default_path());
-    let full_config = if config_path.exists() {
-        BizClawConfig::load_from(&config_path).unwrap_or_default()
-    } else {
+    let config_path = BizClawConfig::config_path();
+    let full_config = BizClawConfig::load().unwrap_or_else(|e| {
+        tracing::error!("{e}; starting with the default config");
         BizClawConfig::default()
-    };
+    });
+
+    let events = bizclaw_core::EventBus::new();
+    if full_config.events.audit_log {
+        bizclaw_core::events::EventLog::new(bizclaw_core::events::EventLog::default_path())
+            .spawn(&events);
+    }
+    bizclaw_core::metrics::spawn_event_metrics(&events, bizclaw_core::metrics::global().clone());
+    bizclaw_core::metrics::start_from_config(&full_config.metrics);
+    bizclaw_core::telemetry::start_from_config(&full_config.telemetry);
+
+    start_with(config, full_config, config_path, events).await
+}
+
+/// Serve until SIGTERM or Ctrl+C with `full_config`, loaded from `config_path`. The
+/// audit log and metrics are expected to be subscribed to `events` already.
+pub async fn start_with(
+    config: &GatewayConfig,
+    full_config: BizClawConfig,
+    config_path: std::path::PathBuf,
+    events: bizclaw_core::EventBus,
+) -> anyhow::Result<()> {
 
     // Create the Agent engine (sync — no MCP to avoid startup hang)
     let agent: Option<bizclaw_agent::Agent> =
@@ -645,6 +772,46 @@ pub async fn start(config: &GatewayConfig) -> anyhow::Result<()> {
     });
 
     let (activity_tx, _rx) = tokio::sync::broadcast::channel::<super::openai_compat::ActivityEvent>(256);
+    let activity_log = Arc::new(Mutex::new(Vec::new()));
+    super::openai_compat::spawn_activity_feed(&events, activity_tx.clone(), activity_log.clone());
+
+    // WebChat channel — web visitors flow through the same Channel pipeline as bots
+    let webchat_channel = bizclaw_channels::webchat::WebChatChannel::new();
+    let webchat = webchat_channel.handle();
+    let classifier = if full_config.moderation.enabled && full_config.moderation.classifier {
+        use bizclaw_channels::pipeline::{ModerationClassifier, ProviderClassifier};
+        match bizclaw_providers::create_provider(&full_config) {
+            Ok(provider) => Some(Arc::new(ProviderClassifier::from_config(
+                provider.into(),
+                &full_config,
+            )) as Arc<dyn ModerationClassifier>),
+            Err(e) => {
+                tracing::warn!("Moderation classifier unavailable, using rules only: {e}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let mut channels = bizclaw_channels::manager::ChannelManager::new(Default::default())
+        .with_events(events.clone())
+        .with_middleware(bizclaw_channels::pipeline::from_config(
+            &full_config,
+            classifier,
+        ));
+    if full_config.plugins.enabled {
+        channels = channels.with_middleware(bizclaw_plugins::load_middleware(&full_config.plugins));
+    }
+    channels.register(Box::new(webchat_channel));
+
+    let inbound_dedup = {
+        use bizclaw_channels::middleware::{DedupStore, dedup::DEFAULT_CAPACITY};
+        let path = BizClawConfig::home_dir().join("gateway-dedup.log");
+        DedupStore::open(&path, DEFAULT_CAPACITY).unwrap_or_else(|e| {
+            tracing::warn!("Dedup store unavailable, keeping it in memory: {e}");
+            DedupStore::in_memory(DEFAULT_CAPACITY)
+        })
+    };
 
     let state = AppState {
         gateway_config: config.clone(),
@@ -673,11 +840,37 @@ pub async fn start(config: &GatewayConfig) -> anyhow::Result<()> {
         orch_store,
         traces: Arc::new(Mutex::new(Vec::new())),
         activity_tx: activity_tx.clone(),
-        activity_log: Arc::new(Mutex::new(Vec::new())),
+        activity_log,
+        events,
+        webchat,
+        channel_status: channels.status_handle(),
+        inbound_dedup: Arc::new(inbound_dedup),
     };
 
     let state_arc = Arc::new(state);
-    let app = build_router_from_arc(state_arc.clone());
+    let app = build_router_from_arc(state_arc.clone())?;
+
+    // Config edits and SIGHUP refresh the settings the dashboard serves
+    let current = state_arc.full_config.lock().unwrap().clone();
+    let mut reloads =
+        bizclaw_core::reload::ConfigWatcher::new(state_arc.config_path.clone(), current)
+            .spawn()
+            .subscribe();
+    let reload_state = state_arc.clone();
+    tokio::spawn(async move {
+        use tokio::sync::broadcast::error::RecvError;
+        loop {
+            match reloads.recv().await {
+                Ok(reload) => {
+                    *reload_state.full_config.lock().unwrap() = (*reload.config).clone();
+                }
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    channels.start(super::ws::agent_message_handler(state_arc.clone()));
 
     // Auto-connect saved channel instances (Telegram bots, etc.)
     let state_for_channels = state_arc.clone();
@@ -691,8 +884,17 @@ pub async fn start(config: &GatewayConfig) -> anyhow::Result<()> {
     let listener = tokio::net::TcpListener::bind(&addr).await?;
 
     tracing::info!("🌐 Gateway server listening on http://{}", addr);
-
-    axum::serve(listener, app).await?;
+    bizclaw_core::daemon::notify_ready();
+
+    // Stop accepting connections on SIGTERM/Ctrl+C and let in-flight requests finish.
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let signal = bizclaw_core::daemon::shutdown_signal().await;
+            tracing::info!("{signal} received, draining connections");
+            bizclaw_core::daemon::notify_stopping();
+        })
+        .await?;
+    channels.shutdown().await;
     Ok(())
 }
 
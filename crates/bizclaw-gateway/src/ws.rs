@@ -2,8 +2,8 @@
 //!
 //! Architecture:
 //! - If Agent Engine is available → uses it for FULL processing (tools + memory + all providers)
-//! - Streaming mode → direct provider HTTP for UX, then saves to Agent memory
-//! - Fallback → raw HTTP calls to Ollama/OpenAI if Agent unavailable
+//! - Otherwise → direct mode: the configured provider answers without tools,
+//!   streaming through `Provider::stream`, then the reply is saved to Agent memory
 //!
 //! Protocol:
 //! → Client sends: {"type":"chat","content":"...","stream":true}
@@ -19,6 +19,11 @@ use axum::{
     },
     response::IntoResponse,
 };
+use bizclaw_channels::webchat::WebChatSession;
+use bizclaw_core::events::Event;
+use bizclaw_core::metrics;
+use bizclaw_core::traits::provider::GenerateParams;
+use futures::StreamExt;
 use std::sync::Arc;
 
 /// WebSocket upgrade handler.
@@ -29,14 +34,6 @@ pub async fn ws_handler(
     ws.on_upgrade(move |socket| handle_socket(socket, state))
 }
 
-/// Resolve Ollama URL from config or env.
-fn ollama_url(_state: &AppState) -> String {
-    if let Ok(url) = std::env::var("OLLAMA_HOST") {
-        return url;
-    }
-    "http://localhost:11434".to_string()
-}
-
 /// Get the active model from config.
 fn active_model(state: &AppState) -> String {
     let config = state.full_config.lock().unwrap();
@@ -89,6 +86,8 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
     if send_json(&mut socket, &welcome).await.is_err() {
         return;
     }
+    let connections = metrics::gauge("bizclaw_gateway_websocket_connections", &[]);
+    connections.add(1.0);
 
     if has_agent_initial {
         tracing::info!("WS session using Agent Engine (tools + memory enabled)");
@@ -258,6 +257,8 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
                                             "type": "chat_error",
                                             "request_id": &request_id,
                                             "error": e.to_string(),
+                                            "code": e.code(),
+                                            "retryable": e.is_retryable(),
                                         }),
                                     )
                                     .await;
@@ -284,56 +285,16 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
                                 fallback_history.extend(tail);
                             }
 
-                            // Route to provider
-                            let result = match provider.as_str() {
-                                "ollama" | "brain" => {
-                                    chat_ollama(
-                                        &mut socket,
-                                        &state,
-                                        &request_id,
-                                        &fallback_history,
-                                        &model,
-                                        stream,
-                                    )
-                                    .await
-                                }
-                                "openai" => {
-                                    chat_openai(
-                                        &mut socket,
-                                        &state,
-                                        &request_id,
-                                        &fallback_history,
-                                        &model,
-                                        stream,
-                                    )
-                                    .await
-                                }
-                                _ => {
-                                    // Fallback: try Ollama first, then OpenAI
-                                    let r = chat_ollama(
-                                        &mut socket,
-                                        &state,
-                                        &request_id,
-                                        &fallback_history,
-                                        &model,
-                                        stream,
-                                    )
-                                    .await;
-                                    if r.is_err() {
-                                        chat_openai(
-                                            &mut socket,
-                                            &state,
-                                            &request_id,
-                                            &fallback_history,
-                                            "gpt-4o-mini",
-                                            stream,
-                                        )
-                                        .await
-                                    } else {
-                                        r
-                                    }
-                                }
-                            };
+                            let result = chat_direct(
+                                &mut socket,
+                                &state,
+                                &request_id,
+                                &fallback_history,
+                                &provider,
+                                &model,
+                                stream,
+                            )
+                            .await;
 
                             match result {
                                 Ok(response) => {
@@ -390,6 +351,7 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
 
                         let status = serde_json::json!({
                             "type": "status",
+                            "channels": state.channel_status.statuses(),
                             "requests_processed": request_counter,
                             "uptime_secs": state.start_time.elapsed().as_secs(),
                             "provider": &current_provider,
@@ -420,275 +382,277 @@ async fn handle_socket(mut socket: WebSocket, state: Arc<AppState>) {
         }
     }
 
+    connections.add(-1.0);
     tracing::info!("WebSocket connection closed (total requests: {request_counter})");
 }
 
 // ═══════════════════════════════════════════════════════════
-// OLLAMA PROVIDER
+// DIRECT MODE
 // ═══════════════════════════════════════════════════════════
 
-async fn chat_ollama(
+/// Answer through the configured provider without the agent (no tools or
+/// memory), streaming chunks to the socket when `stream` is set.
+async fn chat_direct(
     socket: &mut WebSocket,
     state: &AppState,
     request_id: &str,
-    messages: &[serde_json::Value],
+    history: &[serde_json::Value],
+    provider: &str,
     model: &str,
     stream: bool,
 ) -> Result<String, String> {
-    let url = ollama_url(state);
-    let client = reqwest::Client::new();
+    let mut config = state.full_config.lock().unwrap().clone();
+    // The brain runs in-process behind the agent; without one, use Ollama
+    config.llm.provider = match provider {
+        "brain" => "ollama".into(),
+        other => other.into(),
+    };
+    let client = bizclaw_providers::create_provider(&config).map_err(|e| e.to_string())?;
+    let messages: Vec<bizclaw_core::types::Message> = history
+        .iter()
+        .filter_map(|m| serde_json::from_value(m.clone()).ok())
+        .collect();
+    let params = GenerateParams {
+        model: model.to_string(),
+        ..Default::default()
+    };
 
-    if stream {
-        // Streaming response
+    if !stream {
+        let response = client
+            .chat(&messages, &[], &params)
+            .await
+            .map_err(|e| e.to_string())?;
+        let content = response.content.unwrap_or_default();
         let _ = send_json(
             socket,
             &serde_json::json!({
-                "type": "chat_start",
+                "type": "chat_response",
                 "request_id": request_id,
-                "provider": "ollama",
+                "content": &content,
+                "provider": client.name(),
                 "model": model,
             }),
         )
         .await;
+        return Ok(content);
+    }
 
-        let body = serde_json::json!({
+    let mut chunks = client
+        .stream(&messages, &[], &params)
+        .await
+        .map_err(|e| e.to_string())?;
+    let _ = send_json(
+        socket,
+        &serde_json::json!({
+            "type": "chat_start",
+            "request_id": request_id,
+            "provider": client.name(),
             "model": model,
-            "messages": messages,
-            "stream": true,
-        });
-
-        let resp = client
-            .post(format!("{url}/api/chat"))
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Ollama connection failed ({}): {}", url, e))?;
-
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_default();
-            return Err(format!("Ollama error {status}: {text}"));
-        }
-
-        let mut full_content = String::new();
-        let mut chunk_idx: u64 = 0;
-
-        // Read streaming NDJSON response
-        let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
-        let text = String::from_utf8_lossy(&bytes);
-
-        for line in text.lines() {
-            if line.trim().is_empty() {
-                continue;
-            }
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(line)
-                && let Some(content) = json["message"]["content"].as_str()
-                    && !content.is_empty() {
-                        full_content.push_str(content);
-                        let _ = send_json(
-                            socket,
-                            &serde_json::json!({
-                                "type": "chat_chunk",
-                                "request_id": request_id,
-                                "content": content,
-                                "index": chunk_idx,
-                            }),
-                        )
-                        .await;
-                        chunk_idx += 1;
-                    }
+        }),
+    )
+    .await;
+
+    let mut full_content = String::new();
+    let mut chunk_idx: u64 = 0;
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        if chunk.delta.is_empty() {
+            continue;
         }
-
-        let _ = send_json(
-            socket,
-            &serde_json::json!({
-                "type": "chat_done",
-                "request_id": request_id,
-                "total_tokens": chunk_idx,
-                "full_content": &full_content,
-            }),
-        )
-        .await;
-
-        Ok(full_content)
-    } else {
-        // Non-streaming
-        let body = serde_json::json!({
-            "model": model,
-            "messages": messages,
-            "stream": false,
-        });
-
-        let resp = client
-            .post(format!("{url}/api/chat"))
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Ollama connection failed: {e}"))?;
-
-        let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
-        let content = json["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
-
+        full_content.push_str(&chunk.delta);
         let _ = send_json(
             socket,
             &serde_json::json!({
-                "type": "chat_response",
+                "type": "chat_chunk",
                 "request_id": request_id,
-                "content": &content,
-                "provider": "ollama",
-                "model": model,
+                "content": &chunk.delta,
+                "index": chunk_idx,
             }),
         )
         .await;
-
-        Ok(content)
+        chunk_idx += 1;
     }
+
+    let _ = send_json(
+        socket,
+        &serde_json::json!({
+            "type": "chat_done",
+            "request_id": request_id,
+            "total_tokens": chunk_idx,
+            "full_content": &full_content,
+        }),
+    )
+    .await;
+
+    Ok(full_content)
 }
 
 // ═══════════════════════════════════════════════════════════
-// OPENAI PROVIDER
+// WEBCHAT CHANNEL
 // ═══════════════════════════════════════════════════════════
-
-async fn chat_openai(
-    socket: &mut WebSocket,
-    state: &AppState,
-    request_id: &str,
-    messages: &[serde_json::Value],
-    model: &str,
-    stream: bool,
-) -> Result<String, String> {
-    let api_key = {
-        let config = state.full_config.lock().unwrap();
-        config.api_key.clone()
-    };
-    let api_key = if api_key.is_empty() {
-        std::env::var("OPENAI_API_KEY").map_err(|_| {
-            "OpenAI API key not configured. Set in Settings → API Key or OPENAI_API_KEY env var"
-                .to_string()
-        })?
-    } else {
-        api_key
+//
+// Protocol (/ws/webchat?session=<id>&token=<resume_token>):
+// → Client sends: {"type":"chat","content":"...","sender_name":"optional"}
+// ← Server sends: {"type":"connected","session_id":"...","resume_token":"..."}
+// ← Server sends: {"type":"message","content":"...","reply_to":null}
+
+/// WebChat WebSocket upgrade handler.
+/// One socket = one WebChat thread; reconnecting with the same `session` and
+/// the `token` it was given resumes it. A known session without its token is
+/// refused.
+pub async fn webchat_ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> axum::response::Response {
+    let session_id = params
+        .get("session")
+        .filter(|s| {
+            !s.is_empty()
+                && s.len() <= 64
+                && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .cloned()
+        .unwrap_or_else(|| format!("web-{}", uuid::Uuid::new_v4()));
+    let session = match state
+        .webchat
+        .resume_session(&session_id, params.get("token").map(String::as_str))
+    {
+        Ok(session) => session,
+        Err(e) => {
+            tracing::warn!("[webchat] Refused to resume session {session_id}: {e}");
+            return (axum::http::StatusCode::FORBIDDEN, "Unknown session token").into_response();
+        }
     };
+    ws.on_upgrade(move |socket| handle_webchat_socket(socket, session))
+}
 
-    let client = reqwest::Client::new();
-
-    if stream {
-        // Streaming SSE mode
-        let _ = send_json(
-            socket,
-            &serde_json::json!({
-                "type": "chat_start",
-                "request_id": request_id,
-                "provider": "openai",
-                "model": model,
-            }),
-        )
-        .await;
-
-        let body = serde_json::json!({
-            "model": model,
-            "messages": messages,
-            "stream": true,
-        });
-
-        let resp = client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {api_key}"))
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("OpenAI request failed: {e}"))?;
-
-        if !resp.status().is_success() {
-            let text = resp.text().await.unwrap_or_default();
-            return Err(format!("OpenAI error: {text}"));
-        }
+/// Pump one WebChat socket: visitor messages into the channel, replies back out.
+async fn handle_webchat_socket(mut socket: WebSocket, mut session: WebChatSession) {
+    let session_id = session.id().to_string();
+    tracing::info!("[webchat] Visitor connected (session={session_id})");
 
-        // Read SSE stream
-        let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
-        let text = String::from_utf8_lossy(&bytes);
-        let mut full_content = String::new();
-        let mut chunk_idx: u64 = 0;
+    let welcome = serde_json::json!({
+        "type": "connected",
+        "session_id": session.id(),
+        "resume_token": session.token(),
+        "version": env!("CARGO_PKG_VERSION"),
+    });
+    if send_json(&mut socket, &welcome).await.is_err() {
+        return;
+    }
 
-        for line in text.lines() {
-            let line = line.trim();
-            if line.is_empty() || line == "data: [DONE]" {
-                continue;
-            }
-            if let Some(data) = line.strip_prefix("data: ")
-                && let Ok(json) = serde_json::from_str::<serde_json::Value>(data)
-                    && let Some(content) = json["choices"][0]["delta"]["content"].as_str()
-                        && !content.is_empty() {
-                            full_content.push_str(content);
-                            let _ = send_json(
-                                socket,
-                                &serde_json::json!({
-                                    "type": "chat_chunk",
-                                    "request_id": request_id,
-                                    "content": content,
-                                    "index": chunk_idx,
-                                }),
-                            )
-                            .await;
-                            chunk_idx += 1;
+    loop {
+        tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let json = match serde_json::from_str::<serde_json::Value>(&text) {
+                        Ok(j) => j,
+                        Err(e) => {
+                            send_error(&mut socket, &format!("Invalid JSON: {e}")).await;
+                            continue;
                         }
+                    };
+                    match json["type"].as_str().unwrap_or("unknown") {
+                        "chat" => {
+                            let content = json["content"].as_str().unwrap_or("").trim();
+                            if content.is_empty() {
+                                send_error(&mut socket, "Empty message").await;
+                                continue;
+                            }
+                            let sender_name = json["sender_name"].as_str().map(String::from);
+                            if let Err(e) = session.submit(content, sender_name) {
+                                send_error(&mut socket, &e.to_string()).await;
+                                break;
+                            }
+                        }
+                        "ping" => {
+                            let pong = serde_json::json!({
+                                "type": "pong",
+                                "timestamp": chrono::Utc::now().timestamp_millis(),
+                            });
+                            let _ = send_json(&mut socket, &pong).await;
+                        }
+                        other => {
+                            send_error(&mut socket, &format!("Unknown message type: {other}")).await;
+                        }
+                    }
+                }
+                Some(Ok(Message::Ping(data))) => {
+                    let _ = socket.send(Message::Pong(data)).await;
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            reply = session.recv() => match reply {
+                Some(out) => {
+                    let msg = serde_json::json!({
+                        "type": "message",
+                        "content": out.content,
+                        "reply_to": out.reply_to,
+                    });
+                    if send_json(&mut socket, &msg).await.is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            },
         }
+    }
 
-        let _ = send_json(
-            socket,
-            &serde_json::json!({
-                "type": "chat_done",
-                "request_id": request_id,
-                "total_tokens": chunk_idx,
-                "full_content": &full_content,
-            }),
-        )
-        .await;
-
-        Ok(full_content)
-    } else {
-        // Non-streaming mode
-        let body = serde_json::json!({
-            "model": model,
-            "messages": messages,
-        });
-
-        let resp = client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {api_key}"))
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("OpenAI request failed: {e}"))?;
+    tracing::info!("[webchat] Visitor disconnected (session={session_id})");
+}
 
-        if !resp.status().is_success() {
-            let text = resp.text().await.unwrap_or_default();
-            return Err(format!("OpenAI error: {text}"));
+/// Message handler that routes channel messages through the Agent engine.
+/// Agent failures become an error reply instead of silence.
+pub fn agent_message_handler(
+    state: Arc<AppState>,
+) -> Arc<dyn bizclaw_channels::manager::MessageHandler> {
+    Arc::new(move |incoming: bizclaw_core::types::IncomingMessage| {
+        let state = state.clone();
+        async move {
+            let result = {
+                let mut agent = state.agent.lock().await;
+                match agent.as_mut() {
+                    Some(agent) => {
+                        agent.set_knowledge(state.knowledge.clone());
+                        let model = agent.model_name().to_string();
+                        state.events.publish(Event::GenerationStarted {
+                            channel: incoming.channel.clone(),
+                            thread_id: incoming.thread_id.clone(),
+                            model: model.clone(),
+                        });
+                        let started = std::time::Instant::now();
+                        let result = agent.handle_incoming(&incoming).await;
+                        state.events.publish(Event::GenerationFinished {
+                            channel: incoming.channel.clone(),
+                            thread_id: incoming.thread_id.clone(),
+                            model,
+                            duration_ms: started.elapsed().as_millis() as u64,
+                            success: result.is_ok(),
+                        });
+                        if let Err(e) = &result {
+                            bizclaw_core::metrics::count_error(e);
+                        }
+                        result
+                    }
+                    None => Err(bizclaw_core::BizClawError::Other(
+                        "Agent not available".into(),
+                    )),
+                }
+            };
+
+            let reply = result.unwrap_or_else(|e| {
+                tracing::error!("[{}] Agent error: {e}", incoming.channel);
+                bizclaw_core::types::OutgoingMessage::text(
+                    incoming.thread_id.clone(),
+                    format!("⚠️ {e}"),
+                    incoming.thread_type.clone(),
+                )
+            });
+            Ok::<_, bizclaw_core::BizClawError>(Some(reply))
         }
-
-        let json: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
-        let content = json["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
-
-        let _ = send_json(
-            socket,
-            &serde_json::json!({
-                "type": "chat_response",
-                "request_id": request_id,
-                "content": &content,
-                "provider": "openai",
-                "model": model,
-            }),
-        )
-        .await;
-
-        Ok(content)
-    }
+    })
 }
 
 // ═══════════════════════════════════════════════════════════
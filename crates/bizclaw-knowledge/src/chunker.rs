@@ -55,6 +55,58 @@ pub fn chunk_text(text: &str, max_chars: usize) -> Vec<String> {
     chunks
 }
 
+/// Like [`chunk_text`], but each chunk after the first starts with the last
+/// `overlap` characters (whole words) of the previous one, so a sentence cut
+/// at a boundary is still retrievable from either side.
+pub fn chunk_with_overlap(text: &str, max_chars: usize, overlap: usize) -> Vec<String> {
+    let chunks = chunk_text(text, max_chars);
+    if overlap == 0 {
+        return chunks;
+    }
+    let mut out: Vec<String> = Vec::with_capacity(chunks.len());
+    for (i, chunk) in chunks.iter().enumerate() {
+        if i == 0 {
+            out.push(chunk.clone());
+            continue;
+        }
+        let prev = &chunks[i - 1];
+        let mut start = prev.len().saturating_sub(overlap);
+        while !prev.is_char_boundary(start) {
+            start += 1;
+        }
+        let tail = match prev[start..].find(char::is_whitespace) {
+            Some(ws) if start > 0 => prev[start + ws..].trim(),
+            _ => prev[start..].trim(),
+        };
+        if tail.is_empty() {
+            out.push(chunk.clone());
+        } else {
+            out.push(format!("{tail} {chunk}"));
+        }
+    }
+    out
+}
+
+/// Extract the text layer of a PDF.
+pub fn extract_pdf(bytes: &[u8]) -> Result<String, String> {
+    pdf_extract::extract_text_from_mem(bytes).map_err(|e| format!("PDF error: {e}"))
+}
+
+/// Read a document from disk as plain text — PDFs through [`extract_pdf`],
+/// everything else through [`extract_text`].
+pub fn read_document(path: &std::path::Path) -> Result<String, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Read error: {e}"))?;
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if name.to_lowercase().ends_with(".pdf") {
+        extract_pdf(&bytes)
+    } else {
+        Ok(extract_text(&String::from_utf8_lossy(&bytes), &name))
+    }
+}
+
 /// Extract plain text from common file formats.
 /// Supports: .txt, .md, .json, .toml, .yaml, .csv, .log
 /// For Pi: no heavy PDF/DOCX parsing — keep it simple.
@@ -142,6 +194,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_chunk_overlap() {
+        let text = (0..120)
+            .map(|i| format!("w{i}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let plain = chunk_text(&text, 200);
+        let overlapped = chunk_with_overlap(&text, 200, 20);
+        assert_eq!(plain.len(), overlapped.len());
+        assert_eq!(overlapped[0], plain[0]);
+        assert!(overlapped[1].ends_with(&plain[1]));
+        let carried = overlapped[1][..overlapped[1].len() - plain[1].len()].trim();
+        assert!(!carried.is_empty() && carried.len() <= 20);
+        assert!(plain[0].ends_with(carried));
+    }
+
     #[test]
     fn test_extract_markdown() {
         let md = "# Title\n## Sub\n- item\n> quote";
@@ -0,0 +1,600 @@
+//! HNSW — approximate nearest-neighbor index for [`RagStore`](crate::rag::RagStore).
+//!
+//! A brute-force scan over 100k chunks costs tens of milliseconds per query;
+//! a hierarchical navigable small-world graph answers in well under ten by
+//! visiting only a few hundred nodes.
+//!
+//! - Vectors are L2-normalized on insert; the score is cosine similarity.
+//! - Deletes are tombstones: deleted nodes still route searches but are never
+//!   returned. [`HnswIndex::compact`] rebuilds once they pile up.
+//! - [`HnswIndex::save`] / [`HnswIndex::load`] persist the graph, so a
+//!   restart doesn't re-index.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"BZHNSW01";
+
+/// Graph parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HnswConfig {
+    /// Links per node on upper layers (twice as many on layer 0).
+    pub m: usize,
+    /// Candidate list size while inserting.
+    pub ef_construction: usize,
+    /// Candidate list size while searching.
+    pub ef_search: usize,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 100,
+            ef_search: 64,
+        }
+    }
+}
+
+struct Node {
+    id: i64,
+    doc_id: i64,
+    vector: Vec<f32>,
+    /// Neighbor indices per layer; `links.len() - 1` is the node's level.
+    links: Vec<Vec<u32>>,
+    deleted: bool,
+}
+
+/// Distance paired with a node index, ordered by distance.
+#[derive(Clone, Copy, PartialEq)]
+struct Scored(f32, u32);
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// Approximate nearest-neighbor index over `(chunk id, document id)` vectors.
+pub struct HnswIndex {
+    config: HnswConfig,
+    dim: usize,
+    nodes: Vec<Node>,
+    by_id: HashMap<i64, u32>,
+    entry: Option<u32>,
+    deleted: usize,
+    rng: u64,
+}
+
+impl HnswIndex {
+    pub fn new(config: HnswConfig) -> Self {
+        Self {
+            config,
+            dim: 0,
+            nodes: Vec::new(),
+            by_id: HashMap::new(),
+            entry: None,
+            deleted: 0,
+            rng: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    /// Live (not deleted) vectors.
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.deleted
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, id: i64) -> bool {
+        self.by_id.contains_key(&id)
+    }
+
+    /// Vector dimension; 0 until the first insert.
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Add a vector. Re-inserting an id replaces the old vector.
+    pub fn insert(&mut self, id: i64, doc_id: i64, vector: &[f32]) -> Result<(), String> {
+        if self.dim == 0 {
+            self.dim = vector.len();
+        }
+        if vector.len() != self.dim || vector.is_empty() {
+            return Err(format!(
+                "vector has {} dimensions, index has {}",
+                vector.len(),
+                self.dim
+            ));
+        }
+        self.remove(id);
+
+        let vector = normalize(vector);
+        let level = self.random_level();
+        let idx = self.nodes.len() as u32;
+        self.nodes.push(Node {
+            id,
+            doc_id,
+            vector,
+            links: vec![Vec::new(); level + 1],
+            deleted: false,
+        });
+        self.by_id.insert(id, idx);
+
+        let Some(entry) = self.entry else {
+            self.entry = Some(idx);
+            return Ok(());
+        };
+        let top = self.level(entry);
+        let query = self.nodes[idx as usize].vector.clone();
+
+        let mut ep = entry;
+        for layer in (level + 1..=top).rev() {
+            ep = self.greedy(&query, ep, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            let candidates = self.search_layer(&query, &[ep], self.config.ef_construction, layer);
+            let max = self.max_links(layer);
+            let neighbors: Vec<u32> = candidates.iter().take(max).map(|s| s.1).collect();
+            for &n in &neighbors {
+                self.link(n, idx, layer);
+            }
+            self.nodes[idx as usize].links[layer] = neighbors;
+            ep = candidates.first().map_or(ep, |s| s.1);
+        }
+        if level > top {
+            self.entry = Some(idx);
+        }
+        Ok(())
+    }
+
+    /// Tombstone one vector. Returns whether it was present.
+    pub fn remove(&mut self, id: i64) -> bool {
+        let Some(idx) = self.by_id.remove(&id) else {
+            return false;
+        };
+        self.nodes[idx as usize].deleted = true;
+        self.deleted += 1;
+        true
+    }
+
+    /// Tombstone every vector of a document. Returns how many were removed.
+    pub fn remove_document(&mut self, doc_id: i64) -> usize {
+        let ids: Vec<i64> = self
+            .nodes
+            .iter()
+            .filter(|n| !n.deleted && n.doc_id == doc_id)
+            .map(|n| n.id)
+            .collect();
+        ids.into_iter().filter(|&id| self.remove(id)).count()
+    }
+
+    /// Whether enough nodes are tombstoned that a rebuild pays off.
+    pub fn needs_compaction(&self) -> bool {
+        self.deleted > 0 && self.deleted * 4 >= self.nodes.len()
+    }
+
+    /// Rebuild the graph from live vectors, dropping tombstones.
+    pub fn compact(&mut self) {
+        let live: Vec<Node> = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .filter(|n| !n.deleted)
+            .collect();
+        let mut fresh = Self::new(self.config);
+        fresh.rng = self.rng;
+        for node in live {
+            // Vectors were validated on their first insert.
+            let _ = fresh.insert(node.id, node.doc_id, &node.vector);
+        }
+        *self = fresh;
+    }
+
+    /// The `k` nearest live vectors as `(id, cosine similarity)`, best first.
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(i64, f32)> {
+        self.search_filtered(query, k, |_| true)
+    }
+
+    /// Like [`search`](Self::search), restricted to documents accepted by
+    /// `filter`. The candidate list widens until `k` matches are found or
+    /// the whole graph has been considered.
+    pub fn search_filtered(
+        &self,
+        query: &[f32],
+        k: usize,
+        filter: impl Fn(i64) -> bool,
+    ) -> Vec<(i64, f32)> {
+        let Some(entry) = self.entry else {
+            return Vec::new();
+        };
+        if k == 0 || query.len() != self.dim {
+            return Vec::new();
+        }
+        let query = normalize(query);
+
+        let mut ep = entry;
+        for layer in (1..=self.level(entry)).rev() {
+            ep = self.greedy(&query, ep, layer);
+        }
+
+        let mut ef = self.config.ef_search.max(k);
+        loop {
+            let results: Vec<(i64, f32)> = self
+                .search_layer(&query, &[ep], ef, 0)
+                .into_iter()
+                .map(|s| &self.nodes[s.1 as usize])
+                .filter(|n| !n.deleted && filter(n.doc_id))
+                .take(k)
+                .map(|n| (n.id, dot(&query, &n.vector)))
+                .collect();
+            if results.len() >= k || ef >= self.nodes.len() {
+                return results;
+            }
+            ef = (ef * 4).min(self.nodes.len());
+        }
+    }
+
+    /// Write the index to `path` (via a temporary file, so a crash never
+    /// leaves a truncated index behind).
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        {
+            let mut w = BufWriter::new(std::fs::File::create(&tmp)?);
+            w.write_all(MAGIC)?;
+            for v in [
+                self.config.m,
+                self.config.ef_construction,
+                self.config.ef_search,
+                self.dim,
+                self.nodes.len(),
+            ] {
+                w.write_all(&(v as u32).to_le_bytes())?;
+            }
+            w.write_all(&self.entry.map_or(-1, i64::from).to_le_bytes())?;
+            w.write_all(&self.rng.to_le_bytes())?;
+            for node in &self.nodes {
+                w.write_all(&node.id.to_le_bytes())?;
+                w.write_all(&node.doc_id.to_le_bytes())?;
+                w.write_all(&[node.deleted as u8, node.links.len() as u8])?;
+                for v in &node.vector {
+                    w.write_all(&v.to_le_bytes())?;
+                }
+                for links in &node.links {
+                    w.write_all(&(links.len() as u32).to_le_bytes())?;
+                    for l in links {
+                        w.write_all(&l.to_le_bytes())?;
+                    }
+                }
+            }
+            w.flush()?;
+        }
+        std::fs::rename(tmp, path)
+    }
+
+    /// Read an index written by [`save`](Self::save).
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut r = BufReader::new(std::fs::File::open(path)?);
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an HNSW index",
+            ));
+        }
+        let config = HnswConfig {
+            m: read_u32(&mut r)? as usize,
+            ef_construction: read_u32(&mut r)? as usize,
+            ef_search: read_u32(&mut r)? as usize,
+        };
+        let dim = read_u32(&mut r)? as usize;
+        let count = read_u32(&mut r)? as usize;
+        let entry = read_i64(&mut r)?;
+        let mut index = Self::new(config);
+        index.dim = dim;
+        index.entry = u32::try_from(entry).ok();
+        index.rng = read_u64(&mut r)?;
+
+        for idx in 0..count {
+            let id = read_i64(&mut r)?;
+            let doc_id = read_i64(&mut r)?;
+            let mut flags = [0u8; 2];
+            r.read_exact(&mut flags)?;
+            let mut vector = Vec::with_capacity(dim);
+            for _ in 0..dim {
+                vector.push(f32::from_bits(read_u32(&mut r)?));
+            }
+            let mut links = Vec::with_capacity(flags[1] as usize);
+            for _ in 0..flags[1] {
+                let n = read_u32(&mut r)? as usize;
+                let mut layer = Vec::with_capacity(n);
+                for _ in 0..n {
+                    let l = read_u32(&mut r)?;
+                    if l as usize >= count {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, "dangling link"));
+                    }
+                    layer.push(l);
+                }
+                links.push(layer);
+            }
+            let deleted = flags[0] != 0;
+            if deleted {
+                index.deleted += 1;
+            } else {
+                index.by_id.insert(id, idx as u32);
+            }
+            index.nodes.push(Node {
+                id,
+                doc_id,
+                vector,
+                links,
+                deleted,
+            });
+        }
+        if index.entry.is_some_and(|e| e as usize >= count) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "bad entry point",
+            ));
+        }
+        Ok(index)
+    }
+
+    fn level(&self, idx: u32) -> usize {
+        self.nodes[idx as usize].links.len() - 1
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.config.m * 2
+        } else {
+            self.config.m
+        }
+    }
+
+    fn distance(&self, query: &[f32], idx: u32) -> f32 {
+        1.0 - dot(query, &self.nodes[idx as usize].vector)
+    }
+
+    /// Exponentially distributed level with normalization 1 / ln(M).
+    fn random_level(&mut self) -> usize {
+        // xorshift64*: deterministic, so rebuilt indexes are reproducible.
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+        let uniform = (bits as f64 + 1.0) / (1u64 << 53) as f64;
+        let ml = 1.0 / (self.config.m.max(2) as f64).ln();
+        ((-uniform.ln() * ml) as usize).min(16)
+    }
+
+    /// Walk to the closest node on one layer.
+    fn greedy(&self, query: &[f32], mut ep: u32, layer: usize) -> u32 {
+        let mut best = self.distance(query, ep);
+        loop {
+            let mut moved = false;
+            for &n in &self.nodes[ep as usize].links[layer] {
+                let d = self.distance(query, n);
+                if d < best {
+                    best = d;
+                    ep = n;
+                    moved = true;
+                }
+            }
+            if !moved {
+                return ep;
+            }
+        }
+    }
+
+    /// Beam search on one layer; returns up to `ef` nodes, closest first.
+    fn search_layer(&self, query: &[f32], entry: &[u32], ef: usize, layer: usize) -> Vec<Scored> {
+        let mut visited: HashSet<u32> = entry.iter().copied().collect();
+        let mut candidates: BinaryHeap<Reverse<Scored>> = BinaryHeap::new();
+        let mut found: BinaryHeap<Scored> = BinaryHeap::new();
+        for &e in entry {
+            let s = Scored(self.distance(query, e), e);
+            candidates.push(Reverse(s));
+            found.push(s);
+        }
+
+        while let Some(Reverse(current)) = candidates.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|worst| current.0 > worst.0) {
+                break;
+            }
+            let node = &self.nodes[current.1 as usize];
+            let Some(links) = node.links.get(layer) else {
+                continue;
+            };
+            for &n in links {
+                if !visited.insert(n) {
+                    continue;
+                }
+                let s = Scored(self.distance(query, n), n);
+                if found.len() < ef || found.peek().is_some_and(|worst| s.0 < worst.0) {
+                    candidates.push(Reverse(s));
+                    found.push(s);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    /// Add `to` to `from`'s links on `layer`, keeping only the closest when
+    /// the list overflows.
+    fn link(&mut self, from: u32, to: u32, layer: usize) {
+        let max = self.max_links(layer);
+        let links = &self.nodes[from as usize].links[layer];
+        if links.len() < max {
+            self.nodes[from as usize].links[layer].push(to);
+            return;
+        }
+        let base = self.nodes[from as usize].vector.clone();
+        let mut scored: Vec<Scored> = links
+            .iter()
+            .chain(std::iter::once(&to))
+            .map(|&n| Scored(self.distance(&base, n), n))
+            .collect();
+        scored.sort();
+        scored.truncate(max);
+        self.nodes[from as usize].links[layer] = scored.into_iter().map(|s| s.1).collect();
+    }
+}
+
+impl Default for HnswIndex {
+    fn default() -> Self {
+        Self::new(HnswConfig::default())
+    }
+}
+
+fn normalize(v: &[f32]) -> Vec<f32> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        v.to_vec()
+    } else {
+        v.iter().map(|x| x / norm).collect()
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut b = [0u8; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn read_i64(r: &mut impl Read) -> io::Result<i64> {
+    Ok(read_u64(r)? as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random vectors.
+    fn vectors(n: usize, dim: usize) -> Vec<Vec<f32>> {
+        let mut state = 42u64;
+        (0..n)
+            .map(|_| {
+                (0..dim)
+                    .map(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        ((state >> 33) as f32 / (1u64 << 31) as f32) - 0.5
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn brute_force(data: &[Vec<f32>], query: &[f32], k: usize) -> Vec<i64> {
+        let q = normalize(query);
+        let mut scored: Vec<(i64, f32)> = data
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i as i64, dot(&q, &normalize(v))))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().take(k).map(|(id, _)| id).collect()
+    }
+
+    fn build(data: &[Vec<f32>]) -> HnswIndex {
+        let mut index = HnswIndex::default();
+        for (i, v) in data.iter().enumerate() {
+            // Ten chunks per document.
+            index.insert(i as i64, i as i64 / 10, v).unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn test_recall_against_brute_force() {
+        let data = vectors(1000, 24);
+        let index = build(&data);
+        let queries = vectors(20, 24);
+        let mut hits = 0;
+        for q in &queries {
+            let expected = brute_force(&data, q, 10);
+            let got: Vec<i64> = index.search(q, 10).iter().map(|r| r.0).collect();
+            hits += got.iter().filter(|id| expected.contains(id)).count();
+        }
+        let recall = hits as f32 / (queries.len() * 10) as f32;
+        assert!(recall >= 0.9, "recall {recall}");
+    }
+
+    #[test]
+    fn test_delete_filter_and_compact() {
+        let data = vectors(300, 8);
+        let mut index = build(&data);
+        let query = &data[5];
+        assert_eq!(index.search(query, 1)[0].0, 5);
+
+        // Deleting document 0 (ids 0..10) hides its chunks.
+        assert_eq!(index.remove_document(0), 10);
+        assert_eq!(index.len(), 290);
+        assert!(index.search(query, 20).iter().all(|(id, _)| *id >= 10));
+
+        // Only document 7 (ids 70..80).
+        let only_seven = index.search_filtered(query, 5, |doc| doc == 7);
+        assert_eq!(only_seven.len(), 5);
+        assert!(only_seven.iter().all(|(id, _)| (70..80).contains(id)));
+
+        for doc in 1..10 {
+            index.remove_document(doc);
+        }
+        assert!(index.needs_compaction());
+        index.compact();
+        assert_eq!(index.len(), 200);
+        assert_eq!(index.search(&data[150], 1)[0].0, 150);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let data = vectors(200, 8);
+        let mut index = build(&data);
+        index.remove_document(3);
+        let path = std::env::temp_dir().join(format!("bizclaw-hnsw-{}.idx", std::process::id()));
+        index.save(&path).unwrap();
+
+        let loaded = HnswIndex::load(&path).unwrap();
+        assert_eq!(loaded.len(), index.len());
+        assert_eq!(loaded.dim(), 8);
+        for q in vectors(5, 8) {
+            assert_eq!(loaded.search(&q, 5), index.search(&q, 5));
+        }
+        assert!(!loaded.contains(30));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_dimension_mismatch() {
+        let mut index = HnswIndex::default();
+        index.insert(1, 1, &[1.0, 0.0]).unwrap();
+        assert!(index.insert(2, 1, &[1.0, 0.0, 0.0]).is_err());
+        assert!(index.search(&[1.0, 0.0, 0.0], 1).is_empty());
+    }
+}
@@ -22,8 +22,13 @@
 //!   ↓
 //! Agent responds with grounded answer
 //! ```
+//!
+//! For semantic search, [`rag::RagStore`] embeds chunks with the LLM
+//! provider and retrieves by cosine similarity through an [`hnsw`] index.
 
 pub mod chunker;
+pub mod hnsw;
+pub mod rag;
 pub mod search;
 pub mod store;
 
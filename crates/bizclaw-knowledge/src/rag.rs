@@ -0,0 +1,634 @@
+//! Semantic retrieval — the embedding half of the knowledge base.
+//!
+//! Where [`KnowledgeStore`](crate::KnowledgeStore) matches keywords, a
+//! [`RagStore`] embeds every chunk with the configured provider (the local
+//! brain or an embeddings API) and retrieves by cosine similarity, so
+//! "giờ mở cửa?" finds the "opening hours" paragraph.
+//!
+//! Chunks and vectors live in SQLite; search goes through an
+//! [`HnswIndex`] persisted next to the database and rebuilt from it when
+//! missing or stale.
+
+use crate::chunker;
+use crate::hnsw::HnswIndex;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::Provider;
+use rusqlite::{Connection, params};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Texts sent to the embedder per request.
+const EMBED_BATCH: usize = 16;
+
+/// A stored chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RagChunk {
+    pub id: i64,
+    pub doc_id: i64,
+    pub doc_name: String,
+    pub chunk_idx: usize,
+    pub content: String,
+}
+
+/// A chunk with its similarity to the query.
+#[derive(Debug, Clone)]
+pub struct Retrieved {
+    pub chunk: RagChunk,
+    pub score: f32,
+}
+
+/// Restricts retrieval to some documents.
+#[derive(Debug, Clone, Default)]
+pub struct RetrievalFilter {
+    /// Only these documents, when set.
+    pub doc_ids: Option<HashSet<i64>>,
+    /// Documents must carry every one of these metadata values.
+    pub metadata: HashMap<String, String>,
+}
+
+impl RetrievalFilter {
+    pub fn with_document(mut self, doc_id: i64) -> Self {
+        self.doc_ids.get_or_insert_with(HashSet::new).insert(doc_id);
+        self
+    }
+
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.doc_ids.is_none() && self.metadata.is_empty()
+    }
+
+    fn matches(&self, doc_id: i64, metadata: Option<&HashMap<String, String>>) -> bool {
+        if let Some(ids) = &self.doc_ids
+            && !ids.contains(&doc_id)
+        {
+            return false;
+        }
+        self.metadata
+            .iter()
+            .all(|(k, v)| metadata.and_then(|m| m.get(k)) == Some(v))
+    }
+}
+
+/// Embedded document chunks with similarity search.
+pub struct RagStore {
+    conn: Mutex<Connection>,
+    embedder: Arc<dyn Provider>,
+    index: RwLock<HnswIndex>,
+    /// Where the index is persisted; `None` for in-memory stores.
+    index_path: Option<PathBuf>,
+    /// Metadata per document, for filtering without a database round-trip.
+    doc_metadata: RwLock<HashMap<i64, HashMap<String, String>>>,
+    chunk_chars: usize,
+    overlap_chars: usize,
+}
+
+impl RagStore {
+    /// Open or create a store at `path`, embedding with `embedder`. The
+    /// vector index is kept next to it, with an `.hnsw` extension.
+    pub fn open(path: &Path, embedder: Arc<dyn Provider>) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path).map_err(db_err)?;
+        Self::init(conn, embedder, Some(path.with_extension("hnsw")))
+    }
+
+    /// A throwaway store, for tests.
+    pub fn in_memory(embedder: Arc<dyn Provider>) -> Result<Self> {
+        Self::init(
+            Connection::open_in_memory().map_err(db_err)?,
+            embedder,
+            None,
+        )
+    }
+
+    /// Default path: `~/.bizclaw/rag.db`.
+    pub fn default_path() -> PathBuf {
+        bizclaw_core::config::BizClawConfig::home_dir().join("rag.db")
+    }
+
+    fn init(
+        conn: Connection,
+        embedder: Arc<dyn Provider>,
+        index_path: Option<PathBuf>,
+    ) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS rag_documents (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                source TEXT DEFAULT '',
+                created_at TEXT DEFAULT (datetime('now')),
+                chunk_count INTEGER DEFAULT 0,
+                metadata TEXT DEFAULT '{}'
+            );
+            CREATE TABLE IF NOT EXISTS rag_chunks (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                doc_id INTEGER NOT NULL REFERENCES rag_documents(id),
+                chunk_idx INTEGER NOT NULL,
+                content TEXT NOT NULL,
+                embedding BLOB NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_rag_chunks_doc ON rag_chunks(doc_id);",
+        )
+        .map_err(db_err)?;
+        // Stores created before metadata filters lack the column.
+        conn.execute_batch("ALTER TABLE rag_documents ADD COLUMN metadata TEXT DEFAULT '{}';")
+            .ok();
+
+        let doc_metadata = {
+            let mut stmt = conn
+                .prepare("SELECT id, metadata FROM rag_documents")
+                .map_err(db_err)?;
+            let rows = stmt
+                .query_map([], |row| {
+                    let json: Option<String> = row.get(1)?;
+                    let metadata = json
+                        .and_then(|j| serde_json::from_str(&j).ok())
+                        .unwrap_or_default();
+                    Ok((row.get::<_, i64>(0)?, metadata))
+                })
+                .map_err(db_err)?;
+            rows.collect::<std::result::Result<HashMap<_, _>, _>>()
+                .map_err(db_err)?
+        };
+
+        let chunk_count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM rag_chunks", [], |r| r.get(0))
+            .map_err(db_err)?;
+        let index = match index_path.as_deref().map(HnswIndex::load) {
+            Some(Ok(index)) if index.len() == chunk_count as usize => index,
+            _ => {
+                let index = rebuild_index(&conn)?;
+                if let Some(path) = &index_path
+                    && let Err(e) = index.save(path)
+                {
+                    tracing::warn!("Failed to save vector index: {e}");
+                }
+                index
+            }
+        };
+        tracing::debug!("📚 RAG store opened: {} vectors", index.len());
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            embedder,
+            index: RwLock::new(index),
+            index_path,
+            doc_metadata: RwLock::new(doc_metadata),
+            chunk_chars: 800,
+            overlap_chars: 120,
+        })
+    }
+
+    /// Target chunk length; overlap between chunks is 15% of it.
+    pub fn with_chunk_size(mut self, chars: usize) -> Self {
+        self.chunk_chars = chars.max(100);
+        self.overlap_chars = self.chunk_chars * 15 / 100;
+        self
+    }
+
+    /// Read, chunk, embed and store a file (text, markdown, JSON or PDF).
+    pub async fn ingest_file(&self, path: &Path) -> Result<i64> {
+        let text = chunker::read_document(path).map_err(BizClawError::Other)?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.ingest_text(&name, &text, &path.display().to_string(), HashMap::new())
+            .await
+    }
+
+    /// Chunk, embed and store a document. Returns its id.
+    pub async fn ingest(&self, name: &str, content: &str, source: &str) -> Result<i64> {
+        self.ingest_with_metadata(name, content, source, HashMap::new())
+            .await
+    }
+
+    /// Like [`ingest`](Self::ingest), tagging the document with metadata
+    /// (e.g. `lang = vi`, `branch = hanoi`) for [`RetrievalFilter`]s.
+    pub async fn ingest_with_metadata(
+        &self,
+        name: &str,
+        content: &str,
+        source: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<i64> {
+        let text = chunker::extract_text(content, name);
+        self.ingest_text(name, &text, source, metadata).await
+    }
+
+    async fn ingest_text(
+        &self,
+        name: &str,
+        text: &str,
+        source: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<i64> {
+        let chunks = chunker::chunk_with_overlap(text, self.chunk_chars, self.overlap_chars);
+        if chunks.is_empty() {
+            return Err(BizClawError::Other(format!(
+                "'{name}' has no text to index"
+            )));
+        }
+
+        // Embed everything before touching the database, so a failed
+        // embedding request leaves no half-indexed document behind.
+        let mut embeddings = Vec::with_capacity(chunks.len());
+        for batch in chunks.chunks(EMBED_BATCH) {
+            let vectors = self.embedder.embed(batch).await?;
+            if vectors.len() != batch.len() {
+                return Err(BizClawError::Provider(format!(
+                    "{} returned {} embeddings for {} chunks",
+                    self.embedder.name(),
+                    vectors.len(),
+                    batch.len()
+                )));
+            }
+            embeddings.extend(vectors);
+        }
+        let dim = self.index.read().unwrap().dim();
+        if let Some(bad) = embeddings
+            .iter()
+            .find(|v| v.is_empty() || (dim != 0 && v.len() != dim))
+        {
+            return Err(BizClawError::Provider(format!(
+                "{} returned a {}-dimensional embedding; the index holds {dim}. \
+                 Re-index after changing the embedding model.",
+                self.embedder.name(),
+                bad.len()
+            )));
+        }
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_err)?;
+        tx.execute(
+            "INSERT INTO rag_documents (name, source, chunk_count, metadata)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                name,
+                source,
+                chunks.len() as i64,
+                serde_json::to_string(&metadata).unwrap_or_default()
+            ],
+        )
+        .map_err(db_err)?;
+        let doc_id = tx.last_insert_rowid();
+
+        let mut added = Vec::with_capacity(chunks.len());
+        for (idx, (chunk, vector)) in chunks.iter().zip(embeddings).enumerate() {
+            tx.execute(
+                "INSERT INTO rag_chunks (doc_id, chunk_idx, content, embedding)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![doc_id, idx as i64, chunk, encode(&vector)],
+            )
+            .map_err(db_err)?;
+            added.push((tx.last_insert_rowid(), vector));
+        }
+        tx.commit().map_err(db_err)?;
+        drop(conn);
+
+        self.doc_metadata.write().unwrap().insert(doc_id, metadata);
+        {
+            let mut index = self.index.write().unwrap();
+            for (id, vector) in &added {
+                // Dimensions were checked above.
+                let _ = index.insert(*id, doc_id, vector);
+            }
+        }
+        self.save_index();
+        tracing::info!("📄 Embedded '{name}' → {} chunks", chunks.len());
+        Ok(doc_id)
+    }
+
+    /// The `k` chunks most similar to `query`, best first, scoring at least
+    /// `min_score`.
+    pub async fn retrieve(&self, query: &str, k: usize, min_score: f32) -> Result<Vec<Retrieved>> {
+        self.retrieve_filtered(query, k, min_score, &RetrievalFilter::default())
+            .await
+    }
+
+    /// Like [`retrieve`](Self::retrieve), only from documents matching `filter`.
+    pub async fn retrieve_filtered(
+        &self,
+        query: &str,
+        k: usize,
+        min_score: f32,
+        filter: &RetrievalFilter,
+    ) -> Result<Vec<Retrieved>> {
+        if k == 0 || query.trim().is_empty() || self.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query_vec = self
+            .embedder
+            .embed(&[query.to_string()])
+            .await?
+            .pop()
+            .unwrap_or_default();
+
+        let scored: Vec<(i64, f32)> = {
+            let index = self.index.read().unwrap();
+            if filter.is_empty() {
+                index.search(&query_vec, k)
+            } else {
+                let metadata = self.doc_metadata.read().unwrap();
+                index.search_filtered(&query_vec, k, |doc_id| {
+                    filter.matches(doc_id, metadata.get(&doc_id))
+                })
+            }
+        };
+
+        let conn = self.conn.lock().unwrap();
+        let mut results = Vec::with_capacity(scored.len());
+        for (id, score) in scored.into_iter().filter(|(_, s)| *s >= min_score) {
+            let chunk = conn
+                .query_row(
+                    "SELECT c.id, c.doc_id, d.name, c.chunk_idx, c.content
+                     FROM rag_chunks c JOIN rag_documents d ON d.id = c.doc_id
+                     WHERE c.id = ?1",
+                    params![id],
+                    |row| {
+                        Ok(RagChunk {
+                            id: row.get(0)?,
+                            doc_id: row.get(1)?,
+                            doc_name: row.get(2)?,
+                            chunk_idx: row.get::<_, i64>(3)? as usize,
+                            content: row.get(4)?,
+                        })
+                    },
+                )
+                .map_err(db_err)?;
+            results.push(Retrieved { chunk, score });
+        }
+        Ok(results)
+    }
+
+    /// Remove a document and its chunks. Returns whether it existed.
+    pub fn remove_document(&self, doc_id: i64) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM rag_chunks WHERE doc_id = ?1", params![doc_id])
+            .map_err(db_err)?;
+        let removed = conn
+            .execute("DELETE FROM rag_documents WHERE id = ?1", params![doc_id])
+            .map_err(db_err)?;
+        drop(conn);
+
+        self.doc_metadata.write().unwrap().remove(&doc_id);
+        {
+            let mut index = self.index.write().unwrap();
+            index.remove_document(doc_id);
+            if index.needs_compaction() {
+                index.compact();
+            }
+        }
+        self.save_index();
+        Ok(removed > 0)
+    }
+
+    /// All documents as `(id, name, source, chunk_count)`, newest first.
+    pub fn list_documents(&self) -> Vec<(i64, String, String, i64)> {
+        let conn = self.conn.lock().unwrap();
+        let Ok(mut stmt) = conn
+            .prepare("SELECT id, name, source, chunk_count FROM rag_documents ORDER BY id DESC")
+        else {
+            return Vec::new();
+        };
+        stmt.query_map([], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .map(|rows| rows.filter_map(|r| r.ok()).collect())
+        .unwrap_or_default()
+    }
+
+    /// Number of indexed chunks.
+    pub fn len(&self) -> usize {
+        self.index.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn save_index(&self) {
+        if let Some(path) = &self.index_path
+            && let Err(e) = self.index.read().unwrap().save(path)
+        {
+            tracing::warn!("Failed to save vector index: {e}");
+        }
+    }
+}
+
+/// Build the index from the vectors stored in the database.
+fn rebuild_index(conn: &Connection) -> Result<HnswIndex> {
+    let mut stmt = conn
+        .prepare("SELECT id, doc_id, embedding FROM rag_chunks ORDER BY id")
+        .map_err(db_err)?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                decode(&row.get::<_, Vec<u8>>(2)?),
+            ))
+        })
+        .map_err(db_err)?;
+    let mut index = HnswIndex::default();
+    for row in rows {
+        let (id, doc_id, vector) = row.map_err(db_err)?;
+        if let Err(e) = index.insert(id, doc_id, &vector) {
+            tracing::warn!("Skipping chunk {id}: {e}");
+        }
+    }
+    Ok(index)
+}
+
+/// Format retrieved chunks as a prompt block, citing the source document.
+pub fn format_context(results: &[Retrieved]) -> String {
+    if results.is_empty() {
+        return String::new();
+    }
+    let mut ctx = String::from("[Knowledge Base]\n");
+    for (i, r) in results.iter().enumerate() {
+        ctx.push_str(&format!(
+            "[{}] ({}) {}\n\n",
+            i + 1,
+            r.chunk.doc_name,
+            r.chunk.content.trim()
+        ));
+    }
+    ctx.push_str("[End knowledge]");
+    ctx
+}
+
+fn encode(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+fn db_err(e: rusqlite::Error) -> BizClawError {
+    BizClawError::Memory(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bizclaw_core::traits::provider::GenerateParams;
+    use bizclaw_core::types::{Message, ModelInfo, ProviderResponse, ToolDefinition};
+
+    /// Embeds text as keyword counts over a tiny fixed vocabulary.
+    struct KeywordEmbedder;
+
+    const VOCAB: [&str; 4] = ["hours", "price", "shipping", "refund"];
+
+    #[async_trait]
+    impl Provider for KeywordEmbedder {
+        fn name(&self) -> &str {
+            "keywords"
+        }
+        async fn chat(
+            &self,
+            _messages: &[Message],
+            _tools: &[ToolDefinition],
+            _params: &GenerateParams,
+        ) -> Result<ProviderResponse> {
+            Ok(ProviderResponse::text(""))
+        }
+        async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+            Ok(vec![])
+        }
+        async fn health_check(&self) -> Result<bool> {
+            Ok(true)
+        }
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            Ok(texts
+                .iter()
+                .map(|t| {
+                    let t = t.to_lowercase();
+                    VOCAB.iter().map(|w| t.matches(w).count() as f32).collect()
+                })
+                .collect())
+        }
+    }
+
+    fn store() -> RagStore {
+        RagStore::in_memory(Arc::new(KeywordEmbedder))
+            .unwrap()
+            .with_chunk_size(100)
+    }
+
+    #[tokio::test]
+    async fn test_ingest_and_retrieve() {
+        let store = store();
+        let doc = "Opening hours: 8am to 9pm, hours may change on holidays.\n\n\
+                   Shipping is free over 500k. Shipping takes 2 days.\n\n\
+                   Refund within 7 days with receipt; refund goes to the original card.";
+        let doc_id = store.ingest("faq.md", doc, "upload").await.unwrap();
+        assert!(store.len() >= 3);
+
+        let hits = store
+            .retrieve("what are your hours?", 1, 0.5)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].chunk.content.contains("Opening hours"));
+        assert_eq!(hits[0].chunk.doc_name, "faq.md");
+
+        let hits = store.retrieve("refund please", 3, 0.5).await.unwrap();
+        assert!(hits[0].chunk.content.contains("Refund"));
+        assert!(format_context(&hits).contains("(faq.md)"));
+
+        // Nothing about prices was ingested.
+        assert!(store.retrieve("price", 3, 0.5).await.unwrap().is_empty());
+
+        assert!(store.remove_document(doc_id).unwrap());
+        assert!(store.is_empty());
+        assert!(store.retrieve("hours", 3, 0.0).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_vectors_survive_reopen() {
+        let path = std::env::temp_dir().join(format!("bizclaw-rag-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let store = RagStore::open(&path, Arc::new(KeywordEmbedder)).unwrap();
+            store
+                .ingest("a.txt", "Shipping is fast.", "test")
+                .await
+                .unwrap();
+        }
+        let index_path = path.with_extension("hnsw");
+        assert!(index_path.exists());
+
+        let store = RagStore::open(&path, Arc::new(KeywordEmbedder)).unwrap();
+        assert_eq!(store.len(), 1);
+        let hits = store.retrieve("shipping", 1, 0.5).await.unwrap();
+        assert_eq!(hits[0].chunk.content, "Shipping is fast.");
+        assert_eq!(store.list_documents().len(), 1);
+        drop(store);
+
+        // A lost index is rebuilt from the database.
+        std::fs::remove_file(&index_path).unwrap();
+        let store = RagStore::open(&path, Arc::new(KeywordEmbedder)).unwrap();
+        assert_eq!(store.len(), 1);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&index_path);
+    }
+
+    #[tokio::test]
+    async fn test_metadata_and_document_filters() {
+        let store = store();
+        let hanoi = store
+            .ingest_with_metadata(
+                "hanoi.txt",
+                "Hanoi shipping: same day.",
+                "test",
+                HashMap::from([("branch".into(), "hanoi".into())]),
+            )
+            .await
+            .unwrap();
+        let saigon = store
+            .ingest_with_metadata(
+                "saigon.txt",
+                "Saigon shipping: next day.",
+                "test",
+                HashMap::from([("branch".into(), "saigon".into())]),
+            )
+            .await
+            .unwrap();
+
+        let filter = RetrievalFilter::default().with_metadata("branch", "saigon");
+        let hits = store
+            .retrieve_filtered("shipping", 5, 0.5, &filter)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk.doc_id, saigon);
+
+        let filter = RetrievalFilter::default().with_document(hanoi);
+        let hits = store
+            .retrieve_filtered("shipping", 5, 0.5, &filter)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chunk.doc_name, "hanoi.txt");
+
+        assert_eq!(store.retrieve("shipping", 5, 0.5).await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_vector_encoding_roundtrip() {
+        let v = vec![0.5, -1.25, 3.0];
+        assert_eq!(decode(&encode(&v)), v);
+    }
+}
@@ -0,0 +1,409 @@
+//! Analytics store — one row per answered message, persisted in SQLite.
+//!
+//! Each row has the message's channel, thread, sender, persona, model,
+//! latency, tokens and tools, and the feedback the sender gave on the reply
+//! afterwards. [`AnalyticsStore::report`] rolls them up per day or per week
+//! for people who want to know how the bot is doing rather than what it
+//! said; no message text is stored. Days and weeks are in UTC, and weeks
+//! start on Monday.
+
+use crate::db_err;
+use bizclaw_core::error::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+const POSITIVE_REACTIONS: &[char] = &['👍', '❤', '😍', '🥰', '👏', '🙏', '😊', '💯', '✅'];
+const NEGATIVE_REACTIONS: &[char] = &['👎', '😡', '😠', '😞', '🙁', '☹', '❌'];
+
+/// One answered message, as recorded.
+#[derive(Debug, Clone, Default)]
+pub struct MessageRecord {
+    pub channel: String,
+    pub thread_id: String,
+    /// Empty for messages nobody asked for, e.g. scheduled posts.
+    pub user_id: String,
+    /// Empty without a persona.
+    pub persona: String,
+    pub model: String,
+    pub latency_ms: u64,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    /// Tools run, in order.
+    pub tools: Vec<String>,
+    /// Whether a reply was produced.
+    pub success: bool,
+}
+
+/// The length of a report's rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportPeriod {
+    Day,
+    Week,
+}
+
+impl ReportPeriod {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "day" | "daily" => Some(Self::Day),
+            "week" | "weekly" => Some(Self::Week),
+            _ => None,
+        }
+    }
+
+    /// The period's first day, `YYYY-MM-DD`.
+    fn column(self) -> &'static str {
+        match self {
+            Self::Day => "substr(timestamp, 1, 10)",
+            Self::Week => "date(substr(timestamp, 1, 10), 'weekday 0', '-6 days')",
+        }
+    }
+}
+
+/// What a report's rows are split by, besides the period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnalyticsGroup {
+    Channel,
+    Persona,
+    Model,
+}
+
+impl AnalyticsGroup {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "channel" => Some(Self::Channel),
+            "persona" => Some(Self::Persona),
+            "model" => Some(Self::Model),
+            _ => None,
+        }
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            Self::Channel => "channel",
+            Self::Persona => "persona",
+            Self::Model => "model",
+        }
+    }
+}
+
+/// The totals of one period, and group if any.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReportRow {
+    /// First day of the period, `YYYY-MM-DD`.
+    pub period: String,
+    /// The group's value; empty without a group.
+    pub key: String,
+    pub messages: u64,
+    pub users: u64,
+    pub threads: u64,
+    /// Messages that got no reply.
+    pub failures: u64,
+    pub avg_latency_ms: f64,
+    pub max_latency_ms: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub tool_calls: u64,
+    pub positive_feedback: u64,
+    pub negative_feedback: u64,
+}
+
+/// SQLite-backed per-message analytics.
+pub struct AnalyticsStore {
+    conn: Mutex<Connection>,
+}
+
+impl AnalyticsStore {
+    /// Open (or create) the store at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::init(Connection::open(path).map_err(db_err)?)
+    }
+
+    /// A throwaway store, for tests.
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(db_err)?)
+    }
+
+    /// `~/.bizclaw/analytics.db`
+    pub fn default_path() -> std::path::PathBuf {
+        bizclaw_core::config::BizClawConfig::home_dir().join("analytics.db")
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS messages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp TEXT NOT NULL,
+                channel TEXT NOT NULL,
+                thread_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                persona TEXT NOT NULL,
+                model TEXT NOT NULL,
+                latency_ms INTEGER NOT NULL,
+                prompt_tokens INTEGER NOT NULL,
+                completion_tokens INTEGER NOT NULL,
+                tools TEXT NOT NULL,
+                tool_calls INTEGER NOT NULL,
+                success INTEGER NOT NULL,
+                feedback INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS idx_messages_time ON messages(timestamp);
+            CREATE INDEX IF NOT EXISTS idx_messages_thread ON messages(channel, thread_id);",
+        )
+        .map_err(db_err)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Record a message answered now.
+    pub fn record(&self, record: &MessageRecord) -> Result<()> {
+        self.record_at(Utc::now(), record)
+    }
+
+    /// Record a message answered at `at`.
+    pub fn record_at(&self, at: DateTime<Utc>, record: &MessageRecord) -> Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO messages (timestamp, channel, thread_id, user_id, persona, model,
+                     latency_ms, prompt_tokens, completion_tokens, tools, tool_calls, success)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    timestamp(at),
+                    record.channel,
+                    record.thread_id,
+                    record.user_id,
+                    record.persona,
+                    record.model,
+                    record.latency_ms as i64,
+                    record.prompt_tokens,
+                    record.completion_tokens,
+                    record.tools.join(","),
+                    record.tools.len() as i64,
+                    record.success
+                ],
+            )
+            .map_err(db_err)?;
+        Ok(())
+    }
+
+    /// Set the feedback on the thread's last reply: positive above 0,
+    /// negative below. Returns whether the thread has a reply.
+    pub fn record_feedback(&self, channel: &str, thread_id: &str, score: i8) -> Result<bool> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE messages SET feedback = ?3 WHERE id = (
+                    SELECT id FROM messages WHERE channel = ?1 AND thread_id = ?2 AND success
+                    ORDER BY id DESC LIMIT 1)",
+                params![channel, thread_id, score.signum()],
+            )
+            .map(|n| n > 0)
+            .map_err(db_err)
+    }
+
+    /// Totals per period since `since`, split by `group` if given, oldest
+    /// period first.
+    pub fn report(
+        &self,
+        period: ReportPeriod,
+        since: DateTime<Utc>,
+        group: Option<AnalyticsGroup>,
+    ) -> Result<Vec<ReportRow>> {
+        let key = group.map_or("''", AnalyticsGroup::column);
+        let sql = format!(
+            "SELECT {period} AS bucket, {key} AS grp, COUNT(*),
+                    COUNT(DISTINCT CASE WHEN user_id != '' THEN channel || ':' || user_id END),
+                    COUNT(DISTINCT channel || ':' || thread_id),
+                    SUM(NOT success), AVG(latency_ms), MAX(latency_ms),
+                    SUM(prompt_tokens), SUM(completion_tokens), SUM(tool_calls),
+                    SUM(feedback > 0), SUM(feedback < 0)
+             FROM messages WHERE timestamp >= ?1
+             GROUP BY bucket, grp
+             ORDER BY bucket, COUNT(*) DESC",
+            period = period.column()
+        );
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&sql).map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![timestamp(since)], |row| {
+                Ok(ReportRow {
+                    period: row.get(0)?,
+                    key: row.get(1)?,
+                    messages: row.get(2)?,
+                    users: row.get(3)?,
+                    threads: row.get(4)?,
+                    failures: row.get(5)?,
+                    avg_latency_ms: row.get(6)?,
+                    max_latency_ms: row.get(7)?,
+                    prompt_tokens: row.get(8)?,
+                    completion_tokens: row.get(9)?,
+                    tool_calls: row.get(10)?,
+                    positive_feedback: row.get::<_, Option<u64>>(11)?.unwrap_or(0),
+                    negative_feedback: row.get::<_, Option<u64>>(12)?.unwrap_or(0),
+                })
+            })
+            .map_err(db_err)?;
+        rows.collect::<std::result::Result<_, _>>().map_err(db_err)
+    }
+
+    /// How often each tool ran since `since`, most used first.
+    pub fn tool_counts(&self, since: DateTime<Utc>) -> Result<Vec<(String, u64)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT tools FROM messages WHERE timestamp >= ?1 AND tools != ''")
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![timestamp(since)], |row| row.get::<_, String>(0))
+            .map_err(db_err)?;
+        let mut counts = BTreeMap::<String, u64>::new();
+        for tools in rows {
+            for tool in tools.map_err(db_err)?.split(',') {
+                *counts.entry(tool.to_string()).or_default() += 1;
+            }
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by_key(|c| std::cmp::Reverse(c.1));
+        Ok(counts)
+    }
+
+    /// Delete rows older than `days` days. Returns the number removed.
+    pub fn prune(&self, days: u32) -> Result<usize> {
+        let cutoff = timestamp(days_ago(days));
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM messages WHERE timestamp < ?1", params![cutoff])
+            .map_err(db_err)
+    }
+}
+
+/// The start of the last `days` days, for [`AnalyticsStore::report`].
+pub fn days_ago(days: u32) -> DateTime<Utc> {
+    Utc::now() - chrono::Duration::days(days.into())
+}
+
+/// The feedback a message made only of reaction emoji gives: 1 for 👍 and
+/// the like, -1 for 👎 and the like, `None` for anything else.
+pub fn reaction_score(text: &str) -> Option<i8> {
+    // Variation selectors, skin tones and spaces don't change the meaning.
+    let mut signs = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '\u{fe0f}' | '\u{1f3fb}'..='\u{1f3ff}'))
+        .peekable();
+    signs.peek()?;
+    let mut score = None;
+    for c in signs {
+        let sign = if POSITIVE_REACTIONS.contains(&c) {
+            1
+        } else if NEGATIVE_REACTIONS.contains(&c) {
+            -1
+        } else {
+            return None;
+        };
+        if score.is_some_and(|s| s != sign) {
+            return None;
+        }
+        score = Some(sign);
+    }
+    score
+}
+
+fn timestamp(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record(channel: &str, thread_id: &str, latency_ms: u64, tools: &[&str]) -> MessageRecord {
+        MessageRecord {
+            channel: channel.into(),
+            thread_id: thread_id.into(),
+            user_id: format!("u{thread_id}"),
+            model: "gpt-4o-mini".into(),
+            latency_ms,
+            prompt_tokens: 100,
+            completion_tokens: 20,
+            tools: tools.iter().map(|t| t.to_string()).collect(),
+            success: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let store = AnalyticsStore::in_memory().unwrap();
+        // Wednesday, Sunday and the next Monday.
+        let wed = Utc.with_ymd_and_hms(2026, 10, 14, 9, 0, 0).unwrap();
+        let sun = Utc.with_ymd_and_hms(2026, 10, 18, 23, 0, 0).unwrap();
+        let mon = Utc.with_ymd_and_hms(2026, 10, 19, 1, 0, 0).unwrap();
+        store
+            .record_at(wed, &record("zalo", "1", 400, &["web_search"]))
+            .unwrap();
+        store
+            .record_at(wed, &record("zalo", "1", 800, &[]))
+            .unwrap();
+        store
+            .record_at(sun, &record("telegram", "2", 300, &["web_search", "calc"]))
+            .unwrap();
+        store
+            .record_at(
+                mon,
+                &MessageRecord {
+                    success: false,
+                    ..record("zalo", "3", 5000, &[])
+                },
+            )
+            .unwrap();
+        assert!(store.record_feedback("zalo", "1", 1).unwrap());
+        assert!(store.record_feedback("telegram", "2", -3).unwrap());
+        assert!(!store.record_feedback("zalo", "3", 1).unwrap());
+
+        let weekly = store.report(ReportPeriod::Week, wed, None).unwrap();
+        assert_eq!(weekly.len(), 2);
+        let first = &weekly[0];
+        assert_eq!(first.period, "2026-10-12");
+        assert_eq!((first.messages, first.users, first.threads), (3, 2, 2));
+        assert_eq!(first.avg_latency_ms, 500.0);
+        assert_eq!((first.tool_calls, first.completion_tokens), (3, 60));
+        assert_eq!((first.positive_feedback, first.negative_feedback), (1, 1));
+        assert_eq!(
+            (weekly[1].period.as_str(), weekly[1].failures),
+            ("2026-10-19", 1)
+        );
+
+        let daily = store
+            .report(ReportPeriod::Day, sun, Some(AnalyticsGroup::Channel))
+            .unwrap();
+        let keys: Vec<_> = daily
+            .iter()
+            .map(|r| (r.period.as_str(), r.key.as_str()))
+            .collect();
+        assert_eq!(keys, [("2026-10-18", "telegram"), ("2026-10-19", "zalo")]);
+
+        let tools = store.tool_counts(wed).unwrap();
+        assert_eq!(tools[0], ("web_search".to_string(), 2));
+    }
+
+    #[test]
+    fn test_reaction_score() {
+        assert_eq!(reaction_score("👍"), Some(1));
+        assert_eq!(reaction_score("👍🏽 ❤️"), Some(1));
+        assert_eq!(reaction_score("👎"), Some(-1));
+        assert_eq!(reaction_score("👍👎"), None);
+        assert_eq!(reaction_score("👍 thanks"), None);
+        assert_eq!(reaction_score(" "), None);
+    }
+}
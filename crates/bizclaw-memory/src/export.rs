@@ -0,0 +1,156 @@
+//! Conversation export and import.
+//!
+//! A thread's stored turns are exported as JSON, to move them to another
+//! deployment and [`import`](ThreadExport::import_into) them there, or as
+//! Markdown for people to read, e.g. when answering a data-access request.
+//! Exports carry a format version; newer ones than this build understands
+//! are refused rather than half-imported.
+
+use crate::history::{HistoryStore, HistoryTurn};
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::types::Role;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Version of the JSON export format.
+pub const EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Markdown,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "markdown" | "md" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+/// Everything stored for one thread.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThreadExport {
+    pub version: u32,
+    pub channel: String,
+    pub thread_id: String,
+    pub exported_at: DateTime<Utc>,
+    pub turns: Vec<HistoryTurn>,
+}
+
+impl ThreadExport {
+    /// Every turn stored for `channel:thread_id`.
+    pub fn from_store(store: &HistoryStore, channel: &str, thread_id: &str) -> Result<Self> {
+        Ok(Self {
+            version: EXPORT_VERSION,
+            channel: channel.to_string(),
+            thread_id: thread_id.to_string(),
+            exported_at: Utc::now(),
+            turns: store.thread(channel, thread_id)?,
+        })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let export: Self = serde_json::from_str(json)?;
+        if export.version > EXPORT_VERSION {
+            return Err(BizClawError::Memory(format!(
+                "Export format version {} is newer than the supported {EXPORT_VERSION}",
+                export.version
+            )));
+        }
+        Ok(export)
+    }
+
+    pub fn render(&self, format: ExportFormat) -> Result<String> {
+        match format {
+            ExportFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            ExportFormat::Markdown => Ok(self.to_markdown()),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "# Conversation {}:{}\n\nExported {} · {} messages\n",
+            self.channel,
+            self.thread_id,
+            time(self.exported_at),
+            self.turns.len()
+        );
+        for turn in &self.turns {
+            let speaker = match turn.role {
+                Role::User => "User",
+                Role::Assistant => "Assistant",
+                Role::System => "System",
+                Role::Tool => "Tool",
+            };
+            out.push_str(&format!(
+                "\n**{speaker}** · {}\n\n{}\n",
+                time(turn.created_at),
+                turn.content.trim()
+            ));
+        }
+        out
+    }
+
+    /// Add the turns to the thread in `store`, after those it has, or
+    /// instead of them with `replace`. Returns the number of turns added.
+    pub fn import_into(&self, store: &HistoryStore, replace: bool) -> Result<usize> {
+        if replace {
+            store.clear(&self.channel, &self.thread_id)?;
+        }
+        store.import(&self.channel, &self.thread_id, &self.turns)
+    }
+}
+
+fn time(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let source = HistoryStore::in_memory().unwrap();
+        source.append("zalo", "9", Role::User, "xin chào").unwrap();
+        source
+            .append("zalo", "9", Role::Assistant, "Chào bạn!")
+            .unwrap();
+        let export = ThreadExport::from_store(&source, "zalo", "9").unwrap();
+
+        let markdown = export.render(ExportFormat::Markdown).unwrap();
+        assert!(markdown.starts_with("# Conversation zalo:9\n"));
+        assert!(markdown.contains("**Assistant** · "));
+        assert!(markdown.contains("\n\nChào bạn!\n"));
+
+        let json = export.render(ExportFormat::Json).unwrap();
+        let imported = ThreadExport::from_json(&json).unwrap();
+        assert_eq!(imported, export);
+
+        let target = HistoryStore::in_memory().unwrap();
+        target.append("zalo", "9", Role::User, "old").unwrap();
+        assert_eq!(imported.import_into(&target, true).unwrap(), 2);
+        assert_eq!(target.thread("zalo", "9").unwrap(), export.turns);
+
+        let newer = json.replacen("\"version\": 1", "\"version\": 2", 1);
+        assert!(ThreadExport::from_json(&newer).is_err());
+    }
+}
@@ -0,0 +1,280 @@
+//! Fact store — long-term memory about each user, persisted in SQLite.
+//!
+//! Facts are short statements the agent extracted from conversations
+//! ("prefers invoices in English"), kept per `(channel, user_id)` with their
+//! embedding. A user has at most a few hundred, so recall scores all of
+//! them rather than keeping an index. Remembering something close to a
+//! known fact replaces it, so corrections ("now prefers Vietnamese") don't
+//! pile up next to what they correct.
+
+use crate::db_err;
+use crate::vector::cosine_similarity;
+use bizclaw_core::error::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{Connection, params};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Similarity above which a new fact replaces an old one.
+const DUPLICATE_SCORE: f32 = 0.9;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fact {
+    pub id: i64,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+    /// Last time the fact was extracted again or replaced.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// SQLite-backed per-user facts.
+pub struct FactStore {
+    conn: Mutex<Connection>,
+    /// Facts kept per user; 0 keeps everything.
+    max_per_user: usize,
+}
+
+impl FactStore {
+    /// Open (or create) the store at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::init(Connection::open(path).map_err(db_err)?)
+    }
+
+    /// A throwaway store, for tests.
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(db_err)?)
+    }
+
+    /// `~/.bizclaw/facts.db`
+    pub fn default_path() -> std::path::PathBuf {
+        bizclaw_core::config::BizClawConfig::home_dir().join("facts.db")
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS facts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                channel TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                text TEXT NOT NULL,
+                embedding BLOB NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_facts_user ON facts(channel, user_id);",
+        )
+        .map_err(db_err)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            max_per_user: 100,
+        })
+    }
+
+    pub fn with_max_per_user(mut self, max_per_user: usize) -> Self {
+        self.max_per_user = max_per_user;
+        self
+    }
+
+    /// Store a fact, replacing a near-duplicate if the user has one.
+    /// Returns the fact's id.
+    pub fn remember(
+        &self,
+        channel: &str,
+        user_id: &str,
+        text: &str,
+        embedding: &[f32],
+    ) -> Result<i64> {
+        let now = timestamp(Utc::now());
+        let duplicate = self
+            .scored(channel, user_id, embedding)?
+            .into_iter()
+            .find(|(_, score)| *score >= DUPLICATE_SCORE)
+            .map(|(fact, _)| fact.id);
+
+        let conn = self.conn.lock().unwrap();
+        let id = match duplicate {
+            Some(id) => {
+                conn.execute(
+                    "UPDATE facts SET text = ?1, embedding = ?2, updated_at = ?3 WHERE id = ?4",
+                    params![text, encode(embedding), now, id],
+                )
+                .map_err(db_err)?;
+                id
+            }
+            None => {
+                conn.execute(
+                    "INSERT INTO facts (channel, user_id, text, embedding, created_at, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                    params![channel, user_id, text, encode(embedding), now],
+                )
+                .map_err(db_err)?;
+                conn.last_insert_rowid()
+            }
+        };
+
+        if self.max_per_user > 0 {
+            conn.execute(
+                "DELETE FROM facts WHERE channel = ?1 AND user_id = ?2 AND id NOT IN (
+                    SELECT id FROM facts WHERE channel = ?1 AND user_id = ?2
+                    ORDER BY updated_at DESC, id DESC LIMIT ?3)",
+                params![channel, user_id, self.max_per_user as i64],
+            )
+            .map_err(db_err)?;
+        }
+        Ok(id)
+    }
+
+    /// The user's `k` facts most similar to `query`, best first, scoring
+    /// at least `min_score`.
+    pub fn recall(
+        &self,
+        channel: &str,
+        user_id: &str,
+        query: &[f32],
+        k: usize,
+        min_score: f32,
+    ) -> Result<Vec<(Fact, f32)>> {
+        let mut scored = self.scored(channel, user_id, query)?;
+        scored.retain(|(_, score)| *score >= min_score);
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(k);
+        Ok(scored)
+    }
+
+    /// Every fact about the user, oldest first.
+    pub fn list(&self, channel: &str, user_id: &str) -> Result<Vec<Fact>> {
+        Ok(self
+            .scored(channel, user_id, &[])?
+            .into_iter()
+            .map(|(fact, _)| fact)
+            .collect())
+    }
+
+    /// Delete one fact about the user. Returns whether it existed.
+    pub fn forget(&self, channel: &str, user_id: &str, id: i64) -> Result<bool> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "DELETE FROM facts WHERE channel = ?1 AND user_id = ?2 AND id = ?3",
+                params![channel, user_id, id],
+            )
+            .map(|n| n > 0)
+            .map_err(db_err)
+    }
+
+    /// Delete everything known about the user. Returns the number of facts.
+    pub fn clear(&self, channel: &str, user_id: &str) -> Result<usize> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "DELETE FROM facts WHERE channel = ?1 AND user_id = ?2",
+                params![channel, user_id],
+            )
+            .map_err(db_err)
+    }
+
+    /// The user's facts with their similarity to `query`, oldest first.
+    fn scored(&self, channel: &str, user_id: &str, query: &[f32]) -> Result<Vec<(Fact, f32)>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, text, embedding, created_at, updated_at FROM facts
+                 WHERE channel = ?1 AND user_id = ?2 ORDER BY id",
+            )
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![channel, user_id], |row| {
+                let fact = Fact {
+                    id: row.get(0)?,
+                    text: row.get(1)?,
+                    created_at: parse_time(&row.get::<_, String>(3)?),
+                    updated_at: parse_time(&row.get::<_, String>(4)?),
+                };
+                let score = cosine_similarity(query, &decode(&row.get::<_, Vec<u8>>(2)?));
+                Ok((fact, score))
+            })
+            .map_err(db_err)?;
+        rows.collect::<std::result::Result<_, _>>().map_err(db_err)
+    }
+}
+
+fn encode(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+fn timestamp(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn parse_time(text: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remember_and_recall() {
+        let store = FactStore::in_memory().unwrap().with_max_per_user(2);
+        store
+            .remember("zalo", "7", "Prefers invoices in English", &[1.0, 0.0, 0.0])
+            .unwrap();
+        store
+            .remember("zalo", "7", "Owns a bakery in Đà Nẵng", &[0.0, 1.0, 0.0])
+            .unwrap();
+        store
+            .remember("zalo", "8", "Vegetarian", &[1.0, 0.0, 0.0])
+            .unwrap();
+
+        let hits = store.recall("zalo", "7", &[0.9, 0.1, 0.0], 5, 0.5).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0.text, "Prefers invoices in English");
+
+        // Close enough to replace, rather than add.
+        let id = hits[0].0.id;
+        let replaced = store
+            .remember(
+                "zalo",
+                "7",
+                "Prefers invoices in Vietnamese",
+                &[0.99, 0.05, 0.0],
+            )
+            .unwrap();
+        assert_eq!(replaced, id);
+        assert_eq!(store.list("zalo", "7").unwrap().len(), 2);
+
+        // Over the cap, the least recently confirmed fact goes.
+        store
+            .remember("zalo", "7", "Closed on Mondays", &[0.0, 0.0, 1.0])
+            .unwrap();
+        let texts: Vec<_> = store
+            .list("zalo", "7")
+            .unwrap()
+            .into_iter()
+            .map(|f| f.text)
+            .collect();
+        assert_eq!(
+            texts,
+            ["Prefers invoices in Vietnamese", "Closed on Mondays"]
+        );
+
+        assert!(store.forget("zalo", "7", id).unwrap());
+        assert_eq!(store.clear("zalo", "7").unwrap(), 1);
+        assert_eq!(store.list("zalo", "8").unwrap().len(), 1);
+    }
+}
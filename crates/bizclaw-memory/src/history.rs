@@ -0,0 +1,337 @@
+//! Conversation history store — channel turns persisted in SQLite.
+//!
+//! Turns are keyed by `(channel, thread_id)` so the agent can rebuild a
+//! thread's context after a restart. Retention keeps the table bounded: a
+//! per-thread turn cap applied on every append, and an age limit applied by
+//! [`HistoryStore::prune`].
+
+use crate::db_err;
+use bizclaw_core::config::MemoryConfig;
+use bizclaw_core::error::Result;
+use bizclaw_core::types::{Message, Role};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// How much history to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Newest turns kept per thread; 0 keeps everything.
+    pub max_turns_per_thread: usize,
+    /// Turns older than this are pruned; `None` keeps them forever.
+    pub max_age: Option<chrono::Duration>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_turns_per_thread: 200,
+            max_age: Some(chrono::Duration::days(30)),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    pub fn from_config(config: &MemoryConfig) -> Self {
+        Self {
+            max_turns_per_thread: config.history_max_turns,
+            max_age: (config.history_retention_days > 0)
+                .then(|| chrono::Duration::days(config.history_retention_days.into())),
+        }
+    }
+}
+
+/// One stored turn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryTurn {
+    pub role: Role,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl HistoryTurn {
+    pub fn to_message(&self) -> Message {
+        Message {
+            role: self.role.clone(),
+            content: self.content.clone(),
+            name: None,
+            tool_call_id: None,
+            tool_calls: None,
+        }
+    }
+}
+
+/// SQLite-backed conversation history.
+pub struct HistoryStore {
+    conn: Mutex<Connection>,
+    retention: RetentionPolicy,
+}
+
+impl HistoryStore {
+    /// Open (or create) the store at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::init(Connection::open(path).map_err(db_err)?)
+    }
+
+    /// A throwaway store, for tests.
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(db_err)?)
+    }
+
+    /// `~/.bizclaw/history.db`
+    pub fn default_path() -> std::path::PathBuf {
+        bizclaw_core::config::BizClawConfig::home_dir().join("history.db")
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS turns (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                channel TEXT NOT NULL,
+                thread_id TEXT NOT NULL,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_turns_thread ON turns(channel, thread_id, id);
+            CREATE INDEX IF NOT EXISTS idx_turns_created ON turns(created_at);",
+        )
+        .map_err(db_err)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            retention: RetentionPolicy::default(),
+        })
+    }
+
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn retention(&self) -> RetentionPolicy {
+        self.retention
+    }
+
+    /// Record a turn, dropping the thread's oldest turns beyond the cap.
+    pub fn append(&self, channel: &str, thread_id: &str, role: Role, content: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO turns (channel, thread_id, role, content, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                channel,
+                thread_id,
+                role.to_string(),
+                content,
+                timestamp(Utc::now())
+            ],
+        )
+        .map_err(db_err)?;
+        self.apply_cap(&conn, channel, thread_id)
+    }
+
+    /// Add turns to a thread with their original timestamps, after the
+    /// turns it already has, e.g. from another deployment's export. The
+    /// per-thread cap applies as for [`append`](Self::append). Returns the
+    /// number of turns added.
+    pub fn import(&self, channel: &str, thread_id: &str, turns: &[HistoryTurn]) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(db_err)?;
+        for turn in turns {
+            tx.execute(
+                "INSERT INTO turns (channel, thread_id, role, content, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    channel,
+                    thread_id,
+                    turn.role.to_string(),
+                    turn.content,
+                    timestamp(turn.created_at)
+                ],
+            )
+            .map_err(db_err)?;
+        }
+        self.apply_cap(&tx, channel, thread_id)?;
+        tx.commit().map_err(db_err)?;
+        Ok(turns.len())
+    }
+
+    /// Drop the thread's oldest turns beyond the cap.
+    fn apply_cap(&self, conn: &Connection, channel: &str, thread_id: &str) -> Result<()> {
+        let cap = self.retention.max_turns_per_thread;
+        if cap > 0 {
+            conn.execute(
+                "DELETE FROM turns WHERE channel = ?1 AND thread_id = ?2 AND id NOT IN (
+                    SELECT id FROM turns WHERE channel = ?1 AND thread_id = ?2
+                    ORDER BY id DESC LIMIT ?3)",
+                params![channel, thread_id, cap as i64],
+            )
+            .map_err(db_err)?;
+        }
+        Ok(())
+    }
+
+    /// The newest `limit` turns of a thread, oldest first.
+    pub fn recent(&self, channel: &str, thread_id: &str, limit: usize) -> Result<Vec<HistoryTurn>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT role, content, created_at FROM turns
+                 WHERE channel = ?1 AND thread_id = ?2
+                 ORDER BY id DESC LIMIT ?3",
+            )
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![channel, thread_id, limit as i64], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })
+            .map_err(db_err)?;
+
+        let mut turns = Vec::new();
+        for row in rows {
+            let (role, content, created_at) = row.map_err(db_err)?;
+            let Some(role) = parse_role(&role) else {
+                continue;
+            };
+            let created_at = DateTime::parse_from_rfc3339(&created_at)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now());
+            turns.push(HistoryTurn {
+                role,
+                content,
+                created_at,
+            });
+        }
+        turns.reverse();
+        Ok(turns)
+    }
+
+    /// Every stored turn of a thread, oldest first.
+    pub fn thread(&self, channel: &str, thread_id: &str) -> Result<Vec<HistoryTurn>> {
+        self.recent(channel, thread_id, i64::MAX as usize)
+    }
+
+    /// Delete a thread's history. Returns the number of turns removed.
+    pub fn clear(&self, channel: &str, thread_id: &str) -> Result<usize> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "DELETE FROM turns WHERE channel = ?1 AND thread_id = ?2",
+                params![channel, thread_id],
+            )
+            .map_err(db_err)
+    }
+
+    /// Apply the age limit to every thread. Returns the number of turns removed.
+    pub fn prune(&self) -> Result<usize> {
+        let Some(max_age) = self.retention.max_age else {
+            return Ok(0);
+        };
+        let cutoff = timestamp(Utc::now() - max_age);
+        self.conn
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM turns WHERE created_at < ?1", params![cutoff])
+            .map_err(db_err)
+    }
+
+    /// Number of threads with stored turns.
+    pub fn thread_count(&self) -> Result<usize> {
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM (SELECT DISTINCT channel, thread_id FROM turns)",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|n| n as usize)
+            .map_err(db_err)
+    }
+}
+
+fn parse_role(role: &str) -> Option<Role> {
+    match role {
+        "system" => Some(Role::System),
+        "user" => Some(Role::User),
+        "assistant" => Some(Role::Assistant),
+        "tool" => Some(Role::Tool),
+        _ => None,
+    }
+}
+
+/// Fixed-width so timestamps compare correctly as text.
+fn timestamp(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_recent_per_thread() {
+        let store = HistoryStore::in_memory().unwrap();
+        store.append("telegram", "1", Role::User, "hi").unwrap();
+        store
+            .append("telegram", "1", Role::Assistant, "hello")
+            .unwrap();
+        store.append("telegram", "1", Role::User, "price?").unwrap();
+        store.append("discord", "1", Role::User, "other").unwrap();
+
+        let turns = store.recent("telegram", "1", 2).unwrap();
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0].role, Role::Assistant);
+        assert_eq!(turns[1].content, "price?");
+        assert_eq!(store.thread_count().unwrap(), 2);
+
+        assert_eq!(store.clear("telegram", "1").unwrap(), 3);
+        assert!(store.recent("telegram", "1", 10).unwrap().is_empty());
+        assert_eq!(store.recent("discord", "1", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_retention() {
+        let store = HistoryStore::in_memory()
+            .unwrap()
+            .with_retention(RetentionPolicy {
+                max_turns_per_thread: 3,
+                max_age: Some(chrono::Duration::zero()),
+            });
+        for i in 0..5 {
+            store.append("x", "t", Role::User, &i.to_string()).unwrap();
+        }
+        let turns = store.recent("x", "t", 10).unwrap();
+        let contents: Vec<_> = turns.iter().map(|t| t.content.as_str()).collect();
+        assert_eq!(contents, ["2", "3", "4"]);
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert_eq!(store.prune().unwrap(), 3);
+        assert_eq!(store.thread_count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.db");
+        HistoryStore::open(&path)
+            .unwrap()
+            .append("zalo", "9", Role::User, "xin chào")
+            .unwrap();
+        let turns = HistoryStore::open(&path)
+            .unwrap()
+            .recent("zalo", "9", 5)
+            .unwrap();
+        assert_eq!(turns[0].to_message().content, "xin chào");
+    }
+}
@@ -1,13 +1,19 @@
 //! # BizClaw Memory
 //! Memory and persistence backends with 3-tier brain architecture
 
+pub mod analytics;
 pub mod brain;
+pub mod export;
+pub mod facts;
+pub mod history;
 pub mod noop;
+pub mod profile;
 pub mod sqlite;
+pub mod usage;
 pub mod vector;
 
 use bizclaw_core::config::MemoryConfig;
-use bizclaw_core::error::Result;
+use bizclaw_core::error::{BizClawError, Result};
 use bizclaw_core::traits::MemoryBackend;
 
 /// Create a memory backend from configuration.
@@ -15,8 +21,13 @@ pub fn create_memory(config: &MemoryConfig) -> Result<Box<dyn MemoryBackend>> {
     match config.backend.as_str() {
         "sqlite" => Ok(Box::new(sqlite::SqliteMemory::new()?)),
         "none" => Ok(Box::new(noop::NoopMemory)),
-        other => Err(bizclaw_core::error::BizClawError::Memory(format!(
+        other => Err(BizClawError::Memory(format!(
             "Unknown memory backend: {other}"
         ))),
     }
 }
+
+/// A SQLite error as the memory error the stores here report.
+pub(crate) fn db_err(e: rusqlite::Error) -> BizClawError {
+    BizClawError::Memory(e.to_string())
+}
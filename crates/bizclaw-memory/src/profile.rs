@@ -0,0 +1,231 @@
+//! User profile store — per-user preferences persisted in SQLite.
+//!
+//! Profiles are keyed by `(channel, user_id)`: the same person on Zalo and
+//! Telegram has two. A profile holds what the user told the bot about
+//! themselves — how to address them, which language to reply in, free-form
+//! notes — and the topics they opted in to.
+
+use crate::db_err;
+use bizclaw_core::error::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// What is known about one user.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserProfile {
+    pub channel: String,
+    pub user_id: String,
+    /// How the user wants to be addressed.
+    pub name: Option<String>,
+    /// Language to reply in, e.g. `English`.
+    pub language: Option<String>,
+    pub notes: Vec<String>,
+    /// Topics the user subscribed to, lowercase.
+    pub opt_ins: Vec<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl UserProfile {
+    pub fn new(channel: &str, user_id: &str) -> Self {
+        Self {
+            channel: channel.to_string(),
+            user_id: user_id.to_string(),
+            name: None,
+            language: None,
+            notes: Vec::new(),
+            opt_ins: Vec::new(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.language.is_none()
+            && self.notes.is_empty()
+            && self.opt_ins.is_empty()
+    }
+}
+
+/// SQLite-backed user profiles.
+pub struct ProfileStore {
+    conn: Mutex<Connection>,
+}
+
+impl ProfileStore {
+    /// Open (or create) the store at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::init(Connection::open(path).map_err(db_err)?)
+    }
+
+    /// A throwaway store, for tests.
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(db_err)?)
+    }
+
+    /// `~/.bizclaw/profiles.db`
+    pub fn default_path() -> std::path::PathBuf {
+        bizclaw_core::config::BizClawConfig::home_dir().join("profiles.db")
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS profiles (
+                channel TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                name TEXT,
+                language TEXT,
+                notes TEXT NOT NULL DEFAULT '[]',
+                opt_ins TEXT NOT NULL DEFAULT '[]',
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (channel, user_id)
+            );",
+        )
+        .map_err(db_err)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn get(&self, channel: &str, user_id: &str) -> Result<Option<UserProfile>> {
+        let row = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT name, language, notes, opt_ins, updated_at FROM profiles
+                 WHERE channel = ?1 AND user_id = ?2",
+                params![channel, user_id],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(4)?,
+                    ))
+                },
+            )
+            .optional()
+            .map_err(db_err)?;
+        let Some((name, language, notes, opt_ins, updated_at)) = row else {
+            return Ok(None);
+        };
+        Ok(Some(UserProfile {
+            channel: channel.to_string(),
+            user_id: user_id.to_string(),
+            name,
+            language,
+            notes: serde_json::from_str(&notes)?,
+            opt_ins: serde_json::from_str(&opt_ins)?,
+            updated_at: DateTime::parse_from_rfc3339(&updated_at)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+        }))
+    }
+
+    /// The user's profile, or an empty one.
+    pub fn get_or_default(&self, channel: &str, user_id: &str) -> Result<UserProfile> {
+        Ok(self
+            .get(channel, user_id)?
+            .unwrap_or_else(|| UserProfile::new(channel, user_id)))
+    }
+
+    /// Insert or replace a profile, stamping `updated_at`.
+    pub fn save(&self, profile: &UserProfile) -> Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO profiles
+                 (channel, user_id, name, language, notes, opt_ins, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    profile.channel,
+                    profile.user_id,
+                    profile.name,
+                    profile.language,
+                    serde_json::to_string(&profile.notes)?,
+                    serde_json::to_string(&profile.opt_ins)?,
+                    Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)
+                ],
+            )
+            .map_err(db_err)?;
+        Ok(())
+    }
+
+    /// Forget a user. Returns whether there was a profile.
+    pub fn delete(&self, channel: &str, user_id: &str) -> Result<bool> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "DELETE FROM profiles WHERE channel = ?1 AND user_id = ?2",
+                params![channel, user_id],
+            )
+            .map(|n| n > 0)
+            .map_err(db_err)
+    }
+
+    /// Users of `channel` opted in to `topic`, e.g. for a broadcast.
+    pub fn subscribers(&self, channel: &str, topic: &str) -> Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT user_id, opt_ins FROM profiles WHERE channel = ?1")
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![channel], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(db_err)?;
+        let topic = topic.to_lowercase();
+        let mut users = Vec::new();
+        for row in rows {
+            let (user_id, opt_ins) = row.map_err(db_err)?;
+            let opt_ins: Vec<String> = serde_json::from_str(&opt_ins)?;
+            if opt_ins.contains(&topic) {
+                users.push(user_id);
+            }
+        }
+        Ok(users)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_get_delete() {
+        let store = ProfileStore::in_memory().unwrap();
+        assert!(store.get("zalo", "u1").unwrap().is_none());
+
+        let mut profile = store.get_or_default("zalo", "u1").unwrap();
+        assert!(profile.is_empty());
+        profile.name = Some("Anh".into());
+        profile.notes.push("Vegetarian".into());
+        profile.opt_ins.push("promotions".into());
+        store.save(&profile).unwrap();
+        store.save(&UserProfile::new("telegram", "u1")).unwrap();
+
+        let loaded = store.get("zalo", "u1").unwrap().unwrap();
+        assert_eq!(loaded.name.as_deref(), Some("Anh"));
+        assert_eq!(loaded.language, None);
+        assert_eq!(loaded.notes, ["Vegetarian"]);
+        assert_eq!(store.subscribers("zalo", "Promotions").unwrap(), ["u1"]);
+        assert!(
+            store
+                .subscribers("telegram", "promotions")
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(store.delete("zalo", "u1").unwrap());
+        assert!(!store.delete("zalo", "u1").unwrap());
+        assert!(store.get("telegram", "u1").unwrap().is_some());
+    }
+}
@@ -0,0 +1,279 @@
+//! Usage store — token and cost totals persisted in SQLite.
+//!
+//! Requests are not kept one by one: each is added to the row of its
+//! `(month, provider, model, channel, user_id)`, so the store stays small
+//! and a month's spend is one cheap query. Months are `YYYY-MM` in UTC.
+//! Costs are estimates, see `bizclaw_providers::pricing`.
+
+use crate::db_err;
+use bizclaw_core::error::Result;
+use chrono::Utc;
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// One request, as recorded.
+#[derive(Debug, Clone, Default)]
+pub struct UsageEntry {
+    pub provider: String,
+    pub model: String,
+    pub channel: String,
+    /// Empty for messages nobody asked for, e.g. scheduled posts.
+    pub user_id: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub cost_usd: f64,
+}
+
+/// What totals are grouped by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageGroup {
+    Provider,
+    Model,
+    Channel,
+    /// `channel:user_id`
+    User,
+}
+
+impl UsageGroup {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "provider" => Some(Self::Provider),
+            "model" => Some(Self::Model),
+            "channel" => Some(Self::Channel),
+            "user" => Some(Self::User),
+            _ => None,
+        }
+    }
+
+    fn column(self) -> &'static str {
+        match self {
+            Self::Provider => "provider",
+            Self::Model => "provider || '/' || model",
+            Self::Channel => "channel",
+            Self::User => "channel || ':' || user_id",
+        }
+    }
+}
+
+/// Totals of one group, or of everything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageTotal {
+    pub key: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+}
+
+/// SQLite-backed usage totals.
+pub struct UsageStore {
+    conn: Mutex<Connection>,
+}
+
+impl UsageStore {
+    /// Open (or create) the store at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::init(Connection::open(path).map_err(db_err)?)
+    }
+
+    /// A throwaway store, for tests.
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(db_err)?)
+    }
+
+    /// `~/.bizclaw/usage.db`
+    pub fn default_path() -> std::path::PathBuf {
+        bizclaw_core::config::BizClawConfig::home_dir().join("usage.db")
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS usage (
+                month TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                channel TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                requests INTEGER NOT NULL DEFAULT 0,
+                prompt_tokens INTEGER NOT NULL DEFAULT 0,
+                completion_tokens INTEGER NOT NULL DEFAULT 0,
+                cost_usd REAL NOT NULL DEFAULT 0,
+                PRIMARY KEY (month, provider, model, channel, user_id)
+            );",
+        )
+        .map_err(db_err)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Add a request to the current month.
+    pub fn record(&self, entry: &UsageEntry) -> Result<()> {
+        self.record_in(&current_month(), entry)
+    }
+
+    /// Add a request to `month`.
+    pub fn record_in(&self, month: &str, entry: &UsageEntry) -> Result<()> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO usage (month, provider, model, channel, user_id,
+                     requests, prompt_tokens, completion_tokens, cost_usd)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 1, ?6, ?7, ?8)
+                 ON CONFLICT (month, provider, model, channel, user_id) DO UPDATE SET
+                     requests = requests + 1,
+                     prompt_tokens = prompt_tokens + excluded.prompt_tokens,
+                     completion_tokens = completion_tokens + excluded.completion_tokens,
+                     cost_usd = cost_usd + excluded.cost_usd",
+                params![
+                    month,
+                    entry.provider,
+                    entry.model,
+                    entry.channel,
+                    entry.user_id,
+                    entry.prompt_tokens,
+                    entry.completion_tokens,
+                    entry.cost_usd
+                ],
+            )
+            .map_err(db_err)?;
+        Ok(())
+    }
+
+    /// Totals of `month` per group, most expensive first.
+    pub fn summary(&self, month: &str, by: UsageGroup) -> Result<Vec<UsageTotal>> {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!(
+            "SELECT {key}, SUM(requests), SUM(prompt_tokens), SUM(completion_tokens),
+                    SUM(cost_usd)
+             FROM usage WHERE month = ?1
+             GROUP BY {key}
+             ORDER BY SUM(cost_usd) DESC, SUM(prompt_tokens + completion_tokens) DESC",
+            key = by.column()
+        );
+        let mut stmt = conn.prepare(&sql).map_err(db_err)?;
+        let rows = stmt
+            .query_map(params![month], |row| {
+                Ok(UsageTotal {
+                    key: row.get(0)?,
+                    requests: row.get(1)?,
+                    prompt_tokens: row.get(2)?,
+                    completion_tokens: row.get(3)?,
+                    cost_usd: row.get(4)?,
+                })
+            })
+            .map_err(db_err)?;
+        rows.collect::<std::result::Result<_, _>>().map_err(db_err)
+    }
+
+    /// Totals of `month` across everything.
+    pub fn total(&self, month: &str) -> Result<UsageTotal> {
+        self.total_where(month, "1", params![month])
+    }
+
+    /// Totals of `month` for one user.
+    pub fn user_total(&self, month: &str, channel: &str, user_id: &str) -> Result<UsageTotal> {
+        let key = format!("{channel}:{user_id}");
+        let mut total = self.total_where(
+            month,
+            "channel = ?2 AND user_id = ?3",
+            params![month, channel, user_id],
+        )?;
+        total.key = key;
+        Ok(total)
+    }
+
+    fn total_where(
+        &self,
+        month: &str,
+        filter: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<UsageTotal> {
+        let sql = format!(
+            "SELECT COALESCE(SUM(requests), 0), COALESCE(SUM(prompt_tokens), 0),
+                    COALESCE(SUM(completion_tokens), 0), COALESCE(SUM(cost_usd), 0.0)
+             FROM usage WHERE month = ?1 AND {filter}"
+        );
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(&sql, params, |row| {
+                Ok(UsageTotal {
+                    key: month.to_string(),
+                    requests: row.get(0)?,
+                    prompt_tokens: row.get(1)?,
+                    completion_tokens: row.get(2)?,
+                    cost_usd: row.get(3)?,
+                })
+            })
+            .map_err(db_err)
+    }
+}
+
+/// The current month, `YYYY-MM` in UTC.
+pub fn current_month() -> String {
+    Utc::now().format("%Y-%m").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(provider: &str, model: &str, user_id: &str, cost_usd: f64) -> UsageEntry {
+        UsageEntry {
+            provider: provider.into(),
+            model: model.into(),
+            channel: "zalo".into(),
+            user_id: user_id.into(),
+            prompt_tokens: 100,
+            completion_tokens: 20,
+            cost_usd,
+        }
+    }
+
+    #[test]
+    fn test_totals() {
+        let store = UsageStore::in_memory().unwrap();
+        store
+            .record_in("2026-09", &entry("openai", "gpt-4o", "1", 5.0))
+            .unwrap();
+        store
+            .record_in("2026-10", &entry("openai", "gpt-4o", "1", 0.5))
+            .unwrap();
+        store
+            .record_in("2026-10", &entry("openai", "gpt-4o", "1", 0.25))
+            .unwrap();
+        store
+            .record_in("2026-10", &entry("ollama", "qwen2.5", "2", 0.0))
+            .unwrap();
+
+        let total = store.total("2026-10").unwrap();
+        assert_eq!(total.requests, 3);
+        assert_eq!(total.prompt_tokens, 300);
+        assert_eq!(total.cost_usd, 0.75);
+        assert_eq!(
+            store.user_total("2026-10", "zalo", "2").unwrap().requests,
+            1
+        );
+        assert_eq!(
+            store.total("2026-08").unwrap(),
+            UsageTotal {
+                key: "2026-08".into(),
+                ..Default::default()
+            }
+        );
+
+        let by_model = store.summary("2026-10", UsageGroup::Model).unwrap();
+        let keys: Vec<_> = by_model.iter().map(|t| t.key.as_str()).collect();
+        assert_eq!(keys, ["openai/gpt-4o", "ollama/qwen2.5"]);
+        assert_eq!(by_model[0].requests, 2);
+        let by_user = store.summary("2026-10", UsageGroup::User).unwrap();
+        assert_eq!(by_user[0].key, "zalo:1");
+    }
+}
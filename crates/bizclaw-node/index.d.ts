@@ -0,0 +1,40 @@
+/* auto-generated by NAPI-RS */
+/* eslint-disable */
+
+/** Engine settings; any left out take the brain defaults. */
+export interface EngineOptions {
+  /** Positions the KV cache holds, prompt and output together. */
+  contextLength?: number
+  temperature?: number
+  topP?: number
+  /** Upper bound on `maxTokens` for every call. */
+  maxTokens?: number
+}
+/** The library version, e.g. `"0.2.0"`. */
+export declare function version(): string
+/** A GGUF model loaded into the brain engine. */
+export declare class BrainEngine {
+  /** Load a GGUF model. */
+  static load(modelPath: string, options?: EngineOptions | undefined | null): Promise<BrainEngine>
+  /**
+   * Generate up to `maxTokens` tokens after `prompt`. The prompt is sent
+   * as is: apply the model's chat template first.
+   */
+  generate(prompt: string, maxTokens?: number | undefined | null): Promise<string>
+  /**
+   * Like `generate`, also calling `onToken` with each piece of text as
+   * it is sampled.
+   */
+  generateStream(prompt: string, onToken: (piece: string) => void, maxTokens?: number): Promise<string>
+  /** An L2-normalized embedding of `text`. */
+  embed(text: string): Promise<Float32Array>
+  /** Token IDs of `text`, without BOS. */
+  tokenize(text: string): Array<number>
+  /** Text of `tokens`. */
+  detokenize(tokens: Array<number>): string
+  /** Number of tokens `text` encodes to, without BOS. */
+  countTokens(text: string): number
+  /** Positions the KV cache holds: prompt and output together. */
+  get contextSize(): number | null
+  get modelInfo(): string | null
+}
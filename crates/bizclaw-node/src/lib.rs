@@ -0,0 +1,216 @@
+//! # BizClaw Node.js bindings
+//! The brain engine as a native Node module, so services can run a GGUF
+//! model in-process instead of shelling out to `bizclaw`.
+//!
+//! ```js
+//! const { BrainEngine } = require('@bizclaw/brain');
+//! const engine = await BrainEngine.load('tinyllama.Q4_0.gguf', { temperature: 0.2 });
+//! const text = await engine.generateStream('Once upon a time', (piece) => {
+//!   process.stdout.write(piece);
+//! }, 64);
+//! ```
+//!
+//! Loading, generation and embedding run on the libuv thread pool and
+//! return promises. An engine runs one call at a time; others wait for it,
+//! and the synchronous tokenizer calls wait on the event loop, so use one
+//! engine per concurrent generation.
+
+use bizclaw_brain::BrainConfig;
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{JsFunction, Task};
+use napi_derive::napi;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+type Engine = Arc<Mutex<bizclaw_brain::BrainEngine>>;
+
+fn lock(engine: &Engine) -> MutexGuard<'_, bizclaw_brain::BrainEngine> {
+    engine.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn napi_err(e: bizclaw_core::error::BizClawError) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+/// Engine settings; any left out take the brain defaults.
+#[napi(object)]
+pub struct EngineOptions {
+    /// Positions the KV cache holds, prompt and output together.
+    pub context_length: Option<u32>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    /// Upper bound on `maxTokens` for every call.
+    pub max_tokens: Option<u32>,
+}
+
+pub struct Load {
+    path: String,
+    config: BrainConfig,
+}
+
+impl Task for Load {
+    type Output = bizclaw_brain::BrainEngine;
+    type JsValue = BrainEngine;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let mut engine = bizclaw_brain::BrainEngine::new(self.config.clone());
+        engine
+            .load_model(std::path::Path::new(&self.path))
+            .map_err(napi_err)?;
+        Ok(engine)
+    }
+
+    fn resolve(&mut self, _env: Env, engine: Self::Output) -> Result<Self::JsValue> {
+        Ok(BrainEngine {
+            engine: Arc::new(Mutex::new(engine)),
+        })
+    }
+}
+
+pub struct Generate {
+    engine: Engine,
+    prompt: String,
+    max_tokens: u32,
+    on_token: Option<ThreadsafeFunction<String, ErrorStrategy::Fatal>>,
+}
+
+impl Task for Generate {
+    type Output = String;
+    type JsValue = String;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        let on_token = self.on_token.as_ref();
+        lock(&self.engine)
+            .generate_stream(&self.prompt, self.max_tokens, |piece| {
+                if let Some(on_token) = on_token {
+                    on_token.call(piece.to_string(), ThreadsafeFunctionCallMode::NonBlocking);
+                }
+                true
+            })
+            .map_err(napi_err)
+    }
+
+    fn resolve(&mut self, _env: Env, text: Self::Output) -> Result<Self::JsValue> {
+        Ok(text)
+    }
+}
+
+pub struct Embed {
+    engine: Engine,
+    text: String,
+}
+
+impl Task for Embed {
+    type Output = Vec<f32>;
+    type JsValue = Float32Array;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        lock(&self.engine).embed(&self.text).map_err(napi_err)
+    }
+
+    fn resolve(&mut self, _env: Env, embedding: Self::Output) -> Result<Self::JsValue> {
+        Ok(Float32Array::new(embedding))
+    }
+}
+
+/// A GGUF model loaded into the brain engine.
+#[napi]
+pub struct BrainEngine {
+    engine: Engine,
+}
+
+#[napi]
+impl BrainEngine {
+    /// Load a GGUF model.
+    #[napi(ts_return_type = "Promise<BrainEngine>")]
+    pub fn load(model_path: String, options: Option<EngineOptions>) -> AsyncTask<Load> {
+        let mut config = BrainConfig::default();
+        if let Some(options) = options {
+            config.context_length = options.context_length.unwrap_or(config.context_length);
+            config.temperature = options.temperature.map_or(config.temperature, |t| t as f32);
+            config.top_p = options.top_p.map_or(config.top_p, |p| p as f32);
+            config.max_tokens = options.max_tokens.unwrap_or(config.max_tokens);
+        }
+        AsyncTask::new(Load {
+            path: model_path,
+            config,
+        })
+    }
+
+    /// Generate up to `maxTokens` tokens after `prompt`. The prompt is sent
+    /// as is: apply the model's chat template first.
+    #[napi(ts_return_type = "Promise<string>")]
+    pub fn generate(&self, prompt: String, max_tokens: Option<u32>) -> AsyncTask<Generate> {
+        AsyncTask::new(Generate {
+            engine: self.engine.clone(),
+            prompt,
+            max_tokens: max_tokens.unwrap_or(256),
+            on_token: None,
+        })
+    }
+
+    /// Like `generate`, also calling `onToken` with each piece of text as
+    /// it is sampled.
+    #[napi(
+        ts_args_type = "prompt: string, onToken: (piece: string) => void, maxTokens?: number",
+        ts_return_type = "Promise<string>"
+    )]
+    pub fn generate_stream(
+        &self,
+        prompt: String,
+        on_token: JsFunction,
+        max_tokens: Option<u32>,
+    ) -> Result<AsyncTask<Generate>> {
+        let on_token = on_token.create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))?;
+        Ok(AsyncTask::new(Generate {
+            engine: self.engine.clone(),
+            prompt,
+            max_tokens: max_tokens.unwrap_or(256),
+            on_token: Some(on_token),
+        }))
+    }
+
+    /// An L2-normalized embedding of `text`.
+    #[napi(ts_return_type = "Promise<Float32Array>")]
+    pub fn embed(&self, text: String) -> AsyncTask<Embed> {
+        AsyncTask::new(Embed {
+            engine: self.engine.clone(),
+            text,
+        })
+    }
+
+    /// Token IDs of `text`, without BOS.
+    #[napi]
+    pub fn tokenize(&self, text: String) -> Result<Vec<u32>> {
+        lock(&self.engine).tokenize(&text).map_err(napi_err)
+    }
+
+    /// Text of `tokens`.
+    #[napi]
+    pub fn detokenize(&self, tokens: Vec<u32>) -> Result<String> {
+        lock(&self.engine).detokenize(&tokens).map_err(napi_err)
+    }
+
+    /// Number of tokens `text` encodes to, without BOS.
+    #[napi]
+    pub fn count_tokens(&self, text: String) -> Result<u32> {
+        Ok(lock(&self.engine).count_tokens(&text).map_err(napi_err)? as u32)
+    }
+
+    /// Positions the KV cache holds: prompt and output together.
+    #[napi(getter)]
+    pub fn context_size(&self) -> Option<u32> {
+        lock(&self.engine).context_size().map(|n| n as u32)
+    }
+
+    #[napi(getter)]
+    pub fn model_info(&self) -> Option<String> {
+        lock(&self.engine).model_info()
+    }
+}
+
+/// The library version, e.g. `"0.2.0"`.
+#[napi]
+pub fn version() -> &'static str {
+    env!("CARGO_PKG_VERSION")
+}
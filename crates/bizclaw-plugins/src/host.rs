@@ -0,0 +1,129 @@
+//! The wasmtime host that runs plugin modules.
+//!
+//! Modules are compiled once; every call gets a fresh instance, so plugins
+//! keep no state between calls and one call can't affect another. No host
+//! functions are linked, so a module that imports anything fails to load.
+
+use crate::{ABI_VERSION, ABI_VERSION_EXPORT, ALLOC_EXPORT};
+use bizclaw_core::config::PluginsConfig;
+use bizclaw_core::error::{BizClawError, Result};
+use std::path::Path;
+use wasmtime::{Config, Engine, Instance, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+/// A compiled plugin module and the limits its calls run under.
+#[derive(Clone)]
+pub struct WasmModule {
+    name: String,
+    engine: Engine,
+    module: Module,
+    fuel: u64,
+    max_memory: usize,
+}
+
+impl WasmModule {
+    /// Compile the module at `path` and check that it speaks our ABI.
+    pub fn load(name: &str, path: &Path, limits: &PluginsConfig) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|e| {
+            BizClawError::Config(format!(
+                "Plugin '{name}': can't read {}: {e}",
+                path.display()
+            ))
+        })?;
+        Self::from_bytes(name, &bytes, limits)
+    }
+
+    /// Compile a module from its binary or WAT text.
+    pub fn from_bytes(name: &str, bytes: &[u8], limits: &PluginsConfig) -> Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| plugin_err(name, e))?;
+        let module = Module::new(&engine, bytes).map_err(|e| plugin_err(name, e))?;
+        let wasm = Self {
+            name: name.to_string(),
+            engine,
+            module,
+            fuel: limits.fuel,
+            max_memory: limits.max_memory_mb as usize * 1024 * 1024,
+        };
+        wasm.check_abi()?;
+        Ok(wasm)
+    }
+
+    pub fn has_export(&self, export: &str) -> bool {
+        self.module.get_export(export).is_some()
+    }
+
+    fn check_abi(&self) -> Result<()> {
+        let (mut store, instance) = self.instantiate()?;
+        let version = instance
+            .get_typed_func::<(), i32>(&mut store, ABI_VERSION_EXPORT)
+            .and_then(|f| f.call(&mut store, ()))
+            .map_err(|e| plugin_err(&self.name, e))?;
+        if version != ABI_VERSION {
+            return Err(BizClawError::Config(format!(
+                "Plugin '{}' uses ABI version {version}, this BizClaw supports {ABI_VERSION}",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    fn instantiate(&self) -> Result<(Store<StoreLimits>, Instance)> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.max_memory)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store
+            .set_fuel(self.fuel)
+            .map_err(|e| plugin_err(&self.name, e))?;
+        let instance = Linker::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .map_err(|e| plugin_err(&self.name, e))?;
+        Ok((store, instance))
+    }
+
+    /// Call `export` with `input` in a fresh instance and return its output.
+    ///
+    /// Blocks until the plugin returns or runs out of fuel; run it with
+    /// `spawn_blocking` from async code.
+    pub fn call(&self, export: &str, input: &[u8]) -> Result<Vec<u8>> {
+        let (mut store, instance) = self.instantiate()?;
+        let err = |e: wasmtime::Error| plugin_err(&self.name, e);
+        let memory = instance.get_memory(&mut store, "memory").ok_or_else(|| {
+            BizClawError::Tool(format!("Plugin '{}' exports no memory", self.name))
+        })?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, ALLOC_EXPORT)
+            .map_err(err)?;
+        let func = instance
+            .get_typed_func::<(i32, i32), i64>(&mut store, export)
+            .map_err(err)?;
+
+        let len = i32::try_from(input.len()).map_err(|_| {
+            BizClawError::Tool(format!("Input for plugin '{}' is too large", self.name))
+        })?;
+        let ptr = alloc.call(&mut store, len).map_err(err)?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|e| plugin_err(&self.name, e.into()))?;
+
+        let packed = func.call(&mut store, (ptr, len)).map_err(err)? as u64;
+        let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        if out_ptr.saturating_add(out_len) > memory.data_size(&store) {
+            return Err(BizClawError::Tool(format!(
+                "Plugin '{}' returned output outside its memory",
+                self.name
+            )));
+        }
+        let mut output = vec![0; out_len];
+        memory
+            .read(&store, out_ptr, &mut output)
+            .map_err(|e| plugin_err(&self.name, e.into()))?;
+        Ok(output)
+    }
+}
+
+fn plugin_err(name: &str, e: wasmtime::Error) -> BizClawError {
+    BizClawError::Tool(format!("Plugin '{name}': {e:#}"))
+}
@@ -0,0 +1,190 @@
+//! # BizClaw Plugins
+//! Sandboxed WASM plugins that add tools and message pipeline stages
+//! without rebuilding BizClaw.
+//!
+//! Each plugin is a directory under `[plugins] dir` holding a
+//! `plugin.toml` manifest (see [`manifest`]) and a WASM module:
+//!
+//! ```text
+//! ~/.bizclaw/plugins/
+//!   order_status/
+//!     plugin.toml
+//!     order_status.wasm
+//! ```
+//!
+//! ## ABI (version 1)
+//!
+//! Modules talk to the host through linear memory and JSON:
+//!
+//! - export `memory`;
+//! - `bizclaw_abi_version() -> i32` returns [`ABI_VERSION`];
+//! - `bizclaw_alloc(len: i32) -> i32` returns a buffer of `len` bytes the
+//!   host writes the input into;
+//! - the entry points take `(ptr: i32, len: i32)` of that input and return
+//!   an `i64` packing the output as `ptr << 32 | len`.
+//!
+//! Tools export `bizclaw_tool`, called with the model's arguments and
+//! returning `{"output": "...", "success": true}`. Middleware exports
+//! `bizclaw_incoming` and/or `bizclaw_outgoing`, called with
+//! `{"channel", "thread_id", "sender_id", "content"}` and returning
+//! `{"action": "continue" | "reply" | "drop", "content": "..."}`.
+//!
+//! ## Sandbox
+//!
+//! No host functions are linked, so plugins have no file, network or clock
+//! access. Every call runs in a fresh instance with a fuel budget
+//! (`[plugins] fuel`) and a memory cap (`[plugins] max_memory_mb`); a
+//! plugin that exceeds either fails that call only.
+//!
+//! Running modules needs the `wasm` feature. Without it plugins are still
+//! discovered, and a warning says they weren't loaded.
+
+pub mod manifest;
+
+#[cfg(feature = "wasm")]
+pub mod host;
+#[cfg(feature = "wasm")]
+pub mod middleware;
+#[cfg(feature = "wasm")]
+pub mod tool;
+
+use bizclaw_channels::pipeline::MessageMiddleware;
+use bizclaw_core::config::PluginsConfig;
+use bizclaw_core::traits::Tool;
+use manifest::PluginKind;
+use std::sync::Arc;
+
+/// The plugin ABI this host implements.
+pub const ABI_VERSION: i32 = 1;
+pub const ABI_VERSION_EXPORT: &str = "bizclaw_abi_version";
+pub const ALLOC_EXPORT: &str = "bizclaw_alloc";
+pub const TOOL_EXPORT: &str = "bizclaw_tool";
+pub const INCOMING_EXPORT: &str = "bizclaw_incoming";
+pub const OUTGOING_EXPORT: &str = "bizclaw_outgoing";
+
+/// Load the tool plugins under `config.dir`. Plugins that fail to load are
+/// logged and skipped.
+pub fn load_tools(config: &PluginsConfig) -> Vec<Box<dyn Tool>> {
+    let found = manifest::discover(&config.plugins_dir());
+    let found: Vec<_> = found
+        .into_iter()
+        .filter(|(_, m)| m.kind == PluginKind::Tool)
+        .collect();
+    #[cfg(feature = "wasm")]
+    {
+        found
+            .into_iter()
+            .filter_map(|(path, manifest)| {
+                let name = manifest.name.clone();
+                host::WasmModule::load(&name, &path, config)
+                    .and_then(|module| tool::PluginTool::new(manifest, module))
+                    .inspect(|_| tracing::info!("🔌 Loaded tool plugin '{name}'"))
+                    .inspect_err(|e| tracing::warn!("Skipping plugin: {e}"))
+                    .ok()
+                    .map(|tool| Box::new(tool) as Box<dyn Tool>)
+            })
+            .collect()
+    }
+    #[cfg(not(feature = "wasm"))]
+    {
+        warn_unsupported(found.len());
+        Vec::new()
+    }
+}
+
+/// Load the middleware plugins under `config.dir`, in directory order.
+/// Plugins that fail to load are logged and skipped.
+pub fn load_middleware(config: &PluginsConfig) -> Vec<Arc<dyn MessageMiddleware>> {
+    let found = manifest::discover(&config.plugins_dir());
+    let found: Vec<_> = found
+        .into_iter()
+        .filter(|(_, m)| m.kind == PluginKind::Middleware)
+        .collect();
+    #[cfg(feature = "wasm")]
+    {
+        found
+            .into_iter()
+            .filter_map(|(path, manifest)| {
+                let name = manifest.name.clone();
+                host::WasmModule::load(&name, &path, config)
+                    .and_then(|module| middleware::PluginMiddleware::new(manifest, module))
+                    .inspect(|_| tracing::info!("🔌 Loaded middleware plugin '{name}'"))
+                    .inspect_err(|e| tracing::warn!("Skipping plugin: {e}"))
+                    .ok()
+                    .map(|stage| Arc::new(stage) as Arc<dyn MessageMiddleware>)
+            })
+            .collect()
+    }
+    #[cfg(not(feature = "wasm"))]
+    {
+        warn_unsupported(found.len());
+        Vec::new()
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+fn warn_unsupported(count: usize) {
+    if count > 0 {
+        tracing::warn!(
+            "{count} plugin(s) not loaded: BizClaw was built without the `wasm` feature"
+        );
+    }
+}
+
+#[cfg(all(test, feature = "wasm"))]
+mod tests {
+    use super::*;
+
+    /// Returns its input as output, after a bump allocation.
+    const ECHO: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (func (export "bizclaw_abi_version") (result i32) (i32.const 1))
+          (func (export "bizclaw_alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (local.get $ptr))
+          (func (export "bizclaw_tool") (param $ptr i32) (param $len i32) (result i64)
+            (i64.or
+              (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+              (i64.extend_i32_u (local.get $len)))))
+    "#;
+
+    const SPIN: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (func (export "bizclaw_abi_version") (result i32) (i32.const 1))
+          (func (export "bizclaw_alloc") (param i32) (result i32) (i32.const 0))
+          (func (export "bizclaw_tool") (param i32 i32) (result i64)
+            (loop $forever (br $forever))
+            (i64.const 0)))
+    "#;
+
+    fn manifest(name: &str) -> manifest::PluginManifest {
+        toml::from_str(&format!("name = \"{name}\"\nkind = \"tool\"\n")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tool_plugin() {
+        let limits = PluginsConfig::default();
+        let module = host::WasmModule::from_bytes("echo", ECHO.as_bytes(), &limits).unwrap();
+        let tool = tool::PluginTool::new(manifest("echo"), module).unwrap();
+        let result = tool.execute(r#"{"output":"pong"}"#).await.unwrap();
+        assert_eq!(result.output, "pong");
+        assert!(result.success);
+        assert!(tool.execute("not json").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fuel_limit() {
+        let limits = PluginsConfig {
+            fuel: 10_000,
+            ..Default::default()
+        };
+        let module = host::WasmModule::from_bytes("spin", SPIN.as_bytes(), &limits).unwrap();
+        let tool = tool::PluginTool::new(manifest("spin"), module).unwrap();
+        assert!(tool.execute("{}").await.is_err());
+    }
+}
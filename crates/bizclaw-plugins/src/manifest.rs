@@ -0,0 +1,141 @@
+//! Plugin manifest — the `plugin.toml` next to each module.
+//!
+//! ```toml
+//! name = "order_status"
+//! kind = "tool"
+//! description = "Look up the status of an order by its number."
+//! module = "order_status.wasm"
+//!
+//! [parameters]
+//! type = "object"
+//! required = ["order_id"]
+//!
+//! [parameters.properties.order_id]
+//! type = "string"
+//! ```
+
+use bizclaw_core::error::{BizClawError, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// File name of the manifest in a plugin's directory.
+pub const MANIFEST_FILE: &str = "plugin.toml";
+
+/// What a plugin plugs into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginKind {
+    /// A tool the model can call.
+    Tool,
+    /// A message pipeline stage.
+    Middleware,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    pub kind: PluginKind,
+    #[serde(default)]
+    pub description: String,
+    /// Module file, relative to the plugin's directory.
+    #[serde(default = "default_module")]
+    pub module: String,
+    /// JSON schema of a tool's arguments.
+    #[serde(default = "default_parameters")]
+    pub parameters: serde_json::Value,
+}
+
+fn default_module() -> String {
+    "plugin.wasm".into()
+}
+
+fn default_parameters() -> serde_json::Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+impl PluginManifest {
+    /// Read `plugin.toml` from a plugin's directory.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| BizClawError::Config(format!("Failed to read {}: {e}", path.display())))?;
+        let manifest: Self = toml::from_str(&content)
+            .map_err(|e| BizClawError::Config(format!("Invalid {}: {e}", path.display())))?;
+        if manifest.name.trim().is_empty() {
+            return Err(BizClawError::Config(format!(
+                "{}: name must not be empty",
+                path.display()
+            )));
+        }
+        Ok(manifest)
+    }
+}
+
+/// The plugins under `root`: every subdirectory with a readable manifest,
+/// sorted by directory name. Broken plugins are logged and skipped.
+pub fn discover(root: &Path) -> Vec<(PathBuf, PluginManifest)> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        tracing::debug!("No plugins directory at {}", root.display());
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.join(MANIFEST_FILE).is_file())
+        .collect();
+    dirs.sort();
+    dirs.into_iter()
+        .filter_map(|dir| match PluginManifest::load(&dir) {
+            Ok(manifest) => {
+                let module = dir.join(&manifest.module);
+                Some((module, manifest))
+            }
+            Err(e) => {
+                tracing::warn!("Skipping plugin: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover() {
+        let root = std::env::temp_dir().join(format!("bizclaw-plugins-{}", uuid::Uuid::new_v4()));
+        let write = |dir: &str, manifest: &str| {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join(MANIFEST_FILE), manifest).unwrap();
+        };
+        write(
+            "b_redact",
+            "name = \"redact\"\nkind = \"middleware\"\nmodule = \"redact.wasm\"\n",
+        );
+        write(
+            "a_weather",
+            r#"
+                name = "weather"
+                kind = "tool"
+                description = "Current weather"
+
+                [parameters]
+                type = "object"
+                required = ["city"]
+            "#,
+        );
+        write("c_broken", "name = \"x\"\nkind = \"exporter\"\n");
+        std::fs::create_dir_all(root.join("d_empty")).unwrap();
+
+        let found = discover(&root);
+        let names: Vec<&str> = found.iter().map(|(_, m)| m.name.as_str()).collect();
+        assert_eq!(names, ["weather", "redact"]);
+        assert_eq!(found[0].0, root.join("a_weather").join("plugin.wasm"));
+        assert_eq!(found[0].1.kind, PluginKind::Tool);
+        assert_eq!(found[0].1.parameters["required"][0], "city");
+        assert_eq!(found[1].0, root.join("b_redact").join("redact.wasm"));
+        assert_eq!(found[1].1.kind, PluginKind::Middleware);
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
@@ -0,0 +1,149 @@
+//! Plugins of kind `middleware`.
+
+use crate::host::WasmModule;
+use crate::manifest::PluginManifest;
+use crate::{INCOMING_EXPORT, OUTGOING_EXPORT};
+use async_trait::async_trait;
+use bizclaw_channels::pipeline::{Flow, MessageContext, MessageMiddleware};
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::types::{IncomingMessage, OutgoingMessage};
+use serde::{Deserialize, Serialize};
+
+/// The message as a plugin sees it.
+#[derive(Serialize)]
+struct PluginMessage<'a> {
+    channel: &'a str,
+    thread_id: &'a str,
+    sender_id: &'a str,
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Action {
+    Continue,
+    Reply,
+    Drop,
+}
+
+/// What a middleware plugin decided.
+#[derive(Deserialize)]
+struct Decision {
+    action: Action,
+    /// New text for the message or reply; unset keeps it.
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// A pipeline stage backed by a WASM module.
+pub struct PluginMiddleware {
+    manifest: PluginManifest,
+    module: WasmModule,
+    has_outgoing: bool,
+}
+
+impl PluginMiddleware {
+    pub fn new(manifest: PluginManifest, module: WasmModule) -> Result<Self> {
+        if !module.has_export(INCOMING_EXPORT) && !module.has_export(OUTGOING_EXPORT) {
+            return Err(BizClawError::Config(format!(
+                "Plugin '{}' is middleware but exports neither {INCOMING_EXPORT} nor \
+                 {OUTGOING_EXPORT}",
+                manifest.name
+            )));
+        }
+        let has_outgoing = module.has_export(OUTGOING_EXPORT);
+        Ok(Self {
+            manifest,
+            module,
+            has_outgoing,
+        })
+    }
+
+    async fn decide(&self, export: &'static str, message: PluginMessage<'_>) -> Result<Decision> {
+        let input = serde_json::to_vec(&message)?;
+        let module = self.module.clone();
+        let output = tokio::task::spawn_blocking(move || module.call(export, &input))
+            .await
+            .map_err(|e| BizClawError::Tool(format!("Plugin task failed: {e}")))??;
+        serde_json::from_slice(&output).map_err(|e| {
+            BizClawError::Tool(format!(
+                "Plugin '{}' returned an invalid decision: {e}",
+                self.manifest.name
+            ))
+        })
+    }
+}
+
+#[async_trait]
+impl MessageMiddleware for PluginMiddleware {
+    fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    async fn incoming(
+        &self,
+        mut message: IncomingMessage,
+        ctx: &mut MessageContext,
+    ) -> Result<Flow> {
+        if !self.module.has_export(INCOMING_EXPORT) {
+            return Ok(Flow::Continue(message));
+        }
+        let decision = self
+            .decide(
+                INCOMING_EXPORT,
+                PluginMessage {
+                    channel: &message.channel,
+                    thread_id: &message.thread_id,
+                    sender_id: &message.sender_id,
+                    content: &message.content,
+                },
+            )
+            .await?;
+        Ok(match decision.action {
+            Action::Continue => {
+                if let Some(content) = decision.content {
+                    ctx.annotate(self.manifest.name.clone(), "rewritten");
+                    message.content = content;
+                }
+                Flow::Continue(message)
+            }
+            Action::Reply => Flow::Reply(OutgoingMessage::text(
+                &message.thread_id,
+                decision.content.unwrap_or_default(),
+                message.thread_type.clone(),
+            )),
+            Action::Drop => Flow::Drop,
+        })
+    }
+
+    async fn outgoing(
+        &self,
+        incoming: &IncomingMessage,
+        mut reply: OutgoingMessage,
+        _ctx: &mut MessageContext,
+    ) -> Result<Option<OutgoingMessage>> {
+        if !self.has_outgoing {
+            return Ok(Some(reply));
+        }
+        let decision = self
+            .decide(
+                OUTGOING_EXPORT,
+                PluginMessage {
+                    channel: &incoming.channel,
+                    thread_id: &reply.thread_id,
+                    sender_id: &incoming.sender_id,
+                    content: &reply.content,
+                },
+            )
+            .await?;
+        Ok(match decision.action {
+            Action::Continue | Action::Reply => {
+                if let Some(content) = decision.content {
+                    reply.content = content;
+                }
+                Some(reply)
+            }
+            Action::Drop => None,
+        })
+    }
+}
@@ -0,0 +1,74 @@
+//! Plugins of kind `tool`.
+
+use crate::TOOL_EXPORT;
+use crate::host::WasmModule;
+use crate::manifest::PluginManifest;
+use async_trait::async_trait;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::Tool;
+use bizclaw_core::types::{ToolDefinition, ToolResult};
+use serde::Deserialize;
+
+/// What a tool plugin returns.
+#[derive(Deserialize)]
+struct ToolOutput {
+    output: String,
+    #[serde(default = "default_success")]
+    success: bool,
+}
+
+fn default_success() -> bool {
+    true
+}
+
+/// A tool backed by a WASM module.
+pub struct PluginTool {
+    manifest: PluginManifest,
+    module: WasmModule,
+}
+
+impl PluginTool {
+    pub fn new(manifest: PluginManifest, module: WasmModule) -> Result<Self> {
+        if !module.has_export(TOOL_EXPORT) {
+            return Err(BizClawError::Config(format!(
+                "Plugin '{}' is a tool but doesn't export {TOOL_EXPORT}",
+                manifest.name
+            )));
+        }
+        Ok(Self { manifest, module })
+    }
+}
+
+#[async_trait]
+impl Tool for PluginTool {
+    fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: self.manifest.name.clone(),
+            description: self.manifest.description.clone(),
+            parameters: self.manifest.parameters.clone(),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<ToolResult> {
+        let module = self.module.clone();
+        let input = arguments.as_bytes().to_vec();
+        let output = tokio::task::spawn_blocking(move || module.call(TOOL_EXPORT, &input))
+            .await
+            .map_err(|e| BizClawError::Tool(format!("Plugin task failed: {e}")))??;
+        let output: ToolOutput = serde_json::from_slice(&output).map_err(|e| {
+            BizClawError::Tool(format!(
+                "Plugin '{}' returned invalid output: {e}",
+                self.manifest.name
+            ))
+        })?;
+        Ok(ToolResult {
+            tool_call_id: String::new(),
+            output: output.output,
+            success: output.success,
+        })
+    }
+}
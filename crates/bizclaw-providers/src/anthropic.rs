@@ -0,0 +1,461 @@
+//! Native Anthropic Messages API provider.
+//!
+//! Talks to `/v1/messages` directly instead of Anthropic's OpenAI-compatible
+//! shim: system prompts and tool definitions are sent with `cache_control`
+//! for prompt caching, and tool calls round-trip as `tool_use` /
+//! `tool_result` blocks.
+
+use async_trait::async_trait;
+use bizclaw_core::config::BizClawConfig;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::provider::{GenerateParams, Provider, ProviderStream};
+use bizclaw_core::types::{
+    FunctionCall, Message, ModelInfo, ProviderResponse, Role, StreamChunk, ToolCall,
+    ToolDefinition, Usage,
+};
+use futures::StreamExt;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+use crate::openai_compatible::{api_error, transport_error};
+use crate::provider_registry::ProviderConfig;
+
+/// Value of the `anthropic-version` header.
+const API_VERSION: &str = "2023-06-01";
+
+pub struct AnthropicProvider {
+    api_key: String,
+    /// e.g. `https://api.anthropic.com/v1`.
+    base_url: String,
+    chat_path: String,
+    default_models: Vec<ModelInfo>,
+    client: reqwest::Client,
+}
+
+impl AnthropicProvider {
+    pub fn from_registry(registry: &ProviderConfig, config: &BizClawConfig) -> Result<Self> {
+        Ok(Self {
+            api_key: registry.resolve_api_key(config),
+            base_url: registry
+                .resolve_base_url(config)
+                .trim_end_matches('/')
+                .to_string(),
+            chat_path: registry.chat_path.to_string(),
+            default_models: registry.model_infos(),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    fn request_body(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Value {
+        let mut system: Vec<Value> = Vec::new();
+        let mut turns: Vec<Value> = Vec::new();
+        for msg in messages {
+            let (role, blocks) = match msg.role {
+                Role::System => {
+                    system.push(json!({
+                        "type": "text",
+                        "text": msg.content,
+                        "cache_control": { "type": "ephemeral" },
+                    }));
+                    continue;
+                }
+                Role::User => ("user", vec![json!({ "type": "text", "text": msg.content })]),
+                Role::Assistant => {
+                    let mut blocks = Vec::new();
+                    if !msg.content.is_empty() {
+                        blocks.push(json!({ "type": "text", "text": msg.content }));
+                    }
+                    for call in msg.tool_calls.iter().flatten() {
+                        let input: Value = serde_json::from_str(&call.function.arguments)
+                            .unwrap_or_else(|_| json!({}));
+                        blocks.push(json!({
+                            "type": "tool_use",
+                            "id": call.id,
+                            "name": call.function.name,
+                            "input": input,
+                        }));
+                    }
+                    ("assistant", blocks)
+                }
+                Role::Tool => (
+                    "user",
+                    vec![json!({
+                        "type": "tool_result",
+                        "tool_use_id": msg.tool_call_id.clone().unwrap_or_default(),
+                        "content": msg.content,
+                    })],
+                ),
+            };
+            // Roles must alternate: merge consecutive turns (e.g. several
+            // tool results) into one.
+            match turns.last_mut() {
+                Some(last) if last["role"] == role => {
+                    if let Some(content) = last["content"].as_array_mut() {
+                        content.extend(blocks);
+                    }
+                }
+                _ => turns.push(json!({ "role": role, "content": blocks })),
+            }
+        }
+
+        let mut body = json!({
+            "model": params.model,
+            "max_tokens": params.max_tokens,
+            "temperature": params.temperature,
+            "messages": turns,
+        });
+        if !system.is_empty() {
+            body["system"] = Value::Array(system);
+        }
+        if !params.stop.is_empty() {
+            body["stop_sequences"] = json!(params.stop);
+        }
+        if !tools.is_empty() {
+            let mut defs: Vec<Value> = tools
+                .iter()
+                .map(|t| {
+                    json!({
+                        "name": t.name,
+                        "description": t.description,
+                        "input_schema": t.parameters,
+                    })
+                })
+                .collect();
+            // A breakpoint on the last tool caches the whole list
+            if let Some(last) = defs.last_mut() {
+                last["cache_control"] = json!({ "type": "ephemeral" });
+            }
+            body["tools"] = Value::Array(defs);
+        }
+        body
+    }
+
+    async fn post(&self, body: &Value) -> Result<reqwest::Response> {
+        if self.api_key.is_empty() {
+            return Err(BizClawError::ApiKeyMissing("anthropic".into()));
+        }
+        let url = format!("{}{}", self.base_url, self.chat_path);
+        let resp = self
+            .client
+            .post(&url)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| transport_error("anthropic", &url, e))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(api_error("anthropic", "API error", status, &headers, &text));
+        }
+        Ok(resp)
+    }
+}
+
+/// Anthropic `stop_reason` in OpenAI terms.
+fn finish_reason(stop_reason: &str) -> String {
+    match stop_reason {
+        "end_turn" | "stop_sequence" => "stop",
+        "tool_use" => "tool_calls",
+        "max_tokens" => "length",
+        other => other,
+    }
+    .to_string()
+}
+
+fn usage(input_tokens: u64, output_tokens: u64) -> Usage {
+    Usage {
+        prompt_tokens: input_tokens as u32,
+        completion_tokens: output_tokens as u32,
+        total_tokens: (input_tokens + output_tokens) as u32,
+    }
+}
+
+/// Turns Messages API stream events into [`StreamChunk`]s. `tool_use`
+/// input arrives as JSON fragments per content block and is emitted whole
+/// with the stop reason.
+#[derive(Default)]
+struct StreamDecoder {
+    input_tokens: u64,
+    tool_calls: BTreeMap<u64, ToolCall>,
+}
+
+impl StreamDecoder {
+    fn decode(&mut self, data: &str) -> Result<Option<StreamChunk>> {
+        let event: Value = serde_json::from_str(data)?;
+        let index = event["index"].as_u64().unwrap_or(0);
+        match event["type"].as_str().unwrap_or_default() {
+            "message_start" => {
+                self.input_tokens = event["message"]["usage"]["input_tokens"]
+                    .as_u64()
+                    .unwrap_or(0);
+                Ok(None)
+            }
+            "content_block_start" => {
+                let block = &event["content_block"];
+                if block["type"] == "tool_use" {
+                    self.tool_calls.insert(
+                        index,
+                        ToolCall {
+                            id: block["id"].as_str().unwrap_or_default().to_string(),
+                            r#type: "function".into(),
+                            function: FunctionCall {
+                                name: block["name"].as_str().unwrap_or_default().to_string(),
+                                arguments: String::new(),
+                            },
+                        },
+                    );
+                }
+                Ok(None)
+            }
+            "content_block_delta" => {
+                let delta = &event["delta"];
+                match delta["type"].as_str().unwrap_or_default() {
+                    "text_delta" => Ok(Some(StreamChunk::text(
+                        delta["text"].as_str().unwrap_or_default(),
+                    ))),
+                    "input_json_delta" => {
+                        if let Some(call) = self.tool_calls.get_mut(&index) {
+                            let json = delta["partial_json"].as_str().unwrap_or_default();
+                            call.function.arguments.push_str(json);
+                        }
+                        Ok(None)
+                    }
+                    _ => Ok(None),
+                }
+            }
+            "message_delta" => {
+                let Some(stop_reason) = event["delta"]["stop_reason"].as_str() else {
+                    return Ok(None);
+                };
+                let mut tool_calls: Vec<ToolCall> =
+                    std::mem::take(&mut self.tool_calls).into_values().collect();
+                for call in &mut tool_calls {
+                    if call.function.arguments.is_empty() {
+                        call.function.arguments = "{}".into();
+                    }
+                }
+                let output_tokens = event["usage"]["output_tokens"].as_u64().unwrap_or(0);
+                Ok(Some(StreamChunk {
+                    delta: String::new(),
+                    tool_calls,
+                    finish_reason: Some(finish_reason(stop_reason)),
+                    usage: Some(usage(self.input_tokens, output_tokens)),
+                }))
+            }
+            "error" => {
+                let error = &event["error"];
+                let message = format!(
+                    "anthropic stream error: {}",
+                    error["message"].as_str().unwrap_or("unknown")
+                );
+                Err(match error["type"].as_str().unwrap_or_default() {
+                    "rate_limit_error" => BizClawError::rate_limited(message, None),
+                    "overloaded_error" | "api_error" => BizClawError::Http(message),
+                    _ => BizClawError::Provider(message),
+                })
+            }
+            // ping, content_block_stop, message_stop
+            _ => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for AnthropicProvider {
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    async fn chat(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderResponse> {
+        let body = self.request_body(messages, tools, params);
+        let json: Value = self
+            .post(&body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| BizClawError::Http(e.to_string()))?;
+
+        let mut text = String::new();
+        let mut tool_calls = Vec::new();
+        for block in json["content"].as_array().into_iter().flatten() {
+            match block["type"].as_str().unwrap_or_default() {
+                "text" => text.push_str(block["text"].as_str().unwrap_or_default()),
+                "tool_use" => tool_calls.push(ToolCall {
+                    id: block["id"].as_str().unwrap_or_default().to_string(),
+                    r#type: "function".into(),
+                    function: FunctionCall {
+                        name: block["name"].as_str().unwrap_or_default().to_string(),
+                        arguments: block["input"].to_string(),
+                    },
+                }),
+                _ => {}
+            }
+        }
+
+        Ok(ProviderResponse {
+            content: (!text.is_empty()).then_some(text),
+            tool_calls,
+            finish_reason: json["stop_reason"].as_str().map(finish_reason),
+            usage: json["usage"].as_object().map(|u| {
+                let count = |key: &str| u.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+                usage(count("input_tokens"), count("output_tokens"))
+            }),
+        })
+    }
+
+    async fn stream(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderStream> {
+        let mut body = self.request_body(messages, tools, params);
+        body["stream"] = json!(true);
+        let resp = self.post(&body).await?;
+
+        let mut decoder = StreamDecoder::default();
+        let chunks = crate::sse::events(resp)
+            .map(move |event| event.and_then(|data| decoder.decode(&data)))
+            .filter_map(|chunk| async move { chunk.transpose() });
+        Ok(Box::pin(chunks))
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        let url = format!("{}/models", self.base_url);
+        let resp = self
+            .client
+            .get(&url)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .send()
+            .await;
+        let Ok(resp) = resp else {
+            return Ok(self.default_models.clone());
+        };
+        if !resp.status().is_success() {
+            return Ok(self.default_models.clone());
+        }
+        let json: Value = resp.json().await.unwrap_or_default();
+        let models: Vec<ModelInfo> = json["data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| {
+                let id = m["id"].as_str()?;
+                Some(ModelInfo {
+                    id: id.to_string(),
+                    name: m["display_name"].as_str().unwrap_or(id).to_string(),
+                    provider: "anthropic".into(),
+                    context_length: 200_000,
+                    max_output_tokens: Some(8192),
+                })
+            })
+            .collect();
+        if models.is_empty() {
+            Ok(self.default_models.clone())
+        } else {
+            Ok(models)
+        }
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        Ok(!self.api_key.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider() -> AnthropicProvider {
+        let registry = crate::provider_registry::get_provider_config("anthropic").unwrap();
+        AnthropicProvider::from_registry(registry, &BizClawConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_request_body() {
+        let call = ToolCall {
+            id: "toolu_1".into(),
+            r#type: "function".into(),
+            function: FunctionCall {
+                name: "web_search".into(),
+                arguments: r#"{"q":"rust"}"#.into(),
+            },
+        };
+        let mut assistant = Message::assistant("");
+        assistant.tool_calls = Some(vec![call]);
+        let messages = [
+            Message::system("Be brief."),
+            Message::user("Search rust"),
+            assistant,
+            Message::tool("results", "toolu_1"),
+            Message::user("Thanks"),
+        ];
+        let tools = [ToolDefinition {
+            name: "web_search".into(),
+            description: "Search the web".into(),
+            parameters: json!({ "type": "object" }),
+        }];
+        let params = GenerateParams {
+            model: "claude-sonnet-4-20250514".into(),
+            ..Default::default()
+        };
+        let body = provider().request_body(&messages, &tools, &params);
+
+        assert_eq!(body["system"][0]["text"], "Be brief.");
+        let turns = body["messages"].as_array().unwrap();
+        assert_eq!(turns.len(), 3);
+        assert_eq!(turns[1]["content"][0]["type"], "tool_use");
+        assert_eq!(turns[1]["content"][0]["input"]["q"], "rust");
+        // The tool result and the next user message share one turn
+        assert_eq!(turns[2]["role"], "user");
+        assert_eq!(turns[2]["content"][0]["type"], "tool_result");
+        assert_eq!(turns[2]["content"][1]["text"], "Thanks");
+        assert_eq!(body["tools"][0]["input_schema"]["type"], "object");
+        assert!(body["tools"][0]["cache_control"].is_object());
+    }
+
+    #[test]
+    fn test_stream_decoder() {
+        let mut decoder = StreamDecoder::default();
+        let events = [
+            r#"{"type":"message_start","message":{"usage":{"input_tokens":10}}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#,
+            r#"{"type":"content_block_start","index":1,
+                "content_block":{"type":"tool_use","id":"toolu_1","name":"web_search"}}"#,
+            r#"{"type":"content_block_delta","index":1,
+                "delta":{"type":"input_json_delta","partial_json":"{\"q\":"}}"#,
+            r#"{"type":"content_block_delta","index":1,
+                "delta":{"type":"input_json_delta","partial_json":"\"rust\"}"}}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"tool_use"},
+                "usage":{"output_tokens":4}}"#,
+            r#"{"type":"message_stop"}"#,
+        ];
+        let chunks: Vec<StreamChunk> = events
+            .iter()
+            .filter_map(|e| decoder.decode(e).unwrap())
+            .collect();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].delta, "Hi");
+        let last = &chunks[1];
+        assert_eq!(last.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(last.tool_calls[0].function.arguments, r#"{"q":"rust"}"#);
+        assert_eq!(last.usage.as_ref().unwrap().total_tokens, 14);
+
+        let overloaded = r#"{"type":"error","error":{"type":"overloaded_error","message":"busy"}}"#;
+        assert!(decoder.decode(overloaded).unwrap_err().is_retryable());
+    }
+}
@@ -2,14 +2,49 @@ use async_trait::async_trait;
 use bizclaw_core::config::BizClawConfig;
 use bizclaw_core::error::{BizClawError, Result};
 use bizclaw_core::traits::provider::{GenerateParams, Provider};
-use bizclaw_core::types::{Message, ModelInfo, ProviderResponse, Role, ToolDefinition};
+use crate::chat_template::ChatTemplate;
+use crate::tool_prompt;
+use bizclaw_core::types::{Message, ModelInfo, ProviderResponse, Role, ToolDefinition, Usage};
 use tokio::sync::Mutex;
 
 pub struct BrainProvider {
     engine: Mutex<bizclaw_brain::BrainEngine>,
+    template: ChatTemplate,
 }
 
 impl BrainProvider {
+    /// Tokens of the leading system messages as the template renders them,
+    /// so a conversation that fills the context never loses them.
+    fn system_tokens(&self, engine: &bizclaw_brain::BrainEngine, messages: &[Message]) -> u32 {
+        let system = messages
+            .iter()
+            .take_while(|m| m.role == Role::System)
+            .count();
+        if system == 0 {
+            return 0;
+        }
+        engine
+            .count_tokens(&self.template.render(&messages[..system]))
+            .map_or(0, |n| n as u32)
+    }
+
+    /// The reply in `result`, with its token usage.
+    fn response(&self, result: bizclaw_brain::GenerationResult) -> ProviderResponse {
+        let finish_reason = match result.finish_reason {
+            bizclaw_brain::FinishReason::Length => "length",
+            _ => "stop",
+        };
+        ProviderResponse {
+            finish_reason: Some(finish_reason.into()),
+            usage: Some(Usage {
+                prompt_tokens: result.prompt_tokens as u32,
+                completion_tokens: result.generated_tokens as u32,
+                total_tokens: (result.prompt_tokens + result.generated_tokens) as u32,
+            }),
+            ..ProviderResponse::text(result.text.trim())
+        }
+    }
+
     pub fn new(config: &BizClawConfig) -> Result<Self> {
         let brain_config = bizclaw_brain::BrainConfig {
             threads: config.brain.threads,
@@ -18,6 +53,21 @@ impl BrainProvider {
             temperature: config.brain.temperature,
             top_p: config.brain.top_p,
             json_mode: config.brain.json_mode,
+            skip_unknown_tensors: config.brain.skip_unknown_tensors,
+            tuning_file: config.brain.tuning_file(),
+            kv_cache: bizclaw_brain::kv_cache::KvCacheType::from_name(&config.brain.kv_cache)
+                .unwrap_or_default(),
+            context_shift: config.brain.context_shift,
+            rope_scaling: bizclaw_brain::rope::RopeScaling::from_name(
+                &config.brain.rope_scaling,
+                config.brain.rope_scaling_factor,
+            ),
+            pin_threads: config.brain.pin_threads,
+            reserve_runtime_core: config.brain.reserve_runtime_core,
+            numa: config.brain.numa,
+            backend: bizclaw_brain::gpu::Backend::from_name(&config.brain.backend)
+                .unwrap_or_default(),
+            ..Default::default()
         };
 
         let mut engine = bizclaw_brain::BrainEngine::new(brain_config);
@@ -45,8 +95,13 @@ impl BrainProvider {
             );
         }
 
+        let template =
+            ChatTemplate::resolve(&config.brain.chat_template, &model_path.to_string_lossy());
+        tracing::debug!("Brain provider: {template:?} chat template");
+
         Ok(Self {
             engine: Mutex::new(engine),
+            template,
         })
     }
 }
@@ -79,7 +134,7 @@ impl Provider for BrainProvider {
     async fn chat(
         &self,
         messages: &[Message],
-        _tools: &[ToolDefinition],
+        tools: &[ToolDefinition],
         params: &GenerateParams,
     ) -> Result<ProviderResponse> {
         if !self.engine.lock().await.is_loaded() {
@@ -88,17 +143,51 @@ impl Provider for BrainProvider {
             ));
         }
 
-        // Format messages into a chat prompt (Llama-style)
-        let prompt = format_chat_prompt(messages);
-
         let max_tokens = if params.max_tokens > 0 {
             params.max_tokens
         } else {
             256
         };
+        let keep_tokens = self.system_tokens(&*self.engine.lock().await, messages);
+        let generation = bizclaw_brain::GenerationParams {
+            keep_tokens,
+            temperature: Some(params.temperature),
+            top_p: Some(params.top_p),
+            stop: self
+                .template
+                .stop_sequences()
+                .iter()
+                .map(|s| s.to_string())
+                .chain(params.stop.iter().cloned())
+                .collect(),
+            ..bizclaw_brain::GenerationParams::max_tokens(
+                max_tokens.min(self.engine.lock().await.config().max_tokens),
+            )
+        };
+
+        if !tools.is_empty() {
+            let prompt = self.template.render(&tool_prompt::prepare(messages, tools));
+            let call_id = format!("call_{}", uuid::Uuid::new_v4().simple());
+            let mut engine = self.engine.lock().await;
+            return match engine.generate_json(&prompt, max_tokens) {
+                Ok(reply) => Ok(tool_prompt::parse_reply(&reply, &call_id)),
+                // Cut off mid-object: answer again without tools.
+                Err(e) => {
+                    tracing::debug!("Brain provider: {e}");
+                    let result =
+                        engine.generate_with(&self.template.render(messages), &generation)?;
+                    Ok(self.response(result))
+                }
+            };
+        }
 
-        let response = self.engine.lock().await.generate(&prompt, max_tokens)?;
-        Ok(ProviderResponse::text(response))
+        let prompt = self.template.render(messages);
+        let result = self
+            .engine
+            .lock()
+            .await
+            .generate_with(&prompt, &generation)?;
+        Ok(self.response(result))
     }
 
     async fn list_models(&self) -> Result<Vec<ModelInfo>> {
@@ -158,28 +247,36 @@ impl Provider for BrainProvider {
     async fn health_check(&self) -> Result<bool> {
         Ok(self.engine.lock().await.is_loaded())
     }
-}
 
-/// Format messages into a LLaMA-style chat prompt.
-fn format_chat_prompt(messages: &[Message]) -> String {
-    let mut prompt = String::new();
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut engine = self.engine.lock().await;
+        texts.iter().map(|text| engine.embed(text)).collect()
+    }
 
-    for msg in messages {
-        match msg.role {
-            Role::System => {
-                prompt.push_str(&format!("[INST] <<SYS>>\n{}\n<</SYS>>\n\n", msg.content));
-            }
-            Role::User => {
-                prompt.push_str(&format!("{} [/INST]", msg.content));
-            }
-            Role::Assistant => {
-                prompt.push_str(&format!(" {} </s><s>[INST] ", msg.content));
-            }
-            Role::Tool => {
-                prompt.push_str(&format!("Tool result: {} [/INST]", msg.content));
-            }
-        }
+    /// The engine caps every reply at its own `max_tokens`, so a raised
+    /// `brain.max_tokens` only takes effect once it is passed down.
+    async fn reload(&self, config: &BizClawConfig) {
+        self.engine
+            .lock()
+            .await
+            .set_max_tokens(config.brain.max_tokens);
     }
+}
 
-    prompt
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reload_raises_max_tokens() {
+        let mut config = BizClawConfig::default();
+        config.brain.model_path = "/nonexistent/model.gguf".into();
+        config.brain.max_tokens = 128;
+        let provider = BrainProvider::new(&config).unwrap();
+        assert_eq!(provider.engine.lock().await.config().max_tokens, 128);
+
+        config.brain.max_tokens = 1024;
+        provider.reload(&config).await;
+        assert_eq!(provider.engine.lock().await.config().max_tokens, 1024);
+    }
 }
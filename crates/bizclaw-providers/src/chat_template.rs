@@ -0,0 +1,276 @@
+//! Chat templates — turn a message list into the raw prompt a local model
+//! was fine-tuned on.
+//!
+//! Remote providers take structured messages, but a GGUF model only sees
+//! text: a Llama-3 model prompted in Llama-2 format rambles or never stops.
+//! The template is picked from `brain.chat_template` or guessed from the
+//! model file name.
+
+use bizclaw_core::types::{Message, Role};
+
+/// Prompt format of a local chat model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatTemplate {
+    /// `[INST] <<SYS>> … <</SYS>> … [/INST]`
+    Llama2,
+    /// `<|start_header_id|>role<|end_header_id|> … <|eot_id|>`
+    Llama3,
+    /// `<|im_start|>role … <|im_end|>` (Qwen, Hermes, Yi, …)
+    ChatMl,
+    /// `<start_of_turn>user … <end_of_turn>` — no system role.
+    Gemma,
+    /// `<|user|> … <|end|>`
+    Phi3,
+    /// `<|user|> … </s>` (TinyLlama, Zephyr)
+    Zephyr,
+}
+
+impl ChatTemplate {
+    /// Parse a configured template name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+            "llama2" | "llama" => Some(Self::Llama2),
+            "llama3" => Some(Self::Llama3),
+            "chatml" | "qwen" => Some(Self::ChatMl),
+            "gemma" => Some(Self::Gemma),
+            "phi3" => Some(Self::Phi3),
+            "zephyr" | "tinyllama" => Some(Self::Zephyr),
+            _ => None,
+        }
+    }
+
+    /// Guess the template from a model file name; Llama-2 when unknown.
+    pub fn detect(model_name: &str) -> Self {
+        let name = model_name.to_ascii_lowercase();
+        let has = |s: &str| name.contains(s);
+        if has("llama-3") || has("llama3") {
+            Self::Llama3
+        } else if has("qwen") || has("hermes") || has("chatml") || has("yi-") {
+            Self::ChatMl
+        } else if has("gemma") {
+            Self::Gemma
+        } else if has("phi-3") || has("phi3") {
+            Self::Phi3
+        } else if has("tinyllama") || has("zephyr") {
+            Self::Zephyr
+        } else {
+            Self::Llama2
+        }
+    }
+
+    /// Resolve from config: an explicit name wins, otherwise detect from the model path.
+    pub fn resolve(configured: &str, model_path: &str) -> Self {
+        Self::from_name(configured).unwrap_or_else(|| {
+            if !configured.is_empty() {
+                tracing::warn!("Unknown chat template '{configured}', detecting from model name");
+            }
+            Self::detect(model_path)
+        })
+    }
+
+    /// Render the conversation, ending with the assistant turn opener so the
+    /// model continues as the assistant. BOS is left to the tokenizer.
+    pub fn render(&self, messages: &[Message]) -> String {
+        match self {
+            Self::Llama2 => render_llama2(messages),
+            Self::Llama3 => {
+                let mut out = String::new();
+                for m in messages {
+                    out.push_str(&format!(
+                        "<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>",
+                        role_name(&m.role),
+                        m.content
+                    ));
+                }
+                out.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+                out
+            }
+            Self::ChatMl => {
+                let mut out = String::new();
+                for m in messages {
+                    out.push_str(&format!(
+                        "<|im_start|>{}\n{}<|im_end|>\n",
+                        role_name(&m.role),
+                        m.content
+                    ));
+                }
+                out.push_str("<|im_start|>assistant\n");
+                out
+            }
+            Self::Gemma => {
+                // No system role: fold system text into the next user turn.
+                let mut out = String::new();
+                let mut pending_system = Vec::new();
+                for m in messages {
+                    match m.role {
+                        Role::System => pending_system.push(m.content.as_str()),
+                        Role::Assistant => out.push_str(&format!(
+                            "<start_of_turn>model\n{}<end_of_turn>\n",
+                            m.content
+                        )),
+                        Role::User | Role::Tool => {
+                            let mut text = pending_system.join("\n\n");
+                            pending_system.clear();
+                            if !text.is_empty() {
+                                text.push_str("\n\n");
+                            }
+                            text.push_str(&m.content);
+                            out.push_str(&format!("<start_of_turn>user\n{text}<end_of_turn>\n"));
+                        }
+                    }
+                }
+                out.push_str("<start_of_turn>model\n");
+                out
+            }
+            Self::Phi3 => {
+                let mut out = String::new();
+                for m in messages {
+                    out.push_str(&format!(
+                        "<|{}|>\n{}<|end|>\n",
+                        role_name(&m.role),
+                        m.content
+                    ));
+                }
+                out.push_str("<|assistant|>\n");
+                out
+            }
+            Self::Zephyr => {
+                let mut out = String::new();
+                for m in messages {
+                    out.push_str(&format!("<|{}|>\n{}</s>\n", role_name(&m.role), m.content));
+                }
+                out.push_str("<|assistant|>\n");
+                out
+            }
+        }
+    }
+
+    /// Markers that end the assistant turn, for use as stop sequences.
+    pub fn stop_sequences(&self) -> &'static [&'static str] {
+        match self {
+            Self::Llama2 => &["</s>", "[INST]"],
+            Self::Llama3 => &["<|eot_id|>", "<|start_header_id|>"],
+            Self::ChatMl => &["<|im_end|>", "<|im_start|>"],
+            Self::Gemma => &["<end_of_turn>", "<start_of_turn>"],
+            Self::Phi3 => &["<|end|>", "<|user|>"],
+            Self::Zephyr => &["</s>", "<|user|>"],
+        }
+    }
+
+    /// Cut generated text at the first stop sequence.
+    pub fn trim_output<'a>(&self, output: &'a str) -> &'a str {
+        let end = self
+            .stop_sequences()
+            .iter()
+            .filter_map(|s| output.find(s))
+            .min()
+            .unwrap_or(output.len());
+        output[..end].trim()
+    }
+}
+
+fn role_name(role: &Role) -> &'static str {
+    match role {
+        Role::System => "system",
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        // Templates without a tool role read tool output as user input.
+        Role::Tool => "user",
+    }
+}
+
+/// The engine adds BOS itself, so the first `<s>` is omitted.
+fn render_llama2(messages: &[Message]) -> String {
+    let mut prompt = String::from("[INST] ");
+    let mut open = true;
+    for msg in messages {
+        match msg.role {
+            Role::System => prompt.push_str(&format!("<<SYS>>\n{}\n<</SYS>>\n\n", msg.content)),
+            Role::User | Role::Tool => {
+                if !open {
+                    prompt.push_str("<s>[INST] ");
+                }
+                let prefix = if msg.role == Role::Tool {
+                    "Tool result: "
+                } else {
+                    ""
+                };
+                prompt.push_str(&format!("{prefix}{} [/INST]", msg.content));
+                open = false;
+            }
+            Role::Assistant => prompt.push_str(&format!(" {} </s>", msg.content)),
+        }
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convo() -> Vec<Message> {
+        vec![
+            Message::system("Be brief."),
+            Message::user("Hi"),
+            Message::assistant("Hello!"),
+            Message::user("Price?"),
+        ]
+    }
+
+    #[test]
+    fn test_detect_from_file_name() {
+        assert_eq!(
+            ChatTemplate::detect("Meta-Llama-3-8B-Instruct.Q4_K_M.gguf"),
+            ChatTemplate::Llama3
+        );
+        assert_eq!(
+            ChatTemplate::detect("qwen2.5-1.5b-instruct-q4.gguf"),
+            ChatTemplate::ChatMl
+        );
+        assert_eq!(
+            ChatTemplate::detect("~/.bizclaw/models/tinyllama-1.1b-chat-v1.0.Q4_K_M.gguf"),
+            ChatTemplate::Zephyr
+        );
+        assert_eq!(ChatTemplate::detect("mystery.gguf"), ChatTemplate::Llama2);
+        assert_eq!(
+            ChatTemplate::resolve("chatml", "gemma.gguf"),
+            ChatTemplate::ChatMl
+        );
+        assert_eq!(
+            ChatTemplate::resolve("", "gemma-2b.gguf"),
+            ChatTemplate::Gemma
+        );
+    }
+
+    #[test]
+    fn test_chatml_render() {
+        let prompt = ChatTemplate::ChatMl.render(&convo());
+        assert!(prompt.starts_with("<|im_start|>system\nBe brief.<|im_end|>\n"));
+        assert!(prompt.contains("<|im_start|>assistant\nHello!<|im_end|>\n"));
+        assert!(prompt.ends_with("<|im_start|>user\nPrice?<|im_end|>\n<|im_start|>assistant\n"));
+    }
+
+    #[test]
+    fn test_llama2_render() {
+        let prompt = ChatTemplate::Llama2.render(&convo());
+        assert_eq!(
+            prompt,
+            "[INST] <<SYS>>\nBe brief.\n<</SYS>>\n\nHi [/INST] Hello! </s><s>[INST] Price? [/INST]"
+        );
+    }
+
+    #[test]
+    fn test_gemma_folds_system_into_user() {
+        let prompt = ChatTemplate::Gemma.render(&convo());
+        assert!(prompt.starts_with("<start_of_turn>user\nBe brief.\n\nHi<end_of_turn>\n"));
+        assert!(!prompt.contains("system"));
+        assert!(prompt.ends_with("<start_of_turn>model\n"));
+    }
+
+    #[test]
+    fn test_trim_output() {
+        let t = ChatTemplate::ChatMl;
+        assert_eq!(t.trim_output(" Sure.<|im_end|>\n<|im_start|>user"), "Sure.");
+        assert_eq!(t.trim_output("No stop"), "No stop");
+    }
+}
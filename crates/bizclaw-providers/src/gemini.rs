@@ -0,0 +1,405 @@
+//! Native Google Gemini provider (`generateContent`).
+//!
+//! Talks to the Generative Language API directly instead of its OpenAI
+//! shim, which gets function calling, streaming over `alt=sse` and
+//! `batchEmbedContents` embeddings. Gemini has no tool call ids, so calls
+//! are given fresh ids and tool results are matched back by function name.
+
+use async_trait::async_trait;
+use bizclaw_core::config::{BizClawConfig, RagConfig};
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::provider::{GenerateParams, Provider, ProviderStream};
+use bizclaw_core::types::{
+    FunctionCall, Message, ModelInfo, ProviderResponse, Role, StreamChunk, ToolCall,
+    ToolDefinition, Usage,
+};
+use futures::StreamExt;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+use crate::openai_compatible::{api_error, transport_error};
+use crate::provider_registry::ProviderConfig;
+
+/// Used when `[rag] embedding_model` is left at its OpenAI default.
+const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-004";
+
+pub struct GeminiProvider {
+    api_key: String,
+    /// e.g. `https://generativelanguage.googleapis.com/v1beta`.
+    base_url: String,
+    default_models: Vec<ModelInfo>,
+    embedding_model: String,
+    client: reqwest::Client,
+}
+
+impl GeminiProvider {
+    pub fn from_registry(registry: &ProviderConfig, config: &BizClawConfig) -> Result<Self> {
+        let embedding_model = if config.rag.embedding_model == RagConfig::default().embedding_model
+        {
+            DEFAULT_EMBEDDING_MODEL.to_string()
+        } else {
+            config.rag.embedding_model.clone()
+        };
+        Ok(Self {
+            api_key: registry.resolve_api_key(config),
+            base_url: registry
+                .resolve_base_url(config)
+                .trim_end_matches('/')
+                .to_string(),
+            default_models: registry.model_infos(),
+            embedding_model,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    fn request_body(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Value {
+        // Tool results only carry the call id; Gemini wants the function name
+        let names: HashMap<&str, &str> = messages
+            .iter()
+            .flat_map(|m| m.tool_calls.iter().flatten())
+            .map(|c| (c.id.as_str(), c.function.name.as_str()))
+            .collect();
+
+        let mut system = Vec::new();
+        let mut contents: Vec<Value> = Vec::new();
+        for msg in messages {
+            let (role, parts) = match msg.role {
+                Role::System => {
+                    system.push(json!({ "text": msg.content }));
+                    continue;
+                }
+                Role::User => ("user", vec![json!({ "text": msg.content })]),
+                Role::Assistant => {
+                    let mut parts = Vec::new();
+                    if !msg.content.is_empty() {
+                        parts.push(json!({ "text": msg.content }));
+                    }
+                    for call in msg.tool_calls.iter().flatten() {
+                        let args: Value = serde_json::from_str(&call.function.arguments)
+                            .unwrap_or_else(|_| json!({}));
+                        parts.push(json!({
+                            "functionCall": { "name": call.function.name, "args": args }
+                        }));
+                    }
+                    ("model", parts)
+                }
+                Role::Tool => {
+                    let id = msg.tool_call_id.as_deref().unwrap_or_default();
+                    let name = names.get(id).copied().or(msg.name.as_deref());
+                    (
+                        "user",
+                        vec![json!({
+                            "functionResponse": {
+                                "name": name.unwrap_or(id),
+                                "response": { "content": msg.content },
+                            }
+                        })],
+                    )
+                }
+            };
+            match contents.last_mut() {
+                Some(last) if last["role"] == role => {
+                    if let Some(existing) = last["parts"].as_array_mut() {
+                        existing.extend(parts);
+                    }
+                }
+                _ => contents.push(json!({ "role": role, "parts": parts })),
+            }
+        }
+
+        let mut generation = json!({
+            "temperature": params.temperature,
+            "topP": params.top_p,
+            "maxOutputTokens": params.max_tokens,
+        });
+        if !params.stop.is_empty() {
+            generation["stopSequences"] = json!(params.stop);
+        }
+        let mut body = json!({
+            "contents": contents,
+            "generationConfig": generation,
+        });
+        if !system.is_empty() {
+            body["systemInstruction"] = json!({ "parts": system });
+        }
+        if !tools.is_empty() {
+            let declarations: Vec<Value> = tools
+                .iter()
+                .map(|t| {
+                    json!({
+                        "name": t.name,
+                        "description": t.description,
+                        "parameters": schema(&t.parameters),
+                    })
+                })
+                .collect();
+            body["tools"] = json!([{ "functionDeclarations": declarations }]);
+        }
+        body
+    }
+
+    fn model_url(&self, model: &str, method: &str) -> String {
+        let model = model.strip_prefix("models/").unwrap_or(model);
+        format!("{}/models/{model}:{method}", self.base_url)
+    }
+
+    async fn post(&self, url: &str, body: &Value) -> Result<reqwest::Response> {
+        if self.api_key.is_empty() {
+            return Err(BizClawError::ApiKeyMissing("gemini".into()));
+        }
+        let resp = self
+            .client
+            .post(url)
+            .header("x-goog-api-key", &self.api_key)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| transport_error("gemini", url, e))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(api_error("gemini", "API error", status, &headers, &text));
+        }
+        Ok(resp)
+    }
+}
+
+/// A JSON schema reduced to the OpenAPI subset Gemini accepts.
+fn schema(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(key, _)| !matches!(key.as_str(), "$schema" | "additionalProperties"))
+                .map(|(key, v)| (key.clone(), schema(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(schema).collect()),
+        other => other.clone(),
+    }
+}
+
+/// One `GenerateContentResponse` — a whole reply or one streamed piece.
+fn parse_response(json: &Value) -> StreamChunk {
+    let candidate = &json["candidates"][0];
+    let parts = candidate["content"]["parts"].as_array();
+    let mut chunk = StreamChunk::default();
+    for part in parts.into_iter().flatten() {
+        if let Some(text) = part["text"].as_str() {
+            chunk.delta.push_str(text);
+        } else if let Some(call) = part.get("functionCall") {
+            chunk.tool_calls.push(ToolCall {
+                id: format!("call_{}", uuid::Uuid::new_v4().simple()),
+                r#type: "function".into(),
+                function: FunctionCall {
+                    name: call["name"].as_str().unwrap_or_default().to_string(),
+                    arguments: call.get("args").unwrap_or(&json!({})).to_string(),
+                },
+            });
+        }
+    }
+    chunk.finish_reason = candidate["finishReason"].as_str().map(|reason| {
+        match reason {
+            _ if !chunk.tool_calls.is_empty() => "tool_calls",
+            "STOP" => "stop",
+            "MAX_TOKENS" => "length",
+            "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" => "content_filter",
+            other => other,
+        }
+        .to_string()
+    });
+    chunk.usage = json["usageMetadata"].as_object().map(|u| {
+        let count = |key: &str| u.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        Usage {
+            prompt_tokens: count("promptTokenCount"),
+            completion_tokens: count("candidatesTokenCount"),
+            total_tokens: count("totalTokenCount"),
+        }
+    });
+    chunk
+}
+
+#[async_trait]
+impl Provider for GeminiProvider {
+    fn name(&self) -> &str {
+        "gemini"
+    }
+
+    async fn chat(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderResponse> {
+        let url = self.model_url(&params.model, "generateContent");
+        let body = self.request_body(messages, tools, params);
+        let json: Value = self
+            .post(&url, &body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| BizClawError::Http(e.to_string()))?;
+        if json["candidates"].as_array().is_none_or(|c| c.is_empty()) {
+            let reason = json["promptFeedback"]["blockReason"]
+                .as_str()
+                .unwrap_or("no candidates");
+            return Err(BizClawError::Provider(format!(
+                "gemini returned no reply: {reason}"
+            )));
+        }
+
+        let chunk = parse_response(&json);
+        Ok(ProviderResponse {
+            content: (!chunk.delta.is_empty()).then_some(chunk.delta),
+            tool_calls: chunk.tool_calls,
+            finish_reason: chunk.finish_reason,
+            usage: chunk.usage,
+        })
+    }
+
+    async fn stream(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderStream> {
+        let url = self.model_url(&params.model, "streamGenerateContent?alt=sse");
+        let body = self.request_body(messages, tools, params);
+        let resp = self.post(&url, &body).await?;
+
+        let chunks = crate::sse::events(resp).map(|event| {
+            let json: Value = serde_json::from_str(&event?)?;
+            if let Some(message) = json["error"]["message"].as_str() {
+                return Err(BizClawError::Provider(format!(
+                    "gemini stream error: {message}"
+                )));
+            }
+            Ok(parse_response(&json))
+        });
+        Ok(Box::pin(chunks))
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        Ok(self.default_models.clone())
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        Ok(!self.api_key.is_empty())
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let model = format!("models/{}", self.embedding_model);
+        let url = self.model_url(&self.embedding_model, "batchEmbedContents");
+        let requests: Vec<Value> = texts
+            .iter()
+            .map(|text| json!({ "model": model, "content": { "parts": [{ "text": text }] } }))
+            .collect();
+        let json: Value = self
+            .post(&url, &json!({ "requests": requests }))
+            .await?
+            .json()
+            .await
+            .map_err(|e| BizClawError::Http(format!("Invalid embeddings response: {e}")))?;
+        let vectors: Vec<Vec<f32>> = json["embeddings"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|e| {
+                e["values"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|x| x.as_f64())
+                    .map(|x| x as f32)
+                    .collect()
+            })
+            .collect();
+        if vectors.len() != texts.len() {
+            return Err(BizClawError::Provider(format!(
+                "gemini returned {} embeddings for {} inputs",
+                vectors.len(),
+                texts.len()
+            )));
+        }
+        Ok(vectors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider() -> GeminiProvider {
+        let registry = crate::provider_registry::get_provider_config("google").unwrap();
+        GeminiProvider::from_registry(registry, &BizClawConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_request_body() {
+        let call = ToolCall {
+            id: "call_1".into(),
+            r#type: "function".into(),
+            function: FunctionCall {
+                name: "web_search".into(),
+                arguments: r#"{"q":"rust"}"#.into(),
+            },
+        };
+        let mut assistant = Message::assistant("");
+        assistant.tool_calls = Some(vec![call]);
+        let messages = [
+            Message::system("Be brief."),
+            Message::user("Search rust"),
+            assistant,
+            Message::tool("results", "call_1"),
+        ];
+        let tools = [ToolDefinition {
+            name: "web_search".into(),
+            description: "Search the web".into(),
+            parameters: json!({
+                "type": "object",
+                "additionalProperties": false,
+                "properties": { "q": { "type": "string" } },
+            }),
+        }];
+        let body = provider().request_body(&messages, &tools, &GenerateParams::default());
+
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be brief.");
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["functionCall"]["args"]["q"], "rust");
+        let response = &contents[2]["parts"][0]["functionResponse"];
+        assert_eq!(response["name"], "web_search");
+        let parameters = &body["tools"][0]["functionDeclarations"][0]["parameters"];
+        assert!(parameters.get("additionalProperties").is_none());
+        assert_eq!(parameters["properties"]["q"]["type"], "string");
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let json = json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [
+                    { "text": "Looking it up" },
+                    { "functionCall": { "name": "web_search", "args": { "q": "rust" } } },
+                ]},
+                "finishReason": "STOP",
+            }],
+            "usageMetadata": { "promptTokenCount": 3, "candidatesTokenCount": 5, "totalTokenCount": 8 },
+        });
+        let chunk = parse_response(&json);
+        assert_eq!(chunk.delta, "Looking it up");
+        assert_eq!(chunk.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(chunk.tool_calls[0].function.arguments, r#"{"q":"rust"}"#);
+        assert_eq!(chunk.usage.unwrap().total_tokens, 8);
+
+        assert_eq!(
+            provider().model_url("models/gemini-2.5-flash", "generateContent"),
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash:generateContent"
+        );
+    }
+}
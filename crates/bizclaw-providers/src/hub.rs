@@ -0,0 +1,438 @@
+//! Hugging Face hub downloads and the local model registry.
+//!
+//! `bizclaw pull <owner>/<repo>/<file.gguf>` downloads a GGUF file into the
+//! models directory with [`HubClient::pull`]: into a `.part` file that a
+//! later pull resumes, checked against the SHA-256 the hub publishes for
+//! it. Pulled files are recorded in the directory's `registry.json`
+//! ([`ModelRegistry`]), which the gateway's `/v1/models` lists too.
+
+use bizclaw_core::config::BizClawConfig;
+use bizclaw_core::error::{BizClawError, Result};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+pub const HUB_URL: &str = "https://huggingface.co";
+
+const REGISTRY_FILE: &str = "registry.json";
+
+/// A file in a hub repo: `owner/repo[@revision]/path/to/file.gguf`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelRef {
+    pub repo: String,
+    pub file: String,
+    pub revision: String,
+}
+
+impl ModelRef {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim().trim_matches('/');
+        let mut parts = spec.splitn(3, '/');
+        let (Some(owner), Some(repo), Some(file)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(BizClawError::Config(format!(
+                "Expected <owner>/<repo>/<file.gguf>, got '{spec}'"
+            )));
+        };
+        let (repo, revision) = repo.split_once('@').unwrap_or((repo, "main"));
+        if owner.is_empty() || repo.is_empty() || revision.is_empty() {
+            return Err(BizClawError::Config(format!(
+                "Invalid model reference '{spec}'"
+            )));
+        }
+        if !file.ends_with(".gguf") {
+            return Err(BizClawError::Config(format!(
+                "'{file}' is not a .gguf file"
+            )));
+        }
+        Ok(Self {
+            repo: format!("{owner}/{repo}"),
+            file: file.to_string(),
+            revision: revision.to_string(),
+        })
+    }
+
+    /// The name the model is registered and listed under.
+    pub fn name(&self) -> String {
+        format!("{}/{}", self.repo, self.file)
+    }
+
+    /// The file name it is saved as in the models directory.
+    pub fn file_name(&self) -> &str {
+        self.file.rsplit('/').next().unwrap_or(&self.file)
+    }
+}
+
+/// A pulled model, as recorded in `registry.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelEntry {
+    pub name: String,
+    pub repo: String,
+    pub file: String,
+    pub revision: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+    /// Unix seconds.
+    pub pulled_at: u64,
+}
+
+/// The models a directory holds, by name.
+#[derive(Debug, Default)]
+pub struct ModelRegistry {
+    path: PathBuf,
+    models: Vec<ModelEntry>,
+}
+
+impl ModelRegistry {
+    /// `~/.bizclaw/models`, where the brain provider looks for models too.
+    pub fn default_dir() -> PathBuf {
+        BizClawConfig::home_dir().join("models")
+    }
+
+    /// The registry of `dir`; empty if nothing was pulled into it yet.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let path = dir.join(REGISTRY_FILE);
+        let models = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self { path, models })
+    }
+
+    /// Registered models whose file is still there.
+    pub fn models(&self) -> impl Iterator<Item = &ModelEntry> {
+        self.models.iter().filter(|m| m.path.exists())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ModelEntry> {
+        self.models().find(|m| m.name == name)
+    }
+
+    /// Record `entry`, replacing the one of the same name.
+    pub fn insert(&mut self, entry: ModelEntry) {
+        self.models.retain(|m| m.name != entry.name);
+        self.models.push(entry);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&self.models)?)?;
+        Ok(())
+    }
+}
+
+/// What the hub says about a file.
+#[derive(Debug, Clone)]
+pub struct RemoteFile {
+    pub size: u64,
+    /// Only published for files stored in LFS, which GGUF files are.
+    pub sha256: Option<String>,
+}
+
+/// Downloads from the hub; `HF_TOKEN` authenticates for gated repos.
+pub struct HubClient {
+    client: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl Default for HubClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HubClient {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: HUB_URL.to_string(),
+            token: std::env::var("HF_TOKEN").ok().filter(|t| !t.is_empty()),
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        let request = self.client.get(url);
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Size and checksum of `model`'s file, from the repo's file tree.
+    pub async fn remote_file(&self, model: &ModelRef) -> Result<RemoteFile> {
+        let mut url = format!(
+            "{}/api/models/{}/tree/{}",
+            self.base_url, model.repo, model.revision
+        );
+        if let Some((dir, _)) = model.file.rsplit_once('/') {
+            url.push_str(&format!("/{dir}"));
+        }
+        let response = self
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| BizClawError::Http(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(BizClawError::Http(format!(
+                "{} not found on the hub ({})",
+                model.repo,
+                response.status()
+            )));
+        }
+        let tree: Vec<serde_json::Value> = response
+            .json()
+            .await
+            .map_err(|e| BizClawError::Http(e.to_string()))?;
+        let entry = tree
+            .iter()
+            .find(|e| e["path"].as_str() == Some(model.file.as_str()))
+            .ok_or_else(|| BizClawError::ModelNotFound(model.name()))?;
+        Ok(RemoteFile {
+            size: entry["lfs"]["size"]
+                .as_u64()
+                .or_else(|| entry["size"].as_u64())
+                .unwrap_or(0),
+            sha256: entry["lfs"]["oid"].as_str().map(str::to_string),
+        })
+    }
+
+    /// Download `model` into `dir`, resuming a partial download, and verify
+    /// its checksum. `on_progress` gets the bytes done and the total.
+    pub async fn pull(
+        &self,
+        model: &ModelRef,
+        dir: &Path,
+        mut on_progress: impl FnMut(u64, u64),
+    ) -> Result<ModelEntry> {
+        let remote = self.remote_file(model).await?;
+        tokio::fs::create_dir_all(dir).await?;
+        let dest = dir.join(model.file_name());
+        let part = dir.join(format!("{}.part", model.file_name()));
+
+        let mut done = tokio::fs::metadata(&part).await.map_or(0, |m| m.len());
+        if remote.size == 0 || done < remote.size {
+            let url = format!(
+                "{}/{}/resolve/{}/{}",
+                self.base_url, model.repo, model.revision, model.file
+            );
+            let mut request = self.get(&url);
+            if done > 0 {
+                request = request.header(reqwest::header::RANGE, format!("bytes={done}-"));
+            }
+            let response = request
+                .send()
+                .await
+                .map_err(|e| BizClawError::Http(e.to_string()))?;
+            let status = response.status();
+            // Nothing past the end of the file: the part already holds it
+            // all, if the checksum agrees.
+            if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE && done > 0 {
+                on_progress(done, done);
+                return finish(model, &remote, &part, &dest, done).await;
+            }
+            if !status.is_success() {
+                return Err(BizClawError::Http(format!("Download failed: {status}")));
+            }
+            // A server ignoring the range sends the whole file again.
+            let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
+            if !resumed {
+                done = 0;
+            }
+            let total = remote
+                .size
+                .max(done + response.content_length().unwrap_or(0));
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(resumed)
+                .truncate(!resumed)
+                .open(&part)
+                .await?;
+            let mut stream = response.bytes_stream();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(|e| BizClawError::Http(e.to_string()))?;
+                file.write_all(&chunk).await?;
+                done += chunk.len() as u64;
+                on_progress(done, total);
+            }
+            file.flush().await?;
+        } else {
+            on_progress(done, remote.size);
+        }
+        finish(model, &remote, &part, &dest, done).await
+    }
+}
+
+/// Verify the downloaded `part` and move it to `dest`.
+async fn finish(
+    model: &ModelRef,
+    remote: &RemoteFile,
+    part: &Path,
+    dest: &Path,
+    size: u64,
+) -> Result<ModelEntry> {
+    let hashed = part.to_path_buf();
+    let sha256 = tokio::task::spawn_blocking(move || sha256_file(&hashed))
+        .await
+        .map_err(|e| BizClawError::Other(e.to_string()))??;
+    if let Some(expected) = &remote.sha256
+        && !expected.eq_ignore_ascii_case(&sha256)
+    {
+        tokio::fs::remove_file(part).await?;
+        return Err(BizClawError::Http(format!(
+            "Checksum mismatch for {}: expected {expected}, got {sha256}",
+            model.name()
+        )));
+    }
+    tokio::fs::rename(part, dest).await?;
+
+    Ok(ModelEntry {
+        name: model.name(),
+        repo: model.repo.clone(),
+        file: model.file.clone(),
+        revision: model.revision.clone(),
+        path: dest.to_path_buf(),
+        size,
+        sha256,
+        pulled_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    })
+}
+
+/// Hex SHA-256 of a file, read in chunks.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    use std::io::Read;
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_ref_and_registry() {
+        let model = ModelRef::parse("TheBloke/phi-2-GGUF@v1/q4/phi-2.Q4_K_M.gguf").unwrap();
+        assert_eq!(model.repo, "TheBloke/phi-2-GGUF");
+        assert_eq!(model.revision, "v1");
+        assert_eq!(model.file, "q4/phi-2.Q4_K_M.gguf");
+        assert_eq!(model.file_name(), "phi-2.Q4_K_M.gguf");
+        assert_eq!(
+            ModelRef::parse("TheBloke/phi-2-GGUF/phi-2.Q4_K_M.gguf")
+                .unwrap()
+                .revision,
+            "main"
+        );
+        assert!(ModelRef::parse("TheBloke/phi-2-GGUF").is_err());
+        assert!(ModelRef::parse("TheBloke/phi-2-GGUF/README.md").is_err());
+
+        let dir = std::env::temp_dir().join(format!("bizclaw-hub-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(model.file_name());
+        std::fs::write(&path, b"GGUF").unwrap();
+        let entry = ModelEntry {
+            name: model.name(),
+            repo: model.repo.clone(),
+            file: model.file.clone(),
+            revision: model.revision.clone(),
+            path: path.clone(),
+            size: 4,
+            sha256: sha256_file(&path).unwrap(),
+            pulled_at: 0,
+        };
+        let mut registry = ModelRegistry::open(&dir).unwrap();
+        registry.insert(entry.clone());
+        registry.insert(entry.clone());
+        registry.save().unwrap();
+
+        let registry = ModelRegistry::open(&dir).unwrap();
+        assert_eq!(registry.models().count(), 1);
+        assert_eq!(registry.get(&model.name()), Some(&entry));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(registry.models().count(), 0);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    /// Answer every request on a local port: the file listing for the
+    /// tree API, 416 for any download.
+    async fn serve_complete(listing: serde_json::Value) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let response = if request.contains("/tree/") {
+                    let body = listing.to_string();
+                    format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                } else {
+                    "HTTP/1.1 416 Range Not Satisfiable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".to_string()
+                };
+                socket.write_all(response.as_bytes()).await.ok();
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn test_resume_of_complete_part() {
+        let model = ModelRef::parse("acme/tiny-GGUF/tiny.gguf").unwrap();
+        let content = b"GGUF and the rest of the model";
+        let dir = std::env::temp_dir().join(format!("bizclaw-hub-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let part = dir.join("tiny.gguf.part");
+        std::fs::write(&part, content).unwrap();
+        let oid = {
+            let mut hasher = Sha256::new();
+            hasher.update(content);
+            format!("{:x}", hasher.finalize())
+        };
+        // No size published, so the part can't be known complete up front
+        let listing = serde_json::json!([{ "path": "tiny.gguf", "lfs": { "oid": oid } }]);
+        let client = HubClient::new().with_base_url(&serve_complete(listing).await);
+
+        let entry = client.pull(&model, &dir, |_, _| {}).await.unwrap();
+        assert_eq!(entry.size, content.len() as u64);
+        assert_eq!(entry.sha256, oid);
+        assert_eq!(std::fs::read(dir.join("tiny.gguf")).unwrap(), content);
+        assert!(!part.exists());
+
+        // A part that isn't the file fails the checksum and is dropped
+        std::fs::write(&part, b"GGUF but something else").unwrap();
+        assert!(client.pull(&model, &dir, |_, _| {}).await.is_err());
+        assert!(!part.exists());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -2,31 +2,60 @@
 //!
 //! LLM provider implementations for BizClaw.
 //!
-//! All OpenAI-compatible providers (OpenAI, Anthropic, DeepSeek, Gemini, Groq,
-//! Ollama, LlamaCpp, OpenRouter) are handled by a single `OpenAiCompatibleProvider`.
-//! The `BrainProvider` handles local GGUF models separately.
+//! All OpenAI-compatible providers (OpenAI, DeepSeek, Groq, LlamaCpp,
+//! OpenRouter, …) are handled by a single `OpenAiCompatibleProvider`.
+//! Anthropic, Gemini and Ollama have native clients (`AnthropicProvider`,
+//! `GeminiProvider`, `OllamaProvider`), and the `BrainProvider` handles
+//! local GGUF models.
+//! All of them implement [`Provider`], including streaming. With `[LLM]
+//! fallbacks` or `chains` configured, [`router::ProviderRouter`] fails over
+//! between them.
 
+pub mod anthropic;
 pub mod brain;
+pub mod chat_template;
+pub mod gemini;
+pub mod hub;
+pub mod ollama;
 pub mod openai_compatible;
+pub mod pricing;
 pub mod provider_registry;
+pub mod router;
+pub mod sse;
+pub mod tool_prompt;
 
 use bizclaw_core::config::BizClawConfig;
 use bizclaw_core::error::{BizClawError, Result};
 use bizclaw_core::traits::Provider;
+use provider_registry::ApiFormat;
 
-/// Create a provider from configuration.
+/// Create a provider from configuration, wrapped in a
+/// [`router::ProviderRouter`] when `[LLM]` has fallbacks or chains.
 ///
 /// Resolution order for provider name:
 /// 1. `config.llm.provider` (from `[LLM]` section)
 /// 2. `config.default_provider` (legacy top-level field)
 pub fn create_provider(config: &BizClawConfig) -> Result<Box<dyn Provider>> {
-    // Prefer [LLM] section, fallback to legacy top-level field
-    let provider_name = if !config.llm.provider.is_empty() {
+    let provider = create_single(config)?;
+    if config.llm.fallbacks.is_empty() && config.llm.chains.is_empty() {
+        return Ok(provider);
+    }
+    let router = router::ProviderRouter::from_config(config, provider);
+    Ok(Box::new(router))
+}
+
+/// The configured provider name: `[LLM]` section, else the legacy
+/// top-level field.
+pub(crate) fn provider_name(config: &BizClawConfig) -> &str {
+    if !config.llm.provider.is_empty() {
         config.llm.provider.as_str()
     } else {
         config.default_provider.as_str()
-    };
+    }
+}
 
+fn create_single(config: &BizClawConfig) -> Result<Box<dyn Provider>> {
+    let provider_name = provider_name(config);
     match provider_name {
         // Local GGUF engine — not OpenAI-compatible
         "brain" => Ok(Box::new(brain::BrainProvider::new(config)?)),
@@ -36,13 +65,25 @@ pub fn create_provider(config: &BizClawConfig) -> Result<Box<dyn Provider>> {
             openai_compatible::OpenAiCompatibleProvider::custom(other, config)?,
         )),
 
-        // All known OpenAI-compatible providers
+        // All known cloud and local HTTP providers
         _ => {
             let registry = provider_registry::get_provider_config(provider_name)
                 .ok_or_else(|| BizClawError::ProviderNotFound(provider_name.into()))?;
-            Ok(Box::new(
-                openai_compatible::OpenAiCompatibleProvider::from_registry(registry, config)?,
-            ))
+            let provider: Box<dyn Provider> = match registry.api {
+                ApiFormat::OpenAi => Box::new(
+                    openai_compatible::OpenAiCompatibleProvider::from_registry(registry, config)?,
+                ),
+                ApiFormat::Anthropic => Box::new(anthropic::AnthropicProvider::from_registry(
+                    registry, config,
+                )?),
+                ApiFormat::Gemini => {
+                    Box::new(gemini::GeminiProvider::from_registry(registry, config)?)
+                }
+                ApiFormat::Ollama => {
+                    Box::new(ollama::OllamaProvider::from_registry(registry, config)?)
+                }
+            };
+            Ok(provider)
         }
     }
 }
@@ -0,0 +1,496 @@
+//! Native Ollama provider (`/api/chat`, `/api/generate`, `/api/embed`).
+//!
+//! Talks to a local Ollama server with its own API rather than the `/v1`
+//! shim, which streams newline-delimited JSON, reports token counts and
+//! embeds in batches. Models pulled without tool support fall back to
+//! prompted tool calling ([`crate::tool_prompt`]) under Ollama's JSON mode.
+
+use async_trait::async_trait;
+use bizclaw_core::config::{BizClawConfig, RagConfig};
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::provider::{GenerateParams, Provider, ProviderStream};
+use bizclaw_core::types::{
+    FunctionCall, Message, ModelInfo, ProviderResponse, Role, StreamChunk, ToolCall,
+    ToolDefinition, Usage,
+};
+use futures::StreamExt;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+use crate::openai_compatible::{api_error, transport_error};
+use crate::provider_registry::ProviderConfig;
+use crate::tool_prompt;
+
+/// Used when `[rag] embedding_model` is left at its OpenAI default.
+const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+
+pub struct OllamaProvider {
+    /// e.g. `http://localhost:11434`, without the `/v1` OpenAI shim.
+    base_url: String,
+    default_models: Vec<ModelInfo>,
+    embedding_model: String,
+    client: reqwest::Client,
+}
+
+impl OllamaProvider {
+    pub fn from_registry(registry: &ProviderConfig, config: &BizClawConfig) -> Result<Self> {
+        let embedding_model = if config.rag.embedding_model == RagConfig::default().embedding_model
+        {
+            DEFAULT_EMBEDDING_MODEL.to_string()
+        } else {
+            config.rag.embedding_model.clone()
+        };
+        // Endpoints written for the OpenAI shim still end in /v1
+        let base_url = registry.resolve_base_url(config);
+        let base_url = base_url.trim_end_matches('/').trim_end_matches("/v1");
+        Ok(Self {
+            base_url: base_url.to_string(),
+            default_models: registry.model_infos(),
+            embedding_model,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Complete a raw prompt with `/api/generate`, bypassing the model's
+    /// chat template.
+    pub async fn generate(&self, prompt: &str, params: &GenerateParams) -> Result<String> {
+        let body = generate_body(prompt, params, false);
+        let json: Value = self
+            .post("/api/generate", &body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| BizClawError::Http(format!("Invalid ollama response: {e}")))?;
+        Ok(parse_generate(&json)?.delta)
+    }
+
+    /// [`generate`](Self::generate), streamed.
+    pub async fn generate_stream(
+        &self,
+        prompt: &str,
+        params: &GenerateParams,
+    ) -> Result<ProviderStream> {
+        let body = generate_body(prompt, params, true);
+        let resp = self.post("/api/generate", &body).await?;
+        let chunks = crate::sse::lines(resp).map(|line| {
+            let json: Value = serde_json::from_str(&line?)?;
+            parse_generate(&json)
+        });
+        Ok(Box::pin(chunks))
+    }
+
+    fn chat_body(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+        stream: bool,
+    ) -> Value {
+        // Ollama matches tool results by function name, not call id
+        let names: HashMap<&str, &str> = messages
+            .iter()
+            .flat_map(|m| m.tool_calls.iter().flatten())
+            .map(|c| (c.id.as_str(), c.function.name.as_str()))
+            .collect();
+
+        let messages: Vec<Value> = messages
+            .iter()
+            .map(|msg| {
+                let mut out = json!({ "role": msg.role.to_string(), "content": msg.content });
+                match msg.role {
+                    Role::Assistant => {
+                        let calls: Vec<Value> = msg
+                            .tool_calls
+                            .iter()
+                            .flatten()
+                            .map(|call| {
+                                let args: Value = serde_json::from_str(&call.function.arguments)
+                                    .unwrap_or_else(|_| json!({}));
+                                let function =
+                                    json!({ "name": call.function.name, "arguments": args });
+                                json!({ "function": function })
+                            })
+                            .collect();
+                        if !calls.is_empty() {
+                            out["tool_calls"] = json!(calls);
+                        }
+                    }
+                    Role::Tool => {
+                        let id = msg.tool_call_id.as_deref().unwrap_or_default();
+                        if let Some(name) = names.get(id).copied().or(msg.name.as_deref()) {
+                            out["tool_name"] = json!(name);
+                        }
+                    }
+                    Role::System | Role::User => {}
+                }
+                out
+            })
+            .collect();
+
+        let mut body = json!({
+            "model": params.model,
+            "messages": messages,
+            "stream": stream,
+            "options": options(params),
+        });
+        if !tools.is_empty() {
+            let tools: Vec<Value> = tools
+                .iter()
+                .map(|t| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": t.name,
+                            "description": t.description,
+                            "parameters": t.parameters,
+                        }
+                    })
+                })
+                .collect();
+            body["tools"] = json!(tools);
+        }
+        body
+    }
+
+    async fn post(&self, path: &str, body: &Value) -> Result<reqwest::Response> {
+        let url = format!("{}{path}", self.base_url);
+        let resp = self
+            .client
+            .post(&url)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| transport_error("ollama", &url, e))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let text = resp.text().await.unwrap_or_default();
+            if status == reqwest::StatusCode::NOT_FOUND && text.contains("not found") {
+                let model = body["model"].as_str().unwrap_or_default();
+                return Err(BizClawError::ModelNotLoaded(format!(
+                    "ollama has no model '{model}' — run `ollama pull {model}`"
+                )));
+            }
+            return Err(api_error("ollama", "API error", status, &headers, &text));
+        }
+        Ok(resp)
+    }
+
+    /// Chat with a model that has no native tool support: describe the
+    /// tools in the prompt and decode the reply in JSON mode.
+    async fn chat_prompted(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderResponse> {
+        let mut body = self.chat_body(&tool_prompt::prepare(messages, tools), &[], params, false);
+        body["format"] = json!("json");
+        let json: Value = self
+            .post("/api/chat", &body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| BizClawError::Http(format!("Invalid ollama response: {e}")))?;
+        let chunk = parse_chat(&json)?;
+        let call_id = format!("call_{}", uuid::Uuid::new_v4().simple());
+        let reply = serde_json::from_str(&chunk.delta).unwrap_or(Value::String(chunk.delta));
+        let mut response = tool_prompt::parse_reply(&reply, &call_id);
+        response.usage = chunk.usage;
+        Ok(response)
+    }
+}
+
+/// Ollama rejects `tools` for models whose template has no tool support.
+fn tools_unsupported(e: &BizClawError) -> bool {
+    matches!(e, BizClawError::Provider(msg) if msg.contains("does not support tools"))
+}
+
+fn options(params: &GenerateParams) -> Value {
+    let mut options = json!({
+        "temperature": params.temperature,
+        "top_p": params.top_p,
+        "num_predict": params.max_tokens,
+    });
+    if !params.stop.is_empty() {
+        options["stop"] = json!(params.stop);
+    }
+    options
+}
+
+fn generate_body(prompt: &str, params: &GenerateParams, stream: bool) -> Value {
+    json!({
+        "model": params.model,
+        "prompt": prompt,
+        "raw": true,
+        "stream": stream,
+        "options": options(params),
+    })
+}
+
+/// Fields shared by `/api/chat` and `/api/generate` replies: the error,
+/// and on the final (`done`) record the finish reason and token counts.
+fn parse_done(json: &Value, chunk: &mut StreamChunk) -> Result<()> {
+    if let Some(message) = json["error"].as_str() {
+        return Err(BizClawError::Provider(format!("ollama error: {message}")));
+    }
+    if json["done"].as_bool() != Some(true) {
+        return Ok(());
+    }
+    let reason = match json["done_reason"].as_str() {
+        _ if !chunk.tool_calls.is_empty() => "tool_calls",
+        Some("length") => "length",
+        Some(other) if other != "stop" => other,
+        _ => "stop",
+    };
+    chunk.finish_reason = Some(reason.to_string());
+    let count = |key: &str| json[key].as_u64().unwrap_or(0) as u32;
+    let (prompt, completion) = (count("prompt_eval_count"), count("eval_count"));
+    chunk.usage = Some(Usage {
+        prompt_tokens: prompt,
+        completion_tokens: completion,
+        total_tokens: prompt + completion,
+    });
+    Ok(())
+}
+
+/// One `/api/chat` record — a whole reply or one streamed line.
+fn parse_chat(json: &Value) -> Result<StreamChunk> {
+    let message = &json["message"];
+    let mut chunk = StreamChunk::text(message["content"].as_str().unwrap_or_default());
+    for call in message["tool_calls"].as_array().into_iter().flatten() {
+        let function = &call["function"];
+        chunk.tool_calls.push(ToolCall {
+            id: format!("call_{}", uuid::Uuid::new_v4().simple()),
+            r#type: "function".into(),
+            function: FunctionCall {
+                name: function["name"].as_str().unwrap_or_default().to_string(),
+                arguments: match &function["arguments"] {
+                    Value::String(s) => s.clone(),
+                    Value::Null => "{}".into(),
+                    other => other.to_string(),
+                },
+            },
+        });
+    }
+    parse_done(json, &mut chunk)?;
+    Ok(chunk)
+}
+
+/// One `/api/generate` record.
+fn parse_generate(json: &Value) -> Result<StreamChunk> {
+    let mut chunk = StreamChunk::text(json["response"].as_str().unwrap_or_default());
+    parse_done(json, &mut chunk)?;
+    Ok(chunk)
+}
+
+#[async_trait]
+impl Provider for OllamaProvider {
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    async fn chat(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderResponse> {
+        let body = self.chat_body(messages, tools, params, false);
+        let resp = match self.post("/api/chat", &body).await {
+            Err(e) if !tools.is_empty() && tools_unsupported(&e) => {
+                tracing::warn!(
+                    "⚠️ Model '{}' doesn't support tools — using prompted tool calls",
+                    params.model
+                );
+                return self.chat_prompted(messages, tools, params).await;
+            }
+            other => other?,
+        };
+        let json: Value = resp
+            .json()
+            .await
+            .map_err(|e| BizClawError::Http(format!("Invalid ollama response: {e}")))?;
+
+        let chunk = parse_chat(&json)?;
+        Ok(ProviderResponse {
+            content: (!chunk.delta.is_empty()).then_some(chunk.delta),
+            tool_calls: chunk.tool_calls,
+            finish_reason: chunk.finish_reason,
+            usage: chunk.usage,
+        })
+    }
+
+    async fn stream(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderStream> {
+        let body = self.chat_body(messages, tools, params, true);
+        let resp = match self.post("/api/chat", &body).await {
+            Err(e) if !tools.is_empty() && tools_unsupported(&e) => {
+                // A JSON-mode reply is only usable whole
+                let response = self.chat_prompted(messages, tools, params).await?;
+                let chunk = StreamChunk::from(response);
+                return Ok(Box::pin(futures::stream::iter([Ok::<_, BizClawError>(
+                    chunk,
+                )])));
+            }
+            other => other?,
+        };
+
+        // Tool calls arrive before the final record, which only says "stop"
+        let mut called = false;
+        let chunks = crate::sse::lines(resp).map(move |line| -> Result<StreamChunk> {
+            let json: Value = serde_json::from_str(&line?)?;
+            let mut chunk = parse_chat(&json)?;
+            called |= !chunk.tool_calls.is_empty();
+            if called && chunk.finish_reason.is_some() {
+                chunk.finish_reason = Some("tool_calls".into());
+            }
+            Ok(chunk)
+        });
+        Ok(Box::pin(chunks))
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        let url = format!("{}/api/tags", self.base_url);
+        let json: Value = match self.client.get(&url).send().await {
+            Ok(resp) if resp.status().is_success() => resp.json().await.unwrap_or_default(),
+            _ => return Ok(self.default_models.clone()),
+        };
+        let models: Vec<ModelInfo> = json["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| m["name"].as_str())
+            .map(|name| ModelInfo {
+                id: name.to_string(),
+                name: name.to_string(),
+                provider: "ollama".into(),
+                context_length: 4096,
+                max_output_tokens: None,
+            })
+            .collect();
+        if models.is_empty() {
+            Ok(self.default_models.clone())
+        } else {
+            Ok(models)
+        }
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        let url = format!("{}/api/tags", self.base_url);
+        let resp = self.client.get(&url).send().await;
+        Ok(resp.is_ok_and(|r| r.status().is_success()))
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let body = json!({ "model": self.embedding_model, "input": texts });
+        let json: Value = self
+            .post("/api/embed", &body)
+            .await?
+            .json()
+            .await
+            .map_err(|e| BizClawError::Http(format!("Invalid embeddings response: {e}")))?;
+        let vectors: Vec<Vec<f32>> = json["embeddings"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|e| {
+                e.as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|x| x.as_f64())
+                    .map(|x| x as f32)
+                    .collect()
+            })
+            .collect();
+        if vectors.len() != texts.len() {
+            return Err(BizClawError::Provider(format!(
+                "ollama returned {} embeddings for {} inputs",
+                vectors.len(),
+                texts.len()
+            )));
+        }
+        Ok(vectors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider() -> OllamaProvider {
+        let registry = crate::provider_registry::get_provider_config("ollama").unwrap();
+        let mut config = BizClawConfig::default();
+        config.llm.endpoint = "http://gpu-box:11434/v1/".into();
+        OllamaProvider::from_registry(registry, &config).unwrap()
+    }
+
+    #[test]
+    fn test_chat_body() {
+        let call = ToolCall {
+            id: "call_1".into(),
+            r#type: "function".into(),
+            function: FunctionCall {
+                name: "web_search".into(),
+                arguments: r#"{"q":"rust"}"#.into(),
+            },
+        };
+        let mut assistant = Message::assistant("");
+        assistant.tool_calls = Some(vec![call]);
+        let messages = [
+            Message::user("Search rust"),
+            assistant,
+            Message::tool("results", "call_1"),
+        ];
+        let tools = [ToolDefinition {
+            name: "web_search".into(),
+            description: "Search the web".into(),
+            parameters: json!({ "type": "object" }),
+        }];
+        let params = GenerateParams {
+            model: "qwen2.5".into(),
+            stop: vec!["</s>".into()],
+            ..Default::default()
+        };
+        let provider = provider();
+        assert_eq!(provider.base_url, "http://gpu-box:11434");
+
+        let body = provider.chat_body(&messages, &tools, &params, true);
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["options"]["num_predict"], 4096);
+        assert_eq!(body["options"]["stop"][0], "</s>");
+        let call = &body["messages"][1]["tool_calls"][0]["function"];
+        assert_eq!(call["arguments"]["q"], "rust");
+        assert_eq!(body["messages"][2]["tool_name"], "web_search");
+        assert_eq!(body["tools"][0]["function"]["name"], "web_search");
+    }
+
+    #[test]
+    fn test_parse_chat() {
+        let json = json!({
+            "message": { "role": "assistant", "content": "", "tool_calls": [
+                { "function": { "name": "web_search", "arguments": { "q": "rust" } } },
+            ]},
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 12,
+            "eval_count": 7,
+        });
+        let chunk = parse_chat(&json).unwrap();
+        assert_eq!(chunk.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(chunk.tool_calls[0].function.arguments, r#"{"q":"rust"}"#);
+        assert_eq!(chunk.usage.unwrap().total_tokens, 19);
+
+        let line = json!({ "response": "Xin", "done": false });
+        let chunk = parse_generate(&line).unwrap();
+        assert_eq!(chunk.delta, "Xin");
+        assert!(chunk.finish_reason.is_none() && chunk.usage.is_none());
+
+        let err = parse_chat(&json!({ "error": "model requires more system memory" }));
+        assert!(err.is_err());
+    }
+}
@@ -8,10 +8,12 @@
 use async_trait::async_trait;
 use bizclaw_core::config::BizClawConfig;
 use bizclaw_core::error::{BizClawError, Result};
-use bizclaw_core::traits::provider::{GenerateParams, Provider};
+use bizclaw_core::traits::provider::{GenerateParams, Provider, ProviderStream};
 use bizclaw_core::types::{
-    FunctionCall, Message, ModelInfo, ProviderResponse, ToolCall, ToolDefinition, Usage,
+    FunctionCall, Message, ModelInfo, ProviderResponse, StreamChunk, ToolCall, ToolDefinition,
+    Usage,
 };
+use futures::StreamExt;
 use serde_json::{Value, json};
 
 use crate::provider_registry::{AuthStyle, ProviderConfig};
@@ -32,6 +34,8 @@ pub struct OpenAiCompatibleProvider {
     auth_style: AuthStyle,
     /// Default models to return from `list_models`.
     default_models: Vec<ModelInfo>,
+    /// Model used by `embed`.
+    embedding_model: String,
     /// HTTP client.
     client: reqwest::Client,
 }
@@ -43,42 +47,9 @@ impl OpenAiCompatibleProvider {
     /// - API key: `config.llm.api_key` > `config.api_key` > env vars > empty
     /// - Base URL: `config.llm.endpoint` > env override > registry default
     pub fn from_registry(registry: &ProviderConfig, config: &BizClawConfig) -> Result<Self> {
-        // Resolve API key: config.llm.api_key > config.api_key > env vars > empty
-        let api_key = if !config.llm.api_key.is_empty() {
-            config.llm.api_key.clone()
-        } else if !config.api_key.is_empty() {
-            config.api_key.clone()
-        } else {
-            registry
-                .env_keys
-                .iter()
-                .find_map(|key| std::env::var(key).ok())
-                .unwrap_or_default()
-        };
-
-        // Resolve base URL: config.llm.endpoint > env override > registry default
-        let base_url = if !config.llm.endpoint.is_empty() {
-            config.llm.endpoint.clone()
-        } else {
-            registry
-                .base_url_env
-                .and_then(|env_key| {
-                    let val = std::env::var(env_key).ok()?;
-                    // For OLLAMA_HOST / LLAMACPP_HOST, append /v1 if not present
-                    if val.ends_with("/v1") {
-                        Some(val)
-                    } else {
-                        Some(format!("{}/v1", val.trim_end_matches('/')))
-                    }
-                })
-                .unwrap_or_else(|| registry.base_url.to_string())
-        };
-
-        let default_models = registry
-            .default_models
-            .iter()
-            .map(|m| m.to_model_info(registry.name))
-            .collect();
+        let api_key = registry.resolve_api_key(config);
+        let base_url = registry.resolve_base_url(config);
+        let default_models = registry.model_infos();
 
         Ok(Self {
             name: registry.name.to_string(),
@@ -88,6 +59,7 @@ impl OpenAiCompatibleProvider {
             models_path: registry.models_path.to_string(),
             auth_style: registry.auth_style,
             default_models,
+            embedding_model: config.rag.embedding_model.clone(),
             client: reqwest::Client::new(),
         })
     }
@@ -120,6 +92,7 @@ impl OpenAiCompatibleProvider {
             models_path: "/models".to_string(),
             auth_style,
             default_models: vec![],
+            embedding_model: config.rag.embedding_model.clone(),
             client: reqwest::Client::new(),
         })
     }
@@ -133,25 +106,14 @@ impl OpenAiCompatibleProvider {
             _ => req,
         }
     }
-}
 
-#[async_trait]
-impl Provider for OpenAiCompatibleProvider {
-    fn name(&self) -> &str {
-        &self.name
-    }
-
-    async fn chat(
+    /// The `/chat/completions` request body, without `stream`.
+    fn request_body(
         &self,
         messages: &[Message],
         tools: &[ToolDefinition],
         params: &GenerateParams,
-    ) -> Result<ProviderResponse> {
-        // For providers that require auth, check API key
-        if self.auth_style != AuthStyle::None && self.api_key.is_empty() {
-            return Err(BizClawError::ApiKeyMissing(self.name.clone()));
-        }
-
+    ) -> Value {
         let is_anthropic = self.name == "anthropic" || self.base_url.contains("anthropic");
 
         // Build request body — standard OpenAI format
@@ -216,6 +178,150 @@ impl Provider for OpenAiCompatibleProvider {
                 .collect();
             body["tools"] = Value::Array(tool_defs);
         }
+        body
+    }
+}
+
+/// Classify a failed API response: 429 → `RateLimited` (with the
+/// `Retry-After` wait), 408/504 → `Timeout`, other 5xx → `Http`, 401/403 →
+/// `AuthFailed`, a prompt over the model's context → `ContextOverflow`,
+/// anything else → `Provider`.
+pub(crate) fn api_error(
+    name: &str,
+    what: &str,
+    status: reqwest::StatusCode,
+    headers: &reqwest::header::HeaderMap,
+    text: &str,
+) -> BizClawError {
+    use reqwest::StatusCode;
+    let msg = format!("{name} {what} {status}: {text}");
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => {
+            let wait = headers
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(bizclaw_core::error::parse_retry_after);
+            BizClawError::rate_limited(msg, wait)
+        }
+        StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => BizClawError::Timeout(msg),
+        s if s.is_server_error() => BizClawError::Http(msg),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => BizClawError::AuthFailed(msg),
+        _ if is_context_overflow(text) => BizClawError::ContextOverflow(msg),
+        _ => BizClawError::Provider(msg),
+    }
+}
+
+/// A request that never got a response; timeouts are reported as such.
+pub(crate) fn transport_error(name: &str, url: &str, e: reqwest::Error) -> BizClawError {
+    let msg = format!("{name} connection failed ({url}): {e}");
+    if e.is_timeout() {
+        BizClawError::Timeout(msg)
+    } else {
+        BizClawError::Http(msg)
+    }
+}
+
+/// Error bodies OpenAI-style APIs return for a prompt that doesn't fit.
+pub(crate) fn is_context_overflow(text: &str) -> bool {
+    const MARKERS: &[&str] = &[
+        "context_length_exceeded",
+        "maximum context length",
+        "prompt is too long",
+        "exceeds the context window",
+    ];
+    let text = text.to_lowercase();
+    MARKERS.iter().any(|m| text.contains(m))
+}
+
+/// Turns `chat.completion.chunk` events into [`StreamChunk`]s. Tool call
+/// fragments are joined by index and emitted with the finish reason.
+#[derive(Default)]
+struct StreamDecoder {
+    tool_calls: Vec<ToolCall>,
+}
+
+impl StreamDecoder {
+    fn decode(&mut self, data: &str) -> Result<Option<StreamChunk>> {
+        if data == "[DONE]" {
+            return Ok(None);
+        }
+        let json: Value = serde_json::from_str(data)?;
+        if let Some(message) = json["error"]["message"].as_str() {
+            return Err(BizClawError::Provider(format!("Stream error: {message}")));
+        }
+        let choice = &json["choices"][0];
+        let delta = &choice["delta"];
+        for fragment in delta["tool_calls"].as_array().into_iter().flatten() {
+            let index = fragment["index"].as_u64().unwrap_or(0) as usize;
+            if self.tool_calls.len() <= index {
+                self.tool_calls.resize_with(index + 1, || ToolCall {
+                    id: String::new(),
+                    r#type: "function".into(),
+                    function: FunctionCall {
+                        name: String::new(),
+                        arguments: String::new(),
+                    },
+                });
+            }
+            let call = &mut self.tool_calls[index];
+            if let Some(id) = fragment["id"].as_str() {
+                call.id.push_str(id);
+            }
+            let function = &fragment["function"];
+            if let Some(name) = function["name"].as_str() {
+                call.function.name.push_str(name);
+            }
+            if let Some(arguments) = function["arguments"].as_str() {
+                call.function.arguments.push_str(arguments);
+            }
+        }
+
+        let finish_reason = choice["finish_reason"].as_str().map(String::from);
+        let chunk = StreamChunk {
+            delta: delta["content"].as_str().unwrap_or_default().to_string(),
+            tool_calls: if finish_reason.is_some() {
+                std::mem::take(&mut self.tool_calls)
+            } else {
+                vec![]
+            },
+            finish_reason,
+            usage: parse_usage(&json["usage"]),
+        };
+        let empty =
+            chunk.delta.is_empty() && chunk.finish_reason.is_none() && chunk.usage.is_none();
+        Ok((!empty).then_some(chunk))
+    }
+}
+
+/// An OpenAI `usage` object.
+fn parse_usage(usage: &Value) -> Option<Usage> {
+    let u = usage.as_object()?;
+    let count = |key: &str| u.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    Some(Usage {
+        prompt_tokens: count("prompt_tokens"),
+        completion_tokens: count("completion_tokens"),
+        total_tokens: count("total_tokens"),
+    })
+}
+
+#[async_trait]
+impl Provider for OpenAiCompatibleProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn chat(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderResponse> {
+        // For providers that require auth, check API key
+        if self.auth_style != AuthStyle::None && self.api_key.is_empty() {
+            return Err(BizClawError::ApiKeyMissing(self.name.clone()));
+        }
+
+        let mut body = self.request_body(messages, tools, params);
 
         // Send request
         let url = format!("{}{}", self.base_url, self.chat_path);
@@ -226,12 +332,14 @@ impl Provider for OpenAiCompatibleProvider {
             .json(&body);
         let req = self.apply_auth(req);
 
-        let resp = req.send().await.map_err(|e| {
-            BizClawError::Http(format!("{} connection failed ({}): {}", self.name, url, e))
-        })?;
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| transport_error(&self.name, &url, e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
+            let headers = resp.headers().clone();
             let text = resp.text().await.unwrap_or_default();
 
             // Auto-retry WITHOUT tools if model doesn't support function calling
@@ -259,11 +367,15 @@ impl Provider for OpenAiCompatibleProvider {
                 })?;
                 if !retry_resp.status().is_success() {
                     let rs = retry_resp.status();
+                    let rh = retry_resp.headers().clone();
                     let rt = retry_resp.text().await.unwrap_or_default();
-                    return Err(BizClawError::Provider(format!(
-                        "{} API error {} (retry without tools): {}",
-                        self.name, rs, rt
-                    )));
+                    return Err(api_error(
+                        &self.name,
+                        "API error (retry without tools)",
+                        rs,
+                        &rh,
+                        &rt,
+                    ));
                 }
                 // Parse the retry response (same flow as below)
                 let json: Value = retry_resp
@@ -274,11 +386,7 @@ impl Provider for OpenAiCompatibleProvider {
                     .get(0)
                     .ok_or_else(|| BizClawError::Provider("No choices in retry response".into()))?;
                 let content = choice["message"]["content"].as_str().map(String::from);
-                let usage = json["usage"].as_object().map(|u| Usage {
-                    prompt_tokens: u.get("prompt_tokens").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
-                    completion_tokens: u.get("completion_tokens").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
-                    total_tokens: u.get("total_tokens").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
-                });
+                let usage = parse_usage(&json["usage"]);
                 return Ok(ProviderResponse {
                     content,
                     tool_calls: vec![], // No tools available
@@ -287,10 +395,7 @@ impl Provider for OpenAiCompatibleProvider {
                 });
             }
 
-            return Err(BizClawError::Provider(format!(
-                "{} API error {}: {}",
-                self.name, status, text
-            )));
+            return Err(api_error(&self.name, "API error", status, &headers, &text));
         }
 
         // Parse response — standard OpenAI format
@@ -322,14 +427,7 @@ impl Provider for OpenAiCompatibleProvider {
             vec![]
         };
 
-        let usage = json["usage"].as_object().map(|u| Usage {
-            prompt_tokens: u.get("prompt_tokens").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
-            completion_tokens: u
-                .get("completion_tokens")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0) as u32,
-            total_tokens: u.get("total_tokens").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
-        });
+        let usage = parse_usage(&json["usage"]);
 
         Ok(ProviderResponse {
             content,
@@ -339,6 +437,38 @@ impl Provider for OpenAiCompatibleProvider {
         })
     }
 
+    async fn stream(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderStream> {
+        if self.auth_style != AuthStyle::None && self.api_key.is_empty() {
+            return Err(BizClawError::ApiKeyMissing(self.name.clone()));
+        }
+
+        let mut body = self.request_body(messages, tools, params);
+        body["stream"] = json!(true);
+        let url = format!("{}{}", self.base_url, self.chat_path);
+        let req = self.apply_auth(self.client.post(&url).json(&body));
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| transport_error(&self.name, &url, e))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(api_error(&self.name, "API error", status, &headers, &text));
+        }
+
+        let mut decoder = StreamDecoder::default();
+        let chunks = crate::sse::events(resp)
+            .map(move |event| event.and_then(|data| decoder.decode(&data)))
+            .filter_map(|chunk| async move { chunk.transpose() });
+        Ok(Box::pin(chunks))
+    }
+
     async fn list_models(&self) -> Result<Vec<ModelInfo>> {
         // Try to fetch models from the API
         let url = format!("{}{}", self.base_url, self.models_path);
@@ -386,4 +516,119 @@ impl Provider for OpenAiCompatibleProvider {
         let resp = self.client.get(&url).send().await;
         Ok(resp.is_ok())
     }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if self.auth_style != AuthStyle::None && self.api_key.is_empty() {
+            return Err(BizClawError::ApiKeyMissing(self.name.clone()));
+        }
+
+        let url = format!("{}/embeddings", self.base_url);
+        let body = json!({ "model": self.embedding_model, "input": texts });
+        let req = self.apply_auth(self.client.post(&url).json(&body));
+        let resp = req
+            .send()
+            .await
+            .map_err(|e| transport_error(&self.name, &url, e))?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let headers = resp.headers().clone();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(api_error(
+                &self.name,
+                "embeddings error",
+                status,
+                &headers,
+                &text,
+            ));
+        }
+
+        let json: Value = resp
+            .json()
+            .await
+            .map_err(|e| BizClawError::Http(format!("Invalid embeddings response: {e}")))?;
+        let mut data: Vec<&Value> = json["data"].as_array().into_iter().flatten().collect();
+        data.sort_by_key(|d| d["index"].as_u64().unwrap_or(0));
+        let vectors: Vec<Vec<f32>> = data
+            .iter()
+            .map(|d| {
+                d["embedding"]
+                    .as_array()
+                    .map(|v| {
+                        v.iter()
+                            .filter_map(|x| x.as_f64())
+                            .map(|x| x as f32)
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect();
+        if vectors.len() != texts.len() {
+            return Err(BizClawError::Provider(format!(
+                "{} returned {} embeddings for {} inputs",
+                self.name,
+                vectors.len(),
+                texts.len()
+            )));
+        }
+        Ok(vectors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+
+    #[test]
+    fn test_api_error_classification() {
+        let none = HeaderMap::new();
+        let classify = |status, headers: &HeaderMap, text| {
+            api_error("openai", "API error", status, headers, text)
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("12"));
+        let err = classify(StatusCode::TOO_MANY_REQUESTS, &headers, "");
+        assert_eq!(err.code(), "rate_limited");
+        assert_eq!(err.retry_after(), Some(std::time::Duration::from_secs(12)));
+
+        let overflow = r#"{"error":{"code":"context_length_exceeded"}}"#;
+        let err = classify(StatusCode::BAD_REQUEST, &none, overflow);
+        assert_eq!(err.code(), "context_overflow");
+        assert!(!err.is_retryable());
+
+        let code = |status| classify(status, &none, "").code();
+        assert_eq!(code(StatusCode::UNAUTHORIZED), "auth_failed");
+        assert_eq!(code(StatusCode::GATEWAY_TIMEOUT), "timeout");
+        assert_eq!(code(StatusCode::BAD_GATEWAY), "http_error");
+        assert_eq!(code(StatusCode::NOT_FOUND), "provider_error");
+    }
+
+    #[test]
+    fn test_stream_decoder() {
+        let mut decoder = StreamDecoder::default();
+        let text = r#"{"choices":[{"index":0,"delta":{"content":"Chào"}}]}"#;
+        assert_eq!(decoder.decode(text).unwrap().unwrap().delta, "Chào");
+
+        let first = r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_1",
+            "function":{"name":"web_search","arguments":"{\"q\":"}}]}}]}"#;
+        assert!(decoder.decode(first).unwrap().is_none());
+        let rest = r#"{"choices":[{"delta":{"tool_calls":[{"index":0,
+            "function":{"arguments":"\"rust\"}"}}]}}]}"#;
+        assert!(decoder.decode(rest).unwrap().is_none());
+
+        let done = r#"{"choices":[{"delta":{},"finish_reason":"tool_calls"}],
+            "usage":{"prompt_tokens":5,"completion_tokens":7,"total_tokens":12}}"#;
+        let last = decoder.decode(done).unwrap().unwrap();
+        assert_eq!(last.finish_reason.as_deref(), Some("tool_calls"));
+        assert_eq!(last.tool_calls.len(), 1);
+        assert_eq!(last.tool_calls[0].id, "call_1");
+        assert_eq!(last.tool_calls[0].function.arguments, r#"{"q":"rust"}"#);
+        assert_eq!(last.usage.unwrap().total_tokens, 12);
+
+        assert!(decoder.decode("[DONE]").unwrap().is_none());
+        let error = r#"{"error":{"message":"overloaded"}}"#;
+        assert!(decoder.decode(error).is_err());
+    }
 }
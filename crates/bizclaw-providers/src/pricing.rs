@@ -0,0 +1,62 @@
+//! Token prices, for cost estimates and budget caps.
+//!
+//! List prices in USD per million tokens, matched on the model name. Local
+//! providers cost nothing; unknown remote models get a middle-of-the-road
+//! estimate rather than zero, so a budget still notices them.
+
+/// Providers that run on the business's own hardware.
+pub const LOCAL_PROVIDERS: &[&str] = &["brain", "ollama", "llamacpp"];
+
+/// `(input, output)` USD per million tokens.
+pub fn rates(provider: &str, model: &str) -> (f64, f64) {
+    if LOCAL_PROVIDERS.contains(&provider) {
+        return (0.0, 0.0);
+    }
+    let model = model.to_ascii_lowercase();
+    match model.as_str() {
+        m if m.contains("gpt-4o-mini") => (0.15, 0.60),
+        m if m.contains("gpt-4o") => (2.50, 10.00),
+        m if m.contains("gpt-4") => (30.00, 60.00),
+        m if m.contains("claude-3-5-sonnet") || m.contains("claude-sonnet-4") => (3.00, 15.00),
+        m if m.contains("claude-opus-4") => (15.00, 75.00),
+        m if m.contains("claude-3-5-haiku") => (0.80, 4.00),
+        m if m.contains("gemini-2.0-flash") => (0.075, 0.30),
+        m if m.contains("gemini") => (0.50, 1.50),
+        m if m.contains("deepseek") => (0.14, 0.28),
+        m if m.contains("llama") => (0.05, 0.10),
+        m if m.contains("mistral") => (0.25, 0.25),
+        _ => (1.00, 3.00),
+    }
+}
+
+/// Estimated cost of one request, in USD, rounded to 1/100 000 of a dollar.
+pub fn estimate_cost(
+    provider: &str,
+    model: &str,
+    prompt_tokens: u32,
+    completion_tokens: u32,
+) -> f64 {
+    let (input_rate, output_rate) = rates(provider, model);
+    let input_cost = (prompt_tokens as f64 / 1_000_000.0) * input_rate;
+    let output_cost = (completion_tokens as f64 / 1_000_000.0) * output_rate;
+    ((input_cost + output_cost) * 100_000.0).round() / 100_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_cost() {
+        assert_eq!(
+            estimate_cost("openai", "gpt-4o-mini", 1_000_000, 1_000_000),
+            0.75
+        );
+        assert_eq!(estimate_cost("openai", "gpt-4o", 1000, 0), 0.0025);
+        assert_eq!(
+            estimate_cost("ollama", "llama3.2", 1_000_000, 1_000_000),
+            0.0
+        );
+        assert_eq!(estimate_cost("groq", "llama-3.3-70b", 1_000_000, 0), 0.05);
+    }
+}
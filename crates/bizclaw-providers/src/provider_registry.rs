@@ -1,8 +1,10 @@
 //! Provider registry — maps provider names to endpoint configurations.
 //!
-//! All OpenAI-compatible providers are defined here as static config entries.
-//! The unified `OpenAiCompatibleProvider` uses these configs to connect to any provider.
+//! All cloud and local HTTP providers are defined here as static config entries.
+//! Most speak the OpenAI format and are served by the unified
+//! `OpenAiCompatibleProvider`; [`ApiFormat`] marks the ones with a native client.
 
+use bizclaw_core::config::BizClawConfig;
 use bizclaw_core::types::ModelInfo;
 
 /// How to attach auth credentials to requests.
@@ -14,6 +16,19 @@ pub enum AuthStyle {
     None,
 }
 
+/// Which wire format a provider speaks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiFormat {
+    /// OpenAI `/chat/completions`.
+    OpenAi,
+    /// Anthropic `/messages`.
+    Anthropic,
+    /// Google `generateContent`.
+    Gemini,
+    /// Ollama's native `/api/chat`.
+    Ollama,
+}
+
 /// Static model definition for a provider.
 #[derive(Debug, Clone)]
 pub struct ModelDef {
@@ -50,12 +65,56 @@ pub struct ProviderConfig {
     pub env_keys: &'static [&'static str],
     /// How to send auth credentials.
     pub auth_style: AuthStyle,
+    /// Wire format, which picks the client.
+    pub api: ApiFormat,
     /// Environment variable to override the base URL (e.g., OLLAMA_HOST).
     pub base_url_env: Option<&'static str>,
     /// Default models to return from `list_models`.
     pub default_models: &'static [ModelDef],
 }
 
+impl ProviderConfig {
+    /// API key: `config.llm.api_key` > `config.api_key` > env vars > empty.
+    pub fn resolve_api_key(&self, config: &BizClawConfig) -> String {
+        if !config.llm.api_key.is_empty() {
+            config.llm.api_key.clone()
+        } else if !config.api_key.is_empty() {
+            config.api_key.clone()
+        } else {
+            self.env_keys
+                .iter()
+                .find_map(|key| std::env::var(key).ok())
+                .unwrap_or_default()
+        }
+    }
+
+    /// Base URL: `config.llm.endpoint` > env override > registry default.
+    pub fn resolve_base_url(&self, config: &BizClawConfig) -> String {
+        if !config.llm.endpoint.is_empty() {
+            return config.llm.endpoint.clone();
+        }
+        self.base_url_env
+            .and_then(|env_key| {
+                let val = std::env::var(env_key).ok()?;
+                // OpenAI-format hosts (LLAMACPP_HOST) need /v1 if not present
+                if self.api != ApiFormat::OpenAi || val.ends_with("/v1") {
+                    Some(val)
+                } else {
+                    Some(format!("{}/v1", val.trim_end_matches('/')))
+                }
+            })
+            .unwrap_or_else(|| self.base_url.to_string())
+    }
+
+    /// `default_models` as [`ModelInfo`]s.
+    pub fn model_infos(&self) -> Vec<ModelInfo> {
+        self.default_models
+            .iter()
+            .map(|m| m.to_model_info(self.name))
+            .collect()
+    }
+}
+
 // ─── Provider Definitions ────────────────────────────────────────────────────
 
 static OPENAI_MODELS: &[ModelDef] = &[
@@ -243,6 +302,7 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &["OPENAI_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::OpenAi,
         base_url_env: Some("OPENAI_API_BASE"),
         default_models: OPENAI_MODELS,
     },
@@ -253,16 +313,18 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &["OPENROUTER_API_KEY", "OPENAI_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::OpenAi,
         base_url_env: None,
         default_models: OPENROUTER_MODELS,
     },
     ProviderConfig {
         name: "anthropic",
         base_url: "https://api.anthropic.com/v1",
-        chat_path: "/chat/completions",
+        chat_path: "/messages",
         models_path: "/models",
         env_keys: &["ANTHROPIC_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::Anthropic,
         base_url_env: None,
         default_models: ANTHROPIC_MODELS,
     },
@@ -273,16 +335,18 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &["DEEPSEEK_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::OpenAi,
         base_url_env: None,
         default_models: DEEPSEEK_MODELS,
     },
     ProviderConfig {
         name: "gemini",
-        base_url: "https://generativelanguage.googleapis.com/v1beta/openai",
-        chat_path: "/chat/completions",
+        base_url: "https://generativelanguage.googleapis.com/v1beta",
+        chat_path: ":generateContent",
         models_path: "/models",
         env_keys: &["GEMINI_API_KEY", "GOOGLE_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::Gemini,
         base_url_env: None,
         default_models: GEMINI_MODELS,
     },
@@ -293,16 +357,18 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &["GROQ_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::OpenAi,
         base_url_env: None,
         default_models: GROQ_MODELS,
     },
     ProviderConfig {
         name: "ollama",
-        base_url: "http://localhost:11434/v1",
-        chat_path: "/chat/completions",
-        models_path: "/models",
+        base_url: "http://localhost:11434",
+        chat_path: "/api/chat",
+        models_path: "/api/tags",
         env_keys: &[],
         auth_style: AuthStyle::None,
+        api: ApiFormat::Ollama,
         base_url_env: Some("OLLAMA_HOST"),
         default_models: OLLAMA_MODELS,
     },
@@ -313,6 +379,7 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &[],
         auth_style: AuthStyle::None,
+        api: ApiFormat::OpenAi,
         base_url_env: Some("LLAMACPP_HOST"),
         default_models: LLAMACPP_MODELS,
     },
@@ -323,6 +390,7 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &["CLIPROXY_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::OpenAi,
         base_url_env: Some("CLIPROXY_HOST"),
         default_models: &[ModelDef {
             id: "default",
@@ -338,6 +406,7 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &["VLLM_API_KEY"],
         auth_style: AuthStyle::None,
+        api: ApiFormat::OpenAi,
         base_url_env: Some("VLLM_HOST"),
         default_models: &[ModelDef {
             id: "default",
@@ -353,6 +422,7 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &["TOGETHER_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::OpenAi,
         base_url_env: None,
         default_models: &[ModelDef {
             id: "meta-llama/Llama-3.3-70B-Instruct-Turbo",
@@ -368,6 +438,7 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &["MISTRAL_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::OpenAi,
         base_url_env: None,
         default_models: MISTRAL_MODELS,
     },
@@ -378,6 +449,7 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &["MINIMAX_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::OpenAi,
         base_url_env: None,
         default_models: MINIMAX_MODELS,
     },
@@ -388,6 +460,7 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &["XAI_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::OpenAi,
         base_url_env: None,
         default_models: XAI_MODELS,
     },
@@ -398,6 +471,7 @@ static PROVIDERS: &[ProviderConfig] = &[
         models_path: "/models",
         env_keys: &["ARK_API_KEY"],
         auth_style: AuthStyle::Bearer,
+        api: ApiFormat::OpenAi,
         base_url_env: Some("ARK_BASE_URL"),
         default_models: MODELARK_MODELS,
     },
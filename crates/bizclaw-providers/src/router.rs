@@ -0,0 +1,509 @@
+//! Provider failover chains.
+//!
+//! A [`ProviderRouter`] answers with the first route of a chain that
+//! succeeds: `[LLM] provider` then its `fallbacks`, or a named
+//! `[LLM.chains]` entry when the requested model is `chain:<name>` — which
+//! is how personas pick one. Each attempt gets `attempt_timeout_secs`, and a
+//! route that fails `failure_threshold` times in a row is only tried after
+//! the healthy ones until `cooldown_secs` have passed.
+//!
+//! Streams fail over until their first chunk arrives; after that the reply
+//! is committed. Embeddings always come from the primary, since vectors
+//! from different models can't be compared.
+
+use async_trait::async_trait;
+use bizclaw_core::config::BizClawConfig;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::metrics;
+use bizclaw_core::traits::provider::{GenerateParams, Provider, ProviderStream};
+use bizclaw_core::types::{Message, ModelInfo, ProviderResponse, ToolDefinition};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Model prefix that selects a named chain, e.g. `chain:vip`.
+pub const CHAIN_PREFIX: &str = "chain:";
+
+struct Route {
+    /// `provider` or `provider/model`, as configured.
+    label: String,
+    provider: Box<dyn Provider>,
+    /// `None` uses the requested model.
+    model: Option<String>,
+    health: Mutex<Health>,
+}
+
+#[derive(Default)]
+struct Health {
+    failures: u32,
+    down_until: Option<Instant>,
+}
+
+impl Route {
+    fn is_up(&self, now: Instant) -> bool {
+        let health = self.health.lock().unwrap_or_else(|e| e.into_inner());
+        health.down_until.is_none_or(|until| until <= now)
+    }
+}
+
+pub struct ProviderRouter {
+    /// The primary first.
+    routes: Vec<Route>,
+    default_chain: Vec<usize>,
+    chains: HashMap<String, Vec<usize>>,
+    /// What the primary is asked for when a named chain is requested.
+    default_model: String,
+    attempt_timeout: Option<Duration>,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl ProviderRouter {
+    pub fn new(label: impl Into<String>, primary: Box<dyn Provider>) -> Self {
+        let primary = Route {
+            label: label.into(),
+            provider: primary,
+            model: None,
+            health: Mutex::default(),
+        };
+        Self {
+            routes: vec![primary],
+            default_chain: vec![0],
+            chains: HashMap::new(),
+            default_model: String::new(),
+            attempt_timeout: Some(Duration::from_secs(60)),
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(60),
+        }
+    }
+
+    /// Wrap `primary` with the `[LLM]` fallbacks and chains. Routes that
+    /// can't be created (e.g. a brain model that isn't downloaded) are
+    /// skipped with a warning.
+    pub fn from_config(config: &BizClawConfig, primary: Box<dyn Provider>) -> Self {
+        let llm = &config.llm;
+        let timeout =
+            (llm.attempt_timeout_secs > 0).then_some(Duration::from_secs(llm.attempt_timeout_secs));
+        let mut router = Self::new(crate::provider_name(config), primary)
+            .with_default_model(config.default_model.clone())
+            .with_limits(
+                timeout,
+                llm.failure_threshold,
+                Duration::from_secs(llm.cooldown_secs),
+            );
+
+        let specs = llm.fallbacks.iter().chain(llm.chains.values().flatten());
+        for spec in specs {
+            let spec = spec.trim();
+            if router.position(spec).is_some() {
+                continue;
+            }
+            match create_route(config, spec) {
+                Ok((route, model)) => router = router.with_route(spec, route, model),
+                Err(e) => tracing::warn!("⚠️ Skipping provider route '{spec}': {e}"),
+            }
+        }
+
+        router = router.with_fallbacks(&llm.fallbacks);
+        for (name, chain) in &llm.chains {
+            router = router.with_chain(name, chain);
+        }
+        router
+    }
+
+    pub fn with_default_model(mut self, model: impl Into<String>) -> Self {
+        self.default_model = model.into();
+        self
+    }
+
+    /// `attempt_timeout` of `None` waits for every route as long as it takes.
+    pub fn with_limits(
+        mut self,
+        attempt_timeout: Option<Duration>,
+        failure_threshold: u32,
+        cooldown: Duration,
+    ) -> Self {
+        self.attempt_timeout = attempt_timeout;
+        self.failure_threshold = failure_threshold.max(1);
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Register a route for [`with_fallbacks`](Self::with_fallbacks) and
+    /// [`with_chain`](Self::with_chain) to refer to. `model` of `None` uses
+    /// the requested model.
+    pub fn with_route(
+        mut self,
+        label: impl Into<String>,
+        provider: Box<dyn Provider>,
+        model: Option<String>,
+    ) -> Self {
+        self.routes.push(Route {
+            label: label.into(),
+            provider,
+            model,
+            health: Mutex::default(),
+        });
+        self
+    }
+
+    /// Routes tried after the primary, by label.
+    pub fn with_fallbacks<S: AsRef<str>>(mut self, labels: &[S]) -> Self {
+        let fallbacks = self.positions(labels);
+        self.default_chain = std::iter::once(0).chain(fallbacks).collect();
+        self.default_chain.dedup();
+        self
+    }
+
+    /// A chain selected with the model `chain:<name>`.
+    pub fn with_chain<S: AsRef<str>>(mut self, name: impl Into<String>, labels: &[S]) -> Self {
+        let chain = self.positions(labels);
+        self.chains.insert(name.into(), chain);
+        self
+    }
+
+    fn position(&self, label: &str) -> Option<usize> {
+        self.routes.iter().position(|r| r.label == label)
+    }
+
+    fn positions<S: AsRef<str>>(&self, labels: &[S]) -> Vec<usize> {
+        labels
+            .iter()
+            .filter_map(|label| self.position(label.as_ref().trim()))
+            .collect()
+    }
+
+    /// Routes to try for `params`, healthy ones first, and the model the
+    /// routes without their own should be asked for.
+    fn plan(&self, params: &GenerateParams) -> (Vec<usize>, String) {
+        let (chain, model) = match params.model.strip_prefix(CHAIN_PREFIX) {
+            Some(name) => {
+                let chain = self.chains.get(name).unwrap_or_else(|| {
+                    tracing::warn!("Unknown provider chain '{name}', using the default");
+                    &self.default_chain
+                });
+                (chain, self.default_model.clone())
+            }
+            None => (&self.default_chain, params.model.clone()),
+        };
+        let now = Instant::now();
+        let (up, down): (Vec<usize>, Vec<usize>) =
+            chain.iter().partition(|&&i| self.routes[i].is_up(now));
+        (up.into_iter().chain(down).collect(), model)
+    }
+
+    /// Run `attempt` on each planned route until one succeeds.
+    async fn run<'a, T, F, Fut>(&'a self, params: &GenerateParams, mut attempt: F) -> Result<T>
+    where
+        F: FnMut(&'a dyn Provider, GenerateParams) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let (order, model) = self.plan(params);
+        let mut last_error = None;
+        for (tried, i) in order.into_iter().enumerate() {
+            let route = &self.routes[i];
+            if tried > 0 {
+                tracing::warn!("↪️ Failing over to provider {}", route.label);
+                metrics::counter(
+                    "bizclaw_provider_failovers_total",
+                    &[("route", &route.label)],
+                )
+                .inc();
+            }
+            let params = GenerateParams {
+                model: route.model.clone().unwrap_or_else(|| model.clone()),
+                ..params.clone()
+            };
+            let call = attempt(route.provider.as_ref(), params);
+            let result = match self.attempt_timeout {
+                Some(limit) => tokio::time::timeout(limit, call).await.unwrap_or_else(|_| {
+                    Err(BizClawError::Timeout(format!(
+                        "{} did not answer within {}s",
+                        route.label,
+                        limit.as_secs_f32()
+                    )))
+                }),
+                None => call.await,
+            };
+            match result {
+                Ok(value) => {
+                    self.record(route, None);
+                    return Ok(value);
+                }
+                Err(e) => {
+                    tracing::warn!("Provider {} failed: {e}", route.label);
+                    self.record(route, Some(&e));
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| BizClawError::Provider("no provider route".into())))
+    }
+
+    fn record(&self, route: &Route, error: Option<&BizClawError>) {
+        let mut health = route.health.lock().unwrap_or_else(|e| e.into_inner());
+        match error {
+            None => {
+                if health.down_until.take().is_some() {
+                    tracing::info!("✅ Provider {} recovered", route.label);
+                }
+                health.failures = 0;
+            }
+            // The prompt is at fault, not the provider
+            Some(BizClawError::ContextOverflow(_)) => {}
+            Some(_) => {
+                health.failures += 1;
+                if health.failures >= self.failure_threshold {
+                    tracing::warn!(
+                        "⚠️ Provider {} failed {} times in a row, deprioritized for {}s",
+                        route.label,
+                        health.failures,
+                        self.cooldown.as_secs()
+                    );
+                    health.down_until = Some(Instant::now() + self.cooldown);
+                }
+            }
+        }
+        let up = if health.down_until.is_some() {
+            0.0
+        } else {
+            1.0
+        };
+        metrics::gauge("bizclaw_provider_up", &[("route", &route.label)]).set(up);
+    }
+
+    fn primary(&self) -> &dyn Provider {
+        self.routes[0].provider.as_ref()
+    }
+}
+
+/// Create the provider a route spec — `"provider"` or `"provider/model"` —
+/// names, and the model to ask it for: the spec's, else the provider's
+/// first listed model, else `None` for the requested one. Providers other
+/// than the configured one don't get its API key or endpoint.
+pub fn create_route(
+    config: &BizClawConfig,
+    spec: &str,
+) -> Result<(Box<dyn Provider>, Option<String>)> {
+    let spec = spec.trim();
+    let (provider, model) = match spec.split_once('/') {
+        Some((provider, model)) if !provider.starts_with("custom:") => {
+            (provider, Some(model.to_string()))
+        }
+        _ => (spec, None),
+    };
+    let model = model.or_else(|| {
+        let registry = crate::provider_registry::get_provider_config(provider)?;
+        registry.default_models.first().map(|m| m.id.to_string())
+    });
+
+    let mut route_config = config.clone();
+    route_config.llm.provider = provider.to_string();
+    route_config.llm.fallbacks.clear();
+    route_config.llm.chains.clear();
+    if provider != crate::provider_name(config) {
+        route_config.api_key.clear();
+        route_config.llm.api_key.clear();
+        route_config.llm.endpoint.clear();
+    }
+    Ok((crate::create_provider(&route_config)?, model))
+}
+
+#[async_trait]
+impl Provider for ProviderRouter {
+    fn name(&self) -> &str {
+        self.primary().name()
+    }
+
+    async fn chat(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderResponse> {
+        self.run(params, |provider, params| async move {
+            provider.chat(messages, tools, &params).await
+        })
+        .await
+    }
+
+    async fn stream(
+        &self,
+        messages: &[Message],
+        tools: &[ToolDefinition],
+        params: &GenerateParams,
+    ) -> Result<ProviderStream> {
+        self.run(params, |provider, params| async move {
+            let mut stream = provider.stream(messages, tools, &params).await?;
+            let stream: ProviderStream = match stream.next().await {
+                Some(Ok(first)) => Box::pin(futures::stream::iter([Ok(first)]).chain(stream)),
+                Some(Err(e)) => return Err(e),
+                None => stream,
+            };
+            Ok(stream)
+        })
+        .await
+    }
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+        self.primary().list_models().await
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        for &i in &self.default_chain {
+            if self.routes[i]
+                .provider
+                .health_check()
+                .await
+                .unwrap_or(false)
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        self.primary().embed(texts).await
+    }
+
+    async fn reload(&self, config: &BizClawConfig) {
+        for route in &self.routes {
+            route.provider.reload(config).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bizclaw_core::types::StreamChunk;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Answers with its name and the model it was asked for, or fails.
+    struct Fake {
+        name: &'static str,
+        fail: bool,
+        delay: Duration,
+        calls: Arc<AtomicU32>,
+    }
+
+    fn fake(name: &'static str, fail: bool) -> (Box<dyn Provider>, Arc<AtomicU32>) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let provider = Fake {
+            name,
+            fail,
+            delay: Duration::ZERO,
+            calls: calls.clone(),
+        };
+        (Box::new(provider), calls)
+    }
+
+    #[async_trait]
+    impl Provider for Fake {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn chat(
+            &self,
+            _messages: &[Message],
+            _tools: &[ToolDefinition],
+            params: &GenerateParams,
+        ) -> Result<ProviderResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            if self.fail {
+                return Err(BizClawError::Http(format!("{} is down", self.name)));
+            }
+            Ok(ProviderResponse::text(format!(
+                "{}:{}",
+                self.name, params.model
+            )))
+        }
+
+        async fn list_models(&self) -> Result<Vec<ModelInfo>> {
+            Ok(Vec::new())
+        }
+
+        async fn health_check(&self) -> Result<bool> {
+            Ok(!self.fail)
+        }
+    }
+
+    async fn ask(router: &ProviderRouter, model: &str) -> Result<String> {
+        let params = GenerateParams {
+            model: model.into(),
+            ..Default::default()
+        };
+        let response = router.chat(&[Message::user("hi")], &[], &params).await?;
+        Ok(response.content.unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_failover_and_cooldown() {
+        let (brain, brain_calls) = fake("brain", true);
+        let (openai, _) = fake("openai", false);
+        let router = ProviderRouter::new("brain", brain)
+            .with_limits(None, 2, Duration::from_secs(60))
+            .with_route("openai/gpt-4o-mini", openai, Some("gpt-4o-mini".into()))
+            .with_fallbacks(&["openai/gpt-4o-mini"]);
+
+        assert_eq!(router.name(), "brain");
+        assert_eq!(ask(&router, "qwen").await.unwrap(), "openai:gpt-4o-mini");
+        assert_eq!(ask(&router, "qwen").await.unwrap(), "openai:gpt-4o-mini");
+        assert_eq!(brain_calls.load(Ordering::SeqCst), 2);
+
+        // Two failures in a row: the brain goes to the back of the chain
+        assert_eq!(router.plan(&GenerateParams::default()).0, [1, 0]);
+        ask(&router, "qwen").await.unwrap();
+        assert_eq!(brain_calls.load(Ordering::SeqCst), 2);
+        assert!(router.health_check().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_chains_and_errors() {
+        let (brain, _) = fake("brain", false);
+        let (anthropic, _) = fake("anthropic", true);
+        let (openai, _) = fake("openai", true);
+        let router = ProviderRouter::new("brain", brain)
+            .with_default_model("qwen")
+            .with_route("anthropic", anthropic, Some("claude".into()))
+            .with_route("openai", openai, None)
+            .with_chain("vip", &["anthropic", "brain"])
+            .with_chain("cloud", &["anthropic", "openai"]);
+
+        assert_eq!(ask(&router, "chain:vip").await.unwrap(), "brain:qwen");
+        assert_eq!(ask(&router, "chain:nope").await.unwrap(), "brain:qwen");
+        // Every route failed: the last error comes back
+        let err = ask(&router, "chain:cloud").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            BizClawError::Http("openai is down".into()).to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_stream() {
+        let slow = Fake {
+            name: "ollama",
+            fail: false,
+            delay: Duration::from_secs(5),
+            calls: Arc::default(),
+        };
+        let (openai, _) = fake("openai", false);
+        let router = ProviderRouter::new("ollama", Box::new(slow))
+            .with_limits(Some(Duration::from_millis(20)), 3, Duration::from_secs(60))
+            .with_route("openai", openai, Some("gpt-4o".into()))
+            .with_fallbacks(&["openai"]);
+
+        let params = GenerateParams::default();
+        let stream = router.stream(&[], &[], &params).await.unwrap();
+        let chunks: Vec<StreamChunk> = stream.map(|c| c.unwrap()).collect().await;
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].delta, "openai:gpt-4o");
+    }
+}
@@ -0,0 +1,179 @@
+//! Streaming response framing: server-sent events, which every cloud chat
+//! API uses, and newline-delimited JSON, which Ollama uses.
+
+use bizclaw_core::error::{BizClawError, Result};
+use futures::{Stream, StreamExt};
+use std::collections::VecDeque;
+
+/// Splits bytes into the `data:` payloads of complete events.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    /// Bytes after the last newline.
+    line: Vec<u8>,
+    /// `data:` lines of the event being read.
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Feed bytes as they arrive; returns the events they complete.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut events = Vec::new();
+        for &byte in bytes {
+            if byte != b'\n' {
+                self.line.push(byte);
+                continue;
+            }
+            let line = std::mem::take(&mut self.line);
+            let line = String::from_utf8_lossy(&line);
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(self.data.drain(..).collect::<Vec<_>>().join("\n"));
+                }
+            } else if let Some(data) = line.strip_prefix("data:") {
+                self.data
+                    .push(data.strip_prefix(' ').unwrap_or(data).to_string());
+            }
+            // `event:`, `id:`, `retry:` and `:` comments carry nothing we use
+        }
+        events
+    }
+
+    /// The last event, if the body ended without a blank line.
+    pub fn finish(&mut self) -> Option<String> {
+        let events = self.push(b"\n\n");
+        events.into_iter().next()
+    }
+}
+
+/// Splits bytes into non-empty lines, e.g. NDJSON records.
+#[derive(Debug, Default)]
+pub struct LineParser {
+    line: Vec<u8>,
+}
+
+impl LineParser {
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+        for &byte in bytes {
+            if byte != b'\n' {
+                self.line.push(byte);
+                continue;
+            }
+            let line = std::mem::take(&mut self.line);
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if !line.is_empty() {
+                lines.push(line.to_string());
+            }
+        }
+        lines
+    }
+
+    pub fn finish(&mut self) -> Option<String> {
+        self.push(b"\n").into_iter().next()
+    }
+}
+
+trait Framer: Send + 'static {
+    fn push(&mut self, bytes: &[u8]) -> Vec<String>;
+    fn finish(&mut self) -> Option<String>;
+}
+
+impl Framer for SseParser {
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        SseParser::push(self, bytes)
+    }
+    fn finish(&mut self) -> Option<String> {
+        SseParser::finish(self)
+    }
+}
+
+impl Framer for LineParser {
+    fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        LineParser::push(self, bytes)
+    }
+    fn finish(&mut self) -> Option<String> {
+        LineParser::finish(self)
+    }
+}
+
+/// The `data:` payloads of a server-sent events body, in order.
+pub fn events(response: reqwest::Response) -> impl Stream<Item = Result<String>> + Send {
+    framed(response, SseParser::default())
+}
+
+/// The lines of a newline-delimited JSON body, in order.
+pub fn lines(response: reqwest::Response) -> impl Stream<Item = Result<String>> + Send {
+    framed(response, LineParser::default())
+}
+
+fn framed<F: Framer>(
+    response: reqwest::Response,
+    parser: F,
+) -> impl Stream<Item = Result<String>> + Send {
+    let state = (
+        Some(Box::pin(response.bytes_stream())),
+        parser,
+        VecDeque::new(),
+    );
+    futures::stream::unfold(state, |(mut body, mut parser, mut ready)| async move {
+        loop {
+            if let Some(event) = ready.pop_front() {
+                return Some((Ok(event), (body, parser, ready)));
+            }
+            match body.as_mut()?.next().await {
+                Some(Ok(bytes)) => ready.extend(parser.push(&bytes)),
+                Some(Err(e)) => {
+                    let err = BizClawError::Http(format!("Stream interrupted: {e}"));
+                    return Some((Err(err), (None, parser, ready)));
+                }
+                None => {
+                    body = None;
+                    ready.extend(parser.finish());
+                    if ready.is_empty() {
+                        return None;
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"data: {\"a\"").is_empty());
+        assert_eq!(
+            parser.push(b":1}\r\n\r\n: keep-alive\n\ndata: x\n"),
+            ["{\"a\":1}"]
+        );
+        assert_eq!(
+            parser.push(b"data: y\n\nevent: done\ndata: [DONE]"),
+            ["x\ny"]
+        );
+        assert_eq!(parser.finish().as_deref(), Some("[DONE]"));
+        assert_eq!(parser.finish(), None);
+    }
+
+    #[test]
+    fn test_multibyte_split() {
+        let mut parser = SseParser::default();
+        let text = "data: Xin chào\n\n".as_bytes();
+        let (a, b) = text.split_at(13); // inside "à"
+        assert!(parser.push(a).is_empty());
+        assert_eq!(parser.push(b), ["Xin chào"]);
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut parser = LineParser::default();
+        assert_eq!(parser.push(b"{\"a\":1}\n\n{\"b\""), ["{\"a\":1}"]);
+        assert_eq!(parser.push(b":2}\r\n{\"c\":3}"), ["{\"b\":2}"]);
+        assert_eq!(parser.finish().as_deref(), Some("{\"c\":3}"));
+    }
+}
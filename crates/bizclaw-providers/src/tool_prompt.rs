@@ -0,0 +1,181 @@
+//! Prompted tool calling for models without a native tool API.
+//!
+//! The local brain only sees text, so the available tools are described in
+//! the system prompt and the model answers with one JSON object, decoded
+//! under the JSON grammar:
+//!
+//! ```json
+//! {"tool": "web_search", "arguments": {"query": "giá vàng hôm nay"}}
+//! {"answer": "Giá vàng hôm nay là ..."}
+//! ```
+//!
+//! The reply is parsed back into a [`ProviderResponse`] with `tool_calls`,
+//! so the agent loop is the same for local and remote providers.
+
+use bizclaw_core::types::{
+    FunctionCall, Message, ProviderResponse, Role, ToolCall, ToolDefinition,
+};
+
+/// Describe `tools` and the reply format the model must follow.
+pub fn instructions(tools: &[ToolDefinition]) -> String {
+    let mut out = String::from(
+        "You can use tools. Reply with exactly one JSON object and nothing else.\n\
+         To call a tool: {\"tool\": \"<name>\", \"arguments\": {...}}\n\
+         To answer the user: {\"answer\": \"<your reply>\"}\n\
+         After a tool call you will receive its result; then call another tool or answer.\n\n\
+         Tools:\n",
+    );
+    for tool in tools {
+        out.push_str(&format!(
+            "- {}: {}\n  arguments: {}\n",
+            tool.name, tool.description, tool.parameters
+        ));
+    }
+    out
+}
+
+/// Rewrite a conversation for a text-only model: append the tool
+/// instructions to the system prompt, show earlier tool calls as the JSON
+/// the model would have written and tool results as user turns.
+pub fn prepare(messages: &[Message], tools: &[ToolDefinition]) -> Vec<Message> {
+    let instructions = instructions(tools);
+    let mut out = Vec::with_capacity(messages.len() + 1);
+    let mut injected = false;
+
+    for msg in messages {
+        match msg.role {
+            Role::System if !injected => {
+                out.push(Message::system(format!(
+                    "{}\n\n{instructions}",
+                    msg.content
+                )));
+                injected = true;
+            }
+            Role::Assistant if msg.tool_calls.as_ref().is_some_and(|c| !c.is_empty()) => {
+                for call in msg.tool_calls.iter().flatten() {
+                    let arguments: serde_json::Value =
+                        serde_json::from_str(&call.function.arguments)
+                            .unwrap_or(serde_json::Value::Null);
+                    let json = serde_json::json!({
+                        "tool": call.function.name,
+                        "arguments": arguments,
+                    });
+                    out.push(Message::assistant(json.to_string()));
+                }
+            }
+            Role::Assistant => {
+                let json = serde_json::json!({ "answer": msg.content });
+                out.push(Message::assistant(json.to_string()));
+            }
+            Role::Tool => out.push(Message::user(format!("Tool result: {}", msg.content))),
+            _ => out.push(msg.clone()),
+        }
+    }
+    if !injected {
+        out.insert(0, Message::system(instructions));
+    }
+    out
+}
+
+/// Turn the model's JSON reply into a response. Anything that is not a
+/// tool call is treated as the answer, so a model that ignores the format
+/// still gets its text through.
+pub fn parse_reply(reply: &serde_json::Value, call_id: &str) -> ProviderResponse {
+    if let Some(name) = reply.get("tool").and_then(|t| t.as_str()) {
+        let arguments = reply
+            .get("arguments")
+            .cloned()
+            .unwrap_or_else(|| serde_json::json!({}));
+        return ProviderResponse {
+            content: None,
+            tool_calls: vec![ToolCall {
+                id: call_id.to_string(),
+                r#type: "function".into(),
+                function: FunctionCall {
+                    name: name.to_string(),
+                    arguments: arguments.to_string(),
+                },
+            }],
+            finish_reason: Some("tool_calls".into()),
+            usage: None,
+        };
+    }
+
+    let answer = match reply.get("answer") {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+        None => match reply {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        },
+    };
+    ProviderResponse::text(answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_tool() -> ToolDefinition {
+        ToolDefinition {
+            name: "web_search".into(),
+            description: "Search the web".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {"query": {"type": "string"}},
+                "required": ["query"]
+            }),
+        }
+    }
+
+    #[test]
+    fn test_parse_tool_call_and_answer() {
+        let reply = serde_json::json!({"tool": "web_search", "arguments": {"query": "vàng"}});
+        let resp = parse_reply(&reply, "call_1");
+        assert_eq!(resp.tool_calls.len(), 1);
+        assert_eq!(resp.tool_calls[0].id, "call_1");
+        assert_eq!(resp.tool_calls[0].function.name, "web_search");
+        let args: serde_json::Value =
+            serde_json::from_str(&resp.tool_calls[0].function.arguments).unwrap();
+        assert_eq!(args["query"], "vàng");
+
+        let resp = parse_reply(&serde_json::json!({"answer": "Xin chào"}), "call_2");
+        assert!(resp.tool_calls.is_empty());
+        assert_eq!(resp.content.as_deref(), Some("Xin chào"));
+
+        let resp = parse_reply(&serde_json::json!({"text": "off format"}), "call_3");
+        assert_eq!(resp.content.as_deref(), Some(r#"{"text":"off format"}"#));
+    }
+
+    #[test]
+    fn test_prepare_rewrites_tool_turns() {
+        let call = parse_reply(
+            &serde_json::json!({"tool": "web_search", "arguments": {"query": "x"}}),
+            "call_1",
+        );
+        let messages = vec![
+            Message::system("Be brief."),
+            Message::user("search x"),
+            Message {
+                role: Role::Assistant,
+                content: String::new(),
+                name: None,
+                tool_call_id: None,
+                tool_calls: Some(call.tool_calls),
+            },
+            Message::tool("x is y", "call_1"),
+        ];
+        let out = prepare(&messages, &[search_tool()]);
+        assert_eq!(out.len(), 4);
+        assert!(out[0].content.starts_with("Be brief."));
+        assert!(out[0].content.contains("- web_search: Search the web"));
+        assert_eq!(out[2].role, Role::Assistant);
+        assert!(out[2].content.contains(r#""tool":"web_search""#));
+        assert_eq!(out[3].role, Role::User);
+        assert_eq!(out[3].content, "Tool result: x is y");
+
+        // No system prompt: the instructions become one.
+        let out = prepare(&[Message::user("hi")], &[search_tool()]);
+        assert_eq!(out[0].role, Role::System);
+    }
+}
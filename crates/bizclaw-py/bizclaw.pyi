@@ -0,0 +1,31 @@
+from os import PathLike
+from typing import Iterator, Optional
+
+__version__: str
+
+class BizClawError(Exception): ...
+
+class BrainEngine:
+    def __init__(
+        self,
+        model_path: str | PathLike[str],
+        *,
+        context_length: int = 2048,
+        temperature: float = 0.7,
+        top_p: float = 0.9,
+        threads: int = 4,
+    ) -> None: ...
+    def generate(self, prompt: str, max_tokens: int = 256) -> str: ...
+    def stream(self, prompt: str, max_tokens: int = 256) -> TokenStream: ...
+    def embed(self, text: str) -> list[float]: ...
+    def tokenize(self, text: str) -> list[int]: ...
+    def detokenize(self, tokens: list[int]) -> str: ...
+    def count_tokens(self, text: str) -> int: ...
+    @property
+    def context_size(self) -> Optional[int]: ...
+    @property
+    def model_info(self) -> Optional[str]: ...
+
+class TokenStream(Iterator[str]):
+    def __iter__(self) -> TokenStream: ...
+    def __next__(self) -> str: ...
@@ -0,0 +1,168 @@
+//! # BizClaw Python bindings
+//! The brain engine as the `bizclaw` Python module: load a GGUF model,
+//! generate (all at once or as an iterator of pieces), embed and tokenize,
+//! without running the gateway.
+//!
+//! ```python
+//! from bizclaw import BrainEngine
+//! engine = BrainEngine("tinyllama.Q4_0.gguf", temperature=0.2)
+//! for piece in engine.stream("Once upon a time", max_tokens=64):
+//!     print(piece, end="")
+//! ```
+//!
+//! Calls release the GIL while the engine runs. An engine runs one call at
+//! a time; calls from other threads wait for it.
+
+use bizclaw_brain::BrainConfig;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, mpsc};
+
+create_exception!(bizclaw, BizClawError, PyException);
+
+fn py_err(e: bizclaw_core::error::BizClawError) -> PyErr {
+    BizClawError::new_err(e.to_string())
+}
+
+/// A GGUF model loaded into the brain engine.
+#[pyclass(module = "bizclaw")]
+struct BrainEngine {
+    engine: Arc<Mutex<bizclaw_brain::BrainEngine>>,
+}
+
+impl BrainEngine {
+    fn lock(&self) -> MutexGuard<'_, bizclaw_brain::BrainEngine> {
+        self.engine.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[pymethods]
+impl BrainEngine {
+    #[new]
+    #[pyo3(signature = (model_path, *, context_length = 2048, temperature = 0.7, top_p = 0.9, threads = 4))]
+    fn new(
+        py: Python<'_>,
+        model_path: PathBuf,
+        context_length: u32,
+        temperature: f32,
+        top_p: f32,
+        threads: u32,
+    ) -> PyResult<Self> {
+        let mut engine = bizclaw_brain::BrainEngine::new(BrainConfig {
+            threads,
+            context_length,
+            temperature,
+            top_p,
+            ..Default::default()
+        });
+        py.allow_threads(|| engine.load_model(&model_path))
+            .map_err(py_err)?;
+        Ok(Self {
+            engine: Arc::new(Mutex::new(engine)),
+        })
+    }
+
+    /// Generate up to `max_tokens` tokens after `prompt` and return them.
+    #[pyo3(signature = (prompt, max_tokens = 256))]
+    fn generate(&self, py: Python<'_>, prompt: String, max_tokens: u32) -> PyResult<String> {
+        py.allow_threads(|| self.lock().generate(&prompt, max_tokens))
+            .map_err(py_err)
+    }
+
+    /// Iterate over the pieces of text as they are generated. Stopping
+    /// early stops generation.
+    #[pyo3(signature = (prompt, max_tokens = 256))]
+    fn stream(&self, prompt: String, max_tokens: u32) -> TokenStream {
+        // Bounded so an unread stream holds generation back.
+        let (tx, rx) = mpsc::sync_channel(64);
+        let engine = self.engine.clone();
+        std::thread::spawn(move || {
+            let mut engine = engine.lock().unwrap_or_else(PoisonError::into_inner);
+            let result = engine.generate_stream(&prompt, max_tokens, |piece| {
+                tx.send(Ok(piece.to_string())).is_ok()
+            });
+            if let Err(e) = result {
+                let _ = tx.send(Err(e.to_string()));
+            }
+        });
+        TokenStream { rx: Mutex::new(rx) }
+    }
+
+    /// An L2-normalized embedding of `text`.
+    fn embed(&self, py: Python<'_>, text: String) -> PyResult<Vec<f32>> {
+        py.allow_threads(|| self.lock().embed(&text))
+            .map_err(py_err)
+    }
+
+    /// Token IDs of `text`, without BOS.
+    fn tokenize(&self, text: String) -> PyResult<Vec<u32>> {
+        self.lock().tokenize(&text).map_err(py_err)
+    }
+
+    /// Text of `tokens`.
+    fn detokenize(&self, tokens: Vec<u32>) -> PyResult<String> {
+        self.lock().detokenize(&tokens).map_err(py_err)
+    }
+
+    /// Number of tokens `text` encodes to, without BOS.
+    fn count_tokens(&self, text: String) -> PyResult<usize> {
+        self.lock().count_tokens(&text).map_err(py_err)
+    }
+
+    /// Positions the KV cache holds: prompt and output together.
+    #[getter]
+    fn context_size(&self) -> Option<usize> {
+        self.lock().context_size()
+    }
+
+    #[getter]
+    fn model_info(&self) -> Option<String> {
+        self.lock().model_info()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BrainEngine({})",
+            self.lock().model_info().unwrap_or_default()
+        )
+    }
+}
+
+/// Pieces of generated text, from `BrainEngine.stream`.
+#[pyclass(module = "bizclaw")]
+struct TokenStream {
+    rx: Mutex<mpsc::Receiver<Result<String, String>>>,
+}
+
+#[pymethods]
+impl TokenStream {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<String>> {
+        let next = py.allow_threads(|| {
+            self.rx
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .recv()
+        });
+        match next {
+            Ok(Ok(piece)) => Ok(Some(piece)),
+            Ok(Err(e)) => Err(BizClawError::new_err(e)),
+            // The generating thread finished.
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+#[pymodule]
+fn bizclaw(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<BrainEngine>()?;
+    m.add_class::<TokenStream>()?;
+    m.add("BizClawError", m.py().get_type_bound::<BizClawError>())?;
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    Ok(())
+}
@@ -1,88 +1,151 @@
 //! Lightweight cron expression parser.
 //! Supports: "MIN HOUR DOM MON DOW" (5-field, no seconds)
-//! Wildcards: *, */N, N
-//! Example: "0 8 * * *" = every day at 8:00
+//! Fields: *, N, N-M, lists "1,15", steps "*/N" and "N-M/S";
+//! month and weekday names ("jan", "mon-fri"); Sunday is 0 or 7.
+//! Example: "0 8 * * 1-5" = weekdays at 8:00
+//!
+//! As in classic cron, when both day-of-month and day-of-week are
+//! restricted a day matching either one fires.
 //!
 //! Designed for PicoClaw-level simplicity — no cron crate dependency.
 
-use chrono::{DateTime, Duration, Timelike, Utc};
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, TimeZone, Timelike, Utc};
+
+/// How far ahead a schedule is searched before giving up ("0 0 30 2 *").
+const MAX_YEARS_AHEAD: i64 = 5;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A parsed cron expression. Each field is a bitset of matching values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    /// Day-of-month / day-of-week were given explicitly (not `*`).
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
 
-/// Parse a simple cron expression and compute the next run time.
-pub fn next_run_from_cron(expression: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
-    let parts: Vec<&str> = expression.split_whitespace().collect();
-    if parts.len() != 5 {
-        tracing::warn!(
-            "Invalid cron expression: '{}' (need 5 fields: MIN HOUR DOM MON DOW)",
-            expression
-        );
-        return None;
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = expression.split_whitespace().collect();
+        if parts.len() != 5 {
+            return Err(format!(
+                "Invalid cron expression: '{expression}' (need 5 fields: MIN HOUR DOM MON DOW)"
+            ));
+        }
+        let field = |i: usize, min: u32, max: u32, names: &[&str]| {
+            parse_field(parts[i], min, max, names)
+                .ok_or_else(|| format!("Invalid cron field '{}' in '{expression}'", parts[i]))
+        };
+
+        // Sunday may be written as 7.
+        let dow = field(4, 0, 7, &WEEKDAYS)?;
+        Ok(Self {
+            minutes: field(0, 0, 59, &[])?,
+            hours: field(1, 0, 23, &[])? as u32,
+            days: field(2, 1, 31, &[])? as u32,
+            months: field(3, 1, 12, &MONTHS)? as u16,
+            weekdays: ((dow | (dow >> 7)) & 0x7f) as u8,
+            days_restricted: parts[2] != "*",
+            weekdays_restricted: parts[4] != "*",
+        })
     }
 
-    let minute_spec = parts[0];
-    let hour_spec = parts[1];
-    let _dom_spec = parts[2]; // Day of month (simplified: only * supported)
-    let _mon_spec = parts[3]; // Month (simplified: only * supported)
-    let _dow_spec = parts[4]; // Day of week (simplified: only * supported)
-
-    // Parse minute
-    let minutes = parse_field(minute_spec, 0, 59)?;
-    let hours = parse_field(hour_spec, 0, 23)?;
-
-    // Find next matching time after `after`
-    let mut candidate = after + Duration::minutes(1);
-    // Zero out seconds
-    candidate = candidate.with_second(0).unwrap_or(candidate);
-
-    // Try up to 48 hours ahead
-    for _ in 0..(48 * 60) {
-        let m = candidate.minute();
-        let h = candidate.hour();
-
-        if minutes.contains(&m) && hours.contains(&h) {
-            return Some(candidate);
+    fn day_matches(&self, t: &NaiveDateTime) -> bool {
+        let dom = self.days & (1 << t.day()) != 0;
+        let dow = self.weekdays & (1 << t.weekday().num_days_from_sunday()) != 0;
+        if self.days_restricted && self.weekdays_restricted {
+            dom || dow
+        } else {
+            dom && dow
         }
-        candidate += Duration::minutes(1);
     }
 
-    None
+    /// The first matching minute strictly after `after`, in `after`'s
+    /// timezone.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = after.timezone();
+        let start = after.naive_local();
+        let limit = start + Duration::days(366 * MAX_YEARS_AHEAD);
+        let mut t = start.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+
+        while t <= limit {
+            if self.months & (1 << t.month()) == 0 || !self.day_matches(&t) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + Duration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += Duration::minutes(1);
+            } else if let Some(local) = tz.from_local_datetime(&t).earliest() {
+                return Some(local);
+            } else {
+                // Skipped by a daylight-saving jump.
+                t += Duration::minutes(1);
+            }
+        }
+        None
+    }
 }
 
-/// Parse a cron field into a list of matching values.
-fn parse_field(field: &str, min: u32, max: u32) -> Option<Vec<u32>> {
-    if field == "*" {
-        return Some((min..=max).collect());
+/// Parse a simple cron expression and compute the next run time.
+pub fn next_run_from_cron(expression: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    match CronSchedule::parse(expression) {
+        Ok(schedule) => schedule.next_after(&after),
+        Err(e) => {
+            tracing::warn!("{e}");
+            None
+        }
     }
+}
 
-    // */N — every N
-    if let Some(step) = field.strip_prefix("*/") {
-        let n: u32 = step.parse().ok()?;
-        if n == 0 {
+/// Parse a cron field into a bitset of matching values.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Option<u64> {
+    let value = |s: &str| -> Option<u32> {
+        let s = s.trim().to_ascii_lowercase();
+        let n = match names.iter().position(|name| *name == s) {
+            // Months are numbered from 1, weekdays from 0.
+            Some(i) => i as u32 + if names.len() == 12 { 1 } else { 0 },
+            None => s.parse().ok()?,
+        };
+        (min..=max).contains(&n).then_some(n)
+    };
+
+    let mut bits = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (item, 1),
+        };
+        let (from, to) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (value(a)?, value(b)?)
+        } else {
+            let n = value(range)?;
+            // "N/S" runs from N to the end of the range.
+            (n, if item.contains('/') { max } else { n })
+        };
+        if from > to {
             return None;
         }
-        return Some((min..=max).step_by(n as usize).collect());
-    }
-
-    // Comma-separated: "0,15,30,45"
-    if field.contains(',') {
-        let vals: Result<Vec<u32>, _> = field.split(',').map(|s| s.trim().parse()).collect();
-        return vals
-            .ok()
-            .map(|v| v.into_iter().filter(|x| *x >= min && *x <= max).collect());
-    }
-
-    // Single number
-    let n: u32 = field.parse().ok()?;
-    if n >= min && n <= max {
-        Some(vec![n])
-    } else {
-        None
+        for n in (from..=to).step_by(step as usize) {
+            bits |= 1 << n;
+        }
     }
+    Some(bits)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::TimeZone;
+    use chrono::{FixedOffset, TimeZone};
 
     #[test]
     fn test_every_hour() {
@@ -111,5 +174,47 @@ mod tests {
     fn test_invalid_expression() {
         let after = Utc::now();
         assert!(next_run_from_cron("bad", after).is_none());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("0 8 * * fri-mon").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+    }
+
+    #[test]
+    fn test_weekdays_and_months() {
+        // Saturday 2026-02-21 → next weekday 09:00 is Monday the 23rd.
+        let after = Utc.with_ymd_and_hms(2026, 2, 21, 12, 0, 0).unwrap();
+        let next = next_run_from_cron("0 9 * * mon-fri", after).unwrap();
+        assert_eq!(next, Utc.with_ymd_and_hms(2026, 2, 23, 9, 0, 0).unwrap());
+
+        // Sunday as 7.
+        let next = next_run_from_cron("30 18 * * 7", after).unwrap();
+        assert_eq!(next, Utc.with_ymd_and_hms(2026, 2, 22, 18, 30, 0).unwrap());
+
+        // First of each quarter.
+        let next = next_run_from_cron("0 7 1 1,4,7,10 *", after).unwrap();
+        assert_eq!(next, Utc.with_ymd_and_hms(2026, 4, 1, 7, 0, 0).unwrap());
+
+        // Day of month OR Friday when both are given.
+        let next = next_run_from_cron("0 0 15 * fri", after).unwrap();
+        assert_eq!(next, Utc.with_ymd_and_hms(2026, 2, 27, 0, 0, 0).unwrap());
+
+        // Ranges with steps.
+        let next = next_run_from_cron("10-40/15 * * * *", after).unwrap();
+        assert_eq!(next.minute(), 10);
+
+        // Never matches.
+        assert!(next_run_from_cron("0 0 30 2 *", after).is_none());
+    }
+
+    #[test]
+    fn test_local_timezone() {
+        let vn = FixedOffset::east_opt(7 * 3600).unwrap();
+        let after = vn.with_ymd_and_hms(2026, 2, 23, 8, 30, 0).unwrap();
+        let next = CronSchedule::parse("0 8 * * *")
+            .unwrap()
+            .next_after(&after)
+            .unwrap();
+        assert_eq!(next, vn.with_ymd_and_hms(2026, 2, 24, 8, 0, 0).unwrap());
+        assert_eq!(next.with_timezone(&Utc).hour(), 1);
     }
 }
@@ -0,0 +1,341 @@
+//! Configured jobs — proactive messages posted to channel threads.
+//!
+//! Jobs come from `[[scheduler.jobs]]`: a cron expression, a target thread
+//! and either a prompt (the model writes the message, e.g. a daily standup
+//! summary) or a fixed message (reminders, heartbeats). [`run_jobs`] sleeps
+//! until the next job is due, generates and delivers it, and repeats.
+//!
+//! Generation and delivery are callbacks, like the agent callback of
+//! [`crate::engine::spawn_scheduler_with_agent`], so this crate does not
+//! depend on the agent or channel crates.
+
+use std::future::Future;
+use std::time::Duration;
+
+use bizclaw_core::config::{ScheduledJobConfig, SchedulerConfig};
+use bizclaw_core::types::ThreadType;
+use chrono::{DateTime, FixedOffset, Local, Offset, Utc};
+
+use crate::cron::CronSchedule;
+
+/// A job due longer ago than this (machine asleep, clock jumped) is
+/// skipped instead of posted late.
+const MAX_LATENESS_SECS: i64 = 10 * 60;
+
+/// Longest single sleep, so clock changes are noticed.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// The thread a job posts to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobTarget {
+    pub channel: String,
+    pub thread_id: String,
+    pub thread_type: ThreadType,
+}
+
+/// What a job posts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobContent {
+    /// Instruction for the model; its answer is posted.
+    Prompt(String),
+    /// Posted as is.
+    Message(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub name: String,
+    pub schedule: CronSchedule,
+    pub target: JobTarget,
+    pub content: JobContent,
+}
+
+impl Job {
+    pub fn from_config(config: &ScheduledJobConfig) -> Result<Self, String> {
+        let schedule = CronSchedule::parse(&config.cron)?;
+        if config.channel.is_empty() || config.thread_id.is_empty() {
+            return Err(format!(
+                "Job '{}' needs a channel and thread_id",
+                config.name
+            ));
+        }
+        let content = if !config.prompt.trim().is_empty() {
+            JobContent::Prompt(config.prompt.trim().to_string())
+        } else if !config.message.trim().is_empty() {
+            JobContent::Message(config.message.clone())
+        } else {
+            return Err(format!("Job '{}' needs a prompt or a message", config.name));
+        };
+        Ok(Self {
+            name: config.name.clone(),
+            schedule,
+            target: JobTarget {
+                channel: config.channel.clone(),
+                thread_id: config.thread_id.clone(),
+                thread_type: if config.group {
+                    ThreadType::Group
+                } else {
+                    ThreadType::Direct
+                },
+            },
+            content,
+        })
+    }
+}
+
+/// Enabled, valid jobs from `[scheduler]`; invalid ones are logged and
+/// left out.
+pub fn jobs_from_config(config: &SchedulerConfig) -> Vec<Job> {
+    config
+        .jobs
+        .iter()
+        .filter(|job| job.enabled)
+        .filter_map(|job| match Job::from_config(job) {
+            Ok(job) => Some(job),
+            Err(e) => {
+                tracing::warn!("Scheduled job '{}' ignored: {e}", job.name);
+                None
+            }
+        })
+        .collect()
+}
+
+/// `"+07:00"`, `"-0530"`, `"+7"` or `"UTC"`.
+pub fn parse_utc_offset(s: &str) -> Option<FixedOffset> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("utc") || s == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+    let (sign, rest) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// The timezone of `[scheduler] utc_offset`, the system's offset if unset.
+pub fn utc_offset(config: &SchedulerConfig) -> FixedOffset {
+    if !config.utc_offset.is_empty() {
+        match parse_utc_offset(&config.utc_offset) {
+            Some(offset) => return offset,
+            None => tracing::warn!(
+                "Invalid [scheduler] utc_offset '{}', using the system timezone",
+                config.utc_offset
+            ),
+        }
+    }
+    Local::now().offset().fix()
+}
+
+/// When the next job is due after `after`, and every job due then.
+pub fn next_due(
+    jobs: &[Job],
+    after: &DateTime<FixedOffset>,
+) -> Option<(DateTime<FixedOffset>, Vec<usize>)> {
+    let times: Vec<Option<DateTime<FixedOffset>>> = jobs
+        .iter()
+        .map(|job| job.schedule.next_after(after))
+        .collect();
+    let at = *times.iter().flatten().min()?;
+    let due = times
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| **t == Some(at))
+        .map(|(i, _)| i)
+        .collect();
+    Some((at, due))
+}
+
+/// Generate (for prompt jobs) and deliver one job's message.
+pub async fn run_job<G, GF, D, DF>(job: &Job, generate: &G, deliver: &D)
+where
+    G: Fn(JobTarget, String) -> GF,
+    GF: Future<Output = Result<String, String>>,
+    D: Fn(JobTarget, String) -> DF,
+    DF: Future<Output = Result<(), String>>,
+{
+    let text = match &job.content {
+        JobContent::Prompt(prompt) => generate(job.target.clone(), prompt.clone()).await,
+        JobContent::Message(message) => Ok(message.clone()),
+    };
+    match text {
+        Ok(text) if text.trim().is_empty() => {
+            tracing::info!("📅 Job '{}' produced nothing to post", job.name);
+        }
+        Ok(text) => match deliver(job.target.clone(), text).await {
+            Ok(()) => tracing::info!(
+                "📅 Job '{}' posted to {}:{}",
+                job.name,
+                job.target.channel,
+                job.target.thread_id
+            ),
+            Err(e) => tracing::warn!("📅 Job '{}' could not be delivered: {e}", job.name),
+        },
+        Err(e) => tracing::warn!("📅 Job '{}' failed to generate: {e}", job.name),
+    }
+}
+
+/// Run `jobs` forever in the `offset` timezone.
+///
+/// `generate(target, prompt)` writes the message for prompt jobs, in the
+/// context of the target thread; `deliver(target, text)` posts it.
+pub async fn run_jobs<G, GF, D, DF>(jobs: Vec<Job>, offset: FixedOffset, generate: G, deliver: D)
+where
+    G: Fn(JobTarget, String) -> GF,
+    GF: Future<Output = Result<String, String>>,
+    D: Fn(JobTarget, String) -> DF,
+    DF: Future<Output = Result<(), String>>,
+{
+    tracing::info!("📅 {} scheduled job(s) running (UTC{offset})", jobs.len());
+    let mut after = Utc::now().with_timezone(&offset);
+    loop {
+        let Some((at, due)) = next_due(&jobs, &after) else {
+            tracing::warn!("📅 No scheduled job will run again");
+            return;
+        };
+        loop {
+            let now = Utc::now().with_timezone(&offset);
+            if now >= at {
+                break;
+            }
+            let wait = (at - now).to_std().unwrap_or_default();
+            tokio::time::sleep(wait.min(MAX_SLEEP)).await;
+        }
+
+        let late = (Utc::now().with_timezone(&offset) - at).num_seconds();
+        for i in due {
+            if late > MAX_LATENESS_SECS {
+                tracing::warn!(
+                    "📅 Job '{}' skipped: due at {at}, {late}s ago",
+                    jobs[i].name
+                );
+                continue;
+            }
+            run_job(&jobs[i], &generate, &deliver).await;
+        }
+        after = at;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::sync::{Arc, Mutex};
+
+    fn config(name: &str, cron: &str) -> ScheduledJobConfig {
+        ScheduledJobConfig {
+            name: name.into(),
+            cron: cron.into(),
+            channel: "telegram".into(),
+            thread_id: "-100".into(),
+            group: true,
+            prompt: String::new(),
+            message: "ping".into(),
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_jobs_from_config() {
+        let mut prompt = config("standup", "0 9 * * mon-fri");
+        prompt.prompt = "Summarize yesterday".into();
+        let mut disabled = config("off", "* * * * *");
+        disabled.enabled = false;
+        let mut empty = config("empty", "* * * * *");
+        empty.message.clear();
+
+        let jobs = jobs_from_config(&SchedulerConfig {
+            utc_offset: String::new(),
+            jobs: vec![
+                prompt,
+                config("heartbeat", "*/30 * * * *"),
+                config("bad", "every day"),
+                disabled,
+                empty,
+            ],
+        });
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(
+            jobs[0].content,
+            JobContent::Prompt("Summarize yesterday".into())
+        );
+        assert_eq!(jobs[0].target.thread_type, ThreadType::Group);
+        assert_eq!(jobs[1].content, JobContent::Message("ping".into()));
+    }
+
+    #[test]
+    fn test_parse_utc_offset() {
+        let east = |s: i32| FixedOffset::east_opt(s);
+        assert_eq!(parse_utc_offset("+07:00"), east(7 * 3600));
+        assert_eq!(parse_utc_offset("-0530"), east(-(5 * 3600 + 30 * 60)));
+        assert_eq!(parse_utc_offset("+7"), east(7 * 3600));
+        assert_eq!(parse_utc_offset("UTC"), east(0));
+        assert_eq!(parse_utc_offset("7"), None);
+        assert_eq!(parse_utc_offset("+25:00"), None);
+    }
+
+    #[test]
+    fn test_next_due_groups_jobs() {
+        let jobs = jobs_from_config(&SchedulerConfig {
+            utc_offset: String::new(),
+            jobs: vec![
+                config("a", "0 9 * * *"),
+                config("b", "30 8 * * *"),
+                config("c", "30 8 * * *"),
+            ],
+        });
+        let vn = FixedOffset::east_opt(7 * 3600).unwrap();
+        let after = vn.with_ymd_and_hms(2026, 3, 2, 8, 0, 0).unwrap();
+        let (at, due) = next_due(&jobs, &after).unwrap();
+        assert_eq!(at, vn.with_ymd_and_hms(2026, 3, 2, 8, 30, 0).unwrap());
+        assert_eq!(due, vec![1, 2]);
+        assert!(next_due(&[], &after).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_run_job() {
+        let posted = Arc::new(Mutex::new(Vec::new()));
+        let generate = |target: JobTarget, prompt: String| async move {
+            Ok::<_, String>(format!(
+                "{} for {}",
+                prompt.to_uppercase(),
+                target.thread_id
+            ))
+        };
+        let deliver = |target: JobTarget, text: String| {
+            let posted = posted.clone();
+            async move {
+                posted.lock().unwrap().push((target.channel, text));
+                Ok::<_, String>(())
+            }
+        };
+
+        let mut standup = config("standup", "0 9 * * *");
+        standup.prompt = "standup".into();
+        for job in jobs_from_config(&SchedulerConfig {
+            utc_offset: String::new(),
+            jobs: vec![standup, config("heartbeat", "* * * * *")],
+        }) {
+            run_job(&job, &generate, &deliver).await;
+        }
+        assert_eq!(
+            *posted.lock().unwrap(),
+            vec![
+                ("telegram".to_string(), "STANDUP for -100".to_string()),
+                ("telegram".to_string(), "ping".to_string()),
+            ]
+        );
+    }
+}
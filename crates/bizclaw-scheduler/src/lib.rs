@@ -22,6 +22,10 @@
 //!                      ├── Webhook (HTTP POST)
 //!                      └── Dashboard (WebSocket)
 //!
+//! Configured jobs ([scheduler.jobs] in config.toml)
+//!   ├── cron "0 9 * * mon-fri" → prompt → model writes the message
+//!   └── posted to a channel thread (standups, reminders, heartbeats)
+//!
 //! Workflow Engine
 //!   ├── Event (message, schedule, metric) → evaluate rules
 //!   ├── Matching rules → generate actions
@@ -31,6 +35,7 @@
 pub mod cron;
 pub mod dispatch;
 pub mod engine;
+pub mod jobs;
 pub mod notify;
 pub mod persistence;
 pub mod store;
@@ -38,6 +43,7 @@ pub mod tasks;
 pub mod workflow;
 
 pub use engine::{RetryStats, SchedulerEngine};
+pub use jobs::{Job, JobTarget};
 pub use notify::{Notification, NotifyChannel, NotifyRouter};
 pub use persistence::SchedulerDb;
 pub use store::TaskStore;
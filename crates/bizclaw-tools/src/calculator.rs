@@ -0,0 +1,597 @@
+//! Calculator Tool — exact arithmetic and unit conversion.
+//!
+//! A small quantized model is unreliable at arithmetic, so numeric
+//! questions go through this evaluator instead. It parses expressions
+//! itself (no `eval`, no shell) and supports:
+//!
+//! - `+ - * / ^`, parentheses, `mod`
+//! - percentages: `15% of 240`, `1200000 - 15%` (discount), `80 + 10%`
+//! - functions: `sqrt abs round floor ceil ln log10 log2 exp sin cos tan min max`
+//! - constants: `pi`, `e`
+//! - unit conversion: `<expr> <unit> to <unit>` — length, mass, volume,
+//!   area, time, data and temperature, e.g. `5 km to mi`, `98.6 f to c`
+
+use async_trait::async_trait;
+use bizclaw_core::error::Result;
+use bizclaw_core::traits::Tool;
+use bizclaw_core::types::{ToolDefinition, ToolResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Length,
+    Mass,
+    Volume,
+    Area,
+    Time,
+    Data,
+    Temperature,
+}
+
+/// (aliases, dimension, size in the dimension's base unit)
+const UNITS: &[(&[&str], Dimension, f64)] = &[
+    (
+        &["mm", "millimeter", "millimeters"],
+        Dimension::Length,
+        0.001,
+    ),
+    (
+        &["cm", "centimeter", "centimeters"],
+        Dimension::Length,
+        0.01,
+    ),
+    (
+        &["m", "meter", "meters", "metre", "metres"],
+        Dimension::Length,
+        1.0,
+    ),
+    (
+        &["km", "kilometer", "kilometers"],
+        Dimension::Length,
+        1000.0,
+    ),
+    (&["in", "inch", "inches"], Dimension::Length, 0.0254),
+    (&["ft", "foot", "feet"], Dimension::Length, 0.3048),
+    (&["yd", "yard", "yards"], Dimension::Length, 0.9144),
+    (&["mi", "mile", "miles"], Dimension::Length, 1609.344),
+    (&["nmi"], Dimension::Length, 1852.0),
+    (&["mg", "milligram", "milligrams"], Dimension::Mass, 0.001),
+    (&["g", "gram", "grams"], Dimension::Mass, 1.0),
+    (&["kg", "kilogram", "kilograms"], Dimension::Mass, 1000.0),
+    (
+        &["t", "tonne", "tonnes", "ton", "tons"],
+        Dimension::Mass,
+        1_000_000.0,
+    ),
+    (&["oz", "ounce", "ounces"], Dimension::Mass, 28.349523125),
+    (
+        &["lb", "lbs", "pound", "pounds"],
+        Dimension::Mass,
+        453.59237,
+    ),
+    (
+        &["ml", "milliliter", "milliliters"],
+        Dimension::Volume,
+        0.001,
+    ),
+    (
+        &["l", "liter", "liters", "litre", "litres"],
+        Dimension::Volume,
+        1.0,
+    ),
+    (&["m3"], Dimension::Volume, 1000.0),
+    (
+        &["gal", "gallon", "gallons"],
+        Dimension::Volume,
+        3.785411784,
+    ),
+    (&["cm2"], Dimension::Area, 0.0001),
+    (&["m2", "sqm"], Dimension::Area, 1.0),
+    (&["ha", "hectare", "hectares"], Dimension::Area, 10_000.0),
+    (&["km2"], Dimension::Area, 1_000_000.0),
+    (&["ft2", "sqft"], Dimension::Area, 0.09290304),
+    (&["acre", "acres"], Dimension::Area, 4046.8564224),
+    (
+        &["ms", "millisecond", "milliseconds"],
+        Dimension::Time,
+        0.001,
+    ),
+    (&["s", "sec", "second", "seconds"], Dimension::Time, 1.0),
+    (&["min", "minute", "minutes"], Dimension::Time, 60.0),
+    (&["h", "hr", "hour", "hours"], Dimension::Time, 3600.0),
+    (&["day", "days"], Dimension::Time, 86_400.0),
+    (&["week", "weeks"], Dimension::Time, 604_800.0),
+    (&["b", "byte", "bytes"], Dimension::Data, 1.0),
+    (&["kb"], Dimension::Data, 1e3),
+    (&["mb"], Dimension::Data, 1e6),
+    (&["gb"], Dimension::Data, 1e9),
+    (&["tb"], Dimension::Data, 1e12),
+    (&["kib"], Dimension::Data, 1024.0),
+    (&["mib"], Dimension::Data, 1_048_576.0),
+    (&["gib"], Dimension::Data, 1_073_741_824.0),
+    (&["c", "celsius"], Dimension::Temperature, 0.0),
+    (&["f", "fahrenheit"], Dimension::Temperature, 0.0),
+    (&["k", "kelvin"], Dimension::Temperature, 0.0),
+];
+
+fn unit(name: &str) -> Option<(&'static str, Dimension, f64)> {
+    let name = name.to_ascii_lowercase();
+    UNITS
+        .iter()
+        .find(|(aliases, _, _)| aliases.contains(&name.as_str()))
+        .map(|(aliases, dim, size)| (aliases[0], *dim, *size))
+}
+
+/// Convert `value` between two units of the same dimension.
+fn convert(value: f64, from: &str, to: &str) -> std::result::Result<f64, String> {
+    let (from_name, from_dim, from_size) =
+        unit(from).ok_or_else(|| format!("Unknown unit: {from}"))?;
+    let (to_name, to_dim, to_size) = unit(to).ok_or_else(|| format!("Unknown unit: {to}"))?;
+    if from_dim != to_dim {
+        return Err(format!("Cannot convert {from_name} to {to_name}"));
+    }
+    if from_dim != Dimension::Temperature {
+        return Ok(value * from_size / to_size);
+    }
+    let kelvin = match from_name {
+        "c" => value + 273.15,
+        "f" => (value - 32.0) * 5.0 / 9.0 + 273.15,
+        _ => value,
+    };
+    Ok(match to_name {
+        "c" => kelvin - 273.15,
+        "f" => (kelvin - 273.15) * 9.0 / 5.0 + 32.0,
+        _ => kelvin,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+    Comma,
+}
+
+fn tokenize(input: &str) -> std::result::Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '0'..='9' | '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                // Exponent: 1e6, 2.5E-3
+                if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                let n = text
+                    .parse()
+                    .map_err(|_| format!("Invalid number: {text}"))?;
+                tokens.push(Token::Num(n));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            '+' | '-' | '*' | '/' | '^' | '%' => {
+                tokens.push(Token::Op(c));
+                i += 1;
+            }
+            '×' => {
+                tokens.push(Token::Op('*'));
+                i += 1;
+            }
+            '÷' => {
+                tokens.push(Token::Op('/'));
+                i += 1;
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            ',' | ';' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            other => return Err(format!("Unexpected character: {other}")),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat_op(&mut self, ops: &[char]) -> Option<char> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        match self.peek() {
+            Some(Token::Ident(id)) if id.eq_ignore_ascii_case(word) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// expr := term (('+' | '-') term)*
+    ///
+    /// Adding or subtracting a bare percentage is relative to the left
+    /// side: `200 - 15%` is 170.
+    fn expr(&mut self) -> std::result::Result<f64, String> {
+        let (mut acc, _) = self.term()?;
+        while let Some(op) = self.eat_op(&['+', '-']) {
+            let (rhs, percent) = self.term()?;
+            acc = match (op, percent) {
+                ('+', true) => acc * (1.0 + rhs),
+                ('-', true) => acc * (1.0 - rhs),
+                ('+', false) => acc + rhs,
+                _ => acc - rhs,
+            };
+        }
+        Ok(acc)
+    }
+
+    /// term := unary (('*' | '/' | 'mod' | 'of') unary)*
+    ///
+    /// Also reports whether the term is a single percentage.
+    fn term(&mut self) -> std::result::Result<(f64, bool), String> {
+        let (mut acc, mut percent) = self.unary()?;
+        loop {
+            let op = if let Some(op) = self.eat_op(&['*', '/']) {
+                op
+            } else if self.eat_word("mod") {
+                'm'
+            } else if self.eat_word("of") {
+                '*'
+            } else {
+                break;
+            };
+            let (rhs, _) = self.unary()?;
+            acc = match op {
+                '*' => acc * rhs,
+                '/' if rhs == 0.0 => return Err("Division by zero".into()),
+                '/' => acc / rhs,
+                _ if rhs == 0.0 => return Err("Division by zero".into()),
+                _ => acc % rhs,
+            };
+            percent = false;
+        }
+        Ok((acc, percent))
+    }
+
+    /// unary := ('-' | '+') unary | power
+    fn unary(&mut self) -> std::result::Result<(f64, bool), String> {
+        match self.eat_op(&['-', '+']) {
+            Some('-') => self.unary().map(|(v, p)| (-v, p)),
+            Some(_) => self.unary(),
+            None => self.power(),
+        }
+    }
+
+    /// power := postfix ('^' unary)?
+    fn power(&mut self) -> std::result::Result<(f64, bool), String> {
+        let (base, percent) = self.postfix()?;
+        if self.eat_op(&['^']).is_some() {
+            let (exp, _) = self.unary()?;
+            return Ok((base.powf(exp), false));
+        }
+        Ok((base, percent))
+    }
+
+    /// postfix := primary '%'?
+    fn postfix(&mut self) -> std::result::Result<(f64, bool), String> {
+        let value = self.primary()?;
+        if self.eat_op(&['%']).is_some() {
+            return Ok((value / 100.0, true));
+        }
+        Ok((value, false))
+    }
+
+    fn primary(&mut self) -> std::result::Result<f64, String> {
+        match self.advance() {
+            Some(Token::Num(n)) => Ok(n),
+            Some(Token::LParen) => {
+                let value = self.expr()?;
+                match self.advance() {
+                    Some(Token::RParen) => Ok(value),
+                    _ => Err("Missing ')'".into()),
+                }
+            }
+            Some(Token::Ident(name)) => {
+                let lower = name.to_ascii_lowercase();
+                if self.peek() == Some(&Token::LParen) {
+                    self.pos += 1;
+                    let mut args = vec![self.expr()?];
+                    while self.peek() == Some(&Token::Comma) {
+                        self.pos += 1;
+                        args.push(self.expr()?);
+                    }
+                    if self.advance() != Some(Token::RParen) {
+                        return Err(format!("Missing ')' after {name}("));
+                    }
+                    return call(&lower, &args);
+                }
+                match lower.as_str() {
+                    "pi" => Ok(std::f64::consts::PI),
+                    "e" => Ok(std::f64::consts::E),
+                    _ => Err(format!("Unknown name: {name}")),
+                }
+            }
+            Some(token) => Err(format!("Unexpected {token:?}")),
+            None => Err("Unexpected end of expression".into()),
+        }
+    }
+}
+
+fn call(name: &str, args: &[f64]) -> std::result::Result<f64, String> {
+    let one = |f: fn(f64) -> f64| match args {
+        [x] => Ok(f(*x)),
+        _ => Err(format!("{name}() takes 1 argument")),
+    };
+    match name {
+        "sqrt" => match args {
+            [x] if *x < 0.0 => Err("sqrt() of a negative number".into()),
+            _ => one(f64::sqrt),
+        },
+        "abs" => one(f64::abs),
+        "floor" => one(f64::floor),
+        "ceil" => one(f64::ceil),
+        "ln" => one(f64::ln),
+        "log10" | "log" => one(f64::log10),
+        "log2" => one(f64::log2),
+        "exp" => one(f64::exp),
+        "sin" => one(f64::sin),
+        "cos" => one(f64::cos),
+        "tan" => one(f64::tan),
+        "round" => match args {
+            [x] => Ok(x.round()),
+            [x, digits] => {
+                let scale = 10f64.powi(*digits as i32);
+                Ok((x * scale).round() / scale)
+            }
+            _ => Err("round() takes 1 or 2 arguments".into()),
+        },
+        "min" if !args.is_empty() => Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+        "max" if !args.is_empty() => Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+        _ => Err(format!("Unknown function: {name}()")),
+    }
+}
+
+/// Evaluate an arithmetic expression.
+pub fn evaluate(input: &str) -> std::result::Result<f64, String> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+    };
+    let value = parser.expr()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("Unexpected {token:?}"));
+    }
+    if !value.is_finite() {
+        return Err("Result is not a finite number".into());
+    }
+    Ok(value)
+}
+
+/// Evaluate `input`, converting units if it has the form
+/// `<expr> <unit> to|in <unit>`. Returns the formatted answer.
+pub fn calculate(input: &str) -> std::result::Result<String, String> {
+    let input = input.trim();
+    if let Some((lhs, target)) = split_conversion(input) {
+        let lhs = lhs.trim_end();
+        let split = lhs
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        let (expr, from) = lhs.split_at(split);
+        // A unit glued to a number ("5km") leaves digits on the unit side.
+        let digits = from.find(|c: char| !c.is_ascii_digit() && c != '.');
+        let (expr, from) = match digits {
+            Some(d) if d > 0 && expr.trim().is_empty() => (&from[..d], &from[d..]),
+            _ => (expr, from),
+        };
+        if unit(from).is_some() {
+            let value = evaluate(expr)?;
+            let converted = convert(value, from, target)?;
+            return Ok(format!(
+                "{} {from} = {} {target}",
+                format_number(value),
+                format_number(converted)
+            ));
+        }
+    }
+    evaluate(input).map(format_number)
+}
+
+/// Split at the last " to " / " in " whose right side is a known unit.
+fn split_conversion(input: &str) -> Option<(&str, &str)> {
+    let lower = input.to_ascii_lowercase();
+    [" to ", " in "]
+        .iter()
+        .filter_map(|sep| lower.rfind(sep).map(|i| (i, sep.len())))
+        .max_by_key(|(i, _)| *i)
+        .and_then(|(i, len)| {
+            let target = input[i + len..].trim();
+            unit(target).map(|_| (&input[..i], target))
+        })
+}
+
+/// 12 significant digits (at most 10 decimals), which hides float noise
+/// like `0.30000000000000004`, without trailing zeros.
+pub fn format_number(value: f64) -> String {
+    let int_digits = if value.abs() < 1.0 {
+        1
+    } else {
+        value.abs().log10().floor() as i32 + 1
+    };
+    let decimals = (12 - int_digits).clamp(0, 10) as usize;
+    let text = format!("{value:.decimals$}");
+    let text = if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &text
+    };
+    if text == "-0" {
+        "0".into()
+    } else {
+        text.to_string()
+    }
+}
+
+pub struct CalculatorTool;
+
+impl Default for CalculatorTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CalculatorTool {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for CalculatorTool {
+    fn name(&self) -> &str {
+        "calculator"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "calculator".into(),
+            description: "Compute an arithmetic expression exactly. Use it for every total, \
+                          percentage or unit conversion. Examples: \"(120000 * 3) - 15%\", \
+                          \"15% of 240\", \"sqrt(2)\", \"5 km to mi\", \"98.6 f to c\"."
+                .into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "expression": { "type": "string", "description": "Expression to evaluate" }
+                },
+                "required": ["expression"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<ToolResult> {
+        let args: serde_json::Value = serde_json::from_str(arguments)
+            .unwrap_or_else(|_| serde_json::json!({"expression": arguments}));
+        let expression = args["expression"].as_str().unwrap_or(arguments);
+
+        let (output, success) = match calculate(expression) {
+            Ok(answer) => (format!("{expression} = {answer}"), true),
+            Err(e) => (format!("Cannot evaluate \"{expression}\": {e}"), false),
+        };
+        Ok(ToolResult {
+            tool_call_id: String::new(),
+            output,
+            success,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(calculate("1 + 2 * 3").unwrap(), "7");
+        assert_eq!(calculate("(1 + 2) * 3").unwrap(), "9");
+        assert_eq!(calculate("-2^2").unwrap(), "-4");
+        assert_eq!(calculate("2^3^2").unwrap(), "512");
+        assert_eq!(calculate("0.1 + 0.2").unwrap(), "0.3");
+        assert_eq!(calculate("10 / 4").unwrap(), "2.5");
+        assert_eq!(calculate("17 mod 5").unwrap(), "2");
+        assert_eq!(calculate("1.5e3 × 2").unwrap(), "3000");
+        assert_eq!(calculate("round(pi, 2)").unwrap(), "3.14");
+        assert_eq!(calculate("max(3, 9, 4) + sqrt(16)").unwrap(), "13");
+    }
+
+    #[test]
+    fn test_percentages() {
+        assert_eq!(calculate("15% of 240").unwrap(), "36");
+        assert_eq!(calculate("1200000 - 15%").unwrap(), "1020000");
+        assert_eq!(calculate("80 + 10%").unwrap(), "88");
+        assert_eq!(calculate("200 * 5%").unwrap(), "10");
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(calculate("5 km to m").unwrap(), "5 km = 5000 m");
+        assert_eq!(calculate("5km in mi").unwrap(), "5 km = 3.1068559612 mi");
+        assert_eq!(
+            calculate("(1 + 1) kg to lb").unwrap(),
+            "2 kg = 4.4092452437 lb"
+        );
+        assert_eq!(calculate("212 F to C").unwrap(), "212 F = 100 C");
+        assert_eq!(calculate("90 min to h").unwrap(), "90 min = 1.5 h");
+        assert!(
+            calculate("5 kg to km")
+                .unwrap_err()
+                .contains("Cannot convert")
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(calculate("1 / 0").is_err());
+        assert!(calculate("2 +").is_err());
+        assert!(calculate("(1 + 2").is_err());
+        assert!(
+            calculate("system(1)")
+                .unwrap_err()
+                .contains("Unknown function")
+        );
+        assert!(calculate("sqrt(-1)").is_err());
+        assert!(calculate("1 2").is_err());
+    }
+}
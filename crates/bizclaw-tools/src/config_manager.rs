@@ -59,7 +59,7 @@ impl Tool for ConfigManagerTool {
             .as_str()
             .ok_or_else(|| bizclaw_core::error::BizClawError::Tool("Missing 'action'".into()))?;
 
-        let config_path = bizclaw_core::config::BizClawConfig::default_path();
+        let config_path = bizclaw_core::config::BizClawConfig::config_path();
 
         match action {
             "read" => {
@@ -134,9 +134,15 @@ impl Tool for ConfigManagerTool {
                     });
                 }
 
-                let mut config = bizclaw_core::config::BizClawConfig::load().map_err(|e| {
-                    bizclaw_core::error::BizClawError::Tool(format!("Load config: {e}"))
-                })?;
+                // Edit the file as written, so environment overrides aren't
+                // saved into it.
+                let mut config = if config_path.exists() {
+                    bizclaw_core::config::BizClawConfig::load_file(&config_path).map_err(|e| {
+                        bizclaw_core::error::BizClawError::Tool(format!("Load config: {e}"))
+                    })?
+                } else {
+                    bizclaw_core::config::BizClawConfig::default()
+                };
 
                 let mut json = serde_json::to_value(&config).map_err(|e| {
                     bizclaw_core::error::BizClawError::Tool(format!("Serialize: {e}"))
@@ -153,6 +159,9 @@ impl Tool for ConfigManagerTool {
                 config = serde_json::from_value(json).map_err(|e| {
                     bizclaw_core::error::BizClawError::Tool(format!("Invalid value: {e}"))
                 })?;
+                config.validate().map_err(|e| {
+                    bizclaw_core::error::BizClawError::Tool(format!("Invalid value: {e}"))
+                })?;
 
                 config.save().map_err(|e| {
                     bizclaw_core::error::BizClawError::Tool(format!("Save failed: {e}"))
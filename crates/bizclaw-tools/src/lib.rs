@@ -4,16 +4,20 @@
 //! ## Tool Registry
 //! | Tool | Description |
 //! |------|-------------|
-//! | shell | Execute shell commands |
+//! | shell | Run allowlisted commands inside the workspace |
 //! | file | Read/write/append files, list directories |
 //! | edit_file | Precise text replacements in files |
+//! | list_files / read_file / write_file | Files inside the workspace only |
 //! | glob | Find files matching patterns |
 //! | grep | Search file contents with regex |
-//! | web_search | DuckDuckGo search (no key needed) |
+//! | web_search | DuckDuckGo, SearxNG, Brave or Bing search |
 //! | http_request | Make HTTP requests to APIs |
+//! | web_fetch | Read a web page as plain text |
 //! | config_manager | Read/write config.toml at runtime |
 //! | memory_search | Search past conversation memory |
 //! | execute_code | Run code in 9 languages |
+//! | calculator | Exact arithmetic, percentages and unit conversion |
+//! | sql_query | Read-only SELECTs on `[[tools.sql.databases]]` |
 //! | plan | Structured task decomposition |
 //! | session_context | Session self-awareness for agent |
 //! | group_summarizer | Buffer + summarize group messages |
@@ -21,6 +25,7 @@
 //! | document_reader | Offline PDF/DOCX/XLSX/CSV reader |
 //! + MCP server tools (dynamic)
 
+pub mod calculator;
 pub mod calendar;
 pub mod config_manager;
 pub mod document_reader;
@@ -38,9 +43,16 @@ pub mod plan_store;
 pub mod registry;
 pub mod session_context;
 pub mod shell;
+pub mod sql_query;
+pub mod web_fetch;
 pub mod web_search;
+pub mod workspace;
 
 use bizclaw_core::traits::Tool;
+use bizclaw_core::types::{ToolCall, ToolResult};
+
+/// Longest tool output fed back to the model, in bytes.
+pub const MAX_TOOL_OUTPUT: usize = 4000;
 
 /// Tool registry — manages available tools.
 pub struct ToolRegistry {
@@ -80,12 +92,17 @@ impl ToolRegistry {
         reg.register(Box::new(edit_file::EditFileTool::new()));
         reg.register(Box::new(glob_find::GlobTool::new()));
         reg.register(Box::new(grep_search::GrepTool::new()));
+        reg.register(Box::new(workspace::ListFilesTool::default()));
+        reg.register(Box::new(workspace::ReadFileTool::default()));
+        reg.register(Box::new(workspace::WriteFileTool::default()));
         // Search & network tools
         reg.register(Box::new(web_search::WebSearchTool::new()));
         reg.register(Box::new(http_request::HttpRequestTool::new()));
+        reg.register(Box::new(web_fetch::WebFetchTool::new()));
         // Config & code tools
         reg.register(Box::new(config_manager::ConfigManagerTool::new()));
         reg.register(Box::new(execute_code::ExecuteCodeTool::new()));
+        reg.register(Box::new(calculator::CalculatorTool::new()));
         // Plan mode
         reg.register(Box::new(plan_tool::PlanTool::new(plan_store)));
         // Domain tools
@@ -99,6 +116,43 @@ impl ToolRegistry {
         reg
     }
 
+    /// Default tools, set up from `[tools]`.
+    pub fn with_config(config: &bizclaw_core::config::ToolsConfig) -> Self {
+        let mut reg = Self::with_defaults();
+        reg.replace(Box::new(web_search::WebSearchTool::from_config(
+            &config.web_search,
+        )));
+        reg.replace(Box::new(web_fetch::WebFetchTool::from_config(
+            &config.web_fetch,
+        )));
+        reg.replace(Box::new(shell::ShellTool::from_config(
+            &config.shell,
+            config.workspace_dir(),
+        )));
+        let files = workspace::Workspace::new(config.workspace_dir());
+        reg.replace(Box::new(workspace::ListFilesTool::new(files.clone())));
+        reg.replace(Box::new(workspace::ReadFileTool::new(
+            files.clone(),
+            &config.files,
+        )));
+        reg.replace(Box::new(workspace::WriteFileTool::new(
+            files,
+            &config.files,
+        )));
+        if !config.sql.databases.is_empty() {
+            reg.replace(Box::new(sql_query::SqlQueryTool::from_config(&config.sql)));
+        }
+        reg
+    }
+
+    /// Register `tool`, replacing any tool with the same name.
+    pub fn replace(&mut self, tool: Box<dyn Tool>) {
+        match self.tools.iter_mut().find(|t| t.name() == tool.name()) {
+            Some(slot) => *slot = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
     /// Register the memory_search tool with a shared memory backend.
     pub fn register_memory_search(
         &mut self,
@@ -122,6 +176,49 @@ impl ToolRegistry {
         }
     }
 
+    /// Keep only the tools whose name passes `keep`.
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        self.tools.retain(|t| keep(t.name()));
+    }
+
+    /// Run a model-issued tool call.
+    ///
+    /// Unknown tools, malformed arguments and tool errors come back as
+    /// unsuccessful results the model can read and recover from, not as
+    /// `Err`. Output is capped at [`MAX_TOOL_OUTPUT`].
+    pub async fn execute(&self, call: &ToolCall) -> ToolResult {
+        let failed = |output: String| ToolResult {
+            tool_call_id: call.id.clone(),
+            output,
+            success: false,
+        };
+        let Some(tool) = self.get(&call.function.name) else {
+            return failed(format!("Unknown tool: {}", call.function.name));
+        };
+
+        let arguments = if call.function.arguments.trim().is_empty() {
+            "{}"
+        } else {
+            call.function.arguments.as_str()
+        };
+        let args: serde_json::Value = match serde_json::from_str(arguments) {
+            Ok(args) => args,
+            Err(e) => return failed(format!("Invalid arguments: {e}")),
+        };
+        if let Err(e) = registry::validate_args(&tool.definition(), &args) {
+            return failed(e);
+        }
+
+        match tool.execute(arguments).await {
+            Ok(mut result) => {
+                result.tool_call_id = call.id.clone();
+                result.output = registry::truncate_output(&result.output, MAX_TOOL_OUTPUT);
+                result
+            }
+            Err(e) => failed(format!("Error: {e}")),
+        }
+    }
+
     /// Get the count of registered tools.
     pub fn count(&self) -> usize {
         self.tools.len()
@@ -152,10 +249,15 @@ mod tests {
         assert!(reg.get("edit_file").is_some());
         assert!(reg.get("glob").is_some());
         assert!(reg.get("grep").is_some());
+        assert!(reg.get("list_files").is_some());
+        assert!(reg.get("read_file").is_some());
+        assert!(reg.get("write_file").is_some());
         assert!(reg.get("web_search").is_some());
         assert!(reg.get("http_request").is_some());
+        assert!(reg.get("web_fetch").is_some());
         assert!(reg.get("config_manager").is_some());
         assert!(reg.get("execute_code").is_some());
+        assert!(reg.get("calculator").is_some());
         assert!(reg.get("plan").is_some());
         assert!(reg.get("group_summarizer").is_some());
         assert!(reg.get("calendar").is_some());
@@ -197,4 +299,39 @@ mod tests {
         let reg = ToolRegistry::with_defaults();
         assert_eq!(reg.count(), reg.list().len());
     }
+
+    #[test]
+    fn test_retain() {
+        let mut reg = ToolRegistry::with_defaults();
+        reg.retain(|name| name == "plan" || name == "calendar");
+        assert_eq!(reg.tool_names(), ["plan", "calendar"]);
+    }
+
+    #[tokio::test]
+    async fn test_execute_reports_failures_as_results() {
+        use bizclaw_core::types::FunctionCall;
+
+        let reg = ToolRegistry::with_defaults();
+        let call = |name: &str, arguments: &str| ToolCall {
+            id: "call_1".into(),
+            r#type: "function".into(),
+            function: FunctionCall {
+                name: name.into(),
+                arguments: arguments.into(),
+            },
+        };
+
+        let result = reg.execute(&call("nope", "{}")).await;
+        assert!(!result.success);
+        assert_eq!(result.tool_call_id, "call_1");
+        assert!(result.output.contains("Unknown tool"));
+
+        let result = reg.execute(&call("shell", "not json")).await;
+        assert!(!result.success);
+        assert!(result.output.starts_with("Invalid arguments"));
+
+        let result = reg.execute(&call("shell", "{}")).await;
+        assert!(!result.success);
+        assert!(result.output.contains("Missing required argument: command"));
+    }
 }
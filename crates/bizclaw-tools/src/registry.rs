@@ -27,6 +27,18 @@ pub fn validate_args(definition: &ToolDefinition, args: &serde_json::Value) -> R
     Ok(())
 }
 
+/// Cut `output` to at most `max` bytes on a char boundary, marking the cut.
+pub fn truncate_output(output: &str, max: usize) -> String {
+    if output.len() <= max {
+        return output.to_string();
+    }
+    let mut end = max;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...[truncated]", &output[..end])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -62,4 +74,10 @@ mod tests {
         };
         assert!(validate_args(&def, &serde_json::json!({})).is_ok());
     }
+
+    #[test]
+    fn test_truncate_output() {
+        assert_eq!(truncate_output("short", 10), "short");
+        assert_eq!(truncate_output("xin chào", 7), "xin ch...[truncated]");
+    }
 }
@@ -1,15 +1,109 @@
-//! Shell command execution tool.
+//! Shell command execution tool — sandboxed.
+//!
+//! Commands are not passed to `sh`. The command line is split into words
+//! (quotes are honoured, pipes, redirects, `;`, `&&` and `$()` are refused)
+//! and the program is run directly, so one allowlisted command can't smuggle
+//! in another. On top of that:
+//!
+//! - the words must start with an entry of the allowlist
+//!   (`"systemctl status"` allows `systemctl status nginx` only);
+//! - programs are looked up on `PATH`, never by path, so a script dropped
+//!   in the workspace can't pose as `df`;
+//! - the working directory is confined to the workspace, and so is every
+//!   argument read as a path from there (`ls /etc`, `du ..`, an option's
+//!   `=/path` value), symlinks followed;
+//! - the environment is reduced to `PATH`, `HOME` and `LANG`;
+//! - the process is killed at the timeout and its output truncated.
 
+use crate::registry::truncate_output;
+use crate::workspace::Workspace;
 use async_trait::async_trait;
-use bizclaw_core::error::Result;
+use bizclaw_core::config::ShellToolConfig;
+use bizclaw_core::error::{BizClawError, Result};
 use bizclaw_core::traits::Tool;
 use bizclaw_core::types::{ToolDefinition, ToolResult};
+use std::path::{Path, PathBuf};
 
-pub struct ShellTool;
+/// Characters that only mean something to a shell.
+const SHELL_METACHARACTERS: &[char] =
+    &[';', '|', '&', '`', '$', '(', ')', '{', '}', '>', '<', '\n'];
+
+pub struct ShellTool {
+    allowed: Vec<Vec<String>>,
+    workspace: Workspace,
+    timeout: std::time::Duration,
+    max_output: usize,
+}
 
 impl ShellTool {
+    /// Default allowlist, confined to `~/.bizclaw/workspace`.
     pub fn new() -> Self {
-        Self
+        Self::from_config(&ShellToolConfig::default(), Workspace::default_root())
+    }
+
+    pub fn from_config(config: &ShellToolConfig, workspace: impl Into<PathBuf>) -> Self {
+        Self {
+            allowed: config
+                .allowed_commands
+                .iter()
+                .map(|c| c.split_whitespace().map(String::from).collect::<Vec<_>>())
+                .filter(|words| !words.is_empty())
+                .collect(),
+            workspace: Workspace::new(workspace),
+            timeout: std::time::Duration::from_secs(config.timeout_secs.max(1)),
+            max_output: config.max_output_bytes.max(256),
+        }
+    }
+
+    /// Whether `words` starts with an allowlisted command.
+    fn is_allowed(&self, words: &[String]) -> bool {
+        self.allowed
+            .iter()
+            .any(|entry| words.len() >= entry.len() && words[..entry.len()] == entry[..])
+    }
+
+    /// `workdir` resolved inside the workspace, the workspace itself if unset.
+    fn resolve_workdir(&self, workdir: Option<&str>) -> std::result::Result<PathBuf, String> {
+        let workdir = workdir.filter(|w| !w.trim().is_empty()).unwrap_or(".");
+        match self.workspace.resolve(workdir) {
+            Ok(dir) if dir.is_dir() => Ok(dir),
+            Ok(_) => Err(format!("Working directory {workdir} is not a folder")),
+            Err(BizClawError::Tool(e)) => Err(e),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Refuse arguments that, taken as paths from `workdir`, leave the
+    /// workspace. Words that aren't meant as paths (`nginx`, `-h`) resolve
+    /// inside it and pass; an option's value after `=` or its first `/` is
+    /// checked on its own.
+    fn check_args(&self, args: &[String], workdir: &Path) -> std::result::Result<(), String> {
+        for arg in args {
+            let path = match arg.strip_prefix('-') {
+                Some(option) => match option.split_once('=') {
+                    Some((_, value)) => value,
+                    None => option.find('/').map_or("", |i| &option[i..]),
+                },
+                None => arg.as_str(),
+            };
+            if path.is_empty() {
+                continue;
+            }
+            match self.workspace.resolve_from(workdir, path) {
+                Ok(_) => {}
+                Err(BizClawError::Tool(e)) => return Err(e),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Ok(())
+    }
+
+    fn refused(reason: String) -> ToolResult {
+        ToolResult {
+            tool_call_id: String::new(),
+            output: format!("Refused: {reason}"),
+            success: false,
+        }
     }
 }
 
@@ -19,6 +113,48 @@ impl Default for ShellTool {
     }
 }
 
+/// Split a command line into words. Single and double quotes group words;
+/// shell metacharacters outside quotes are an error.
+pub fn split_command(command: &str) -> std::result::Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+
+    for c in command.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.push(c),
+            None => match c {
+                '\'' | '"' => {
+                    quote = Some(c);
+                    in_word = true;
+                }
+                c if SHELL_METACHARACTERS.contains(&c) => {
+                    return Err(format!("'{c}' is not supported; run one command at a time"));
+                }
+                c if c.is_whitespace() => {
+                    if in_word {
+                        words.push(std::mem::take(&mut word));
+                        in_word = false;
+                    }
+                }
+                c => {
+                    word.push(c);
+                    in_word = true;
+                }
+            },
+        }
+    }
+    if quote.is_some() {
+        return Err("Unterminated quote".into());
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
 #[async_trait]
 impl Tool for ShellTool {
     fn name(&self) -> &str {
@@ -26,19 +162,23 @@ impl Tool for ShellTool {
     }
 
     fn definition(&self) -> ToolDefinition {
+        let allowed: Vec<String> = self.allowed.iter().map(|words| words.join(" ")).collect();
         ToolDefinition {
             name: "shell".into(),
-            description: "Execute a shell command and return stdout/stderr.".into(),
+            description: format!(
+                "Run one command (no pipes or redirects) and return its output. Allowed: {}.",
+                allowed.join(", ")
+            ),
             parameters: serde_json::json!({
                 "type": "object",
                 "properties": {
                     "command": {
                         "type": "string",
-                        "description": "The shell command to execute"
+                        "description": "The command to run, e.g. \"df -h\""
                     },
                     "workdir": {
                         "type": "string",
-                        "description": "Working directory (optional)"
+                        "description": "Working directory inside the workspace (optional)"
                     }
                 },
                 "required": ["command"]
@@ -47,25 +187,61 @@ impl Tool for ShellTool {
     }
 
     async fn execute(&self, arguments: &str) -> Result<ToolResult> {
-        let args: serde_json::Value = serde_json::from_str(arguments)
-            .map_err(|e| bizclaw_core::error::BizClawError::Tool(e.to_string()))?;
+        let args: serde_json::Value =
+            serde_json::from_str(arguments).map_err(|e| BizClawError::Tool(e.to_string()))?;
 
         let command = args["command"]
             .as_str()
-            .ok_or_else(|| bizclaw_core::error::BizClawError::Tool("Missing 'command'".into()))?;
+            .ok_or_else(|| BizClawError::Tool("Missing 'command'".into()))?;
 
-        let workdir = args["workdir"].as_str();
-
-        let mut cmd = tokio::process::Command::new("sh");
-        cmd.arg("-c").arg(command);
-        if let Some(dir) = workdir {
-            cmd.current_dir(dir);
+        let words = match split_command(command) {
+            Ok(words) if words.is_empty() => return Ok(Self::refused("empty command".into())),
+            Ok(words) => words,
+            Err(e) => return Ok(Self::refused(e)),
+        };
+        if words[0].contains('/') {
+            return Ok(Self::refused(format!(
+                "run programs by name, not path ({})",
+                words[0]
+            )));
+        }
+        if !self.is_allowed(&words) {
+            tracing::warn!("[security] Shell command not allowlisted: {command}");
+            return Ok(Self::refused(format!(
+                "'{command}' is not on the allowlist"
+            )));
+        }
+        let workdir = match self.resolve_workdir(args["workdir"].as_str()) {
+            Ok(dir) => dir,
+            Err(e) => return Ok(Self::refused(e)),
+        };
+        if let Err(e) = self.check_args(&words[1..], &workdir) {
+            tracing::warn!("[security] Shell command reaches outside the workspace: {command}");
+            return Ok(Self::refused(e));
         }
 
-        let output = cmd
-            .output()
-            .await
-            .map_err(|e| bizclaw_core::error::BizClawError::Tool(e.to_string()))?;
+        let mut cmd = tokio::process::Command::new(&words[0]);
+        cmd.args(&words[1..])
+            .current_dir(&workdir)
+            .env_clear()
+            .envs(
+                ["PATH", "HOME", "LANG"]
+                    .iter()
+                    .filter_map(|k| std::env::var(k).ok().map(|v| (*k, v))),
+            )
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true);
+
+        let output = match tokio::time::timeout(self.timeout, cmd.output()).await {
+            Ok(output) => output.map_err(|e| BizClawError::Tool(format!("{}: {e}", words[0])))?,
+            Err(_) => {
+                return Ok(ToolResult {
+                    tool_call_id: String::new(),
+                    output: format!("Timed out after {}s: {command}", self.timeout.as_secs()),
+                    success: false,
+                });
+            }
+        };
 
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
@@ -81,8 +257,124 @@ impl Tool for ShellTool {
 
         Ok(ToolResult {
             tool_call_id: String::new(),
-            output: result,
+            output: truncate_output(&result, self.max_output),
             success: output.status.success(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace() -> PathBuf {
+        std::env::temp_dir().join(format!("bizclaw-shell-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn tool(root: &Path, allowed: &[&str], timeout_secs: u64) -> ShellTool {
+        ShellTool::from_config(
+            &ShellToolConfig {
+                allowed_commands: allowed.iter().map(|s| s.to_string()).collect(),
+                timeout_secs,
+                max_output_bytes: 256,
+            },
+            root,
+        )
+    }
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(split_command("df -h").unwrap(), ["df", "-h"]);
+        assert_eq!(
+            split_command(r#"grep "two words" 'a;b'"#).unwrap(),
+            ["grep", "two words", "a;b"]
+        );
+        assert_eq!(split_command("echo ''").unwrap(), ["echo", ""]);
+        assert!(split_command("ls; rm -rf /").is_err());
+        assert!(split_command("cat x | sh").is_err());
+        assert!(split_command("echo $(id)").is_err());
+        assert!(split_command("echo 'open").is_err());
+    }
+
+    #[test]
+    fn test_allowlist_prefixes() {
+        let shell = tool(&workspace(), &["df", "systemctl status"], 5);
+        let words = |c: &str| split_command(c).unwrap();
+        assert!(shell.is_allowed(&words("df -h")));
+        assert!(shell.is_allowed(&words("systemctl status nginx")));
+        assert!(!shell.is_allowed(&words("systemctl stop nginx")));
+        assert!(!shell.is_allowed(&words("rm -rf /")));
+    }
+
+    #[tokio::test]
+    async fn test_execute_in_sandbox() {
+        let root = workspace();
+        std::fs::create_dir_all(root.join("logs")).unwrap();
+        let shell = tool(&root, &["pwd", "sleep", "echo"], 1);
+
+        let result = shell.execute(r#"{"command": "pwd"}"#).await.unwrap();
+        assert!(result.success);
+        assert_eq!(
+            Path::new(result.output.trim()),
+            root.canonicalize().unwrap()
+        );
+
+        let result = shell
+            .execute(r#"{"command": "pwd", "workdir": "logs"}"#)
+            .await
+            .unwrap();
+        assert!(result.output.trim().ends_with("logs"));
+
+        let result = shell
+            .execute(r#"{"command": "pwd", "workdir": "../.."}"#)
+            .await
+            .unwrap();
+        assert!(result.output.contains("outside the workspace"));
+
+        let result = shell.execute(r#"{"command": "id"}"#).await.unwrap();
+        assert!(result.output.contains("not on the allowlist"));
+
+        let result = shell
+            .execute(r#"{"command": "/bin/echo hi"}"#)
+            .await
+            .unwrap();
+        assert!(result.output.contains("not path"));
+
+        let result = shell.execute(r#"{"command": "sleep 5"}"#).await.unwrap();
+        assert!(!result.success);
+        assert!(result.output.starts_with("Timed out"));
+
+        let outside = [
+            "echo /etc",
+            "echo ..",
+            "echo logs/../../x",
+            "echo --file=/etc/passwd",
+            "echo -f/etc/passwd",
+        ];
+        for command in outside {
+            let args = serde_json::json!({ "command": command }).to_string();
+            let result = shell.execute(&args).await.unwrap();
+            assert!(result.output.contains("outside the workspace"), "{command}");
+        }
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/etc", root.join("etc")).unwrap();
+            let result = shell
+                .execute(r#"{"command": "echo etc/passwd"}"#)
+                .await
+                .unwrap();
+            assert!(result.output.contains("outside the workspace"));
+        }
+        let result = shell
+            .execute(r#"{"command": "echo -n logs/../logs", "workdir": "logs"}"#)
+            .await
+            .unwrap();
+        assert!(result.success);
+
+        let long = format!(r#"{{"command": "echo {}"}}"#, "x".repeat(1000));
+        let result = shell.execute(&long).await.unwrap();
+        assert!(result.output.ends_with("[truncated]"));
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
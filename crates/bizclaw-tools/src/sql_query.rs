@@ -0,0 +1,509 @@
+//! SQL query tool — read-only queries against databases listed in
+//! `[[tools.sql.databases]]`.
+//!
+//! Only a single `SELECT`/`WITH` statement is accepted, and the database
+//! enforces it too: SQLite files are opened read-only and statements that
+//! would write are refused, PostgreSQL queries run in a `READ ONLY`
+//! transaction that is rolled back. Queries are cancelled at the timeout and
+//! at most `max_rows` rows are returned.
+//!
+//! PostgreSQL needs the `postgres` feature.
+
+use async_trait::async_trait;
+use bizclaw_core::config::{SqlDatabaseConfig, SqlToolConfig};
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::Tool;
+use bizclaw_core::types::{ToolDefinition, ToolResult};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Longest value shown in a cell.
+const MAX_CELL_CHARS: usize = 200;
+
+/// Where a configured database lives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite(PathBuf),
+    Postgres(String),
+}
+
+impl Backend {
+    /// `sqlite://path`, `sqlite:path`, a path ending in `.db`/`.sqlite`,
+    /// or `postgres://…` / `postgresql://…`.
+    pub fn from_url(url: &str) -> std::result::Result<Self, String> {
+        let url = url.trim();
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            return Ok(Self::Postgres(url.to_string()));
+        }
+        let path = url
+            .strip_prefix("sqlite://")
+            .or_else(|| url.strip_prefix("sqlite:"))
+            .or_else(|| {
+                [".db", ".sqlite", ".sqlite3"]
+                    .iter()
+                    .any(|ext| url.ends_with(ext))
+                    .then_some(url)
+            })
+            .ok_or_else(|| format!("Unsupported database URL: {url}"))?;
+        Ok(Self::Sqlite(PathBuf::from(
+            shellexpand::tilde(path).as_ref(),
+        )))
+    }
+}
+
+/// Rows of a query result, as display strings.
+#[derive(Debug, Default)]
+pub struct QueryRows {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// More rows were available than returned.
+    pub truncated: bool,
+}
+
+/// Accept one read-only statement; returns it without a trailing `;`.
+///
+/// This is a first filter for a clear error message; the database itself
+/// is what keeps queries read-only.
+pub fn check_query(sql: &str) -> std::result::Result<&str, String> {
+    let sql = sql.trim().trim_end_matches(';').trim_end();
+
+    // Delimiters are ASCII, so scanning bytes is safe in UTF-8 text.
+    let bytes = sql.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += 1;
+                }
+            }
+            b'-' if bytes[i..].starts_with(b"--") => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes[i..].starts_with(b"/*") => {
+                i += 2;
+                while i < bytes.len() && !bytes[i..].starts_with(b"*/") {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b';' => return Err("Only one statement per query".into()),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let first = sql
+        .trim_start_matches(|c: char| c == '(' || c.is_whitespace())
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match first.as_str() {
+        "select" | "with" => Ok(sql),
+        "" => Err("Empty query".into()),
+        other => Err(format!(
+            "Only SELECT queries are allowed, not {}",
+            other.to_uppercase()
+        )),
+    }
+}
+
+fn cell(value: String) -> String {
+    let value = value.replace(['\n', '\r'], " ");
+    if value.chars().count() > MAX_CELL_CHARS {
+        let cut: String = value.chars().take(MAX_CELL_CHARS).collect();
+        format!("{cut}…")
+    } else {
+        value
+    }
+}
+
+/// A pipe-separated table with a row count footer.
+pub fn format_rows(result: &QueryRows) -> String {
+    if result.columns.is_empty() || result.rows.is_empty() {
+        return "No rows.".into();
+    }
+    let mut out = result.columns.join(" | ");
+    out.push('\n');
+    for row in &result.rows {
+        out.push_str(&row.join(" | "));
+        out.push('\n');
+    }
+    let n = result.rows.len();
+    if result.truncated {
+        out.push_str(&format!(
+            "(first {n} rows shown; aggregate or add a LIMIT/WHERE for the rest)"
+        ));
+    } else {
+        out.push_str(&format!("({n} row{})", if n == 1 { "" } else { "s" }));
+    }
+    out
+}
+
+fn query_sqlite_blocking(
+    conn: &rusqlite::Connection,
+    sql: &str,
+    max_rows: usize,
+) -> std::result::Result<QueryRows, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    if !stmt.readonly() {
+        return Err("Only read-only queries are allowed".into());
+    }
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let width = columns.len();
+
+    let mut result = QueryRows {
+        columns,
+        ..Default::default()
+    };
+    let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        if result.rows.len() == max_rows {
+            result.truncated = true;
+            break;
+        }
+        let mut values = Vec::with_capacity(width);
+        for i in 0..width {
+            use rusqlite::types::ValueRef;
+            let value = match row.get_ref(i).map_err(|e| e.to_string())? {
+                ValueRef::Null => "NULL".to_string(),
+                ValueRef::Integer(n) => n.to_string(),
+                ValueRef::Real(f) => f.to_string(),
+                ValueRef::Text(t) => String::from_utf8_lossy(t).to_string(),
+                ValueRef::Blob(b) => format!("<{} bytes>", b.len()),
+            };
+            values.push(cell(value));
+        }
+        result.rows.push(values);
+    }
+    Ok(result)
+}
+
+/// Run `sql` on a read-only connection, interrupting it at `timeout`.
+pub async fn query_sqlite(
+    path: PathBuf,
+    sql: String,
+    max_rows: usize,
+    timeout: Duration,
+) -> Result<QueryRows> {
+    use rusqlite::OpenFlags;
+    let conn = rusqlite::Connection::open_with_flags(
+        &path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(|e| BizClawError::Tool(format!("Cannot open {}: {e}", path.display())))?;
+    let interrupt = conn.get_interrupt_handle();
+
+    let task = tokio::task::spawn_blocking(move || query_sqlite_blocking(&conn, &sql, max_rows));
+    match tokio::time::timeout(timeout, task).await {
+        Ok(Ok(result)) => result.map_err(|e| BizClawError::Tool(format!("Query failed: {e}"))),
+        Ok(Err(e)) => Err(BizClawError::Tool(format!("Query failed: {e}"))),
+        Err(_) => {
+            interrupt.interrupt();
+            Err(BizClawError::Tool(format!(
+                "Query cancelled after {}s",
+                timeout.as_secs()
+            )))
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use super::{QueryRows, cell};
+    use bizclaw_core::error::{BizClawError, Result};
+    use sqlx::{Connection, Row};
+    use std::time::Duration;
+
+    /// One row as produced by the query in [`query`]:
+    /// `[["column", value], ...]` in column order.
+    pub(super) fn parse_row(json: &str) -> Result<Vec<(String, String)>> {
+        let pairs: Vec<(String, serde_json::Value)> = serde_json::from_str(json)?;
+        Ok(pairs
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    serde_json::Value::Null => "NULL".to_string(),
+                    serde_json::Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                (name, cell(value))
+            })
+            .collect())
+    }
+
+    /// Run `sql` in a read-only transaction with `statement_timeout` set.
+    /// Each row is turned into JSON by the server so any column type can
+    /// be shown without a decoder for it.
+    pub(super) async fn query(
+        url: &str,
+        sql: &str,
+        max_rows: usize,
+        timeout: Duration,
+    ) -> Result<QueryRows> {
+        let failed = |e: sqlx::Error| BizClawError::Tool(format!("Query failed: {e}"));
+        let mut conn = sqlx::PgConnection::connect(url)
+            .await
+            .map_err(|e| BizClawError::Tool(format!("Cannot connect: {e}")))?;
+        let mut tx = conn.begin().await.map_err(failed)?;
+        sqlx::query("SET TRANSACTION READ ONLY")
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+        sqlx::query(&format!(
+            "SET LOCAL statement_timeout = {}",
+            timeout.as_millis()
+        ))
+        .execute(&mut *tx)
+        .await
+        .map_err(failed)?;
+
+        let wrapped = format!(
+            "SELECT (SELECT json_agg(json_build_array(key, value)) \
+             FROM json_each(row_to_json(q)))::text \
+             FROM ({sql}) AS q LIMIT {}",
+            max_rows + 1
+        );
+        let rows = sqlx::query(&wrapped)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(failed)?;
+        tx.rollback().await.ok();
+
+        let mut result = QueryRows::default();
+        for row in rows {
+            if result.rows.len() == max_rows {
+                result.truncated = true;
+                break;
+            }
+            let json: Option<String> = row.try_get(0).map_err(failed)?;
+            let pairs = parse_row(json.as_deref().unwrap_or("[]"))?;
+            if result.columns.is_empty() {
+                result.columns = pairs.iter().map(|(name, _)| name.clone()).collect();
+            }
+            result
+                .rows
+                .push(pairs.into_iter().map(|(_, v)| v).collect());
+        }
+        Ok(result)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_parse_row_keeps_column_order() {
+            let row =
+                parse_row(r#"[["total", 12.5], ["day", "2026-10-15"], ["note", null]]"#).unwrap();
+            assert_eq!(row[0], ("total".into(), "12.5".into()));
+            assert_eq!(row[1], ("day".into(), "2026-10-15".into()));
+            assert_eq!(row[2], ("note".into(), "NULL".into()));
+        }
+    }
+}
+
+pub struct SqlQueryTool {
+    databases: Vec<SqlDatabaseConfig>,
+    timeout: Duration,
+    max_rows: usize,
+}
+
+impl SqlQueryTool {
+    pub fn from_config(config: &SqlToolConfig) -> Self {
+        Self {
+            databases: config.databases.clone(),
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+            max_rows: config.max_rows.clamp(1, 500),
+        }
+    }
+
+    fn database(&self, name: Option<&str>) -> Result<&SqlDatabaseConfig> {
+        match name {
+            Some(name) => self.databases.iter().find(|db| db.name == name),
+            None if self.databases.len() == 1 => self.databases.first(),
+            None => None,
+        }
+        .ok_or_else(|| {
+            let names: Vec<&str> = self.databases.iter().map(|db| db.name.as_str()).collect();
+            BizClawError::Tool(format!(
+                "Unknown database {}; configured: {}",
+                name.unwrap_or("(none given)"),
+                names.join(", ")
+            ))
+        })
+    }
+}
+
+#[async_trait]
+impl Tool for SqlQueryTool {
+    fn name(&self) -> &str {
+        "sql_query"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        let mut description = String::from(
+            "Run a read-only SQL SELECT query and return the rows. Aggregate \
+             (COUNT, SUM, GROUP BY) instead of fetching many rows. Databases:",
+        );
+        for db in &self.databases {
+            description.push_str(&format!("\n- {}", db.name));
+            if !db.description.is_empty() {
+                description.push_str(&format!(": {}", db.description));
+            }
+        }
+        let names: Vec<&str> = self.databases.iter().map(|db| db.name.as_str()).collect();
+        ToolDefinition {
+            name: "sql_query".into(),
+            description,
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "database": {
+                        "type": "string",
+                        "enum": names,
+                        "description": "Database to query (optional when only one is configured)"
+                    },
+                    "query": { "type": "string", "description": "One SELECT statement" }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<ToolResult> {
+        let args: serde_json::Value =
+            serde_json::from_str(arguments).map_err(|e| BizClawError::Tool(e.to_string()))?;
+        let query = args["query"]
+            .as_str()
+            .ok_or_else(|| BizClawError::Tool("Missing 'query'".into()))?;
+        let db = self.database(args["database"].as_str())?;
+        let sql = check_query(query).map_err(BizClawError::Tool)?;
+        let backend = Backend::from_url(&db.url).map_err(BizClawError::Tool)?;
+
+        tracing::info!("sql_query on {}: {sql}", db.name);
+        let result = match backend {
+            Backend::Sqlite(path) => {
+                query_sqlite(path, sql.to_string(), self.max_rows, self.timeout).await?
+            }
+            #[cfg(feature = "postgres")]
+            Backend::Postgres(url) => tokio::time::timeout(
+                self.timeout + Duration::from_secs(5),
+                postgres::query(&url, sql, self.max_rows, self.timeout),
+            )
+            .await
+            .map_err(|_| {
+                BizClawError::Tool(format!("Query cancelled after {}s", self.timeout.as_secs()))
+            })??,
+            #[cfg(not(feature = "postgres"))]
+            Backend::Postgres(_) => {
+                return Err(BizClawError::Tool(
+                    "PostgreSQL support is not built in (enable the `postgres` feature)".into(),
+                ));
+            }
+        };
+
+        Ok(ToolResult {
+            tool_call_id: String::new(),
+            output: format_rows(&result),
+            success: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_from_url() {
+        assert_eq!(
+            Backend::from_url("sqlite:///var/shop.db").unwrap(),
+            Backend::Sqlite("/var/shop.db".into())
+        );
+        assert_eq!(
+            Backend::from_url("data/shop.sqlite").unwrap(),
+            Backend::Sqlite("data/shop.sqlite".into())
+        );
+        assert!(matches!(
+            Backend::from_url("postgres://u:p@localhost/shop").unwrap(),
+            Backend::Postgres(_)
+        ));
+        assert!(Backend::from_url("mysql://localhost/shop").is_err());
+    }
+
+    #[test]
+    fn test_check_query() {
+        assert_eq!(
+            check_query("SELECT count(*) FROM orders;\n").unwrap(),
+            "SELECT count(*) FROM orders"
+        );
+        assert!(check_query("with t as (select 1) select * from t").is_ok());
+        assert!(check_query("select 'a;b' -- trailing; comment").is_ok());
+        assert!(check_query("SELECT 1; DROP TABLE orders").is_err());
+        assert!(check_query("DELETE FROM orders").is_err());
+        assert!(check_query("  ").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_query() {
+        let path = std::env::temp_dir().join(format!("bizclaw-sql-{}.db", uuid::Uuid::new_v4()));
+        {
+            let conn = rusqlite::Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE orders (id INTEGER, day TEXT, total REAL, note TEXT);
+                 INSERT INTO orders VALUES (1, '2026-10-15', 120000.5, NULL),
+                                           (2, '2026-10-15', 80000, 'giao nhanh'),
+                                           (3, '2026-10-16', 5000, NULL);",
+            )
+            .unwrap();
+        }
+        let tool = SqlQueryTool::from_config(&SqlToolConfig {
+            databases: vec![SqlDatabaseConfig {
+                name: "shop".into(),
+                url: format!("sqlite://{}", path.display()),
+                description: "orders".into(),
+            }],
+            timeout_secs: 5,
+            max_rows: 2,
+        });
+
+        let result = tool
+            .execute(r#"{"query": "SELECT day, count(*) AS n FROM orders GROUP BY day"}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            result.output,
+            "day | n\n2026-10-15 | 2\n2026-10-16 | 1\n(2 rows)"
+        );
+
+        let result = tool
+            .execute(r#"{"query": "SELECT id, note FROM orders ORDER BY id"}"#)
+            .await
+            .unwrap();
+        assert!(
+            result
+                .output
+                .starts_with("id | note\n1 | NULL\n2 | giao nhanh\n")
+        );
+        assert!(result.output.contains("first 2 rows shown"));
+
+        // Writes are refused even when disguised as a CTE.
+        assert!(
+            tool.execute(r#"{"query": "WITH x AS (SELECT 1) DELETE FROM orders"}"#)
+                .await
+                .is_err()
+        );
+        assert!(
+            tool.execute(r#"{"database": "other", "query": "SELECT 1"}"#)
+                .await
+                .is_err()
+        );
+
+        std::fs::remove_file(&path).ok();
+    }
+}
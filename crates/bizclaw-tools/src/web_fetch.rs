@@ -0,0 +1,479 @@
+//! Web Fetch Tool — read a web page as plain text.
+//!
+//! Unlike `http_request`, which returns raw responses for API calls, this
+//! tool is for reading: HTML is stripped down to its visible text and the
+//! result is cut to a token budget so a long page doesn't flood a small
+//! context window.
+//!
+//! Safety: the same SSRF checks as `http_request` (also applied to every
+//! redirect), an optional domain allowlist and denylist, a download size
+//! cap, and text-like content types only.
+
+use crate::http_request::is_url_blocked;
+use async_trait::async_trait;
+use bizclaw_core::config::WebFetchConfig;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::Tool;
+use bizclaw_core::types::{ToolDefinition, ToolResult};
+use std::sync::Arc;
+
+/// Elements whose content is never visible text.
+const SKIPPED_ELEMENTS: &[&str] = &[
+    "script", "style", "noscript", "svg", "head", "template", "iframe",
+];
+
+/// Which domains may be fetched.
+#[derive(Debug, Clone, Default)]
+pub struct DomainPolicy {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl DomainPolicy {
+    pub fn new(allow: &[String], deny: &[String]) -> Self {
+        let normalize = |domains: &[String]| {
+            domains
+                .iter()
+                .map(|d| d.trim().trim_matches('.').to_ascii_lowercase())
+                .filter(|d| !d.is_empty())
+                .collect()
+        };
+        Self {
+            allow: normalize(allow),
+            deny: normalize(deny),
+        }
+    }
+
+    /// Why `host` may not be fetched, if it may not.
+    pub fn check(&self, host: &str) -> Option<String> {
+        let host = host.to_ascii_lowercase();
+        if self.deny.iter().any(|d| domain_matches(&host, d)) {
+            return Some(format!("{host} is on the denylist"));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|d| domain_matches(&host, d)) {
+            return Some(format!("{host} is not on the allowlist"));
+        }
+        None
+    }
+}
+
+/// `host` is `domain` or one of its subdomains.
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Why `url` may not be fetched, if it may not.
+fn check_url(url: &str, policy: &DomainPolicy) -> Option<String> {
+    if let Some(reason) = is_url_blocked(url) {
+        return Some(reason);
+    }
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return Some(format!("Invalid URL: {e}")),
+    };
+    match parsed.host_str() {
+        Some(host) => policy.check(host),
+        None => Some("URL has no host".into()),
+    }
+}
+
+pub struct WebFetchTool {
+    policy: Arc<DomainPolicy>,
+    max_bytes: usize,
+    max_tokens: usize,
+    timeout_secs: u64,
+}
+
+impl Default for WebFetchTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebFetchTool {
+    pub fn new() -> Self {
+        Self::from_config(&WebFetchConfig::default())
+    }
+
+    pub fn from_config(config: &WebFetchConfig) -> Self {
+        Self {
+            policy: Arc::new(DomainPolicy::new(
+                &config.allow_domains,
+                &config.deny_domains,
+            )),
+            max_bytes: config.max_bytes.max(1024),
+            max_tokens: config.max_tokens.max(100),
+            timeout_secs: config.timeout_secs.max(1),
+        }
+    }
+
+    fn blocked(reason: String) -> ToolResult {
+        ToolResult {
+            tool_call_id: String::new(),
+            output: format!("Blocked: {reason}"),
+            success: false,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for WebFetchTool {
+    fn name(&self) -> &str {
+        "web_fetch"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "web_fetch".into(),
+            description: "Fetch a web page and return its readable text (title, URL and \
+                          content, shortened if long)."
+                .into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "url": { "type": "string", "description": "http(s) URL of the page" },
+                    "max_tokens": {
+                        "type": "integer",
+                        "description": "Limit the returned text to about this many tokens"
+                    }
+                },
+                "required": ["url"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<ToolResult> {
+        let args: serde_json::Value =
+            serde_json::from_str(arguments).map_err(|e| BizClawError::Tool(e.to_string()))?;
+        let url = args["url"]
+            .as_str()
+            .ok_or_else(|| BizClawError::Tool("Missing 'url'".into()))?
+            .trim();
+        let max_tokens = args["max_tokens"]
+            .as_u64()
+            .map(|n| (n as usize).min(self.max_tokens))
+            .unwrap_or(self.max_tokens);
+
+        if let Some(reason) = check_url(url, &self.policy) {
+            return Ok(Self::blocked(reason));
+        }
+
+        // Redirects are checked like the original URL, so an allowed page
+        // can't bounce the request onto the internal network.
+        let policy = self.policy.clone();
+        let redirects = reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= 5 {
+                return attempt.error("too many redirects");
+            }
+            match check_url(attempt.url().as_str(), &policy) {
+                Some(reason) => attempt.error(reason),
+                None => attempt.follow(),
+            }
+        });
+        let client = reqwest::Client::builder()
+            .user_agent("BizClaw/1.0")
+            .timeout(std::time::Duration::from_secs(self.timeout_secs))
+            .redirect(redirects)
+            .build()
+            .map_err(|e| BizClawError::Tool(format!("Client error: {e}")))?;
+
+        let mut response = client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| BizClawError::Tool(format!("Fetch failed: {e}")))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Ok(ToolResult {
+                tool_call_id: String::new(),
+                output: format!("HTTP {status} for {url}"),
+                success: false,
+            });
+        }
+        if let Some(length) = response.content_length()
+            && length as usize > self.max_bytes
+        {
+            return Ok(Self::blocked(format!(
+                "page is {length} bytes, limit is {}",
+                self.max_bytes
+            )));
+        }
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let final_url = response.url().to_string();
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| BizClawError::Tool(format!("Read body failed: {e}")))?
+        {
+            let room = self.max_bytes - body.len();
+            if chunk.len() >= room {
+                body.extend_from_slice(&chunk[..room]);
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let raw = String::from_utf8_lossy(&body);
+
+        let is_html = content_type.contains("html")
+            || (content_type.is_empty() && raw.trim_start().starts_with('<'));
+        let (title, text) = if is_html {
+            (extract_title(&raw), html_to_text(&raw))
+        } else if content_type.starts_with("text/")
+            || content_type.contains("json")
+            || content_type.contains("xml")
+            || content_type.is_empty()
+        {
+            (None, raw.trim().to_string())
+        } else {
+            return Ok(Self::blocked(format!(
+                "unsupported content type {content_type}"
+            )));
+        };
+
+        let mut output = String::new();
+        if let Some(title) = title {
+            output.push_str(&format!("Title: {title}\n"));
+        }
+        output.push_str(&format!("URL: {final_url}\n\n"));
+        output.push_str(&truncate_tokens(&text, max_tokens));
+
+        Ok(ToolResult {
+            tool_call_id: String::new(),
+            output,
+            success: true,
+        })
+    }
+}
+
+/// Visible text of an HTML document, one block element per line.
+pub fn html_to_text(html: &str) -> String {
+    // ASCII lowercasing keeps byte offsets aligned with `html`.
+    let lower = html.to_ascii_lowercase();
+    let mut out = String::with_capacity(html.len() / 2);
+    let mut i = 0;
+
+    while i < html.len() {
+        let rest = &html[i..];
+        if rest.starts_with("<!--") {
+            i += rest.find("-->").map_or(rest.len(), |end| end + 3);
+            continue;
+        }
+        if rest.starts_with('<') {
+            let end = rest.find('>').map_or(rest.len(), |end| end + 1);
+            let inner = lower[i + 1..i + end].trim_end_matches('>');
+            let closing = inner.starts_with('/');
+            let name: String = inner
+                .trim_start_matches('/')
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric())
+                .collect();
+            i += end;
+
+            if !closing && SKIPPED_ELEMENTS.contains(&name.as_str()) {
+                // Jump to the closing tag; it's consumed as a tag next round.
+                let close = format!("</{name}");
+                i = lower[i..].find(&close).map_or(html.len(), |p| i + p);
+            } else if is_block(&name) {
+                out.push('\n');
+            } else if name == "td" || name == "th" {
+                out.push(' ');
+            }
+            continue;
+        }
+        let next = rest.find('<').unwrap_or(rest.len());
+        out.push_str(&decode_entities(&rest[..next]));
+        i += next;
+    }
+    tidy(&out)
+}
+
+/// Elements that start a new line.
+fn is_block(name: &str) -> bool {
+    matches!(
+        name,
+        "p" | "div"
+            | "br"
+            | "li"
+            | "ul"
+            | "ol"
+            | "tr"
+            | "table"
+            | "section"
+            | "article"
+            | "header"
+            | "footer"
+            | "nav"
+            | "main"
+            | "aside"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "blockquote"
+            | "pre"
+            | "hr"
+            | "dt"
+            | "dd"
+            | "figcaption"
+    )
+}
+
+/// Contents of `<title>`, if any.
+pub fn extract_title(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title = tidy(&decode_entities(&html[start..end]));
+    (!title.is_empty()).then_some(title)
+}
+
+/// Decode named entities common in page text and numeric references.
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| {
+                let entity = &rest[1..1 + end];
+                let c = match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some(' '),
+                    _ => entity
+                        .strip_prefix("#x")
+                        .or_else(|| entity.strip_prefix("#X"))
+                        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                        .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                        .and_then(char::from_u32),
+                };
+                c.map(|c| (c, end + 2))
+            });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Collapse runs of whitespace and keep at most one blank line.
+fn tidy(text: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !line.is_empty() || lines.last().is_some_and(|l| !l.is_empty()) {
+            lines.push(line);
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// Cut `text` to about `max_tokens` tokens (4 characters each), at a word
+/// boundary.
+pub fn truncate_tokens(text: &str, max_tokens: usize) -> String {
+    let max_chars = max_tokens * 4;
+    let Some((cut, _)) = text.char_indices().nth(max_chars) else {
+        return text.to_string();
+    };
+    let head = &text[..cut];
+    let head = head
+        .rfind(char::is_whitespace)
+        .map_or(head, |space| &head[..space]);
+    format!("{}\n\n[truncated]", head.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_text() {
+        let html = r#"<html><head><title>Giá vàng &amp; tỷ giá</title>
+            <style>body { color: red }</style></head>
+            <body><nav>Home</nav><!-- ad -->
+            <h1>Hôm&nbsp;nay</h1><p>SJC: <b>80</b>   triệu&#x2F;lượng</p>
+            <script>alert("x")</script><p>5 &lt; 6</p></body></html>"#;
+        assert_eq!(extract_title(html).as_deref(), Some("Giá vàng & tỷ giá"));
+        assert_eq!(
+            html_to_text(html),
+            "Home\n\nHôm nay\n\nSJC: 80 triệu/lượng\n\n5 < 6"
+        );
+        assert_eq!(decode_entities("a & b &bogus; &#39;"), "a & b &bogus; '");
+    }
+
+    #[test]
+    fn test_domain_policy() {
+        let policy = DomainPolicy::new(&[], &["Evil.com".into()]);
+        assert!(policy.check("evil.com").is_some());
+        assert!(policy.check("cdn.evil.com").is_some());
+        assert!(policy.check("notevil.com").is_none());
+
+        let policy = DomainPolicy::new(&["vnexpress.net".into()], &[]);
+        assert!(policy.check("vnexpress.net").is_none());
+        assert!(policy.check("e.vnexpress.net").is_none());
+        assert!(policy.check("example.com").is_some());
+
+        assert!(check_url("http://127.0.0.1/", &DomainPolicy::default()).is_some());
+        assert!(check_url("https://example.com/a", &DomainPolicy::default()).is_none());
+    }
+
+    #[test]
+    fn test_truncate_tokens() {
+        assert_eq!(truncate_tokens("short text", 10), "short text");
+        let long = "word ".repeat(100);
+        let cut = truncate_tokens(&long, 5);
+        assert!(cut.starts_with("word word word word"));
+        assert!(cut.ends_with("[truncated]"));
+        assert!(cut.len() < 40);
+    }
+
+    #[tokio::test]
+    async fn test_execute_blocks_before_fetching() {
+        let tool = WebFetchTool::from_config(&WebFetchConfig {
+            deny_domains: vec!["example.com".into()],
+            ..Default::default()
+        });
+        let result = tool
+            .execute(r#"{"url": "https://www.example.com/"}"#)
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.output.contains("denylist"));
+
+        let result = tool
+            .execute(r#"{"url": "http://192.168.1.1/"}"#)
+            .await
+            .unwrap();
+        assert!(result.output.starts_with("Blocked"));
+    }
+}
@@ -1,13 +1,74 @@
 //! Web Search Tool — enables the agent to search the internet.
 //!
-//! Uses DuckDuckGo Instant Answer API (no API key required).
+//! Backends:
+//! - DuckDuckGo HTML search (default, no API key required)
+//! - SearxNG — a self-hosted instance's JSON API
+//! - Brave Search API (`X-Subscription-Token`)
+//! - Bing Web Search API (`Ocp-Apim-Subscription-Key`)
+//!
+//! Results are numbered with their URL so the model can cite them.
 
 use async_trait::async_trait;
-use bizclaw_core::error::Result;
+use bizclaw_core::config::WebSearchConfig;
+use bizclaw_core::error::{BizClawError, Result};
 use bizclaw_core::traits::Tool;
 use bizclaw_core::types::{ToolDefinition, ToolResult};
 
-pub struct WebSearchTool;
+/// Where searches are sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchBackend {
+    DuckDuckGo,
+    Searxng { base_url: String },
+    Brave { api_key: String },
+    Bing { api_key: String },
+}
+
+impl SearchBackend {
+    /// Backend described by `[tools.web_search]`. Unknown names and
+    /// backends missing their URL or key fall back to DuckDuckGo.
+    pub fn from_config(config: &WebSearchConfig) -> Self {
+        match config.backend.to_ascii_lowercase().as_str() {
+            "searxng" | "searx" if !config.base_url.is_empty() => Self::Searxng {
+                base_url: config.base_url.trim_end_matches('/').to_string(),
+            },
+            "brave" if !config.api_key.is_empty() => Self::Brave {
+                api_key: config.api_key.clone(),
+            },
+            "bing" if !config.api_key.is_empty() => Self::Bing {
+                api_key: config.api_key.clone(),
+            },
+            "duckduckgo" | "ddg" | "" => Self::DuckDuckGo,
+            other => {
+                tracing::warn!(
+                    "web_search: backend '{other}' is unknown or missing base_url/api_key, using DuckDuckGo"
+                );
+                Self::DuckDuckGo
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DuckDuckGo => "duckduckgo",
+            Self::Searxng { .. } => "searxng",
+            Self::Brave { .. } => "brave",
+            Self::Bing { .. } => "bing",
+        }
+    }
+}
+
+/// One search hit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub title: String,
+    pub snippet: String,
+    pub url: String,
+}
+
+pub struct WebSearchTool {
+    backend: SearchBackend,
+    max_results: usize,
+}
 
 impl Default for WebSearchTool {
     fn default() -> Self {
@@ -17,7 +78,74 @@ impl Default for WebSearchTool {
 
 impl WebSearchTool {
     pub fn new() -> Self {
-        Self
+        Self {
+            backend: SearchBackend::DuckDuckGo,
+            max_results: 5,
+        }
+    }
+
+    pub fn from_config(config: &WebSearchConfig) -> Self {
+        Self {
+            backend: SearchBackend::from_config(config),
+            max_results: config.max_results.max(1),
+        }
+    }
+
+    pub fn backend(&self) -> &SearchBackend {
+        &self.backend
+    }
+
+    async fn search(&self, query: &str, max: usize) -> Result<Vec<SearchResult>> {
+        let client = reqwest::Client::builder()
+            .user_agent("BizClaw/1.0")
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .map_err(|e| BizClawError::Tool(format!("HTTP error: {e}")))?;
+        let q = urlencoding::encode(query);
+
+        let request = match &self.backend {
+            SearchBackend::DuckDuckGo => {
+                client.get(format!("https://html.duckduckgo.com/html/?q={q}"))
+            }
+            SearchBackend::Searxng { base_url } => {
+                client.get(format!("{base_url}/search?q={q}&format=json"))
+            }
+            SearchBackend::Brave { api_key } => client
+                .get(format!(
+                    "https://api.search.brave.com/res/v1/web/search?q={q}&count={max}"
+                ))
+                .header("Accept", "application/json")
+                .header("X-Subscription-Token", api_key),
+            SearchBackend::Bing { api_key } => client
+                .get(format!(
+                    "https://api.bing.microsoft.com/v7.0/search?q={q}&count={max}"
+                ))
+                .header("Ocp-Apim-Subscription-Key", api_key),
+        };
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| BizClawError::Tool(format!("Search failed: {e}")))?;
+        if !response.status().is_success() {
+            return Err(BizClawError::Tool(format!(
+                "Search failed: {} returned {}",
+                self.backend.name(),
+                response.status()
+            )));
+        }
+        let body = response
+            .text()
+            .await
+            .map_err(|e| BizClawError::Tool(format!("Read failed: {e}")))?;
+
+        let results = match self.backend {
+            SearchBackend::DuckDuckGo => parse_ddg_results(&body, max),
+            SearchBackend::Searxng { .. } => parse_searxng(&json(&body)?, max),
+            SearchBackend::Brave { .. } => parse_brave(&json(&body)?, max),
+            SearchBackend::Bing { .. } => parse_bing(&json(&body)?, max),
+        };
+        Ok(results)
     }
 }
 
@@ -30,7 +158,9 @@ impl Tool for WebSearchTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             name: "web_search".into(),
-            description: "Search the web and return results".into(),
+            description: "Search the web for current information. Returns numbered results \
+                          with title, snippet and URL; cite the URLs you use."
+                .into(),
             parameters: serde_json::json!({
                 "type": "object",
                 "properties": {
@@ -51,83 +181,186 @@ impl Tool for WebSearchTool {
         let max_results: usize = args["max_results"]
             .as_u64()
             .map(|v| v as usize)
-            .unwrap_or(5);
-
-        // Use DuckDuckGo HTML search (no API key needed)
-        let client = reqwest::Client::builder()
-            .user_agent("BizClaw/1.0")
-            .timeout(std::time::Duration::from_secs(10))
-            .build()
-            .map_err(|e| bizclaw_core::error::BizClawError::Tool(format!("HTTP error: {e}")))?;
+            .unwrap_or(self.max_results)
+            .clamp(1, 20);
 
-        let url = format!(
-            "https://html.duckduckgo.com/html/?q={}",
-            urlencoding::encode(query)
-        );
-        let response =
-            client.get(&url).send().await.map_err(|e| {
-                bizclaw_core::error::BizClawError::Tool(format!("Search failed: {e}"))
-            })?;
-
-        let html = response
-            .text()
-            .await
-            .map_err(|e| bizclaw_core::error::BizClawError::Tool(format!("Read failed: {e}")))?;
-
-        let results = parse_ddg_results(&html, max_results);
-
-        let output = if results.is_empty() {
-            format!("No results found for: {query}")
-        } else {
-            let mut out = format!("Search results for \"{query}\":\n\n");
-            for (i, r) in results.iter().enumerate() {
-                out.push_str(&format!("{}. {}\n   {}\n   {}\n\n", i + 1, r.0, r.1, r.2));
-            }
-            out
-        };
+        let results = self.search(query, max_results).await?;
 
         Ok(ToolResult {
             tool_call_id: String::new(),
-            output,
+            output: format_results(query, &results),
             success: true,
         })
     }
 }
 
-fn parse_ddg_results(html: &str, max: usize) -> Vec<(String, String, String)> {
+fn json(body: &str) -> Result<serde_json::Value> {
+    serde_json::from_str(body).map_err(|e| BizClawError::Tool(format!("Bad search response: {e}")))
+}
+
+/// Numbered results, one block per hit.
+pub fn format_results(query: &str, results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return format!("No results found for: {query}");
+    }
+    let mut out = format!("Search results for \"{query}\":\n\n");
+    for (i, r) in results.iter().enumerate() {
+        out.push_str(&format!(
+            "[{}] {}\n    {}\n    {}\n\n",
+            i + 1,
+            r.title,
+            r.snippet,
+            r.url
+        ));
+    }
+    out
+}
+
+fn collect(
+    items: Option<&Vec<serde_json::Value>>,
+    max: usize,
+    title: &str,
+    snippet: &str,
+) -> Vec<SearchResult> {
+    items
+        .into_iter()
+        .flatten()
+        .filter_map(|item| {
+            let title = strip_tags(item[title].as_str()?);
+            let url = item["url"].as_str()?.to_string();
+            let snippet = strip_tags(item[snippet].as_str().unwrap_or_default());
+            (!title.is_empty()).then_some(SearchResult {
+                title,
+                snippet,
+                url,
+            })
+        })
+        .take(max)
+        .collect()
+}
+
+/// SearxNG: `{"results": [{"title", "url", "content"}]}`
+fn parse_searxng(body: &serde_json::Value, max: usize) -> Vec<SearchResult> {
+    collect(body["results"].as_array(), max, "title", "content")
+}
+
+/// Brave: `{"web": {"results": [{"title", "url", "description"}]}}`
+fn parse_brave(body: &serde_json::Value, max: usize) -> Vec<SearchResult> {
+    collect(
+        body["web"]["results"].as_array(),
+        max,
+        "title",
+        "description",
+    )
+}
+
+/// Bing: `{"webPages": {"value": [{"name", "url", "snippet"}]}}`
+fn parse_bing(body: &serde_json::Value, max: usize) -> Vec<SearchResult> {
+    collect(body["webPages"]["value"].as_array(), max, "name", "snippet")
+}
+
+fn parse_ddg_results(html: &str, max: usize) -> Vec<SearchResult> {
     let mut results = Vec::new();
 
     for segment in html.split("class=\"result__a\"").skip(1).take(max) {
-        let title = extract_between(segment, ">", "</a>")
-            .unwrap_or_default()
-            .replace("<b>", "")
-            .replace("</b>", "");
+        let title = strip_tags(&extract_between(segment, ">", "</a>").unwrap_or_default());
 
         let url = extract_between(segment, "href=\"", "\"").unwrap_or_default();
 
         let snippet = if let Some(snip_seg) = segment.split("class=\"result__snippet\"").nth(1) {
-            extract_between(snip_seg, ">", "</")
-                .unwrap_or_default()
-                .replace("<b>", "")
-                .replace("</b>", "")
+            strip_tags(&extract_between(snip_seg, ">", "</").unwrap_or_default())
         } else {
             String::new()
         };
 
         if !title.is_empty() {
-            results.push((
-                title.trim().into(),
-                snippet.trim().into(),
-                url.trim().into(),
-            ));
+            results.push(SearchResult {
+                title,
+                snippet,
+                url: url.trim().into(),
+            });
         }
     }
     results
 }
 
+/// Drop `<b>`/`<strong>` highlighting and similar inline tags.
+fn strip_tags(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out.trim().to_string()
+}
+
 fn extract_between(text: &str, start: &str, end: &str) -> Option<String> {
     let start_idx = text.find(start)? + start.len();
     let remaining = &text[start_idx..];
     let end_idx = remaining.find(end)?;
     Some(remaining[..end_idx].to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_from_config() {
+        let mut config = WebSearchConfig::default();
+        assert_eq!(
+            SearchBackend::from_config(&config),
+            SearchBackend::DuckDuckGo
+        );
+
+        config.backend = "searxng".into();
+        config.base_url = "http://localhost:8888/".into();
+        assert_eq!(
+            SearchBackend::from_config(&config),
+            SearchBackend::Searxng {
+                base_url: "http://localhost:8888".into()
+            }
+        );
+
+        // A keyed backend without its key falls back.
+        config.backend = "brave".into();
+        assert_eq!(
+            SearchBackend::from_config(&config),
+            SearchBackend::DuckDuckGo
+        );
+        config.api_key = "k".into();
+        assert_eq!(SearchBackend::from_config(&config).name(), "brave");
+    }
+
+    #[test]
+    fn test_parse_backends() {
+        let searxng = serde_json::json!({"results": [
+            {"title": "Giá vàng", "url": "https://a.vn", "content": "SJC <b>hôm nay</b>"},
+            {"title": "", "url": "https://skip.vn", "content": "untitled"},
+            {"title": "Two", "url": "https://b.vn", "content": ""}
+        ]});
+        let results = parse_searxng(&searxng, 5);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].snippet, "SJC hôm nay");
+        assert_eq!(parse_searxng(&searxng, 1).len(), 1);
+
+        let brave = serde_json::json!({"web": {"results": [
+            {"title": "<strong>Rust</strong>", "url": "https://rust-lang.org", "description": "A language"}
+        ]}});
+        assert_eq!(parse_brave(&brave, 5)[0].title, "Rust");
+
+        let bing = serde_json::json!({"webPages": {"value": [
+            {"name": "Bing hit", "url": "https://c.com", "snippet": "text"}
+        ]}});
+        assert_eq!(parse_bing(&bing, 5)[0].url, "https://c.com");
+        assert!(parse_bing(&serde_json::json!({}), 5).is_empty());
+
+        let out = format_results("q", &parse_bing(&bing, 5));
+        assert!(out.contains("[1] Bing hit\n    text\n    https://c.com"));
+    }
+}
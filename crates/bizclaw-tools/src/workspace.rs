@@ -0,0 +1,572 @@
+//! Workspace file tools — list, read and write files inside one directory.
+//!
+//! Unlike `file`, these tools never leave the workspace (`[tools] workspace`,
+//! `~/.bizclaw/workspace` by default), so they can be offered to chat users
+//! for "summarize this report and save the result" flows. Paths are taken
+//! relative to the workspace; `..` that climbs out of it and symlinks that
+//! point outside are refused. Reads are paged to fit the tool output limit
+//! and both reads and writes are capped by `[tools.files]`.
+
+use async_trait::async_trait;
+use bizclaw_core::config::FileToolsConfig;
+use bizclaw_core::error::{BizClawError, Result};
+use bizclaw_core::traits::Tool;
+use bizclaw_core::types::{ToolDefinition, ToolResult};
+use std::path::{Component, Path, PathBuf};
+
+/// Bytes returned per `read_file` call, leaving room for the header
+/// within [`crate::MAX_TOOL_OUTPUT`].
+const READ_PAGE_BYTES: usize = 3500;
+
+/// Entries shown by `list_files`.
+const MAX_LIST_ENTRIES: usize = 200;
+
+/// A directory the file tools are confined to.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+}
+
+impl Workspace {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// `~/.bizclaw/workspace`.
+    pub fn default_root() -> PathBuf {
+        bizclaw_core::config::BizClawConfig::home_dir().join("workspace")
+    }
+
+    /// The workspace directory, created if missing and fully resolved.
+    fn canonical_root(&self) -> Result<PathBuf> {
+        std::fs::create_dir_all(&self.root).map_err(|e| {
+            BizClawError::Tool(format!(
+                "Cannot create workspace {}: {e}",
+                self.root.display()
+            ))
+        })?;
+        self.root
+            .canonicalize()
+            .map_err(|e| BizClawError::Tool(format!("Workspace unavailable: {e}")))
+    }
+
+    /// `path` relative to the workspace with `.` and `..` applied, or an
+    /// error if `..` climbs above it. A leading `/` means the workspace root.
+    fn relative(path: &str) -> Result<PathBuf> {
+        let mut rel = PathBuf::new();
+        for component in Path::new(path.trim()).components() {
+            match component {
+                Component::Normal(part) => rel.push(part),
+                Component::ParentDir => {
+                    if !rel.pop() {
+                        return Err(BizClawError::Tool(format!(
+                            "{path} is outside the workspace"
+                        )));
+                    }
+                }
+                Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+            }
+        }
+        Ok(rel)
+    }
+
+    /// An existing file or directory inside the workspace.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let root = self.canonical_root()?;
+        let resolved = root
+            .join(Self::relative(path)?)
+            .canonicalize()
+            .map_err(|e| BizClawError::Tool(format!("{path}: {e}")))?;
+        if !resolved.starts_with(&root) {
+            return Err(BizClawError::Tool(format!(
+                "{path} is outside the workspace"
+            )));
+        }
+        Ok(resolved)
+    }
+
+    /// A file to create or overwrite inside the workspace. Missing parent
+    /// directories are created once the deepest existing one is known to be
+    /// inside the workspace.
+    pub fn resolve_for_write(&self, path: &str) -> Result<PathBuf> {
+        let root = self.canonical_root()?;
+        let rel = Self::relative(path)?;
+        if rel.as_os_str().is_empty() {
+            return Err(BizClawError::Tool("A file name is required".into()));
+        }
+        let target = root.join(&rel);
+
+        let existing = target
+            .ancestors()
+            .find(|p| p.symlink_metadata().is_ok())
+            .unwrap_or(&root);
+        let resolved = existing
+            .canonicalize()
+            .map_err(|e| BizClawError::Tool(format!("{path}: {e}")))?;
+        if !resolved.starts_with(&root) {
+            return Err(BizClawError::Tool(format!(
+                "{path} is outside the workspace"
+            )));
+        }
+        if existing == target {
+            if resolved.is_dir() {
+                return Err(BizClawError::Tool(format!("{path} is a directory")));
+            }
+            return Ok(resolved);
+        }
+
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| BizClawError::Tool(format!("Create dir: {e}")))?;
+        }
+        Ok(target)
+    }
+
+    /// `path` as a program running in `dir` would open it, or an error if
+    /// that is outside the workspace. Unlike [`resolve`](Self::resolve) a
+    /// leading `/` is the real root and the path needn't exist. Symlinks
+    /// are followed before any `..` after them, as the kernel does.
+    pub fn resolve_from(&self, dir: &Path, path: &str) -> Result<PathBuf> {
+        let root = self.canonical_root()?;
+        let mut target = dir.to_path_buf();
+        for component in Path::new(path).components() {
+            match component {
+                Component::ParentDir => {
+                    target.pop();
+                }
+                Component::CurDir => {}
+                other => {
+                    target.push(other);
+                    if let Ok(real) = target.canonicalize() {
+                        target = real;
+                    }
+                }
+            }
+        }
+        if !target.starts_with(&root) {
+            return Err(BizClawError::Tool(format!(
+                "{path} is outside the workspace"
+            )));
+        }
+        Ok(target)
+    }
+
+    /// `path` as shown to the model: relative to the workspace.
+    pub fn display(&self, path: &Path) -> String {
+        let root = self
+            .root
+            .canonicalize()
+            .unwrap_or_else(|_| self.root.clone());
+        match path.strip_prefix(&root) {
+            Ok(rel) if rel.as_os_str().is_empty() => ".".into(),
+            Ok(rel) => rel.display().to_string(),
+            Err(_) => path.display().to_string(),
+        }
+    }
+}
+
+fn parse_args(arguments: &str) -> Result<serde_json::Value> {
+    serde_json::from_str(arguments).map_err(|e| BizClawError::Tool(e.to_string()))
+}
+
+fn ok(output: String) -> Result<ToolResult> {
+    Ok(ToolResult {
+        tool_call_id: String::new(),
+        output,
+        success: true,
+    })
+}
+
+/// One page of `content` starting at byte `offset`, with a header and,
+/// when more remains, the offset to continue from.
+pub fn read_page(name: &str, content: &str, offset: usize) -> String {
+    let mut start = offset.min(content.len());
+    while !content.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (start + READ_PAGE_BYTES).min(content.len());
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    // Prefer to stop at a line break when one is reasonably close.
+    if end < content.len()
+        && let Some(nl) = content[start..end].rfind('\n')
+        && nl > READ_PAGE_BYTES / 2
+    {
+        end = start + nl + 1;
+    }
+
+    if start == 0 && end == content.len() {
+        return format!("{name} ({} bytes):\n{content}", content.len());
+    }
+    let mut out = format!(
+        "{name} ({} bytes, showing {start}-{end}):\n{}",
+        content.len(),
+        &content[start..end]
+    );
+    if end < content.len() {
+        out.push_str(&format!("\n[more: read_file with offset={end}]"));
+    }
+    out
+}
+
+/// `list_files` — entries of a workspace directory.
+pub struct ListFilesTool {
+    workspace: Workspace,
+}
+
+impl ListFilesTool {
+    pub fn new(workspace: Workspace) -> Self {
+        Self { workspace }
+    }
+}
+
+impl Default for ListFilesTool {
+    fn default() -> Self {
+        Self::new(Workspace::new(Workspace::default_root()))
+    }
+}
+
+#[async_trait]
+impl Tool for ListFilesTool {
+    fn name(&self) -> &str {
+        "list_files"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "list_files".into(),
+            description: "List files and folders in the workspace.".into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Folder relative to the workspace (default: the workspace itself)"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<ToolResult> {
+        let args = parse_args(arguments)?;
+        let path = args["path"].as_str().unwrap_or(".");
+        let dir = self.workspace.resolve(path)?;
+        if !dir.is_dir() {
+            return Err(BizClawError::Tool(format!("{path} is not a folder")));
+        }
+
+        let mut reader = tokio::fs::read_dir(&dir)
+            .await
+            .map_err(|e| BizClawError::Tool(e.to_string()))?;
+        let mut items = Vec::new();
+        while let Some(entry) = reader
+            .next_entry()
+            .await
+            .map_err(|e| BizClawError::Tool(e.to_string()))?
+        {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let item = match entry.metadata().await {
+                Ok(meta) if meta.is_dir() => format!("{name}/"),
+                Ok(meta) => format!("{name}  ({})", crate::file::format_size(meta.len())),
+                Err(_) => name,
+            };
+            items.push(item);
+        }
+        items.sort();
+
+        let shown = self.workspace.display(&dir);
+        if items.is_empty() {
+            return ok(format!("{shown} is empty"));
+        }
+        let total = items.len();
+        let mut output = format!("{shown} ({total} entries):\n");
+        output.push_str(&items[..total.min(MAX_LIST_ENTRIES)].join("\n"));
+        if total > MAX_LIST_ENTRIES {
+            output.push_str(&format!("\n... and {} more", total - MAX_LIST_ENTRIES));
+        }
+        ok(output)
+    }
+}
+
+/// `read_file` — a text file from the workspace, one page at a time.
+pub struct ReadFileTool {
+    workspace: Workspace,
+    max_bytes: u64,
+}
+
+impl ReadFileTool {
+    pub fn new(workspace: Workspace, config: &FileToolsConfig) -> Self {
+        Self {
+            workspace,
+            max_bytes: config.max_read_bytes,
+        }
+    }
+}
+
+impl Default for ReadFileTool {
+    fn default() -> Self {
+        Self::new(
+            Workspace::new(Workspace::default_root()),
+            &FileToolsConfig::default(),
+        )
+    }
+}
+
+#[async_trait]
+impl Tool for ReadFileTool {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "read_file".into(),
+            description: "Read a text file from the workspace. Long files are returned in \
+                          pages; call again with the offset shown to continue."
+                .into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "File relative to the workspace" },
+                    "offset": { "type": "integer", "description": "Byte offset to start from (default 0)" }
+                },
+                "required": ["path"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<ToolResult> {
+        let args = parse_args(arguments)?;
+        let path = args["path"]
+            .as_str()
+            .ok_or_else(|| BizClawError::Tool("Missing 'path'".into()))?;
+        let offset = args["offset"].as_u64().unwrap_or(0) as usize;
+
+        let file = self.workspace.resolve(path)?;
+        let meta = tokio::fs::metadata(&file)
+            .await
+            .map_err(|e| BizClawError::Tool(format!("{path}: {e}")))?;
+        if meta.is_dir() {
+            return Err(BizClawError::Tool(format!(
+                "{path} is a folder; use list_files"
+            )));
+        }
+        if meta.len() > self.max_bytes {
+            return Err(BizClawError::Tool(format!(
+                "{path} is {}, over the {} read limit",
+                crate::file::format_size(meta.len()),
+                crate::file::format_size(self.max_bytes)
+            )));
+        }
+
+        let bytes = tokio::fs::read(&file)
+            .await
+            .map_err(|e| BizClawError::Tool(format!("Read failed: {e}")))?;
+        let content = String::from_utf8(bytes).map_err(|_| {
+            BizClawError::Tool(format!(
+                "{path} is not a text file; use document_reader for PDF, DOCX or XLSX"
+            ))
+        })?;
+        ok(read_page(&self.workspace.display(&file), &content, offset))
+    }
+}
+
+/// `write_file` — create, overwrite or append to a workspace file.
+pub struct WriteFileTool {
+    workspace: Workspace,
+    max_bytes: u64,
+}
+
+impl WriteFileTool {
+    pub fn new(workspace: Workspace, config: &FileToolsConfig) -> Self {
+        Self {
+            workspace,
+            max_bytes: config.max_write_bytes,
+        }
+    }
+}
+
+impl Default for WriteFileTool {
+    fn default() -> Self {
+        Self::new(
+            Workspace::new(Workspace::default_root()),
+            &FileToolsConfig::default(),
+        )
+    }
+}
+
+#[async_trait]
+impl Tool for WriteFileTool {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "write_file".into(),
+            description: "Save text to a file in the workspace, creating folders as needed. \
+                          Overwrites the file unless append is true."
+                .into(),
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "File relative to the workspace" },
+                    "content": { "type": "string", "description": "Text to write" },
+                    "append": { "type": "boolean", "description": "Add to the end instead of overwriting" }
+                },
+                "required": ["path", "content"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &str) -> Result<ToolResult> {
+        let args = parse_args(arguments)?;
+        let path = args["path"]
+            .as_str()
+            .ok_or_else(|| BizClawError::Tool("Missing 'path'".into()))?;
+        let content = args["content"]
+            .as_str()
+            .ok_or_else(|| BizClawError::Tool("Missing 'content'".into()))?;
+        let append = args["append"].as_bool().unwrap_or(false);
+
+        let file = self.workspace.resolve_for_write(path)?;
+        let existing = if append {
+            tokio::fs::metadata(&file)
+                .await
+                .map(|m| m.len())
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        let size = existing + content.len() as u64;
+        if size > self.max_bytes {
+            return Err(BizClawError::Tool(format!(
+                "{path} would be {}, over the {} write limit",
+                crate::file::format_size(size),
+                crate::file::format_size(self.max_bytes)
+            )));
+        }
+
+        if append {
+            use tokio::io::AsyncWriteExt;
+            let mut handle = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&file)
+                .await
+                .map_err(|e| BizClawError::Tool(format!("Open failed: {e}")))?;
+            handle
+                .write_all(content.as_bytes())
+                .await
+                .map_err(|e| BizClawError::Tool(format!("Write failed: {e}")))?;
+        } else {
+            tokio::fs::write(&file, content)
+                .await
+                .map_err(|e| BizClawError::Tool(format!("Write failed: {e}")))?;
+        }
+
+        let verb = if append { "Appended" } else { "Wrote" };
+        ok(format!(
+            "{verb} {} bytes to {}",
+            content.len(),
+            self.workspace.display(&file)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace() -> Workspace {
+        Workspace::new(
+            std::env::temp_dir().join(format!("bizclaw-workspace-{}", uuid::Uuid::new_v4())),
+        )
+    }
+
+    #[test]
+    fn test_paths_stay_in_workspace() {
+        let ws = workspace();
+        let root = ws.canonical_root().unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+
+        assert_eq!(ws.resolve("a.txt").unwrap(), root.join("a.txt"));
+        assert_eq!(ws.resolve("/sub/../a.txt").unwrap(), root.join("a.txt"));
+        assert!(ws.resolve("../a.txt").is_err());
+        assert!(ws.resolve_for_write("../../etc/passwd").is_err());
+        assert!(ws.resolve_for_write(".").is_err());
+
+        let nested = ws.resolve_for_write("reports/2026/q1.md").unwrap();
+        assert_eq!(nested, root.join("reports/2026/q1.md"));
+        assert!(root.join("reports/2026").is_dir());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(std::env::temp_dir(), root.join("out")).unwrap();
+            assert!(ws.resolve("out").is_err());
+            assert!(ws.resolve_for_write("out/x.txt").is_err());
+        }
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_read_page() {
+        assert_eq!(read_page("a.txt", "hello", 0), "a.txt (5 bytes):\nhello");
+
+        let content = "dòng\n".repeat(1000);
+        let first = read_page("big.txt", &content, 0);
+        assert!(first.ends_with("[more: read_file with offset=3498]"));
+        assert!(first.len() < crate::MAX_TOOL_OUTPUT);
+
+        // An offset inside a character backs up to its start.
+        let page = read_page("big.txt", &content, 2);
+        assert!(page.contains("showing 1-"));
+        let last = read_page("big.txt", &content, content.len() - 6);
+        assert!(!last.contains("[more"));
+    }
+
+    #[tokio::test]
+    async fn test_write_read_list() {
+        let ws = workspace();
+        let config = FileToolsConfig {
+            max_read_bytes: 1024,
+            max_write_bytes: 16,
+        };
+        let write = WriteFileTool::new(ws.clone(), &config);
+        let read = ReadFileTool::new(ws.clone(), &config);
+        let list = ListFilesTool::new(ws.clone());
+
+        let result = write
+            .execute(r##"{"path": "out/summary.md", "content": "# Tóm tắt"}"##)
+            .await
+            .unwrap();
+        assert_eq!(result.output, "Wrote 12 bytes to out/summary.md");
+        write
+            .execute(r#"{"path": "out/summary.md", "content": "\nok", "append": true}"#)
+            .await
+            .unwrap();
+        assert!(
+            write
+                .execute(r#"{"path": "out/summary.md", "content": "too much", "append": true}"#)
+                .await
+                .is_err()
+        );
+
+        let result = read.execute(r#"{"path": "out/summary.md"}"#).await.unwrap();
+        assert_eq!(result.output, "out/summary.md (15 bytes):\n# Tóm tắt\nok");
+
+        let result = list.execute("{}").await.unwrap();
+        assert_eq!(result.output, ". (1 entries):\nout/");
+        let result = list.execute(r#"{"path": "out"}"#).await.unwrap();
+        assert!(result.output.contains("summary.md  (15 B)"));
+
+        std::fs::write(ws.canonical_root().unwrap().join("big.log"), [b'x'; 2048]).unwrap();
+        let err = read.execute(r#"{"path": "big.log"}"#).await.unwrap_err();
+        assert!(err.to_string().contains("read limit"));
+
+        std::fs::remove_dir_all(ws.canonical_root().unwrap()).ok();
+    }
+}
@@ -0,0 +1,177 @@
+//! # BizClaw WASM
+//! The brain engine in the browser: fetch a small GGUF model and run it on
+//! the page, with no server round-trip per token. The dashboard chat runs
+//! it in a Web Worker (`brain-worker.js`) so generation doesn't block the UI.
+//!
+//! ```js
+//! import init, { Brain } from '/static/wasm/bizclaw_wasm.js';
+//! await init();
+//! const brain = await Brain.load(url, (loaded, total) => {});
+//! const text = brain.generate(prompt, 256, ['</s>'], piece => {});
+//! ```
+//!
+//! The brain is built without mmap or threads; the model is held in memory
+//! and generation runs on one thread, so models up to ~1B parameters at
+//! Q4_0 are practical.
+
+use bizclaw_brain::{BrainConfig, BrainEngine, GenerationParams, sampler::SamplerConfig, system};
+use js_sys::{Function, Reflect, Uint8Array};
+use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+#[wasm_bindgen]
+extern "C" {
+    // The global `fetch`, in a window or a worker alike.
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_with_str(input: &str) -> js_sys::Promise;
+}
+
+fn js_error(e: impl std::fmt::Display) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+/// Fetch `url` into memory, calling `on_progress(loaded, total)` as the
+/// body arrives; `total` is 0 when the server doesn't say.
+async fn fetch_bytes(url: &str, on_progress: Option<&Function>) -> Result<Vec<u8>, JsValue> {
+    let response: web_sys::Response = JsFuture::from(fetch_with_str(url)).await?.dyn_into()?;
+    if !response.ok() {
+        return Err(js_error(format!("Fetching {url} failed: HTTP {}", response.status())).into());
+    }
+    let total = response
+        .headers()
+        .get("Content-Length")?
+        .and_then(|len| len.parse::<usize>().ok())
+        .unwrap_or(0);
+    let Some(body) = response.body() else {
+        let buffer = JsFuture::from(response.array_buffer()?).await?;
+        return Ok(Uint8Array::new(&buffer).to_vec());
+    };
+
+    let reader: web_sys::ReadableStreamDefaultReader = body.get_reader().unchecked_into();
+    let mut bytes = Vec::with_capacity(total);
+    loop {
+        let chunk = JsFuture::from(reader.read()).await?;
+        if Reflect::get(&chunk, &"done".into())?.is_truthy() {
+            break;
+        }
+        let value: Uint8Array = Reflect::get(&chunk, &"value".into())?.dyn_into()?;
+        let start = bytes.len();
+        bytes.resize(start + value.length() as usize, 0);
+        value.copy_to(&mut bytes[start..]);
+        if let Some(on_progress) = on_progress {
+            on_progress.call2(
+                &JsValue::NULL,
+                &(bytes.len() as f64).into(),
+                &(total as f64).into(),
+            )?;
+        }
+    }
+    Ok(bytes)
+}
+
+/// A GGUF model loaded into the brain engine.
+#[wasm_bindgen]
+pub struct Brain {
+    engine: BrainEngine,
+}
+
+#[wasm_bindgen]
+impl Brain {
+    /// Fetch a GGUF model from `url` and load it.
+    pub async fn load(url: String, on_progress: Option<Function>) -> Result<Brain, JsValue> {
+        let bytes = fetch_bytes(&url, on_progress.as_ref()).await?;
+        let name = url.rsplit('/').next().unwrap_or(&url);
+        let name = name.split('?').next().unwrap_or(name);
+        Ok(Self::from_bytes(bytes, name)?)
+    }
+
+    /// Load a GGUF model already in memory, e.g. a file the user picked.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: Vec<u8>, name: &str) -> Result<Brain, JsError> {
+        let mut engine = BrainEngine::new(BrainConfig {
+            threads: 1,
+            max_tokens: u32::MAX,
+            ..Default::default()
+        });
+        engine.load_model_bytes(bytes, name).map_err(js_error)?;
+        Ok(Self { engine })
+    }
+
+    /// Generate up to `max_tokens` tokens after `prompt`, which is sent as
+    /// is: render the chat template first. `on_token` gets each piece as it
+    /// is sampled. Output ends before the first of `stop`.
+    pub fn generate(
+        &mut self,
+        prompt: &str,
+        max_tokens: u32,
+        stop: Vec<String>,
+        on_token: Option<Function>,
+    ) -> Result<String, JsError> {
+        let params = GenerationParams {
+            stop,
+            ..GenerationParams::max_tokens(max_tokens)
+        };
+        let mut callback_error = None;
+        let generation = self
+            .engine
+            .generate_stream_with(prompt, &params, |piece| {
+                if let Some(on_token) = &on_token
+                    && let Err(e) = on_token.call1(&JsValue::NULL, &piece.into())
+                {
+                    callback_error = Some(e);
+                    return false;
+                }
+                true
+            })
+            .map_err(js_error)?;
+        if let Some(e) = callback_error {
+            return Err(js_error(
+                e.as_string().unwrap_or_else(|| "on_token threw".into()),
+            ));
+        }
+        Ok(generation.text)
+    }
+
+    /// An L2-normalized embedding of `text`.
+    pub fn embed(&mut self, text: &str) -> Result<Vec<f32>, JsError> {
+        self.engine.embed(text).map_err(js_error)
+    }
+
+    /// Number of tokens `text` encodes to, without BOS.
+    #[wasm_bindgen(js_name = countTokens)]
+    pub fn count_tokens(&self, text: &str) -> Result<usize, JsError> {
+        self.engine.count_tokens(text).map_err(js_error)
+    }
+
+    /// Change temperature and top-p.
+    #[wasm_bindgen(js_name = setSampling)]
+    pub fn set_sampling(&mut self, temperature: f32, top_p: f32) {
+        let config = self.engine.sampler_config().cloned().unwrap_or_default();
+        self.engine.set_sampler_config(SamplerConfig {
+            temperature,
+            top_p,
+            ..config
+        });
+    }
+
+    /// Positions the KV cache holds: prompt and output together.
+    #[wasm_bindgen(getter, js_name = contextSize)]
+    pub fn context_size(&self) -> usize {
+        self.engine.context_size().unwrap_or(0)
+    }
+
+    #[wasm_bindgen(getter, js_name = modelInfo)]
+    pub fn model_info(&self) -> String {
+        self.engine.model_info().unwrap_or_default()
+    }
+}
+
+/// SIMD features this build uses, e.g. `["simd128"]`.
+#[wasm_bindgen]
+pub fn features() -> Vec<String> {
+    system::compiled_features()
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
@@ -7,13 +7,23 @@
 //!   bizclaw agent -m "Hello"           # One-shot message
 //!   bizclaw agent --interactive        # Interactive CLI
 //!   bizclaw channel start              # Start channel listener
+//!   bizclaw serve --config bizclaw.toml # Gateway, dashboard and channels
 //!   bizclaw onboard                    # First-time setup
 //!   bizclaw brain download             # Download local model
 //!   bizclaw config show                # Show configuration
+//!   bizclaw analytics report --weekly  # Usage report for the last 30 days
+//!   bizclaw chat model.gguf            # Chat with a local model directly
+//!   bizclaw pull <repo>/<file.gguf>    # Download a model from Hugging Face
+//!   bizclaw bench model.gguf -t 1,4    # Prefill/decode tokens/sec
+//!   bizclaw quantize in.gguf out.gguf  # Requantize a model (default q4_0)
+//!   bizclaw inspect model.gguf         # Metadata, tensors, memory needs
+//!   bizclaw run -m m.gguf -p in.jsonl -o out.jsonl # Batch generation
+//!   bizclaw eval -m q4.gguf -f wiki.txt # Perplexity (and KL with --reference)
+//!   bizclaw tokenize -m model.gguf "Hi" # Token IDs and pieces
+//!   bizclaw doctor                     # Diagnose CPU, memory, channels
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use tracing_subscriber::EnvFilter;
 
 #[derive(Parser)]
 #[command(
@@ -71,17 +81,131 @@ enum Commands {
         action: BrainAction,
     },
 
+    /// Download GGUF models from Hugging Face: <owner>/<repo>[@revision]/<file.gguf>
+    Pull {
+        #[arg(required = true)]
+        models: Vec<String>,
+    },
+
+    /// Measure prefill and decode throughput of a GGUF model
+    Bench {
+        model: std::path::PathBuf,
+        /// Thread counts to compare (default: 1, 2, 4, … up to all cores)
+        #[arg(short, long, value_delimiter = ',')]
+        threads: Vec<usize>,
+        /// Prompt lengths to prefill; the engine prefills a token at a time,
+        /// so these stand in for batch sizes
+        #[arg(short, long, value_delimiter = ',', default_value = "64,256")]
+        prompt: Vec<usize>,
+        /// Tokens to generate per run
+        #[arg(short = 'n', long, default_value = "32")]
+        gen_tokens: usize,
+    },
+
+    /// Requantize a GGUF model's weights
+    Quantize {
+        input: std::path::PathBuf,
+        output: std::path::PathBuf,
+        /// Target format: q4_k_m, q4_k_s, q5_k_m, q5_k_s, q6_k, q8_0, q4_0 or f16
+        #[arg(short = 't', long = "type", default_value = "q4_0")]
+        ggml_type: String,
+        /// llama.cpp importance matrix to fit k-quant scales to
+        #[arg(long)]
+        imatrix: Option<std::path::PathBuf>,
+        /// Skip comparing perplexity before and after
+        #[arg(long)]
+        no_perplexity: bool,
+    },
+
+    /// Measure a GGUF model's perplexity on a text corpus
+    Eval {
+        #[arg(short, long)]
+        model: std::path::PathBuf,
+        /// Plain-text corpus
+        #[arg(short, long)]
+        file: std::path::PathBuf,
+        /// Unquantized model to measure KL-divergence against
+        #[arg(long)]
+        reference: Option<std::path::PathBuf>,
+        /// Tokens per chunk; the second half of each is scored
+        #[arg(short, long, default_value = "512")]
+        context: usize,
+    },
+
+    /// Generate for every prompt in a JSONL file, writing results as JSONL
+    Run {
+        #[arg(short, long)]
+        model: std::path::PathBuf,
+        /// One JSON object per line: `prompt` or `messages`, plus optional
+        /// `id`, `system`, `temperature`, `top_p`, `top_k`, `repeat_penalty`,
+        /// `max_tokens`, `stop`, `json` and `raw`
+        #[arg(short, long)]
+        prompts: std::path::PathBuf,
+        #[arg(short, long)]
+        out: std::path::PathBuf,
+    },
+
+    /// Show a GGUF model's metadata, tensors, tokenizer and memory needs
+    Inspect { model: std::path::PathBuf },
+
+    /// Diagnose the environment: CPU, memory, limits and channel credentials
+    Doctor {
+        /// Model to size memory for (default: `[brain] model_path`)
+        #[arg(long)]
+        model: Option<std::path::PathBuf>,
+        /// Skip contacting channel APIs
+        #[arg(long)]
+        offline: bool,
+    },
+
+    /// Split text into a GGUF model's tokens (reads stdin without TEXT)
+    Tokenize {
+        #[arg(short, long)]
+        model: std::path::PathBuf,
+        text: Option<String>,
+        /// Prepend BOS, as the engine does for prompts
+        #[arg(long)]
+        bos: bool,
+        /// Print only the IDs, space-separated, for `bizclaw detokenize`
+        #[arg(long)]
+        ids: bool,
+    },
+
+    /// Turn token IDs back into text
+    Detokenize {
+        #[arg(short, long)]
+        model: std::path::PathBuf,
+        /// Token IDs, space- or comma-separated
+        #[arg(required = true, value_delimiter = ',', num_args = 1..)]
+        tokens: Vec<u32>,
+    },
+
+    /// Document retrieval (RAG) management
+    Docs {
+        #[command(subcommand)]
+        action: DocsAction,
+    },
+
     /// Configuration management
     Config {
         #[command(subcommand)]
         action: ConfigAction,
     },
 
+    /// Message analytics reports
+    Analytics {
+        #[command(subcommand)]
+        action: AnalyticsAction,
+    },
+
     /// Show system info
     Info,
 
     /// Quick interactive chat (alias for agent --interactive)
     Chat {
+        /// Chat with this GGUF model directly, without the agent
+        gguf: Option<std::path::PathBuf>,
+
         /// Override provider
         #[arg(short, long)]
         provider: Option<String>,
@@ -91,15 +215,19 @@ enum Commands {
         model: Option<String>,
     },
 
-    /// Start web dashboard + API server
+    /// Start the web dashboard, API server and configured channels
     Serve {
-        /// Port number
-        #[arg(short, long, default_value = "3000")]
-        port: u16,
+        /// Port number (default: `[gateway] port`)
+        #[arg(short, long)]
+        port: Option<u16>,
 
         /// Open browser automatically
         #[arg(long)]
         open: bool,
+
+        /// Write the process ID here while running
+        #[arg(long)]
+        pid_file: Option<std::path::PathBuf>,
     },
 
     /// Interactive setup wizard
@@ -134,6 +262,52 @@ enum BrainAction {
         #[arg(default_value = "Hello, who are you?")]
         prompt: String,
     },
+    /// Benchmark matmul kernel settings for this CPU and cache the fastest
+    Tune {
+        /// Tune again even if this CPU has a cached result
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DocsAction {
+    /// Chunk, embed and index documents (txt, md, json, pdf)
+    Add {
+        /// Files to ingest
+        #[arg(required = true)]
+        paths: Vec<std::path::PathBuf>,
+    },
+    /// Show the chunks retrieved for a query
+    Search {
+        query: String,
+        /// Number of chunks
+        #[arg(short, long, default_value = "3")]
+        k: usize,
+    },
+    /// List indexed documents
+    List,
+    /// Remove a document by id
+    Remove { id: i64 },
+}
+
+#[derive(Subcommand)]
+enum AnalyticsAction {
+    /// Messages, users, latency, tokens, tools and feedback per day
+    Report {
+        /// One row per week (starting Monday) instead of per day
+        #[arg(long)]
+        weekly: bool,
+        /// Days to cover, up to today
+        #[arg(short, long, default_value = "30")]
+        days: u32,
+        /// Split rows by channel, persona or model
+        #[arg(long)]
+        by: Option<String>,
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -150,19 +324,6 @@ enum ConfigAction {
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    // Initialize logging
-    let filter = if cli.verbose {
-        "bizclaw=debug,bizclaw_core=debug,bizclaw_agent=debug"
-    } else {
-        "bizclaw=info"
-    };
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(filter)),
-        )
-        .with_target(false)
-        .init();
-
     // Load config
     let mut config = if let Some(path) = &cli.config {
         bizclaw_core::BizClawConfig::load_from(std::path::Path::new(path))?
@@ -170,6 +331,14 @@ async fn main() -> Result<()> {
         bizclaw_core::BizClawConfig::load()?
     };
 
+    // Initialize logging
+    bizclaw_core::logging::init(&config.logging, cli.verbose)?;
+    let config_path = cli
+        .config
+        .clone()
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(bizclaw_core::BizClawConfig::config_path);
+
     match cli.command {
         Commands::Agent {
             message,
@@ -243,24 +412,28 @@ async fn main() -> Result<()> {
             match action {
                 ChannelAction::Start { channel } => {
                     println!("🦀 BizClaw Channel Listener");
-                    if let Some(ch) = channel {
+                    if let Some(ch) = &channel {
                         println!("Starting channel: {ch}");
                     } else {
                         println!("Starting all configured channels...");
                     }
 
-                    // Start configured channels
-                    if let Some(zalo_config) = &config.channel.zalo
-                        && zalo_config.enabled {
-                            println!("  📱 Zalo ({}) channel starting...", zalo_config.mode);
-                            let mut zalo =
-                                bizclaw_channels::zalo::ZaloChannel::new(zalo_config.clone());
-                            use bizclaw_core::traits::Channel;
-                            zalo.connect().await?;
-                        }
+                    let Some(channels) = start_channels(
+                        &config,
+                        config_path,
+                        channel.as_deref(),
+                        event_bus(&config),
+                    )?
+                    else {
+                        println!("No enabled channels found in config.");
+                        return Ok(());
+                    };
 
                     println!("\nChannels are running. Press Ctrl+C to stop.");
-                    tokio::signal::ctrl_c().await?;
+                    bizclaw_core::daemon::notify_ready();
+                    bizclaw_core::daemon::shutdown_signal().await;
+                    bizclaw_core::daemon::notify_stopping();
+                    channels.stop().await;
                     println!("\n👋 Channels stopped.");
                 }
                 ChannelAction::List => {
@@ -453,9 +626,646 @@ async fn main() -> Result<()> {
                         }
                     }
                 }
+                BrainAction::Tune { force } => {
+                    let path = std::path::PathBuf::from(
+                        shellexpand::tilde(&config.brain.cache_dir).as_ref(),
+                    )
+                    .join("tuning.json");
+                    let cpu = bizclaw_brain::tuning::cpu_key();
+                    match bizclaw_brain::tuning::cached(&path) {
+                        Some(tuned) if !force => {
+                            println!("🧠 {cpu} is already tuned (--force to tune again)");
+                            println!(
+                                "   {} rows per tile, {} tasks per thread: {:.1} ms (defaults {:.1} ms)",
+                                tuned.tuning.tile_rows,
+                                tuned.tuning.tasks_per_thread,
+                                tuned.tuned_ms,
+                                tuned.default_ms
+                            );
+                        }
+                        _ => {
+                            println!("🧠 Tuning matmul kernels for {cpu}...");
+                            let tuned =
+                                tokio::task::spawn_blocking(bizclaw_brain::tuning::autotune)
+                                    .await??;
+                            bizclaw_brain::tuning::save(&path, &tuned)?;
+                            let gain = (tuned.default_ms / tuned.tuned_ms - 1.0) * 100.0;
+                            println!(
+                                "   {} rows per tile, {} tasks per thread: {:.1} ms, {gain:.0}% faster than the defaults ({:.1} ms)",
+                                tuned.tuning.tile_rows,
+                                tuned.tuning.tasks_per_thread,
+                                tuned.tuned_ms,
+                                tuned.default_ms
+                            );
+                            println!("   Saved to {}", path.display());
+                        }
+                    }
+                }
+            }
+        }
+
+        Commands::Pull { models } => {
+            use bizclaw_providers::hub::{HubClient, ModelRef, ModelRegistry};
+            use std::io::Write;
+
+            let dir = ModelRegistry::default_dir();
+            let mut registry = ModelRegistry::open(&dir)?;
+            let hub = HubClient::new();
+            let mb = |bytes: u64| bytes as f64 / 1024.0 / 1024.0;
+            for spec in models {
+                let model = ModelRef::parse(&spec)?;
+                if let Some(entry) = registry.get(&model.name()) {
+                    println!("✅ Already pulled: {}", entry.path.display());
+                    continue;
+                }
+                println!("🧠 Pulling {} ({})", model.name(), model.revision);
+                let pulled = hub
+                    .pull(&model, &dir, |done, total| {
+                        let pct = (done * 100).checked_div(total).unwrap_or(0).min(100);
+                        let filled = pct as usize * 30 / 100;
+                        print!(
+                            "\r   [{}{}] {:.1} / {:.1} MB ({pct}%)",
+                            "█".repeat(filled),
+                            "░".repeat(30 - filled),
+                            mb(done),
+                            mb(total)
+                        );
+                        std::io::stdout().flush().ok();
+                    })
+                    .await;
+                match pulled {
+                    Ok(entry) => {
+                        println!("\n✅ {} (sha256 {})", entry.path.display(), entry.sha256);
+                        registry.insert(entry);
+                        registry.save()?;
+                    }
+                    Err(e) => println!("\n❌ {}: {e}", model.name()),
+                }
+            }
+        }
+
+        Commands::Bench {
+            model,
+            mut threads,
+            prompt,
+            gen_tokens,
+        } => {
+            if threads.is_empty() {
+                let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+                threads = std::iter::successors(Some(1), |&n| Some(n * 2))
+                    .take_while(|&n| n < cores)
+                    .chain([cores])
+                    .collect();
+            }
+
+            let mut engine = bizclaw_brain::BrainEngine::load(&model)?;
+            println!(
+                "🧠 Benchmarking {}",
+                engine.model_info().unwrap_or_default()
+            );
+            println!("   Generating {gen_tokens} tokens per run\n");
+            // Pages the weights in so the first run isn't slower.
+            engine.bench(threads[0], 8, 4)?;
+
+            println!(
+                "   {:>7}  {:>7}  {:>12}  {:>12}",
+                "threads", "prompt", "prefill t/s", "decode t/s"
+            );
+            for &n in &threads {
+                for &prompt_tokens in &prompt {
+                    match engine.bench(n, prompt_tokens, gen_tokens) {
+                        Ok(r) => println!(
+                            "   {:>7}  {:>7}  {:>12.1}  {:>12.1}",
+                            r.threads,
+                            r.prompt_tokens,
+                            r.prefill_tps(),
+                            r.decode_tps()
+                        ),
+                        Err(e) => println!("   {n:>7}  {prompt_tokens:>7}  ❌ {e}"),
+                    }
+                }
+            }
+        }
+
+        Commands::Quantize {
+            input,
+            output,
+            ggml_type,
+            imatrix,
+            no_perplexity,
+        } => {
+            use bizclaw_brain::quantize;
+
+            let target = quantize::parse_type(&ggml_type)?;
+            let imatrix = match imatrix {
+                Some(path) if !target.uses_importance() => anyhow::bail!(
+                    "{} doesn't use an importance matrix ({}); only k-quants do",
+                    target.name(),
+                    path.display()
+                ),
+                Some(path) => Some(quantize::ImportanceMatrix::load(&path)?),
+                None => None,
+            };
+            let mb = |bytes: u64| bytes as f64 / 1024.0 / 1024.0;
+
+            println!(
+                "🧠 Quantizing {} → {} ({})",
+                input.display(),
+                output.display(),
+                target.name()
+            );
+            if let Some(imatrix) = &imatrix {
+                println!("   Importance matrix covers {} tensor(s)", imatrix.len());
+            }
+            println!();
+            let summary =
+                quantize::quantize_file(&input, &output, target, imatrix.as_ref(), |t| {
+                    let dims: Vec<String> = t.dims.iter().map(u64::to_string).collect();
+                    println!(
+                        "   [{:>4}/{}] {:<40} {:>14}  {:?} → {:?}  {:.1} MB",
+                        t.index,
+                        t.count,
+                        t.name,
+                        dims.join("x"),
+                        t.from,
+                        t.to,
+                        mb(t.bytes)
+                    );
+                })?;
+
+            println!(
+                "\n✅ {} of {} tensors converted",
+                summary.converted, summary.tensors
+            );
+            println!(
+                "   Size: {:.1} MB → {:.1} MB ({:.0}%)",
+                mb(summary.input_bytes),
+                mb(summary.output_bytes),
+                summary.output_bytes as f64 * 100.0 / summary.input_bytes.max(1) as f64
+            );
+
+            if !no_perplexity {
+                const SAMPLE: &str = "The shop opens at eight in the morning and closes at \
+                    nine in the evening. Customers can order online, pay by card or cash, \
+                    and pick up their orders at the counter or have them delivered.";
+                let mut ppl = Vec::new();
+                for path in [&input, &output] {
+                    let mut engine = bizclaw_brain::BrainEngine::load(path)?;
+                    ppl.push(engine.perplexity(SAMPLE)?);
+                }
+                println!("   Perplexity: {:.2} → {:.2}", ppl[0], ppl[1]);
+            }
+        }
+
+        Commands::Eval {
+            model,
+            file,
+            reference,
+            context,
+        } => {
+            let text = std::fs::read_to_string(&file)?;
+            let mut engine = bizclaw_brain::BrainEngine::load(&model)?;
+            println!("🧠 Evaluating {}", engine.model_info().unwrap_or_default());
+            let mut reference = reference
+                .map(|path| bizclaw_brain::BrainEngine::load(&path))
+                .transpose()?;
+            if let Some(reference) = &reference {
+                println!(
+                    "   Reference: {}",
+                    reference.model_info().unwrap_or_default()
+                );
+            }
+            println!("   Corpus: {} ({} chars)\n", file.display(), text.len());
+
+            let progress = |s: &bizclaw_brain::eval::EvalSummary, chunks: usize| match s.mean_kl() {
+                Some(kl) => println!(
+                    "   [{}/{chunks}] ppl {:.4} | KL {kl:.6}",
+                    s.chunks,
+                    s.perplexity()
+                ),
+                None => println!("   [{}/{chunks}] ppl {:.4}", s.chunks, s.perplexity()),
+            };
+            let summary = engine.evaluate(reference.as_mut(), &text, context, progress)?;
+
+            println!(
+                "\n📊 Perplexity: {:.4} over {} tokens in {} chunks",
+                summary.perplexity(),
+                summary.tokens,
+                summary.chunks
+            );
+            if let (Some(kl), Some(top1)) = (summary.mean_kl(), summary.top1_agreement()) {
+                println!("   Mean KL-divergence: {kl:.6}");
+                println!("   Same top token:     {:.2}%", top1 * 100.0);
+            }
+        }
+
+        Commands::Run {
+            model,
+            prompts,
+            out,
+        } => run_batch(&config, &model, &prompts, &out)?,
+
+        Commands::Inspect { model } => {
+            use bizclaw_brain::inspect;
+
+            let gguf = inspect::read_header(&model)?;
+            let params = bizclaw_brain::model::ModelParams::from_gguf(&gguf);
+            let mb = |bytes: u64| bytes as f64 / 1024.0 / 1024.0;
+
+            println!("🧠 {}\n", model.display());
+            println!("   GGUF version:  {}", gguf.version);
+            println!(
+                "   Architecture:  {}",
+                gguf.architecture().unwrap_or("unknown")
+            );
+            println!("   Name:          {}", gguf.model_name().unwrap_or("-"));
+            println!(
+                "   Layers: {} | Dim: {} | FFN: {} | Heads: {} (KV {}) | Context: {}",
+                params.n_layers,
+                params.dim,
+                params.hidden_dim,
+                params.n_heads,
+                params.n_kv_heads,
+                params.max_seq_len
+            );
+            if params.sliding_window > 0 {
+                println!("   Sliding window: {}", params.sliding_window);
+            }
+
+            println!("\n📋 Metadata ({} keys)", gguf.metadata.len());
+            let mut keys: Vec<&String> = gguf.metadata.keys().collect();
+            keys.sort();
+            for key in keys {
+                println!("   {key:<45} {}", gguf.metadata[key]);
+            }
+
+            println!("\n📦 Tensors ({})", gguf.tensors.len());
+            for tensor in &gguf.tensors {
+                let shape: Vec<String> = tensor.dims.iter().map(u64::to_string).collect();
+                println!(
+                    "   {:<40} {:>16}  {:<6} {:>9.2} MB",
+                    tensor.name,
+                    shape.join("x"),
+                    format!("{:?}", tensor.ggml_type),
+                    mb(tensor.size_bytes())
+                );
+            }
+
+            let tokenizer = inspect::TokenizerInfo::from_gguf(&gguf);
+            println!("\n🔤 Tokenizer");
+            let chat_template = if tokenizer.has_chat_template {
+                "yes"
+            } else {
+                "no"
+            };
+            println!(
+                "   Model: {} | Vocab: {} | Merges: {} | Chat template: {chat_template}",
+                tokenizer.model, tokenizer.vocab_size, tokenizer.merges
+            );
+            for (role, id, text) in &tokenizer.special {
+                println!("   {role}: {id} {text:?}");
+            }
+
+            println!("\n💾 Memory (weights + KV cache + dequantize scratch)");
+            let max = params.max_seq_len as u64;
+            let mut contexts: Vec<u64> = [512, 2048, 4096, 8192]
+                .into_iter()
+                .filter(|&c| c < max)
+                .collect();
+            contexts.push(max);
+            for context in contexts {
+                let m = inspect::estimate_memory(&gguf, context);
+                println!(
+                    "   ctx {:>6}: {:>8.1} MB  ({:.1} + {:.1} + {:.1})",
+                    m.context,
+                    mb(m.total()),
+                    mb(m.weights),
+                    mb(m.kv_cache),
+                    mb(m.scratch)
+                );
+            }
+        }
+
+        Commands::Doctor { model, offline } => {
+            use bizclaw_brain::system::{self, Limit};
+            let mb = |bytes: u64| bytes as f64 / 1024.0 / 1024.0;
+            let mut warnings = 0;
+
+            println!("🩺 BizClaw Doctor v{}\n", env!("CARGO_PKG_VERSION"));
+
+            let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+            println!("🖥️  CPU: {} with {cores} threads", std::env::consts::ARCH);
+            let detected = system::simd_features();
+            let compiled = system::compiled_features();
+            for (feature, present) in &detected {
+                let built = if compiled.contains(feature) {
+                    "compiled in"
+                } else {
+                    "not compiled in"
+                };
+                if *present {
+                    println!("   ✅ {feature:<8} {built}");
+                } else {
+                    println!("   ➖ {feature:<8} not supported");
+                }
+            }
+            println!(
+                "   Kernels: {} (picked at runtime)",
+                bizclaw_brain::simd::simd_level().name()
+            );
+
+            println!("\n🧵 Threads");
+            println!(
+                "   Inference pool: {}",
+                bizclaw_brain::thread_pool::num_threads()
+            );
+            if let Ok(n) = std::env::var("RAYON_NUM_THREADS") {
+                println!("   RAYON_NUM_THREADS={n}");
+            }
+            println!("   [brain] threads: {}", config.brain.threads);
+            if config.brain.threads as usize > cores {
+                warnings += 1;
+                println!("   ⚠️  More threads configured than the CPU has ({cores})");
+            }
+
+            println!("\n💾 Memory");
+            let memory = system::memory_info();
+            match &memory {
+                Some(m) => println!(
+                    "   Total: {:.0} MB | Available: {:.0} MB",
+                    mb(m.total),
+                    mb(m.available)
+                ),
+                None => println!("   ➖ Unknown on this platform"),
+            }
+            let model = model.unwrap_or_else(|| {
+                std::path::PathBuf::from(shellexpand::tilde(&config.brain.model_path).as_ref())
+            });
+            if !model.exists() {
+                println!("   ➖ No model at {}", model.display());
+            } else {
+                match bizclaw_brain::inspect::read_header(&model) {
+                    Ok(gguf) => {
+                        let needed = bizclaw_brain::inspect::estimate_memory(
+                            &gguf,
+                            config.brain.context_length as u64,
+                        );
+                        println!(
+                            "   Model {} at ctx {}: {:.0} MB",
+                            model.display(),
+                            needed.context,
+                            mb(needed.total())
+                        );
+                        if let Some(m) = &memory {
+                            if needed.total() > m.total {
+                                warnings += 1;
+                                println!("   ❌ The model doesn't fit in RAM");
+                            } else if needed.total() > m.available {
+                                warnings += 1;
+                                println!(
+                                    "   ⚠️  More than is available now; expect swapping \
+                                     or page-ins while it runs"
+                                );
+                            } else {
+                                println!("   ✅ Fits in available memory");
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        warnings += 1;
+                        println!("   ❌ {}: {e}", model.display());
+                    }
+                }
+            }
+
+            println!("\n📌 Limits");
+            let limit = |limit: Option<Limit>| match limit {
+                Some(Limit::Unlimited) => "unlimited".to_string(),
+                Some(Limit::Bytes(bytes)) => format!("{:.1} MB", mb(bytes)),
+                None => "unknown".to_string(),
+            };
+            println!("   Locked memory (mlock): {}", limit(system::mlock_limit()));
+            println!(
+                "   Address space (mmap):  {}",
+                limit(system::address_space_limit())
+            );
+            if let Some(count) = system::max_map_count() {
+                println!("   vm.max_map_count:      {count}");
+            }
+            if let Some(Limit::Bytes(bytes)) = system::address_space_limit()
+                && model.exists()
+                && std::fs::metadata(&model).is_ok_and(|m| m.len() > bytes)
+            {
+                warnings += 1;
+                println!("   ❌ The model is larger than the address space limit");
+            }
+
+            println!("\n📡 Channels");
+            if offline {
+                println!("   ➖ Skipped (--offline)");
+            } else {
+                let checks =
+                    bizclaw_channels::diagnostics::check_credentials(&config.channel).await;
+                if checks.is_empty() {
+                    println!("   ➖ No channels with credentials enabled");
+                }
+                for check in checks {
+                    match check.result {
+                        Ok(who) => println!("   ✅ {:<10} {who}", check.channel),
+                        Err(e) => {
+                            warnings += 1;
+                            println!("   ❌ {:<10} {e}", check.channel);
+                        }
+                    }
+                }
+            }
+
+            if warnings == 0 {
+                println!("\n✅ No problems found");
+            } else {
+                println!("\n⚠️  {warnings} problem(s) found");
+            }
+        }
+
+        Commands::Tokenize {
+            model,
+            text,
+            bos,
+            ids,
+        } => {
+            let tokenizer = load_tokenizer(&model)?;
+            let text = match text {
+                Some(text) => text,
+                None => {
+                    let mut text = String::new();
+                    std::io::Read::read_to_string(&mut std::io::stdin(), &mut text)?;
+                    text
+                }
+            };
+            let mut tokens = Vec::new();
+            if bos {
+                tokens.push(tokenizer.bos_id);
+            }
+            tokens.extend(tokenizer.encode(&text));
+
+            if ids {
+                let ids: Vec<String> = tokens.iter().map(u32::to_string).collect();
+                println!("{}", ids.join(" "));
+            } else {
+                for id in &tokens {
+                    println!("{id:>8}  {:?}", tokenizer.decode_token(*id));
+                }
+                let chars = text.chars().count();
+                println!(
+                    "\n📊 {} tokens, {chars} chars ({:.2} chars/token)",
+                    tokens.len(),
+                    chars as f64 / tokens.len().max(1) as f64
+                );
+            }
+        }
+
+        Commands::Detokenize { model, tokens } => {
+            let tokenizer = load_tokenizer(&model)?;
+            if let Some(id) = tokens
+                .iter()
+                .find(|&&id| id as usize >= tokenizer.vocab_size())
+            {
+                anyhow::bail!(
+                    "Token {id} is outside the vocabulary ({} tokens)",
+                    tokenizer.vocab_size()
+                );
+            }
+            for id in &tokens {
+                println!("{id:>8}  {:?}", tokenizer.decode_token(*id));
+            }
+            println!("\n{}", tokenizer.decode(&tokens));
+            println!("\n📊 {} tokens", tokens.len());
+        }
+
+        Commands::Docs { action } => {
+            let embedder: std::sync::Arc<dyn bizclaw_core::traits::Provider> =
+                bizclaw_providers::create_provider(&config)?.into();
+            let store = bizclaw_knowledge::rag::RagStore::open(
+                &bizclaw_knowledge::rag::RagStore::default_path(),
+                embedder,
+            )?
+            .with_chunk_size(config.rag.chunk_chars);
+            match action {
+                DocsAction::Add { paths } => {
+                    for path in paths {
+                        match store.ingest_file(&path).await {
+                            Ok(id) => println!("✅ [{id}] {}", path.display()),
+                            Err(e) => println!("❌ {}: {e}", path.display()),
+                        }
+                    }
+                    println!("\n📚 {} chunks indexed", store.len());
+                }
+                DocsAction::Search { query, k } => {
+                    let hits = store.retrieve(&query, k, 0.0).await?;
+                    if hits.is_empty() {
+                        println!("(no matches)");
+                    }
+                    for hit in hits {
+                        println!(
+                            "── {:.3}  {} #{}\n{}\n",
+                            hit.score,
+                            hit.chunk.doc_name,
+                            hit.chunk.chunk_idx,
+                            hit.chunk.content.trim()
+                        );
+                    }
+                }
+                DocsAction::List => {
+                    let docs = store.list_documents();
+                    if docs.is_empty() {
+                        println!("(no documents — add some with `bizclaw docs add <file>`)");
+                    }
+                    for (id, name, source, chunks) in docs {
+                        println!("  [{id}] {name} — {chunks} chunks ({source})");
+                    }
+                }
+                DocsAction::Remove { id } => {
+                    if store.remove_document(id)? {
+                        println!("🗑️  Removed document {id}");
+                    } else {
+                        println!("❌ No document with id {id}");
+                    }
+                }
             }
         }
 
+        Commands::Analytics { action } => match action {
+            AnalyticsAction::Report {
+                weekly,
+                days,
+                by,
+                json,
+            } => {
+                use bizclaw_memory::analytics::{
+                    self, AnalyticsGroup, AnalyticsStore, ReportPeriod,
+                };
+
+                let group = match by.as_deref() {
+                    Some(name) => Some(AnalyticsGroup::parse(name).ok_or_else(|| {
+                        anyhow::anyhow!("--by must be channel, persona or model")
+                    })?),
+                    None => None,
+                };
+                let period = if weekly {
+                    ReportPeriod::Week
+                } else {
+                    ReportPeriod::Day
+                };
+                let since = analytics::days_ago(days.max(1));
+                let store = AnalyticsStore::open(AnalyticsStore::default_path())?;
+                let rows = store.report(period, since, group)?;
+                let tools = store.tool_counts(since)?;
+                if json {
+                    let report = serde_json::json!({ "rows": rows, "tools": tools });
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                    return Ok(());
+                }
+                if rows.is_empty() {
+                    println!("(no messages in the last {days} days)");
+                    return Ok(());
+                }
+                println!(
+                    "{:<10} {:<12} {:>8} {:>6} {:>7} {:>8} {:>9} {:>7} {:>5} {:>5}",
+                    if weekly { "WEEK" } else { "DAY" },
+                    by.as_deref().unwrap_or("").to_uppercase(),
+                    "MESSAGES",
+                    "USERS",
+                    "FAILED",
+                    "AVG MS",
+                    "TOKENS",
+                    "TOOLS",
+                    "👍",
+                    "👎",
+                );
+                for row in &rows {
+                    println!(
+                        "{:<10} {:<12} {:>8} {:>6} {:>7} {:>8.0} {:>9} {:>7} {:>5} {:>5}",
+                        row.period,
+                        row.key,
+                        row.messages,
+                        row.users,
+                        row.failures,
+                        row.avg_latency_ms,
+                        row.prompt_tokens + row.completion_tokens,
+                        row.tool_calls,
+                        row.positive_feedback,
+                        row.negative_feedback,
+                    );
+                }
+                if !tools.is_empty() {
+                    println!("\nTools:");
+                    for (tool, calls) in tools {
+                        println!("  {tool:<24} {calls}");
+                    }
+                }
+            }
+        },
+
         Commands::Config { action } => match action {
             ConfigAction::Show => {
                 let content = toml::to_string_pretty(&config)?;
@@ -468,7 +1278,10 @@ async fn main() -> Result<()> {
             }
             ConfigAction::Set { key, value } => {
                 println!("Setting {key} = {value}");
-                println!("(Direct config editing — edit ~/.bizclaw/config.toml)");
+                println!(
+                    "(Direct config editing — edit {}, or set BIZCLAW__<SECTION>__<KEY>)",
+                    bizclaw_core::BizClawConfig::config_path().display()
+                );
             }
         },
 
@@ -481,7 +1294,7 @@ async fn main() -> Result<()> {
             );
             println!(
                 "   Config: {}",
-                bizclaw_core::BizClawConfig::default_path().display()
+                bizclaw_core::BizClawConfig::config_path().display()
             );
             println!("   Provider: {}", config.default_provider);
             println!("   Model: {}", config.default_model);
@@ -502,7 +1315,17 @@ async fn main() -> Result<()> {
             }
         }
 
-        Commands::Chat { provider, model } => {
+        Commands::Chat {
+            gguf: Some(path), ..
+        } => {
+            run_model_repl(&config, &path)?;
+        }
+
+        Commands::Chat {
+            gguf: None,
+            provider,
+            model,
+        } => {
             if let Some(p) = provider {
                 config.default_provider = p;
             }
@@ -563,11 +1386,20 @@ async fn main() -> Result<()> {
             println!("\n👋 Goodbye!");
         }
 
-        Commands::Serve { port, open } => {
+        Commands::Serve {
+            port,
+            open,
+            pid_file,
+        } => {
             println!("🦀 BizClaw v{} — Web Dashboard", env!("CARGO_PKG_VERSION"));
+            let _pid_file = pid_file
+                .map(bizclaw_core::daemon::PidFile::create)
+                .transpose()?;
 
             let mut gw_config = config.gateway.clone();
-            gw_config.port = port;
+            if let Some(port) = port {
+                gw_config.port = port;
+            }
 
             let url = format!("http://{}:{}", gw_config.host, gw_config.port);
             println!("   🌐 Dashboard: {url}");
@@ -584,100 +1416,22 @@ async fn main() -> Result<()> {
             println!("   │     URL: {}  │", url);
             println!("   └──────────────────────────────────────────────┘");
 
-            // Start configured channels in background
-            // ═══════════════════════════════════════════
-            let channel_config = config.channel.clone();
-            let agent_config = config.clone();
-
-            // Telegram channel
-            if let Some(tg_config) = &channel_config.telegram
-                && tg_config.enabled && !tg_config.bot_token.is_empty() {
-                    println!("   🤖 Telegram: starting bot...");
-                    let tg = bizclaw_channels::telegram::TelegramChannel::new(
-                        bizclaw_channels::telegram::TelegramConfig {
-                            bot_token: tg_config.bot_token.clone(),
-                            enabled: true,
-                            poll_interval: 1,
-                        },
-                    );
-                    let cfg_clone = agent_config.clone();
-                    tokio::spawn(async move {
-                        run_channel_loop("telegram", tg.start_polling(), cfg_clone).await;
-                    });
-                }
-
-            // Discord channel
-            if let Some(dc_config) = &channel_config.discord
-                && dc_config.enabled && !dc_config.bot_token.is_empty() {
-                    println!("   🎮 Discord: starting bot...");
-                    let dc = bizclaw_channels::discord::DiscordChannel::new(
-                        bizclaw_channels::discord::DiscordConfig {
-                            bot_token: dc_config.bot_token.clone(),
-                            enabled: true,
-                            intents: (1 << 0) | (1 << 9) | (1 << 12) | (1 << 15),
-                        },
-                    );
-                    let cfg_clone = agent_config.clone();
-                    tokio::spawn(async move {
-                        run_channel_loop("discord", dc.start_gateway(), cfg_clone).await;
-                    });
-                }
-
-            // Email channel
-            if let Some(ref email_cfg) = channel_config.email
-                && email_cfg.enabled && !email_cfg.email.is_empty() {
-                    println!("   📧 Email: starting listener ({})...", email_cfg.email);
-                    let em = bizclaw_channels::email::EmailChannel::new(
-                        bizclaw_channels::email::EmailConfig {
-                            imap_host: email_cfg.imap_host.clone(),
-                            imap_port: email_cfg.imap_port,
-                            smtp_host: email_cfg.smtp_host.clone(),
-                            smtp_port: email_cfg.smtp_port,
-                            email: email_cfg.email.clone(),
-                            password: email_cfg.password.clone(),
-                            ..Default::default()
-                        },
-                    );
-                    let cfg_clone = agent_config.clone();
-                    tokio::spawn(async move {
-                        run_channel_loop("email", em.start_polling(), cfg_clone).await;
-                    });
-                }
-
-            // Zalo channel (Personal mode — requires cookie)
-            if let Some(ref zalo_cfg) = channel_config.zalo
-                && zalo_cfg.enabled {
-                    let cookie_path = &zalo_cfg.personal.cookie_path;
-                    let expanded_path = if cookie_path.starts_with("~/") {
-                        std::env::var("HOME")
-                            .ok()
-                            .map(|h| std::path::PathBuf::from(h).join(&cookie_path[2..]))
-                            .unwrap_or_else(|| std::path::PathBuf::from(cookie_path))
-                    } else {
-                        std::path::PathBuf::from(cookie_path)
-                    };
-
-                    if expanded_path.exists() {
-                        println!("   💬 Zalo: starting ({} mode)...", zalo_cfg.mode);
-                        tracing::info!(
-                            "Zalo channel starting with cookie from: {}",
-                            expanded_path.display()
-                        );
-                        // Note: Zalo uses WebSocket-based listening which is handled inside the
-                        // ZaloChannel::connect(). For now, we log that it's ready.
-                        // Full polling requires zpw_enk encryption which is complex.
-                        // The channel will be activated when admin sends a
-                        // "start channel" command via the API.
-                    } else {
-                        println!("   💬 Zalo: skipped (no cookie at {})", cookie_path);
-                    }
-                }
+            // Channels start first, so a provider or model that fails to load
+            // stops the server before the gateway binds
+            let events = event_bus(&config);
+            println!(
+                "   🧠 Model: {} / {}",
+                config.default_provider, config.default_model
+            );
+            let channels = start_channels(&config, config_path.clone(), None, events.clone())?;
 
             // WhatsApp channel (webhook-based — no background task needed)
-            if let Some(ref wa_cfg) = channel_config.whatsapp
-                && wa_cfg.enabled && !wa_cfg.access_token.is_empty() {
-                    println!("   📱 WhatsApp: enabled (webhook at /api/v1/webhook/whatsapp)");
-                }
+            if let Some(wa_cfg) = &config.channel.whatsapp
+                && wa_cfg.enabled
+                && !wa_cfg.access_token.is_empty()
+            {
+                println!("   📱 WhatsApp: enabled (webhook at /api/v1/webhook/whatsapp)");
+            }
 
             println!();
 
@@ -685,7 +1439,12 @@ async fn main() -> Result<()> {
                 let _ = std::process::Command::new("open").arg(&url).spawn();
             }
 
-            bizclaw_gateway::start_server(&gw_config).await?;
+            // Tells systemd it's ready once bound; runs until SIGTERM or Ctrl+C
+            bizclaw_gateway::start_server_with(&gw_config, config, config_path, events).await?;
+            if let Some(channels) = channels {
+                channels.stop().await;
+            }
+            println!("\n👋 Server stopped.");
         }
 
         Commands::Init => {
@@ -862,114 +1621,585 @@ async fn run_init_wizard() -> Result<()> {
     Ok(())
 }
 
-/// Run a channel listener loop — receives messages, routes through Agent, sends replies.
-/// Works for any channel that produces a Stream<Item = IncomingMessage>.
-async fn run_channel_loop<S>(channel_name: &str, mut stream: S, config: bizclaw_core::BizClawConfig)
-where
-    S: futures::Stream<Item = bizclaw_core::types::IncomingMessage> + Unpin,
-{
-    use futures::StreamExt;
-
-    tracing::info!("📡 Channel '{channel_name}' listener started");
-
-    // Create a dedicated Agent for this channel
-    let mut agent = match bizclaw_agent::Agent::new(config.clone()) {
-        Ok(a) => {
-            tracing::info!(
-                "✅ Agent for channel '{channel_name}' initialized (provider={})",
-                a.provider_name()
-            );
-            a
-        }
-        Err(e) => {
-            tracing::error!("❌ Failed to create agent for channel '{channel_name}': {e}");
-            return;
-        }
-    };
+/// What `/save` writes and `/load` reads in the model REPL. The engine
+/// snapshot with the conversation's KV cache goes next to it, see
+/// [`kv_snapshot_path`].
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ChatSession {
+    sampler: bizclaw_brain::sampler::SamplerConfig,
+    max_tokens: u32,
+    messages: Vec<bizclaw_core::types::Message>,
+}
 
-    // Create channel sender for replies
-    // We need a way to send messages back. For now, use the provider-specific send.
-    let send_client = reqwest::Client::new();
-
-    while let Some(incoming) = stream.next().await {
-        tracing::info!(
-            "[{channel_name}] Message from {}: {}",
-            incoming
-                .sender_name
-                .as_deref()
-                .unwrap_or(&incoming.sender_id),
-            &incoming.content[..incoming.content.len().min(100)]
-        );
+/// Where `/save` puts the engine snapshot for the session file `path`, so
+/// `/load` can pick the conversation up without evaluating it again.
+fn kv_snapshot_path(path: &str) -> std::path::PathBuf {
+    format!("{path}.bcss").into()
+}
 
-        // Process through Agent Engine (tools + memory + providers)
-        match agent.process(&incoming.content).await {
-            Ok(response) => {
-                tracing::info!(
-                    "[{channel_name}] Response: {}...",
-                    &response[..response.len().min(80)]
-                );
+/// Chat with a GGUF model in the brain engine directly: the history is
+/// rendered with the model's chat template each turn and the reply is
+/// streamed as it is sampled.
+fn run_model_repl(config: &bizclaw_core::BizClawConfig, path: &std::path::Path) -> Result<()> {
+    use bizclaw_core::types::{Message, Role};
+    use std::io::{self, BufRead, Write};
 
-                // Send response back through the same channel
-                match channel_name {
-                    "telegram" => {
-                        // Use Telegram sendMessage API
-                        if let Some(ref tg_cfg) = config.channel.telegram {
-                            let url = format!(
-                                "https://api.telegram.org/bot{}/sendMessage",
-                                tg_cfg.bot_token
-                            );
-                            let body = serde_json::json!({
-                                "chat_id": incoming.thread_id,
-                                "text": &response,
-                                "parse_mode": "Markdown",
-                            });
-                            if let Err(e) = send_client.post(&url).json(&body).send().await {
-                                tracing::error!("[telegram] Send failed: {e}");
-                            }
-                        }
+    let mut engine = bizclaw_brain::BrainEngine::new(bizclaw_brain::BrainConfig {
+        threads: config.brain.threads,
+        // `/max_tokens` sets the limit per reply.
+        max_tokens: config.brain.context_length,
+        context_length: config.brain.context_length,
+        temperature: config.brain.temperature,
+        top_p: config.brain.top_p,
+        json_mode: false,
+        skip_unknown_tensors: config.brain.skip_unknown_tensors,
+        tuning_file: config.brain.tuning_file(),
+        kv_cache: bizclaw_brain::kv_cache::KvCacheType::from_name(&config.brain.kv_cache)
+            .unwrap_or_default(),
+        context_shift: config.brain.context_shift,
+        rope_scaling: bizclaw_brain::rope::RopeScaling::from_name(
+            &config.brain.rope_scaling,
+            config.brain.rope_scaling_factor,
+        ),
+        pin_threads: config.brain.pin_threads,
+        reserve_runtime_core: config.brain.reserve_runtime_core,
+        numa: config.brain.numa,
+        backend: bizclaw_brain::gpu::Backend::from_name(&config.brain.backend)
+            .unwrap_or_default(),
+        ..Default::default()
+    });
+    engine.load_model(path)?;
+    let template = bizclaw_providers::chat_template::ChatTemplate::resolve(
+        &config.brain.chat_template,
+        &path.to_string_lossy(),
+    );
+    let context = engine.context_size().unwrap_or(2048);
+    let mut max_tokens = config.brain.max_tokens;
+    let mut messages: Vec<Message> = Vec::new();
+
+    println!("🦀 BizClaw v{} — Model Chat", env!("CARGO_PKG_VERSION"));
+    println!("   Model: {}", engine.model_info().unwrap_or_default());
+    println!("   Template: {template:?} | Context: {context} tokens");
+    println!("   Type /help for commands, /quit to exit\n");
+
+    let stdin = io::stdin();
+    let mut line = String::new();
+    loop {
+        print!("You: ");
+        io::stdout().flush()?;
+        line.clear();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        let input = line.trim();
+        if input.is_empty() {
+            continue;
+        }
+
+        if let Some(command) = input.strip_prefix('/') {
+            let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+            let arg = arg.trim();
+            let mut sampler = engine.sampler_config().cloned().unwrap_or_default();
+            let parsed = match name {
+                "quit" | "exit" => break,
+                "help" => {
+                    println!("   /temp <t>         Temperature (0 = greedy)");
+                    println!("   /top_p <p>        Nucleus sampling threshold");
+                    println!("   /top_k <k>        Sample from the k most likely tokens");
+                    println!("   /repeat <p>       Repeat penalty (1 = off)");
+                    println!("   /max_tokens <n>   Tokens per reply");
+                    println!("   /system <text>    Set the system prompt (empty clears it)");
+                    println!("   /settings         Show the current settings");
+                    println!("   /clear            Forget the conversation");
+                    println!("   /save <file>      Save the session and its KV cache");
+                    println!("   /load <file>      Load a saved session, KV cache included");
+                    println!("   /quit             Exit\n");
+                    continue;
+                }
+                "temp" => arg.parse::<f32>().map(|v| sampler.temperature = v).is_ok(),
+                "top_p" => arg.parse::<f32>().map(|v| sampler.top_p = v).is_ok(),
+                "top_k" => arg.parse::<u32>().map(|v| sampler.top_k = v).is_ok(),
+                "repeat" => arg
+                    .parse::<f32>()
+                    .map(|v| sampler.repeat_penalty = v)
+                    .is_ok(),
+                "max_tokens" => arg.parse::<u32>().map(|v| max_tokens = v).is_ok(),
+                "settings" => true,
+                "system" => {
+                    messages.retain(|m| m.role != Role::System);
+                    if !arg.is_empty() {
+                        messages.insert(0, Message::system(arg));
                     }
-                    "discord" => {
-                        // Use Discord REST API to send message
-                        if let Some(ref dc_cfg) = config.channel.discord {
-                            let url = format!(
-                                "https://discord.com/api/v10/channels/{}/messages",
-                                incoming.thread_id
-                            );
-                            let body = serde_json::json!({ "content": &response });
-                            if let Err(e) = send_client
-                                .post(&url)
-                                .header("Authorization", format!("Bot {}", dc_cfg.bot_token))
-                                .json(&body)
-                                .send()
-                                .await
-                            {
-                                tracing::error!("[discord] Send failed: {e}");
-                            }
-                        }
+                    println!(
+                        "✅ System prompt {}.\n",
+                        if arg.is_empty() { "cleared" } else { "set" }
+                    );
+                    continue;
+                }
+                "clear" => {
+                    messages.retain(|m| m.role == Role::System);
+                    println!("🔄 Conversation cleared.\n");
+                    continue;
+                }
+                "save" if !arg.is_empty() => {
+                    let session = ChatSession {
+                        sampler,
+                        max_tokens,
+                        messages: messages.clone(),
+                    };
+                    std::fs::write(arg, serde_json::to_string_pretty(&session)?)?;
+                    println!("💾 Saved {} messages to {arg}", messages.len());
+                    let snapshot = kv_snapshot_path(arg);
+                    match engine.snapshot(&snapshot) {
+                        Ok(()) => println!("   KV cache saved to {}\n", snapshot.display()),
+                        Err(e) => println!("   KV cache not saved: {e}\n"),
                     }
-                    "email" => {
-                        // Reply via SMTP (if email channel has send capability)
-                        if let Some(ref _email_cfg) = config.channel.email {
-                            tracing::info!(
-                                "[email] Reply to {}: {}...",
-                                incoming.sender_id,
-                                &response[..response.len().min(60)]
+                    continue;
+                }
+                "load" if !arg.is_empty() => {
+                    match std::fs::read_to_string(arg)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|s| Ok(serde_json::from_str::<ChatSession>(&s)?))
+                    {
+                        Ok(session) => {
+                            // The KV cache, if saved, spares evaluating the
+                            // conversation again on the next reply.
+                            let snapshot = kv_snapshot_path(arg);
+                            let restored = snapshot.exists()
+                                && match engine.restore(&snapshot) {
+                                    Ok(()) => true,
+                                    Err(e) => {
+                                        println!("   KV cache not restored: {e}");
+                                        false
+                                    }
+                                };
+                            engine.set_sampler_config(session.sampler);
+                            max_tokens = session.max_tokens;
+                            messages = session.messages;
+                            println!(
+                                "📂 Loaded {} messages from {arg}{}\n",
+                                messages.len(),
+                                if restored { " with its KV cache" } else { "" }
                             );
-                            // Email replies are handled by the EmailChannel's send() method
-                            // The incoming.sender_id contains the From address
-                            // For now, log the reply — full SMTP send is in EmailChannel
                         }
+                        Err(e) => println!("❌ Could not load {arg}: {e}\n"),
                     }
-                    _ => {
-                        tracing::warn!("[{channel_name}] No send handler implemented");
-                    }
+                    continue;
+                }
+                _ => {
+                    println!("❓ Unknown command or missing argument. Type /help.\n");
+                    continue;
                 }
+            };
+            if !parsed {
+                println!("❌ Invalid value for /{name}: '{arg}'\n");
+                continue;
+            }
+            println!(
+                "⚙️  temp={} top_p={} top_k={} repeat={} max_tokens={max_tokens}\n",
+                sampler.temperature, sampler.top_p, sampler.top_k, sampler.repeat_penalty
+            );
+            engine.set_sampler_config(sampler);
+            continue;
+        }
+
+        messages.push(Message::user(input));
+        // Forget the oldest turns once the prompt and reply don't fit.
+        while engine.count_tokens(&template.render(&messages))? + max_tokens as usize > context {
+            match messages.iter().position(|m| m.role != Role::System) {
+                Some(i) if i + 1 < messages.len() => {
+                    messages.remove(i);
+                }
+                _ => break,
+            }
+        }
+
+        print!("Bot: ");
+        io::stdout().flush()?;
+        let params = bizclaw_brain::GenerationParams {
+            stop: template
+                .stop_sequences()
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            ..bizclaw_brain::GenerationParams::max_tokens(max_tokens)
+        };
+        let result = engine.generate_stream_with(&template.render(&messages), &params, |piece| {
+            print!("{piece}");
+            let _ = io::stdout().flush();
+            true
+        });
+        match result {
+            Ok(generation) => {
+                println!("\n");
+                messages.push(Message::assistant(generation.text.trim()));
             }
             Err(e) => {
-                tracing::error!("[{channel_name}] Agent error: {e}");
+                println!("\n❌ Error: {e}\n");
+                messages.pop();
             }
         }
     }
 
-    tracing::warn!("📡 Channel '{channel_name}' stream ended — channel may have disconnected");
+    println!("\n👋 Goodbye!");
+    Ok(())
+}
+
+/// One line of `bizclaw run --prompts`: a prompt or a conversation, with
+/// sampling settings overriding the configured ones.
+#[derive(serde::Deserialize)]
+struct BatchItem {
+    #[serde(default)]
+    id: serde_json::Value,
+    prompt: Option<String>,
+    system: Option<String>,
+    #[serde(default)]
+    messages: Vec<bizclaw_core::types::Message>,
+    /// Send `prompt` as it is, without the chat template.
+    #[serde(default)]
+    raw: bool,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    top_k: Option<u32>,
+    repeat_penalty: Option<f32>,
+    max_tokens: Option<u32>,
+    #[serde(default)]
+    stop: Vec<String>,
+    /// Token ID to bias added to its logit, e.g. `{"13": -100}`.
+    #[serde(default)]
+    logit_bias: std::collections::HashMap<u32, f32>,
+    /// Constrain the output to JSON.
+    #[serde(default)]
+    json: bool,
+}
+
+/// One line of `bizclaw run --out`.
+#[derive(serde::Serialize)]
+struct BatchResult {
+    id: serde_json::Value,
+    output: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json: Option<serde_json::Value>,
+    tokens: usize,
+    /// `stop` (end of turn or a stop sequence), `length` or `error`.
+    finish_reason: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    elapsed_ms: u128,
+}
+
+/// Run every prompt in `prompts` through the model at `path`, appending a
+/// result line to `out` as each finishes. Prompts run one after another:
+/// the engine decodes a single sequence at a time.
+fn run_batch(
+    config: &bizclaw_core::BizClawConfig,
+    path: &std::path::Path,
+    prompts: &std::path::Path,
+    out: &std::path::Path,
+) -> Result<()> {
+    use bizclaw_core::types::Message;
+    use std::io::Write;
+
+    let lines: Vec<String> = std::fs::read_to_string(prompts)?
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(str::to_string)
+        .collect();
+    let mut engine = bizclaw_brain::BrainEngine::new(bizclaw_brain::BrainConfig {
+        threads: config.brain.threads,
+        max_tokens: config.brain.max_tokens,
+        context_length: config.brain.context_length,
+        temperature: config.brain.temperature,
+        top_p: config.brain.top_p,
+        json_mode: false,
+        skip_unknown_tensors: config.brain.skip_unknown_tensors,
+        tuning_file: config.brain.tuning_file(),
+        kv_cache: bizclaw_brain::kv_cache::KvCacheType::from_name(&config.brain.kv_cache)
+            .unwrap_or_default(),
+        context_shift: config.brain.context_shift,
+        rope_scaling: bizclaw_brain::rope::RopeScaling::from_name(
+            &config.brain.rope_scaling,
+            config.brain.rope_scaling_factor,
+        ),
+        pin_threads: config.brain.pin_threads,
+        reserve_runtime_core: config.brain.reserve_runtime_core,
+        numa: config.brain.numa,
+        backend: bizclaw_brain::gpu::Backend::from_name(&config.brain.backend)
+            .unwrap_or_default(),
+        ..Default::default()
+    });
+    engine.load_model(path)?;
+    let template = bizclaw_providers::chat_template::ChatTemplate::resolve(
+        &config.brain.chat_template,
+        &path.to_string_lossy(),
+    );
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(out)?);
+
+    eprintln!(
+        "🧠 {} | Template: {template:?} | {} prompts",
+        engine.model_info().unwrap_or_default(),
+        lines.len()
+    );
+    let mut failed = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let started = std::time::Instant::now();
+        let mut result = BatchResult {
+            id: serde_json::json!(i + 1),
+            output: String::new(),
+            json: None,
+            tokens: 0,
+            finish_reason: "error",
+            error: None,
+            elapsed_ms: 0,
+        };
+        let item = match serde_json::from_str::<BatchItem>(line) {
+            Ok(item) => item,
+            Err(e) => {
+                result.error = Some(format!("Invalid line: {e}"));
+                failed += 1;
+                writeln!(writer, "{}", serde_json::to_string(&result)?)?;
+                writer.flush()?;
+                eprintln!("❌ [{}/{}] invalid line: {e}", i + 1, lines.len());
+                continue;
+            }
+        };
+        if !item.id.is_null() {
+            result.id = item.id.clone();
+        }
+
+        let mut messages = item.messages;
+        if let Some(system) = item.system {
+            messages.insert(0, Message::system(system));
+        }
+        if let Some(prompt) = &item.prompt {
+            messages.push(Message::user(prompt.as_str()));
+        }
+        let prompt = match (&item.prompt, item.raw) {
+            (Some(prompt), true) => prompt.clone(),
+            _ => template.render(&messages),
+        };
+        let mut stop: Vec<String> = template
+            .stop_sequences()
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        stop.extend(item.stop);
+        let params = bizclaw_brain::GenerationParams {
+            max_tokens: Some(item.max_tokens.unwrap_or(config.brain.max_tokens)),
+            temperature: item.temperature,
+            top_p: item.top_p,
+            top_k: item.top_k,
+            repeat_penalty: item.repeat_penalty,
+            seed: None,
+            stop,
+            logit_bias: item.logit_bias,
+            json: item.json,
+            keep_tokens: 0,
+            slot: 0,
+        };
+
+        let generated = if messages.is_empty() {
+            Err(anyhow::anyhow!("Needs a prompt or messages"))
+        } else if item.json {
+            engine
+                .generate_with(&prompt, &params)
+                .map_err(anyhow::Error::from)
+                .and_then(|generation| {
+                    let value: serde_json::Value = serde_json::from_str(generation.text.trim())
+                        .map_err(|e| anyhow::anyhow!("Model produced invalid JSON: {e}"))?;
+                    result.output = value.to_string();
+                    result.json = Some(value);
+                    Ok(generation)
+                })
+        } else {
+            engine
+                .generate_with(&prompt, &params)
+                .inspect(|generation| result.output = generation.text.trim().to_string())
+                .map_err(anyhow::Error::from)
+        };
+        match generated {
+            Ok(generation) => {
+                result.tokens = generation.generated_tokens;
+                result.finish_reason = match generation.finish_reason {
+                    bizclaw_brain::FinishReason::Length => "length",
+                    _ => "stop",
+                };
+            }
+            Err(e) => {
+                failed += 1;
+                result.error = Some(e.to_string());
+            }
+        }
+        result.elapsed_ms = started.elapsed().as_millis();
+        writeln!(writer, "{}", serde_json::to_string(&result)?)?;
+        writer.flush()?;
+
+        let mark = if result.error.is_some() { "❌" } else { "✅" };
+        eprintln!(
+            "{mark} [{}/{}] {} ({} tokens, {:.1}s)",
+            i + 1,
+            lines.len(),
+            result.id,
+            result.tokens,
+            result.elapsed_ms as f64 / 1000.0
+        );
+    }
+
+    eprintln!(
+        "\n📊 {} done, {failed} failed → {}",
+        lines.len() - failed,
+        out.display()
+    );
+    Ok(())
+}
+
+/// A GGUF model's tokenizer, read from its header without the weights.
+fn load_tokenizer(path: &std::path::Path) -> Result<bizclaw_brain::tokenizer::BpeTokenizer> {
+    let gguf = bizclaw_brain::inspect::read_header(path)?;
+    Ok(bizclaw_brain::tokenizer::BpeTokenizer::from_gguf(
+        &gguf.metadata,
+    )?)
+}
+
+/// Configured channels running under the channel manager, answered by a
+/// [`ChannelAgent`](bizclaw_agent::channel_agent::ChannelAgent).
+struct RunningChannels {
+    manager: bizclaw_channels::manager::ChannelManager,
+    jobs: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl RunningChannels {
+    async fn stop(mut self) {
+        if let Some(task) = self.jobs {
+            task.abort();
+        }
+        self.manager.shutdown().await;
+    }
+}
+
+/// The event bus channels, the agent and the gateway report on, with the
+/// audit log and metrics subscribed.
+fn event_bus(config: &bizclaw_core::BizClawConfig) -> bizclaw_core::EventBus {
+    let events = bizclaw_core::EventBus::new();
+    if config.events.audit_log {
+        bizclaw_core::events::EventLog::new(bizclaw_core::events::EventLog::default_path())
+            .spawn(&events);
+    }
+    bizclaw_core::metrics::spawn_event_metrics(&events, bizclaw_core::metrics::global().clone());
+    bizclaw_core::metrics::start_from_config(&config.metrics);
+    bizclaw_core::telemetry::start_from_config(&config.telemetry);
+    events
+}
+
+/// Start the enabled channels (only the one named `only`, if given) with
+/// the agent, its config reloads from `config_path` and the scheduled jobs.
+/// None if no channel is enabled.
+fn start_channels(
+    config: &bizclaw_core::BizClawConfig,
+    config_path: std::path::PathBuf,
+    only: Option<&str>,
+    events: bizclaw_core::EventBus,
+) -> Result<Option<RunningChannels>> {
+    // Start configured channels under supervision (auto-reconnect)
+    let mut manager = bizclaw_channels::manager::ChannelManager::from_config(config);
+    if let Some(ch) = only {
+        for name in manager.channel_names() {
+            if name != ch {
+                manager.remove(&name);
+            }
+        }
+    }
+    if manager.channel_names().is_empty() {
+        return Ok(None);
+    }
+    for name in manager.channel_names() {
+        println!("  📡 {name}: starting...");
+    }
+
+    manager = manager.with_events(events.clone());
+
+    // One conversation per (channel, thread), answered by the configured provider
+    let agent = std::sync::Arc::new(
+        bizclaw_agent::channel_agent::ChannelAgent::from_config(config)?.with_events(events),
+    );
+    // Moderation may share the agent's provider for its classifier
+    use bizclaw_channels::pipeline::{self, ModerationClassifier, ProviderClassifier};
+    let classifier = config.moderation.classifier.then(|| {
+        std::sync::Arc::new(ProviderClassifier::from_config(agent.provider(), config))
+            as std::sync::Arc<dyn ModerationClassifier>
+    });
+    manager = manager.with_middleware(pipeline::from_config(config, classifier));
+    if config.plugins.enabled {
+        manager = manager.with_middleware(bizclaw_plugins::load_middleware(&config.plugins));
+    }
+    manager.start(agent.clone());
+
+    // Config edits reach the agent and the channels without a restart
+    let reloader = manager.reloader();
+    let mut reloads = bizclaw_core::reload::ConfigWatcher::new(config_path, config.clone())
+        .spawn()
+        .subscribe();
+    let reload_agent = agent.clone();
+    tokio::spawn(async move {
+        use tokio::sync::broadcast::error::RecvError;
+        loop {
+            match reloads.recv().await {
+                Ok(reload) if !reload.change.applied.is_empty() => {
+                    reload_agent.reload(&reload.config).await;
+                    reloader.reload(&reload.config);
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    // Scheduled jobs post into threads through the same agent and channels
+    let jobs = bizclaw_scheduler::jobs::jobs_from_config(&config.scheduler);
+    let jobs_task = (!jobs.is_empty()).then(|| {
+        use bizclaw_scheduler::JobTarget;
+        println!("  📅 {} scheduled job(s)", jobs.len());
+        let sender = manager.sender();
+        let generate = move |target: JobTarget, prompt: String| {
+            let agent = agent.clone();
+            async move {
+                agent
+                    .proactive(&target.channel, &target.thread_id, &prompt)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+        };
+        let deliver = move |target: JobTarget, text: String| {
+            let sender = sender.clone();
+            async move {
+                let message = bizclaw_core::types::OutgoingMessage::text(
+                    &target.thread_id,
+                    text,
+                    target.thread_type,
+                );
+                match sender.send(&target.channel, message).await {
+                    Ok(bizclaw_channels::manager::Delivery::Queued) => {
+                        tracing::info!(
+                            "📅 {} is unreachable; the job's message waits in the outbox",
+                            target.channel
+                        );
+                        Ok(())
+                    }
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+        };
+        tokio::spawn(bizclaw_scheduler::jobs::run_jobs(
+            jobs,
+            bizclaw_scheduler::jobs::utc_offset(&config.scheduler),
+            generate,
+            deliver,
+        ))
+    });
+
+    Ok(Some(RunningChannels {
+        manager,
+        jobs: jobs_task,
+    }))
 }